@@ -31,6 +31,10 @@ in {
   config = mkIf cfg.enable {
     environment.systemPackages = [cfg.package];
 
+    # Bus policy for the daemon's D-Bus interface. Privileged methods are
+    # additionally guarded by the polkit actions shipped with the package.
+    services.dbus.packages = [cfg.package];
+
     # This is necessary for the Watt CLI. The environment variable
     # passed to the systemd service will take priority in read order.
     environment.etc."watt.toml".source = cfgFile;
@@ -47,11 +51,14 @@ in {
       serviceConfig = {
         Environment = optional (cfg.settings != {}) ["WATT_CONFIG=${cfgFile}"];
         WorkingDirectory = "";
+        Type = "notify";
         ExecStart = "${getExe cfg.package} daemon --verbose";
         Restart = "on-failure";
+        WatchdogSec = "30s";
 
         RuntimeDirectory = "watt";
         RuntimeDirectoryMode = "0755";
+        StateDirectory = "watt";
       };
     };
 
@@ -1,6 +1,7 @@
 {
   lib,
   rustPlatform,
+  installShellFiles,
 }: let
   fs = lib.fileset;
 in
@@ -14,6 +15,7 @@ in
         (fs.fileFilter (file: builtins.any file.hasExt ["rs"]) ../src)
         ../Cargo.lock
         ../Cargo.toml
+        ../data
       ];
     };
 
@@ -21,6 +23,19 @@ in
     useFetchCargoVendor = true;
     enableParallelBuilding = true;
 
+    nativeBuildInputs = [installShellFiles];
+
+    postInstall = ''
+      install -Dm644 data/dbus/org.watt.Daemon.conf -t $out/share/dbus-1/system.d
+      install -Dm644 data/polkit/org.watt.policy -t $out/share/polkit-1/actions
+      install -Dm644 data/polkit/50-watt.rules -t $out/share/polkit-1/rules.d
+
+      installShellCompletion --cmd watt \
+        --bash <($out/bin/watt completions bash) \
+        --zsh <($out/bin/watt completions zsh) \
+        --fish <($out/bin/watt completions fish)
+    '';
+
     meta = {
       description = "Automatic CPU speed & power optimizer for Linux";
       longDescription = ''
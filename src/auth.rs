@@ -0,0 +1,243 @@
+// Polkit authorization for control requests from unprivileged users
+use log::debug;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use zbus::zvariant::Value;
+
+/// Privileged operations, each guarded by its own polkit action
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    /// Forcing a profile or overriding settings for a while, or pausing and
+    /// resuming the daemon
+    SetProfile,
+    /// Overriding turbo
+    SetTurbo,
+    /// Changing battery charge thresholds
+    ManageThresholds,
+    /// Reloading the configuration or changing the log level
+    Reload,
+}
+
+impl Action {
+    /// The polkit action id, as defined in `data/polkit/org.watt.policy`
+    pub const fn id(self) -> &'static str {
+        match self {
+            Self::SetProfile => "org.watt.set-profile",
+            Self::SetTurbo => "org.watt.set-turbo",
+            Self::ManageThresholds => "org.watt.manage-thresholds",
+            Self::Reload => "org.watt.reload",
+        }
+    }
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.id())
+    }
+}
+
+/// Credentials of a process talking to the daemon
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Caller {
+    pub pid: u32,
+    pub uid: u32,
+}
+
+/// Decides whether a caller may perform an action. Implemented by [`Polkit`],
+/// and replaceable wherever requests are handled so that code can be exercised
+/// without a running polkit.
+pub trait Authority: Send + Sync {
+    /// Whether `caller` may perform `action`. An error means the question
+    /// could not be answered at all.
+    fn is_authorized(&self, caller: &Caller, action: Action) -> Result<bool, String>;
+}
+
+/// How long a check may take, including the user answering an
+/// authentication prompt. The connection asking holds one of the few IPC
+/// slots until then.
+const CHECK_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Asks polkit over the system bus, letting an authentication agent prompt
+/// the user if the action's policy requires it
+#[derive(Default)]
+pub struct Polkit {
+    connection: OnceLock<zbus::blocking::Connection>,
+}
+
+impl Polkit {
+    fn connection(&self) -> zbus::Result<&zbus::blocking::Connection> {
+        if let Some(connection) = self.connection.get() {
+            return Ok(connection);
+        }
+        let connection = zbus::blocking::connection::Builder::system()?
+            .method_timeout(CHECK_TIMEOUT)
+            .build()?;
+        Ok(self.connection.get_or_init(|| connection))
+    }
+}
+
+impl Authority for Polkit {
+    fn is_authorized(&self, caller: &Caller, action: Action) -> Result<bool, String> {
+        let connection = self
+            .connection()
+            .map_err(|e| format!("System bus unavailable: {e}"))?;
+
+        // A start time of 0 makes polkit look it up, and passing the uid
+        // guards against the pid being reused in the meantime
+        let subject: (&str, HashMap<&str, Value<'_>>) = (
+            "unix-process",
+            HashMap::from([
+                ("pid", Value::from(caller.pid)),
+                ("start-time", Value::from(0u64)),
+                ("uid", Value::from(caller.uid as i32)),
+            ]),
+        );
+        let details: HashMap<&str, &str> = HashMap::new();
+        // AllowUserInteraction, so agents can prompt for authentication
+        let flags: u32 = 1;
+        // Names the check, so it can be cancelled once it runs out of time
+        static NEXT_CHECK: AtomicU64 = AtomicU64::new(0);
+        let cancellation_id = format!("watt-{}", NEXT_CHECK.fetch_add(1, Ordering::Relaxed));
+
+        let reply = connection
+            .call_method(
+                Some("org.freedesktop.PolicyKit1"),
+                "/org/freedesktop/PolicyKit1/Authority",
+                Some("org.freedesktop.PolicyKit1.Authority"),
+                "CheckAuthorization",
+                &(
+                    subject,
+                    action.id(),
+                    details,
+                    flags,
+                    cancellation_id.as_str(),
+                ),
+            )
+            .map_err(|e| {
+                if is_timeout(&e) {
+                    // Closes the prompt, if the agent still shows one
+                    let _ = connection.call_method(
+                        Some("org.freedesktop.PolicyKit1"),
+                        "/org/freedesktop/PolicyKit1/Authority",
+                        Some("org.freedesktop.PolicyKit1.Authority"),
+                        "CancelCheckAuthorization",
+                        &(cancellation_id.as_str(),),
+                    );
+                    return format!(
+                        "Authorization check timed out after {}s",
+                        CHECK_TIMEOUT.as_secs()
+                    );
+                }
+                format!("Authorization check failed: {e}")
+            })?;
+
+        let (authorized, _challenge, _details): (bool, bool, HashMap<String, String>) = reply
+            .body()
+            .deserialize()
+            .map_err(|e| format!("Invalid polkit reply: {e}"))?;
+
+        debug!(
+            "Polkit {} PID {} (UID {}) for {action}",
+            if authorized { "authorized" } else { "denied" },
+            caller.pid,
+            caller.uid
+        );
+        Ok(authorized)
+    }
+}
+
+fn is_timeout(error: &zbus::Error) -> bool {
+    matches!(error, zbus::Error::InputOutput(e) if e.kind() == std::io::ErrorKind::TimedOut)
+}
+
+/// Checks that `caller` may perform `action`, returning the reason if not.
+/// Root is always allowed.
+pub fn authorize(authority: &dyn Authority, caller: &Caller, action: Action) -> Result<(), String> {
+    if caller.uid == 0 {
+        return Ok(());
+    }
+
+    match authority.is_authorized(caller, action) {
+        Ok(true) => Ok(()),
+        Ok(false) => Err(format!("Not authorized to perform {action}")),
+        Err(e) => Err(format!("Could not check authorization for {action}: {e}")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    /// Answers from a fixed list of allowed actions, recording each question
+    struct FakeAuthority {
+        allowed: Result<Vec<Action>, String>,
+        asked: Mutex<Vec<(Caller, Action)>>,
+    }
+
+    impl FakeAuthority {
+        fn new(allowed: Result<Vec<Action>, String>) -> Self {
+            Self {
+                allowed,
+                asked: Mutex::new(Vec::new()),
+            }
+        }
+    }
+
+    impl Authority for FakeAuthority {
+        fn is_authorized(&self, caller: &Caller, action: Action) -> Result<bool, String> {
+            self.asked.lock().unwrap().push((*caller, action));
+            self.allowed
+                .as_ref()
+                .map(|allowed| allowed.contains(&action))
+                .map_err(Clone::clone)
+        }
+    }
+
+    const USER: Caller = Caller {
+        pid: 4242,
+        uid: 1000,
+    };
+
+    #[test]
+    fn allowed_actions_pass() {
+        let authority = FakeAuthority::new(Ok(vec![Action::SetProfile]));
+        assert_eq!(authorize(&authority, &USER, Action::SetProfile), Ok(()));
+        assert_eq!(
+            *authority.asked.lock().unwrap(),
+            [(USER, Action::SetProfile)]
+        );
+    }
+
+    #[test]
+    fn denied_actions_fail() {
+        let authority = FakeAuthority::new(Ok(vec![Action::SetProfile]));
+        assert_eq!(
+            authorize(&authority, &USER, Action::ManageThresholds),
+            Err("Not authorized to perform org.watt.manage-thresholds".to_string())
+        );
+    }
+
+    #[test]
+    fn failed_checks_deny() {
+        let authority = FakeAuthority::new(Err("System bus unavailable".to_string()));
+        assert_eq!(
+            authorize(&authority, &USER, Action::Reload),
+            Err(
+                "Could not check authorization for org.watt.reload: System bus unavailable"
+                    .to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn root_is_never_asked_about() {
+        let authority = FakeAuthority::new(Ok(Vec::new()));
+        let root = Caller { pid: 1, uid: 0 };
+        assert_eq!(authorize(&authority, &root, Action::SetTurbo), Ok(()));
+        assert!(authority.asked.lock().unwrap().is_empty());
+    }
+}
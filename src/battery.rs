@@ -101,7 +101,9 @@ pub fn set_battery_charge_thresholds(start_threshold: u8, stop_threshold: u8) ->
 }
 
 /// Finds all batteries in the system that support threshold control
-fn find_supported_batteries(power_supply_path: &Path) -> Result<Vec<SupportedBattery<'static>>> {
+pub fn find_supported_batteries(
+    power_supply_path: &Path,
+) -> Result<Vec<SupportedBattery<'static>>> {
     let entries = fs::read_dir(power_supply_path).map_err(|e| {
         if e.kind() == io::ErrorKind::PermissionDenied {
             ControlError::PermissionDenied(format!(
@@ -226,6 +228,37 @@ fn apply_thresholds_to_batteries(
     }
 }
 
+/// Batteries with charge threshold files and the scheme they follow, whether
+/// or not this process may write them
+pub fn threshold_schemes() -> Vec<(String, &'static str)> {
+    let Ok(entries) = fs::read_dir("/sys/class/power_supply") else {
+        return Vec::new();
+    };
+
+    let mut schemes: Vec<(String, &'static str)> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| is_battery(path).unwrap_or(false))
+        .filter_map(|path| {
+            let pattern = threshold_pattern(&path)?;
+            Some((
+                path.file_name()?.to_string_lossy().to_string(),
+                pattern.description,
+            ))
+        })
+        .collect();
+    schemes.sort();
+    schemes
+}
+
+/// The threshold scheme whose files the power supply at `path` has, whether
+/// or not this process may write them
+pub fn threshold_pattern(path: &Path) -> Option<&'static ThresholdPathPattern> {
+    THRESHOLD_PATTERNS.iter().find(|pattern| {
+        path.join(pattern.start_path).exists() && path.join(pattern.stop_path).exists()
+    })
+}
+
 /// Determines if a power supply entry is a battery
 fn is_battery(path: &Path) -> Result<bool> {
     let type_path = path.join("type");
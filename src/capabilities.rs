@@ -0,0 +1,176 @@
+//! What this machine supports, probed from sysfs
+//!
+//! Collected in one place so that everything validating values or describing
+//! the system sees the same picture, instead of probing sysfs on its own.
+
+use crate::battery;
+use crate::cpu;
+use crate::util::sysfs::read_sysfs_value;
+use serde::Serialize;
+use std::fs;
+use std::path::Path;
+
+const CPU_PATH: &str = "/sys/devices/system/cpu";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Capabilities {
+    /// cpufreq scaling driver, e.g. `intel_pstate` or `amd-pstate-epp`
+    pub driver: Option<String>,
+    /// Operating mode of `intel_pstate` or `amd_pstate`: active, passive or
+    /// guided
+    pub driver_mode: Option<String>,
+    pub policies: Vec<PolicyCapabilities>,
+    /// Whether the Energy Performance Bias can be set
+    pub epb: bool,
+    /// The file turbo is switched with, if it can be
+    pub turbo: Option<&'static str>,
+    /// ACPI platform profiles, empty if there are none
+    pub platform_profiles: Vec<String>,
+    /// Batteries whose charge thresholds can be set
+    pub charge_thresholds: Vec<ThresholdScheme>,
+    /// State of SMT control, e.g. on, off or notsupported
+    pub smt_control: Option<String>,
+    /// Number of idle states cpuidle offers
+    pub idle_states: usize,
+}
+
+/// What one cpufreq policy supports
+#[derive(Debug, Clone, Serialize)]
+pub struct PolicyCapabilities {
+    pub id: u32,
+    /// CPUs the policy covers, e.g. "0-3"
+    pub cpus: String,
+    pub governors: Vec<String>,
+    /// Energy Performance Preferences, empty if EPP is not supported
+    pub epp_values: Vec<String>,
+    /// Hardware frequency limits
+    pub min_freq_mhz: Option<u32>,
+    pub max_freq_mhz: Option<u32>,
+}
+
+/// A battery and the vendor scheme of its charge threshold files
+#[derive(Debug, Clone, Serialize)]
+pub struct ThresholdScheme {
+    pub battery: String,
+    pub scheme: &'static str,
+}
+
+impl Capabilities {
+    /// Probes sysfs. Whatever cannot be read counts as unsupported.
+    pub fn probe() -> Self {
+        Self {
+            driver: cpu::scaling_driver(),
+            driver_mode: ["intel_pstate", "amd_pstate"]
+                .iter()
+                .find_map(|driver| read_sysfs_value(format!("{CPU_PATH}/{driver}/status")).ok()),
+            policies: cpu::cpufreq_policies()
+                .iter()
+                .filter_map(|path| PolicyCapabilities::probe(path))
+                .collect(),
+            epb: Path::new(CPU_PATH)
+                .join("cpu0/power/energy_perf_bias")
+                .exists(),
+            turbo: cpu::turbo_mechanism(),
+            platform_profiles: cpu::get_platform_profiles().unwrap_or_default(),
+            charge_thresholds: battery::threshold_schemes()
+                .into_iter()
+                .map(|(battery, scheme)| ThresholdScheme { battery, scheme })
+                .collect(),
+            smt_control: read_sysfs_value(format!("{CPU_PATH}/smt/control")).ok(),
+            idle_states: fs::read_dir(format!("{CPU_PATH}/cpu0/cpuidle")).map_or(0, |entries| {
+                entries
+                    .flatten()
+                    .filter(|entry| entry.file_name().to_string_lossy().starts_with("state"))
+                    .count()
+            }),
+        }
+    }
+
+    /// Governors offered by any policy, in the order first seen
+    pub fn governors(&self) -> Vec<String> {
+        union(self.policies.iter().map(|policy| &policy.governors))
+    }
+
+    /// Energy Performance Preferences offered by any policy, in the order
+    /// first seen
+    pub fn epp_values(&self) -> Vec<String> {
+        union(self.policies.iter().map(|policy| &policy.epp_values))
+    }
+}
+
+fn union<'a>(lists: impl Iterator<Item = &'a Vec<String>>) -> Vec<String> {
+    let mut all: Vec<String> = Vec::new();
+    for value in lists.flatten() {
+        if !all.contains(value) {
+            all.push(value.clone());
+        }
+    }
+    all
+}
+
+impl PolicyCapabilities {
+    fn probe(path: &Path) -> Option<Self> {
+        let id = path
+            .file_name()?
+            .to_str()?
+            .strip_prefix("policy")?
+            .parse()
+            .ok()?;
+        let read_list = |file: &str| {
+            read_sysfs_value(path.join(file)).map_or_else(
+                |_| Vec::new(),
+                |value| value.split_whitespace().map(ToString::to_string).collect(),
+            )
+        };
+        let read_mhz = |file: &str| {
+            read_sysfs_value(path.join(file))
+                .ok()?
+                .parse::<u32>()
+                .ok()
+                .map(|khz| khz / 1000)
+        };
+
+        Some(Self {
+            id,
+            cpus: read_sysfs_value(path.join("related_cpus"))
+                .map(|cpus| compact_cpu_list(&cpus))
+                .unwrap_or_default(),
+            governors: read_list("scaling_available_governors"),
+            epp_values: read_list("energy_performance_available_preferences"),
+            min_freq_mhz: read_mhz("cpuinfo_min_freq"),
+            max_freq_mhz: read_mhz("cpuinfo_max_freq"),
+        })
+    }
+}
+
+/// Turns a space-separated CPU list such as "0 1 2 3 6" into "0-3,6"
+fn compact_cpu_list(cpus: &str) -> String {
+    let mut ids: Vec<u32> = cpus
+        .split_whitespace()
+        .filter_map(|id| id.parse().ok())
+        .collect();
+    ids.sort_unstable();
+    compact_ranges(&ids)
+}
+
+/// Writes sorted ids as ranges, e.g. "0-3,8,10-11"
+pub fn compact_ranges(ids: &[u32]) -> String {
+    let mut ranges: Vec<(u32, u32)> = Vec::new();
+    for &id in ids {
+        match ranges.last_mut() {
+            Some((_, end)) if *end + 1 == id => *end = id,
+            _ => ranges.push((id, id)),
+        }
+    }
+    ranges
+        .iter()
+        .map(|&(start, end)| {
+            if start == end {
+                start.to_string()
+            } else {
+                format!("{start}-{end}")
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
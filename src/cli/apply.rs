@@ -0,0 +1,161 @@
+use super::style;
+use crate::config::AppConfig;
+use crate::control::PowerController;
+use crate::core::ForcedProfile;
+use crate::cpu;
+use crate::engine::{self, KnobStates, TurboStates};
+use crate::ipc;
+use crate::monitor;
+use crate::overrides;
+use crate::pidfile;
+use crate::util::error::AppError;
+use crate::util::sysfs::{self, WriteOutcome, WriteRecord};
+use log::warn;
+use std::path::Path;
+
+/// How a one-off apply went
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ApplyOutcome {
+    /// Every setting was applied, or already in place
+    Success,
+    /// Some settings were applied, others failed
+    Partial,
+}
+
+/// Applies the profile for the current system state once and prints what was
+/// written, what was already in place and what failed, then a line with what
+/// happened to each knob
+///
+/// Nothing is started besides the apply itself: no config watching, control
+/// socket or D-Bus service. Returns an error if nothing could be applied.
+///
+/// A running daemon would apply its own profile again on its next cycle, so
+/// with `pause_daemon` it is paused for that long first.
+pub fn run_apply(
+    config: &AppConfig,
+    force_mode: Option<ForcedProfile>,
+    pause_daemon: Option<jiff::SignedDuration>,
+    controller: &dyn PowerController,
+) -> Result<ApplyOutcome, AppError> {
+    let daemon_pid = pidfile::running_daemon_pid(Path::new(&config.daemon.pid_file));
+    match (daemon_pid, pause_daemon) {
+        (Some(pid), Some(duration)) => {
+            let duration_sec = match u64::try_from(duration.as_secs()) {
+                Ok(secs) if secs > 0 => secs,
+                _ => {
+                    return Err(AppError::Usage(format!(
+                        "Pause duration must be at least a second, got {duration:#}"
+                    )));
+                }
+            };
+            ipc::send_command(&ipc::Request::Pause {
+                duration_sec: Some(duration_sec),
+            })
+            .map_err(|e| AppError::daemon("Failed to pause the daemon", &e))?;
+            println!("Paused the Watt daemon (PID {pid}) for {duration:#}");
+        }
+        (Some(pid), None) => warn!(
+            "A Watt daemon is running (PID {pid}) and may override these settings. Pass \
+             --pause-daemon 10m to pause it meanwhile"
+        ),
+        (None, Some(_)) => warn!("No Watt daemon is running, so there is nothing to pause"),
+        (None, None) => {}
+    }
+
+    let report = monitor::collect_system_report(config)?;
+    let force_mode = force_mode
+        .or_else(|| engine::load_profile(&report, config))
+        .or_else(|| engine::clamshell_profile(&report, config));
+
+    // Overrides from `watt override` hold until they expire, those limited to
+    // a power source only while on it
+    let mut config = config.clone();
+    overrides::apply(
+        &overrides::load(),
+        &mut config,
+        engine::is_on_ac_power(&report),
+    );
+    // A single apply cannot step a ramp, so a raised max frequency applies at
+    // once
+    for profile in [&mut config.charger, &mut config.battery]
+        .into_iter()
+        .chain(config.balanced.iter_mut())
+        .chain(config.profiles.values_mut())
+    {
+        profile.transition_ramp_sec = None;
+    }
+    let config = &config;
+
+    // Skips the CPU knobs, with a warning, when there is no cpufreq driver
+    let knobs = KnobStates::default();
+    knobs.set_cpu_control_unavailable(cpu::cpufreq_unavailable_reason());
+
+    let dry_run = controller.is_dry_run();
+    sysfs::record_writes();
+    let result = engine::determine_and_apply_settings(
+        &report,
+        config,
+        force_mode.as_ref(),
+        &TurboStates::default(),
+        &knobs,
+        controller,
+    );
+    let writes = sysfs::take_recorded_writes();
+
+    println!(
+        "Profile: {} ({}){}",
+        engine::selected_profile_name(&report, force_mode.as_ref()),
+        engine::selected_profile_reason(&report, force_mode.as_ref()),
+        if dry_run { ", dry run" } else { "" }
+    );
+
+    let counts = print_writes(&writes, dry_run);
+    let summary = knobs.last_summary();
+    println!("Summary: {summary}");
+    match result {
+        Ok(()) if counts.failed == 0 && summary.failed() == 0 => Ok(ApplyOutcome::Success),
+        Ok(()) => Ok(ApplyOutcome::Partial),
+        Err(e) if counts.written + counts.unchanged > 0 => {
+            println!("{}", style::error(format!("Stopped early: {e}")));
+            Ok(ApplyOutcome::Partial)
+        }
+        Err(e) => Err(AppError::Engine(e)),
+    }
+}
+
+/// How many writes of an apply went which way
+#[derive(Debug, Clone, Copy, Default)]
+pub struct WriteCounts {
+    /// Written, or in a dry run, to be written
+    pub written: usize,
+    pub unchanged: usize,
+    pub failed: usize,
+}
+
+/// Prints a line per write and a line with the totals
+pub fn print_writes(writes: &[WriteRecord], dry_run: bool) -> WriteCounts {
+    let mut counts = WriteCounts::default();
+    for write in writes {
+        match &write.outcome {
+            WriteOutcome::Written | WriteOutcome::WouldWrite => counts.written += 1,
+            WriteOutcome::Unchanged => counts.unchanged += 1,
+            WriteOutcome::Failed(_) => counts.failed += 1,
+        }
+    }
+    for line in super::write_table(writes) {
+        println!("{line}");
+    }
+    let failed_text = format!("{} failed", counts.failed);
+    println!(
+        "{} {}, {} unchanged, {}",
+        counts.written,
+        if dry_run { "to write" } else { "written" },
+        counts.unchanged,
+        if counts.failed > 0 {
+            style::error(failed_text)
+        } else {
+            failed_text
+        }
+    );
+    counts
+}
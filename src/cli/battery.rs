@@ -0,0 +1,356 @@
+use super::style;
+use crate::battery;
+use crate::config::AppConfig;
+use crate::monitor;
+use crate::util::error::AppError;
+use crate::util::sysfs;
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const POWER_SUPPLY_PATH: &str = "/sys/class/power_supply";
+
+/// Power supply types that can power the machine
+const AC_TYPES: &[&str] = &[
+    "Mains",
+    "USB",
+    "USB_PD",
+    "USB_PD_DRP",
+    "USB_DCP",
+    "USB_CDP",
+    "USB_ACA",
+    "USB_C",
+];
+
+/// Everything shown for one battery. Every field is optional, as drivers
+/// expose wildly different subsets of attributes.
+#[derive(Debug, Serialize)]
+pub struct BatteryDetail {
+    pub name: String,
+    pub manufacturer: Option<String>,
+    pub model: Option<String>,
+    pub technology: Option<String>,
+    /// "System" for batteries powering the machine, "Device" for peripherals
+    pub scope: Option<String>,
+    pub status: Option<String>,
+    pub capacity_percent: Option<u8>,
+    pub energy_now_wh: Option<f64>,
+    pub energy_full_wh: Option<f64>,
+    pub energy_full_design_wh: Option<f64>,
+    /// Full energy as a percentage of the design energy
+    pub health_percent: Option<f64>,
+    pub cycle_count: Option<u32>,
+    /// Positive while charging, negative while discharging, where the driver
+    /// reports the direction
+    pub power_w: Option<f64>,
+    pub voltage_v: Option<f64>,
+    pub temperature_celsius: Option<f64>,
+    /// Thresholds the configuration sets in the profile for the current power
+    /// source
+    pub configured_thresholds: Option<[u8; 2]>,
+    /// Thresholds the battery reports
+    pub effective_start_threshold: Option<u8>,
+    pub effective_stop_threshold: Option<u8>,
+    /// Selected charge behaviour, e.g. `auto` or `inhibit-charge`
+    pub charge_behaviour: Option<String>,
+    pub threshold_scheme: Option<&'static str>,
+}
+
+/// A power supply that can power the machine
+#[derive(Debug, Serialize)]
+pub struct AcSource {
+    pub name: String,
+    pub kind: Option<String>,
+    pub online: Option<bool>,
+    /// What the source supplies or negotiated, where the driver tells
+    pub power_w: Option<f64>,
+}
+
+#[derive(Debug, Serialize)]
+struct BatteryView {
+    batteries: Vec<BatteryDetail>,
+    ac_sources: Vec<AcSource>,
+}
+
+/// Prints every battery and AC source, or with `json` a JSON document
+///
+/// Power supplies in `ignored_power_supplies` are left out.
+pub fn run_battery(config: &AppConfig, json: bool) -> Result<(), AppError> {
+    let ignored = config.ignored_power_supplies.clone().unwrap_or_default();
+    let supplies: Vec<(String, PathBuf)> = fs::read_dir(POWER_SUPPLY_PATH)
+        .map(|entries| {
+            let mut supplies: Vec<(String, PathBuf)> = entries
+                .flatten()
+                .map(|entry| {
+                    (
+                        entry.file_name().to_string_lossy().into_owned(),
+                        entry.path(),
+                    )
+                })
+                .filter(|(name, _)| !ignored.contains(name))
+                .collect();
+            supplies.sort();
+            supplies
+        })
+        .unwrap_or_default();
+
+    let (_, ac_power) = monitor::get_power_info(config)?;
+    let on_ac = ac_power.online;
+    let profile = if on_ac {
+        &config.charger
+    } else {
+        &config.battery
+    };
+    let configured = profile
+        .battery_charge_thresholds
+        .as_ref()
+        .map(|thresholds| [thresholds.start, thresholds.stop]);
+
+    let kind = |path: &Path| read(path, "type");
+    let view = BatteryView {
+        batteries: supplies
+            .iter()
+            .filter(|(_, path)| kind(path).as_deref() == Some("Battery"))
+            .map(|(name, path)| battery_detail(name, path, configured))
+            .collect(),
+        ac_sources: supplies
+            .iter()
+            .filter(|(_, path)| kind(path).is_some_and(|kind| AC_TYPES.contains(&kind.as_str())))
+            .map(|(name, path)| ac_source(name, path))
+            .collect(),
+    };
+
+    if json {
+        return super::print_json(&view);
+    }
+    view.print();
+    Ok(())
+}
+
+fn read(path: &Path, file: &str) -> Option<String> {
+    sysfs::read_sysfs_value(path.join(file))
+        .ok()
+        .filter(|value| !value.is_empty())
+}
+
+fn read_number(path: &Path, file: &str) -> Option<f64> {
+    read(path, file)?.parse().ok()
+}
+
+/// Reads a value in micro-units, e.g. µWh or µV, as whole units
+fn read_micro(path: &Path, file: &str) -> Option<f64> {
+    read_number(path, file).map(|value| value / 1_000_000.0)
+}
+
+fn battery_detail(name: &str, path: &Path, configured: Option<[u8; 2]>) -> BatteryDetail {
+    // Some drivers report charge in µAh instead of energy, which converts
+    // with the design voltage
+    let design_voltage =
+        read_micro(path, "voltage_min_design").or_else(|| read_micro(path, "voltage_max_design"));
+    let energy = |kind: &str| {
+        read_micro(path, &format!("energy_{kind}"))
+            .or_else(|| Some(read_micro(path, &format!("charge_{kind}"))? * design_voltage?))
+    };
+    let energy_full = energy("full");
+    let energy_full_design = energy("full_design");
+
+    let voltage = read_micro(path, "voltage_now");
+    let status = read(path, "status");
+    let power = read_micro(path, "power_now")
+        .or_else(|| Some(read_micro(path, "current_now")? * voltage?))
+        .map(|power| {
+            // Most drivers report the magnitude only
+            if status.as_deref() == Some("Discharging") {
+                -power.abs()
+            } else {
+                power
+            }
+        });
+
+    let scheme = battery::threshold_pattern(path);
+    let threshold = |file: Option<&str>| read(path, file?)?.parse().ok();
+
+    BatteryDetail {
+        name: name.to_string(),
+        manufacturer: read(path, "manufacturer"),
+        model: read(path, "model_name"),
+        technology: read(path, "technology"),
+        scope: read(path, "scope"),
+        status,
+        capacity_percent: read(path, "capacity").and_then(|value| value.parse().ok()),
+        energy_now_wh: energy("now"),
+        energy_full_wh: energy_full,
+        energy_full_design_wh: energy_full_design,
+        health_percent: energy_full
+            .zip(energy_full_design)
+            .filter(|(_, design)| *design > 0.0)
+            .map(|(full, design)| full / design * 100.0),
+        cycle_count: read(path, "cycle_count")
+            .and_then(|value| value.parse().ok())
+            // Drivers without a counter report 0
+            .filter(|count| *count > 0),
+        power_w: power,
+        voltage_v: voltage,
+        temperature_celsius: read_number(path, "temp").map(|tenths| tenths / 10.0),
+        configured_thresholds: configured,
+        effective_start_threshold: threshold(scheme.map(|scheme| scheme.start_path)),
+        effective_stop_threshold: threshold(scheme.map(|scheme| scheme.stop_path)),
+        charge_behaviour: read(path, "charge_behaviour").map(|behaviours| selected(&behaviours)),
+        threshold_scheme: scheme.map(|scheme| scheme.description),
+    }
+}
+
+/// The bracketed entry of a choice list such as `[auto] inhibit-charge`, or
+/// the whole value if nothing is bracketed
+fn selected(choices: &str) -> String {
+    choices
+        .split_whitespace()
+        .find_map(|choice| choice.strip_prefix('[')?.strip_suffix(']'))
+        .unwrap_or(choices)
+        .to_string()
+}
+
+fn ac_source(name: &str, path: &Path) -> AcSource {
+    let power = read_micro(path, "power_now")
+        .or_else(|| Some(read_micro(path, "current_now")? * read_micro(path, "voltage_now")?))
+        .or_else(|| Some(read_micro(path, "current_max")? * read_micro(path, "voltage_max")?))
+        .filter(|power| *power > 0.0);
+
+    AcSource {
+        name: name.to_string(),
+        kind: read(path, "type"),
+        online: read(path, "online").map(|online| online != "0"),
+        power_w: power,
+    }
+}
+
+impl BatteryView {
+    fn print(&self) {
+        if self.batteries.is_empty() {
+            println!("{}", style::warn("No batteries found"));
+        }
+        for battery in &self.batteries {
+            battery.print();
+            println!();
+        }
+
+        if self.ac_sources.is_empty() {
+            println!("{}", style::warn("No AC sources found"));
+            return;
+        }
+        println!("{}", style::heading("AC Sources:"));
+        for source in &self.ac_sources {
+            println!(
+                "  {:<16}{:<12}{}{}",
+                source.name,
+                source.kind.as_deref().unwrap_or("-"),
+                match source.online {
+                    Some(true) => style::ok("online"),
+                    Some(false) => "offline".to_string(),
+                    None => style::warn("unknown"),
+                },
+                source
+                    .power_w
+                    .map_or_else(String::new, |power| format!(", {power:.1} W"))
+            );
+        }
+    }
+}
+
+impl BatteryDetail {
+    fn print(&self) {
+        let na = || style::warn("N/A");
+        let wh = |energy: Option<f64>| energy.map_or_else(na, |wh| format!("{wh:.1} Wh"));
+        let percent = |value: Option<u8>| value.map_or_else(na, |value| format!("{value}%"));
+
+        let identity: Vec<&str> = [&self.manufacturer, &self.model, &self.technology]
+            .into_iter()
+            .filter_map(Option::as_deref)
+            .collect();
+        println!(
+            "{}",
+            style::heading(format!(
+                "Battery {}{}",
+                self.name,
+                if identity.is_empty() {
+                    String::new()
+                } else {
+                    format!(" ({})", identity.join(" "))
+                }
+            ))
+        );
+        if let Some(scope) = &self.scope {
+            println!("  Scope:              {scope}");
+        }
+        println!(
+            "  Status:             {}, {}",
+            self.status
+                .clone()
+                .unwrap_or_else(|| style::warn("unknown")),
+            percent(self.capacity_percent)
+        );
+        println!(
+            "  Energy:             {} of {} (design {})",
+            wh(self.energy_now_wh),
+            wh(self.energy_full_wh),
+            wh(self.energy_full_design_wh)
+        );
+        println!(
+            "  Health:             {}",
+            self.health_percent
+                .map_or_else(na, |health| format!("{health:.0}%"))
+        );
+        println!(
+            "  Cycle Count:        {}",
+            self.cycle_count.map_or_else(na, |count| count.to_string())
+        );
+        println!(
+            "  Power:              {}",
+            self.power_w
+                .map_or_else(na, |power| format!("{power:.2} W"))
+        );
+        println!(
+            "  Voltage:            {}",
+            self.voltage_v
+                .map_or_else(na, |voltage| format!("{voltage:.2} V"))
+        );
+        println!(
+            "  Temperature:        {}",
+            self.temperature_celsius
+                .map_or_else(na, |temp| format!("{temp:.1}°C"))
+        );
+        println!(
+            "  Thresholds:         {} configured, {} effective",
+            self.configured_thresholds.map_or_else(
+                || "none".to_string(),
+                |[start, stop]| format!("{start}-{stop}%")
+            ),
+            match (
+                self.effective_start_threshold,
+                self.effective_stop_threshold
+            ) {
+                // Green where the battery does what the profile says, red where
+                // it does not
+                (Some(start), Some(stop)) => match self.configured_thresholds {
+                    Some(configured) if configured == [start, stop] => {
+                        style::ok(format!("{start}-{stop}%"))
+                    }
+                    Some(_) => style::error(format!("{start}-{stop}%")),
+                    None => format!("{start}-{stop}%"),
+                },
+                (None, Some(stop)) => format!("stop at {stop}%"),
+                (Some(start), None) => format!("start at {start}%"),
+                (None, None) => na(),
+            }
+        );
+        println!(
+            "  Threshold Scheme:   {}",
+            self.threshold_scheme
+                .map_or_else(|| style::warn("not supported"), ToString::to_string)
+        );
+        println!(
+            "  Charge Behaviour:   {}",
+            self.charge_behaviour.clone().unwrap_or_else(na)
+        );
+    }
+}
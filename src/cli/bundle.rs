@@ -0,0 +1,347 @@
+use crate::config::AppConfig;
+use crate::util::error::AppError;
+use flate2::Compression;
+use flate2::write::GzEncoder;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Directories whose files go into the bundle, with how deep to descend
+const SYSFS_TREES: &[(&str, usize)] = &[
+    ("/sys/devices/system/cpu/cpufreq", 2),
+    ("/sys/devices/system/cpu/intel_pstate", 1),
+    ("/sys/devices/system/cpu/amd_pstate", 1),
+    ("/sys/devices/system/cpu/smt", 1),
+    ("/sys/devices/system/cpu/cpuidle", 1),
+    ("/sys/class/power_supply", 2),
+];
+
+/// Single files that go into the bundle
+const SYSFS_FILES: &[&str] = &[
+    "/sys/firmware/acpi/platform_profile",
+    "/sys/firmware/acpi/platform_profile_choices",
+    "/sys/devices/system/cpu/online",
+    "/sys/devices/system/cpu/present",
+    "/proc/cpuinfo",
+    "/proc/version",
+];
+
+/// Files in each `cpuN` directory that go into the bundle
+const PER_CPU_FILES: &[&str] = &["power/energy_perf_bias", "topology/core_cpus_list"];
+
+/// Attributes left out because they identify the machine or its owner. Lines
+/// mentioning them, e.g. in `uevent` files, are left out as well.
+const IDENTIFYING: &[&str] = &["serial", "uuid", "asset_tag"];
+
+/// Largest amount read from a single file
+const MAX_FILE_SIZE: u64 = 256 * 1024;
+
+/// How long a single file or command may take
+const READ_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How long collecting everything may take, after which the rest is skipped
+const TOTAL_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Log lines included from the daemon's log
+const LOG_LINES: usize = 500;
+
+/// Files collected so far, by their path inside the bundle
+struct Bundle {
+    files: Vec<(PathBuf, Vec<u8>)>,
+    /// Files that could not be read, with the reason
+    missing: Vec<String>,
+    deadline: Instant,
+}
+
+/// Collects what is needed to reproduce a bug report into a tar.gz archive,
+/// or a directory with `as_dir`, and prints where it went
+///
+/// Every read is bounded in size and time, and anything that cannot be read
+/// is listed in `missing.txt` instead. Serial numbers and other identifying
+/// attributes are left out.
+pub fn run_debug_dump(
+    config: &AppConfig,
+    out: Option<&Path>,
+    as_dir: bool,
+) -> Result<(), AppError> {
+    let name = format!(
+        "watt-debug-{}",
+        jiff::Zoned::now().strftime("%Y%m%d-%H%M%S")
+    );
+    let out = out.map_or_else(
+        || {
+            PathBuf::from(if as_dir {
+                name.clone()
+            } else {
+                format!("{name}.tar.gz")
+            })
+        },
+        Path::to_path_buf,
+    );
+
+    let mut bundle = Bundle {
+        files: Vec::new(),
+        missing: Vec::new(),
+        deadline: Instant::now() + TOTAL_TIMEOUT,
+    };
+    bundle.add("version.txt", version_info().into_bytes());
+    bundle.add("config.toml", config_dump(config).into_bytes());
+    for (tree, depth) in SYSFS_TREES {
+        bundle.add_tree(Path::new(tree), *depth);
+    }
+    for file in SYSFS_FILES {
+        bundle.add_file(Path::new(file));
+    }
+    for cpu in cpu_dirs() {
+        for file in PER_CPU_FILES {
+            bundle.add_file(&cpu.join(file));
+        }
+    }
+    bundle.add_log(config);
+    let missing = bundle.missing.join("\n");
+    bundle.add("missing.txt", missing.into_bytes());
+
+    if as_dir {
+        bundle.write_dir(&out)?;
+    } else {
+        bundle.write_archive(&out, &name)?;
+    }
+    println!("{}", out.display());
+    Ok(())
+}
+
+impl Bundle {
+    fn add(&mut self, path: impl Into<PathBuf>, contents: Vec<u8>) {
+        self.files.push((path.into(), contents));
+    }
+
+    /// Adds a file under its own path, or records why it could not be read
+    fn add_file(&mut self, path: &Path) {
+        if Instant::now() > self.deadline {
+            self.missing.push(format!(
+                "{}: skipped, collecting took too long",
+                path.display()
+            ));
+            return;
+        }
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        if IDENTIFYING.iter().any(|word| name.contains(word)) {
+            return;
+        }
+
+        match read_bounded(path) {
+            Ok(contents) => {
+                let inside = path.strip_prefix("/").unwrap_or(path);
+                self.add(inside, sanitize(contents));
+            }
+            Err(e) => self.missing.push(format!("{}: {e}", path.display())),
+        }
+    }
+
+    /// Adds the regular files under `dir`, descending `depth` levels without
+    /// following links out of it, e.g. to the device a power supply belongs to
+    fn add_tree(&mut self, dir: &Path, depth: usize) {
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(e) => {
+                self.missing.push(format!("{}: {e}", dir.display()));
+                return;
+            }
+        };
+        let mut paths: Vec<PathBuf> = entries.flatten().map(|entry| entry.path()).collect();
+        paths.sort();
+
+        for path in paths {
+            let Ok(metadata) = fs::symlink_metadata(&path) else {
+                continue;
+            };
+            if metadata.is_file() {
+                self.add_file(&path);
+            } else if depth > 1 && (metadata.is_dir() || is_class_entry(&metadata, &path)) {
+                self.add_tree(&path, depth - 1);
+            }
+        }
+    }
+
+    /// Adds the last lines of the daemon's log, from its log file or the
+    /// journal
+    fn add_log(&mut self, config: &AppConfig) {
+        let log = match &config.daemon.log_file {
+            Some(path) => read_bounded_tail(Path::new(path)),
+            None => run_bounded(
+                Command::new("journalctl")
+                    .args(["--unit", "watt", "--no-pager", "--lines"])
+                    .arg(LOG_LINES.to_string()),
+            ),
+        };
+        match log {
+            Ok(log) => self.add("watt.log", log),
+            Err(e) => self.missing.push(format!("daemon log: {e}")),
+        }
+    }
+
+    fn write_dir(&self, out: &Path) -> Result<(), AppError> {
+        for (path, contents) in &self.files {
+            let path = out.join(path);
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(path, contents)?;
+        }
+        Ok(())
+    }
+
+    fn write_archive(&self, out: &Path, name: &str) -> Result<(), AppError> {
+        let encoder = GzEncoder::new(File::create(out)?, Compression::default());
+        let mut archive = tar::Builder::new(encoder);
+        let mtime = jiff::Timestamp::now().as_second().unsigned_abs();
+        for (path, contents) in &self.files {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(contents.len() as u64);
+            header.set_mode(0o644);
+            header.set_mtime(mtime);
+            archive.append_data(&mut header, Path::new(name).join(path), contents.as_slice())?;
+        }
+        archive.into_inner()?.finish()?;
+        Ok(())
+    }
+}
+
+/// Whether `path` is an entry of a `/sys/class` directory, which are links to
+/// the device directories
+fn is_class_entry(metadata: &fs::Metadata, path: &Path) -> bool {
+    metadata.file_type().is_symlink()
+        && path.parent().and_then(Path::parent) == Some(Path::new("/sys/class"))
+        && path.is_dir()
+}
+
+/// Drops the lines of `contents` that mention an identifying attribute
+fn sanitize(contents: Vec<u8>) -> Vec<u8> {
+    let Ok(text) = String::from_utf8(contents) else {
+        return b"(binary contents left out)".to_vec();
+    };
+    let mentions_identifying = |line: &str| {
+        let line = line.to_ascii_lowercase();
+        IDENTIFYING.iter().any(|word| line.contains(word))
+    };
+    if !text.lines().any(mentions_identifying) {
+        return text.into_bytes();
+    }
+    text.lines()
+        .filter(|line| !mentions_identifying(line))
+        .map(|line| format!("{line}\n"))
+        .collect::<String>()
+        .into_bytes()
+}
+
+/// Reads at most [`MAX_FILE_SIZE`] bytes of `path`, giving up after
+/// [`READ_TIMEOUT`], as some attributes make the driver talk to hardware
+fn read_bounded(path: &Path) -> io::Result<Vec<u8>> {
+    let path = path.to_path_buf();
+    with_timeout(move || {
+        let mut contents = Vec::new();
+        File::open(path)?
+            .take(MAX_FILE_SIZE)
+            .read_to_end(&mut contents)?;
+        Ok(contents)
+    })
+}
+
+/// Reads the last [`LOG_LINES`] lines of the file at `path`
+fn read_bounded_tail(path: &Path) -> io::Result<Vec<u8>> {
+    let path = path.to_path_buf();
+    with_timeout(move || {
+        let mut file = File::open(path)?;
+        let len = file.metadata()?.len();
+        // Log lines are rarely longer than this, and the first one may be cut
+        let start = len.saturating_sub(MAX_FILE_SIZE);
+        io::Seek::seek(&mut file, io::SeekFrom::Start(start))?;
+        let mut contents = String::new();
+        file.take(MAX_FILE_SIZE).read_to_string(&mut contents)?;
+        let mut lines: Vec<&str> = contents.lines().collect();
+        if start > 0 && !lines.is_empty() {
+            lines.remove(0);
+        }
+        let tail = &lines[lines.len().saturating_sub(LOG_LINES)..];
+        Ok(tail.join("\n").into_bytes())
+    })
+}
+
+/// Runs `command` and returns its output, killing it after [`READ_TIMEOUT`]
+fn run_bounded(command: &mut Command) -> io::Result<Vec<u8>> {
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()?;
+    let mut stdout = child.stdout.take().ok_or(io::ErrorKind::BrokenPipe)?;
+    let output = with_timeout(move || {
+        let mut output = Vec::new();
+        (&mut stdout).take(MAX_FILE_SIZE).read_to_end(&mut output)?;
+        Ok(output)
+    });
+    let _ = child.kill();
+    let _ = child.wait();
+
+    let output = output?;
+    if output.is_empty() {
+        return Err(io::Error::other("no output"));
+    }
+    Ok(output)
+}
+
+/// Runs `read` on its own thread, giving up on it after [`READ_TIMEOUT`]
+fn with_timeout(
+    read: impl FnOnce() -> io::Result<Vec<u8>> + Send + 'static,
+) -> io::Result<Vec<u8>> {
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let _ = sender.send(read());
+    });
+    receiver
+        .recv_timeout(READ_TIMEOUT)
+        .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "timed out"))?
+}
+
+/// `cpuN` directories, in no particular order
+fn cpu_dirs() -> Vec<PathBuf> {
+    fs::read_dir("/sys/devices/system/cpu").map_or_else(
+        |_| Vec::new(),
+        |entries| {
+            entries
+                .flatten()
+                .filter(|entry| {
+                    entry
+                        .file_name()
+                        .to_str()
+                        .and_then(|name| name.strip_prefix("cpu"))
+                        .is_some_and(|id| id.parse::<u32>().is_ok())
+                })
+                .map(|entry| entry.path())
+                .collect()
+        },
+    )
+}
+
+fn version_info() -> String {
+    format!(
+        "watt {}\ntarget: {}-{}\ncollected: {}\n",
+        env!("CARGO_PKG_VERSION"),
+        std::env::consts::ARCH,
+        std::env::consts::OS,
+        jiff::Timestamp::now()
+    )
+}
+
+fn config_dump(config: &AppConfig) -> String {
+    let header = "# Effective configuration, defaults included. Watt's configuration holds\n\
+                  # no secrets, so nothing was removed.\n\n";
+    match toml::to_string_pretty(config) {
+        Ok(config) => format!("{header}{config}"),
+        Err(e) => format!("{header}# Failed to serialize the configuration: {e}\n{config:#?}\n"),
+    }
+}
@@ -0,0 +1,91 @@
+use super::style;
+use crate::capabilities::Capabilities;
+use crate::util::error::AppError;
+
+/// Prints what this machine supports, as a table or with `json` as a JSON
+/// document
+pub fn run_capabilities(json: bool) -> Result<(), AppError> {
+    let capabilities = Capabilities::probe();
+    if json {
+        return super::print_json(&capabilities);
+    }
+
+    let list = |values: &[String]| {
+        if values.is_empty() {
+            style::warn("not supported")
+        } else {
+            values.join(" ")
+        }
+    };
+    let yes_no = |supported: bool| {
+        if supported {
+            style::ok("yes")
+        } else {
+            style::warn("no")
+        }
+    };
+    let unsupported = || style::warn("not supported");
+
+    println!(
+        "Driver:             {}{}",
+        capabilities
+            .driver
+            .clone()
+            .unwrap_or_else(|| style::warn("none")),
+        capabilities
+            .driver_mode
+            .as_ref()
+            .map_or_else(String::new, |mode| format!(" ({mode} mode)"))
+    );
+    println!(
+        "Turbo:              {}",
+        capabilities
+            .turbo
+            .map_or_else(unsupported, ToString::to_string)
+    );
+    println!("EPB:                {}", yes_no(capabilities.epb));
+    println!(
+        "Platform Profiles:  {}",
+        list(&capabilities.platform_profiles)
+    );
+    println!(
+        "Charge Thresholds:  {}",
+        if capabilities.charge_thresholds.is_empty() {
+            unsupported()
+        } else {
+            capabilities
+                .charge_thresholds
+                .iter()
+                .map(|battery| format!("{} ({})", battery.battery, battery.scheme))
+                .collect::<Vec<_>>()
+                .join(", ")
+        }
+    );
+    println!(
+        "SMT Control:        {}",
+        capabilities.smt_control.clone().unwrap_or_else(unsupported)
+    );
+    println!("Idle States:        {}", capabilities.idle_states);
+
+    if capabilities.policies.is_empty() {
+        println!(
+            "Policies:           {}",
+            style::warn("none, no cpufreq driver is loaded")
+        );
+        return Ok(());
+    }
+    for policy in &capabilities.policies {
+        println!();
+        println!(
+            "{}",
+            style::heading(format!("Policy {} (CPUs {})", policy.id, policy.cpus))
+        );
+        println!("  Governors:        {}", list(&policy.governors));
+        println!("  EPP:              {}", list(&policy.epp_values));
+        match (policy.min_freq_mhz, policy.max_freq_mhz) {
+            (Some(min), Some(max)) => println!("  Frequency:        {min}-{max} MHz"),
+            _ => println!("  Frequency:        {}", style::warn("unknown")),
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,172 @@
+use crate::capabilities::Capabilities;
+use crate::daemon;
+use crate::util::error::AppError;
+use clap::builder::{PossibleValue, PossibleValuesParser};
+use clap::{Command, ValueEnum};
+use clap_complete::Generator;
+use clap_complete_nushell::Nushell;
+use std::io::{self, Write};
+use std::path::Path;
+
+/// Governors built into mainline kernels, offered when this system does not
+/// list its own
+const KERNEL_GOVERNORS: &[&str] = &[
+    "performance",
+    "powersave",
+    "schedutil",
+    "ondemand",
+    "conservative",
+    "userspace",
+];
+
+/// Platform profiles defined by the kernel, offered when this system does not
+/// list its own
+const KERNEL_PLATFORM_PROFILES: &[&str] = &[
+    "low-power",
+    "cool",
+    "quiet",
+    "balanced",
+    "balanced-performance",
+    "performance",
+];
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+    Nushell,
+}
+
+/// Prints the completion script for `shell`, or with `out_dir` writes the
+/// scripts for every shell there
+///
+/// Governors, EPP values and platform profiles are completed from what this
+/// system supports, falling back to the values the kernel defines.
+pub fn run_completions(
+    command: Command,
+    shell: Option<Shell>,
+    out_dir: Option<&Path>,
+) -> Result<(), AppError> {
+    let mut command = with_value_hints(command);
+    let bin_name = command.get_name().to_string();
+
+    let Some(out_dir) = out_dir else {
+        let shell = shell.ok_or_else(|| {
+            AppError::Usage("Name a shell, or use --out-dir for all of them".to_string())
+        })?;
+        // Rendered in full first, as clap_complete panics on a failed write
+        let script = render(shell, &mut command, &bin_name);
+        io::stdout().lock().write_all(&script)?;
+        return Ok(());
+    };
+
+    std::fs::create_dir_all(out_dir)?;
+    let shells = shell.map_or_else(|| Shell::value_variants().to_vec(), |shell| vec![shell]);
+    for shell in shells {
+        let path = clap_complete::generate_to(shell, &mut command, &bin_name, out_dir)?;
+        println!("Wrote {}", path.display());
+    }
+    Ok(())
+}
+
+/// The completion script for `shell`
+fn render(shell: Shell, command: &mut Command, bin_name: &str) -> Vec<u8> {
+    let mut script = Vec::new();
+    clap_complete::generate(shell, command, bin_name, &mut script);
+    script
+}
+
+impl Generator for Shell {
+    fn file_name(&self, name: &str) -> String {
+        match self {
+            Self::Bash => clap_complete::Shell::Bash.file_name(name),
+            Self::Zsh => clap_complete::Shell::Zsh.file_name(name),
+            Self::Fish => clap_complete::Shell::Fish.file_name(name),
+            Self::Nushell => Nushell.file_name(name),
+        }
+    }
+
+    fn generate(&self, command: &Command, buf: &mut dyn Write) {
+        match self {
+            Self::Bash => clap_complete::Shell::Bash.generate(command, buf),
+            Self::Zsh => clap_complete::Shell::Zsh.generate(command, buf),
+            Self::Fish => clap_complete::Shell::Fish.generate(command, buf),
+            Self::Nushell => Nushell.generate(command, buf),
+        }
+    }
+}
+
+/// Attaches the values worth completing to arguments that take any string,
+/// only in the command used for generating completions
+fn with_value_hints(command: Command) -> Command {
+    let capabilities = Capabilities::probe();
+    let or_kernel = |values: Vec<String>, kernel: &[&str]| {
+        if values.is_empty() {
+            kernel.iter().map(ToString::to_string).collect()
+        } else {
+            values
+        }
+    };
+    let governors = or_kernel(capabilities.governors(), KERNEL_GOVERNORS);
+    let epp_values = capabilities.epp_values();
+    let platform_profiles = or_kernel(capabilities.platform_profiles, KERNEL_PLATFORM_PROFILES);
+
+    command
+        .mut_subcommand("set", |set| {
+            set.mut_subcommand("governor", |c| hint(c, "governor", &governors))
+                .mut_subcommand("epp", |c| hint(c, "epp", &epp_values))
+                .mut_subcommand("platform-profile", |c| {
+                    hint(c, "profile", &platform_profiles)
+                })
+        })
+        .mut_subcommand("set-governor", |c| hint(c, "governor", &governors))
+        .mut_subcommand("set-epp", |c| hint(c, "epp", &epp_values))
+        .mut_subcommand("set-platform-profile", |c| {
+            hint(c, "profile", &platform_profiles)
+        })
+        .mut_subcommand("profile", |c| hint(c, "name", daemon::PROFILE_NAMES))
+        .mut_subcommand("log-level", |c| {
+            hint(c, "level", &["error", "warning", "info", "debug"])
+        })
+}
+
+fn hint(command: Command, arg: &str, values: &[impl ToString]) -> Command {
+    let values: Vec<PossibleValue> = values
+        .iter()
+        .map(|value| PossibleValue::new(value.to_string()))
+        .collect();
+    command.mut_arg(arg, |arg| {
+        arg.value_parser(PossibleValuesParser::new(values))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+    use std::process::{Command as Process, Stdio};
+
+    #[test]
+    fn bash_script_parses() {
+        let mut command = with_value_hints(crate::Cli::command());
+        let script = render(Shell::Bash, &mut command, "watt");
+
+        let Ok(mut bash) = Process::new("bash")
+            .arg("-n")
+            .stdin(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+        else {
+            eprintln!("bash is not installed, skipping");
+            return;
+        };
+        bash.stdin.take().unwrap().write_all(&script).unwrap();
+        let output = bash.wait_with_output().unwrap();
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+}
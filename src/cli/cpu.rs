@@ -0,0 +1,244 @@
+use super::style;
+use crate::config::AppConfig;
+use crate::cpu;
+use crate::monitor;
+use crate::sensors;
+use crate::util::error::AppError;
+use crate::util::sysfs;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::thread;
+use std::time::Duration;
+
+/// Time between the two `/proc/stat` reads usage is computed from
+const USAGE_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Everything shown for one logical CPU
+#[derive(Debug, Clone, Serialize)]
+pub struct CoreDetail {
+    pub cpu: u32,
+    pub online: bool,
+    /// The cpufreq policy the CPU belongs to, e.g. `policy0`
+    pub policy: Option<String>,
+    pub governor: Option<String>,
+    pub cur_freq_mhz: Option<u32>,
+    pub min_freq_mhz: Option<u32>,
+    pub max_freq_mhz: Option<u32>,
+    pub epp: Option<String>,
+    pub epb: Option<String>,
+    /// Missing without a temperature sensor. From the core's own or its
+    /// CCD's sensor where the driver has one, otherwise some sensors only
+    /// report the package, which then shows for every CPU.
+    pub temperature_celsius: Option<f32>,
+    pub usage_percent: Option<f32>,
+    /// Per-policy boost where the driver has it, the global turbo state
+    /// otherwise
+    pub boost: Option<bool>,
+    /// Whether Watt applies settings to this CPU, i.e. it is online and has a
+    /// cpufreq policy
+    pub managed: bool,
+}
+
+impl CoreDetail {
+    /// Whether `other` has the same settings, ignoring the values that change
+    /// all the time: current frequency, temperature and usage
+    fn same_settings(&self, other: &Self) -> bool {
+        self.online == other.online
+            && self.governor == other.governor
+            && self.min_freq_mhz == other.min_freq_mhz
+            && self.max_freq_mhz == other.max_freq_mhz
+            && self.epp == other.epp
+            && self.epb == other.epb
+            && self.boost == other.boost
+            && self.managed == other.managed
+    }
+}
+
+/// Prints a table of every logical CPU, or with `json` a JSON array
+///
+/// Consecutive CPUs with the same settings as the one before them collapse
+/// into a single line unless `all` is set. With `watch`, the table is redrawn
+/// in place at that interval until interrupted.
+pub fn run_cpu(
+    config: &AppConfig,
+    all: bool,
+    json: bool,
+    watch: Option<Duration>,
+) -> Result<(), AppError> {
+    loop {
+        let cores = collect(config.count_iowait_as_busy)?;
+        if json {
+            super::print_json(&cores)?;
+        } else {
+            if watch.is_some() {
+                // Home the cursor and clear the screen, so the table stays put
+                print!("\x1b[H\x1b[2J");
+            }
+            for line in table(&cores, all) {
+                println!("{line}");
+            }
+        }
+
+        match watch {
+            Some(interval) => thread::sleep(interval.saturating_sub(USAGE_INTERVAL)),
+            None => return Ok(()),
+        }
+    }
+}
+
+/// Reads every `cpuN` directory, measuring usage over [`USAGE_INTERVAL`]
+fn collect(count_iowait_as_busy: bool) -> Result<Vec<CoreDetail>, AppError> {
+    let before = monitor::read_all_cpu_times()?;
+    thread::sleep(USAGE_INTERVAL);
+    let after = monitor::read_all_cpu_times()?;
+    let turbo = cpu::turbo_enabled();
+    let cpufreq_available = cpu::cpufreq_unavailable_reason().is_none();
+    let core_temps = sensors::core_temperatures();
+
+    let mut ids: Vec<u32> = fs::read_dir("/sys/devices/system/cpu")?
+        .flatten()
+        .filter_map(|entry| {
+            entry
+                .file_name()
+                .to_str()?
+                .strip_prefix("cpu")?
+                .parse()
+                .ok()
+        })
+        .collect();
+    ids.sort_unstable();
+
+    Ok(ids
+        .into_iter()
+        .map(|id| {
+            // Offline CPUs are missing from /proc/stat
+            let usage = after.get(&id).map(|after| {
+                before
+                    .get(&id)
+                    .and_then(|before| after.usage_since(before, count_iowait_as_busy))
+            });
+            core_detail(id, usage, &core_temps, turbo, cpufreq_available)
+        })
+        .collect())
+}
+
+/// `usage` is `None` for CPUs missing from `/proc/stat`, which are offline
+fn core_detail(
+    id: u32,
+    usage: Option<Option<f32>>,
+    core_temps: &BTreeMap<u32, f32>,
+    turbo: Option<bool>,
+    cpufreq_available: bool,
+) -> CoreDetail {
+    let base = Path::new("/sys/devices/system/cpu").join(format!("cpu{id}"));
+    let read = |file: &str| sysfs::read_sysfs_value(base.join(file)).ok();
+    // cpu0 often cannot be taken offline and has no `online` file
+    let online = read("online").is_none_or(|value| value == "1");
+
+    let info = usage.and_then(|usage| monitor::get_cpu_core_info(id, usage, core_temps).ok());
+    let policy = fs::canonicalize(base.join("cpufreq"))
+        .ok()
+        .and_then(|path| Some(path.file_name()?.to_string_lossy().into_owned()));
+
+    CoreDetail {
+        cpu: id,
+        online,
+        managed: online && cpufreq_available && policy.is_some(),
+        policy,
+        governor: read("cpufreq/scaling_governor"),
+        cur_freq_mhz: info.as_ref().and_then(|info| info.current_frequency_mhz),
+        min_freq_mhz: info.as_ref().and_then(|info| info.min_frequency_mhz),
+        max_freq_mhz: info.as_ref().and_then(|info| info.max_frequency_mhz),
+        epp: read("cpufreq/energy_performance_preference"),
+        epb: read("power/energy_perf_bias"),
+        temperature_celsius: info.as_ref().and_then(|info| info.temperature_celsius),
+        usage_percent: info.as_ref().and_then(|info| info.usage_percent),
+        boost: read("cpufreq/boost").map_or(turbo, |value| Some(value == "1")),
+    }
+}
+
+/// Lines of the table, collapsing runs of CPUs with the same settings unless
+/// `all` is set
+fn table(cores: &[CoreDetail], all: bool) -> Vec<String> {
+    let mut lines = vec![style::heading(format!(
+        "{:<9}{:<9}{:<10}{:<14}{:>7}{:>7}{:>7}  {:<22}{:<5}{:>7}{:>7}  {:<6}{}",
+        "CPU",
+        "State",
+        "Policy",
+        "Governor",
+        "Cur",
+        "Min",
+        "Max",
+        "EPP",
+        "EPB",
+        "Temp",
+        "Usage",
+        "Boost",
+        "Managed"
+    ))];
+
+    let mut i = 0;
+    while i < cores.len() {
+        let core = &cores[i];
+        lines.push(row(core));
+        i += 1;
+        if all {
+            continue;
+        }
+
+        let run = cores[i..]
+            .iter()
+            .zip(core.cpu + 1..)
+            .take_while(|(other, id)| other.cpu == *id && other.same_settings(core))
+            .count();
+        match run {
+            0 => {}
+            1 => lines.push(row(&cores[i])),
+            _ => lines.push(format!(
+                "{:<9}same settings as cpu{}",
+                format!("cpu{}-{}", cores[i].cpu, cores[i + run - 1].cpu),
+                core.cpu
+            )),
+        }
+        i += run;
+    }
+    lines
+}
+
+fn row(core: &CoreDetail) -> String {
+    let text = |value: &Option<String>| value.clone().unwrap_or_else(|| "-".to_string());
+    let mhz = |freq: Option<u32>| freq.map_or_else(|| "-".to_string(), |f| f.to_string());
+    format!(
+        "{:<9}{}{:<10}{:<14}{:>7}{:>7}{:>7}  {:<22}{:<5}{:>7}{:>7}  {:<6}{}",
+        format!("cpu{}", core.cpu),
+        // Padded before coloring, which would throw the width off
+        if core.online {
+            format!("{:<9}", "online")
+        } else {
+            style::warn(format!("{:<9}", "offline"))
+        },
+        text(&core.policy),
+        text(&core.governor),
+        mhz(core.cur_freq_mhz),
+        mhz(core.min_freq_mhz),
+        mhz(core.max_freq_mhz),
+        text(&core.epp),
+        text(&core.epb),
+        core.temperature_celsius
+            .map_or_else(|| "-".to_string(), |temp| format!("{temp:.0}°C")),
+        core.usage_percent
+            .map_or_else(|| "-".to_string(), |usage| format!("{usage:.0}%")),
+        match core.boost {
+            Some(true) => "on",
+            Some(false) => "off",
+            None => "-",
+        },
+        if core.managed {
+            style::ok("yes")
+        } else {
+            style::warn("no")
+        }
+    )
+}
@@ -1,14 +1,17 @@
+use super::style;
 use crate::config::AppConfig;
 use crate::cpu;
 use crate::monitor;
+use crate::pidfile;
 use crate::util::error::AppError;
 use std::fs;
+use std::path::Path;
 use std::process::{Command, Stdio};
 use std::time::Duration;
 
 /// Prints comprehensive debug information about the system
 pub fn run_debug(config: &AppConfig) -> Result<(), AppError> {
-    println!("=== WATT DEBUG INFORMATION ===");
+    println!("{}", style::heading("=== WATT DEBUG INFORMATION ==="));
     println!("Version: {}", env!("CARGO_PKG_VERSION"));
 
     // Current date and time
@@ -35,7 +38,7 @@ pub fn run_debug(config: &AppConfig) -> Result<(), AppError> {
     // Get system information
     match monitor::collect_system_report(config) {
         Ok(report) => {
-            println!("\n--- SYSTEM INFORMATION ---");
+            println!("\n{}", style::heading("--- SYSTEM INFORMATION ---"));
             println!("CPU Model: {}", report.system_info.cpu_model);
             println!("Architecture: {}", report.system_info.architecture);
             println!(
@@ -43,11 +46,11 @@ pub fn run_debug(config: &AppConfig) -> Result<(), AppError> {
                 report.system_info.linux_distribution
             );
 
-            println!("\n--- CONFIGURATION ---");
+            println!("\n{}", style::heading("--- CONFIGURATION ---"));
             println!("Current Configuration: {config:#?}");
 
             // Print important sysfs paths and whether they exist
-            println!("\n--- SYSFS PATHS ---");
+            println!("\n{}", style::heading("--- SYSFS PATHS ---"));
             check_and_print_sysfs_path(
                 "/sys/devices/system/cpu/intel_pstate/no_turbo",
                 "Intel P-State Turbo Control",
@@ -66,7 +69,7 @@ pub fn run_debug(config: &AppConfig) -> Result<(), AppError> {
             );
             check_and_print_sysfs_path("/sys/class/power_supply", "Power Supply Information");
 
-            println!("\n--- CPU INFORMATION ---");
+            println!("\n{}", style::heading("--- CPU INFORMATION ---"));
             println!("Current Governor: {:?}", report.cpu_global.current_governor);
             println!(
                 "Available Governors: {}",
@@ -86,7 +89,7 @@ pub fn run_debug(config: &AppConfig) -> Result<(), AppError> {
                 println!("Governor Override: None");
             }
 
-            println!("\n--- PLATFORM PROFILE ---");
+            println!("\n{}", style::heading("--- PLATFORM PROFILE ---"));
             println!(
                 "Current Platform Profile: {:?}",
                 report.cpu_global.platform_profile
@@ -96,7 +99,7 @@ pub fn run_debug(config: &AppConfig) -> Result<(), AppError> {
                 Err(_) => println!("Available Platform Profiles: Not supported on this system"),
             }
 
-            println!("\n--- CPU CORES DETAIL ---");
+            println!("\n{}", style::heading("--- CPU CORES DETAIL ---"));
             println!("Total CPU Cores: {}", report.cpu_cores.len());
             for core in &report.cpu_cores {
                 println!("Core {}:", core.core_id);
@@ -127,7 +130,7 @@ pub fn run_debug(config: &AppConfig) -> Result<(), AppError> {
                 );
             }
 
-            println!("\n--- TEMPERATURE INFORMATION ---");
+            println!("\n{}", style::heading("--- TEMPERATURE INFORMATION ---"));
             println!(
                 "Average CPU Temperature: {}",
                 report.cpu_global.average_temperature_celsius.map_or_else(
@@ -136,7 +139,7 @@ pub fn run_debug(config: &AppConfig) -> Result<(), AppError> {
                 )
             );
 
-            println!("\n--- BATTERY INFORMATION ---");
+            println!("\n{}", style::heading("--- BATTERY INFORMATION ---"));
             if report.batteries.is_empty() {
                 println!("No batteries found or all are ignored.");
             } else {
@@ -174,7 +177,7 @@ pub fn run_debug(config: &AppConfig) -> Result<(), AppError> {
                 }
             }
 
-            println!("\n--- SYSTEM LOAD ---");
+            println!("\n{}", style::heading("--- SYSTEM LOAD ---"));
             println!(
                 "Load Average (1 min): {:.2}",
                 report.system_load.load_avg_1min
@@ -188,10 +191,11 @@ pub fn run_debug(config: &AppConfig) -> Result<(), AppError> {
                 report.system_load.load_avg_15min
             );
 
-            println!("\n--- DAEMON STATUS ---");
-            // Simple check for daemon status - can be expanded later
-            let daemon_status = fs::metadata("/var/run/watt.pid").is_ok();
-            println!("Daemon Running: {daemon_status}");
+            println!("\n{}", style::heading("--- DAEMON STATUS ---"));
+            match pidfile::running_daemon_pid(Path::new(&config.daemon.pid_file)) {
+                Some(pid) => println!("Daemon Running: true (PID {pid})"),
+                None => println!("Daemon Running: false"),
+            }
 
             // Check for systemd service status
             if let Ok(systemd_status) = is_systemd_service_active("watt") {
@@ -236,12 +240,16 @@ fn check_and_print_sysfs_path(path: &str, description: &str) {
         "{}: {} ({})",
         description,
         path,
-        if exists { "Exists" } else { "Not Found" }
+        if exists {
+            style::ok("Exists")
+        } else {
+            style::warn("Not Found")
+        }
     );
 }
 
 /// Check if a systemd service is active
-fn is_systemd_service_active(service_name: &str) -> Result<bool, AppError> {
+pub fn is_systemd_service_active(service_name: &str) -> Result<bool, AppError> {
     let output = Command::new("systemctl")
         .arg("is-active")
         .arg(format!("{service_name}.service"))
@@ -0,0 +1,497 @@
+use super::style;
+use crate::capabilities::Capabilities;
+use crate::config::{self, AppConfig, ProfileConfig};
+use crate::core::TurboSetting;
+use crate::cpu;
+use crate::daemon;
+use crate::engine;
+use crate::ipc;
+use crate::pidfile;
+use crate::privileges;
+use crate::util::error::AppError;
+use serde::Serialize;
+use std::path::Path;
+use std::time::Instant;
+
+/// How a check went, ordered from best to worst
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Outcome {
+    Pass,
+    Warn,
+    Fail,
+}
+
+impl Outcome {
+    /// 0, 1 and 2 for the worst outcome, as monitoring plugins do
+    pub const fn exit_code(self) -> i32 {
+        match self {
+            Self::Pass => 0,
+            Self::Warn => 1,
+            Self::Fail => 2,
+        }
+    }
+}
+
+/// One finding of `watt doctor`
+#[derive(Debug, Serialize)]
+pub struct Check {
+    pub name: &'static str,
+    pub outcome: Outcome,
+    pub message: String,
+    /// What to do about it, for warnings and failures
+    pub hint: Option<String>,
+}
+
+impl Check {
+    fn pass(name: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            name,
+            outcome: Outcome::Pass,
+            message: message.into(),
+            hint: None,
+        }
+    }
+
+    fn warn(name: &'static str, message: impl Into<String>, hint: impl Into<String>) -> Self {
+        Self {
+            name,
+            outcome: Outcome::Warn,
+            message: message.into(),
+            hint: Some(hint.into()),
+        }
+    }
+
+    fn fail(name: &'static str, message: impl Into<String>, hint: impl Into<String>) -> Self {
+        Self {
+            name,
+            outcome: Outcome::Fail,
+            message: message.into(),
+            hint: Some(hint.into()),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct Report {
+    /// The worst outcome of any check
+    result: Outcome,
+    checks: Vec<Check>,
+}
+
+/// Checks the setup for common problems and prints a line per check, or with
+/// `json` a JSON document, returning the worst outcome
+///
+/// The configuration file is read again, as `config` falls back to the
+/// defaults when it does not parse.
+pub fn run_doctor(config: &AppConfig, json: bool) -> Result<Outcome, AppError> {
+    let capabilities = Capabilities::probe();
+    let mut checks = vec![privileges(), driver(&capabilities)];
+    checks.extend(config_file());
+    checks.push(profile_settings(
+        "charger profile",
+        "charger",
+        &config.charger,
+        &capabilities,
+    ));
+    checks.push(profile_settings(
+        "battery profile",
+        "battery",
+        &config.battery,
+        &capabilities,
+    ));
+    if let Some(balanced) = &config.balanced {
+        checks.push(profile_settings(
+            "balanced profile",
+            "balanced",
+            balanced,
+            &capabilities,
+        ));
+    }
+    checks.push(conflicts());
+    checks.push(charge_thresholds(config, &capabilities));
+    checks.push(daemon(config));
+
+    let report = Report {
+        result: checks
+            .iter()
+            .map(|check| check.outcome)
+            .max()
+            .unwrap_or(Outcome::Pass),
+        checks,
+    };
+    if json {
+        super::print_json(&report)?;
+    } else {
+        report.print();
+    }
+    Ok(report.result)
+}
+
+impl Report {
+    fn print(&self) {
+        let width = self
+            .checks
+            .iter()
+            .map(|check| check.name.len())
+            .max()
+            .unwrap_or(0);
+        for check in &self.checks {
+            let outcome = match check.outcome {
+                Outcome::Pass => style::ok("pass"),
+                Outcome::Warn => style::warn("warn"),
+                Outcome::Fail => style::error("fail"),
+            };
+            println!("{outcome}  {:<width$}  {}", check.name, check.message);
+            if let Some(hint) = &check.hint {
+                println!("      {:<width$}  {hint}", "");
+            }
+        }
+
+        let count = |outcome: Outcome| {
+            self.checks
+                .iter()
+                .filter(|check| check.outcome == outcome)
+                .count()
+        };
+        println!();
+        println!(
+            "{} passed, {} warnings, {} failed",
+            count(Outcome::Pass),
+            count(Outcome::Warn),
+            count(Outcome::Fail)
+        );
+    }
+}
+
+/// Whether settings can be written: as root, or with `CAP_DAC_OVERRIDE`
+fn privileges() -> Check {
+    const NAME: &str = "privileges";
+    // SAFETY: geteuid has no preconditions
+    if unsafe { libc::geteuid() } == 0 {
+        return Check::pass(NAME, "running as root");
+    }
+
+    if privileges::can_write_sysfs() {
+        return Check::pass(NAME, "not root, but has CAP_DAC_OVERRIDE");
+    }
+    Check::warn(
+        NAME,
+        "not root, so settings can only be read",
+        "Run `watt daemon` and `watt apply` with sudo, or change settings through a running daemon",
+    )
+}
+
+fn driver(capabilities: &Capabilities) -> Check {
+    const NAME: &str = "cpufreq driver";
+    if let Some(reason) = cpu::cpufreq_unavailable_reason() {
+        return Check::fail(
+            NAME,
+            format!("CPU frequency cannot be controlled, {reason}"),
+            "Load the cpufreq driver for this CPU, e.g. intel_pstate, amd-pstate or acpi-cpufreq",
+        );
+    }
+
+    let driver = capabilities.driver.as_deref().unwrap_or("unknown driver");
+    match capabilities.driver_mode.as_deref() {
+        Some("passive") => Check::warn(
+            NAME,
+            format!("{driver} in passive mode, so EPP cannot be set"),
+            "Boot with intel_pstate=active or amd_pstate=active to use EPP",
+        ),
+        Some(mode) => Check::pass(NAME, format!("{driver} in {mode} mode")),
+        None => Check::pass(NAME, driver),
+    }
+}
+
+/// Whether the configuration file parses and its values are valid
+fn config_file() -> Vec<Check> {
+    const NAME: &str = "config file";
+    let path = config::config_paths()
+        .into_iter()
+        .find(|path| path.exists());
+    let loaded = match config::load_config() {
+        Ok(loaded) => loaded,
+        Err(e) => {
+            return vec![Check::fail(
+                NAME,
+                match &path {
+                    Some(path) => format!("{} does not load: {e}", path.display()),
+                    None => e.to_string(),
+                },
+                "Fix the file; until then `watt daemon` and `watt apply` refuse to start",
+            )];
+        }
+    };
+
+    let mut checks = vec![match &path {
+        Some(path) => Check::pass(NAME, format!("{} parses", path.display())),
+        None => Check::pass(NAME, "no file found, using the defaults"),
+    }];
+
+    let invalid: Vec<String> = [
+        daemon::validate_poll_intervals(
+            loaded.daemon.min_poll_interval_sec,
+            loaded.daemon.max_poll_interval_sec,
+        )
+        .err()
+        .map(|e| e.to_string()),
+        daemon::validate_adaptive_settings(&loaded.daemon.adaptive)
+            .err()
+            .map(|e| e.to_string()),
+        daemon::validate_smoothing_settings(&loaded.daemon.smoothing)
+            .err()
+            .map(|e| e.to_string()),
+        engine::validate_turbo_auto_settings(&loaded.charger.turbo_auto_settings)
+            .err()
+            .map(|e| format!("charger: {e}")),
+        engine::validate_turbo_auto_settings(&loaded.battery.turbo_auto_settings)
+            .err()
+            .map(|e| format!("battery: {e}")),
+        // Whether the governors exist is up to the profile checks
+        engine::validate_auto_governor(&loaded.charger, &[])
+            .err()
+            .map(|e| format!("charger: {e}")),
+        engine::validate_auto_governor(&loaded.battery, &[])
+            .err()
+            .map(|e| format!("battery: {e}")),
+        loaded
+            .charger
+            .thermal_backoff
+            .as_ref()
+            .and_then(|backoff| engine::validate_thermal_backoff(backoff).err())
+            .map(|e| format!("charger: {e}")),
+        loaded
+            .battery
+            .thermal_backoff
+            .as_ref()
+            .and_then(|backoff| engine::validate_thermal_backoff(backoff).err())
+            .map(|e| format!("battery: {e}")),
+        engine::validate_epp_auto_settings(&loaded.charger.epp_auto_settings)
+            .err()
+            .map(|e| format!("charger: {e}")),
+        engine::validate_epp_auto_settings(&loaded.battery.epp_auto_settings)
+            .err()
+            .map(|e| format!("battery: {e}")),
+        engine::apply_order(&loaded.daemon.apply_order)
+            .err()
+            .map(|e| e.to_string()),
+        engine::validate_thermal_safety(&loaded.daemon)
+            .err()
+            .map(|e| e.to_string()),
+        loaded
+            .balanced
+            .as_ref()
+            .and_then(|balanced| {
+                engine::validate_turbo_auto_settings(&balanced.turbo_auto_settings)
+                    .and_then(|()| engine::validate_auto_governor(balanced, &[]))
+                    .and_then(|()| {
+                        balanced
+                            .thermal_backoff
+                            .as_ref()
+                            .map_or(Ok(()), engine::validate_thermal_backoff)
+                    })
+                    .and_then(|()| engine::validate_epp_auto_settings(&balanced.epp_auto_settings))
+                    .err()
+            })
+            .map(|e| format!("balanced: {e}")),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+    if !invalid.is_empty() {
+        checks.push(Check::fail(
+            "config values",
+            invalid.join("; "),
+            "Correct these values; the daemon refuses to start with them",
+        ));
+    }
+
+    // Only an explicit order can break a dependency
+    let violations: Vec<String> = engine::apply_order(&loaded.daemon.apply_order)
+        .map(|order| engine::apply_order_violations(&order))
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(step, dependency)| format!("{} before {}", step.as_str(), dependency.as_str()))
+        .collect();
+    if !violations.is_empty() {
+        checks.push(Check::warn(
+            "apply order",
+            format!("daemon.apply_order applies {}", violations.join(", ")),
+            "Leave out apply_order unless your firmware needs this order",
+        ));
+    }
+    checks
+}
+
+/// Whether what the profile in `[section]` sets exists on this machine
+fn profile_settings(
+    name: &'static str,
+    section: &str,
+    profile: &ProfileConfig,
+    capabilities: &Capabilities,
+) -> Check {
+    let mut invalid = Vec::new();
+    let mut unsupported = Vec::new();
+
+    let mut offered = |setting: &str, value: &Option<String>, values: &[String]| {
+        let Some(value) = value else {
+            return;
+        };
+        if values.is_empty() {
+            unsupported.push(format!("{setting} = \"{value}\""));
+        } else if !values.contains(value) {
+            invalid.push(format!(
+                "{setting} \"{value}\" is not one of: {}",
+                values.join(", ")
+            ));
+        }
+    };
+    if profile.auto_governor() {
+        let settings = profile.governor_auto_settings.clone().unwrap_or_default();
+        for governor in [settings.low_load_governor, settings.high_load_governor] {
+            offered(
+                "governor_auto_settings governor",
+                &Some(governor),
+                &capabilities.governors(),
+            );
+        }
+    } else {
+        offered("governor", &profile.governor, &capabilities.governors());
+    }
+    if profile.auto_epp() {
+        for tier in &profile.epp_auto_settings.tiers {
+            offered(
+                "epp_auto_settings tier",
+                &Some(tier.epp.clone()),
+                &capabilities.epp_values(),
+            );
+        }
+    } else {
+        offered("epp", &profile.epp, &capabilities.epp_values());
+    }
+    offered(
+        "platform_profile",
+        &profile.platform_profile,
+        &capabilities.platform_profiles,
+    );
+
+    if let Some(epb) = &profile.epb {
+        if !capabilities.epb {
+            unsupported.push(format!("epb = \"{epb}\""));
+        }
+    }
+    if matches!(
+        profile.turbo,
+        Some(TurboSetting::Always | TurboSetting::Never)
+    ) && capabilities.turbo.is_none()
+    {
+        unsupported.push("turbo".to_string());
+    }
+
+    let min_hw = capabilities
+        .policies
+        .iter()
+        .filter_map(|policy| policy.min_freq_mhz)
+        .min();
+    let max_hw = capabilities
+        .policies
+        .iter()
+        .filter_map(|policy| policy.max_freq_mhz)
+        .max();
+    for (setting, freq) in [
+        ("min_freq_mhz", profile.min_freq_mhz),
+        ("max_freq_mhz", profile.max_freq_mhz),
+    ] {
+        if let (Some(freq), Some(low), Some(high)) = (freq, min_hw, max_hw) {
+            if freq < low || freq > high {
+                invalid.push(format!(
+                    "{setting} {freq} is outside the hardware range {low}-{high} MHz"
+                ));
+            }
+        }
+    }
+
+    if !invalid.is_empty() {
+        Check::fail(
+            name,
+            invalid.join("; "),
+            format!("Use a value this machine offers in [{section}], see `watt capabilities`"),
+        )
+    } else if !unsupported.is_empty() {
+        Check::warn(
+            name,
+            format!("not supported here, so skipped: {}", unsupported.join(", ")),
+            format!("Remove these from [{section}] to silence the warnings they log"),
+        )
+    } else {
+        Check::pass(name, "every setting is supported")
+    }
+}
+
+fn conflicts() -> Check {
+    const NAME: &str = "conflicts";
+    let conflicts = super::status::conflicts();
+    if conflicts.is_empty() {
+        Check::pass(NAME, "no conflicting services or overrides")
+    } else {
+        Check::warn(
+            NAME,
+            conflicts.join("; "),
+            "Disable the other power manager, e.g. `systemctl disable --now tlp`, and reset overrides with `watt force-governor reset`",
+        )
+    }
+}
+
+fn charge_thresholds(config: &AppConfig, capabilities: &Capabilities) -> Check {
+    const NAME: &str = "charge thresholds";
+    let configured = config.charger.battery_charge_thresholds.is_some()
+        || config.battery.battery_charge_thresholds.is_some();
+    let supported: Vec<String> = capabilities
+        .charge_thresholds
+        .iter()
+        .map(|battery| format!("{} ({})", battery.battery, battery.scheme))
+        .collect();
+
+    match (configured, supported.is_empty()) {
+        (true, true) => Check::warn(
+            NAME,
+            "configured, but no battery supports them",
+            "Remove battery_charge_thresholds, or load the vendor driver that exposes them, e.g. thinkpad_acpi",
+        ),
+        (true, false) => Check::pass(NAME, format!("configured, set on {}", supported.join(", "))),
+        (false, true) => Check::pass(NAME, "not configured and not supported"),
+        (false, false) => Check::pass(
+            NAME,
+            format!("not configured, supported by {}", supported.join(", ")),
+        ),
+    }
+}
+
+/// Whether the daemon is running and answers on its control socket
+fn daemon(config: &AppConfig) -> Check {
+    const NAME: &str = "daemon";
+    let Some(pid) = pidfile::running_daemon_pid(Path::new(&config.daemon.pid_file)) else {
+        return Check::warn(
+            NAME,
+            "not running, so nothing is managed automatically",
+            "Start it with `systemctl enable --now watt` or `sudo watt daemon`",
+        );
+    };
+
+    let start = Instant::now();
+    match ipc::ping() {
+        Ok(_) => Check::pass(
+            NAME,
+            format!(
+                "running as PID {pid}, answered in {:.1} ms",
+                start.elapsed().as_secs_f64() * 1000.0
+            ),
+        ),
+        Err(e) => Check::fail(
+            NAME,
+            format!("running as PID {pid}, but not answering: {e}"),
+            "Check its log, and restart it with `systemctl restart watt`",
+        ),
+    }
+}
@@ -0,0 +1,98 @@
+use crate::cli::style;
+use crate::engine::DecisionTrace;
+use crate::ipc;
+use crate::util::error::AppError;
+
+/// Prints why the running daemon picked its profile and turbo state in its
+/// last cycle, as text or with `json` as a JSON document
+///
+/// Decisions are only made by the daemon, so this fails when none is running
+/// or it has not applied anything yet.
+pub fn run_explain(json: bool) -> Result<(), AppError> {
+    let status = ipc::fetch_status()
+        .map_err(|e| AppError::daemon("Cannot ask the daemon why it decided", &e))?;
+    let Some(trace) = status.decision_traces.last() else {
+        return Err(AppError::Generic(
+            "The daemon has not decided anything yet, it may be paused or still starting"
+                .to_string(),
+        ));
+    };
+
+    if json {
+        return super::print_json(trace);
+    }
+    print_trace(trace);
+    Ok(())
+}
+
+fn print_trace(trace: &DecisionTrace) {
+    let inputs = &trace.inputs;
+    let percent =
+        |value: Option<f32>| value.map_or_else(|| "N/A".to_string(), |v| format!("{v:.1}%"));
+
+    println!("Decided at:     {}", trace.timestamp);
+    println!("Inputs:");
+    println!(
+        "  Power source: {} ({})",
+        if inputs.on_ac_power { "AC" } else { "battery" },
+        inputs.power_source
+    );
+    println!(
+        "  Battery:      {}",
+        inputs
+            .battery_percent
+            .map_or_else(|| "N/A".to_string(), |percent| format!("{percent}%"))
+    );
+    println!("  Load:         {}", percent(inputs.load_percent));
+    println!(
+        "  Temperature:  {}",
+        inputs
+            .temperature_celsius
+            .map_or_else(|| "N/A".to_string(), |temp| format!("{temp:.1}°C"))
+    );
+    if let Some(events) = inputs.throttle_events {
+        println!("  Throttling:   {events} events");
+    }
+
+    println!("Rules:");
+    for rule in &trace.rules {
+        let verdict = if rule.matched {
+            style::ok("matched")
+        } else {
+            style::warn("no")
+        };
+        println!("  {:<32} {verdict}", rule.rule);
+    }
+
+    if !trace.overrides.is_empty() {
+        println!("Overrides:");
+        for description in &trace.overrides {
+            println!("  {description}");
+        }
+    }
+
+    println!(
+        "Profile:        {} ({})",
+        trace.profile, trace.profile_reason
+    );
+    for turbo in &trace.turbo {
+        let details: Vec<String> = turbo
+            .load_percent
+            .map(|load| format!("load {load:.1}%"))
+            .into_iter()
+            .chain(turbo.temperature_celsius.map(|temp| format!("{temp:.1}°C")))
+            .collect();
+        let strategy = turbo
+            .strategy
+            .as_deref()
+            .map_or_else(String::new, |strategy| format!(", {strategy} strategy"));
+        if details.is_empty() {
+            println!("Turbo:          {turbo}{strategy}");
+        } else {
+            println!(
+                "Turbo:          {turbo}{strategy}, at {}",
+                details.join(", ")
+            );
+        }
+    }
+}
@@ -0,0 +1,194 @@
+use crate::config::AppConfig;
+use crate::history::{self, Event};
+use crate::util::error::AppError;
+use clap::ValueEnum;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ExportFormat {
+    /// Comma-separated values with a header line
+    Csv,
+    /// One JSON object per line
+    Json,
+}
+
+/// Fields `--fields` accepts, in the default order
+pub const FIELDS: &[&str] = &[
+    "timestamp",
+    "profile",
+    "on_ac",
+    "battery_pct",
+    "avg_freq",
+    "load",
+    "temp",
+    "turbo",
+    "battery_draw",
+    "cpu_power",
+];
+
+/// Writes the samples the daemon recorded between `from` and `to` to `out`,
+/// or stdout, with only `fields` in that order, all of them if empty
+///
+/// Entries are streamed from the history file and its rotated files, so a
+/// long range is never held in memory. Lines that do not parse are skipped
+/// and counted on stderr.
+pub fn run_export(
+    config: &AppConfig,
+    format: ExportFormat,
+    from: Option<&str>,
+    to: Option<&str>,
+    fields: &[String],
+    out: Option<&Path>,
+) -> Result<(), AppError> {
+    let fields: Vec<&str> = if fields.is_empty() {
+        FIELDS.to_vec()
+    } else {
+        fields.iter().map(String::as_str).collect()
+    };
+    if let Some(unknown) = fields.iter().find(|field| !FIELDS.contains(field)) {
+        return Err(AppError::Usage(format!(
+            "Unknown field '{unknown}', expected one of: {}",
+            FIELDS.join(", ")
+        )));
+    }
+    let from = from.map(|from| parse_time(from, false)).transpose()?;
+    let to = to.map(|to| parse_time(to, true)).transpose()?;
+
+    let mut writer: BufWriter<Box<dyn Write>> = BufWriter::new(match out {
+        Some(path) => Box::new(File::create(path)?),
+        None => Box::new(io::stdout().lock()),
+    });
+    if format == ExportFormat::Csv {
+        writeln!(writer, "{}", fields.join(","))?;
+    }
+
+    let path = Path::new(&config.daemon.history_file);
+    let mut result = Ok(());
+    let mut exported = 0;
+    let skipped = history::for_each_entry(path, |entry| {
+        if result.is_err()
+            || from.is_some_and(|from| entry.at < from)
+            || to.is_some_and(|to| entry.at >= to)
+        {
+            return;
+        }
+        let Event::Sample {
+            profile,
+            on_ac,
+            battery_percent,
+            avg_freq_mhz,
+            load_1m,
+            cpu_temp,
+            turbo,
+            battery_draw_watts,
+            cpu_package_watts,
+        } = entry.event
+        else {
+            return;
+        };
+
+        let values: Vec<serde_json::Value> = fields
+            .iter()
+            .map(|field| match *field {
+                "timestamp" => entry.at.to_string().into(),
+                "profile" => profile.as_str().into(),
+                "on_ac" => on_ac.into(),
+                "battery_pct" => battery_percent.into(),
+                "avg_freq" => avg_freq_mhz.into(),
+                "load" => load_1m.into(),
+                "temp" => cpu_temp.into(),
+                "turbo" => turbo.into(),
+                "battery_draw" => battery_draw_watts.into(),
+                "cpu_power" => cpu_package_watts.into(),
+                _ => unreachable!("fields are checked above"),
+            })
+            .collect();
+        result = match format {
+            ExportFormat::Csv => write_csv_row(&mut writer, &values),
+            ExportFormat::Json => write_json_line(&mut writer, &fields, &values),
+        };
+        exported += 1;
+    })
+    .map_err(|e| {
+        AppError::Generic(format!(
+            "Failed to read history from {}: {e}",
+            path.display()
+        ))
+    })?;
+    result?;
+    writer.flush()?;
+
+    if skipped > 0 {
+        eprintln!("Skipped {skipped} malformed lines");
+    }
+    if let Some(out) = out {
+        eprintln!("Exported {exported} samples to {}", out.display());
+    }
+    Ok(())
+}
+
+/// Parses a timestamp such as `2024-05-01T12:00:00Z`, or a date or date and
+/// time in the local time zone. A bare date means the start of that day, or
+/// the end of it with `end_of_day`, so `--to` includes the day it names.
+fn parse_time(value: &str, end_of_day: bool) -> Result<jiff::Timestamp, AppError> {
+    if let Ok(timestamp) = value.parse::<jiff::Timestamp>() {
+        return Ok(timestamp);
+    }
+    let tz = jiff::tz::TimeZone::system();
+    let local = |datetime: jiff::civil::DateTime| {
+        datetime
+            .to_zoned(tz.clone())
+            .map(|zoned| zoned.timestamp())
+            .map_err(|e| AppError::Usage(format!("Invalid time '{value}': {e}")))
+    };
+    if let Ok(datetime) = value.parse::<jiff::civil::DateTime>() {
+        if value.contains(['T', ' ']) {
+            return local(datetime);
+        }
+    }
+    let date: jiff::civil::Date = value.parse().map_err(|_| {
+        AppError::Usage(format!(
+            "Invalid time '{value}', expected e.g. 2024-05-01 or 2024-05-01T12:00"
+        ))
+    })?;
+    let date = if end_of_day {
+        date.tomorrow()
+            .map_err(|e| AppError::Usage(format!("Invalid time '{value}': {e}")))?
+    } else {
+        date
+    };
+    local(date.to_datetime(jiff::civil::Time::midnight()))
+}
+
+/// Writes one CSV line, quoting text that needs it and leaving nulls empty
+pub fn write_csv_row(writer: &mut impl Write, values: &[serde_json::Value]) -> io::Result<()> {
+    let cells: Vec<String> = values
+        .iter()
+        .map(|value| match value {
+            serde_json::Value::Null => String::new(),
+            serde_json::Value::String(text) if text.contains([',', '"', '\n']) => {
+                format!("\"{}\"", text.replace('"', "\"\""))
+            }
+            serde_json::Value::String(text) => text.clone(),
+            other => other.to_string(),
+        })
+        .collect();
+    writeln!(writer, "{}", cells.join(","))
+}
+
+/// Writes one JSON object with `fields` in the order given, which a
+/// `serde_json::Map` would sort
+fn write_json_line(
+    writer: &mut impl Write,
+    fields: &[&str],
+    values: &[serde_json::Value],
+) -> io::Result<()> {
+    let members: Vec<String> = fields
+        .iter()
+        .zip(values)
+        .map(|(field, value)| format!("\"{field}\":{value}"))
+        .collect();
+    writeln!(writer, "{{{}}}", members.join(","))
+}
@@ -0,0 +1,83 @@
+use super::style;
+use crate::config::AppConfig;
+use crate::history::{self, Entry, Event};
+use crate::util::error::AppError;
+use std::path::Path;
+
+/// Prints the profile switches, turbo changes and error bursts recorded in the
+/// last `since`, oldest first, or with `json` every entry including the
+/// periodic samples as a JSON array
+pub fn run_history(
+    config: &AppConfig,
+    since: jiff::SignedDuration,
+    json: bool,
+) -> Result<(), AppError> {
+    let path = Path::new(&config.daemon.history_file);
+    let start = jiff::Timestamp::now()
+        .checked_sub(since)
+        .map_err(|e| AppError::Usage(format!("Invalid --since: {e}")))?;
+    let entries = history::read(path, start).map_err(|e| {
+        let hint = if config.daemon.history_enabled {
+            ""
+        } else {
+            " (daemon.history_enabled is false)"
+        };
+        AppError::Generic(format!(
+            "Failed to read history from {}: {e}{hint}",
+            path.display()
+        ))
+    })?;
+
+    if json {
+        return super::print_json(&entries);
+    }
+
+    let lines: Vec<String> = entries.iter().filter_map(describe).collect();
+    if lines.is_empty() {
+        println!(
+            "No profile switches, turbo changes or errors since {}",
+            local_time(start)
+        );
+        return Ok(());
+    }
+    for line in lines {
+        println!("{line}");
+    }
+    Ok(())
+}
+
+/// One timeline line for `entry`, `None` for samples
+fn describe(entry: &Entry) -> Option<String> {
+    let what = match &entry.event {
+        Event::Sample { .. } => return None,
+        Event::ProfileSwitch { from, to, trigger } => {
+            let trigger = history::describe_trigger(trigger);
+            match from {
+                Some(from) => format!("Profile  {from} -> {to} ({trigger})"),
+                None => format!("Profile  {to} ({trigger})"),
+            }
+        }
+        Event::Turbo { enabled, reason } => format!(
+            "Turbo    {} ({})",
+            if *enabled { "enabled" } else { "disabled" },
+            reason.replace('_', " ")
+        ),
+        Event::Errors {
+            count,
+            until,
+            message,
+        } => style::error(format!(
+            "Errors   {count} failed {} until {}: {message}",
+            if *count == 1 { "apply" } else { "applies" },
+            local_time(*until)
+        )),
+    };
+    Some(format!("{}  {what}", local_time(entry.at)))
+}
+
+fn local_time(timestamp: jiff::Timestamp) -> String {
+    timestamp
+        .to_zoned(jiff::tz::TimeZone::system())
+        .strftime("%Y-%m-%d %H:%M:%S")
+        .to_string()
+}
@@ -0,0 +1,373 @@
+use crate::capabilities::Capabilities;
+use crate::config::{self, AppConfig, AppConfigToml};
+use crate::monitor;
+use crate::util::error::AppError;
+use clap::ValueEnum;
+use std::fs;
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+
+/// What the generated configuration is tuned for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Preset {
+    /// A laptop that should last as long as possible on battery
+    LaptopBattery,
+    /// A laptop that is fast on AC and frugal on battery
+    LaptopBalanced,
+    /// A laptop that should be fast, on battery too
+    LaptopPerformance,
+    /// A machine without a battery
+    Desktop,
+}
+
+impl Preset {
+    const fn describe(self) -> &'static str {
+        match self {
+            Self::LaptopBattery => "battery life",
+            Self::LaptopBalanced => "a balance of battery life and performance",
+            Self::LaptopPerformance => "performance",
+            Self::Desktop => "a desktop",
+        }
+    }
+}
+
+/// Whether to stop charging early, from `--charge-thresholds`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChargeThresholds {
+    Off,
+    /// Start charging below the first percentage, stop at the second
+    Between(u8, u8),
+}
+
+/// The answers `watt init` asks for, each of which can be given as a flag
+#[derive(Debug, Clone, Default)]
+pub struct InitOptions {
+    pub preset: Option<Preset>,
+    pub charge_thresholds: Option<ChargeThresholds>,
+    pub platform_profile: Option<bool>,
+    pub out: Option<PathBuf>,
+    /// Replace an existing file without asking
+    pub force: bool,
+    /// Print the configuration rather than writing it
+    pub print: bool,
+}
+
+/// Parses `--charge-thresholds`: START-STOP, e.g. 40-80, or off
+pub fn parse_charge_thresholds(value: &str) -> Result<ChargeThresholds, String> {
+    if value.eq_ignore_ascii_case("off") {
+        return Ok(ChargeThresholds::Off);
+    }
+    let invalid = || format!("Expected START-STOP, e.g. 40-80, or off, got '{value}'");
+    let (start, stop) = value.split_once('-').ok_or_else(invalid)?;
+    let start: u8 = start.trim().parse().map_err(|_| invalid())?;
+    let stop: u8 = stop.trim().parse().map_err(|_| invalid())?;
+    config::BatteryChargeThresholds::new(start, stop).map_err(|e| e.to_string())?;
+    Ok(ChargeThresholds::Between(start, stop))
+}
+
+/// Writes a configuration for this machine, asking what it should be tuned
+/// for
+///
+/// Probes what the system supports, so the file only sets what can be
+/// applied. Questions answered by a flag are not asked, and none are when
+/// stdin is not a terminal: the rest fall back to defaults. An existing file
+/// is only replaced after confirming, or with `--force`.
+pub fn run_init(options: InitOptions) -> Result<(), AppError> {
+    let interactive = io::stdin().is_terminal();
+    let capabilities = Capabilities::probe();
+    let has_battery = monitor::get_battery_info(&AppConfig::default())
+        .is_ok_and(|batteries| !batteries.is_empty());
+
+    let preset = match options.preset {
+        Some(preset) => preset,
+        None if !has_battery => Preset::Desktop,
+        None if interactive => ask_preset()?,
+        None => Preset::LaptopBalanced,
+    };
+
+    let thresholds = match options.charge_thresholds {
+        Some(ChargeThresholds::Off) => None,
+        Some(ChargeThresholds::Between(start, stop)) => Some((start, stop)),
+        None if capabilities.charge_thresholds.is_empty() => None,
+        None if interactive => ask_charge_thresholds()?,
+        None => None,
+    };
+    if thresholds.is_some() && capabilities.charge_thresholds.is_empty() {
+        eprintln!("No battery here supports charge thresholds, so they will be skipped");
+    }
+
+    let platform_profile = match options.platform_profile {
+        Some(manage) => manage,
+        None if capabilities.platform_profiles.is_empty() => false,
+        None if interactive => ask_yes_no(
+            &format!(
+                "Manage the platform profile ({})?",
+                capabilities.platform_profiles.join(", ")
+            ),
+            true,
+        )?,
+        None => true,
+    };
+
+    let toml = generate(&capabilities, preset, thresholds, platform_profile);
+    // Anything that does not parse back is a bug here, not in the answers
+    toml::from_str::<AppConfigToml>(&toml)
+        .map_err(|e| AppError::Generic(format!("Generated an invalid configuration: {e}")))?;
+
+    if options.print {
+        print!("{toml}");
+        return Ok(());
+    }
+
+    let path = options.out.unwrap_or_else(|| {
+        config::config_paths()
+            .into_iter()
+            .next()
+            .unwrap_or_else(|| PathBuf::from("/etc/watt.toml"))
+    });
+    println!("{toml}");
+    let replace = path.exists() && !options.force;
+    if replace && !interactive {
+        return Err(AppError::Usage(format!(
+            "{} exists. Pass --force to replace it",
+            path.display()
+        )));
+    }
+    if interactive {
+        let confirmed = if replace {
+            ask_yes_no(&format!("{} exists. Replace it?", path.display()), false)?
+        } else {
+            ask_yes_no(&format!("Write this to {}?", path.display()), true)?
+        };
+        if !confirmed {
+            println!("Nothing written");
+            return Ok(());
+        }
+    }
+
+    write(&path, &toml)?;
+    println!("Wrote {}", path.display());
+    println!("Check it with `watt doctor`, then start `watt daemon` or run `watt apply`");
+    Ok(())
+}
+
+fn ask(question: &str) -> Result<String, AppError> {
+    print!("{question} ");
+    io::stdout().flush()?;
+    let mut answer = String::new();
+    if io::stdin().lock().read_line(&mut answer)? == 0 {
+        return Err(AppError::Usage(
+            "Stopped, stdin was closed before all questions were answered".to_string(),
+        ));
+    }
+    Ok(answer.trim().to_string())
+}
+
+fn ask_yes_no(question: &str, default: bool) -> Result<bool, AppError> {
+    let hint = if default { "[Y/n]" } else { "[y/N]" };
+    loop {
+        match ask(&format!("{question} {hint}"))?.to_lowercase().as_str() {
+            "" => return Ok(default),
+            "y" | "yes" => return Ok(true),
+            "n" | "no" => return Ok(false),
+            _ => println!("Answer y or n"),
+        }
+    }
+}
+
+fn ask_preset() -> Result<Preset, AppError> {
+    println!("What should Watt optimize for?");
+    let choices = [
+        Preset::LaptopBattery,
+        Preset::LaptopBalanced,
+        Preset::LaptopPerformance,
+    ];
+    for (number, preset) in choices.iter().enumerate() {
+        println!("  {}) {}", number + 1, preset.describe());
+    }
+    loop {
+        let answer = ask("Choice [2]:")?;
+        if answer.is_empty() {
+            return Ok(Preset::LaptopBalanced);
+        }
+        match answer.parse::<usize>() {
+            Ok(number) if (1..=choices.len()).contains(&number) => return Ok(choices[number - 1]),
+            _ => println!("Answer 1, 2 or 3"),
+        }
+    }
+}
+
+fn ask_charge_thresholds() -> Result<Option<(u8, u8)>, AppError> {
+    if !ask_yes_no(
+        "Stop charging early to extend the battery's lifespan?",
+        false,
+    )? {
+        return Ok(None);
+    }
+    loop {
+        let answer = ask("Start and stop charging at [40-80]:")?;
+        let answer = if answer.is_empty() { "40-80" } else { &answer };
+        match parse_charge_thresholds(answer) {
+            Ok(ChargeThresholds::Off) => return Ok(None),
+            Ok(ChargeThresholds::Between(start, stop)) => return Ok(Some((start, stop))),
+            Err(e) => println!("{e}"),
+        }
+    }
+}
+
+/// The first of `wanted` that `available` offers
+fn pick(available: &[String], wanted: &[&str]) -> Option<String> {
+    wanted
+        .iter()
+        .find(|value| available.iter().any(|available| available == *value))
+        .map(ToString::to_string)
+}
+
+/// The settings of one profile, as far as the system supports them
+struct ProfileChoice {
+    governor: Option<String>,
+    epp: Option<String>,
+    turbo: Option<&'static str>,
+    platform_profile: Option<String>,
+}
+
+fn profile_choice(
+    capabilities: &Capabilities,
+    governors: &[&str],
+    epp: &[&str],
+    turbo: &'static str,
+    platform_profiles: &[&str],
+    platform_profile: bool,
+) -> ProfileChoice {
+    ProfileChoice {
+        governor: pick(&capabilities.governors(), governors),
+        epp: pick(&capabilities.epp_values(), epp),
+        turbo: capabilities.turbo.map(|_| turbo),
+        platform_profile: platform_profile
+            .then(|| pick(&capabilities.platform_profiles, platform_profiles))
+            .flatten(),
+    }
+}
+
+/// Builds the configuration file. schedutil comes before powersave, which
+/// pins acpi-cpufreq to its lowest frequency, while intel_pstate and
+/// amd-pstate in active mode only offer performance and powersave.
+fn generate(
+    capabilities: &Capabilities,
+    preset: Preset,
+    thresholds: Option<(u8, u8)>,
+    platform_profile: bool,
+) -> String {
+    let choice = |governors: &[&str], epp: &[&str], turbo, profiles: &[&str]| {
+        profile_choice(
+            capabilities,
+            governors,
+            epp,
+            turbo,
+            profiles,
+            platform_profile,
+        )
+    };
+    const FRUGAL: &[&str] = &["schedutil", "powersave"];
+    let (charger, battery) = match preset {
+        Preset::LaptopBattery => (
+            choice(FRUGAL, &["balance_performance"], "auto", &["balanced"]),
+            Some(choice(
+                FRUGAL,
+                &["power", "balance_power"],
+                "never",
+                &["low-power", "quiet"],
+            )),
+        ),
+        Preset::LaptopBalanced => (
+            choice(FRUGAL, &["balance_performance"], "auto", &["balanced"]),
+            Some(choice(
+                FRUGAL,
+                &["balance_power"],
+                "auto",
+                &["low-power", "quiet"],
+            )),
+        ),
+        Preset::LaptopPerformance => (
+            choice(
+                &["performance"],
+                &["performance"],
+                "always",
+                &["performance"],
+            ),
+            Some(choice(
+                FRUGAL,
+                &["balance_performance"],
+                "auto",
+                &["balanced"],
+            )),
+        ),
+        Preset::Desktop => (
+            choice(
+                &["schedutil", "performance"],
+                &["balance_performance"],
+                "auto",
+                &["performance", "balanced"],
+            ),
+            None,
+        ),
+    };
+
+    let mut toml = format!(
+        "# Written by `watt init` for {}. See the README for every setting.\n",
+        preset.describe()
+    );
+    if let Some((start, stop)) = thresholds {
+        toml.push_str(&format!(
+            "\n# Start charging below {start}% and stop at {stop}%\nbattery_charge_thresholds = [{start}, {stop}]\n"
+        ));
+    }
+    write_profile(
+        &mut toml,
+        "charger",
+        "Connected to a power source",
+        &charger,
+    );
+    if let Some(battery) = battery {
+        write_profile(&mut toml, "battery", "On battery power", &battery);
+    }
+    toml
+}
+
+fn write_profile(toml: &mut String, name: &str, comment: &str, choice: &ProfileChoice) {
+    toml.push_str(&format!("\n# {comment}\n[{name}]\n"));
+    match &choice.governor {
+        Some(governor) => toml.push_str(&format!("governor = \"{governor}\"\n")),
+        None => toml.push_str("# No cpufreq governor found, so the default is kept\n"),
+    }
+    if let Some(turbo) = choice.turbo {
+        toml.push_str(&format!("turbo = \"{turbo}\"\n"));
+    }
+    if let Some(epp) = &choice.epp {
+        toml.push_str(&format!("epp = \"{epp}\"\n"));
+    }
+    if let Some(profile) = &choice.platform_profile {
+        toml.push_str(&format!("platform_profile = \"{profile}\"\n"));
+    }
+}
+
+fn write(path: &Path, toml: &str) -> Result<(), AppError> {
+    let result = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|()| fs::write(path, toml));
+    match result {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == io::ErrorKind::PermissionDenied => {
+            Err(AppError::Permission(format!(
+                "Writing {} needs root. Run it with sudo, or pick another path with --out",
+                path.display()
+            )))
+        }
+        Err(e) => Err(AppError::Generic(format!(
+            "Failed to write {}: {e}",
+            path.display()
+        ))),
+    }
+}
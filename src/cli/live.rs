@@ -0,0 +1,107 @@
+use crate::snapshot::Change;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::time::Duration;
+
+/// Most lines of changed settings printed, so the summary fits on one screen
+const MAX_CHANGE_LINES: usize = 12;
+
+/// Prints what a `watt daemon --live` session did: how long each profile was
+/// active and which settings it had changed, as found before restoring them
+pub fn print_summary(
+    duration: Duration,
+    profile_times: &BTreeMap<String, Duration>,
+    changes: &[Change],
+    restore_failures: usize,
+) {
+    println!();
+    println!("Live session ended after {}", format_duration(duration));
+
+    if !profile_times.is_empty() {
+        println!("Time per profile:");
+        for (profile, time) in profile_times {
+            println!("  {profile:<12} {}", format_duration(*time));
+        }
+    }
+
+    if changes.is_empty() {
+        println!("No settings were changed");
+        return;
+    }
+
+    let groups = group_changes(changes);
+    println!("Settings changed during the session:");
+    for group in groups.iter().take(MAX_CHANGE_LINES) {
+        print!(
+            "  {}: {} → {}",
+            group.setting, group.original, group.current
+        );
+        if group.cpus > 1 {
+            print!(" (on {} CPUs)", group.cpus);
+        }
+        println!();
+    }
+    if groups.len() > MAX_CHANGE_LINES {
+        println!("  ... and {} more", groups.len() - MAX_CHANGE_LINES);
+    }
+
+    if restore_failures == 0 {
+        println!("All of them were restored");
+    } else {
+        println!("{restore_failures} settings could not be restored, see the log");
+    }
+}
+
+/// Identical changes of one setting across CPUs
+struct ChangeGroup<'a> {
+    setting: String,
+    original: &'a str,
+    current: &'a str,
+    cpus: usize,
+}
+
+/// Groups per-CPU cpufreq changes by file name and values, so a governor
+/// changed on every CPU takes one line. Other settings keep their full path.
+fn group_changes(changes: &[Change]) -> Vec<ChangeGroup<'_>> {
+    let mut groups: Vec<ChangeGroup<'_>> = Vec::new();
+    for change in changes {
+        let setting =
+            cpufreq_file(&change.path).unwrap_or_else(|| change.path.display().to_string());
+        match groups.iter_mut().find(|group| {
+            group.setting == setting
+                && group.original == change.original
+                && group.current == change.current
+        }) {
+            Some(group) => group.cpus += 1,
+            None => groups.push(ChangeGroup {
+                setting,
+                original: &change.original,
+                current: &change.current,
+                cpus: 1,
+            }),
+        }
+    }
+    groups
+}
+
+/// The file name of a per-CPU cpufreq file, e.g. `scaling_governor`
+fn cpufreq_file(path: &Path) -> Option<String> {
+    let parent = path.parent()?;
+    let cpu = parent.parent()?.file_name()?.to_str()?;
+    if parent.file_name()? != "cpufreq" || cpu.strip_prefix("cpu")?.parse::<u32>().is_err() {
+        return None;
+    }
+    Some(path.file_name()?.to_string_lossy().into_owned())
+}
+
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    let (hours, minutes, seconds) = (secs / 3600, secs / 60 % 60, secs % 60);
+    if hours > 0 {
+        format!("{hours}h {minutes}m {seconds}s")
+    } else if minutes > 0 {
+        format!("{minutes}m {seconds}s")
+    } else {
+        format!("{seconds}s")
+    }
+}
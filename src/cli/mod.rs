@@ -1 +1,129 @@
+use crate::util::error::{AppError, ErrorKind};
+use crate::util::sysfs::{WriteOutcome, WriteRecord};
+use clap::ValueEnum;
+use serde::Serialize;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+pub mod apply;
+pub mod battery;
+pub mod bundle;
+pub mod capabilities;
+pub mod completions;
+pub mod cpu;
 pub mod debug;
+pub mod doctor;
+pub mod explain;
+pub mod export;
+pub mod history;
+pub mod init;
+pub mod live;
+pub mod monitor;
+pub mod overrides;
+pub mod ping;
+pub mod plain;
+pub mod reset;
+pub mod set;
+pub mod simulate;
+pub mod stats;
+pub mod status;
+pub mod statusline;
+pub mod style;
+pub mod turbo;
+pub mod watch;
+
+/// How a failed command reports its error on stderr
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum ErrorFormat {
+    /// Log lines, with a hint where there is one
+    #[default]
+    Text,
+    /// A single JSON object with `code`, `kind`, `message` and `hint`
+    Json,
+}
+
+/// Error as printed by `--error-format json`
+#[derive(Serialize)]
+pub struct ErrorReport {
+    pub code: i32,
+    pub kind: ErrorKind,
+    pub message: String,
+    pub hint: Option<&'static str>,
+}
+
+impl ErrorReport {
+    pub fn new(error: &AppError) -> Self {
+        let kind = error.kind();
+        Self {
+            code: kind.exit_code(),
+            kind,
+            message: error.to_string(),
+            hint: kind.hint(),
+        }
+    }
+}
+
+static PLAIN: AtomicBool = AtomicBool::new(false);
+
+/// Makes [`print_json`] print `key=value` lines instead, for `--plain`
+pub fn set_plain(plain: bool) {
+    PLAIN.store(plain, Ordering::Relaxed);
+}
+
+/// Prints `value` as pretty JSON on stdout, for `--json`, or as plain lines
+/// for `--plain`, see [`plain`]
+pub fn print_json<T: Serialize>(value: &T) -> Result<(), AppError> {
+    let serialize_error =
+        |e: serde_json::Error| AppError::Generic(format!("Failed to serialize output: {e}"));
+    if PLAIN.load(Ordering::Relaxed) {
+        let lines = plain::render(&serde_json::to_value(value).map_err(serialize_error)?);
+        if !lines.is_empty() {
+            println!("{lines}");
+        }
+        return Ok(());
+    }
+    let json = serde_json::to_string_pretty(value).map_err(serialize_error)?;
+    println!("{json}");
+    Ok(())
+}
+
+/// Lines of a table of `writes`: what happened, the file, and its old and new
+/// value
+pub fn write_table(writes: &[WriteRecord]) -> Vec<String> {
+    let path_width = writes
+        .iter()
+        .map(|write| write.path.display().to_string().len())
+        .max()
+        .unwrap_or(0);
+
+    writes
+        .iter()
+        .map(|write| {
+            // Padded before coloring, which would throw the width off
+            let status = match &write.outcome {
+                WriteOutcome::Written => style::ok(format!("{:<12}", "written")),
+                WriteOutcome::WouldWrite => style::warn(format!("{:<12}", "would write")),
+                WriteOutcome::Unchanged => format!("{:<12}", "unchanged"),
+                WriteOutcome::Failed(_) => style::error(format!("{:<12}", "failed")),
+            };
+            let path = write.path.display().to_string();
+            let change = if write.outcome == WriteOutcome::Unchanged {
+                write.value.clone()
+            } else {
+                format!(
+                    "{} → {}",
+                    write.old_value.as_deref().unwrap_or("?"),
+                    write.value
+                )
+            };
+            match &write.outcome {
+                WriteOutcome::Failed(e) => {
+                    format!(
+                        "  {status} {path:<path_width$}  {change} {}",
+                        style::error(format!("({e})"))
+                    )
+                }
+                _ => format!("  {status} {path:<path_width$}  {change}"),
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,205 @@
+use crate::config::AppConfig;
+use crate::core::SystemReport;
+use crate::engine;
+use crate::monitor;
+use crate::util::error::AppError;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+/// CSV columns `--output` writes, one per [`Sample`] field
+const CSV_HEADER: &str =
+    "timestamp,avg_freq_mhz,load_percent,temp_celsius,turbo,battery_percent,power_watts";
+
+/// What one line shows
+struct Sample {
+    at: jiff::Timestamp,
+    avg_freq_mhz: Option<f32>,
+    /// Average CPU usage over all cores
+    load_percent: Option<f32>,
+    temp_celsius: Option<f32>,
+    turbo: Option<bool>,
+    battery_percent: Option<f32>,
+    /// Battery power, with the sign the driver reports
+    power_watts: Option<f32>,
+}
+
+impl Sample {
+    fn from_report(report: &SystemReport) -> Self {
+        let mean = |values: Vec<f32>| {
+            (!values.is_empty()).then(|| values.iter().sum::<f32>() / values.len() as f32)
+        };
+        let powers: Vec<f32> = report
+            .batteries
+            .iter()
+            .filter_map(|battery| battery.power_rate_watts)
+            .collect();
+        Self {
+            at: jiff::Timestamp::now(),
+            avg_freq_mhz: mean(
+                report
+                    .cpu_cores
+                    .iter()
+                    .filter_map(|core| core.current_frequency_mhz)
+                    .map(|mhz| mhz as f32)
+                    .collect(),
+            ),
+            load_percent: engine::average_cpu_usage(report),
+            temp_celsius: report.cpu_global.average_temperature_celsius,
+            turbo: report.cpu_global.turbo_status,
+            battery_percent: mean(
+                report
+                    .batteries
+                    .iter()
+                    .filter_map(|battery| battery.capacity_percent)
+                    .map(f32::from)
+                    .collect(),
+            ),
+            power_watts: (!powers.is_empty()).then(|| powers.iter().sum()),
+        }
+    }
+
+    fn line(&self) -> String {
+        let show = |value: Option<f32>, format: &dyn Fn(f32) -> String| {
+            value.map_or_else(|| "-".to_string(), format)
+        };
+        format!(
+            "{}  {:>9}  load {:>6}  {:>7}  turbo {:<3}  battery {:>4}  {:>8}",
+            self.at
+                .to_zoned(jiff::tz::TimeZone::system())
+                .strftime("%H:%M:%S"),
+            show(self.avg_freq_mhz, &|mhz| format!("{mhz:.0} MHz")),
+            show(self.load_percent, &|load| format!("{load:.1}%")),
+            show(self.temp_celsius, &|temp| format!("{temp:.1}°C")),
+            self.turbo
+                .map_or("-", |turbo| if turbo { "on" } else { "off" }),
+            show(self.battery_percent, &|pct| format!("{pct:.0}%")),
+            show(self.power_watts, &|watts| format!("{watts:+.1} W")),
+        )
+    }
+
+    fn csv_row(&self) -> Vec<serde_json::Value> {
+        // Rounded, as f32 readings widened to f64 print with noise digits
+        let number =
+            |value: Option<f32>| value.map(|value| (f64::from(value) * 100.0).round() / 100.0);
+        vec![
+            self.at.to_string().into(),
+            self.avg_freq_mhz.map(f32::round).into(),
+            number(self.load_percent).into(),
+            number(self.temp_celsius).into(),
+            self.turbo.into(),
+            number(self.battery_percent).into(),
+            number(self.power_watts).into(),
+        ]
+    }
+}
+
+/// Lowest, average and highest value of one column
+#[derive(Default)]
+struct Aggregate {
+    min: Option<f32>,
+    max: Option<f32>,
+    sum: f64,
+    count: u32,
+}
+
+impl Aggregate {
+    fn add(&mut self, value: Option<f32>) {
+        let Some(value) = value else {
+            return;
+        };
+        self.min = Some(self.min.map_or(value, |min| min.min(value)));
+        self.max = Some(self.max.map_or(value, |max| max.max(value)));
+        self.sum += f64::from(value);
+        self.count += 1;
+    }
+
+    fn print(&self, name: &str, unit: &str, precision: usize) {
+        let (Some(min), Some(max)) = (self.min, self.max) else {
+            println!("{name:<14} -");
+            return;
+        };
+        let avg = self.sum / f64::from(self.count);
+        println!("{name:<14} {min:.precision$} / {avg:.precision$} / {max:.precision$} {unit}");
+    }
+}
+
+/// Prints a line with frequency, load, temperature, turbo, battery charge and
+/// power draw every `interval`, `count` times or until interrupted, then the
+/// lowest, average and highest value of each
+///
+/// Only reads sysfs, so it needs neither root nor the daemon. With `output`,
+/// every sample is also written to that file as CSV.
+pub fn run_monitor(
+    config: &AppConfig,
+    interval: Duration,
+    count: Option<u64>,
+    output: Option<&Path>,
+) -> Result<(), AppError> {
+    let mut csv = match output {
+        Some(path) => {
+            let mut writer = BufWriter::new(File::create(path).map_err(|e| {
+                AppError::Generic(format!("Failed to create {}: {e}", path.display()))
+            })?);
+            writeln!(writer, "{CSV_HEADER}")?;
+            Some(writer)
+        }
+        None => None,
+    };
+
+    let (stop_tx, stop_rx) = mpsc::channel();
+    ctrlc::set_handler(move || {
+        let _ = stop_tx.send(());
+    })
+    .map_err(|e| AppError::Generic(format!("Error setting Ctrl-C handler: {e}")))?;
+
+    let mut aggregates: [Aggregate; 5] = Default::default();
+    let mut taken = 0;
+    let mut next = Instant::now();
+    loop {
+        let report = monitor::collect_system_report(config)?;
+        let sample = Sample::from_report(&report);
+        println!("{}", sample.line());
+        if let Some(writer) = &mut csv {
+            super::export::write_csv_row(writer, &sample.csv_row())?;
+            // Kept complete on disk, in case the process is killed
+            writer.flush()?;
+        }
+        for (aggregate, value) in aggregates.iter_mut().zip([
+            sample.avg_freq_mhz,
+            sample.load_percent,
+            sample.temp_celsius,
+            sample.battery_percent,
+            sample.power_watts,
+        ]) {
+            aggregate.add(value);
+        }
+
+        taken += 1;
+        if count.is_some_and(|count| taken >= count) {
+            break;
+        }
+        // Sampling takes a while itself, so wait for the next slot rather
+        // than a whole interval, unless sampling took longer than that
+        next = (next + interval).max(Instant::now());
+        let wait = next.saturating_duration_since(Instant::now());
+        if stop_rx.recv_timeout(wait).is_ok() {
+            break;
+        }
+    }
+
+    let [freq, load, temp, battery, power] = &aggregates;
+    println!();
+    println!("{taken} samples, min / avg / max:");
+    freq.print("Frequency", "MHz", 0);
+    load.print("Load", "%", 1);
+    temp.print("Temperature", "°C", 1);
+    battery.print("Battery", "%", 0);
+    power.print("Power", "W", 1);
+    if let Some(path) = output {
+        println!("Wrote {taken} samples to {}", path.display());
+    }
+    Ok(())
+}
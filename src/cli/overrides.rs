@@ -0,0 +1,286 @@
+use super::style;
+use crate::config::AppConfig;
+use crate::cpu;
+use crate::engine;
+use crate::ipc;
+use crate::monitor;
+use crate::overrides::{self, OverrideKnob, PowerScope, TimedOverride};
+use crate::pidfile;
+use crate::privileges;
+use crate::util::error::AppError;
+use clap::ValueEnum;
+use serde::Serialize;
+use std::path::Path;
+
+/// What `watt override` does
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OverrideAction {
+    /// Use this governor for a while
+    Governor,
+    /// Use this EPP for a while
+    Epp,
+    /// Switch turbo on or off for a while
+    Turbo,
+    /// Cap the maximum frequency at this many MHz for a while
+    MaxFreq,
+    /// End one override, or all of them, before their time is up
+    Clear,
+    /// Show the active overrides and how long they have left
+    List,
+}
+
+impl OverrideAction {
+    const fn knob(self) -> Option<OverrideKnob> {
+        match self {
+            Self::Governor => Some(OverrideKnob::Governor),
+            Self::Epp => Some(OverrideKnob::Epp),
+            Self::Turbo => Some(OverrideKnob::Turbo),
+            Self::MaxFreq => Some(OverrideKnob::MaxFreq),
+            Self::Clear | Self::List => None,
+        }
+    }
+}
+
+/// An override as `watt override list --json` prints it
+#[derive(Serialize)]
+struct ListedOverride<'a> {
+    #[serde(flatten)]
+    entry: &'a TimedOverride,
+    remaining_sec: i64,
+}
+
+/// Sets, clears or lists time-limited overrides of single settings
+///
+/// With a daemon running, overrides are registered with it, so they are
+/// applied on top of whichever profile is selected and end on their own.
+/// Without one, the setting is written right away and recorded in
+/// [`overrides::OVERRIDES_PATH`], which `watt apply` honors until it expires.
+pub fn run_override(
+    config: &AppConfig,
+    action: OverrideAction,
+    value: Option<String>,
+    duration: Option<jiff::SignedDuration>,
+    until: Option<jiff::Timestamp>,
+    scope: Option<PowerScope>,
+    json: bool,
+) -> Result<(), AppError> {
+    if json && action != OverrideAction::List {
+        return Err(AppError::Usage(
+            "--json only applies to `watt override list`".to_string(),
+        ));
+    }
+    if (duration.is_some() || until.is_some()) && action.knob().is_none() {
+        return Err(AppError::Usage(
+            "--for and --until only apply when setting an override".to_string(),
+        ));
+    }
+    if scope.is_some() && action == OverrideAction::List {
+        return Err(AppError::Usage(
+            "--on only applies when setting or clearing an override".to_string(),
+        ));
+    }
+    let daemon_pid = pidfile::running_daemon_pid(Path::new(&config.daemon.pid_file));
+
+    match action {
+        OverrideAction::List => {
+            if value.is_some() {
+                return Err(AppError::Usage(
+                    "`watt override list` takes no value".to_string(),
+                ));
+            }
+            list(daemon_pid.is_some(), json)
+        }
+        OverrideAction::Clear => {
+            let knob = value
+                .map(|value| {
+                    OverrideKnob::from_str(&value, true).map_err(|_| {
+                        AppError::Usage(format!(
+                            "Unknown override '{value}'. Expected one of: governor, epp, turbo, max-freq"
+                        ))
+                    })
+                })
+                .transpose()?;
+            clear(knob, scope, daemon_pid.is_some())
+        }
+        _ => {
+            let knob = action.knob().unwrap_or(OverrideKnob::Governor);
+            let Some(value) = value else {
+                return Err(AppError::Usage(format!(
+                    "The {knob} override needs a value, e.g. `watt override {knob} {} --for 30m`",
+                    example_value(knob)
+                )));
+            };
+            // The daemon works out the expiry for a duration itself
+            let (duration_sec, expiry, end) = match (duration, until) {
+                (Some(duration), _) => match u64::try_from(duration.as_secs()) {
+                    Ok(secs) if secs > 0 => (
+                        Some(secs),
+                        overrides::expiry_after(duration).map_err(AppError::Usage)?,
+                        format!("for {duration:#}"),
+                    ),
+                    _ => {
+                        return Err(AppError::Usage(format!(
+                            "Override duration must be at least a second, got {duration:#}"
+                        )));
+                    }
+                },
+                (None, Some(until)) => (None, until, format!("until {until}")),
+                (None, None) => {
+                    return Err(AppError::Usage(
+                        "Overrides end on their own, so they need a duration or an expiry, \
+                         e.g. `--for 30m` or `--until 2026-01-01T18:00:00Z`"
+                            .to_string(),
+                    ));
+                }
+            };
+
+            let on = scope
+                .map(|scope| format!(" while on {scope}"))
+                .unwrap_or_default();
+            if daemon_pid.is_some() {
+                ipc::send_command(&ipc::Request::SetOverride {
+                    knob,
+                    value: value.clone(),
+                    duration_sec,
+                    until,
+                    scope,
+                })
+                .map_err(|e| AppError::daemon("Failed to set the override", &e))?;
+                println!("Overriding {knob} with {value}{on} {end}");
+            } else {
+                let entry =
+                    TimedOverride::new(knob, &value, expiry, scope).map_err(AppError::Usage)?;
+                set_without_daemon(config, &entry)?;
+                println!("Overriding {knob} with {}{on} {end}", entry.value);
+                println!(
+                    "No Watt daemon is running, so nothing restores the profile's setting when \
+                     the time is up; the next `watt apply` after that does"
+                );
+            }
+            Ok(())
+        }
+    }
+}
+
+const fn example_value(knob: OverrideKnob) -> &'static str {
+    match knob {
+        OverrideKnob::Governor => "performance",
+        OverrideKnob::Epp => "power",
+        OverrideKnob::Turbo => "off",
+        OverrideKnob::MaxFreq => "2000",
+    }
+}
+
+/// Writes the setting and records the override, which needs root. One for
+/// the other power source is only recorded, for `watt apply` to pick up.
+fn set_without_daemon(config: &AppConfig, entry: &TimedOverride) -> Result<(), AppError> {
+    require_root()?;
+    let on_ac = engine::is_on_ac_power(&monitor::collect_system_report(config)?);
+    if !entry.applies(on_ac) {
+        let mut active = overrides::load();
+        overrides::insert(&mut active, entry.clone());
+        return save(&active);
+    }
+    match entry.knob {
+        OverrideKnob::Governor => cpu::set_governor(&entry.value, None),
+        OverrideKnob::Epp => cpu::set_epp(&entry.value, None),
+        OverrideKnob::Turbo => entry.turbo_setting().map_or(Ok(()), cpu::set_turbo),
+        OverrideKnob::MaxFreq => entry
+            .value
+            .parse()
+            .map_or(Ok(()), |mhz| cpu::set_max_frequency(mhz, None)),
+    }
+    .map_err(AppError::Control)?;
+
+    let mut active = overrides::load();
+    overrides::insert(&mut active, entry.clone());
+    save(&active)
+}
+
+fn clear(
+    knob: Option<OverrideKnob>,
+    scope: Option<PowerScope>,
+    daemon_running: bool,
+) -> Result<(), AppError> {
+    let cleared = if daemon_running {
+        ipc::clear_overrides(knob, scope)
+            .map_err(|e| AppError::daemon("Failed to clear the overrides", &e))?
+    } else {
+        let mut active = overrides::load();
+        let before = active.len();
+        active.retain(|entry| !entry.cleared_by(knob, scope));
+        let cleared = before - active.len();
+        if cleared > 0 {
+            require_root()?;
+            save(&active)?;
+        }
+        cleared
+    };
+
+    let on = scope
+        .map(|scope| format!(" on {scope}"))
+        .unwrap_or_default();
+    match (cleared, knob) {
+        (0, Some(knob)) => println!("No {knob} override{on} was set"),
+        (0, None) => println!("No override{on} was set"),
+        (1, Some(knob)) => println!("Cleared the {knob} override{on}"),
+        (cleared, Some(knob)) => println!("Cleared {cleared} {knob} overrides{on}"),
+        (cleared, None) => println!("Cleared {cleared} override(s){on}"),
+    }
+    if cleared > 0 && !daemon_running {
+        println!("Run `watt apply` to restore the profile's settings");
+    }
+    Ok(())
+}
+
+fn list(daemon_running: bool, json: bool) -> Result<(), AppError> {
+    // The daemon's own list, in case the file could not be written
+    let active = match daemon_running.then(ipc::fetch_status).and_then(Result::ok) {
+        Some(status) => status.overrides,
+        None => overrides::load(),
+    };
+
+    if json {
+        let listed: Vec<ListedOverride> = active
+            .iter()
+            .map(|entry| ListedOverride {
+                entry,
+                remaining_sec: entry.remaining().as_secs(),
+            })
+            .collect();
+        return super::print_json(&listed);
+    }
+
+    if active.is_empty() {
+        println!("No active overrides");
+        return Ok(());
+    }
+    let width = active
+        .iter()
+        .map(|entry| entry.knob.to_string().len() + entry.describe_scope().len())
+        .max()
+        .unwrap_or(0);
+    for entry in &active {
+        println!(
+            "{:<width$}  {}  {:#} left, until {}",
+            format!("{}{}", entry.knob, entry.describe_scope()),
+            style::warn(&entry.value),
+            entry.remaining(),
+            entry.until
+        );
+    }
+    Ok(())
+}
+
+fn require_root() -> Result<(), AppError> {
+    privileges::require_sysfs_access("Overriding settings without a running Watt daemon")
+}
+
+fn save(active: &[TimedOverride]) -> Result<(), AppError> {
+    overrides::save(active).map_err(|e| {
+        AppError::Generic(format!(
+            "Failed to write {}: {e}",
+            overrides::OVERRIDES_PATH
+        ))
+    })
+}
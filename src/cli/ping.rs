@@ -0,0 +1,37 @@
+use crate::ipc;
+use crate::util::error::AppError;
+use std::time::Instant;
+
+/// Checks that the daemon answers on its control socket and has finished a
+/// cycle within the last `max_staleness_sec` seconds
+///
+/// Prints the round-trip time and how long ago the last cycle and the last
+/// successful apply were. Returns an error if the daemon cannot be reached or
+/// its last cycle is too old.
+pub fn run_ping(max_staleness_sec: u64) -> Result<(), AppError> {
+    let start = Instant::now();
+    let pong = ipc::ping().map_err(|e| AppError::daemon("Daemon is not reachable", &e))?;
+    let round_trip = start.elapsed();
+
+    let now = jiff::Timestamp::now();
+    let age = |timestamp: jiff::Timestamp| now.duration_since(timestamp).as_secs().max(0);
+    let describe = |timestamp: Option<jiff::Timestamp>| {
+        timestamp.map_or_else(|| "never".to_string(), |t| format!("{}s ago", age(t)))
+    };
+
+    println!(
+        "Daemon answered in {:.1} ms, last cycle {}, last successful apply {}",
+        round_trip.as_secs_f64() * 1000.0,
+        describe(pong.last_cycle),
+        describe(pong.last_successful_apply)
+    );
+
+    // Before its first cycle, the daemon gets as long as a cycle may take
+    let staleness = age(pong.last_cycle.unwrap_or(pong.started_at));
+    if staleness.unsigned_abs() > max_staleness_sec {
+        return Err(AppError::Generic(format!(
+            "Daemon has not finished a cycle for {staleness}s, more than the allowed {max_staleness_sec}s"
+        )));
+    }
+    Ok(())
+}
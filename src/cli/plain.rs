@@ -0,0 +1,271 @@
+// `--plain` output, derived from the same structures as `--json`
+//
+// Keys are the JSON field names, nested ones joined with dots and array
+// elements numbered from 0, e.g. `cpu_cores.0.usage`. A unit suffix is split
+// off the key into a `_unit` key of its own, so `usage_percent: 12.5` becomes
+// `usage=12.5` and `usage_unit=%`. A list of flat records, like `watt cpu`
+// prints, becomes tab-separated columns under a header line instead.
+use serde_json::{Map, Value};
+
+/// Key suffixes that name a unit, and the unit
+const UNITS: &[(&str, &str)] = &[
+    ("_mhz", "MHz"),
+    ("_celsius", "°C"),
+    ("_percent", "%"),
+    ("_watts", "W"),
+    ("_wh", "Wh"),
+    ("_w", "W"),
+    ("_v", "V"),
+    ("_sec", "s"),
+];
+
+/// Renders `value` as plain lines, without a trailing newline
+pub fn render(value: &Value) -> String {
+    match value {
+        Value::Array(rows) if rows.is_empty() => String::new(),
+        Value::Array(rows) if is_table(rows) => table(rows),
+        _ => {
+            let mut lines = Vec::new();
+            flatten("", value, &mut lines);
+            lines.join("\n")
+        }
+    }
+}
+
+/// Whether `rows` are all objects of scalars or lists of scalars
+fn is_table(rows: &[Value]) -> bool {
+    rows.iter().all(|row| {
+        row.as_object().is_some_and(|fields| {
+            fields.values().all(|field| match field {
+                Value::Object(_) => false,
+                Value::Array(items) => items.iter().all(is_scalar),
+                _ => true,
+            })
+        })
+    })
+}
+
+fn is_scalar(value: &Value) -> bool {
+    !matches!(value, Value::Array(_) | Value::Object(_))
+}
+
+/// Splits a unit suffix off `key`, for a field holding a number
+fn split_unit<'a>(key: &'a str, value: &Value) -> (&'a str, Option<&'static str>) {
+    if !matches!(value, Value::Number(_) | Value::Null) {
+        return (key, None);
+    }
+    UNITS
+        .iter()
+        .find_map(|(suffix, unit)| {
+            key.strip_suffix(suffix)
+                .filter(|stem| !stem.is_empty())
+                .map(|stem| (stem, Some(*unit)))
+        })
+        .unwrap_or((key, None))
+}
+
+/// A scalar, or a list of them separated by spaces. Null is empty, and tabs
+/// and newlines are escaped so every value stays on its line and column.
+fn scalar(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(text) => text
+            .replace('\\', "\\\\")
+            .replace('\t', "\\t")
+            .replace('\n', "\\n"),
+        Value::Array(items) => items.iter().map(scalar).collect::<Vec<_>>().join(" "),
+        // Most readings are f32, widened on the way into a Value; printed as
+        // f32 again they read 0.11 rather than 0.10999999940395355
+        Value::Number(number) => match (number.as_f64(), number.is_f64()) {
+            (Some(float), true) if f64::from(float as f32) == float => (float as f32).to_string(),
+            _ => number.to_string(),
+        },
+        other => other.to_string(),
+    }
+}
+
+fn flatten(prefix: &str, value: &Value, lines: &mut Vec<String>) {
+    let join = |key: &str| {
+        if prefix.is_empty() {
+            key.to_string()
+        } else {
+            format!("{prefix}.{key}")
+        }
+    };
+    match value {
+        Value::Object(fields) => {
+            for (key, field) in fields {
+                let (stem, unit) = split_unit(key, field);
+                flatten(&join(stem), field, lines);
+                if let Some(unit) = unit {
+                    lines.push(format!("{}_unit={unit}", join(stem)));
+                }
+            }
+        }
+        Value::Array(items) if !items.iter().all(is_scalar) => {
+            for (index, item) in items.iter().enumerate() {
+                flatten(&join(&index.to_string()), item, lines);
+            }
+        }
+        _ => lines.push(format!("{prefix}={}", scalar(value))),
+    }
+}
+
+fn table(rows: &[Value]) -> String {
+    let first: &Map<String, Value> = rows[0].as_object().expect("checked by is_table");
+    let mut header = Vec::new();
+    for (key, field) in first {
+        let (stem, unit) = split_unit(key, field);
+        header.push(stem.to_string());
+        if unit.is_some() {
+            header.push(format!("{stem}_unit"));
+        }
+    }
+
+    let mut lines = vec![header.join("\t")];
+    for row in rows.iter().filter_map(Value::as_object) {
+        let mut cells = Vec::new();
+        for (key, field) in first {
+            let value = row.get(key).unwrap_or(&Value::Null);
+            cells.push(scalar(value));
+            if let (_, Some(unit)) = split_unit(key, field) {
+                cells.push(unit.to_string());
+            }
+        }
+        lines.push(cells.join("\t"));
+    }
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::SystemReport;
+    use serde_json::json;
+
+    #[test]
+    fn report_snapshot() {
+        let report = SystemReport::for_snapshots();
+        let value = serde_json::to_value(&report).unwrap();
+        assert_eq!(
+            render(&value),
+            r#"ac_power.online=true
+ac_power.reading=AC online
+ac_power.source=test
+batteries.0.ac_connected=true
+batteries.0.capacity=80
+batteries.0.capacity_unit=%
+batteries.0.charge_start_threshold=40
+batteries.0.charge_stop_threshold=80
+batteries.0.charging_state=Charging
+batteries.0.name=BAT0
+batteries.0.power_rate=15.5
+batteries.0.power_rate_unit=W
+cpu_cores.0.core_id=0
+cpu_cores.0.current_frequency=2400
+cpu_cores.0.current_frequency_unit=MHz
+cpu_cores.0.governor=powersave
+cpu_cores.0.max_frequency=4000
+cpu_cores.0.max_frequency_unit=MHz
+cpu_cores.0.min_frequency=400
+cpu_cores.0.min_frequency_unit=MHz
+cpu_cores.0.temperature=45
+cpu_cores.0.temperature_unit=°C
+cpu_cores.0.usage=12.5
+cpu_cores.0.usage_unit=%
+cpu_global.available_governors=
+cpu_global.average_frequency=
+cpu_global.average_frequency_unit=MHz
+cpu_global.average_temperature=
+cpu_global.average_temperature_unit=°C
+cpu_global.critical_temperature=
+cpu_global.critical_temperature_unit=°C
+cpu_global.current_governor=
+cpu_global.epb=
+cpu_global.epp=
+cpu_global.highest_frequency=
+cpu_global.highest_frequency_unit=MHz
+cpu_global.platform_profile=
+cpu_global.smoothed_temperature=
+cpu_global.smoothed_temperature_unit=°C
+cpu_global.temperature_sensor=
+cpu_global.turbo_status=
+cpu_policies.0.cpus=0
+cpu_policies.0.current_frequency=2400
+cpu_policies.0.current_frequency_unit=MHz
+cpu_policies.0.governor=powersave
+cpu_policies.0.hardware_max_frequency=4800
+cpu_policies.0.hardware_max_frequency_unit=MHz
+cpu_policies.0.hardware_min_frequency=400
+cpu_policies.0.hardware_min_frequency_unit=MHz
+cpu_policies.0.id=0
+cpu_policies.0.max_frequency=4000
+cpu_policies.0.max_frequency_unit=MHz
+cpu_policies.0.min_frequency=400
+cpu_policies.0.min_frequency_unit=MHz
+cpu_power.cores=
+cpu_power.cores_unit=W
+cpu_power.package=
+cpu_power.package_unit=W
+cpu_power.uncore=
+cpu_power.uncore_unit=W
+lid.external_displays=
+lid.lid_closed=
+system_info.architecture=x86_64
+system_info.cpu_model=Test CPU
+system_info.linux_distribution=Test Linux
+system_load.cpu_pressure=
+system_load.load_avg_15min=0
+system_load.load_avg_1min=0
+system_load.load_avg_5min=0
+system_load.smoothed_cpu_usage=
+throttling.core_events=
+throttling.package_events=
+throttling.throttled_cpus=
+throttling.total_events=
+timestamp.nanos_since_epoch=0
+timestamp.secs_since_epoch=1700000000"#
+        );
+    }
+
+    #[test]
+    fn records_become_a_table() {
+        let rows = json!([
+            {"cpu": 0, "governor": "powersave", "frequency_mhz": 2400, "temperature_celsius": 41.5},
+            {"cpu": 1, "governor": "performance", "frequency_mhz": null, "temperature_celsius": 0.11_f32},
+        ]);
+        assert_eq!(
+            render(&rows),
+            concat!(
+                "cpu\tfrequency\tfrequency_unit\tgovernor\ttemperature\ttemperature_unit\n",
+                "0\t2400\tMHz\tpowersave\t41.5\t°C\n",
+                "1\t\tMHz\tperformance\t0.11\t°C"
+            )
+        );
+    }
+
+    #[test]
+    fn nested_values_are_numbered_and_escaped() {
+        let value = json!({
+            "name": "tab\there\nnewline\\",
+            "modes": ["a", "b"],
+            "zones": [{"usage_percent": 12.5}, {"usage_percent": null}],
+            "percent": 3,
+        });
+        assert_eq!(
+            render(&value),
+            r#"modes=a b
+name=tab\there\nnewline\\
+percent=3
+zones.0.usage=12.5
+zones.0.usage_unit=%
+zones.1.usage=
+zones.1.usage_unit=%"#
+        );
+    }
+
+    #[test]
+    fn empty_list_renders_nothing() {
+        assert_eq!(render(&json!([])), "");
+    }
+}
@@ -0,0 +1,128 @@
+use super::apply::{self, ApplyOutcome};
+use super::style;
+use crate::config::AppConfig;
+use crate::control::SysfsController;
+use crate::cpu;
+use crate::daemon::ResetReport;
+use crate::ipc;
+use crate::overrides;
+use crate::pidfile;
+use crate::privileges;
+use crate::snapshot::{SNAPSHOT_PATH, Snapshot};
+use crate::util::error::AppError;
+use crate::util::sysfs::WriteOutcome;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Returns the system to what the configuration asks for and applies the
+/// selected profile in full, printing what was cleared and written
+///
+/// Clears the forced profile, turbo and timed overrides, the force-governor
+/// override and a pause, and forgets the turbo hysteresis and which knobs
+/// were parked or left alone after drifting. With `hard`, the settings
+/// snapshot taken when the daemon started is restored first, which also
+/// undoes `watt set` changes to settings the profile leaves alone.
+///
+/// Goes through the daemon when one runs, otherwise does the same directly,
+/// which needs root.
+pub fn run_reset(config: &AppConfig, hard: bool) -> Result<ApplyOutcome, AppError> {
+    if let Some(pid) = pidfile::running_daemon_pid(Path::new(&config.daemon.pid_file)) {
+        let report =
+            ipc::reset(hard).map_err(|e| AppError::daemon("Failed to reset the daemon", &e))?;
+        println!("Reset through the Watt daemon (PID {pid})");
+        return print_report(report, hard);
+    }
+
+    privileges::require_sysfs_access("Resetting without a running Watt daemon")?;
+
+    let mut cleared = Vec::new();
+    let timed = overrides::load().len();
+    if timed > 0 {
+        overrides::save(&[]).map_err(|e| {
+            AppError::Generic(format!(
+                "Failed to remove {}: {e}",
+                overrides::OVERRIDES_PATH
+            ))
+        })?;
+        cleared.push(if timed == 1 {
+            "1 timed override".to_string()
+        } else {
+            format!("{timed} timed overrides")
+        });
+    }
+    if cpu::clear_governor_override().map_err(AppError::Control)? {
+        cleared.push("force-governor override".to_string());
+    }
+    // The next daemon starts over from the configured initial state
+    match fs::remove_file(&config.daemon.turbo_state_file) {
+        Ok(()) => cleared.push("turbo hysteresis".to_string()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+        Err(e) => {
+            return Err(AppError::Generic(format!(
+                "Failed to remove {}: {e}",
+                config.daemon.turbo_state_file
+            )));
+        }
+    }
+    print_cleared(&cleared);
+
+    if hard {
+        let restored = Snapshot::load().map(|snapshot| {
+            let changed = snapshot.changes().len();
+            let failed = snapshot.restore(&SysfsController);
+            (changed.saturating_sub(failed), failed)
+        });
+        print_restored(restored);
+    }
+
+    apply::run_apply(config, None, None, &SysfsController)
+}
+
+fn print_report(report: ResetReport, hard: bool) -> Result<ApplyOutcome, AppError> {
+    print_cleared(&report.cleared);
+    if hard {
+        print_restored(report.restored);
+    }
+
+    let dry_run = report
+        .writes
+        .iter()
+        .any(|write| write.outcome == WriteOutcome::WouldWrite);
+    println!(
+        "Profile: {}{}",
+        report.profile,
+        if dry_run { ", dry run" } else { "" }
+    );
+    let counts = apply::print_writes(&report.writes, dry_run);
+    match report.error {
+        None if counts.failed == 0 => Ok(ApplyOutcome::Success),
+        None => Ok(ApplyOutcome::Partial),
+        Some(e) if counts.written + counts.unchanged > 0 => {
+            println!("{}", style::error(format!("Stopped early: {e}")));
+            Ok(ApplyOutcome::Partial)
+        }
+        Some(e) => Err(AppError::Generic(format!(
+            "Failed to apply the profile: {e}"
+        ))),
+    }
+}
+
+fn print_cleared(cleared: &[String]) {
+    if cleared.is_empty() {
+        println!("Cleared: nothing, no overrides were set");
+    } else {
+        println!("Cleared: {}", cleared.join(", "));
+    }
+}
+
+fn print_restored(restored: Option<(usize, usize)>) {
+    match restored {
+        None => println!("Restored: nothing, there is no snapshot at {SNAPSHOT_PATH}"),
+        Some((restored, 0)) => println!("Restored: {restored} settings from the snapshot"),
+        Some((restored, failed)) => println!(
+            "Restored: {restored} settings from the snapshot, {}",
+            style::error(format!("{failed} failed"))
+        ),
+    }
+}
@@ -0,0 +1,200 @@
+use crate::config::{AppConfig, DriftPolicy};
+use crate::control::PowerController;
+use crate::core::TurboSetting;
+use crate::cpu;
+use crate::pidfile;
+use crate::privileges;
+use crate::util::error::AppError;
+use crate::util::sysfs;
+use clap::Subcommand;
+use log::{info, warn};
+use std::path::Path;
+
+/// A single setting to write once, outside of any profile
+#[derive(Subcommand, Debug)]
+pub enum Knob {
+    /// CPU governor, e.g. performance or powersave
+    Governor {
+        governor: String,
+        /// Only set it for this logical CPU
+        #[clap(long)]
+        core: Option<u32>,
+    },
+    /// Energy Performance Preference, e.g. balance_power
+    Epp {
+        epp: String,
+        /// Only set it for this logical CPU
+        #[clap(long)]
+        core: Option<u32>,
+    },
+    /// Energy Performance Bias, 0-15 or a name such as balance-power
+    Epb {
+        epb: String,
+        /// Only set it for this logical CPU
+        #[clap(long)]
+        core: Option<u32>,
+    },
+    /// Minimum scaling frequency in MHz
+    MinFreq {
+        freq_mhz: u32,
+        /// Only set it for this logical CPU
+        #[clap(long)]
+        core: Option<u32>,
+    },
+    /// Maximum scaling frequency in MHz
+    MaxFreq {
+        freq_mhz: u32,
+        /// Only set it for this logical CPU
+        #[clap(long)]
+        core: Option<u32>,
+    },
+    /// Turbo boost: always or never
+    Turbo {
+        #[clap(value_enum)]
+        setting: TurboSetting,
+    },
+    /// ACPI platform profile, e.g. low-power or balanced
+    PlatformProfile { profile: String },
+}
+
+impl Knob {
+    /// Name used in messages
+    const fn name(&self) -> &'static str {
+        match self {
+            Self::Governor { .. } => "governor",
+            Self::Epp { .. } => "EPP",
+            Self::Epb { .. } => "EPB",
+            Self::MinFreq { .. } => "minimum frequency",
+            Self::MaxFreq { .. } => "maximum frequency",
+            Self::Turbo { .. } => "turbo",
+            Self::PlatformProfile { .. } => "platform profile",
+        }
+    }
+
+    const fn core(&self) -> Option<u32> {
+        match self {
+            Self::Governor { core, .. }
+            | Self::Epp { core, .. }
+            | Self::Epb { core, .. }
+            | Self::MinFreq { core, .. }
+            | Self::MaxFreq { core, .. } => *core,
+            Self::Turbo { .. } | Self::PlatformProfile { .. } => None,
+        }
+    }
+}
+
+/// Writes one setting right away and prints every file written
+///
+/// Values are checked against what the system supports before anything is
+/// written. Needs root, or `CAP_DAC_OVERRIDE`, since the files belong to root.
+pub fn run_set(
+    config: &AppConfig,
+    knob: &Knob,
+    controller: &dyn PowerController,
+) -> Result<(), AppError> {
+    if !privileges::can_write_sysfs() {
+        return Err(AppError::Permission(format!(
+            "Setting the {} writes to sysfs, which needs root. Run it with sudo, or use \
+             `watt profile` to change what the running daemon applies",
+            knob.name()
+        )));
+    }
+    validate(knob)?;
+
+    sysfs::record_writes();
+    let result = match knob {
+        Knob::Governor { governor, core } => controller.set_governor(governor, *core),
+        Knob::Epp { epp, core } => controller.set_epp(epp, *core),
+        Knob::Epb { epb, core } => controller.set_epb(epb, *core),
+        Knob::MinFreq { freq_mhz, core } => controller.set_min_frequency(*freq_mhz, *core),
+        Knob::MaxFreq { freq_mhz, core } => controller.set_max_frequency(*freq_mhz, *core),
+        Knob::Turbo { setting } => controller.set_turbo(*setting),
+        Knob::PlatformProfile { profile } => controller.set_platform_profile(profile),
+    };
+    let writes = sysfs::take_recorded_writes();
+
+    for line in super::write_table(&writes) {
+        println!("{line}");
+    }
+    result.map_err(AppError::Control)?;
+    if writes.is_empty() {
+        println!(
+            "Nothing to write, the {} is not supported here",
+            knob.name()
+        );
+        return Ok(());
+    }
+
+    if let Some(pid) = pidfile::running_daemon_pid(Path::new(&config.daemon.pid_file)) {
+        let policy = config.daemon.effective_drift_policy();
+        match policy {
+            DriftPolicy::Correct => warn!(
+                "The running Watt daemon (PID {pid}) puts its profile's {} back on its next \
+                 cycle, as drift_policy is \"correct\". Use `watt pause` to keep this one",
+                knob.name()
+            ),
+            DriftPolicy::Warn | DriftPolicy::Respect => info!(
+                "The running Watt daemon (PID {pid}) leaves this {} alone until its next \
+                 profile switch, as drift_policy is \"{}\"",
+                knob.name(),
+                format!("{policy:?}").to_lowercase()
+            ),
+        }
+    }
+    Ok(())
+}
+
+/// Rejects values the system cannot take before anything is written
+fn validate(knob: &Knob) -> Result<(), AppError> {
+    if let Some(core) = knob.core() {
+        let count = cpu::get_logical_core_count().map_err(AppError::Control)?;
+        if core >= count {
+            return Err(AppError::Usage(format!(
+                "CPU {core} does not exist, this system has CPUs 0-{}",
+                count.saturating_sub(1)
+            )));
+        }
+    }
+
+    match knob {
+        Knob::MinFreq { freq_mhz, core } | Knob::MaxFreq { freq_mhz, core } => {
+            validate_freq(*freq_mhz, *core)
+        }
+        Knob::Turbo {
+            setting: TurboSetting::Auto,
+        } => Err(AppError::Usage(
+            "Turbo auto is decided by the daemon every cycle and cannot be set once. Use \
+             always or never"
+                .to_string(),
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Checks `freq_mhz` against the hardware limits of `core`, or of every CPU
+fn validate_freq(freq_mhz: u32, core: Option<u32>) -> Result<(), AppError> {
+    let cores = match core {
+        Some(core) => vec![core],
+        None => (0..cpu::get_logical_core_count().map_err(AppError::Control)?).collect(),
+    };
+
+    for core in cores {
+        let read_mhz = |file: &str| {
+            sysfs::read_sysfs_value(format!("/sys/devices/system/cpu/cpu{core}/cpufreq/{file}"))
+                .ok()?
+                .parse::<u32>()
+                .ok()
+                .map(|khz| khz / 1000)
+        };
+        let (Some(min), Some(max)) = (read_mhz("cpuinfo_min_freq"), read_mhz("cpuinfo_max_freq"))
+        else {
+            continue;
+        };
+        if !(min..=max).contains(&freq_mhz) {
+            return Err(AppError::Usage(format!(
+                "{freq_mhz} MHz is outside what CPU {core} supports: {min}-{max} MHz"
+            )));
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,353 @@
+use crate::config::{AppConfig, ProfileConfig};
+use crate::core::{
+    AcPowerReading, BatteryInfo, CpuCoreInfo, CpuGlobalInfo, CpuPowerInfo, ForcedProfile, LidInfo,
+    SystemInfo, SystemLoad, SystemReport, ThrottleInfo, TurboSetting,
+};
+use crate::cpu;
+use crate::engine;
+use crate::turbo_strategy::TurboMetrics;
+use crate::util::error::AppError;
+use serde::Serialize;
+use std::time::SystemTime;
+
+/// Made-up conditions for `watt simulate`. What is left out is missing from
+/// the report, as when the system cannot read it.
+#[derive(Debug, Clone)]
+pub struct Conditions {
+    /// Average CPU usage, in percent
+    pub load: Option<f32>,
+    /// Average CPU temperature, in °C
+    pub temp: Option<f32>,
+    pub on_battery: bool,
+    pub battery_pct: Option<u8>,
+    /// Whether auto turbo was enabled on the previous cycle
+    pub prev_turbo: Option<bool>,
+    pub force_mode: Option<ForcedProfile>,
+}
+
+/// What the engine would do, as `watt simulate --json` prints it
+#[derive(Serialize)]
+struct Simulation {
+    profile: &'static str,
+    profile_reason: &'static str,
+    governor: Option<String>,
+    turbo: TurboPlan,
+    epp: Option<String>,
+    epb: Option<String>,
+    min_freq_mhz: Option<u32>,
+    max_freq_mhz: Option<u32>,
+    platform_profile: Option<String>,
+    charge_start_threshold: Option<u8>,
+    charge_stop_threshold: Option<u8>,
+}
+
+/// What would happen to turbo
+#[derive(Serialize)]
+struct TurboPlan {
+    /// The profile's turbo setting, if it has one
+    setting: Option<TurboSetting>,
+    /// Whether turbo ends up enabled, when Watt decides it
+    enabled: Option<bool>,
+    /// Why, with the reasons of the `turbo_decision` log event for auto turbo
+    reason: String,
+}
+
+/// Prints the profile the engine would select under `conditions`, the value
+/// each knob would get and what auto turbo would decide, without reading or
+/// writing any sysfs setting
+pub fn run_simulate(
+    config: &AppConfig,
+    conditions: Conditions,
+    json: bool,
+) -> Result<(), AppError> {
+    if conditions
+        .load
+        .is_some_and(|load| !(0.0..=100.0).contains(&load))
+    {
+        return Err(AppError::Usage(
+            "--load is a CPU usage between 0 and 100 percent".to_string(),
+        ));
+    }
+    if conditions.temp.is_some_and(|temp| !temp.is_finite()) {
+        return Err(AppError::Usage("--temp must be a number".to_string()));
+    }
+
+    let report = synthetic_report(&conditions);
+    let force = conditions.force_mode.as_ref();
+    let profile_name = engine::selected_profile_name(&report, force);
+    let profile = match force {
+        Some(ForcedProfile::Named(name)) => engine::named_profile(config, name)?,
+        _ => match profile_name {
+            "charger" => &config.charger,
+            "balanced" => config.balanced(),
+            _ => &config.battery,
+        },
+    };
+    let simulation = Simulation {
+        profile: profile_name,
+        profile_reason: engine::selected_profile_reason(&report, force),
+        governor: governor_plan(&report, profile)?,
+        turbo: turbo_plan(&report, profile, conditions.prev_turbo)?,
+        epp: epp_plan(&report, profile)?,
+        epb: profile.epb.clone(),
+        min_freq_mhz: profile.min_freq_mhz,
+        max_freq_mhz: max_freq_plan(&report, profile)?,
+        platform_profile: profile.platform_profile.clone(),
+        charge_start_threshold: profile.battery_charge_thresholds.as_ref().map(|t| t.start),
+        charge_stop_threshold: profile.battery_charge_thresholds.as_ref().map(|t| t.stop),
+    };
+
+    if json {
+        return super::print_json(&simulation);
+    }
+    print_simulation(&conditions, &simulation);
+    Ok(())
+}
+
+/// A report with one CPU and, when on battery or a charge is given, one
+/// battery
+fn synthetic_report(conditions: &Conditions) -> SystemReport {
+    let batteries = if conditions.on_battery || conditions.battery_pct.is_some() {
+        vec![BatteryInfo {
+            name: "BAT0".to_string(),
+            ac_connected: !conditions.on_battery,
+            charging_state: Some(
+                if conditions.on_battery {
+                    "Discharging"
+                } else {
+                    "Charging"
+                }
+                .to_string(),
+            ),
+            capacity_percent: conditions.battery_pct,
+            power_rate_watts: None,
+            charge_start_threshold: None,
+            charge_stop_threshold: None,
+        }]
+    } else {
+        Vec::new()
+    };
+
+    SystemReport {
+        system_info: SystemInfo {
+            cpu_model: "simulated".to_string(),
+            architecture: std::env::consts::ARCH.to_string(),
+            linux_distribution: "simulated".to_string(),
+        },
+        cpu_cores: vec![CpuCoreInfo {
+            core_id: 0,
+            current_frequency_mhz: None,
+            min_frequency_mhz: None,
+            max_frequency_mhz: None,
+            usage_percent: conditions.load,
+            temperature_celsius: conditions.temp,
+            governor: None,
+        }],
+        cpu_global: CpuGlobalInfo {
+            current_governor: None,
+            available_governors: Vec::new(),
+            turbo_status: conditions.prev_turbo,
+            epp: None,
+            epb: None,
+            platform_profile: None,
+            average_temperature_celsius: conditions.temp,
+            temperature_sensor: None,
+            smoothed_temperature_celsius: None,
+            critical_temperature_celsius: None,
+            average_frequency_mhz: None,
+            highest_frequency_mhz: None,
+        },
+        cpu_policies: Vec::new(),
+        batteries,
+        ac_power: AcPowerReading {
+            source: "simulated".to_string(),
+            reading: if conditions.on_battery {
+                "on battery"
+            } else {
+                "on AC"
+            }
+            .to_string(),
+            online: !conditions.on_battery,
+        },
+        lid: LidInfo::default(),
+        throttling: ThrottleInfo::default(),
+        cpu_power: CpuPowerInfo::default(),
+        system_load: SystemLoad {
+            load_avg_1min: 0.0,
+            load_avg_5min: 0.0,
+            load_avg_15min: 0.0,
+            cpu_pressure: None,
+            smoothed_cpu_usage: None,
+        },
+        timestamp: SystemTime::now(),
+    }
+}
+
+/// The governor the profile sets, with `governor = "auto"` the one picked for
+/// the load, as on the first cycle
+fn governor_plan(
+    report: &SystemReport,
+    profile: &ProfileConfig,
+) -> Result<Option<String>, AppError> {
+    engine::validate_auto_governor(profile, &[])?;
+    if !profile.auto_governor() {
+        return Ok(profile.governor.clone());
+    }
+    let settings = profile.governor_auto_settings.clone().unwrap_or_default();
+    let load = engine::cpu_load(report, settings.load_metric, settings.top_n);
+    Ok(
+        engine::decide_auto_governor(&settings, load, None).map(|(high_load, _)| {
+            if high_load {
+                settings.high_load_governor
+            } else {
+                settings.low_load_governor
+            }
+        }),
+    )
+}
+
+/// The profile's max frequency, or the `thermal_backoff` cap for the
+/// temperature if lower. The cap needs the hardware maximum of this machine.
+fn max_freq_plan(report: &SystemReport, profile: &ProfileConfig) -> Result<Option<u32>, AppError> {
+    let Some(backoff) = &profile.thermal_backoff else {
+        return Ok(profile.max_freq_mhz);
+    };
+    engine::validate_thermal_backoff(backoff)?;
+    let temp = report.cpu_global.average_temperature_celsius;
+    let (Some(step), Ok(hardware_max)) = (
+        engine::decide_thermal_step(backoff, temp, None),
+        cpu::hardware_max_frequency(),
+    ) else {
+        return Ok(profile.max_freq_mhz);
+    };
+    Ok(Some(engine::thermal_cap_mhz(
+        &backoff.steps[step],
+        hardware_max,
+        profile.max_freq_mhz,
+    )))
+}
+
+/// The EPP the profile sets, with `epp = "auto"` the one its tiers pick for
+/// the load, as on the first cycle
+fn epp_plan(report: &SystemReport, profile: &ProfileConfig) -> Result<Option<String>, AppError> {
+    if !profile.auto_epp() {
+        return Ok(profile.epp.clone());
+    }
+    let settings = &profile.epp_auto_settings;
+    engine::validate_epp_auto_settings(settings)?;
+    let load = engine::cpu_load(report, settings.load_metric, settings.top_n);
+    Ok(engine::decide_auto_epp(settings, load, None)
+        .map(|(tier, _)| settings.tiers[tier].epp.clone()))
+}
+
+fn turbo_plan(
+    report: &SystemReport,
+    profile: &ProfileConfig,
+    prev_turbo: Option<bool>,
+) -> Result<TurboPlan, AppError> {
+    let plan = |setting, enabled, reason: &str| TurboPlan {
+        setting,
+        enabled,
+        reason: reason.to_string(),
+    };
+    Ok(match profile.turbo {
+        None => plan(None, None, "not set by the profile, left alone"),
+        Some(setting @ TurboSetting::Always) => {
+            plan(Some(setting), Some(true), "set by the profile")
+        }
+        Some(setting @ TurboSetting::Never) => {
+            plan(Some(setting), Some(false), "set by the profile")
+        }
+        Some(setting @ TurboSetting::Auto) if !profile.enable_auto_turbo => plan(
+            Some(setting),
+            None,
+            "left to the system, since enable_auto_turbo is off",
+        ),
+        Some(setting @ TurboSetting::Auto) => {
+            let settings = &profile.turbo_auto_settings;
+            engine::validate_turbo_auto_settings(settings)?;
+            let previous = prev_turbo.unwrap_or(settings.initial_turbo_state);
+            let on_ac_power = engine::is_on_ac_power(report);
+            let battery_percent = engine::battery_percent(report);
+            let metrics = TurboMetrics {
+                temperature: engine::turbo_temperature(report, settings),
+                load: engine::turbo_load(report, settings),
+                cpu_pressure: report.system_load.cpu_pressure,
+                battery_percent,
+                on_ac_power,
+                low_battery: engine::battery_limits_turbo(
+                    settings,
+                    on_ac_power,
+                    battery_percent,
+                    false,
+                ),
+                throttle_events: report.throttling.events(),
+            };
+            let (enabled, reason) = engine::decide_auto_turbo(settings, &metrics, previous);
+            plan(Some(setting), Some(enabled), reason.as_str())
+        }
+    })
+}
+
+fn print_simulation(conditions: &Conditions, simulation: &Simulation) {
+    println!(
+        "Conditions:        load {}, temperature {}, {}{}",
+        conditions
+            .load
+            .map_or("unknown".to_string(), |load| format!("{load:.1}%")),
+        conditions
+            .temp
+            .map_or("unknown".to_string(), |temp| format!("{temp:.1}°C")),
+        if conditions.on_battery {
+            "on battery"
+        } else {
+            "on AC"
+        },
+        conditions
+            .battery_pct
+            .map_or(String::new(), |pct| format!(" at {pct}%")),
+    );
+    println!(
+        "Profile:           {} ({})",
+        simulation.profile, simulation.profile_reason
+    );
+
+    let show = |value: Option<String>| value.unwrap_or_else(|| "not set".to_string());
+    println!("Governor:          {}", show(simulation.governor.clone()));
+    let turbo = &simulation.turbo;
+    println!(
+        "Turbo:             {}{} ({})",
+        turbo
+            .setting
+            .map_or("not set".to_string(), |setting| format!("{setting:?}")
+                .to_lowercase()),
+        turbo.enabled.map_or(String::new(), |enabled| format!(
+            ", {}",
+            if enabled { "enabled" } else { "disabled" }
+        )),
+        turbo.reason
+    );
+    println!("EPP:               {}", show(simulation.epp.clone()));
+    println!("EPB:               {}", show(simulation.epb.clone()));
+    println!(
+        "Min frequency:     {}",
+        show(simulation.min_freq_mhz.map(|mhz| format!("{mhz} MHz")))
+    );
+    println!(
+        "Max frequency:     {}",
+        show(simulation.max_freq_mhz.map(|mhz| format!("{mhz} MHz")))
+    );
+    println!(
+        "Platform profile:  {}",
+        show(simulation.platform_profile.clone())
+    );
+    println!(
+        "Charge thresholds: {}",
+        show(
+            simulation
+                .charge_start_threshold
+                .zip(simulation.charge_stop_threshold)
+                .map(|(start, stop)| format!("{start}-{stop}%"))
+        )
+    );
+}
@@ -0,0 +1,369 @@
+use crate::config::AppConfig;
+use crate::history::{self, Entry, Event, SAMPLE_INTERVAL};
+use crate::util::error::AppError;
+use clap::ValueEnum;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum StatsAction {
+    /// Aggregate what the daemon recorded over a time range
+    Summary,
+}
+
+/// Parses `--since`, which unlike other durations takes days, e.g. 7d or 30d
+pub fn parse_since(value: &str) -> Result<jiff::SignedDuration, String> {
+    value
+        .parse::<jiff::Span>()
+        .and_then(|span| span.to_duration(jiff::SpanRelativeTo::days_are_24_hours()))
+        .map_err(|e| format!("Expected a duration such as 7d or 12h: {e}"))
+}
+
+/// Entries further apart than this have the daemon stopped between them, so
+/// the time in between is not counted
+const MAX_GAP: jiff::SignedDuration =
+    jiff::SignedDuration::from_secs(2 * SAMPLE_INTERVAL.as_secs() as i64);
+
+/// Aggregates over a time range, as `watt stats summary --json` prints them
+#[derive(Debug, Serialize)]
+struct Summary {
+    since: jiff::Timestamp,
+    until: jiff::Timestamp,
+    /// Time the daemon was running in the range, as far as the history shows
+    covered_sec: u64,
+    /// Time in each profile
+    profiles: BTreeMap<String, Share>,
+    /// Share of the time turbo was enabled, of the time its state was known
+    turbo_on_percent: Option<f32>,
+    temperature: Option<TemperatureRange>,
+    avg_freq_on_ac_mhz: Option<u32>,
+    avg_freq_on_battery_mhz: Option<u32>,
+    /// Energy drawn from the batteries, estimated from their power draw
+    battery_energy_wh: Option<f32>,
+    /// Times auto turbo disabled turbo because the CPU was too hot
+    thermal_events: u32,
+    /// Runs of failed applies, and how many applies failed in them
+    error_bursts: u32,
+    failed_applies: u32,
+    samples: u64,
+}
+
+#[derive(Debug, Serialize)]
+struct Share {
+    time_sec: u64,
+    percent: f32,
+}
+
+#[derive(Debug, Serialize)]
+struct TemperatureRange {
+    avg_celsius: f32,
+    min_celsius: f32,
+    max_celsius: f32,
+}
+
+/// A running mean, minimum and maximum
+#[derive(Debug, Default)]
+struct Mean {
+    sum: f64,
+    count: u64,
+    min: Option<f32>,
+    max: Option<f32>,
+}
+
+impl Mean {
+    fn add(&mut self, value: f32) {
+        self.sum += f64::from(value);
+        self.count += 1;
+        self.min = Some(self.min.map_or(value, |min| min.min(value)));
+        self.max = Some(self.max.map_or(value, |max| max.max(value)));
+    }
+
+    fn mean(&self) -> Option<f64> {
+        (self.count > 0).then(|| self.sum / self.count as f64)
+    }
+}
+
+/// What the last entries said about the system, which holds until the next
+/// one
+#[derive(Debug, Default)]
+struct State {
+    profile: Option<String>,
+    on_ac: Option<bool>,
+    turbo: Option<bool>,
+    battery_draw_watts: Option<f32>,
+}
+
+/// Folds entries into the aggregates one at a time, so a long history is
+/// never held in memory
+#[derive(Debug)]
+struct Accumulator {
+    since: jiff::Timestamp,
+    state: State,
+    last_at: Option<jiff::Timestamp>,
+    covered_sec: f64,
+    profile_sec: BTreeMap<String, f64>,
+    turbo_known_sec: f64,
+    turbo_on_sec: f64,
+    energy_wh: Option<f64>,
+    temperature: Mean,
+    freq_on_ac: Mean,
+    freq_on_battery: Mean,
+    thermal_events: u32,
+    error_bursts: u32,
+    failed_applies: u32,
+    samples: u64,
+}
+
+impl Accumulator {
+    fn new(since: jiff::Timestamp) -> Self {
+        Self {
+            since,
+            state: State::default(),
+            last_at: None,
+            covered_sec: 0.0,
+            profile_sec: BTreeMap::new(),
+            turbo_known_sec: 0.0,
+            turbo_on_sec: 0.0,
+            energy_wh: None,
+            temperature: Mean::default(),
+            freq_on_ac: Mean::default(),
+            freq_on_battery: Mean::default(),
+            thermal_events: 0,
+            error_bursts: 0,
+            failed_applies: 0,
+            samples: 0,
+        }
+    }
+
+    /// Counts the time from the last entry to `to` towards the state the
+    /// last entry left, as far as it lies in the range
+    fn advance(&mut self, to: jiff::Timestamp) {
+        let Some(last_at) = self.last_at.replace(to) else {
+            return;
+        };
+        if last_at.duration_until(to) > MAX_GAP {
+            return;
+        }
+        let from = last_at.max(self.since);
+        if to <= from {
+            return;
+        }
+        let secs = from.duration_until(to).as_secs_f64();
+
+        self.covered_sec += secs;
+        if let Some(profile) = &self.state.profile {
+            *self.profile_sec.entry(profile.clone()).or_default() += secs;
+        }
+        if let Some(turbo) = self.state.turbo {
+            self.turbo_known_sec += secs;
+            if turbo {
+                self.turbo_on_sec += secs;
+            }
+        }
+        if let (Some(false), Some(watts)) = (self.state.on_ac, self.state.battery_draw_watts) {
+            *self.energy_wh.get_or_insert(0.0) += f64::from(watts) * secs / 3600.0;
+        }
+    }
+
+    fn add(&mut self, entry: Entry) {
+        // Error bursts are written when they end, dated to when they started,
+        // so they are out of order and only counted
+        if let Event::Errors { count, until, .. } = entry.event {
+            if until >= self.since {
+                self.error_bursts += 1;
+                self.failed_applies += count;
+            }
+            return;
+        }
+
+        self.advance(entry.at);
+        let in_range = entry.at >= self.since;
+        match entry.event {
+            Event::Sample {
+                profile,
+                on_ac,
+                avg_freq_mhz,
+                cpu_temp,
+                turbo,
+                battery_draw_watts,
+                ..
+            } => {
+                if in_range {
+                    self.samples += 1;
+                    if let Some(temp) = cpu_temp {
+                        self.temperature.add(temp);
+                    }
+                    if let Some(freq) = avg_freq_mhz {
+                        let mean = if on_ac {
+                            &mut self.freq_on_ac
+                        } else {
+                            &mut self.freq_on_battery
+                        };
+                        mean.add(freq as f32);
+                    }
+                }
+                self.state = State {
+                    profile: Some(profile),
+                    on_ac: Some(on_ac),
+                    turbo,
+                    battery_draw_watts,
+                };
+            }
+            Event::ProfileSwitch { to, .. } => self.state.profile = Some(to),
+            Event::Turbo { enabled, reason } => {
+                if in_range && !enabled && reason == "high_temperature" {
+                    self.thermal_events += 1;
+                }
+                self.state.turbo = Some(enabled);
+            }
+            Event::Errors { .. } => unreachable!("handled above"),
+        }
+    }
+
+    fn finish(mut self, until: jiff::Timestamp) -> Summary {
+        // The last state holds until now if the daemon is still running
+        self.advance(until);
+
+        let percent = |part: f64, whole: f64| (100.0 * part / whole) as f32;
+        let covered = self.covered_sec;
+        Summary {
+            since: self.since,
+            until,
+            covered_sec: covered as u64,
+            profiles: self
+                .profile_sec
+                .into_iter()
+                .map(|(profile, secs)| {
+                    let share = Share {
+                        time_sec: secs as u64,
+                        percent: percent(secs, covered),
+                    };
+                    (profile, share)
+                })
+                .collect(),
+            turbo_on_percent: (self.turbo_known_sec > 0.0)
+                .then(|| percent(self.turbo_on_sec, self.turbo_known_sec)),
+            temperature: self.temperature.mean().map(|avg| TemperatureRange {
+                avg_celsius: avg as f32,
+                min_celsius: self.temperature.min.unwrap_or_default(),
+                max_celsius: self.temperature.max.unwrap_or_default(),
+            }),
+            avg_freq_on_ac_mhz: self.freq_on_ac.mean().map(|mhz| mhz.round() as u32),
+            avg_freq_on_battery_mhz: self.freq_on_battery.mean().map(|mhz| mhz.round() as u32),
+            battery_energy_wh: self.energy_wh.map(|wh| wh as f32),
+            thermal_events: self.thermal_events,
+            error_bursts: self.error_bursts,
+            failed_applies: self.failed_applies,
+            samples: self.samples,
+        }
+    }
+}
+
+/// Prints aggregates over the last `since` of the history file: the share of
+/// time in each profile, how often turbo was on, CPU temperatures, average
+/// frequencies on AC and on battery, the energy drawn from the batteries and
+/// the number of thermal and error events
+///
+/// Entries are streamed from the history file and its rotated files, so a
+/// long range is never held in memory.
+pub fn run_stats(
+    config: &AppConfig,
+    action: StatsAction,
+    since: jiff::SignedDuration,
+    json: bool,
+) -> Result<(), AppError> {
+    match action {
+        StatsAction::Summary => run_summary(config, since, json),
+    }
+}
+
+fn run_summary(
+    config: &AppConfig,
+    since: jiff::SignedDuration,
+    json: bool,
+) -> Result<(), AppError> {
+    let path = Path::new(&config.daemon.history_file);
+    let until = jiff::Timestamp::now();
+    let start = until
+        .checked_sub(since)
+        .map_err(|e| AppError::Usage(format!("Invalid --since: {e}")))?;
+
+    let mut accumulator = Accumulator::new(start);
+    let skipped = history::for_each_entry(path, |entry| accumulator.add(entry)).map_err(|e| {
+        let hint = if config.daemon.history_enabled {
+            ""
+        } else {
+            " (daemon.history_enabled is false)"
+        };
+        AppError::Generic(format!(
+            "Failed to read history from {}: {e}{hint}",
+            path.display()
+        ))
+    })?;
+    if skipped > 0 {
+        eprintln!("Skipped {skipped} malformed lines");
+    }
+    let summary = accumulator.finish(until);
+
+    if json {
+        return super::print_json(&summary);
+    }
+    print_summary(&summary, since);
+    Ok(())
+}
+
+fn print_summary(summary: &Summary, since: jiff::SignedDuration) {
+    let hours = |secs: u64| format!("{:.1}h", secs as f64 / 3600.0);
+    println!(
+        "Recorded:          {} of the last {since:#}, {} samples",
+        hours(summary.covered_sec),
+        summary.samples
+    );
+    if summary.profiles.is_empty() {
+        println!("Profiles:          none recorded");
+    }
+    for (i, (profile, share)) in summary.profiles.iter().enumerate() {
+        println!(
+            "{:<19}{profile} {:.1}% ({})",
+            if i == 0 { "Profiles:" } else { "" },
+            share.percent,
+            hours(share.time_sec)
+        );
+    }
+
+    let unknown = || "unknown".to_string();
+    println!(
+        "Turbo on:          {}",
+        summary
+            .turbo_on_percent
+            .map_or_else(unknown, |percent| format!("{percent:.1}% of the time"))
+    );
+    println!(
+        "CPU temperature:   {}",
+        summary
+            .temperature
+            .as_ref()
+            .map_or_else(unknown, |t| format!(
+                "{:.1}°C average, {:.1}°C min, {:.1}°C max",
+                t.avg_celsius, t.min_celsius, t.max_celsius
+            ))
+    );
+    let mhz = |freq: Option<u32>| freq.map_or_else(unknown, |mhz| format!("{mhz} MHz"));
+    println!(
+        "Avg frequency:     {} on AC, {} on battery",
+        mhz(summary.avg_freq_on_ac_mhz),
+        mhz(summary.avg_freq_on_battery_mhz)
+    );
+    println!(
+        "Battery energy:    {}",
+        summary
+            .battery_energy_wh
+            .map_or_else(unknown, |wh| format!("{wh:.1} Wh drawn on battery"))
+    );
+    println!("Thermal events:    {}", summary.thermal_events);
+    println!(
+        "Errors:            {} bursts, {} failed applies",
+        summary.error_bursts, summary.failed_applies
+    );
+}
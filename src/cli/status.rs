@@ -0,0 +1,784 @@
+use super::style;
+use crate::capabilities::compact_ranges;
+use crate::config::AppConfig;
+use crate::core::{BatteryInfo, ForcedProfile, SystemReport};
+use crate::cpu;
+use crate::daemon::{DaemonStatus, ForceEnd, TurboHysteresis};
+use crate::dbus;
+use crate::engine::{self, ApplySummary, DriftedKnob, FrequencyRamp, ParkedKnob};
+use crate::ipc;
+use crate::metrics::Counters;
+use crate::monitor;
+use crate::overrides::TimedOverride;
+use crate::process_boost::BoostStatus;
+use crate::statefile::{self, PolicyState};
+use crate::util::error::AppError;
+use log::debug;
+use serde::Serialize;
+
+/// Services that manage the same settings as Watt and undo its changes
+const CONFLICTING_SERVICES: &[&str] = &["tlp", "power-profiles-daemon", "auto-cpufreq", "tuned"];
+
+/// Everything `watt status` shows. Both the text and the JSON output are
+/// made from it, so they always agree.
+#[derive(Debug, Serialize)]
+pub struct Status {
+    /// "local", "daemon" or "daemon (D-Bus)"
+    pub source: &'static str,
+    pub cpu_model: String,
+    pub scaling_driver: Option<String>,
+    /// Why CPU frequency control is unavailable, if it is
+    pub cpu_control_unavailable: Option<String>,
+    pub policies: Vec<PolicyGroup>,
+    pub turbo: Option<bool>,
+    /// The file turbo is controlled through
+    pub turbo_mechanism: Option<&'static str>,
+    pub platform_profile: Option<String>,
+    pub batteries: Vec<BatteryInfo>,
+    pub active_profile: String,
+    /// Why the active profile was chosen, unknown over D-Bus
+    pub profile_reason: Option<&'static str>,
+    pub temperature_celsius: Option<f32>,
+    /// Smoothed per `daemon.smoothing`, missing without a daemon
+    pub smoothed_temperature_celsius: Option<f32>,
+    /// Average over the cores
+    pub cpu_usage_percent: Option<f32>,
+    /// Smoothed per `daemon.smoothing`, missing without a daemon
+    pub smoothed_cpu_usage_percent: Option<f32>,
+    /// Over 1, 5 and 15 minutes
+    pub load_average: [f32; 3],
+    /// The 1-minute load average divided by the number of online CPUs
+    pub load_per_cpu: Option<f32>,
+    /// Missing without a daemon, or when asking it over D-Bus
+    pub daemon: Option<DaemonDetails>,
+    /// Other software that may fight Watt over the same settings
+    pub conflicts: Vec<String>,
+}
+
+/// cpufreq policies with the same governor, EPP and frequency limits
+#[derive(Debug, Serialize)]
+pub struct PolicyGroup {
+    pub policies: Vec<u32>,
+    pub governor: Option<String>,
+    pub epp: Option<String>,
+    pub min_freq_mhz: Option<u32>,
+    pub max_freq_mhz: Option<u32>,
+    /// Lowest and highest current frequency in the group
+    pub cur_freq_mhz: Option<[u32; 2]>,
+}
+
+/// What only the daemon knows
+#[derive(Debug, Serialize)]
+pub struct DaemonDetails {
+    pub force_mode: Option<String>,
+    pub force_end: Option<ForceEnd>,
+    pub turbo_override: Option<String>,
+    pub turbo_override_end: Option<ForceEnd>,
+    pub overrides: Vec<TimedOverride>,
+    pub paused: bool,
+    pub paused_until: Option<jiff::Timestamp>,
+    pub turbo_hysteresis: Option<TurboHysteresis>,
+    pub process_boost: Option<BoostStatus>,
+    pub frequency_ramp: Option<FrequencyRamp>,
+    pub log_level: Option<String>,
+    pub counters: Option<Counters>,
+    pub parked_knobs: Vec<ParkedKnob>,
+    pub drifted_knobs: Vec<DriftedKnob>,
+    pub paused_knobs: Vec<String>,
+    /// What the last apply did to each knob, once the daemon applied
+    pub last_apply: Option<ApplySummary>,
+}
+
+/// Prints a summary of what Watt sees and which profile is active, as text or
+/// with `json` as a JSON document
+///
+/// When the daemon's control socket is present, the report and active profile
+/// are requested from the daemon, along with state only it knows, such as
+/// turbo hysteresis and counters. Otherwise, or if the daemon does not answer,
+/// they are collected directly from sysfs. With `via_dbus`, the daemon is
+/// queried over the system bus instead.
+pub fn run_status(config: &AppConfig, via_dbus: bool, json: bool) -> Result<(), AppError> {
+    let status = if via_dbus {
+        let (report, active_profile) = dbus::fetch_status().map_err(|e| {
+            AppError::DaemonUnreachable(format!("Failed to query daemon over D-Bus: {e}"))
+        })?;
+        Status::new(&report, active_profile, None, "daemon (D-Bus)")
+    } else if let Some((report, daemon)) = fetch_daemon_status() {
+        let force_mode = daemon
+            .force_mode
+            .as_deref()
+            .and_then(|mode| mode.parse::<ForcedProfile>().ok());
+        let mut status = Status::new(
+            &report,
+            daemon
+                .active_profile
+                .clone()
+                .unwrap_or_else(|| "N/A".to_string()),
+            Some(engine::selected_profile_reason(
+                &report,
+                force_mode.as_ref(),
+            )),
+            "daemon",
+        );
+        status.cpu_control_unavailable = daemon.cpu_control_unavailable.clone();
+        status.daemon = Some(DaemonDetails::from(daemon));
+        status
+    } else {
+        let report = monitor::collect_system_report(config)?;
+        Status::new(
+            &report,
+            engine::selected_profile_name(&report, None).to_string(),
+            Some(engine::selected_profile_reason(&report, None)),
+            "local",
+        )
+    };
+
+    if json {
+        super::print_json(&status)
+    } else {
+        status.print();
+        Ok(())
+    }
+}
+
+/// Report and status from the running daemon, if it answers and has a report
+/// yet
+fn fetch_daemon_status() -> Option<(SystemReport, DaemonStatus)> {
+    if !ipc::socket_available() {
+        return None;
+    }
+    match ipc::fetch_status() {
+        Ok(mut status) => {
+            let report = status.report.take();
+            if report.is_none() {
+                debug!("Daemon has not collected a report yet, reading sysfs directly");
+            }
+            report.map(|report| (report, status))
+        }
+        Err(e) => {
+            debug!("Failed to query daemon, reading sysfs directly: {e}");
+            None
+        }
+    }
+}
+
+impl Status {
+    /// Combines `report` with what is read from sysfs here
+    fn new(
+        report: &SystemReport,
+        active_profile: String,
+        profile_reason: Option<&'static str>,
+        source: &'static str,
+    ) -> Self {
+        Self {
+            source,
+            cpu_model: report.system_info.cpu_model.clone(),
+            scaling_driver: cpu::scaling_driver(),
+            cpu_control_unavailable: cpu::cpufreq_unavailable_reason(),
+            policies: policy_groups(),
+            turbo: report.cpu_global.turbo_status,
+            turbo_mechanism: cpu::turbo_mechanism(),
+            platform_profile: report.cpu_global.platform_profile.clone(),
+            batteries: report.batteries.clone(),
+            active_profile,
+            profile_reason,
+            temperature_celsius: report.cpu_global.average_temperature_celsius,
+            smoothed_temperature_celsius: report.cpu_global.smoothed_temperature_celsius,
+            cpu_usage_percent: engine::average_cpu_usage(report),
+            smoothed_cpu_usage_percent: report.system_load.smoothed_cpu_usage,
+            load_average: [
+                report.system_load.load_avg_1min,
+                report.system_load.load_avg_5min,
+                report.system_load.load_avg_15min,
+            ],
+            load_per_cpu: (!report.cpu_cores.is_empty())
+                .then(|| report.system_load.load_avg_1min / report.cpu_cores.len() as f32),
+            daemon: None,
+            conflicts: conflicts(),
+        }
+    }
+
+    fn print(&self) {
+        println!("Source:           {}", self.source);
+        println!("CPU:              {}", self.cpu_model);
+        println!(
+            "Scaling Driver:   {}",
+            self.scaling_driver
+                .clone()
+                .unwrap_or_else(|| style::warn("none"))
+        );
+        for (i, group) in self.policies.iter().enumerate() {
+            group.print(if i == 0 { "Policies:" } else { "" });
+        }
+        println!(
+            "Turbo:            {}{}",
+            match self.turbo {
+                Some(true) => "Enabled".to_string(),
+                Some(false) => "Disabled".to_string(),
+                None => style::warn("Unknown"),
+            },
+            self.turbo_mechanism
+                .map_or_else(String::new, |mechanism| format!(" ({mechanism})"))
+        );
+        println!(
+            "Platform Profile: {}",
+            self.platform_profile
+                .clone()
+                .unwrap_or_else(|| style::warn("N/A"))
+        );
+        for battery in &self.batteries {
+            println!(
+                "Battery {}:     {} {} ({}){}",
+                battery.name,
+                battery
+                    .capacity_percent
+                    .map_or_else(|| "N/A".to_string(), |c| format!("{c}%")),
+                battery.charging_state.as_deref().unwrap_or("Unknown"),
+                if battery.ac_connected {
+                    "AC"
+                } else {
+                    "battery"
+                },
+                match (
+                    battery.charge_start_threshold,
+                    battery.charge_stop_threshold
+                ) {
+                    (None, None) => String::new(),
+                    (start, stop) => format!(
+                        ", thresholds {}-{}",
+                        start.map_or_else(|| "N/A".to_string(), |t| t.to_string()),
+                        stop.map_or_else(|| "N/A".to_string(), |t| t.to_string())
+                    ),
+                }
+            );
+        }
+        match self.profile_reason {
+            Some(reason) => println!("Active Profile:   {} ({reason})", self.active_profile),
+            None => println!("Active Profile:   {}", self.active_profile),
+        }
+        println!(
+            "Temperature:      {}{}",
+            self.temperature_celsius
+                .map_or_else(|| "N/A".to_string(), |t| format!("{t:.1}°C")),
+            self.smoothed_temperature_celsius
+                .map_or_else(String::new, |t| format!(" (smoothed {t:.1}°C)"))
+        );
+        println!(
+            "CPU Usage:        {}{}",
+            self.cpu_usage_percent
+                .map_or_else(|| "N/A".to_string(), |usage| format!("{usage:.1}%")),
+            self.smoothed_cpu_usage_percent
+                .map_or_else(String::new, |usage| format!(" (smoothed {usage:.1}%)"))
+        );
+        let [load_1min, load_5min, load_15min] = self.load_average;
+        println!(
+            "Load Average:     {load_1min:.2} {load_5min:.2} {load_15min:.2}{}",
+            self.load_per_cpu
+                .map_or_else(String::new, |load| format!(" ({load:.2} per CPU)"))
+        );
+        if let Some(daemon) = &self.daemon {
+            daemon.print();
+        }
+        if let Some(reason) = &self.cpu_control_unavailable {
+            println!(
+                "CPU Control:      {}",
+                style::warn(format!("unavailable, {reason}"))
+            );
+        }
+
+        if self.conflicts.is_empty() {
+            println!("Conflicts:        {}", style::ok("none detected"));
+        }
+        for (i, conflict) in self.conflicts.iter().enumerate() {
+            println!(
+                "{:<18}{}",
+                if i == 0 { "Conflicts:" } else { "" },
+                style::error(conflict)
+            );
+        }
+    }
+}
+
+/// Groups cpufreq policies with the same settings, so large machines do not
+/// show dozens of identical lines
+fn policy_groups() -> Vec<PolicyGroup> {
+    let policies: Vec<PolicyState> = cpu::cpufreq_policies()
+        .iter()
+        .map(|path| statefile::policy_state(path))
+        .collect();
+
+    let mut groups: Vec<Vec<&PolicyState>> = Vec::new();
+    for policy in &policies {
+        let same_settings = |other: &&PolicyState| {
+            other.governor == policy.governor
+                && other.epp == policy.epp
+                && other.min_freq_mhz == policy.min_freq_mhz
+                && other.max_freq_mhz == policy.max_freq_mhz
+        };
+        match groups.iter_mut().find(|group| same_settings(&group[0])) {
+            Some(group) => group.push(policy),
+            None => groups.push(vec![policy]),
+        }
+    }
+
+    groups
+        .into_iter()
+        .map(|group| {
+            let first = group[0];
+            let current = group.iter().filter_map(|policy| policy.cur_freq_mhz);
+            PolicyGroup {
+                policies: group
+                    .iter()
+                    .filter_map(|policy| policy.policy.strip_prefix("policy")?.parse().ok())
+                    .collect(),
+                governor: first.governor.clone(),
+                epp: first.epp.clone(),
+                min_freq_mhz: first.min_freq_mhz,
+                max_freq_mhz: first.max_freq_mhz,
+                cur_freq_mhz: current.clone().min().zip(current.max()).map(Into::into),
+            }
+        })
+        .collect()
+}
+
+impl PolicyGroup {
+    fn print(&self, label: &str) {
+        let mhz = |freq: Option<u32>| freq.map_or_else(|| "N/A".to_string(), |f| f.to_string());
+        println!(
+            "{label:<18}{} {}: {}{}, {}-{} MHz{}",
+            if self.policies.len() == 1 {
+                "policy"
+            } else {
+                "policies"
+            },
+            compact_ranges(&self.policies),
+            self.governor.as_deref().unwrap_or("N/A"),
+            self.epp
+                .as_ref()
+                .map_or_else(String::new, |epp| format!(", EPP {epp}")),
+            mhz(self.min_freq_mhz),
+            mhz(self.max_freq_mhz),
+            match self.cur_freq_mhz {
+                Some([low, high]) if low == high => format!(", now {low} MHz"),
+                Some([low, high]) => format!(", now {low}-{high} MHz"),
+                None => String::new(),
+            }
+        );
+    }
+}
+
+/// Time left until `until`, in whole seconds
+fn remaining(until: jiff::Timestamp) -> jiff::SignedDuration {
+    let left = until.duration_since(jiff::Timestamp::now());
+    jiff::SignedDuration::from_secs(left.as_secs().max(0))
+}
+
+impl From<DaemonStatus> for DaemonDetails {
+    fn from(status: DaemonStatus) -> Self {
+        Self {
+            force_mode: status.force_mode,
+            force_end: status.force_end,
+            turbo_override: status.turbo_override,
+            turbo_override_end: status.turbo_override_end,
+            overrides: status.overrides,
+            paused: status.paused,
+            paused_until: status.paused_until,
+            turbo_hysteresis: status.turbo_hysteresis,
+            process_boost: status.process_boost,
+            frequency_ramp: status.frequency_ramp,
+            log_level: status.log_level,
+            counters: status.counters,
+            parked_knobs: status.parked_knobs,
+            drifted_knobs: status.drifted_knobs,
+            paused_knobs: status.paused_knobs,
+            last_apply: Some(status.last_apply).filter(|summary| !summary.profile.is_empty()),
+        }
+    }
+}
+
+impl DaemonDetails {
+    fn print(&self) {
+        if let Some(force_mode) = &self.force_mode {
+            match self.force_end {
+                Some(ForceEnd::At(until)) => println!(
+                    "Forced Profile:   {force_mode} until {until} ({:#} left)",
+                    remaining(until)
+                ),
+                Some(ForceEnd::PowerChange) => {
+                    println!("Forced Profile:   {force_mode} until the power source changes");
+                }
+                None => println!("Forced Profile:   {force_mode} until changed"),
+            }
+        }
+        if let Some(boost) = &self.process_boost {
+            println!(
+                "Process Boost:    {} running since {}{}",
+                boost.process,
+                boost.since,
+                if boost.turbo { ", turbo kept on" } else { "" }
+            );
+        }
+        if let Some(ramp) = &self.frequency_ramp {
+            println!(
+                "Max Frequency:    ramping: {:.1}→{:.1} GHz ({}%)",
+                f64::from(ramp.from_mhz) / 1000.0,
+                f64::from(ramp.to_mhz) / 1000.0,
+                ramp.percent()
+            );
+        }
+        if let Some(turbo_override) = &self.turbo_override {
+            match self.turbo_override_end {
+                Some(ForceEnd::PowerChange) => {
+                    println!("Turbo Override:   {turbo_override} until the power source changes")
+                }
+                _ => println!("Turbo Override:   {turbo_override}"),
+            }
+        }
+        for entry in &self.overrides {
+            println!(
+                "Override:         {}{} = {} until {} ({:#} left)",
+                entry.knob,
+                entry.describe_scope(),
+                entry.value,
+                entry.until,
+                entry.remaining()
+            );
+        }
+        if let Some(hysteresis) = &self.turbo_hysteresis {
+            let describe = |state: Option<bool>| match state {
+                Some(true) => "enabled",
+                Some(false) => "disabled",
+                None => "unset",
+            };
+            println!(
+                "Turbo Hysteresis: {} on AC, {} on battery",
+                describe(hysteresis.on_ac),
+                describe(hysteresis.on_battery)
+            );
+            for (group, state) in &hysteresis.groups {
+                println!(
+                    "  {group:<15} {} on AC, {} on battery",
+                    describe(state.on_ac),
+                    describe(state.on_battery)
+                );
+            }
+        }
+        if self.paused {
+            match self.paused_until {
+                Some(until) => println!(
+                    "Management:       {}",
+                    style::warn(format!(
+                        "paused until {until} ({:#} left)",
+                        remaining(until)
+                    ))
+                ),
+                None => println!("Management:       {}", style::warn("paused until resumed")),
+            }
+        }
+        if let Some(log_level) = &self.log_level {
+            println!("Log Level:        {log_level}");
+        }
+        if let Some(counters) = &self.counters {
+            println!(
+                "Counters:         {} applies ({} failed), {} knob changes ({} failed), {} writes ({} skipped), {} reloads, {} resumes, {} profile switches since {}",
+                counters.apply_cycles,
+                counters.apply_errors,
+                counters.knob_changes,
+                counters.knob_failures,
+                counters.sysfs_writes,
+                counters.sysfs_writes_skipped,
+                counters.config_reloads,
+                counters.resumes,
+                counters.profile_switches,
+                counters.started_at
+            );
+            if counters.thermal_safety_engagements > 0 {
+                println!(
+                    "Thermal Safety:   {}",
+                    style::warn(format!(
+                        "engaged {} times",
+                        counters.thermal_safety_engagements
+                    ))
+                );
+            }
+        }
+        for parked in &self.parked_knobs {
+            println!(
+                "Parked:           {}",
+                style::warn(format!(
+                    "{} since {}, retrying by {} ({})",
+                    parked.knob, parked.since, parked.retry_at, parked.error
+                ))
+            );
+        }
+        for drifted in &self.drifted_knobs {
+            if self.paused_knobs.contains(&drifted.knob) {
+                continue;
+            }
+            println!(
+                "Drifted:          {} is {}, profile wants {}",
+                drifted.knob,
+                style::error(&drifted.current),
+                style::ok(&drifted.desired)
+            );
+        }
+        for knob in &self.paused_knobs {
+            let current = self
+                .drifted_knobs
+                .iter()
+                .find(|drifted| &drifted.knob == knob)
+                .map(|drifted| drifted.current.as_str());
+            println!(
+                "Paused:           {}",
+                style::warn(format!(
+                    "{knob} changed by hand{}, left alone until the next profile switch or reload",
+                    current
+                        .map(|current| format!(" to {current}"))
+                        .unwrap_or_default()
+                ))
+            );
+        }
+        if let Some(summary) = &self.last_apply {
+            println!("Last Apply:       {summary}");
+        }
+    }
+}
+
+/// What may be fighting Watt over the same settings
+pub fn conflicts() -> Vec<String> {
+    let mut conflicts: Vec<String> = CONFLICTING_SERVICES
+        .iter()
+        .filter(|service| super::debug::is_systemd_service_active(service).unwrap_or(false))
+        .map(|service| format!("{service}.service is active"))
+        .collect();
+    if let Some(governor) = cpu::get_governor_override() {
+        conflicts.push(format!(
+            "governor override '{}' is set, see `watt force-governor`",
+            governor.trim()
+        ));
+    }
+    conflicts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A status with every field that can be null set to null
+    fn status() -> Status {
+        Status {
+            source: "daemon",
+            cpu_model: "Test CPU".to_string(),
+            scaling_driver: None,
+            cpu_control_unavailable: None,
+            policies: vec![PolicyGroup {
+                policies: vec![0, 1],
+                governor: Some("powersave".to_string()),
+                epp: None,
+                min_freq_mhz: Some(400),
+                max_freq_mhz: Some(4000),
+                cur_freq_mhz: None,
+            }],
+            turbo: None,
+            turbo_mechanism: None,
+            platform_profile: None,
+            batteries: SystemReport::for_snapshots().batteries,
+            active_profile: "charger".to_string(),
+            profile_reason: None,
+            temperature_celsius: None,
+            smoothed_temperature_celsius: None,
+            cpu_usage_percent: None,
+            smoothed_cpu_usage_percent: None,
+            load_average: [0.5, 0.25, 0.125],
+            load_per_cpu: None,
+            daemon: Some(DaemonDetails {
+                force_mode: None,
+                force_end: None,
+                turbo_override: None,
+                turbo_override_end: None,
+                overrides: Vec::new(),
+                paused: false,
+                paused_until: None,
+                turbo_hysteresis: None,
+                process_boost: None,
+                frequency_ramp: None,
+                log_level: None,
+                counters: None,
+                parked_knobs: Vec::new(),
+                drifted_knobs: Vec::new(),
+                paused_knobs: Vec::new(),
+                last_apply: None,
+            }),
+            conflicts: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn status_json_schema() {
+        let json = serde_json::to_string_pretty(&status()).unwrap();
+        assert_eq!(
+            json,
+            r#"{
+  "source": "daemon",
+  "cpu_model": "Test CPU",
+  "scaling_driver": null,
+  "cpu_control_unavailable": null,
+  "policies": [
+    {
+      "policies": [
+        0,
+        1
+      ],
+      "governor": "powersave",
+      "epp": null,
+      "min_freq_mhz": 400,
+      "max_freq_mhz": 4000,
+      "cur_freq_mhz": null
+    }
+  ],
+  "turbo": null,
+  "turbo_mechanism": null,
+  "platform_profile": null,
+  "batteries": [
+    {
+      "name": "BAT0",
+      "ac_connected": true,
+      "charging_state": "Charging",
+      "capacity_percent": 80,
+      "power_rate_watts": 15.5,
+      "charge_start_threshold": 40,
+      "charge_stop_threshold": 80
+    }
+  ],
+  "active_profile": "charger",
+  "profile_reason": null,
+  "temperature_celsius": null,
+  "smoothed_temperature_celsius": null,
+  "cpu_usage_percent": null,
+  "smoothed_cpu_usage_percent": null,
+  "load_average": [
+    0.5,
+    0.25,
+    0.125
+  ],
+  "load_per_cpu": null,
+  "daemon": {
+    "force_mode": null,
+    "force_end": null,
+    "turbo_override": null,
+    "turbo_override_end": null,
+    "overrides": [],
+    "paused": false,
+    "paused_until": null,
+    "turbo_hysteresis": null,
+    "process_boost": null,
+    "frequency_ramp": null,
+    "log_level": null,
+    "counters": null,
+    "parked_knobs": [],
+    "drifted_knobs": [],
+    "paused_knobs": [],
+    "last_apply": null
+  },
+  "conflicts": []
+}"#
+        );
+    }
+
+    #[test]
+    fn report_json_schema() {
+        let report = SystemReport::for_snapshots();
+        let json = serde_json::to_string_pretty(&report).unwrap();
+        assert_eq!(
+            json,
+            r#"{
+  "system_info": {
+    "cpu_model": "Test CPU",
+    "architecture": "x86_64",
+    "linux_distribution": "Test Linux"
+  },
+  "cpu_cores": [
+    {
+      "core_id": 0,
+      "current_frequency_mhz": 2400,
+      "min_frequency_mhz": 400,
+      "max_frequency_mhz": 4000,
+      "usage_percent": 12.5,
+      "temperature_celsius": 45.0,
+      "governor": "powersave"
+    }
+  ],
+  "cpu_global": {
+    "current_governor": null,
+    "available_governors": [],
+    "turbo_status": null,
+    "epp": null,
+    "epb": null,
+    "platform_profile": null,
+    "average_temperature_celsius": null,
+    "temperature_sensor": null,
+    "smoothed_temperature_celsius": null,
+    "critical_temperature_celsius": null,
+    "average_frequency_mhz": null,
+    "highest_frequency_mhz": null
+  },
+  "cpu_policies": [
+    {
+      "id": 0,
+      "cpus": [
+        0
+      ],
+      "governor": "powersave",
+      "current_frequency_mhz": 2400,
+      "min_frequency_mhz": 400,
+      "max_frequency_mhz": 4000,
+      "hardware_min_frequency_mhz": 400,
+      "hardware_max_frequency_mhz": 4800
+    }
+  ],
+  "batteries": [
+    {
+      "name": "BAT0",
+      "ac_connected": true,
+      "charging_state": "Charging",
+      "capacity_percent": 80,
+      "power_rate_watts": 15.5,
+      "charge_start_threshold": 40,
+      "charge_stop_threshold": 80
+    }
+  ],
+  "ac_power": {
+    "source": "test",
+    "reading": "AC online",
+    "online": true
+  },
+  "lid": {
+    "lid_closed": null,
+    "external_displays": []
+  },
+  "throttling": {
+    "core_events": null,
+    "package_events": null,
+    "throttled_cpus": [],
+    "total_events": null
+  },
+  "cpu_power": {
+    "package_watts": null,
+    "cores_watts": null,
+    "uncore_watts": null
+  },
+  "system_load": {
+    "load_avg_1min": 0.0,
+    "load_avg_5min": 0.0,
+    "load_avg_15min": 0.0,
+    "cpu_pressure": null,
+    "smoothed_cpu_usage": null
+  },
+  "timestamp": {
+    "secs_since_epoch": 1700000000,
+    "nanos_since_epoch": 0
+  }
+}"#
+        );
+    }
+}
@@ -0,0 +1,331 @@
+use crate::config::AppConfig;
+use crate::engine;
+use crate::events::FileWatch;
+use crate::monitor;
+use crate::pidfile;
+use crate::statefile::{BatteryState, PolicyState};
+use crate::util::error::AppError;
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+use std::thread;
+use std::time::Duration;
+
+/// Text shown unless `--template` says otherwise
+pub const DEFAULT_TEMPLATE: &str = "{icon} {profile} {avg_freq} {temp}";
+
+/// How often `--watch` reads sysfs while no daemon keeps the state file up to
+/// date, or the state file's directory cannot be watched
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Longest `--watch` waits for the state file to change before checking that
+/// the daemon is still there
+const WATCH_TIMEOUT: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum StatuslineFormat {
+    /// `text`, `tooltip`, `class`, `alt` and `percentage` for a waybar custom
+    /// module with `return-type = "json"`
+    Waybar,
+    /// `text`, `short_text` and `state` for an i3status-rust custom block with
+    /// `json = true`
+    I3status,
+}
+
+/// What the status line shows, from the daemon's state file or sysfs
+struct Snapshot {
+    /// "daemon" or "sysfs"
+    source: &'static str,
+    profile: String,
+    on_ac: bool,
+    paused: bool,
+    governor: Option<String>,
+    avg_freq_mhz: Option<u32>,
+    temperature_celsius: Option<f32>,
+    battery_percent: Option<u8>,
+    charging_state: Option<String>,
+    turbo: Option<bool>,
+}
+
+/// The parts of the state file the status line needs
+#[derive(Deserialize)]
+struct StateView {
+    active_profile: String,
+    power_source: String,
+    paused: bool,
+    turbo: Option<bool>,
+    /// Missing from files written before it was added
+    #[serde(default)]
+    temperature_celsius: Option<f32>,
+    policies: Vec<PolicyState>,
+    batteries: Vec<BatteryState>,
+}
+
+#[derive(Serialize)]
+struct WaybarLine {
+    text: String,
+    tooltip: String,
+    class: &'static str,
+    /// For `format-icons`: "ac" or "battery"
+    alt: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    percentage: Option<u8>,
+}
+
+#[derive(Serialize)]
+struct I3statusLine {
+    text: String,
+    short_text: String,
+    /// Idle, Info, Good, Warning or Critical
+    state: &'static str,
+}
+
+/// Prints one JSON line for a status bar module, showing `template` with its
+/// placeholders filled in
+///
+/// The daemon's state file is read while the daemon runs, sysfs otherwise.
+/// With `watch`, a new line is printed whenever the state file changes, or
+/// every few seconds from sysfs, until the bar closes the pipe.
+pub fn run_statusline(
+    config: &AppConfig,
+    format: StatuslineFormat,
+    template: &str,
+    watch: bool,
+) -> Result<(), AppError> {
+    let mut out = io::stdout().lock();
+    let state_path = Path::new(&config.daemon.state_file_path);
+    let mut file_watch = None;
+    let mut last_line = None;
+
+    loop {
+        let snapshot = snapshot(config)?;
+        let line = snapshot.line(format, template)?;
+        if last_line.as_ref() != Some(&line) {
+            match writeln!(out, "{line}").and_then(|()| out.flush()) {
+                // The bar went away
+                Err(e) if e.kind() == io::ErrorKind::BrokenPipe => return Ok(()),
+                result => result?,
+            }
+            last_line = Some(line);
+        }
+        if !watch {
+            return Ok(());
+        }
+
+        // The directory may only appear once the daemon starts
+        if file_watch.is_none() && config.daemon.state_file_enabled {
+            file_watch = FileWatch::new(state_path).ok();
+        }
+        match &file_watch {
+            Some(file_watch) if snapshot.source == "daemon" => file_watch.wait(WATCH_TIMEOUT),
+            Some(file_watch) => file_watch.wait(POLL_INTERVAL),
+            None => thread::sleep(POLL_INTERVAL),
+        }
+    }
+}
+
+fn snapshot(config: &AppConfig) -> Result<Snapshot, AppError> {
+    if let Some(snapshot) = from_state_file(config) {
+        return Ok(snapshot);
+    }
+    from_sysfs(config)
+}
+
+fn from_state_file(config: &AppConfig) -> Option<Snapshot> {
+    if !config.daemon.state_file_enabled {
+        return None;
+    }
+    // A file left behind by a daemon that did not stop cleanly is stale
+    pidfile::running_daemon_pid(Path::new(&config.daemon.pid_file))?;
+    let contents = fs::read_to_string(&config.daemon.state_file_path).ok()?;
+    let state: StateView = serde_json::from_str(&contents).ok()?;
+
+    Some(Snapshot {
+        source: "daemon",
+        profile: state.active_profile,
+        on_ac: state.power_source == "ac",
+        paused: state.paused,
+        governor: state
+            .policies
+            .iter()
+            .find_map(|policy| policy.governor.clone()),
+        avg_freq_mhz: average(
+            state
+                .policies
+                .iter()
+                .filter_map(|policy| policy.cur_freq_mhz),
+        ),
+        temperature_celsius: state.temperature_celsius,
+        battery_percent: average(
+            state
+                .batteries
+                .iter()
+                .filter_map(|battery| battery.capacity_percent.map(u32::from)),
+        )
+        .and_then(|percent| u8::try_from(percent).ok()),
+        charging_state: state
+            .batteries
+            .iter()
+            .find_map(|battery| battery.charging_state.clone()),
+        turbo: state.turbo,
+    })
+}
+
+fn from_sysfs(config: &AppConfig) -> Result<Snapshot, AppError> {
+    let report = monitor::collect_system_report(config)?;
+    Ok(Snapshot {
+        source: "sysfs",
+        profile: engine::selected_profile_name(&report, None).to_string(),
+        on_ac: report.ac_power.online,
+        paused: false,
+        governor: report.cpu_global.current_governor.clone(),
+        avg_freq_mhz: report.cpu_global.average_frequency_mhz,
+        temperature_celsius: report.cpu_global.average_temperature_celsius,
+        battery_percent: average(
+            report
+                .batteries
+                .iter()
+                .filter_map(|battery| battery.capacity_percent.map(u32::from)),
+        )
+        .and_then(|percent| u8::try_from(percent).ok()),
+        charging_state: report
+            .batteries
+            .iter()
+            .find_map(|battery| battery.charging_state.clone()),
+        turbo: report.cpu_global.turbo_status,
+    })
+}
+
+fn average(values: impl Iterator<Item = u32>) -> Option<u32> {
+    let (sum, count) = values.fold((0, 0), |(sum, count), value| (sum + value, count + 1));
+    (count > 0).then(|| sum / count)
+}
+
+impl Snapshot {
+    fn line(&self, format: StatuslineFormat, template: &str) -> Result<String, AppError> {
+        let text = self.render(template);
+        let line = match format {
+            StatuslineFormat::Waybar => serde_json::to_string(&WaybarLine {
+                text,
+                tooltip: self.tooltip(),
+                class: if self.paused {
+                    "paused"
+                } else if self.on_ac {
+                    "charger"
+                } else {
+                    "battery"
+                },
+                alt: if self.on_ac { "ac" } else { "battery" },
+                percentage: self.battery_percent,
+            }),
+            StatuslineFormat::I3status => serde_json::to_string(&I3statusLine {
+                text,
+                short_text: self.profile.clone(),
+                state: if self.paused {
+                    "Warning"
+                } else if self.on_ac {
+                    "Good"
+                } else {
+                    "Info"
+                },
+            }),
+        };
+        line.map_err(|e| AppError::Generic(format!("Failed to serialize output: {e}")))
+    }
+
+    /// `template` with every placeholder replaced. Values that are not
+    /// available leave nothing behind, not even the space around them.
+    fn render(&self, template: &str) -> String {
+        let values = [
+            ("{profile}", self.profile.clone()),
+            ("{governor}", self.governor.clone().unwrap_or_default()),
+            (
+                "{avg_freq}",
+                self.avg_freq_mhz.map_or_else(String::new, format_freq),
+            ),
+            (
+                "{temp}",
+                self.temperature_celsius
+                    .map_or_else(String::new, |temp| format!("{temp:.0}°C")),
+            ),
+            (
+                "{battery}",
+                self.battery_percent
+                    .map_or_else(String::new, |percent| format!("{percent}%")),
+            ),
+            (
+                "{power}",
+                if self.on_ac { "AC" } else { "battery" }.to_string(),
+            ),
+            (
+                "{icon}",
+                if self.paused {
+                    "⏸"
+                } else if self.on_ac {
+                    "⚡"
+                } else {
+                    "🔋"
+                }
+                .to_string(),
+            ),
+        ];
+
+        let mut text = template.to_string();
+        for (placeholder, value) in values {
+            text = text.replace(placeholder, &value);
+        }
+        text.split_whitespace().collect::<Vec<_>>().join(" ")
+    }
+
+    fn tooltip(&self) -> String {
+        let na = || "N/A".to_string();
+        let mut lines = vec![
+            format!(
+                "Profile: {} ({})",
+                self.profile,
+                if self.on_ac { "AC" } else { "battery" }
+            ),
+            format!("Governor: {}", self.governor.clone().unwrap_or_else(na)),
+            format!(
+                "Frequency: {}",
+                self.avg_freq_mhz.map_or_else(na, format_freq)
+            ),
+            format!(
+                "Temperature: {}",
+                self.temperature_celsius
+                    .map_or_else(na, |temp| format!("{temp:.1}°C"))
+            ),
+            format!(
+                "Turbo: {}",
+                match self.turbo {
+                    Some(true) => "enabled",
+                    Some(false) => "disabled",
+                    None => "N/A",
+                }
+            ),
+        ];
+        if let Some(percent) = self.battery_percent {
+            lines.push(format!(
+                "Battery: {percent}%{}",
+                self.charging_state
+                    .as_ref()
+                    .map_or_else(String::new, |state| format!(", {state}"))
+            ));
+        }
+        if self.paused {
+            lines.push("Management paused".to_string());
+        }
+        lines.push(format!("Source: {}", self.source));
+        lines.join("\n")
+    }
+}
+
+fn format_freq(mhz: u32) -> String {
+    if mhz >= 1000 {
+        format!("{:.1}GHz", f64::from(mhz) / 1000.0)
+    } else {
+        format!("{mhz}MHz")
+    }
+}
@@ -0,0 +1,110 @@
+// Colors for human-readable output
+use clap::ValueEnum;
+use std::env;
+use std::ffi::OsStr;
+use std::fmt::Display;
+use std::io::{self, IsTerminal};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// When to color output, from `--color`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum ColorChoice {
+    /// Color when stdout is a terminal and NO_COLOR is not set
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Decides once whether output is colored. Until this is called, nothing is.
+pub fn init(choice: ColorChoice) {
+    let enabled = should_color(
+        choice,
+        env::var_os("NO_COLOR").as_deref(),
+        io::stdout().is_terminal(),
+    );
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// Whether to color with `choice`, given the value of `NO_COLOR` and whether
+/// stdout is a terminal
+fn should_color(choice: ColorChoice, no_color: Option<&OsStr>, is_terminal: bool) -> bool {
+    match choice {
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+        // See https://no-color.org, an empty value does not count
+        ColorChoice::Auto => no_color.is_none_or(OsStr::is_empty) && is_terminal,
+    }
+}
+
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+fn paint(code: &str, text: impl Display) -> String {
+    if enabled() {
+        format!("\x1b[{code}m{text}\x1b[0m")
+    } else {
+        text.to_string()
+    }
+}
+
+/// Green, for values as they should be
+pub fn ok(text: impl Display) -> String {
+    paint("32", text)
+}
+
+/// Yellow, for what is skipped, unsupported or unknown
+pub fn warn(text: impl Display) -> String {
+    paint("33", text)
+}
+
+/// Red, for errors and drift
+pub fn error(text: impl Display) -> String {
+    paint("31", text)
+}
+
+/// Bold, for headings
+pub fn heading(text: impl Display) -> String {
+    paint("1", text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn piping_disables_colors() {
+        assert!(!should_color(ColorChoice::Auto, None, false));
+        assert!(should_color(ColorChoice::Auto, None, true));
+        // Unless asked for explicitly
+        assert!(should_color(ColorChoice::Always, None, false));
+    }
+
+    #[test]
+    fn no_color_disables_colors() {
+        let set = Some(OsStr::new("1"));
+        assert!(!should_color(ColorChoice::Auto, set, true));
+        // An empty value does not count
+        assert!(should_color(ColorChoice::Auto, Some(OsStr::new("")), true));
+        // --color=always wins over it
+        assert!(should_color(ColorChoice::Always, set, true));
+        assert!(!should_color(ColorChoice::Never, None, true));
+    }
+
+    #[test]
+    fn escapes_only_when_enabled() {
+        init(ColorChoice::Never);
+        assert_eq!(ok("on"), "on");
+        assert_eq!(heading("CPU"), "CPU");
+
+        init(ColorChoice::Always);
+        assert_eq!(ok("on"), "\x1b[32mon\x1b[0m");
+        assert_eq!(error("drift"), "\x1b[31mdrift\x1b[0m");
+
+        init(ColorChoice::Never);
+        assert!(!warn("skipped").contains('\x1b'));
+    }
+}
@@ -0,0 +1,146 @@
+use crate::config::AppConfig;
+use crate::core::TurboSetting;
+use crate::cpu;
+use crate::daemon::{DaemonStatus, ForceEnd};
+use crate::ipc;
+use crate::pidfile;
+use crate::privileges;
+use crate::util::error::AppError;
+use clap::ValueEnum;
+use log::warn;
+use std::path::Path;
+
+/// What `watt turbo` does
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum TurboAction {
+    /// Enable turbo until told otherwise
+    On,
+    /// Disable turbo until told otherwise
+    Off,
+    /// Hand turbo back to the active profile
+    Auto,
+    /// Show whether turbo is enabled and how it is controlled
+    Status,
+}
+
+/// Switches turbo on or off right away, or hands it back to the profile
+///
+/// As root the setting is written directly. With a daemon running it is also
+/// registered as an override, so the next cycle keeps it instead of applying
+/// the profile's turbo setting; that is enough without root, as the daemon
+/// applies the override on its own.
+pub fn run_turbo(
+    config: &AppConfig,
+    action: TurboAction,
+    until_power_change: bool,
+) -> Result<(), AppError> {
+    let setting = match action {
+        TurboAction::On => TurboSetting::Always,
+        TurboAction::Off => TurboSetting::Never,
+        TurboAction::Auto | TurboAction::Status if until_power_change => {
+            return Err(AppError::Usage(
+                "--until-power-change only applies to on and off".to_string(),
+            ));
+        }
+        TurboAction::Auto => TurboSetting::Auto,
+        TurboAction::Status => {
+            print_status();
+            return Ok(());
+        }
+    };
+
+    let daemon_pid = pidfile::running_daemon_pid(Path::new(&config.daemon.pid_file));
+    let Some(pid) = daemon_pid else {
+        return match setting {
+            TurboSetting::Auto => Err(AppError::DaemonUnreachable(
+                "No Watt daemon is running, so turbo has no profile to return to".to_string(),
+            )),
+            _ if until_power_change => Err(AppError::DaemonUnreachable(
+                "--until-power-change needs a running Watt daemon to notice the change".to_string(),
+            )),
+            _ => {
+                write_turbo(setting)?;
+                println!("Turbo {}", describe(setting));
+                Ok(())
+            }
+        };
+    };
+
+    let can_write = privileges::can_write_sysfs();
+    if can_write {
+        write_turbo(setting)?;
+    }
+
+    let mode = format!("{setting:?}").to_lowercase();
+    match ipc::send_command(&ipc::Request::SetTurbo {
+        mode,
+        until_power_change,
+    }) {
+        Ok(()) => {}
+        Err(e) if can_write && setting != TurboSetting::Auto => warn!(
+            "Turbo is {}, but the Watt daemon (PID {pid}) did not take the override and may \
+             change it back: {e}",
+            describe(setting)
+        ),
+        Err(e) => return Err(AppError::daemon("Failed to set the turbo override", &e)),
+    }
+
+    match setting {
+        TurboSetting::Auto => println!("Turbo follows the active profile again"),
+        _ if until_power_change => {
+            println!("Turbo {} until the power source changes", describe(setting))
+        }
+        _ => println!("Turbo {} until `watt turbo auto`", describe(setting)),
+    }
+    Ok(())
+}
+
+/// Writes turbo directly, which needs root
+fn write_turbo(setting: TurboSetting) -> Result<(), AppError> {
+    privileges::require_sysfs_access("Switching turbo without a running Watt daemon")?;
+    cpu::set_turbo(setting).map_err(AppError::Control)
+}
+
+const fn describe(setting: TurboSetting) -> &'static str {
+    match setting {
+        TurboSetting::Always => "on",
+        TurboSetting::Never => "off",
+        TurboSetting::Auto => "auto",
+    }
+}
+
+fn print_status() {
+    println!(
+        "Turbo:     {}",
+        match cpu::turbo_enabled() {
+            Some(true) => "on",
+            Some(false) => "off",
+            None => "unknown",
+        }
+    );
+    println!(
+        "Mechanism: {}",
+        cpu::turbo_mechanism().unwrap_or("none, turbo cannot be controlled here")
+    );
+
+    let daemon = ipc::socket_available()
+        .then(ipc::fetch_status)
+        .and_then(Result::ok);
+    match daemon {
+        Some(status) => println!(
+            "Override:  {}",
+            describe_override(&status).unwrap_or_else(|| "none, the profile decides".to_string())
+        ),
+        None => println!("Override:  none, no Watt daemon is reachable"),
+    }
+}
+
+/// The daemon's turbo override and when it ends, if there is one
+pub fn describe_override(status: &DaemonStatus) -> Option<String> {
+    let setting = status.turbo_override.as_deref()?;
+    Some(match status.turbo_override_end {
+        Some(ForceEnd::PowerChange) => format!("{setting} until the power source changes"),
+        Some(ForceEnd::At(until)) => format!("{setting} until {until}"),
+        None => setting.to_string(),
+    })
+}
@@ -0,0 +1,587 @@
+use crate::config::AppConfig;
+use crate::core::SystemReport;
+use crate::daemon::DaemonStatus;
+use crate::engine;
+use crate::ipc;
+use crate::monitor;
+use crate::util::error::AppError;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Direction, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, Gauge, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+use std::collections::VecDeque;
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::Duration;
+
+/// Decisions kept for the log pane
+const LOG_CAPACITY: usize = 200;
+
+/// Below this size only a notice is drawn
+const MIN_WIDTH: u16 = 40;
+const MIN_HEIGHT: u16 = 12;
+
+/// One round of collected data
+struct Sample {
+    report: Result<SystemReport, String>,
+    /// `None` if no daemon answers
+    daemon: Option<DaemonStatus>,
+}
+
+/// Runs a live dashboard until `q`, Escape or Ctrl+C, refreshing every
+/// `interval`
+///
+/// Reports are collected locally, so frequencies are current even with a long
+/// daemon poll interval. With a daemon reachable over the control socket, its
+/// profile, turbo and pause state can be changed from the keyboard.
+pub fn run_watch(config: &AppConfig, interval: Duration) -> Result<(), AppError> {
+    let (sender, samples) = mpsc::channel();
+    let config = config.clone();
+    thread::Builder::new()
+        .name("watch-collector".to_string())
+        .spawn(move || {
+            loop {
+                if sender.send(collect(&config)).is_err() {
+                    break;
+                }
+                thread::sleep(interval);
+            }
+        })?;
+
+    // Log records would be drawn over the dashboard
+    let log_level = log::max_level();
+    log::set_max_level(log::LevelFilter::Off);
+    // Also restores the terminal before a panic is reported
+    let mut terminal = ratatui::try_init()?;
+    let result = Dashboard::default().run(&mut terminal, &samples);
+    ratatui::restore();
+    log::set_max_level(log_level);
+    result
+}
+
+fn collect(config: &AppConfig) -> Sample {
+    let daemon = if ipc::socket_available() {
+        ipc::fetch_status().ok().map(|mut status| {
+            status.report = None;
+            status
+        })
+    } else {
+        None
+    };
+    Sample {
+        report: monitor::collect_system_report(config).map_err(|e| e.to_string()),
+        daemon,
+    }
+}
+
+#[derive(Default)]
+struct Dashboard {
+    sample: Option<Sample>,
+    /// Newest last
+    log: VecDeque<String>,
+}
+
+impl Dashboard {
+    fn run(
+        &mut self,
+        terminal: &mut DefaultTerminal,
+        samples: &Receiver<Sample>,
+    ) -> Result<(), AppError> {
+        loop {
+            while let Ok(sample) = samples.try_recv() {
+                self.update(sample);
+            }
+            terminal.draw(|frame| self.draw(frame))?;
+
+            if !event::poll(Duration::from_millis(100))? {
+                continue;
+            }
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            match key.code {
+                KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    return Ok(());
+                }
+                KeyCode::Char('p') => self.cycle_profile(),
+                KeyCode::Char('t') => self.cycle_turbo(),
+                KeyCode::Char(' ') => self.toggle_pause(),
+                _ => {}
+            }
+        }
+    }
+
+    fn record(&mut self, message: impl Into<String>) {
+        let time = jiff::Zoned::now().strftime("%H:%M:%S").to_string();
+        self.log.push_back(format!("{time} {}", message.into()));
+        if self.log.len() > LOG_CAPACITY {
+            self.log.pop_front();
+        }
+    }
+
+    /// Takes in a new sample, logging what changed since the last one
+    fn update(&mut self, sample: Sample) {
+        let previous = self.sample.take();
+        let previous_report = previous.as_ref().and_then(|s| s.report.as_ref().ok());
+        let previous_daemon = previous.as_ref().and_then(|s| s.daemon.as_ref());
+
+        match (&previous_daemon, &sample.daemon) {
+            (None, Some(_)) => self.record("Connected to the daemon"),
+            (Some(_), None) => self.record("Daemon not reachable, read-only"),
+            _ => {}
+        }
+        if previous.is_none() && sample.daemon.is_none() {
+            self.record("No daemon reachable, read-only");
+        }
+        if let Err(e) = &sample.report {
+            self.record(format!("Failed to collect report: {e}"));
+        }
+
+        if let Ok(report) = &sample.report {
+            let profile = active_profile(report, sample.daemon.as_ref());
+            let previous_profile =
+                previous_report.map(|previous| active_profile(previous, previous_daemon));
+            if previous_profile.as_deref() != Some(profile.as_str()) {
+                let reason = engine::selected_profile_reason(
+                    report,
+                    force_mode(sample.daemon.as_ref()).as_ref(),
+                );
+                self.record(format!("Profile {profile} ({reason})"));
+            }
+
+            if let Some(previous) = previous_report {
+                if previous.cpu_global.turbo_status != report.cpu_global.turbo_status {
+                    self.record(format!("Turbo {}", on_off(report.cpu_global.turbo_status)));
+                }
+                if previous.cpu_global.current_governor != report.cpu_global.current_governor {
+                    self.record(format!(
+                        "Governor {}",
+                        report
+                            .cpu_global
+                            .current_governor
+                            .as_deref()
+                            .unwrap_or("unknown")
+                    ));
+                }
+                if previous.cpu_global.epp != report.cpu_global.epp {
+                    self.record(format!(
+                        "EPP {}",
+                        report.cpu_global.epp.as_deref().unwrap_or("unknown")
+                    ));
+                }
+                if previous.ac_power.online != report.ac_power.online {
+                    self.record(if report.ac_power.online {
+                        "On AC power"
+                    } else {
+                        "On battery"
+                    });
+                }
+            }
+        }
+
+        if let (Some(previous), Some(daemon)) = (previous_daemon, &sample.daemon) {
+            if previous.paused != daemon.paused {
+                self.record(if daemon.paused {
+                    "Management paused"
+                } else {
+                    "Management resumed"
+                });
+            }
+            if previous.turbo_override != daemon.turbo_override {
+                self.record(format!(
+                    "Turbo override {}",
+                    daemon.turbo_override.as_deref().unwrap_or("off")
+                ));
+            }
+            let (before, after) = (
+                previous.counters.as_ref().map_or(0, |c| c.apply_errors),
+                daemon.counters.as_ref().map_or(0, |c| c.apply_errors),
+            );
+            if after > before {
+                self.record(format!(
+                    "Applying settings failed ({after} failures so far)"
+                ));
+            }
+            for parked in &daemon.parked_knobs {
+                if !previous.parked_knobs.iter().any(|p| p.knob == parked.knob) {
+                    self.record(format!("Parked {}: {}", parked.knob, parked.error));
+                }
+            }
+            for drifted in &daemon.drifted_knobs {
+                if !previous
+                    .drifted_knobs
+                    .iter()
+                    .any(|d| d.knob == drifted.knob)
+                {
+                    self.record(format!(
+                        "{} changed externally to {}, left alone",
+                        drifted.knob, drifted.current
+                    ));
+                }
+            }
+        }
+
+        self.sample = Some(sample);
+    }
+
+    fn daemon(&self) -> Option<&DaemonStatus> {
+        self.sample
+            .as_ref()
+            .and_then(|sample| sample.daemon.as_ref())
+    }
+
+    /// Sends `request` to the daemon, logging `done` or the error
+    fn send(&mut self, request: &ipc::Request, done: String) {
+        match ipc::send_command(request) {
+            Ok(()) => self.record(done),
+            Err(e) => self.record(format!("Request failed: {e}")),
+        }
+    }
+
+    /// Forced profile: automatic, then performance, balanced and powersave
+    fn cycle_profile(&mut self) {
+        let Some(daemon) = self.daemon() else {
+            self.record("No daemon reachable, cannot change the profile");
+            return;
+        };
+        let next = match daemon.force_mode.as_deref() {
+            None => "performance",
+            Some("performance") => "balanced",
+            Some("balanced") => "powersave",
+            Some(_) => "auto",
+        };
+        self.send(
+            &ipc::Request::SetProfile {
+                name: next.to_string(),
+                duration_sec: None,
+                until: None,
+                until_power_change: false,
+            },
+            format!("Requested profile {next}"),
+        );
+    }
+
+    /// Turbo override: none, then always, then never
+    fn cycle_turbo(&mut self) {
+        let Some(daemon) = self.daemon() else {
+            self.record("No daemon reachable, cannot change turbo");
+            return;
+        };
+        let next = match daemon.turbo_override.as_deref() {
+            None => "always",
+            Some("always") => "never",
+            Some(_) => "auto",
+        };
+        self.send(
+            &ipc::Request::SetTurbo {
+                mode: next.to_string(),
+                until_power_change: false,
+            },
+            format!("Requested turbo {next}"),
+        );
+    }
+
+    fn toggle_pause(&mut self) {
+        let Some(daemon) = self.daemon() else {
+            self.record("No daemon reachable, cannot pause");
+            return;
+        };
+        if daemon.paused {
+            self.send(&ipc::Request::Resume, "Requested resume".to_string());
+        } else {
+            self.send(
+                &ipc::Request::Pause { duration_sec: None },
+                "Requested pause".to_string(),
+            );
+        }
+    }
+
+    fn draw(&self, frame: &mut Frame<'_>) {
+        let area = frame.area();
+        if area.width < MIN_WIDTH || area.height < MIN_HEIGHT {
+            frame.render_widget(
+                Paragraph::new(format!(
+                    "Terminal too small, needs {MIN_WIDTH}x{MIN_HEIGHT}. Press q to quit."
+                )),
+                area,
+            );
+            return;
+        }
+
+        let log_height = (area.height / 4).clamp(3, 10);
+        let [header, body, log, footer] = Layout::vertical([
+            Constraint::Length(1),
+            Constraint::Min(5),
+            Constraint::Length(log_height),
+            Constraint::Length(1),
+        ])
+        .areas(area);
+
+        let Some(sample) = &self.sample else {
+            frame.render_widget(Paragraph::new("Collecting..."), body);
+            return;
+        };
+
+        let report = sample.report.as_ref().ok();
+        let profile = report.map_or_else(
+            || "unknown".to_string(),
+            |report| active_profile(report, sample.daemon.as_ref()),
+        );
+        let mut title = vec![
+            Span::styled("Watt", Style::default().add_modifier(Modifier::BOLD)),
+            Span::raw(format!("  profile {profile}")),
+            Span::raw(format!(
+                "  turbo {}",
+                on_off(report.and_then(|r| r.cpu_global.turbo_status))
+            )),
+            Span::raw(if sample.daemon.is_some() {
+                "  daemon"
+            } else {
+                "  no daemon"
+            }),
+        ];
+        if sample.daemon.as_ref().is_some_and(|d| d.paused) {
+            title.push(Span::styled("  PAUSED", Style::default().fg(Color::Yellow)));
+        }
+        frame.render_widget(Line::from(title), header);
+
+        let (cores, side) = if body.width >= 80 {
+            let [cores, side] =
+                Layout::horizontal([Constraint::Percentage(60), Constraint::Percentage(40)])
+                    .areas(body);
+            (cores, side)
+        } else {
+            let side_height = (body.height / 2).max(4);
+            let [cores, side] =
+                Layout::vertical([Constraint::Min(3), Constraint::Length(side_height)]).areas(body);
+            (cores, side)
+        };
+
+        match &sample.report {
+            Ok(report) => {
+                draw_cores(frame, report, cores);
+                draw_system(frame, report, sample.daemon.as_ref(), side);
+            }
+            Err(e) => frame.render_widget(
+                Paragraph::new(format!("Failed to collect report: {e}"))
+                    .block(Block::default().borders(Borders::ALL)),
+                body,
+            ),
+        }
+
+        let log_block = Block::default().borders(Borders::ALL).title("Decisions");
+        let rows = usize::from(log_block.inner(log).height);
+        let lines: Vec<Line<'_>> = self
+            .log
+            .iter()
+            .skip(self.log.len().saturating_sub(rows))
+            .map(|line| Line::raw(line.as_str()))
+            .collect();
+        frame.render_widget(Paragraph::new(lines).block(log_block), log);
+
+        frame.render_widget(
+            Line::styled(
+                if sample.daemon.is_some() {
+                    "q quit  p profile  t turbo  space pause/resume"
+                } else {
+                    "q quit  (read-only, no daemon reachable)"
+                },
+                Style::default().fg(Color::DarkGray),
+            ),
+            footer,
+        );
+    }
+}
+
+/// One line per core with its frequency against its maximum and its usage
+fn draw_cores(frame: &mut Frame<'_>, report: &SystemReport, area: Rect) {
+    let block = Block::default().borders(Borders::ALL).title("Cores");
+    let inner = block.inner(area);
+    frame.render_widget(block, area);
+
+    let rows = usize::from(inner.height);
+    let shown = if report.cpu_cores.len() > rows {
+        rows.saturating_sub(1)
+    } else {
+        rows
+    };
+    let id_width = report
+        .cpu_cores
+        .last()
+        .map_or(1, |core| core.core_id.to_string().len());
+    // "cpuNN 1234 MHz " and " 100%"
+    let bar_width = usize::from(inner.width).saturating_sub(id_width + 19);
+
+    let mut lines: Vec<Line<'_>> = report
+        .cpu_cores
+        .iter()
+        .take(shown)
+        .map(|core| {
+            let usage = core.usage_percent.unwrap_or(0.0);
+            let fraction = match (core.current_frequency_mhz, core.max_frequency_mhz) {
+                (Some(current), Some(max)) if max > 0 => {
+                    (f64::from(current) / f64::from(max)).min(1.0)
+                }
+                _ => 0.0,
+            };
+            let filled = (fraction * bar_width as f64).round() as usize;
+            let color = match usage {
+                u if u >= 80.0 => Color::Red,
+                u if u >= 40.0 => Color::Yellow,
+                _ => Color::Green,
+            };
+            Line::from(vec![
+                Span::raw(format!(
+                    "cpu{:<id_width$} {:>4} MHz ",
+                    core.core_id,
+                    core.current_frequency_mhz
+                        .map_or_else(|| "N/A".to_string(), |f| f.to_string())
+                )),
+                Span::styled("█".repeat(filled), Style::default().fg(color)),
+                Span::styled(
+                    "░".repeat(bar_width - filled),
+                    Style::default().fg(Color::DarkGray),
+                ),
+                Span::raw(
+                    core.usage_percent
+                        .map_or_else(|| "  N/A".to_string(), |u| format!(" {u:>3.0}%")),
+                ),
+            ])
+        })
+        .collect();
+    if shown < report.cpu_cores.len() {
+        lines.push(Line::raw(format!(
+            "... {} more",
+            report.cpu_cores.len() - shown
+        )));
+    }
+    frame.render_widget(Paragraph::new(lines), inner);
+}
+
+/// Profile, CPU settings and temperature, with a gauge per battery below
+fn draw_system(
+    frame: &mut Frame<'_>,
+    report: &SystemReport,
+    daemon: Option<&DaemonStatus>,
+    area: Rect,
+) {
+    let block = Block::default().borders(Borders::ALL).title("System");
+    let inner = block.inner(area);
+    frame.render_widget(block, area);
+
+    let batteries: Vec<_> = report
+        .batteries
+        .iter()
+        .filter(|b| b.capacity_percent.is_some())
+        .collect();
+    let gauge_rows = u16::try_from(batteries.len())
+        .unwrap_or(u16::MAX)
+        .min(inner.height.saturating_sub(1));
+    let [text, gauges] = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(1), Constraint::Length(gauge_rows)])
+        .areas(inner);
+
+    let global = &report.cpu_global;
+    let mut lines = vec![
+        Line::raw(format!(
+            "Profile      {} ({})",
+            active_profile(report, daemon),
+            engine::selected_profile_reason(report, force_mode(daemon).as_ref())
+        )),
+        Line::raw(format!(
+            "Turbo        {}{}",
+            on_off(global.turbo_status),
+            daemon
+                .and_then(|d| d.turbo_override.as_deref())
+                .map_or_else(String::new, |o| format!(" (override {o})"))
+        )),
+        Line::raw(format!(
+            "Governor     {}",
+            global.current_governor.as_deref().unwrap_or("N/A")
+        )),
+        Line::raw(format!(
+            "EPP          {}",
+            global.epp.as_deref().unwrap_or("N/A")
+        )),
+        Line::raw(format!(
+            "Temperature  {}",
+            global
+                .average_temperature_celsius
+                .map_or_else(|| "N/A".to_string(), |t| format!("{t:.1}°C"))
+        )),
+        Line::raw(format!(
+            "CPU power    {}",
+            report
+                .cpu_power
+                .package_watts
+                .map_or_else(|| "N/A".to_string(), |w| format!("{w:.1} W"))
+        )),
+        Line::raw(format!(
+            "Load         {:.2} {:.2} {:.2}",
+            report.system_load.load_avg_1min,
+            report.system_load.load_avg_5min,
+            report.system_load.load_avg_15min
+        )),
+    ];
+    if let Some(daemon) = daemon {
+        if daemon.paused {
+            lines.push(Line::styled(
+                "Management   paused",
+                Style::default().fg(Color::Yellow),
+            ));
+        }
+    }
+    frame.render_widget(Paragraph::new(lines), text);
+
+    for (battery, row) in batteries.iter().zip(gauges.rows()) {
+        let capacity = battery.capacity_percent.unwrap_or(0);
+        let power = battery
+            .power_rate_watts
+            .map_or_else(String::new, |w| format!(" {:.1} W", w.abs()));
+        frame.render_widget(
+            Gauge::default()
+                .gauge_style(Style::default().fg(if capacity < 20 {
+                    Color::Red
+                } else {
+                    Color::Green
+                }))
+                .percent(u16::from(capacity.min(100)))
+                .label(format!(
+                    "{} {capacity}% {}{power}",
+                    battery.name,
+                    battery.charging_state.as_deref().unwrap_or("Unknown")
+                )),
+            row,
+        );
+    }
+}
+
+fn force_mode(daemon: Option<&DaemonStatus>) -> Option<crate::core::ForcedProfile> {
+    daemon
+        .and_then(|d| d.force_mode.as_deref())
+        .and_then(|mode| mode.parse().ok())
+}
+
+/// The profile the daemon applied, or the one it would select
+fn active_profile(report: &SystemReport, daemon: Option<&DaemonStatus>) -> String {
+    daemon
+        .and_then(|d| d.active_profile.clone())
+        .unwrap_or_else(|| {
+            engine::selected_profile_name(report, force_mode(daemon).as_ref()).to_string()
+        })
+}
+
+const fn on_off(state: Option<bool>) -> &'static str {
+    match state {
+        Some(true) => "enabled",
+        Some(false) => "disabled",
+        None => "unknown",
+    }
+}
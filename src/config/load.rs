@@ -1,8 +1,26 @@
 // Configuration loading functionality
+use log::{error, info, warn};
+use std::collections::BTreeMap;
 use std::fs;
 use std::path::{Path, PathBuf};
 
 use crate::config::types::{AppConfig, AppConfigToml, ConfigError, DaemonConfig, ProfileConfig};
+use crate::core::{ForcedProfile, OperationalMode};
+use crate::sensors::SensorSpec;
+
+/// System-wide configuration files, in order of precedence
+const SYSTEM_CONFIG_PATHS: &[&str] = &["/etc/xdg/watt/config.toml", "/etc/watt.toml"];
+
+/// Every file [`load_config`] may read, in order of precedence, whether it
+/// exists or not
+pub fn config_paths() -> Vec<PathBuf> {
+    let mut paths: Vec<PathBuf> = std::env::var_os("WATT_CONFIG")
+        .map(PathBuf::from)
+        .into_iter()
+        .collect();
+    paths.extend(SYSTEM_CONFIG_PATHS.iter().map(PathBuf::from));
+    paths
+}
 
 /// The primary function to load application configuration from a specific path or from default locations.
 ///
@@ -36,44 +54,56 @@ pub fn load_config_from_path(specific_path: Option<&str>) -> Result<AppConfig, C
     if let Ok(env_path) = std::env::var("WATT_CONFIG") {
         let env_path = Path::new(&env_path);
         if env_path.exists() {
-            println!(
+            info!(
                 "Loading config from WATT_CONFIG: {}",
                 env_path.display()
             );
-            return load_and_parse_config(env_path);
+            return load_and_parse_config(env_path).inspect_err(|e| {
+                error!("Error with config file {}: {}", env_path.display(), e);
+            });
         }
-        eprintln!(
-            "Warning: Config file specified by WATT_CONFIG not found: {}",
+        warn!(
+            "Config file specified by WATT_CONFIG not found: {}",
             env_path.display()
         );
     }
 
-    // System-wide paths
-    let config_paths = vec![
-        PathBuf::from("/etc/xdg/watt/config.toml"),
-        PathBuf::from("/etc/watt.toml"),
-    ];
-
-    for path in config_paths {
+    let mut first_error = None;
+    for path in SYSTEM_CONFIG_PATHS.iter().map(PathBuf::from) {
         if path.exists() {
-            println!("Loading config from: {}", path.display());
+            info!("Loading config from: {}", path.display());
             match load_and_parse_config(&path) {
                 Ok(config) => return Ok(config),
                 Err(e) => {
-                    eprintln!("Error with config file {}: {}", path.display(), e);
+                    error!("Error with config file {}: {}", path.display(), e);
                     // Continue trying other files
+                    first_error.get_or_insert(e);
                 }
             }
         }
     }
 
-    println!("No configuration file found or all failed to parse. Using default configuration.");
+    // A broken file is not the same as no file, callers decide what to do
+    if let Some(e) = first_error {
+        return Err(e);
+    }
+
+    info!("No configuration file found. Using default configuration.");
     // Construct default AppConfig by converting default AppConfigToml
     let default_toml_config = AppConfigToml::default();
     Ok(AppConfig {
         charger: ProfileConfig::from(default_toml_config.charger),
         battery: ProfileConfig::from(default_toml_config.battery),
+        balanced: None,
+        profiles: BTreeMap::new(),
         ignored_power_supplies: default_toml_config.ignored_power_supplies,
+        ac_sources: default_toml_config.ac_sources,
+        ac_detection: default_toml_config.ac_detection,
+        clamshell_profile: None,
+        temperature_sensor: None,
+        count_iowait_as_busy: false,
+        process_boost: None,
+        load_profile: None,
         daemon: DaemonConfig::default(),
     })
 }
@@ -87,6 +117,61 @@ fn load_and_parse_config(path: &Path) -> Result<AppConfig, ConfigError> {
     // Handle inheritance of values from global to profile configs
     let mut charger_profile = toml_app_config.charger.clone();
     let mut battery_profile = toml_app_config.battery.clone();
+    let mut balanced_profile = toml_app_config.balanced.clone();
+    let mut named_profiles = toml_app_config.profiles.clone();
+
+    // A built-in name would be taken by the mode of that name when forcing it
+    if let Some(name) = named_profiles
+        .keys()
+        .find(|name| name.parse::<OperationalMode>().is_ok() || name.eq_ignore_ascii_case("auto"))
+    {
+        return Err(ConfigError::Validation(format!(
+            "[profiles.{name}] uses a built-in profile name, pick another one"
+        )));
+    }
+
+    // Checked here, as a typo would only show once the lid closes on a dock
+    if let Some(name) = &toml_app_config.clamshell_profile {
+        match name.parse::<ForcedProfile>() {
+            Ok(ForcedProfile::Named(name)) if !named_profiles.contains_key(&name) => {
+                return Err(ConfigError::Validation(format!(
+                    "clamshell_profile names '{name}', which is no built-in profile or [profiles.{name}] section"
+                )));
+            }
+            Ok(_) => {}
+            Err(e) => return Err(ConfigError::Validation(format!("clamshell_profile: {e}"))),
+        }
+    }
+
+    if let Some(settings) = &toml_app_config.load_profile {
+        if !settings.above.is_finite() || settings.above <= 0.0 {
+            return Err(ConfigError::Validation(format!(
+                "load_profile.above must be a positive number, got {}",
+                settings.above
+            )));
+        }
+        match settings.profile.parse::<ForcedProfile>() {
+            Ok(ForcedProfile::Named(name)) if !named_profiles.contains_key(&name) => {
+                return Err(ConfigError::Validation(format!(
+                    "load_profile.profile names '{name}', which is no built-in profile or [profiles.{name}] section"
+                )));
+            }
+            Ok(_) => {}
+            Err(e) => {
+                return Err(ConfigError::Validation(format!(
+                    "load_profile.profile: {e}"
+                )));
+            }
+        }
+    }
+
+    if let Some(Err(e)) = toml_app_config
+        .temperature_sensor
+        .as_deref()
+        .map(str::parse::<SensorSpec>)
+    {
+        return Err(ConfigError::Validation(format!("temperature_sensor: {e}")));
+    }
 
     // Clone global battery_charge_thresholds once if it exists
     if let Some(global_thresholds) = toml_app_config.battery_charge_thresholds {
@@ -95,6 +180,16 @@ fn load_and_parse_config(path: &Path) -> Result<AppConfig, ConfigError> {
             charger_profile.battery_charge_thresholds = Some(global_thresholds.clone());
         }
 
+        // Apply to balanced and named profiles if not already set
+        for profile in balanced_profile
+            .iter_mut()
+            .chain(named_profiles.values_mut())
+        {
+            if profile.battery_charge_thresholds.is_none() {
+                profile.battery_charge_thresholds = Some(global_thresholds.clone());
+            }
+        }
+
         // Apply to battery profile if not already set
         if battery_profile.battery_charge_thresholds.is_none() {
             battery_profile.battery_charge_thresholds = Some(global_thresholds);
@@ -105,7 +200,19 @@ fn load_and_parse_config(path: &Path) -> Result<AppConfig, ConfigError> {
     Ok(AppConfig {
         charger: ProfileConfig::from(charger_profile),
         battery: ProfileConfig::from(battery_profile),
+        balanced: balanced_profile.map(ProfileConfig::from),
+        profiles: named_profiles
+            .into_iter()
+            .map(|(name, profile)| (name, ProfileConfig::from(profile)))
+            .collect(),
         ignored_power_supplies: toml_app_config.ignored_power_supplies,
+        ac_sources: toml_app_config.ac_sources,
+        ac_detection: toml_app_config.ac_detection,
+        clamshell_profile: toml_app_config.clamshell_profile,
+        temperature_sensor: toml_app_config.temperature_sensor,
+        count_iowait_as_busy: toml_app_config.count_iowait_as_busy,
+        process_boost: toml_app_config.process_boost,
+        load_profile: toml_app_config.load_profile,
         daemon: DaemonConfig {
             poll_interval_sec: toml_app_config.daemon.poll_interval_sec,
             adaptive_interval: toml_app_config.daemon.adaptive_interval,
@@ -113,7 +220,46 @@ fn load_and_parse_config(path: &Path) -> Result<AppConfig, ConfigError> {
             max_poll_interval_sec: toml_app_config.daemon.max_poll_interval_sec,
             throttle_on_battery: toml_app_config.daemon.throttle_on_battery,
             log_level: toml_app_config.daemon.log_level,
+            log_every_cycle: toml_app_config.daemon.log_every_cycle,
             stats_file_path: toml_app_config.daemon.stats_file_path,
+            stats_include_counters: toml_app_config.daemon.stats_include_counters,
+            stats_include_decisions: toml_app_config.daemon.stats_include_decisions,
+            pid_file: toml_app_config.daemon.pid_file,
+            restore_on_exit: toml_app_config.daemon.restore_on_exit,
+            event_driven: toml_app_config.daemon.event_driven,
+            idle_load_threshold: toml_app_config.daemon.idle_load_threshold,
+            idle_cycles: toml_app_config.daemon.idle_cycles,
+            adaptive: toml_app_config.daemon.adaptive,
+            metrics_address: toml_app_config.daemon.metrics_address,
+            log_format: toml_app_config.daemon.log_format,
+            log_file: toml_app_config.daemon.log_file,
+            log_file_max_size_mb: toml_app_config.daemon.log_file_max_size_mb,
+            log_file_keep: toml_app_config.daemon.log_file_keep,
+            error_repeat_limit: toml_app_config.daemon.error_repeat_limit,
+            error_summary_interval_sec: toml_app_config.daemon.error_summary_interval_sec,
+            error_summary_max_interval_sec: toml_app_config.daemon.error_summary_max_interval_sec,
+            max_consecutive_panics: toml_app_config.daemon.max_consecutive_panics,
+            turbo_state_file: toml_app_config.daemon.turbo_state_file,
+            run_as_user: toml_app_config.daemon.run_as_user,
+            chown_sysfs: toml_app_config.daemon.chown_sysfs,
+            drift_policy: toml_app_config.daemon.drift_policy,
+            respect_manual_changes: toml_app_config.daemon.respect_manual_changes,
+            rollback_on_failure: toml_app_config.daemon.rollback_on_failure,
+            apply_order: toml_app_config.daemon.apply_order,
+            min_write_interval_sec: toml_app_config.daemon.min_write_interval_sec,
+            cpufreq_wait_sec: toml_app_config.daemon.cpufreq_wait_sec,
+            state_file_enabled: toml_app_config.daemon.state_file_enabled,
+            state_file_path: toml_app_config.daemon.state_file_path,
+            history_enabled: toml_app_config.daemon.history_enabled,
+            history_file: toml_app_config.daemon.history_file,
+            history_max_size_mb: toml_app_config.daemon.history_max_size_mb,
+            history_retention_days: toml_app_config.daemon.history_retention_days,
+            notifications: toml_app_config.daemon.notifications,
+            notify: toml_app_config.daemon.notify,
+            smoothing: toml_app_config.daemon.smoothing,
+            thermal_safety: toml_app_config.daemon.thermal_safety,
+            thermal_safety_critical_celsius: toml_app_config.daemon.thermal_safety_critical_celsius,
+            thermal_safety_recovery_celsius: toml_app_config.daemon.thermal_safety_recovery_celsius,
         },
     })
 }
@@ -1,7 +1,9 @@
 // Configuration types and structures for Watt
 use crate::core::TurboSetting;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::convert::TryFrom;
+use std::fmt;
 
 /// Defines constant-returning functions used for default values.
 /// This hopefully reduces repetition since we have way too many default functions
@@ -65,10 +67,45 @@ pub struct ProfileConfig {
     pub turbo_auto_settings: TurboAutoSettings,
     #[serde(default)]
     pub enable_auto_turbo: bool,
+    #[serde(default)]
+    pub epp_auto_settings: EppAutoSettings,
+    /// Only allowed with `governor = "auto"`, which uses the defaults without
+    /// it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub governor_auto_settings: Option<GovernorAutoSettings>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thermal_backoff: Option<ThermalBackoff>,
+    /// Seconds over which a raised max frequency is stepped up to its new
+    /// value. A lowered one always applies at once.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub transition_ramp_sec: Option<u64>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub battery_charge_thresholds: Option<BatteryChargeThresholds>,
 }
 
+/// `governor` value that has the engine switch governors by load, per
+/// `governor_auto_settings`
+pub const AUTO_GOVERNOR: &str = "auto";
+
+/// `epp` value that has the engine pick the EPP from `epp_auto_settings`
+pub const AUTO_EPP: &str = "auto";
+
+impl ProfileConfig {
+    /// Whether the governor follows the load, with `governor = "auto"`
+    pub fn auto_governor(&self) -> bool {
+        self.governor
+            .as_deref()
+            .is_some_and(|governor| governor.eq_ignore_ascii_case(AUTO_GOVERNOR))
+    }
+
+    /// Whether the EPP follows the load, with `epp = "auto"`
+    pub fn auto_epp(&self) -> bool {
+        self.epp
+            .as_deref()
+            .is_some_and(|epp| epp.eq_ignore_ascii_case(AUTO_EPP))
+    }
+}
+
 impl Default for ProfileConfig {
     fn default() -> Self {
         Self {
@@ -81,6 +118,10 @@ impl Default for ProfileConfig {
             platform_profile: None, // no override
             turbo_auto_settings: TurboAutoSettings::default(),
             enable_auto_turbo: default_enable_auto_turbo(),
+            epp_auto_settings: EppAutoSettings::default(),
+            governor_auto_settings: None,
+            thermal_backoff: None,
+            transition_ramp_sec: None,
             battery_charge_thresholds: None,
         }
     }
@@ -92,11 +133,82 @@ pub struct AppConfig {
     pub charger: ProfileConfig,
     #[serde(default)]
     pub battery: ProfileConfig,
+    /// Only applied when forced, see [`Self::balanced`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub balanced: Option<ProfileConfig>,
+    /// Profiles only applied when forced by name, from `[profiles.<name>]`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub profiles: BTreeMap<String, ProfileConfig>,
     pub ignored_power_supplies: Option<Vec<String>>,
+    /// Power supplies whose `online` alone decides whether the system is on
+    /// AC, overriding [`Self::ac_detection`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ac_sources: Option<Vec<String>>,
+    #[serde(default)]
+    pub ac_detection: AcDetection,
+    /// Profile forced while the lid is closed with an external display
+    /// connected, whatever the power source
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub clamshell_profile: Option<String>,
+    /// CPU temperature sensor, as `hwmon:<driver>/<label>` or a sysfs path,
+    /// instead of the one picked automatically
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature_sensor: Option<String>,
+    /// Counts time waiting for I/O and time taken by the hypervisor as busy
+    /// when measuring CPU usage, rather than as idle
+    #[serde(default)]
+    pub count_iowait_as_busy: bool,
+    /// Forces the performance profile while listed processes run
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub process_boost: Option<ProcessBoostSettings>,
+    /// Forces a profile while the load average is high
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub load_profile: Option<LoadProfileSettings>,
     #[serde(default)]
     pub daemon: DaemonConfig,
 }
 
+impl AppConfig {
+    /// The profile of the balanced mode: `[balanced]`, or the battery profile
+    /// when the file has none
+    pub fn balanced(&self) -> &ProfileConfig {
+        self.balanced.as_ref().unwrap_or(&self.battery)
+    }
+
+    /// Names of the profiles that can be forced by name
+    pub fn profile_names(&self) -> Vec<String> {
+        self.profiles.keys().cloned().collect()
+    }
+}
+
+/// How the system decides it is on AC power, unless `ac_sources` pins it to
+/// specific power supplies
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum AcDetection {
+    /// Any online Mains or USB supply, or one named like an AC adapter, and
+    /// AC on a desktop without either
+    #[default]
+    Auto,
+    /// Any online supply of type Mains
+    AnyMainsOnline,
+    /// Every battery reports Charging, Full or Not charging
+    AllBatteriesReportingAc,
+    /// No battery reports Discharging
+    BatteryStatusBased,
+}
+
+impl fmt::Display for AcDetection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Auto => "auto",
+            Self::AnyMainsOnline => "any_mains_online",
+            Self::AllBatteriesReportingAc => "all_batteries_reporting_ac",
+            Self::BatteryStatusBased => "battery_status_based",
+        })
+    }
+}
+
 // Error type for config loading
 #[derive(Debug, thiserror::Error)]
 pub enum ConfigError {
@@ -123,6 +235,10 @@ pub struct ProfileConfigToml {
     pub turbo_auto_settings: Option<TurboAutoSettings>,
     #[serde(default = "default_enable_auto_turbo")]
     pub enable_auto_turbo: bool,
+    pub epp_auto_settings: Option<EppAutoSettings>,
+    pub governor_auto_settings: Option<GovernorAutoSettings>,
+    pub thermal_backoff: Option<ThermalBackoff>,
+    pub transition_ramp_sec: Option<u64>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub battery_charge_thresholds: Option<BatteryChargeThresholds>,
 }
@@ -133,9 +249,27 @@ pub struct AppConfigToml {
     pub charger: ProfileConfigToml,
     #[serde(default)]
     pub battery: ProfileConfigToml,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub balanced: Option<ProfileConfigToml>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub profiles: BTreeMap<String, ProfileConfigToml>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub battery_charge_thresholds: Option<BatteryChargeThresholds>,
     pub ignored_power_supplies: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ac_sources: Option<Vec<String>>,
+    #[serde(default)]
+    pub ac_detection: AcDetection,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub clamshell_profile: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature_sensor: Option<String>,
+    #[serde(default)]
+    pub count_iowait_as_busy: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub process_boost: Option<ProcessBoostSettings>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub load_profile: Option<LoadProfileSettings>,
     #[serde(default)]
     pub daemon: DaemonConfigToml,
 }
@@ -152,12 +286,16 @@ impl Default for ProfileConfigToml {
             platform_profile: None,
             turbo_auto_settings: None,
             enable_auto_turbo: default_enable_auto_turbo(),
+            epp_auto_settings: None,
+            governor_auto_settings: None,
+            thermal_backoff: None,
+            transition_ramp_sec: None,
             battery_charge_thresholds: None,
         }
     }
 }
 
-#[derive(Deserialize, Serialize, Debug, Clone)]
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
 pub struct TurboAutoSettings {
     #[serde(default = "default_load_threshold_high")]
     pub load_threshold_high: f32,
@@ -170,6 +308,169 @@ pub struct TurboAutoSettings {
     /// This is only used at first launch or after a reset.
     #[serde(default = "default_initial_turbo_state")]
     pub initial_turbo_state: bool,
+    /// Number of recent CPU usage samples the load thresholds are compared
+    /// against, so a short spike does not enable turbo. 1 uses only the
+    /// latest sample.
+    #[serde(default = "default_load_window_samples")]
+    pub load_window_samples: usize,
+    /// Percentile of the window to compare against, e.g. 90. The average of
+    /// the window is used when unset.
+    #[serde(default)]
+    pub load_window_percentile: Option<f32>,
+    /// How the per-core usage is reduced to the load compared against the
+    /// thresholds
+    #[serde(default)]
+    pub load_metric: LoadMetric,
+    /// Number of busiest cores averaged with `load_metric = "top_n_average"`
+    #[serde(default = "default_top_n")]
+    pub top_n: usize,
+    /// Battery charge below which turbo stays disabled on battery power,
+    /// whatever the load. Turbo may enable again a couple of percent above it.
+    #[serde(default)]
+    pub min_battery_percent_for_turbo: Option<u8>,
+    /// Throttle events per polling interval at which turbo is disabled,
+    /// whatever the load. Needs the `thermal_throttle` counters of Intel CPUs.
+    #[serde(default)]
+    pub throttle_events_limit: Option<u64>,
+    /// Least CPU pressure (PSI "some" avg10, in percent) for high load to
+    /// enable turbo, so busy-waiting or I/O-bound load does not count.
+    /// Ignored on kernels without PSI.
+    #[serde(default)]
+    pub pressure_threshold_avg10: Option<f32>,
+    /// Like `pressure_threshold_avg10`, over the last minute
+    #[serde(default)]
+    pub pressure_threshold_avg60: Option<f32>,
+    /// Turns turbo on and off in turn once the load has stayed high for a
+    /// while, rather than leaving it on
+    #[serde(default)]
+    pub duty_cycle: Option<TurboDutyCycle>,
+    /// Which temperature is compared against `temp_threshold_high`
+    #[serde(default)]
+    pub temperature_source: TurboTemperatureSource,
+    /// How turbo is decided once the temperature and battery allow it
+    #[serde(default)]
+    pub strategy: TurboStrategyKind,
+    /// Weights and thresholds of `strategy = "weighted_score"`
+    #[serde(default)]
+    pub weighted_score: WeightedScoreSettings,
+    /// Times of day turbo is on with `strategy = "schedule"`
+    #[serde(default)]
+    pub schedule: Vec<TurboScheduleWindow>,
+}
+
+/// The temperature auto turbo is held back by
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum TurboTemperatureSource {
+    /// The CPU temperature sensor, or the average of the cores without one
+    #[default]
+    Package,
+    /// The hottest core, where per-core or per-CCD sensors exist, so a
+    /// single-threaded load cannot overheat one core unnoticed
+    HottestCore,
+}
+
+/// The model auto turbo decides with
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum TurboStrategyKind {
+    /// Load thresholds, holding the previous state in between
+    #[default]
+    Threshold,
+    /// A score of load, temperature headroom and battery level
+    WeightedScore,
+    /// On during the `schedule` windows, off outside them
+    Schedule,
+}
+
+impl TurboStrategyKind {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Threshold => "threshold",
+            Self::WeightedScore => "weighted_score",
+            Self::Schedule => "schedule",
+        }
+    }
+}
+
+/// Each input is scaled to 0 (against turbo) to 1 (for turbo), and the score
+/// is their weighted average
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct WeightedScoreSettings {
+    /// Weight of the load, from 0 at idle to 1 at full load
+    #[serde(default = "default_score_load_weight")]
+    pub load_weight: f32,
+    /// Weight of the temperature headroom, from 1 when cold to 0 at
+    /// `temp_threshold_high`
+    #[serde(default = "default_score_temperature_weight")]
+    pub temperature_weight: f32,
+    /// Weight of the battery level, 1 on AC power
+    #[serde(default = "default_score_battery_weight")]
+    pub battery_weight: f32,
+    /// Score at or above which turbo is enabled
+    #[serde(default = "default_score_enable_above")]
+    pub enable_above: f32,
+    /// Score at or below which turbo is disabled. In between, the previous
+    /// state is kept.
+    #[serde(default = "default_score_disable_below")]
+    pub disable_below: f32,
+}
+
+impl Default for WeightedScoreSettings {
+    fn default() -> Self {
+        Self {
+            load_weight: DEFAULT_SCORE_LOAD_WEIGHT,
+            temperature_weight: DEFAULT_SCORE_TEMPERATURE_WEIGHT,
+            battery_weight: DEFAULT_SCORE_BATTERY_WEIGHT,
+            enable_above: DEFAULT_SCORE_ENABLE_ABOVE,
+            disable_below: DEFAULT_SCORE_DISABLE_BELOW,
+        }
+    }
+}
+
+/// A time of day range in local time, wrapping past midnight when `to` is
+/// before `from`
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TurboScheduleWindow {
+    pub from: jiff::civil::Time,
+    pub to: jiff::civil::Time,
+}
+
+/// Turbo switched on and off in turn under sustained high load, which gives
+/// steadier clocks than turbo left on until the CPU throttles on some machines
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TurboDutyCycle {
+    /// Seconds the load has to stay above `load_threshold_high` before
+    /// cycling starts
+    #[serde(default = "default_duty_cycle_after_sec")]
+    pub after_sec: u64,
+    /// Seconds turbo is on in each cycle
+    pub on_sec: u64,
+    /// Seconds turbo is off in each cycle
+    pub off_sec: u64,
+}
+
+/// The CPU load auto turbo decides on
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum LoadMetric {
+    /// Average usage over all cores
+    #[default]
+    Average,
+    /// Usage of the busiest core, so a single-threaded load counts fully
+    MaxCore,
+    /// Average usage of the `top_n` busiest cores
+    TopNAverage,
+}
+
+impl LoadMetric {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Average => "average",
+            Self::MaxCore => "max_core",
+            Self::TopNAverage => "top_n_average",
+        }
+    }
 }
 
 // Default thresholds for Auto turbo mode
@@ -177,6 +478,14 @@ pub const DEFAULT_LOAD_THRESHOLD_HIGH: f32 = 70.0; // enable turbo if load is ab
 pub const DEFAULT_LOAD_THRESHOLD_LOW: f32 = 30.0; // disable turbo if load is below this
 pub const DEFAULT_TEMP_THRESHOLD_HIGH: f32 = 75.0; // disable turbo if temperature is above this
 pub const DEFAULT_INITIAL_TURBO_STATE: bool = false; // by default, start with turbo disabled
+pub const DEFAULT_LOAD_WINDOW_SAMPLES: usize = 1; // by default, only the latest sample counts
+pub const DEFAULT_TOP_N: usize = 2; // busiest cores averaged for top_n_average
+pub const DEFAULT_DUTY_CYCLE_AFTER_SEC: u64 = 60; // sustained high load before duty cycling
+pub const DEFAULT_SCORE_LOAD_WEIGHT: f32 = 0.6;
+pub const DEFAULT_SCORE_TEMPERATURE_WEIGHT: f32 = 0.25;
+pub const DEFAULT_SCORE_BATTERY_WEIGHT: f32 = 0.15;
+pub const DEFAULT_SCORE_ENABLE_ABOVE: f32 = 0.6; // enable turbo at or above this score
+pub const DEFAULT_SCORE_DISABLE_BELOW: f32 = 0.4; // disable turbo at or below this score
 
 default_const!(
     default_load_threshold_high,
@@ -194,6 +503,34 @@ default_const!(
     bool,
     DEFAULT_INITIAL_TURBO_STATE
 );
+default_const!(
+    default_load_window_samples,
+    usize,
+    DEFAULT_LOAD_WINDOW_SAMPLES
+);
+default_const!(default_top_n, usize, DEFAULT_TOP_N);
+default_const!(
+    default_duty_cycle_after_sec,
+    u64,
+    DEFAULT_DUTY_CYCLE_AFTER_SEC
+);
+default_const!(default_score_load_weight, f32, DEFAULT_SCORE_LOAD_WEIGHT);
+default_const!(
+    default_score_temperature_weight,
+    f32,
+    DEFAULT_SCORE_TEMPERATURE_WEIGHT
+);
+default_const!(
+    default_score_battery_weight,
+    f32,
+    DEFAULT_SCORE_BATTERY_WEIGHT
+);
+default_const!(default_score_enable_above, f32, DEFAULT_SCORE_ENABLE_ABOVE);
+default_const!(
+    default_score_disable_below,
+    f32,
+    DEFAULT_SCORE_DISABLE_BELOW
+);
 
 impl Default for TurboAutoSettings {
     fn default() -> Self {
@@ -202,10 +539,200 @@ impl Default for TurboAutoSettings {
             load_threshold_low: DEFAULT_LOAD_THRESHOLD_LOW,
             temp_threshold_high: DEFAULT_TEMP_THRESHOLD_HIGH,
             initial_turbo_state: DEFAULT_INITIAL_TURBO_STATE,
+            load_window_samples: DEFAULT_LOAD_WINDOW_SAMPLES,
+            load_window_percentile: None,
+            load_metric: LoadMetric::default(),
+            top_n: DEFAULT_TOP_N,
+            min_battery_percent_for_turbo: None,
+            throttle_events_limit: None,
+            pressure_threshold_avg10: None,
+            pressure_threshold_avg60: None,
+            duty_cycle: None,
+            temperature_source: TurboTemperatureSource::default(),
+            strategy: TurboStrategyKind::default(),
+            weighted_score: WeightedScoreSettings::default(),
+            schedule: Vec::new(),
         }
     }
 }
 
+/// How `epp = "auto"` moves the EPP with the CPU load
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct EppAutoSettings {
+    /// EPP values by the load they apply from, highest load first. The last
+    /// tier covers any load below the others.
+    #[serde(default = "default_epp_tiers")]
+    pub tiers: Vec<EppTier>,
+    /// Percentage points the load has to fall below a tier's `min_load`
+    /// before the EPP moves down from it
+    #[serde(default = "default_epp_hysteresis")]
+    pub hysteresis: f32,
+    /// Least time between two EPP changes, so the firmware is not rewritten
+    /// every cycle
+    #[serde(default = "default_epp_min_interval_sec")]
+    pub min_interval_sec: u64,
+    /// Load samples the tiers are compared against, as with auto turbo
+    #[serde(default = "default_epp_load_window_samples")]
+    pub load_window_samples: usize,
+    #[serde(default)]
+    pub load_window_percentile: Option<f32>,
+    #[serde(default)]
+    pub load_metric: LoadMetric,
+    #[serde(default = "default_top_n")]
+    pub top_n: usize,
+}
+
+/// An EPP value and the load from which `epp = "auto"` uses it
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct EppTier {
+    pub min_load: f32,
+    pub epp: String,
+}
+
+pub const DEFAULT_EPP_HYSTERESIS: f32 = 5.0; // load points below a tier before leaving it
+pub const DEFAULT_EPP_MIN_INTERVAL_SEC: u64 = 30; // least time between EPP changes
+pub const DEFAULT_EPP_LOAD_WINDOW_SAMPLES: usize = 5; // follow sustained load, not spikes
+
+fn default_epp_tiers() -> Vec<EppTier> {
+    [
+        (70.0, "performance"),
+        (20.0, "balance_performance"),
+        (0.0, "balance_power"),
+    ]
+    .into_iter()
+    .map(|(min_load, epp)| EppTier {
+        min_load,
+        epp: epp.to_string(),
+    })
+    .collect()
+}
+
+default_const!(default_epp_hysteresis, f32, DEFAULT_EPP_HYSTERESIS);
+default_const!(
+    default_epp_min_interval_sec,
+    u64,
+    DEFAULT_EPP_MIN_INTERVAL_SEC
+);
+default_const!(
+    default_epp_load_window_samples,
+    usize,
+    DEFAULT_EPP_LOAD_WINDOW_SAMPLES
+);
+
+impl Default for EppAutoSettings {
+    fn default() -> Self {
+        Self {
+            tiers: default_epp_tiers(),
+            hysteresis: DEFAULT_EPP_HYSTERESIS,
+            min_interval_sec: DEFAULT_EPP_MIN_INTERVAL_SEC,
+            load_window_samples: DEFAULT_EPP_LOAD_WINDOW_SAMPLES,
+            load_window_percentile: None,
+            load_metric: LoadMetric::default(),
+            top_n: DEFAULT_TOP_N,
+        }
+    }
+}
+
+/// How `governor = "auto"` switches between two governors by CPU load
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct GovernorAutoSettings {
+    /// Governor below `load_threshold_low`, e.g. powersave or conservative
+    #[serde(default = "default_low_load_governor")]
+    pub low_load_governor: String,
+    /// Governor above `load_threshold_high`, e.g. ondemand or performance
+    #[serde(default = "default_high_load_governor")]
+    pub high_load_governor: String,
+    #[serde(default = "default_load_threshold_high")]
+    pub load_threshold_high: f32,
+    #[serde(default = "default_load_threshold_low")]
+    pub load_threshold_low: f32,
+    /// Least time a governor stays before switching to the other one
+    #[serde(default = "default_governor_min_dwell_sec")]
+    pub min_dwell_sec: u64,
+    /// Load samples the thresholds are compared against, as with auto turbo
+    #[serde(default = "default_governor_load_window_samples")]
+    pub load_window_samples: usize,
+    #[serde(default)]
+    pub load_window_percentile: Option<f32>,
+    #[serde(default)]
+    pub load_metric: LoadMetric,
+    #[serde(default = "default_top_n")]
+    pub top_n: usize,
+}
+
+pub const DEFAULT_LOW_LOAD_GOVERNOR: &str = "powersave";
+pub const DEFAULT_HIGH_LOAD_GOVERNOR: &str = "performance";
+pub const DEFAULT_GOVERNOR_MIN_DWELL_SEC: u64 = 60; // least time between governor switches
+pub const DEFAULT_GOVERNOR_LOAD_WINDOW_SAMPLES: usize = 5; // follow sustained load, not spikes
+
+fn default_low_load_governor() -> String {
+    DEFAULT_LOW_LOAD_GOVERNOR.to_string()
+}
+
+fn default_high_load_governor() -> String {
+    DEFAULT_HIGH_LOAD_GOVERNOR.to_string()
+}
+
+default_const!(
+    default_governor_min_dwell_sec,
+    u64,
+    DEFAULT_GOVERNOR_MIN_DWELL_SEC
+);
+default_const!(
+    default_governor_load_window_samples,
+    usize,
+    DEFAULT_GOVERNOR_LOAD_WINDOW_SAMPLES
+);
+
+impl Default for GovernorAutoSettings {
+    fn default() -> Self {
+        Self {
+            low_load_governor: default_low_load_governor(),
+            high_load_governor: default_high_load_governor(),
+            load_threshold_high: DEFAULT_LOAD_THRESHOLD_HIGH,
+            load_threshold_low: DEFAULT_LOAD_THRESHOLD_LOW,
+            min_dwell_sec: DEFAULT_GOVERNOR_MIN_DWELL_SEC,
+            load_window_samples: DEFAULT_GOVERNOR_LOAD_WINDOW_SAMPLES,
+            load_window_percentile: None,
+            load_metric: LoadMetric::default(),
+            top_n: DEFAULT_TOP_N,
+        }
+    }
+}
+
+/// Graduated limits on the max frequency as the CPU gets hotter, rather than
+/// only cutting turbo
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct ThermalBackoff {
+    /// Caps by the temperature they apply above, coolest first
+    pub steps: Vec<ThermalStep>,
+    /// Degrees the temperature has to fall below a step before its cap is
+    /// lifted
+    #[serde(default = "default_thermal_hysteresis")]
+    pub hysteresis: f32,
+    /// Temperature readings averaged before comparing against the steps
+    #[serde(default = "default_thermal_window_samples")]
+    pub temp_window_samples: usize,
+}
+
+/// A max frequency cap, in percent of the hardware maximum, above a
+/// temperature
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct ThermalStep {
+    pub above_celsius: f32,
+    pub max_percent: u8,
+}
+
+pub const DEFAULT_THERMAL_HYSTERESIS: f32 = 3.0; // degrees below a step before lifting its cap
+pub const DEFAULT_THERMAL_WINDOW_SAMPLES: usize = 3; // smooth out single hot readings
+
+default_const!(default_thermal_hysteresis, f32, DEFAULT_THERMAL_HYSTERESIS);
+default_const!(
+    default_thermal_window_samples,
+    usize,
+    DEFAULT_THERMAL_WINDOW_SAMPLES
+);
+
 impl From<ProfileConfigToml> for ProfileConfig {
     fn from(toml_config: ProfileConfigToml) -> Self {
         Self {
@@ -225,6 +752,10 @@ impl From<ProfileConfigToml> for ProfileConfig {
             platform_profile: toml_config.platform_profile,
             turbo_auto_settings: toml_config.turbo_auto_settings.unwrap_or_default(),
             enable_auto_turbo: toml_config.enable_auto_turbo,
+            epp_auto_settings: toml_config.epp_auto_settings.unwrap_or_default(),
+            governor_auto_settings: toml_config.governor_auto_settings,
+            thermal_backoff: toml_config.thermal_backoff,
+            transition_ramp_sec: toml_config.transition_ramp_sec,
             battery_charge_thresholds: toml_config.battery_charge_thresholds,
         }
     }
@@ -244,8 +775,181 @@ pub struct DaemonConfig {
     pub throttle_on_battery: bool,
     #[serde(default = "default_log_level")]
     pub log_level: LogLevel,
+    #[serde(default)]
+    pub log_every_cycle: bool,
     #[serde(default = "default_stats_file_path")]
     pub stats_file_path: Option<String>,
+    #[serde(default = "default_stats_include_counters")]
+    pub stats_include_counters: bool,
+    #[serde(default)]
+    pub stats_include_decisions: bool,
+    #[serde(default = "default_pid_file")]
+    pub pid_file: String,
+    #[serde(default = "default_restore_on_exit")]
+    pub restore_on_exit: bool,
+    #[serde(default = "default_event_driven")]
+    pub event_driven: bool,
+    #[serde(default = "default_idle_load_threshold")]
+    pub idle_load_threshold: f32,
+    #[serde(default = "default_idle_cycles")]
+    pub idle_cycles: u32,
+    #[serde(default)]
+    pub adaptive: AdaptiveIntervalSettings,
+    #[serde(default)]
+    pub metrics_address: Option<String>,
+    #[serde(default)]
+    pub log_format: LogFormat,
+    #[serde(default)]
+    pub log_file: Option<String>,
+    #[serde(default = "default_log_file_max_size_mb")]
+    pub log_file_max_size_mb: u64,
+    #[serde(default = "default_log_file_keep")]
+    pub log_file_keep: u32,
+    #[serde(default = "default_error_repeat_limit")]
+    pub error_repeat_limit: u32,
+    #[serde(default = "default_error_summary_interval_sec")]
+    pub error_summary_interval_sec: u64,
+    #[serde(default = "default_error_summary_max_interval_sec")]
+    pub error_summary_max_interval_sec: u64,
+    #[serde(default = "default_max_consecutive_panics")]
+    pub max_consecutive_panics: u32,
+    #[serde(default = "default_turbo_state_file")]
+    pub turbo_state_file: String,
+    #[serde(default)]
+    pub run_as_user: Option<String>,
+    #[serde(default = "default_chown_sysfs")]
+    pub chown_sysfs: bool,
+    #[serde(default)]
+    pub drift_policy: DriftPolicy,
+    #[serde(default)]
+    pub respect_manual_changes: bool,
+    #[serde(default = "default_rollback_on_failure")]
+    pub rollback_on_failure: bool,
+    #[serde(default)]
+    pub apply_order: Vec<ApplyStep>,
+    #[serde(default)]
+    pub min_write_interval_sec: BTreeMap<ApplyStep, u64>,
+    #[serde(default = "default_cpufreq_wait_sec")]
+    pub cpufreq_wait_sec: u64,
+    #[serde(default = "default_state_file_enabled")]
+    pub state_file_enabled: bool,
+    #[serde(default = "default_state_file_path")]
+    pub state_file_path: String,
+    #[serde(default = "default_history_enabled")]
+    pub history_enabled: bool,
+    #[serde(default = "default_history_file")]
+    pub history_file: String,
+    #[serde(default = "default_history_max_size_mb")]
+    pub history_max_size_mb: u64,
+    #[serde(default = "default_history_retention_days")]
+    pub history_retention_days: u64,
+    #[serde(default = "default_notifications")]
+    pub notifications: bool,
+    #[serde(default)]
+    pub notify: NotificationSettings,
+    #[serde(default)]
+    pub smoothing: SmoothingSettings,
+    #[serde(default = "default_thermal_safety")]
+    pub thermal_safety: bool,
+    #[serde(default)]
+    pub thermal_safety_critical_celsius: Option<f32>,
+    #[serde(default)]
+    pub thermal_safety_recovery_celsius: Option<f32>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// Human-readable lines
+    #[default]
+    Text,
+    /// One JSON object per line, with structured fields
+    Json,
+}
+
+impl DaemonConfig {
+    /// The drift policy in effect, `respect` when `respect_manual_changes` is
+    /// set whatever `drift_policy` says
+    pub const fn effective_drift_policy(&self) -> DriftPolicy {
+        if self.respect_manual_changes {
+            DriftPolicy::Respect
+        } else {
+            self.drift_policy
+        }
+    }
+}
+
+/// What the daemon does when a knob it set was changed by someone else
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum DriftPolicy {
+    /// Write the profile's value back
+    #[default]
+    Correct,
+    /// Log the change and leave it
+    Warn,
+    /// Leave the knob alone until the next profile switch
+    Respect,
+}
+
+/// A group of knobs the engine writes together, in the order given by
+/// `daemon.apply_order` or worked out from their dependencies
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum ApplyStep {
+    Governor,
+    Turbo,
+    Epp,
+    Epb,
+    /// Minimum and maximum frequency, in whichever order the change needs
+    Frequencies,
+    PlatformProfile,
+    ChargeThresholds,
+}
+
+impl ApplyStep {
+    /// Every step, in the order they were applied before they could be
+    /// ordered
+    pub const ALL: [Self; 7] = [
+        Self::Governor,
+        Self::Turbo,
+        Self::Epp,
+        Self::Epb,
+        Self::Frequencies,
+        Self::PlatformProfile,
+        Self::ChargeThresholds,
+    ];
+
+    /// Whether the step writes cpufreq settings, which need a cpufreq driver
+    pub const fn is_cpufreq(self) -> bool {
+        matches!(
+            self,
+            Self::Governor | Self::Turbo | Self::Epp | Self::Epb | Self::Frequencies
+        )
+    }
+
+    /// Seconds to leave between writes unless `daemon.min_write_interval_sec`
+    /// says otherwise. Knobs backed by the embedded controller get unhappy
+    /// when written often.
+    pub const fn default_min_write_interval_sec(self) -> u64 {
+        match self {
+            Self::PlatformProfile => 10,
+            Self::ChargeThresholds => 60,
+            _ => 0,
+        }
+    }
+
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Governor => "governor",
+            Self::Turbo => "turbo",
+            Self::Epp => "epp",
+            Self::Epb => "epb",
+            Self::Frequencies => "frequencies",
+            Self::PlatformProfile => "platform_profile",
+            Self::ChargeThresholds => "charge_thresholds",
+        }
+    }
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
@@ -265,7 +969,46 @@ impl Default for DaemonConfig {
             max_poll_interval_sec: default_max_poll_interval_sec(),
             throttle_on_battery: default_throttle_on_battery(),
             log_level: default_log_level(),
+            log_every_cycle: false,
             stats_file_path: default_stats_file_path(),
+            stats_include_counters: default_stats_include_counters(),
+            stats_include_decisions: false,
+            pid_file: default_pid_file(),
+            restore_on_exit: default_restore_on_exit(),
+            event_driven: default_event_driven(),
+            idle_load_threshold: default_idle_load_threshold(),
+            idle_cycles: default_idle_cycles(),
+            adaptive: AdaptiveIntervalSettings::default(),
+            metrics_address: None,
+            log_format: LogFormat::default(),
+            log_file: None,
+            log_file_max_size_mb: default_log_file_max_size_mb(),
+            log_file_keep: default_log_file_keep(),
+            error_repeat_limit: default_error_repeat_limit(),
+            error_summary_interval_sec: default_error_summary_interval_sec(),
+            error_summary_max_interval_sec: default_error_summary_max_interval_sec(),
+            max_consecutive_panics: default_max_consecutive_panics(),
+            turbo_state_file: default_turbo_state_file(),
+            run_as_user: None,
+            chown_sysfs: default_chown_sysfs(),
+            drift_policy: DriftPolicy::default(),
+            respect_manual_changes: false,
+            rollback_on_failure: default_rollback_on_failure(),
+            apply_order: Vec::new(),
+            min_write_interval_sec: BTreeMap::new(),
+            cpufreq_wait_sec: default_cpufreq_wait_sec(),
+            state_file_enabled: default_state_file_enabled(),
+            state_file_path: default_state_file_path(),
+            history_enabled: default_history_enabled(),
+            history_file: default_history_file(),
+            history_max_size_mb: default_history_max_size_mb(),
+            history_retention_days: default_history_retention_days(),
+            notifications: default_notifications(),
+            notify: NotificationSettings::default(),
+            smoothing: SmoothingSettings::default(),
+            thermal_safety: default_thermal_safety(),
+            thermal_safety_critical_celsius: None,
+            thermal_safety_recovery_celsius: None,
         }
     }
 }
@@ -277,7 +1020,214 @@ default_const!(default_max_poll_interval_sec, u64, 30);
 default_const!(default_throttle_on_battery, bool, true);
 default_const!(default_log_level, LogLevel, LogLevel::Info);
 default_const!(default_stats_file_path, Option<String>, None);
+default_const!(default_stats_include_counters, bool, false);
 default_const!(default_enable_auto_turbo, bool, true);
+default_const!(default_restore_on_exit, bool, false);
+default_const!(default_rollback_on_failure, bool, false);
+default_const!(default_event_driven, bool, true);
+default_const!(default_idle_load_threshold, f32, 10.0);
+default_const!(default_idle_cycles, u32, 3);
+default_const!(default_log_file_max_size_mb, u64, 10);
+default_const!(default_log_file_keep, u32, 5);
+default_const!(default_error_repeat_limit, u32, 3);
+default_const!(default_error_summary_interval_sec, u64, 60);
+default_const!(default_error_summary_max_interval_sec, u64, 3600);
+default_const!(default_max_consecutive_panics, u32, 5);
+default_const!(default_chown_sysfs, bool, false);
+default_const!(default_cpufreq_wait_sec, u64, 10);
+default_const!(default_state_file_enabled, bool, true);
+default_const!(default_history_enabled, bool, true);
+default_const!(default_history_max_size_mb, u64, 1);
+default_const!(default_history_retention_days, u64, 90);
+default_const!(default_notifications, bool, false);
+default_const!(default_thermal_safety, bool, true);
+
+/// Tuning for the adaptive poll interval, see `[daemon.adaptive]`
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct AdaptiveIntervalSettings {
+    /// Weight of the newest sample in the moving averages, in (0, 1]
+    #[serde(default = "default_adaptive_smoothing")]
+    pub smoothing: f32,
+    /// Load variance above which the interval shortens
+    #[serde(default = "default_adaptive_variance_high")]
+    pub variance_high: f32,
+    /// Load variance below which the interval lengthens
+    #[serde(default = "default_adaptive_variance_low")]
+    pub variance_low: f32,
+    /// Seconds added per cycle while load is stable
+    #[serde(default = "default_adaptive_step_up_sec")]
+    pub step_up_sec: u64,
+    /// Seconds removed per cycle while load is bursty
+    #[serde(default = "default_adaptive_step_down_sec")]
+    pub step_down_sec: u64,
+}
+
+impl Default for AdaptiveIntervalSettings {
+    fn default() -> Self {
+        Self {
+            smoothing: default_adaptive_smoothing(),
+            variance_high: default_adaptive_variance_high(),
+            variance_low: default_adaptive_variance_low(),
+            step_up_sec: default_adaptive_step_up_sec(),
+            step_down_sec: default_adaptive_step_down_sec(),
+        }
+    }
+}
+
+default_const!(default_adaptive_smoothing, f32, 0.3);
+default_const!(default_adaptive_variance_high, f32, 100.0);
+default_const!(default_adaptive_variance_low, f32, 16.0);
+default_const!(default_adaptive_step_up_sec, u64, 1);
+default_const!(default_adaptive_step_down_sec, u64, 2);
+
+/// Which desktop notifications to send and how often, see `[daemon.notify]`
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct NotificationSettings {
+    /// When the active profile changes
+    #[serde(default = "default_notify_profile_switch")]
+    pub profile_switch: bool,
+    /// When auto turbo disables turbo because the CPU is too hot
+    #[serde(default = "default_notify_thermal")]
+    pub thermal: bool,
+    /// Seconds after a notification during which others of its kind are
+    /// dropped
+    #[serde(default = "default_notify_min_interval_sec")]
+    pub min_interval_sec: u64,
+}
+
+impl Default for NotificationSettings {
+    fn default() -> Self {
+        Self {
+            profile_switch: default_notify_profile_switch(),
+            thermal: default_notify_thermal(),
+            min_interval_sec: default_notify_min_interval_sec(),
+        }
+    }
+}
+
+default_const!(default_notify_profile_switch, bool, true);
+default_const!(default_notify_thermal, bool, true);
+default_const!(default_notify_min_interval_sec, u64, 60);
+
+/// How much the daemon smooths readings before auto turbo and adaptive
+/// polling see them, see `[daemon.smoothing]`
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct SmoothingSettings {
+    /// Weight of the newest average CPU usage, in (0, 1]. 1 uses it as is.
+    #[serde(default = "default_smoothing_factor")]
+    pub load: f32,
+    /// Weight of the newest average CPU temperature, in (0, 1]
+    #[serde(default = "default_smoothing_factor")]
+    pub temperature: f32,
+}
+
+impl Default for SmoothingSettings {
+    fn default() -> Self {
+        Self {
+            load: default_smoothing_factor(),
+            temperature: default_smoothing_factor(),
+        }
+    }
+}
+
+default_const!(default_smoothing_factor, f32, 1.0);
+
+/// Processes that force the performance profile while any of them runs, see
+/// `[process_boost]`
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct ProcessBoostSettings {
+    /// Process names, with `*` and `?` wildcards, matched without regard to
+    /// case against the command name and the file name of the executable
+    #[serde(default = "default_boost_processes")]
+    pub processes: Vec<String>,
+    /// Keep turbo on while boosting, whatever the charger profile says
+    #[serde(default = "default_boost_turbo")]
+    pub turbo: bool,
+    /// Seconds after a boost ends before another may start, so a process
+    /// that restarts in a loop does not flip the profile every poll
+    #[serde(default = "default_boost_cooldown_sec")]
+    pub cooldown_sec: u64,
+}
+
+impl Default for ProcessBoostSettings {
+    fn default() -> Self {
+        Self {
+            processes: default_boost_processes(),
+            turbo: default_boost_turbo(),
+            cooldown_sec: default_boost_cooldown_sec(),
+        }
+    }
+}
+
+/// Games run through gamescope or Wine, and the heavy parts of C, C++ and
+/// Rust builds
+fn default_boost_processes() -> Vec<String> {
+    ["gamescope*", "*.exe", "cc1", "cc1plus", "rustc"]
+        .into_iter()
+        .map(String::from)
+        .collect()
+}
+
+default_const!(default_boost_turbo, bool, false);
+default_const!(default_boost_cooldown_sec, u64, 30);
+
+/// A profile forced while a load average is above a threshold, see
+/// `[load_profile]`
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct LoadProfileSettings {
+    /// A built-in profile name or one of `[profiles.<name>]`
+    #[serde(default = "default_load_profile")]
+    pub profile: String,
+    #[serde(default)]
+    pub average: LoadAverage,
+    /// The load average above which the profile is forced
+    pub above: f32,
+    /// Divide the load average by the number of online CPUs first, so
+    /// `above = 1.0` means as many runnable tasks as CPUs on any machine
+    #[serde(default)]
+    pub normalize: bool,
+}
+
+fn default_load_profile() -> String {
+    "performance".to_string()
+}
+
+/// Which of the load averages in `/proc/loadavg`
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum LoadAverage {
+    /// Over the last minute
+    #[default]
+    Load1,
+    Load5,
+    Load15,
+}
+
+impl LoadAverage {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Load1 => "load1",
+            Self::Load5 => "load5",
+            Self::Load15 => "load15",
+        }
+    }
+}
+
+fn default_pid_file() -> String {
+    "/run/watt/watt.pid".to_string()
+}
+
+fn default_turbo_state_file() -> String {
+    "/run/watt/turbo-state.json".to_string()
+}
+
+fn default_state_file_path() -> String {
+    "/run/watt/state.json".to_string()
+}
+
+fn default_history_file() -> String {
+    "/var/lib/watt/history.jsonl".to_string()
+}
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct DaemonConfigToml {
@@ -293,8 +1243,86 @@ pub struct DaemonConfigToml {
     pub throttle_on_battery: bool,
     #[serde(default = "default_log_level")]
     pub log_level: LogLevel,
+    #[serde(default)]
+    pub log_every_cycle: bool,
     #[serde(default = "default_stats_file_path")]
     pub stats_file_path: Option<String>,
+    #[serde(default = "default_stats_include_counters")]
+    pub stats_include_counters: bool,
+    #[serde(default)]
+    pub stats_include_decisions: bool,
+    #[serde(default = "default_pid_file")]
+    pub pid_file: String,
+    #[serde(default = "default_restore_on_exit")]
+    pub restore_on_exit: bool,
+    #[serde(default = "default_event_driven")]
+    pub event_driven: bool,
+    #[serde(default = "default_idle_load_threshold")]
+    pub idle_load_threshold: f32,
+    #[serde(default = "default_idle_cycles")]
+    pub idle_cycles: u32,
+    #[serde(default)]
+    pub adaptive: AdaptiveIntervalSettings,
+    #[serde(default)]
+    pub metrics_address: Option<String>,
+    #[serde(default)]
+    pub log_format: LogFormat,
+    #[serde(default)]
+    pub log_file: Option<String>,
+    #[serde(default = "default_log_file_max_size_mb")]
+    pub log_file_max_size_mb: u64,
+    #[serde(default = "default_log_file_keep")]
+    pub log_file_keep: u32,
+    #[serde(default = "default_error_repeat_limit")]
+    pub error_repeat_limit: u32,
+    #[serde(default = "default_error_summary_interval_sec")]
+    pub error_summary_interval_sec: u64,
+    #[serde(default = "default_error_summary_max_interval_sec")]
+    pub error_summary_max_interval_sec: u64,
+    #[serde(default = "default_max_consecutive_panics")]
+    pub max_consecutive_panics: u32,
+    #[serde(default = "default_turbo_state_file")]
+    pub turbo_state_file: String,
+    #[serde(default)]
+    pub run_as_user: Option<String>,
+    #[serde(default = "default_chown_sysfs")]
+    pub chown_sysfs: bool,
+    #[serde(default)]
+    pub drift_policy: DriftPolicy,
+    #[serde(default)]
+    pub respect_manual_changes: bool,
+    #[serde(default = "default_rollback_on_failure")]
+    pub rollback_on_failure: bool,
+    #[serde(default)]
+    pub apply_order: Vec<ApplyStep>,
+    #[serde(default)]
+    pub min_write_interval_sec: BTreeMap<ApplyStep, u64>,
+    #[serde(default = "default_cpufreq_wait_sec")]
+    pub cpufreq_wait_sec: u64,
+    #[serde(default = "default_state_file_enabled")]
+    pub state_file_enabled: bool,
+    #[serde(default = "default_state_file_path")]
+    pub state_file_path: String,
+    #[serde(default = "default_history_enabled")]
+    pub history_enabled: bool,
+    #[serde(default = "default_history_file")]
+    pub history_file: String,
+    #[serde(default = "default_history_max_size_mb")]
+    pub history_max_size_mb: u64,
+    #[serde(default = "default_history_retention_days")]
+    pub history_retention_days: u64,
+    #[serde(default = "default_notifications")]
+    pub notifications: bool,
+    #[serde(default)]
+    pub notify: NotificationSettings,
+    #[serde(default)]
+    pub smoothing: SmoothingSettings,
+    #[serde(default = "default_thermal_safety")]
+    pub thermal_safety: bool,
+    #[serde(default)]
+    pub thermal_safety_critical_celsius: Option<f32>,
+    #[serde(default)]
+    pub thermal_safety_recovery_celsius: Option<f32>,
 }
 
 impl Default for DaemonConfigToml {
@@ -306,7 +1334,46 @@ impl Default for DaemonConfigToml {
             max_poll_interval_sec: default_max_poll_interval_sec(),
             throttle_on_battery: default_throttle_on_battery(),
             log_level: default_log_level(),
+            log_every_cycle: false,
             stats_file_path: default_stats_file_path(),
+            stats_include_counters: default_stats_include_counters(),
+            stats_include_decisions: false,
+            pid_file: default_pid_file(),
+            restore_on_exit: default_restore_on_exit(),
+            event_driven: default_event_driven(),
+            idle_load_threshold: default_idle_load_threshold(),
+            idle_cycles: default_idle_cycles(),
+            adaptive: AdaptiveIntervalSettings::default(),
+            metrics_address: None,
+            log_format: LogFormat::default(),
+            log_file: None,
+            log_file_max_size_mb: default_log_file_max_size_mb(),
+            log_file_keep: default_log_file_keep(),
+            error_repeat_limit: default_error_repeat_limit(),
+            error_summary_interval_sec: default_error_summary_interval_sec(),
+            error_summary_max_interval_sec: default_error_summary_max_interval_sec(),
+            max_consecutive_panics: default_max_consecutive_panics(),
+            turbo_state_file: default_turbo_state_file(),
+            run_as_user: None,
+            chown_sysfs: default_chown_sysfs(),
+            drift_policy: DriftPolicy::default(),
+            respect_manual_changes: false,
+            rollback_on_failure: default_rollback_on_failure(),
+            apply_order: Vec::new(),
+            min_write_interval_sec: BTreeMap::new(),
+            cpufreq_wait_sec: default_cpufreq_wait_sec(),
+            state_file_enabled: default_state_file_enabled(),
+            state_file_path: default_state_file_path(),
+            history_enabled: default_history_enabled(),
+            history_file: default_history_file(),
+            history_max_size_mb: default_history_max_size_mb(),
+            history_retention_days: default_history_retention_days(),
+            notifications: default_notifications(),
+            notify: NotificationSettings::default(),
+            smoothing: SmoothingSettings::default(),
+            thermal_safety: default_thermal_safety(),
+            thermal_safety_critical_celsius: None,
+            thermal_safety_recovery_celsius: None,
         }
     }
 }
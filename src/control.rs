@@ -0,0 +1,378 @@
+//! What the engine changes on the system, behind [`PowerController`], so its
+//! decisions can be applied to something other than the sysfs files
+
+use crate::battery;
+use crate::core::TurboSetting;
+use crate::cpu;
+use crate::util::error::ControlError;
+use crate::util::sysfs;
+use std::path::Path;
+
+type Result<T, E = ControlError> = std::result::Result<T, E>;
+
+/// The settings the engine and the set commands apply, to every CPU or to
+/// `core` alone, and what little they read back to decide on them
+pub trait PowerController: Send + Sync {
+    fn set_governor(&self, governor: &str, core: Option<u32>) -> Result<()>;
+    fn set_epp(&self, epp: &str, core: Option<u32>) -> Result<()>;
+    fn set_epb(&self, epb: &str, core: Option<u32>) -> Result<()>;
+    fn set_turbo(&self, setting: TurboSetting) -> Result<()>;
+    /// Turbo for `cpus` only, through their per-core boost files
+    fn set_turbo_for_cpus(&self, enable: bool, cpus: &[u32]) -> Result<()>;
+    fn set_min_frequency(&self, freq_mhz: u32, core: Option<u32>) -> Result<()>;
+    fn set_max_frequency(&self, freq_mhz: u32, core: Option<u32>) -> Result<()>;
+    fn set_platform_profile(&self, profile: &str) -> Result<()>;
+    fn set_charge_thresholds(&self, start: u8, stop: u8) -> Result<()>;
+    /// Writes back a value `path` held before, e.g. after a failed apply or
+    /// from a snapshot
+    fn restore_file(&self, path: &Path, value: &str) -> Result<()>;
+
+    /// The governor forced with `watt force-governor`, if any
+    fn governor_override(&self) -> Option<String>;
+    fn hardware_min_frequency(&self) -> Result<u32>;
+    fn hardware_max_frequency(&self) -> Result<u32>;
+    /// Whether turbo can be set for `cpus` and not the others
+    fn per_core_turbo_supported(&self, cpus: &[u32]) -> bool;
+
+    /// Whether writes only pretend to happen
+    fn is_dry_run(&self) -> bool {
+        false
+    }
+}
+
+/// The controller for a run, [`DryRunController`] when `dry_run` is set
+pub fn select(dry_run: bool) -> &'static dyn PowerController {
+    if dry_run {
+        &DryRunController
+    } else {
+        &SysfsController
+    }
+}
+
+/// Writes the sysfs files
+pub struct SysfsController;
+
+impl PowerController for SysfsController {
+    fn set_governor(&self, governor: &str, core: Option<u32>) -> Result<()> {
+        cpu::set_governor(governor, core)
+    }
+
+    fn set_epp(&self, epp: &str, core: Option<u32>) -> Result<()> {
+        cpu::set_epp(epp, core)
+    }
+
+    fn set_epb(&self, epb: &str, core: Option<u32>) -> Result<()> {
+        cpu::set_epb(epb, core)
+    }
+
+    fn set_turbo(&self, setting: TurboSetting) -> Result<()> {
+        cpu::set_turbo(setting)
+    }
+
+    fn set_turbo_for_cpus(&self, enable: bool, cpus: &[u32]) -> Result<()> {
+        cpu::set_turbo_for_cpus(enable, cpus)
+    }
+
+    fn set_min_frequency(&self, freq_mhz: u32, core: Option<u32>) -> Result<()> {
+        cpu::set_min_frequency(freq_mhz, core)
+    }
+
+    fn set_max_frequency(&self, freq_mhz: u32, core: Option<u32>) -> Result<()> {
+        cpu::set_max_frequency(freq_mhz, core)
+    }
+
+    fn set_platform_profile(&self, profile: &str) -> Result<()> {
+        cpu::set_platform_profile(profile)
+    }
+
+    fn set_charge_thresholds(&self, start: u8, stop: u8) -> Result<()> {
+        battery::set_battery_charge_thresholds(start, stop)
+    }
+
+    fn restore_file(&self, path: &Path, value: &str) -> Result<()> {
+        sysfs::write_sysfs_value(path, value)
+    }
+
+    fn governor_override(&self) -> Option<String> {
+        cpu::get_governor_override()
+    }
+
+    fn hardware_min_frequency(&self) -> Result<u32> {
+        cpu::hardware_min_frequency()
+    }
+
+    fn hardware_max_frequency(&self) -> Result<u32> {
+        cpu::hardware_max_frequency()
+    }
+
+    fn per_core_turbo_supported(&self, cpus: &[u32]) -> bool {
+        cpu::per_core_turbo_supported(cpus)
+    }
+}
+
+/// Goes through the same checks as [`SysfsController`], but every write it
+/// would make is only logged and recorded as
+/// [`WriteOutcome::WouldWrite`](sysfs::WriteOutcome::WouldWrite). Reads still
+/// see the real system.
+pub struct DryRunController;
+
+impl PowerController for DryRunController {
+    fn set_governor(&self, governor: &str, core: Option<u32>) -> Result<()> {
+        sysfs::pretend(|| SysfsController.set_governor(governor, core))
+    }
+
+    fn set_epp(&self, epp: &str, core: Option<u32>) -> Result<()> {
+        sysfs::pretend(|| SysfsController.set_epp(epp, core))
+    }
+
+    fn set_epb(&self, epb: &str, core: Option<u32>) -> Result<()> {
+        sysfs::pretend(|| SysfsController.set_epb(epb, core))
+    }
+
+    fn set_turbo(&self, setting: TurboSetting) -> Result<()> {
+        sysfs::pretend(|| SysfsController.set_turbo(setting))
+    }
+
+    fn set_turbo_for_cpus(&self, enable: bool, cpus: &[u32]) -> Result<()> {
+        sysfs::pretend(|| SysfsController.set_turbo_for_cpus(enable, cpus))
+    }
+
+    fn set_min_frequency(&self, freq_mhz: u32, core: Option<u32>) -> Result<()> {
+        sysfs::pretend(|| SysfsController.set_min_frequency(freq_mhz, core))
+    }
+
+    fn set_max_frequency(&self, freq_mhz: u32, core: Option<u32>) -> Result<()> {
+        sysfs::pretend(|| SysfsController.set_max_frequency(freq_mhz, core))
+    }
+
+    fn set_platform_profile(&self, profile: &str) -> Result<()> {
+        sysfs::pretend(|| SysfsController.set_platform_profile(profile))
+    }
+
+    fn set_charge_thresholds(&self, start: u8, stop: u8) -> Result<()> {
+        sysfs::pretend(|| SysfsController.set_charge_thresholds(start, stop))
+    }
+
+    fn restore_file(&self, path: &Path, value: &str) -> Result<()> {
+        sysfs::pretend(|| SysfsController.restore_file(path, value))
+    }
+
+    fn governor_override(&self) -> Option<String> {
+        SysfsController.governor_override()
+    }
+
+    fn hardware_min_frequency(&self) -> Result<u32> {
+        SysfsController.hardware_min_frequency()
+    }
+
+    fn hardware_max_frequency(&self) -> Result<u32> {
+        SysfsController.hardware_max_frequency()
+    }
+
+    fn per_core_turbo_supported(&self, cpus: &[u32]) -> bool {
+        SysfsController.per_core_turbo_supported(cpus)
+    }
+
+    fn is_dry_run(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+pub mod mock {
+    use super::*;
+    use std::fs;
+    use std::path::PathBuf;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Mutex, PoisonError};
+
+    /// Keeps each setting in a file of its own under a temporary directory,
+    /// written through [`sysfs`] so the engine journals them as it does the
+    /// real files, and records every call in order
+    pub struct MockController {
+        dir: PathBuf,
+        calls: Mutex<Vec<String>>,
+        /// The call that fails, by the name it is recorded under
+        failing: Option<&'static str>,
+    }
+
+    impl MockController {
+        pub fn new() -> Self {
+            static NEXT: AtomicUsize = AtomicUsize::new(0);
+            let dir = std::env::temp_dir().join(format!(
+                "watt-mock-{}-{}",
+                std::process::id(),
+                NEXT.fetch_add(1, Ordering::Relaxed)
+            ));
+            fs::create_dir_all(&dir).unwrap();
+            for (file, value) in [
+                ("governor", "powersave"),
+                ("epp", "balance_power"),
+                ("epb", "6"),
+                ("turbo", "1"),
+                ("min_frequency", "400"),
+                ("max_frequency", "4000"),
+                ("platform_profile", "balanced"),
+                ("charge_thresholds", "0-100"),
+            ] {
+                fs::write(dir.join(file), value).unwrap();
+            }
+            Self {
+                dir,
+                calls: Mutex::new(Vec::new()),
+                failing: None,
+            }
+        }
+
+        /// Makes `call`, e.g. `set_max_frequency`, fail without writing
+        pub fn failing(mut self, call: &'static str) -> Self {
+            self.failing = Some(call);
+            self
+        }
+
+        /// The calls made so far, e.g. `set_governor performance` or
+        /// `restore_file governor powersave`
+        pub fn calls(&self) -> Vec<String> {
+            self.calls
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .clone()
+        }
+
+        pub fn value(&self, file: &str) -> String {
+            fs::read_to_string(self.dir.join(file)).unwrap()
+        }
+
+        fn set(&self, call: &'static str, file: &str, value: &str) -> Result<()> {
+            self.calls
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .push(format!("{call} {value}"));
+            if self.failing == Some(call) {
+                return Err(ControlError::WriteError(format!("{call} failed")));
+            }
+            sysfs::write_sysfs_value(self.dir.join(file), value)
+        }
+    }
+
+    impl Drop for MockController {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    impl PowerController for MockController {
+        fn set_governor(&self, governor: &str, _core: Option<u32>) -> Result<()> {
+            self.set("set_governor", "governor", governor)
+        }
+
+        fn set_epp(&self, epp: &str, _core: Option<u32>) -> Result<()> {
+            self.set("set_epp", "epp", epp)
+        }
+
+        fn set_epb(&self, epb: &str, _core: Option<u32>) -> Result<()> {
+            self.set("set_epb", "epb", epb)
+        }
+
+        fn set_turbo(&self, setting: TurboSetting) -> Result<()> {
+            let value = if setting == TurboSetting::Never {
+                "0"
+            } else {
+                "1"
+            };
+            self.set("set_turbo", "turbo", value)
+        }
+
+        fn set_turbo_for_cpus(&self, enable: bool, _cpus: &[u32]) -> Result<()> {
+            self.set(
+                "set_turbo_for_cpus",
+                "turbo",
+                if enable { "1" } else { "0" },
+            )
+        }
+
+        fn set_min_frequency(&self, freq_mhz: u32, _core: Option<u32>) -> Result<()> {
+            self.set("set_min_frequency", "min_frequency", &freq_mhz.to_string())
+        }
+
+        fn set_max_frequency(&self, freq_mhz: u32, _core: Option<u32>) -> Result<()> {
+            self.set("set_max_frequency", "max_frequency", &freq_mhz.to_string())
+        }
+
+        fn set_platform_profile(&self, profile: &str) -> Result<()> {
+            self.set("set_platform_profile", "platform_profile", profile)
+        }
+
+        fn set_charge_thresholds(&self, start: u8, stop: u8) -> Result<()> {
+            self.set(
+                "set_charge_thresholds",
+                "charge_thresholds",
+                &format!("{start}-{stop}"),
+            )
+        }
+
+        fn restore_file(&self, path: &Path, value: &str) -> Result<()> {
+            let file = path.file_name().unwrap().to_string_lossy();
+            self.calls
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .push(format!("restore_file {file} {value}"));
+            sysfs::write_sysfs_value(path, value)
+        }
+
+        fn governor_override(&self) -> Option<String> {
+            None
+        }
+
+        fn hardware_min_frequency(&self) -> Result<u32> {
+            Ok(400)
+        }
+
+        fn hardware_max_frequency(&self) -> Result<u32> {
+            Ok(4000)
+        }
+
+        fn per_core_turbo_supported(&self, _cpus: &[u32]) -> bool {
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::sysfs::{WriteOutcome, WriteRecord};
+    use std::fs;
+
+    #[test]
+    fn dry_run_records_writes_without_making_them() {
+        let dir = std::env::temp_dir().join(format!("watt-control-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("scaling_governor");
+        fs::write(&path, "powersave").unwrap();
+
+        let controller = select(true);
+        let (result, writes) =
+            sysfs::capture_writes(|| controller.restore_file(&path, "performance"));
+        let dry_run_value = fs::read_to_string(&path).unwrap();
+        let (_, real_writes) =
+            sysfs::capture_writes(|| select(false).restore_file(&path, "performance"));
+        let real_value = fs::read_to_string(&path).unwrap();
+        let _ = fs::remove_dir_all(&dir);
+
+        assert!(result.is_ok());
+        assert!(controller.is_dry_run());
+        assert_eq!(dry_run_value, "powersave");
+        assert_eq!(
+            writes,
+            vec![WriteRecord {
+                path: path.clone(),
+                old_value: Some("powersave".to_string()),
+                value: "performance".to_string(),
+                outcome: WriteOutcome::WouldWrite,
+            }]
+        );
+        assert_eq!(real_value, "performance");
+        assert_eq!(real_writes[0].outcome, WriteOutcome::Written);
+    }
+}
@@ -1,6 +1,7 @@
 use clap::ValueEnum;
 use serde::{Deserialize, Serialize};
 use std::fmt;
+use std::str::FromStr;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, ValueEnum)]
 pub enum TurboSetting {
@@ -26,6 +27,7 @@ impl fmt::Display for GovernorOverrideMode {
     }
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SystemInfo {
     // Overall system details
     pub cpu_model: String,
@@ -33,6 +35,7 @@ pub struct SystemInfo {
     pub linux_distribution: String,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CpuCoreInfo {
     // Per-core data
     pub core_id: u32,
@@ -41,8 +44,12 @@ pub struct CpuCoreInfo {
     pub max_frequency_mhz: Option<u32>,
     pub usage_percent: Option<f32>,
     pub temperature_celsius: Option<f32>,
+    /// Governor of the core's cpufreq policy
+    #[serde(default)]
+    pub governor: Option<String>,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CpuGlobalInfo {
     // System-wide CPU settings
     pub current_governor: Option<String>,
@@ -51,9 +58,46 @@ pub struct CpuGlobalInfo {
     pub epp: Option<String>,        // Energy Performance Preference
     pub epb: Option<String>,        // Energy Performance Bias
     pub platform_profile: Option<String>,
-    pub average_temperature_celsius: Option<f32>, // Average temperature across all cores
+    /// From the CPU temperature sensor, or else the average of the cores
+    pub average_temperature_celsius: Option<f32>,
+    /// The sensor `average_temperature_celsius` was read from, e.g.
+    /// `hwmon:k10temp/Tctl`, or `None` for the average of the cores
+    #[serde(default)]
+    pub temperature_sensor: Option<String>,
+    /// `average_temperature_celsius` smoothed over the previous reports, per
+    /// `daemon.smoothing.temperature`. Only the daemon smooths.
+    #[serde(default)]
+    pub smoothed_temperature_celsius: Option<f32>,
+    /// Temperature the CPU throttles itself at (Tjmax), from the critical
+    /// threshold of its hwmon sensor, if the driver reports one
+    #[serde(default)]
+    pub critical_temperature_celsius: Option<f32>,
+    /// Average current frequency of the cores that report one, in MHz
+    #[serde(default)]
+    pub average_frequency_mhz: Option<u32>,
+    /// Highest current frequency of any core, in MHz
+    #[serde(default)]
+    pub highest_frequency_mhz: Option<u32>,
 }
 
+/// A cpufreq policy, which the CPUs in `cpus` share
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CpuPolicyInfo {
+    /// N of `/sys/devices/system/cpu/cpufreq/policyN`
+    pub id: u32,
+    pub cpus: Vec<u32>,
+    pub governor: Option<String>,
+    pub current_frequency_mhz: Option<u32>,
+    /// Limits set through `scaling_min_freq` and `scaling_max_freq`
+    pub min_frequency_mhz: Option<u32>,
+    pub max_frequency_mhz: Option<u32>,
+    /// What the hardware allows, from `cpuinfo_min_freq` and
+    /// `cpuinfo_max_freq`
+    pub hardware_min_frequency_mhz: Option<u32>,
+    pub hardware_max_frequency_mhz: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BatteryInfo {
     // Battery status (AC connected, charging state, capacity, power rate, charge start/stop thresholds if available).
     pub name: String,
@@ -65,25 +109,250 @@ pub struct BatteryInfo {
     pub charge_stop_threshold: Option<u8>,
 }
 
+/// The AC power source the detection looked at and what it read there
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AcPowerReading {
+    /// The `ac_detection` strategy, or the `ac_sources` it was pinned to
+    pub source: String,
+    /// What the source reported, e.g. "ADP1 online"
+    pub reading: String,
+    pub online: bool,
+}
+
+/// Whether the lid is closed and which external displays are connected
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LidInfo {
+    /// `None` without a lid switch
+    pub lid_closed: Option<bool>,
+    /// Connected display connectors other than built-in panels, e.g.
+    /// `card1-HDMI-A-1`
+    pub external_displays: Vec<String>,
+}
+
+impl LidInfo {
+    /// Lid closed with an external display connected, as on a dock
+    pub fn is_clamshell(&self) -> bool {
+        self.lid_closed == Some(true) && !self.external_displays.is_empty()
+    }
+}
+
+/// Throttle events since the previous report, from the `thermal_throttle`
+/// counters Intel CPUs have. `None` without the counters, and on the first
+/// report, which has nothing to count from.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ThrottleInfo {
+    /// Times a core was throttled, over all cores
+    pub core_events: Option<u64>,
+    /// Times a package was throttled, over all packages
+    pub package_events: Option<u64>,
+    /// CPUs whose core was throttled
+    pub throttled_cpus: Vec<u32>,
+    /// Core and package events since boot, known on the first report too
+    #[serde(default)]
+    pub total_events: Option<u64>,
+}
+
+impl ThrottleInfo {
+    /// Core and package events together, if either is known
+    pub fn events(&self) -> Option<u64> {
+        match (self.core_events, self.package_events) {
+            (None, None) => None,
+            (core, package) => Some(core.unwrap_or(0) + package.unwrap_or(0)),
+        }
+    }
+}
+
+/// CPU power draw per RAPL domain since the previous report, over all
+/// packages. `None` without RAPL counters or without root to read them.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct CpuPowerInfo {
+    pub package_watts: Option<f32>,
+    pub cores_watts: Option<f32>,
+    /// The integrated GPU on most Intel CPUs, missing on AMD
+    pub uncore_watts: Option<f32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SystemLoad {
     // System load averages.
     pub load_avg_1min: f32,
     pub load_avg_5min: f32,
     pub load_avg_15min: f32,
+    /// CPU pressure stall information, `None` on kernels without PSI
+    #[serde(default)]
+    pub cpu_pressure: Option<CpuPressure>,
+    /// Average CPU usage of the cores smoothed over the previous reports, per
+    /// `daemon.smoothing.load`. Only the daemon smooths.
+    #[serde(default)]
+    pub smoothed_cpu_usage: Option<f32>,
 }
 
+/// Share of time some task was stalled waiting for a CPU, from the "some"
+/// line of `/proc/pressure/cpu`, in percent
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct CpuPressure {
+    pub some_avg10: f32,
+    pub some_avg60: f32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SystemReport {
     // Now combine all the above for a snapshot of the system state.
     pub system_info: SystemInfo,
     pub cpu_cores: Vec<CpuCoreInfo>,
     pub cpu_global: CpuGlobalInfo,
+    #[serde(default)]
+    pub cpu_policies: Vec<CpuPolicyInfo>,
     pub batteries: Vec<BatteryInfo>,
+    /// How AC power was detected, for debugging the detection
+    #[serde(default)]
+    pub ac_power: AcPowerReading,
+    /// Lid state and external displays, for clamshell mode
+    #[serde(default)]
+    pub lid: LidInfo,
+    #[serde(default)]
+    pub throttling: ThrottleInfo,
+    #[serde(default)]
+    pub cpu_power: CpuPowerInfo,
     pub system_load: SystemLoad,
     pub timestamp: std::time::SystemTime, // so we know when the report was generated
 }
 
+#[cfg(test)]
+impl SystemReport {
+    /// A system on AC power that reports nothing else of note
+    pub fn for_tests() -> Self {
+        Self {
+            system_info: SystemInfo {
+                cpu_model: "Test CPU".to_string(),
+                architecture: "x86_64".to_string(),
+                linux_distribution: "Test Linux".to_string(),
+            },
+            cpu_cores: Vec::new(),
+            cpu_global: CpuGlobalInfo {
+                current_governor: None,
+                available_governors: Vec::new(),
+                turbo_status: None,
+                epp: None,
+                epb: None,
+                platform_profile: None,
+                average_temperature_celsius: None,
+                temperature_sensor: None,
+                smoothed_temperature_celsius: None,
+                critical_temperature_celsius: None,
+                average_frequency_mhz: None,
+                highest_frequency_mhz: None,
+            },
+            cpu_policies: Vec::new(),
+            batteries: Vec::new(),
+            ac_power: AcPowerReading {
+                source: "test".to_string(),
+                reading: "AC online".to_string(),
+                online: true,
+            },
+            lid: LidInfo::default(),
+            throttling: ThrottleInfo::default(),
+            cpu_power: CpuPowerInfo::default(),
+            system_load: SystemLoad {
+                load_avg_1min: 0.0,
+                load_avg_5min: 0.0,
+                load_avg_15min: 0.0,
+                cpu_pressure: None,
+                smoothed_cpu_usage: None,
+            },
+            timestamp: std::time::SystemTime::now(),
+        }
+    }
+
+    /// [`Self::for_tests`] with a core, a cpufreq policy and a battery, and a
+    /// fixed timestamp, so output snapshots pin the fields of each record
+    pub fn for_snapshots() -> Self {
+        let mut report = Self::for_tests();
+        report.cpu_cores.push(CpuCoreInfo {
+            core_id: 0,
+            current_frequency_mhz: Some(2400),
+            min_frequency_mhz: Some(400),
+            max_frequency_mhz: Some(4000),
+            usage_percent: Some(12.5),
+            temperature_celsius: Some(45.0),
+            governor: Some("powersave".to_string()),
+        });
+        report.cpu_policies.push(CpuPolicyInfo {
+            id: 0,
+            cpus: vec![0],
+            governor: Some("powersave".to_string()),
+            current_frequency_mhz: Some(2400),
+            min_frequency_mhz: Some(400),
+            max_frequency_mhz: Some(4000),
+            hardware_min_frequency_mhz: Some(400),
+            hardware_max_frequency_mhz: Some(4800),
+        });
+        report.batteries.push(BatteryInfo {
+            name: "BAT0".to_string(),
+            ac_connected: true,
+            charging_state: Some("Charging".to_string()),
+            capacity_percent: Some(80),
+            power_rate_watts: Some(15.5),
+            charge_start_threshold: Some(40),
+            charge_stop_threshold: Some(80),
+        });
+        report.timestamp =
+            std::time::SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1_700_000_000);
+        report
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum OperationalMode {
     Powersave,
+    /// The `[balanced]` profile, or the battery profile without one
+    Balanced,
     Performance,
 }
+
+impl FromStr for OperationalMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "powersave" | "battery" => Ok(Self::Powersave),
+            "balanced" => Ok(Self::Balanced),
+            "performance" | "charger" => Ok(Self::Performance),
+            _ => Err(format!(
+                "Unknown profile '{s}'. Expected one of: performance, balanced, powersave, charger, battery"
+            )),
+        }
+    }
+}
+
+/// A profile used regardless of the power source: a built-in mode, or a
+/// profile of the config by name
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ForcedProfile {
+    Mode(OperationalMode),
+    /// A `[profiles.<name>]` section
+    Named(String),
+}
+
+impl FromStr for ForcedProfile {
+    type Err = String;
+
+    /// Names of built-in modes win, anything else names a config profile
+    /// which is looked up when it is used
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.trim().is_empty() {
+            return Err("Profile name must not be empty".to_string());
+        }
+        Ok(s.parse::<OperationalMode>()
+            .map_or_else(|_| Self::Named(s.to_string()), Self::Mode))
+    }
+}
+
+impl fmt::Display for ForcedProfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Mode(mode) => write!(f, "{}", format!("{mode:?}").to_lowercase()),
+            Self::Named(name) => write!(f, "{name}"),
+        }
+    }
+}
@@ -1,8 +1,13 @@
 use crate::core::{GovernorOverrideMode, TurboSetting};
 use crate::util::error::ControlError;
+use crate::util::sysfs;
 use core::str;
 use log::debug;
-use std::{fs, io, path::Path, string::ToString};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    string::ToString,
+};
 
 pub type Result<T, E = ControlError> = std::result::Result<T, E>;
 
@@ -29,18 +34,7 @@ const EPP_FALLBACK_VALUES: &[&str] = &[
 
 // Write a value to a sysfs file
 fn write_sysfs_value(path: impl AsRef<Path>, value: &str) -> Result<()> {
-    let p = path.as_ref();
-
-    fs::write(p, value).map_err(|e| {
-        let error_msg = format!("Path: {:?}, Value: '{}', Error: {}", p.display(), value, e);
-        match e.kind() {
-            io::ErrorKind::PermissionDenied => ControlError::PermissionDenied(error_msg),
-            io::ErrorKind::NotFound => {
-                ControlError::PathMissing(format!("Path '{}' does not exist", p.display()))
-            }
-            _ => ControlError::WriteError(error_msg),
-        }
-    })
+    sysfs::write_sysfs_value(path, value)
 }
 
 pub fn get_logical_core_count() -> Result<u32> {
@@ -91,14 +85,19 @@ pub fn get_logical_core_count() -> Result<u32> {
     Ok(num_cores)
 }
 
-fn for_each_cpu_core<F>(mut action: F) -> Result<()>
+/// Runs `action` on `core_id`, or on every core without one, naming the
+/// core in any error
+fn for_cores<F>(core_id: Option<u32>, mut action: F) -> Result<()>
 where
     F: FnMut(u32) -> Result<()>,
 {
-    let num_cores: u32 = get_logical_core_count()?;
+    let cores = match core_id {
+        Some(id) => id..id + 1,
+        None => 0..get_logical_core_count()?,
+    };
 
-    for core_id in 0u32..num_cores {
-        action(core_id)?;
+    for core_id in cores {
+        action(core_id).map_err(|e| e.with_cpu(core_id))?;
     }
     Ok(())
 }
@@ -127,7 +126,7 @@ pub fn set_governor(governor: &str, core_id: Option<u32>) -> Result<()> {
         }
     };
 
-    core_id.map_or_else(|| for_each_cpu_core(action), action)
+    for_cores(core_id, action)
 }
 
 /// Check if the provided governor is available in the system
@@ -213,45 +212,39 @@ fn get_available_governors() -> Result<Vec<String>> {
     ))
 }
 
+/// Global turbo controls in order of specificity, with whether writing 1
+/// disables turbo rather than enabling it
+const TURBO_CONTROLS: &[(&str, bool)] = &[
+    ("/sys/devices/system/cpu/intel_pstate/no_turbo", true),
+    // AMD specific paths
+    ("/sys/devices/system/cpu/amd_pstate/cpufreq/boost", false),
+    (
+        "/sys/devices/system/cpu/cpufreq/amd_pstate_enable_boost",
+        false,
+    ),
+    ("/sys/devices/system/cpu/cpufreq/boost", false),
+];
+
 pub fn set_turbo(setting: TurboSetting) -> Result<()> {
-    let value_pstate = match setting {
-        TurboSetting::Always => "0", // no_turbo = 0 means turbo is enabled
-        TurboSetting::Never => "1",  // no_turbo = 1 means turbo is disabled
+    let enable = match setting {
+        TurboSetting::Always => true,
+        TurboSetting::Never => false,
         // Auto mode is handled at the engine level, not directly at the sysfs level
         TurboSetting::Auto => {
             debug!("Turbo Auto mode is managed by engine logic based on system conditions");
             return Ok(());
         }
     };
-    let value_boost = match setting {
-        TurboSetting::Always => "1", // boost = 1 means turbo is enabled
-        TurboSetting::Never => "0",  // boost = 0 means turbo is disabled
-        TurboSetting::Auto => {
-            debug!("Turbo Auto mode is managed by engine logic based on system conditions");
-            return Ok(());
-        }
-    };
-
-    // AMD specific paths
-    let amd_pstate_path = "/sys/devices/system/cpu/amd_pstate/cpufreq/boost";
-    let msr_boost_path = "/sys/devices/system/cpu/cpufreq/amd_pstate_enable_boost";
-
-    // Path priority (from most to least specific)
-    let pstate_path = "/sys/devices/system/cpu/intel_pstate/no_turbo";
-    let boost_path = "/sys/devices/system/cpu/cpufreq/boost";
 
     // Try each boost control path in order of specificity
-    if Path::new(pstate_path).exists() {
-        write_sysfs_value(pstate_path, value_pstate)
-    } else if Path::new(amd_pstate_path).exists() {
-        write_sysfs_value(amd_pstate_path, value_boost)
-    } else if Path::new(msr_boost_path).exists() {
-        write_sysfs_value(msr_boost_path, value_boost)
-    } else if Path::new(boost_path).exists() {
-        write_sysfs_value(boost_path, value_boost)
+    if let Some((path, inverted)) = TURBO_CONTROLS
+        .iter()
+        .find(|(path, _)| Path::new(path).exists())
+    {
+        write_sysfs_value(path, if enable != *inverted { "1" } else { "0" })
     } else {
         // Also try per-core cpufreq boost for some AMD systems
-        let result = try_set_per_core_boost(value_boost)?;
+        let result = try_set_per_core_boost(if enable { "1" } else { "0" })?;
         if result {
             Ok(())
         } else {
@@ -262,6 +255,39 @@ pub fn set_turbo(setting: TurboSetting) -> Result<()> {
     }
 }
 
+/// The file `set_turbo` writes to, or a description of the per-core
+/// fallback. `None` if turbo cannot be controlled.
+pub fn turbo_mechanism() -> Option<&'static str> {
+    TURBO_CONTROLS
+        .iter()
+        .map(|(path, _)| *path)
+        .find(|path| Path::new(path).exists())
+        .or_else(|| {
+            Path::new("/sys/devices/system/cpu/cpu0/cpufreq/boost")
+                .exists()
+                .then_some("per-core cpufreq boost")
+        })
+}
+
+/// Whether turbo is enabled, read from the file `set_turbo` writes to. `None`
+/// if turbo cannot be controlled or the file cannot be read.
+pub fn turbo_enabled() -> Option<bool> {
+    let (path, inverted) = TURBO_CONTROLS
+        .iter()
+        .find(|(path, _)| Path::new(path).exists())
+        .copied()
+        .unwrap_or(("/sys/devices/system/cpu/cpu0/cpufreq/boost", false));
+    let value = sysfs::read_sysfs_value(path).ok()?;
+    Some((value == "1") != inverted)
+}
+
+/// The cpufreq scaling driver, e.g. `intel_pstate` or `acpi-cpufreq`
+pub fn scaling_driver() -> Option<String> {
+    cpufreq_policies()
+        .first()
+        .and_then(|policy| sysfs::read_sysfs_value(policy.join("scaling_driver")).ok())
+}
+
 /// Try to set boost on a per-core basis for systems that support it
 fn try_set_per_core_boost(value: &str) -> Result<bool> {
     let mut success = false;
@@ -271,7 +297,7 @@ fn try_set_per_core_boost(value: &str) -> Result<bool> {
         let boost_path = format!("/sys/devices/system/cpu/cpu{core_id}/cpufreq/boost");
 
         if Path::new(&boost_path).exists() {
-            write_sysfs_value(&boost_path, value)?;
+            write_sysfs_value(&boost_path, value).map_err(|e| e.with_cpu(core_id))?;
             success = true;
         }
     }
@@ -279,6 +305,27 @@ fn try_set_per_core_boost(value: &str) -> Result<bool> {
     Ok(success)
 }
 
+/// Whether each of `cpus` has its own boost file, so turbo can be set for
+/// some CPUs and not others
+pub fn per_core_turbo_supported(cpus: &[u32]) -> bool {
+    !cpus.is_empty()
+        && cpus.iter().all(|cpu| {
+            Path::new(&format!("/sys/devices/system/cpu/cpu{cpu}/cpufreq/boost")).exists()
+        })
+}
+
+/// Set turbo through the per-core boost files of `cpus` only
+pub fn set_turbo_for_cpus(enable: bool, cpus: &[u32]) -> Result<()> {
+    for cpu in cpus {
+        write_sysfs_value(
+            format!("/sys/devices/system/cpu/cpu{cpu}/cpufreq/boost"),
+            if enable { "1" } else { "0" },
+        )
+        .map_err(|e| e.with_cpu(*cpu))?;
+    }
+    Ok(())
+}
+
 pub fn set_epp(epp: &str, core_id: Option<u32>) -> Result<()> {
     // Validate the EPP value against available options
     let available_epp = get_available_epp_values()?;
@@ -298,7 +345,7 @@ pub fn set_epp(epp: &str, core_id: Option<u32>) -> Result<()> {
             Ok(())
         }
     };
-    core_id.map_or_else(|| for_each_cpu_core(action), action)
+    for_cores(core_id, action)
 }
 
 /// Get available EPP values from the system
@@ -334,7 +381,7 @@ pub fn set_epb(epb: &str, core_id: Option<u32>) -> Result<()> {
             Ok(())
         }
     };
-    core_id.map_or_else(|| for_each_cpu_core(action), action)
+    for_cores(core_id, action)
 }
 
 fn validate_epb_value(epb: &str) -> Result<()> {
@@ -367,15 +414,7 @@ fn validate_epb_value(epb: &str) -> Result<()> {
 
 pub fn set_min_frequency(freq_mhz: u32, core_id: Option<u32>) -> Result<()> {
     // Check if the new minimum frequency would be greater than current maximum
-    if let Some(id) = core_id {
-        validate_min_frequency(id, freq_mhz)?;
-    } else {
-        // Check for all cores
-        let num_cores = get_logical_core_count()?;
-        for id in 0..num_cores {
-            validate_min_frequency(id, freq_mhz)?;
-        }
-    }
+    for_cores(core_id, |id| validate_min_frequency(id, freq_mhz))?;
 
     // XXX: We use u64 for the intermediate calculation to prevent overflow
     let freq_khz = u64::from(freq_mhz) * 1000;
@@ -389,20 +428,12 @@ pub fn set_min_frequency(freq_mhz: u32, core_id: Option<u32>) -> Result<()> {
             Ok(())
         }
     };
-    core_id.map_or_else(|| for_each_cpu_core(action), action)
+    for_cores(core_id, action)
 }
 
 pub fn set_max_frequency(freq_mhz: u32, core_id: Option<u32>) -> Result<()> {
     // Check if the new maximum frequency would be less than current minimum
-    if let Some(id) = core_id {
-        validate_max_frequency(id, freq_mhz)?;
-    } else {
-        // Check for all cores
-        let num_cores = get_logical_core_count()?;
-        for id in 0..num_cores {
-            validate_max_frequency(id, freq_mhz)?;
-        }
-    }
+    for_cores(core_id, |id| validate_max_frequency(id, freq_mhz))?;
 
     // XXX: Use a u64 here as well.
     let freq_khz = u64::from(freq_mhz) * 1000;
@@ -416,7 +447,7 @@ pub fn set_max_frequency(freq_mhz: u32, core_id: Option<u32>) -> Result<()> {
             Ok(())
         }
     };
-    core_id.map_or_else(|| for_each_cpu_core(action), action)
+    for_cores(core_id, action)
 }
 
 fn read_sysfs_value_as_u32(path: &str) -> Result<u32> {
@@ -447,10 +478,9 @@ fn validate_min_frequency(core_id: u32, new_min_freq_mhz: u32) -> Result<()> {
 
     if new_min_freq_khz > max_freq_khz {
         return Err(ControlError::InvalidValueError(format!(
-            "Minimum frequency ({} MHz) cannot be higher than maximum frequency ({} MHz) for core {}",
+            "Minimum frequency ({} MHz) cannot be higher than maximum frequency ({} MHz)",
             new_min_freq_mhz,
-            max_freq_khz / 1000,
-            core_id
+            max_freq_khz / 1000
         )));
     }
 
@@ -469,10 +499,9 @@ fn validate_max_frequency(core_id: u32, new_max_freq_mhz: u32) -> Result<()> {
 
     if new_max_freq_khz < min_freq_khz {
         return Err(ControlError::InvalidValueError(format!(
-            "Maximum frequency ({} MHz) cannot be lower than minimum frequency ({} MHz) for core {}",
+            "Maximum frequency ({} MHz) cannot be lower than minimum frequency ({} MHz)",
             new_max_freq_mhz,
-            min_freq_khz / 1000,
-            core_id
+            min_freq_khz / 1000
         )));
     }
 
@@ -567,17 +596,7 @@ pub fn force_governor(mode: GovernorOverrideMode) -> Result<()> {
 
     match mode {
         GovernorOverrideMode::Reset => {
-            // Remove the override file if it exists
-            if Path::new(GOVERNOR_OVERRIDE_PATH).exists() {
-                fs::remove_file(GOVERNOR_OVERRIDE_PATH).map_err(|e| {
-                    if e.kind() == io::ErrorKind::PermissionDenied {
-                        ControlError::PermissionDenied(format!(
-                            "Permission denied removing override file: {GOVERNOR_OVERRIDE_PATH}. Try running with sudo."
-                        ))
-                    } else {
-                        ControlError::Io(e)
-                    }
-                })?;
+            if clear_governor_override()? {
                 println!(
                     "Governor override has been reset. Normal profile-based settings will be used."
                 );
@@ -611,6 +630,23 @@ pub fn force_governor(mode: GovernorOverrideMode) -> Result<()> {
     }
 }
 
+/// Removes the governor override file, returning whether there was one
+pub fn clear_governor_override() -> Result<bool> {
+    if !Path::new(GOVERNOR_OVERRIDE_PATH).exists() {
+        return Ok(false);
+    }
+    fs::remove_file(GOVERNOR_OVERRIDE_PATH).map_err(|e| {
+        if e.kind() == io::ErrorKind::PermissionDenied {
+            ControlError::PermissionDenied(format!(
+                "Permission denied removing override file: {GOVERNOR_OVERRIDE_PATH}. Try running with sudo."
+            ))
+        } else {
+            ControlError::Io(e)
+        }
+    })?;
+    Ok(true)
+}
+
 /// Get the current governor override if set
 pub fn get_governor_override() -> Option<String> {
     if Path::new(GOVERNOR_OVERRIDE_PATH).exists() {
@@ -619,3 +655,80 @@ pub fn get_governor_override() -> Option<String> {
         None
     }
 }
+
+/// Why CPU frequency scaling cannot be controlled, or `None` if it can
+///
+/// This is the case when no cpufreq driver is bound to the CPUs, as in many
+/// virtual machines or early at boot before the driver module is loaded.
+pub fn cpufreq_unavailable_reason() -> Option<String> {
+    let cpu_path = Path::new("/sys/devices/system/cpu");
+    if !cpu_path.exists() {
+        return Some(format!("{} does not exist", cpu_path.display()));
+    }
+    if cpu_path.join("cpu0/cpufreq").exists() {
+        return None;
+    }
+
+    let in_vm = fs::read_to_string("/proc/cpuinfo").is_ok_and(|cpuinfo| {
+        cpuinfo
+            .lines()
+            .filter(|line| line.starts_with("flags"))
+            .any(|line| line.split_whitespace().any(|flag| flag == "hypervisor"))
+    });
+    Some(if in_vm {
+        "no cpufreq driver is loaded, as is common in virtual machines".to_string()
+    } else {
+        "no cpufreq driver is loaded".to_string()
+    })
+}
+
+/// cpufreq policy directories, in policy order
+pub fn cpufreq_policies() -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(Path::new("/sys/devices/system/cpu/cpufreq")) else {
+        return Vec::new();
+    };
+
+    let mut policies: Vec<(u32, PathBuf)> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let id = entry
+                .file_name()
+                .to_str()?
+                .strip_prefix("policy")?
+                .parse()
+                .ok()?;
+            Some((id, entry.path()))
+        })
+        .collect();
+
+    policies.sort_by_key(|(id, _)| *id);
+    policies.into_iter().map(|(_, path)| path).collect()
+}
+
+/// Highest hardware maximum frequency of all cpufreq policies, in MHz
+pub fn hardware_max_frequency() -> Result<u32> {
+    cpufreq_policies()
+        .iter()
+        .filter_map(|policy| {
+            read_sysfs_value_as_u32(&policy.join("cpuinfo_max_freq").to_string_lossy()).ok()
+        })
+        .max()
+        .map(|khz| khz / 1000)
+        .ok_or_else(|| {
+            ControlError::NotSupported("no cpufreq policy reports cpuinfo_max_freq".to_string())
+        })
+}
+
+/// Lowest hardware minimum frequency of all cpufreq policies, in MHz
+pub fn hardware_min_frequency() -> Result<u32> {
+    cpufreq_policies()
+        .iter()
+        .filter_map(|policy| {
+            read_sysfs_value_as_u32(&policy.join("cpuinfo_min_freq").to_string_lossy()).ok()
+        })
+        .min()
+        .map(|khz| khz / 1000)
+        .ok_or_else(|| {
+            ControlError::NotSupported("no cpufreq policy reports cpuinfo_min_freq".to_string())
+        })
+}
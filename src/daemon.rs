@@ -1,154 +1,1114 @@
-use crate::config::{AppConfig, LogLevel};
-use crate::core::SystemReport;
-use crate::engine;
+use crate::auth::{Authority, Polkit};
+use crate::cli;
+use crate::config::{
+    self, AdaptiveIntervalSettings, AppConfig, BatteryChargeThresholds, DaemonConfig, LogLevel,
+    SmoothingSettings,
+};
+use crate::control::{self, PowerController};
+use crate::core::{ForcedProfile, OperationalMode, SystemReport, TurboSetting};
+use crate::cpu;
+use crate::daemonize;
+use crate::dbus::DbusService;
+use crate::dump::{self, LastApply, LoopDetails};
+use crate::engine::{
+    self, ApplySummary, DecisionTrace, DriftedKnob, FrequencyRamp, KnobStates, ParkedKnob,
+    TurboStates,
+};
+use crate::events::{self, EventLoop, Waker, Wakeup};
+use crate::history::{self, History};
+use crate::ipc;
+use crate::metrics::{self, Counters, Metrics};
 use crate::monitor;
-use crate::util::error::{AppError, ControlError};
+use crate::notifications::{self, Notifications};
+use crate::overrides::{self, OVERRIDES_PATH, OverrideKnob, PowerScope, TimedOverride};
+use crate::pidfile::PidFile;
+use crate::privileges;
+use crate::process_boost::{BoostStatus, ProcessBoost};
+use crate::resume::{self, SuspendCounter};
+use crate::signals;
+use crate::smoothing::{Ewma, ReportSmoother};
+use crate::snapshot::Snapshot;
+use crate::statefile::{State, StateFile};
+use crate::systemd::Notifier;
+use crate::util::error::{AppError, ControlError, ErrorContext};
+use crate::util::logging::{Repeat, RepeatedErrors};
+use crate::util::panics;
+use crate::util::sysfs::{self, WriteOutcome, WriteRecord};
+use clap::ValueEnum;
 use log::{LevelFilter, debug, error, info, warn};
-use std::collections::VecDeque;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
 use std::fs::File;
-use std::io::Write;
-use std::sync::Arc;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process;
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::time::{Duration, Instant};
-
-/// Parameters for computing optimal polling interval
-struct IntervalParams {
-    /// Base polling interval in seconds
-    base_interval: u64,
-    /// Minimum allowed polling interval in seconds
-    min_interval: u64,
-    /// Maximum allowed polling interval in seconds
-    max_interval: u64,
-    /// How rapidly CPU usage is changing
-    cpu_volatility: f32,
-    /// How rapidly temperature is changing
-    temp_volatility: f32,
-    /// Battery discharge rate in %/hour if available
-    battery_discharge_rate: Option<f32>,
-    /// Time since last detected user activity
-    last_user_activity: Duration,
-    /// Whether the system appears to be idle
-    is_system_idle: bool,
-    /// Whether the system is running on battery power
-    on_battery: bool,
-}
-
-/// Calculate the idle time multiplier based on system idle duration
-///
-/// Returns a multiplier between 1.0 and 5.0 (capped):
-/// - For idle times < 2 minutes: Linear interpolation from 1.0 to 2.0
-/// - For idle times >= 2 minutes: Logarithmic scaling (1.0 + log2(minutes))
-fn idle_multiplier(idle_secs: u64) -> f32 {
-    if idle_secs == 0 {
-        return 1.0; // No idle time, no multiplier effect
-    }
-
-    let idle_factor = if idle_secs < 120 {
-        // Less than 2 minutes (0 to 119 seconds)
-        // Linear interpolation from 1.0 (at 0s) to 2.0 (at 120s)
-        1.0 + (idle_secs as f32) / 120.0
-    } else {
-        // 2 minutes (120 seconds) or more
-        let idle_time_minutes = idle_secs / 60;
-        // Logarithmic scaling: 1.0 + log2(minutes)
-        1.0 + (idle_time_minutes as f32).log2().max(0.5)
-    };
+use std::sync::{Arc, Condvar, Mutex, OnceLock, PoisonError, mpsc};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+/// State shared between the daemon loop and its control interfaces
+#[derive(Default)]
+pub struct DaemonState {
+    /// Profile forced through a control interface, if any
+    forced_profile: Mutex<Option<Override<ForcedProfile>>>,
+    /// Names of the config profiles that can be forced, as of the last load
+    profile_names: Mutex<Vec<String>>,
+    /// Turbo setting overriding the active profile, if any
+    turbo_override: Mutex<Option<Override<TurboSetting>>>,
+    /// Time-limited overrides of single settings, mirrored to
+    /// [`OVERRIDES_PATH`]
+    timed_overrides: Mutex<Vec<TimedOverride>>,
+    /// Set when a configuration reload was requested
+    reload_requested: AtomicBool,
+    /// Set while applying settings is paused
+    pause: Mutex<Option<Pause>>,
+    /// Most recently collected system report
+    last_report: Mutex<Option<SystemReport>>,
+    /// Name of the profile applied in the last cycle
+    active_profile: Mutex<Option<String>>,
+    /// How long each profile has been active
+    profile_times: Mutex<ProfileTimes>,
+    /// Set when the system resumed from suspend since the last cycle
+    resumed: AtomicBool,
+    /// Battery charge thresholds requested through a control interface,
+    /// waiting to be written by the engine thread
+    pending_thresholds: Mutex<Option<BatteryChargeThresholds>>,
+    /// Reset requested through a control interface, waiting for the engine
+    /// thread
+    pending_reset: Mutex<Option<PendingReset>>,
+    /// Wakes the monitor thread so control requests take effect right away
+    waker: OnceLock<Arc<Waker>>,
+    /// Counters exported to Prometheus
+    metrics: Metrics,
+    /// Knobs the engine has stopped writing after repeated failures
+    knobs: KnobStates,
+    /// When the engine thread last finished a cycle
+    last_cycle: Mutex<Option<jiff::Timestamp>>,
+    /// When settings were last applied without errors
+    last_successful_apply: Mutex<Option<jiff::Timestamp>>,
+    /// Turbo hysteresis state as of the last cycle
+    turbo_hysteresis: Mutex<TurboHysteresis>,
+    /// The process boost as of the last cycle
+    process_boost: Mutex<Option<BoostStatus>>,
+    /// The max frequency ramp in progress as of the last cycle
+    frequency_ramp: Mutex<Option<FrequencyRamp>>,
+}
 
-    // Cap the multiplier to avoid excessive intervals
-    idle_factor.min(5.0) // max factor of 5x
+/// Time spent in each profile
+#[derive(Default)]
+struct ProfileTimes {
+    /// Time in profiles before the active one was selected
+    totals: BTreeMap<String, Duration>,
+    /// When the active profile was selected
+    since: Option<Instant>,
 }
 
-/// Calculate optimal polling interval based on system conditions and history
-///
-/// Returns Ok with the calculated interval, or Err if the configuration is invalid
-fn compute_new(
-    params: &IntervalParams,
-    system_history: &SystemHistory,
-) -> Result<u64, ControlError> {
-    // Use the centralized validation function
-    validate_poll_intervals(params.min_interval, params.max_interval)?;
-
-    // Start with base interval
-    let mut adjusted_interval = params.base_interval;
-
-    // If we're on battery, we want to be more aggressive about saving power
-    if params.on_battery {
-        // Apply a multiplier based on battery discharge rate
-        if let Some(discharge_rate) = params.battery_discharge_rate {
-            if discharge_rate > 20.0 {
-                // High discharge rate - increase polling interval significantly (3x)
-                adjusted_interval = adjusted_interval.saturating_mul(3);
-            } else if discharge_rate > 10.0 {
-                // Moderate discharge - double polling interval (2x)
-                adjusted_interval = adjusted_interval.saturating_mul(2);
-            } else {
-                // Low discharge rate - increase by 50% (multiply by 3/2)
-                adjusted_interval = adjusted_interval.saturating_mul(3).saturating_div(2);
+impl DaemonState {
+    fn set_waker(&self, waker: Arc<Waker>) {
+        let _ = self.waker.set(waker);
+    }
+
+    /// Asks for a cycle now instead of waiting for the timer
+    fn wake(&self) {
+        if let Some(waker) = self.waker.get() {
+            waker.wake();
+        }
+    }
+
+    /// The forced profile, if any. One whose time is up ends here.
+    fn forced_profile(&self) -> Option<Override<ForcedProfile>> {
+        let mut forced = self
+            .forced_profile
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if forced.as_ref().is_some_and(Override::is_due) {
+            info!("Forced profile expired, back to automatic selection");
+            *forced = None;
+        }
+        forced.clone()
+    }
+
+    pub fn force_mode(&self) -> Option<ForcedProfile> {
+        self.forced_profile().map(|forced| forced.value)
+    }
+
+    /// When the forced profile gives way to automatic selection, if it does
+    pub fn force_end(&self) -> Option<ForceEnd> {
+        self.forced_profile().and_then(|forced| forced.until)
+    }
+
+    /// Forces `mode` until `until`, or until changed again without one.
+    /// `None` returns to automatic selection.
+    pub fn set_force_mode(&self, mode: Option<ForcedProfile>, until: Option<ForceEnd>) {
+        match (&mode, until) {
+            (Some(mode), Some(ForceEnd::At(until))) => {
+                info!("Forced profile set to {mode} until {until}");
             }
-        } else {
-            // If we don't know discharge rate, use a conservative multiplier (2x)
-            adjusted_interval = adjusted_interval.saturating_mul(2);
+            (Some(mode), Some(ForceEnd::PowerChange)) => {
+                info!("Forced profile set to {mode} until the power source changes");
+            }
+            (Some(mode), None) => info!("Forced profile set to {mode}"),
+            (None, _) => info!("Forced profile cleared, back to automatic selection"),
         }
+        *self
+            .forced_profile
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) =
+            mode.map(|mode| self.new_override(mode, until));
+        self.wake();
     }
 
-    // Adjust for system idleness
-    if params.is_system_idle {
-        let idle_time_seconds = params.last_user_activity.as_secs();
+    fn new_override<T>(&self, value: T, until: Option<ForceEnd>) -> Override<T> {
+        Override {
+            value,
+            until,
+            deadline: match until {
+                Some(ForceEnd::At(until)) => {
+                    let left = until.duration_since(jiff::Timestamp::now());
+                    Some(Instant::now() + Duration::try_from(left).unwrap_or_default())
+                }
+                _ => None,
+            },
+            // Known right away when a report was collected already, otherwise
+            // taken from the next one
+            on_ac: self
+                .last_report()
+                .map(|report| engine::is_on_ac_power(&report)),
+        }
+    }
 
-        // Apply adjustment only if the system has been idle for a non-zero duration
-        if idle_time_seconds > 0 {
-            let idle_factor = idle_multiplier(idle_time_seconds);
+    /// Ends the forced profile and turbo override set to last until the power
+    /// source changes, once it has
+    fn track_power_source(&self, on_ac: bool) {
+        let mut forced = self
+            .forced_profile
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if forced
+            .as_mut()
+            .is_some_and(|forced| forced.power_changed(on_ac))
+        {
+            info!("Power source changed, forced profile ended, back to automatic selection");
+            *forced = None;
+        }
+        drop(forced);
+
+        let mut turbo = self
+            .turbo_override
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if turbo
+            .as_mut()
+            .is_some_and(|turbo| turbo.power_changed(on_ac))
+        {
+            info!("Power source changed, turbo override ended, back to the profile's setting");
+            *turbo = None;
+        }
+    }
 
-            debug!(
-                "System idle for {} seconds (approx. {} minutes), applying idle factor: {:.2}x",
-                idle_time_seconds,
-                (idle_time_seconds as f32 / 60.0).round(),
-                idle_factor
+    pub fn turbo_override(&self) -> Option<TurboSetting> {
+        self.turbo_override
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .map(|turbo| turbo.value)
+    }
+
+    /// Whether the turbo override ends once the power source changes
+    pub fn turbo_override_end(&self) -> Option<ForceEnd> {
+        self.turbo_override
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .and_then(|turbo| turbo.until)
+    }
+
+    pub fn set_turbo_override(&self, setting: Option<TurboSetting>, until: Option<ForceEnd>) {
+        match (setting, until) {
+            (Some(setting), Some(ForceEnd::PowerChange)) => {
+                info!("Turbo override set to {setting:?} until the power source changes");
+            }
+            _ => info!("Turbo override set to {setting:?}"),
+        }
+        *self
+            .turbo_override
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) =
+            setting.map(|setting| self.new_override(setting, until));
+        self.wake();
+    }
+
+    /// Takes the config profiles that can be forced from a freshly loaded
+    /// config. A forced profile the config no longer defines ends right away.
+    fn set_profile_names(&self, config: &AppConfig) {
+        let names = config.profile_names();
+        let mut forced = self
+            .forced_profile
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if let Some(Override {
+            value: ForcedProfile::Named(name),
+            ..
+        }) = forced.as_ref()
+        {
+            if !names.contains(name) {
+                warn!("Forced profile '{name}' is no longer defined, back to automatic selection");
+                *forced = None;
+            }
+        }
+        *self
+            .profile_names
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = names;
+    }
+
+    /// Forces a profile by name, or returns to automatic selection with "auto"
+    ///
+    /// With a `duration`, an `until` or `until_power_change`, automatic
+    /// selection resumes on its own after that long, at that time or once the
+    /// system switches between AC and battery power.
+    pub fn set_profile_by_name(
+        &self,
+        name: &str,
+        duration: Option<jiff::SignedDuration>,
+        until: Option<jiff::Timestamp>,
+        until_power_change: bool,
+    ) -> Result<(), String> {
+        if name.eq_ignore_ascii_case("auto") {
+            if duration.is_some() || until.is_some() || until_power_change {
+                return Err("Automatic selection has no end, so it takes no duration".to_string());
+            }
+            self.set_force_mode(None, None);
+            return Ok(());
+        }
+
+        let mode = name.parse::<ForcedProfile>()?;
+        if let ForcedProfile::Named(name) = &mode {
+            let names = self
+                .profile_names
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
+            if !names.contains(name) {
+                return Err(format!(
+                    "Unknown profile '{name}'. Valid profiles: {}",
+                    PROFILE_NAMES
+                        .iter()
+                        .copied()
+                        .chain(names.iter().map(String::as_str))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ));
+            }
+        }
+        let until = match (duration, until, until_power_change) {
+            (Some(_), Some(_), _) | (Some(_), _, true) | (_, Some(_), true) => {
+                return Err(
+                    "A forced profile ends after a duration, at a time or on a power change, \
+                     only one of them"
+                        .to_string(),
+                );
+            }
+            (Some(duration), None, false) if !duration.is_positive() => {
+                return Err("Profile duration must be positive".to_string());
+            }
+            (Some(duration), None, false) => Some(ForceEnd::At(
+                jiff::Timestamp::now()
+                    .checked_add(duration)
+                    .map_err(|e| format!("Invalid profile duration: {e}"))?,
+            )),
+            (None, Some(until), false) if until <= jiff::Timestamp::now() => {
+                return Err(format!("Profile expiry {until} has passed already"));
+            }
+            (None, Some(until), false) => Some(ForceEnd::At(until)),
+            (None, None, true) => Some(ForceEnd::PowerChange),
+            (None, None, false) => None,
+        };
+
+        self.set_force_mode(Some(mode), until);
+        Ok(())
+    }
+
+    /// Overrides turbo with "always" or "never", or clears the override with
+    /// "auto". With `until_power_change`, the override ends on its own once
+    /// the system switches between AC and battery power.
+    pub fn set_turbo_by_name(&self, mode: &str, until_power_change: bool) -> Result<(), String> {
+        let setting = TurboSetting::from_str(mode, true).map_err(|_| {
+            format!("Unknown turbo mode '{mode}'. Expected one of: always, never, auto")
+        })?;
+
+        match setting {
+            TurboSetting::Auto if until_power_change => Err(
+                "Clearing the turbo override has no end, so it takes no power change".to_string(),
+            ),
+            TurboSetting::Auto => {
+                self.set_turbo_override(None, None);
+                Ok(())
+            }
+            other => {
+                self.set_turbo_override(
+                    Some(other),
+                    until_power_change.then_some(ForceEnd::PowerChange),
+                );
+                Ok(())
+            }
+        }
+    }
+
+    /// When the forced profile or a time-limited override ends next, for the
+    /// engine to apply the profile's settings again right then
+    fn next_expiry(&self) -> Option<SystemTime> {
+        let forced = self.force_end().and_then(|end| match end {
+            ForceEnd::At(until) => Some(until),
+            ForceEnd::PowerChange => None,
+        });
+        let timed = self.timed_overrides().into_iter().map(|entry| entry.until);
+        forced.into_iter().chain(timed).min().map(SystemTime::from)
+    }
+
+    /// Time-limited overrides still active. Those whose time is up end here.
+    pub fn timed_overrides(&self) -> Vec<TimedOverride> {
+        let mut timed = self
+            .timed_overrides
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let before = timed.len();
+        timed.retain(|entry| {
+            if entry.is_expired() {
+                info!(
+                    "Override of {}{} expired, back to the profile's setting",
+                    entry.knob,
+                    entry.describe_scope()
+                );
+            }
+            !entry.is_expired()
+        });
+        if timed.len() != before {
+            save_timed_overrides(&timed);
+        }
+        timed.clone()
+    }
+
+    /// Overrides `knob` with `value` until `until`, only while on the power
+    /// source of `scope` if given, replacing an earlier override of the same
+    /// knob and power source
+    pub fn set_timed_override(
+        &self,
+        knob: OverrideKnob,
+        value: &str,
+        until: jiff::Timestamp,
+        scope: Option<PowerScope>,
+    ) -> Result<(), String> {
+        let entry = TimedOverride::new(knob, value, until, scope)?;
+        info!(
+            "Override of {knob}{} set to {} until {}",
+            entry.describe_scope(),
+            entry.value,
+            entry.until
+        );
+
+        let mut timed = self
+            .timed_overrides
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        overrides::insert(&mut timed, entry);
+        save_timed_overrides(&timed);
+        drop(timed);
+        self.wake();
+        Ok(())
+    }
+
+    /// Ends the overrides of `knob` limited to the power source of `scope`,
+    /// with a missing one matching any, returning how many ended
+    pub fn clear_timed_overrides(
+        &self,
+        knob: Option<OverrideKnob>,
+        scope: Option<PowerScope>,
+    ) -> usize {
+        let mut timed = self
+            .timed_overrides
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let before = timed.len();
+        timed.retain(|entry| !entry.cleared_by(knob, scope));
+        let cleared = before - timed.len();
+        if cleared > 0 {
+            let on = scope
+                .map(|scope| format!(" on {scope}"))
+                .unwrap_or_default();
+            match knob {
+                Some(knob) => {
+                    info!("Override of {knob}{on} cleared, back to the profile's setting");
+                }
+                None => info!("All overrides{on} cleared, back to the profiles' settings"),
+            }
+            save_timed_overrides(&timed);
+            drop(timed);
+            self.wake();
+        }
+        cleared
+    }
+
+    /// Picks up the overrides left in [`OVERRIDES_PATH`] by an earlier daemon
+    /// or `watt override` without one
+    fn restore_timed_overrides(&self) {
+        let restored = overrides::load();
+        if !restored.is_empty() {
+            info!(
+                "Restored {} override(s) from {OVERRIDES_PATH}",
+                restored.len()
             );
+        }
+        *self
+            .timed_overrides
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = restored;
+    }
+
+    /// Changes the log level until the daemon exits
+    pub fn set_log_level_by_name(&self, name: &str) -> Result<(), String> {
+        let level = match name.to_ascii_lowercase().as_str() {
+            "error" => LogLevel::Error,
+            "warn" | "warning" => LogLevel::Warning,
+            "info" => LogLevel::Info,
+            "debug" => LogLevel::Debug,
+            _ => {
+                return Err(format!(
+                    "Unknown log level '{name}'. Expected one of: error, warning, info, debug"
+                ));
+            }
+        };
+
+        log::set_max_level(level_filter(level));
+        info!("Log level set to {level:?}");
+        Ok(())
+    }
+
+    /// Has the engine thread set battery charge thresholds right away. A profile
+    /// with its own thresholds puts them back on the next cycle.
+    ///
+    /// Only the values are checked here. Writing them is left to the engine
+    /// thread, which keeps the privileges needed for it after
+    /// `daemon.run_as_user` takes effect.
+    pub fn set_battery_thresholds(&self, start: u8, stop: u8) -> Result<(), String> {
+        let thresholds = BatteryChargeThresholds::new(start, stop).map_err(|e| e.to_string())?;
+        *self
+            .pending_thresholds
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = Some(thresholds);
+        self.wake();
+        Ok(())
+    }
+
+    fn take_pending_thresholds(&self) -> Option<BatteryChargeThresholds> {
+        self.pending_thresholds
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take()
+    }
+
+    pub fn request_reload(&self) {
+        info!("Configuration reload requested");
+        self.reload_requested.store(true, Ordering::SeqCst);
+        self.wake();
+    }
 
-            // Convert f32 multiplier to integer-safe math
-            // Multiply by a large number first, then divide to maintain precision
-            // Use 1000 as the scaling factor to preserve up to 3 decimal places
-            let scaling_factor = 1000;
-            let scaled_factor = (idle_factor * scaling_factor as f32) as u64;
-            adjusted_interval = adjusted_interval
-                .saturating_mul(scaled_factor)
-                .saturating_div(scaling_factor);
+    /// Marks that the system resumed, so settings are reapplied right away
+    pub fn notify_resumed(&self) {
+        debug!("System resumed from suspend");
+        self.resumed.store(true, Ordering::SeqCst);
+        self.wake();
+    }
+
+    /// Returns whether the system resumed since the last call
+    fn take_resumed(&self) -> bool {
+        self.resumed.swap(false, Ordering::SeqCst)
+    }
+
+    /// Returns whether a reload was requested, clearing the request
+    fn take_reload_request(&self) -> bool {
+        self.reload_requested.swap(false, Ordering::SeqCst)
+    }
+
+    /// The current pause, if any. A pause whose time is up ends here.
+    pub fn pause_state(&self) -> Option<Pause> {
+        let mut pause = self.pause.lock().unwrap_or_else(PoisonError::into_inner);
+        if pause
+            .and_then(|pause| pause.until)
+            .is_some_and(|until| until <= jiff::Timestamp::now())
+        {
+            info!("Pause is over, settings management resumed");
+            *pause = None;
         }
-        // If idle_time_seconds is 0, no factor is applied by this block
+        *pause
     }
 
-    // Adjust for CPU/temperature volatility
-    if params.cpu_volatility > 10.0 || params.temp_volatility > 2.0 {
-        // For division by 2 (halving the interval), we can safely use integer division
-        adjusted_interval = (adjusted_interval / 2).max(1);
+    pub fn is_paused(&self) -> bool {
+        self.pause_state().is_some()
     }
 
-    // Enforce a minimum of 1 second to prevent busy loops, regardless of params.min_interval
-    let min_safe_interval = params.min_interval.max(1);
-    let new_interval = adjusted_interval.clamp(min_safe_interval, params.max_interval);
+    /// Stops applying settings until [`Self::resume`] is called or, with a
+    /// `duration`, until that much time has passed. Monitoring goes on.
+    pub fn pause(&self, duration: Option<jiff::SignedDuration>) -> Result<(), String> {
+        let now = jiff::Timestamp::now();
+        let until = match duration {
+            Some(duration) if !duration.is_positive() => {
+                return Err("Pause duration must be positive".to_string());
+            }
+            Some(duration) => Some(
+                now.checked_add(duration)
+                    .map_err(|e| format!("Invalid pause duration: {e}"))?,
+            ),
+            None => None,
+        };
 
-    // Blend the new interval with the cached value if available
-    let blended_interval = if let Some(cached) = system_history.last_computed_interval {
-        // Use a weighted average: 70% previous value, 30% new value
-        // This smooths out drastic changes in polling frequency
-        const PREVIOUS_VALUE_WEIGHT: u128 = 7; // 70%
-        const NEW_VALUE_WEIGHT: u128 = 3; // 30%
-        const TOTAL_WEIGHT: u128 = PREVIOUS_VALUE_WEIGHT + NEW_VALUE_WEIGHT; // 10
+        let mut pause = self.pause.lock().unwrap_or_else(PoisonError::into_inner);
+        // Extending or shortening a pause keeps when it started
+        let since = pause.map_or(now, |pause| pause.since);
+        *pause = Some(Pause { since, until });
+        match until {
+            Some(until) => info!("Settings management paused until {until}"),
+            None => info!("Settings management paused until resumed"),
+        }
+        Ok(())
+    }
 
-        // XXX: Use u128 arithmetic to avoid overflow with large interval values
-        let result = (u128::from(cached) * PREVIOUS_VALUE_WEIGHT
-            + u128::from(new_interval) * NEW_VALUE_WEIGHT)
-            / TOTAL_WEIGHT;
+    pub fn resume(&self) {
+        let pause = self
+            .pause
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take();
+        if pause.is_some() {
+            info!("Settings management resumed");
+            self.wake();
+        }
+    }
 
-        result as u64
-    } else {
-        new_interval
-    };
+    /// Returns to what the configuration asks for: clears the forced profile,
+    /// every override and a pause, then waits for the engine thread to forget
+    /// the turbo hysteresis and knob bookkeeping and apply the selected
+    /// profile in full. With `hard`, the settings snapshot is restored before
+    /// that, if there is one.
+    pub fn reset(&self, hard: bool) -> Result<ResetReport, String> {
+        info!("Resetting to the configured state");
+        let mut cleared = Vec::new();
+        let forced = self
+            .forced_profile
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take();
+        if forced.is_some() {
+            cleared.push("forced profile".to_string());
+        }
+        let turbo = self
+            .turbo_override
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take();
+        if turbo.is_some() {
+            cleared.push("turbo override".to_string());
+        }
+        match self.clear_timed_overrides(None, None) {
+            0 => {}
+            1 => cleared.push("1 timed override".to_string()),
+            count => cleared.push(format!("{count} timed overrides")),
+        }
+        let pause = self
+            .pause
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take();
+        if pause.is_some() {
+            cleared.push("pause".to_string());
+        }
+        match cpu::clear_governor_override() {
+            Ok(true) => cleared.push("force-governor override".to_string()),
+            Ok(false) => {}
+            Err(e) => warn!("Failed to clear the force-governor override: {e}"),
+        }
+
+        let (reply, answer) = mpsc::channel();
+        *self
+            .pending_reset
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = Some(PendingReset {
+            hard,
+            cleared,
+            reply,
+        });
+        self.wake();
+        answer.recv_timeout(RESET_TIMEOUT).map_err(|_| {
+            format!(
+                "Settings were not reapplied within {}s",
+                RESET_TIMEOUT.as_secs()
+            )
+        })
+    }
 
-    // Blended result still needs to respect the configured bounds
-    // Again enforce minimum of 1 second regardless of params.min_interval
-    Ok(blended_interval.clamp(min_safe_interval, params.max_interval))
+    fn take_pending_reset(&self) -> Option<PendingReset> {
+        self.pending_reset
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take()
+    }
+
+    /// Snapshot of the daemon's state for status queries
+    pub fn status(&self) -> DaemonStatus {
+        let pause = self.pause_state();
+        DaemonStatus {
+            active_profile: self.active_profile(),
+            force_mode: self.force_mode().map(|mode| mode.to_string()),
+            force_end: self.force_end(),
+            turbo_override: self
+                .turbo_override()
+                .map(|setting| format!("{setting:?}").to_lowercase()),
+            turbo_override_end: self.turbo_override_end(),
+            overrides: self.timed_overrides(),
+            paused: pause.is_some(),
+            paused_until: pause.and_then(|pause| pause.until),
+            counters: Some(self.metrics.counters()),
+            log_level: Some(log::max_level().to_string().to_lowercase()),
+            parked_knobs: self.knobs.parked(),
+            drifted_knobs: self.knobs.drifted(),
+            paused_knobs: self.knobs.paused(),
+            decision_traces: self.knobs.decision_traces(),
+            cpu_control_unavailable: self.knobs.cpu_control_unavailable(),
+            last_apply: self.knobs.last_summary(),
+            turbo_hysteresis: Some(
+                self.turbo_hysteresis
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .clone(),
+            ),
+            process_boost: self.process_boost(),
+            frequency_ramp: *self
+                .frequency_ramp
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner),
+            report: self.last_report(),
+        }
+    }
+
+    /// Answer to a liveness probe. Only reads timestamps, so it is answered
+    /// right away even while a cycle is running.
+    pub fn pong(&self) -> Pong {
+        Pong {
+            started_at: self.metrics.counters().started_at,
+            last_cycle: *self
+                .last_cycle
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner),
+            last_successful_apply: self.last_successful_apply(),
+        }
+    }
+
+    fn record_cycle(&self) {
+        *self
+            .last_cycle
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = Some(jiff::Timestamp::now());
+    }
+
+    pub fn last_successful_apply(&self) -> Option<jiff::Timestamp> {
+        *self
+            .last_successful_apply
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn record_successful_apply(&self, timestamp: jiff::Timestamp) {
+        *self
+            .last_successful_apply
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = Some(timestamp);
+    }
+
+    fn set_frequency_ramp(&self, ramp: Option<FrequencyRamp>) {
+        *self
+            .frequency_ramp
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = ramp;
+    }
+
+    fn set_turbo_hysteresis(&self, turbo_states: &TurboStates) {
+        let (on_ac, on_battery) = turbo_states.previous_states();
+        let groups = turbo_states
+            .group_states()
+            .into_iter()
+            .map(|(group, on_ac, on_battery)| (group, GroupHysteresis { on_ac, on_battery }))
+            .collect();
+        *self
+            .turbo_hysteresis
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = TurboHysteresis {
+            on_ac,
+            on_battery,
+            groups,
+        };
+    }
+
+    pub const fn metrics(&self) -> &Metrics {
+        &self.metrics
+    }
+
+    pub const fn knobs(&self) -> &KnobStates {
+        &self.knobs
+    }
+
+    pub fn last_report(&self) -> Option<SystemReport> {
+        self.last_report
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    pub fn set_last_report(&self, report: SystemReport) {
+        *self
+            .last_report
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = Some(report);
+    }
+
+    pub fn process_boost(&self) -> Option<BoostStatus> {
+        self.process_boost
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    fn set_process_boost(&self, boost: Option<BoostStatus>) {
+        *self
+            .process_boost
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = boost;
+    }
+
+    pub fn active_profile(&self) -> Option<String> {
+        self.active_profile
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Records the profile applied in this cycle, returning whether it changed
+    fn update_active_profile(&self, name: &str) -> bool {
+        let mut active = self
+            .active_profile
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if active.as_deref() == Some(name) {
+            return false;
+        }
+        // The first selection after startup is not a switch
+        if active.is_some() {
+            self.metrics.record_profile_switch();
+        }
+
+        let mut times = self
+            .profile_times
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let now = Instant::now();
+        if let (Some(previous), Some(since)) = (active.as_ref(), times.since) {
+            *times.totals.entry(previous.clone()).or_default() += now - since;
+        }
+        times.since = Some(now);
+
+        *active = Some(name.to_string());
+        true
+    }
+
+    /// How long each profile has been active since startup
+    pub fn profile_times(&self) -> BTreeMap<String, Duration> {
+        let active = self
+            .active_profile
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let times = self
+            .profile_times
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+
+        let mut totals = times.totals.clone();
+        if let (Some(active), Some(since)) = (active.as_ref(), times.since) {
+            *totals.entry(active.clone()).or_default() += since.elapsed();
+        }
+        totals
+    }
+
+    /// Adds the overrides the engine only saw the effect of to the decision
+    /// trace of the cycle, with the process that started a process boost
+    fn note_overrides(&self, boost_process: Option<&str>) {
+        if let Some(process) = boost_process {
+            self.knobs
+                .note_override(format!("Process boost for {process}"));
+        }
+        if let Some(turbo) = self.turbo_override() {
+            self.knobs.note_override(format!(
+                "Turbo forced to {}",
+                format!("{turbo:?}").to_lowercase()
+            ));
+        }
+        for entry in self.timed_overrides() {
+            self.knobs.note_override(format!(
+                "{} set to {} until {}{}",
+                entry.knob,
+                entry.value,
+                entry.until,
+                entry.describe_scope()
+            ));
+        }
+    }
+
+    /// Returns the configuration with any control-interface overrides applied,
+    /// of those limited to a power source only the ones for `on_ac`
+    fn effective_config(&self, config: &AppConfig, on_ac: bool) -> AppConfig {
+        let mut effective = config.clone();
+        // The process boost forces the charger profile unless a profile was
+        // forced by hand
+        if self.force_mode().is_none() && self.process_boost().is_some_and(|boost| boost.turbo) {
+            effective.charger.turbo = Some(TurboSetting::Always);
+        }
+        if let Some(turbo) = self.turbo_override() {
+            effective.charger.turbo = Some(turbo);
+            effective.battery.turbo = Some(turbo);
+            if let Some(balanced) = &mut effective.balanced {
+                balanced.turbo = Some(turbo);
+            }
+        }
+        overrides::apply(&self.timed_overrides(), &mut effective, on_ac);
+        effective
+    }
+}
+
+fn save_timed_overrides(timed: &[TimedOverride]) {
+    if let Err(e) = overrides::save(timed) {
+        warn!("Failed to save overrides to {OVERRIDES_PATH}: {e}");
+    }
+}
+
+/// Longest a reset waits for the engine thread to apply the profile
+const RESET_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// A reset waiting for the engine thread, which answers on `reply`
+struct PendingReset {
+    hard: bool,
+    /// What was cleared already
+    cleared: Vec<String>,
+    reply: mpsc::Sender<ResetReport>,
+}
+
+/// What a reset cleared and applied
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResetReport {
+    pub cleared: Vec<String>,
+    /// With `--hard`, how many settings were restored from the snapshot and
+    /// how many failed. `None` when there was no snapshot.
+    pub restored: Option<(usize, usize)>,
+    /// Profile applied afterwards
+    pub profile: String,
+    /// Every write of the apply, including values already in place
+    pub writes: Vec<WriteRecord>,
+    /// Why applying stopped early, if it did
+    pub error: Option<String>,
+}
+
+/// Names `watt profile` takes, with "auto" returning to automatic selection
+pub const PROFILE_NAMES: &[&str] = &[
+    "charger",
+    "battery",
+    "performance",
+    "balanced",
+    "powersave",
+    "auto",
+];
+
+/// A profile or turbo setting forced through a control interface
+#[derive(Debug, Clone, Copy)]
+struct Override<T> {
+    value: T,
+    until: Option<ForceEnd>,
+    /// The monotonic clock at [`ForceEnd::At`], so setting the wall clock
+    /// back does not make it last longer
+    deadline: Option<Instant>,
+    /// Power source when it was set, for [`ForceEnd::PowerChange`]
+    on_ac: Option<bool>,
+}
+
+impl<T> Override<T> {
+    /// Whether its time is up by the wall clock, or by the monotonic clock in
+    /// case the wall clock was set back since
+    fn is_due(&self) -> bool {
+        matches!(self.until, Some(ForceEnd::At(until)) if until <= jiff::Timestamp::now())
+            || self
+                .deadline
+                .is_some_and(|deadline| deadline <= Instant::now())
+    }
+
+    /// Whether it ends on a power change and the system switched between AC
+    /// and battery power since it was set. The first call after setting it
+    /// without a known power source records the current one.
+    fn power_changed(&mut self, on_ac: bool) -> bool {
+        if self.until != Some(ForceEnd::PowerChange) {
+            return false;
+        }
+        match self.on_ac {
+            None => {
+                self.on_ac = Some(on_ac);
+                false
+            }
+            Some(was_on_ac) => was_on_ac != on_ac,
+        }
+    }
+}
+
+/// When a forced profile or turbo override gives way to automatic selection
+/// again
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ForceEnd {
+    At(jiff::Timestamp),
+    /// Once the system switches between AC and battery power
+    PowerChange,
+}
+
+/// A pause of settings management, see [`DaemonState::pause`]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Pause {
+    pub since: jiff::Timestamp,
+    /// When management resumes on its own, if it does
+    pub until: Option<jiff::Timestamp>,
+}
+
+/// Reply to a ping, for liveness checks
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Pong {
+    pub started_at: jiff::Timestamp,
+    /// `None` until the first cycle finished
+    pub last_cycle: Option<jiff::Timestamp>,
+    pub last_successful_apply: Option<jiff::Timestamp>,
+}
+
+/// Daemon state as reported to status queries
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DaemonStatus {
+    pub active_profile: Option<String>,
+    pub force_mode: Option<String>,
+    /// When the forced profile ends, if it does
+    #[serde(default)]
+    pub force_end: Option<ForceEnd>,
+    pub turbo_override: Option<String>,
+    /// When the turbo override ends, if it does
+    #[serde(default)]
+    pub turbo_override_end: Option<ForceEnd>,
+    /// Time-limited overrides of single settings
+    #[serde(default)]
+    pub overrides: Vec<TimedOverride>,
+    pub paused: bool,
+    /// When a timed pause ends
+    #[serde(default)]
+    pub paused_until: Option<jiff::Timestamp>,
+    /// Missing when talking to an older daemon
+    #[serde(default)]
+    pub log_level: Option<String>,
+    /// Missing when talking to an older daemon
+    #[serde(default)]
+    pub counters: Option<Counters>,
+    /// Knobs not written for now because they are unsupported or keep failing
+    #[serde(default)]
+    pub parked_knobs: Vec<ParkedKnob>,
+    /// Knobs changed by someone else and left that way, per the drift policy
+    #[serde(default)]
+    pub drifted_knobs: Vec<DriftedKnob>,
+    /// Knobs changed by hand and no longer managed, by `respect`
+    #[serde(default)]
+    pub paused_knobs: Vec<String>,
+    /// Why the last cycles decided what they did, oldest first
+    #[serde(default)]
+    pub decision_traces: Vec<DecisionTrace>,
+    /// Why CPU frequency control is unavailable, if it is
+    #[serde(default)]
+    pub cpu_control_unavailable: Option<String>,
+    /// What the last apply did to each knob
+    #[serde(default)]
+    pub last_apply: ApplySummary,
+    /// Missing when talking to an older daemon
+    #[serde(default)]
+    pub turbo_hysteresis: Option<TurboHysteresis>,
+    /// Missing when no process boost is in progress
+    #[serde(default)]
+    pub process_boost: Option<BoostStatus>,
+    /// Missing when no max frequency ramp is in progress
+    #[serde(default)]
+    pub frequency_ramp: Option<FrequencyRamp>,
+    pub report: Option<SystemReport>,
+}
+
+/// Turbo state the auto turbo hysteresis holds for each power source, unset
+/// until it first decided
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TurboHysteresis {
+    pub on_ac: Option<bool>,
+    pub on_battery: Option<bool>,
+    /// Per core group, when turbo is decided for each group separately
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub groups: BTreeMap<String, GroupHysteresis>,
+}
+
+/// Turbo state of a core group for each power source
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct GroupHysteresis {
+    pub on_ac: Option<bool>,
+    pub on_battery: Option<bool>,
+}
+
+/// Adapts the poll interval to how bursty the workload is
+///
+/// Keeps exponentially-weighted estimates of the mean and variance of CPU
+/// usage. High variance shortens the interval toward `min_poll_interval_sec`
+/// so bursts are caught quickly, low variance lengthens it toward
+/// `max_poll_interval_sec`, and anything in between holds it steady.
+#[derive(Debug)]
+struct AdaptiveInterval {
+    /// Smoothed CPU usage and its variance. Fed the raw usage, since
+    /// smoothing it first would hide the bursts the variance is about.
+    usage: Ewma,
+    /// Current interval in seconds
+    interval: u64,
+}
+
+impl AdaptiveInterval {
+    fn new(interval: u64) -> Self {
+        Self {
+            usage: Ewma::default(),
+            interval,
+        }
+    }
+
+    /// Feeds one CPU usage sample and returns the new interval
+    fn update(&mut self, usage: f32, config: &DaemonConfig) -> u64 {
+        let settings = &config.adaptive;
+        self.usage.update(usage, settings.smoothing);
+        let variance = self.usage.variance();
+
+        let previous = self.interval;
+        if variance > settings.variance_high {
+            self.interval = self.interval.saturating_sub(settings.step_down_sec);
+        } else if variance < settings.variance_low {
+            self.interval = self.interval.saturating_add(settings.step_up_sec);
+        }
+        self.interval = self
+            .interval
+            .clamp(config.min_poll_interval_sec, config.max_poll_interval_sec);
+
+        if self.interval != previous {
+            debug!(
+                "Adaptive polling: load variance {variance:.2}, interval {previous}s -> {}s",
+                self.interval
+            );
+        }
+
+        self.interval
+    }
 }
 
 /// Tracks historical system data for "advanced" adaptive polling
@@ -156,39 +1116,21 @@ fn compute_new(
 struct SystemHistory {
     /// Last several CPU usage measurements
     cpu_usage_history: VecDeque<f32>,
-    /// Last several temperature readings
-    temperature_history: VecDeque<f32>,
-    /// Time of last detected user activity
-    last_user_activity: Instant,
-    /// Previous battery percentage (to calculate discharge rate)
-    last_battery_percentage: Option<f32>,
-    /// Timestamp of last battery reading
-    last_battery_timestamp: Option<Instant>,
-    /// Battery discharge rate (%/hour)
-    battery_discharge_rate: Option<f32>,
     /// Time spent in each system state
     state_durations: std::collections::HashMap<SystemState, Duration>,
     /// Last time a state transition happened
     last_state_change: Instant,
     /// Current system state
     current_state: SystemState,
-    /// Last computed optimal polling interval
-    last_computed_interval: Option<u64>,
 }
 
 impl Default for SystemHistory {
     fn default() -> Self {
         Self {
             cpu_usage_history: VecDeque::new(),
-            temperature_history: VecDeque::new(),
-            last_user_activity: Instant::now(),
-            last_battery_percentage: None,
-            last_battery_timestamp: None,
-            battery_discharge_rate: None,
             state_durations: std::collections::HashMap::new(),
             last_state_change: Instant::now(),
             current_state: SystemState::default(),
-            last_computed_interval: None,
         }
     }
 }
@@ -216,377 +1158,1441 @@ impl SystemHistory {
                     self.cpu_usage_history.pop_front();
                 }
                 self.cpu_usage_history.push_back(avg_usage);
+            }
+        }
+
+        // Update system state tracking
+        let new_state = determine_system_state(report, self);
+        if new_state != self.current_state {
+            // Record time spent in previous state
+            let time_in_state = self.last_state_change.elapsed();
+            *self
+                .state_durations
+                .entry(self.current_state.clone())
+                .or_insert(Duration::ZERO) += time_in_state;
+
+            // Update state
+            self.current_state = new_state;
+            self.last_state_change = Instant::now();
+        }
+    }
+
+    /// Calculate CPU usage volatility (how much it's changing)
+    fn get_cpu_volatility(&self) -> f32 {
+        if self.cpu_usage_history.len() < 2 {
+            return 0.0;
+        }
+
+        let mut sum_of_changes = 0.0;
+        for i in 1..self.cpu_usage_history.len() {
+            sum_of_changes += (self.cpu_usage_history[i] - self.cpu_usage_history[i - 1]).abs();
+        }
+
+        sum_of_changes / (self.cpu_usage_history.len() - 1) as f32
+    }
+
+    /// Determine if the system appears to be idle
+    fn is_system_idle(&self) -> bool {
+        if self.cpu_usage_history.is_empty() {
+            return false;
+        }
+
+        // System considered idle if the average CPU usage of last readings is below 10%
+        let recent_avg =
+            self.cpu_usage_history.iter().sum::<f32>() / self.cpu_usage_history.len() as f32;
+        recent_avg < 10.0 && self.get_cpu_volatility() < 5.0
+    }
+}
+
+/// Validates the `[daemon.adaptive]` tuning
+pub fn validate_adaptive_settings(settings: &AdaptiveIntervalSettings) -> Result<(), ControlError> {
+    if !(settings.smoothing > 0.0 && settings.smoothing <= 1.0) {
+        return Err(ControlError::InvalidValueError(format!(
+            "adaptive smoothing ({}) must be in (0, 1]",
+            settings.smoothing
+        )));
+    }
+    if settings.variance_low > settings.variance_high {
+        return Err(ControlError::InvalidValueError(format!(
+            "adaptive variance_low ({}) must not exceed variance_high ({})",
+            settings.variance_low, settings.variance_high
+        )));
+    }
+    Ok(())
+}
+
+/// Checks the smoothing factors of `[daemon.smoothing]`
+pub fn validate_smoothing_settings(settings: &SmoothingSettings) -> Result<(), ControlError> {
+    for (name, factor) in [
+        ("load", settings.load),
+        ("temperature", settings.temperature),
+    ] {
+        if !(factor > 0.0 && factor <= 1.0) {
+            return Err(ControlError::InvalidValueError(format!(
+                "smoothing {name} ({factor}) must be in (0, 1]"
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Stretches the poll interval while nothing is happening
+///
+/// Once CPU usage stayed below `idle_load_threshold` for `idle_cycles`
+/// consecutive cycles, every further idle cycle doubles the interval until it
+/// reaches `max_poll_interval_sec`. A load spike, power event or configuration
+/// change snaps it back to the regular interval.
+#[derive(Debug, Default)]
+struct IdleDetector {
+    /// Consecutive cycles spent idle
+    idle_cycles: u32,
+}
+
+impl IdleDetector {
+    /// Records the average CPU usage of a cycle
+    fn record(&mut self, cpu_usage: Option<f32>, threshold: f32) {
+        match cpu_usage {
+            Some(usage) if usage < threshold => {
+                self.idle_cycles = self.idle_cycles.saturating_add(1);
+            }
+            Some(usage) => {
+                if self.idle_cycles > 0 {
+                    debug!("CPU usage rose to {usage:.1}%, leaving idle back-off");
+                }
+                self.idle_cycles = 0;
+            }
+            None => {}
+        }
+    }
+
+    /// Forgets the idle streak, e.g. after a power event
+    fn reset(&mut self) {
+        self.idle_cycles = 0;
+    }
+
+    /// Returns `interval` lengthened according to the current idle streak
+    fn stretch(&self, interval: u64, config: &DaemonConfig) -> u64 {
+        let Some(steps) = self.idle_cycles.checked_sub(config.idle_cycles) else {
+            return interval;
+        };
+
+        let factor = 1u64 << (steps + 1).min(16);
+        interval
+            .saturating_mul(factor)
+            .min(config.max_poll_interval_sec)
+            .max(interval)
+    }
+}
+
+/// Average CPU usage across all cores that report it
+fn average_cpu_usage(report: &SystemReport) -> Option<f32> {
+    let usages: Vec<f32> = report
+        .cpu_cores
+        .iter()
+        .filter_map(|core| core.usage_percent)
+        .collect();
+
+    if usages.is_empty() {
+        None
+    } else {
+        Some(usages.iter().sum::<f32>() / usages.len() as f32)
+    }
+}
+
+/// Validates that poll interval configuration is consistent
+/// Returns Ok if configuration is valid, Err with a descriptive message if invalid
+pub fn validate_poll_intervals(min_interval: u64, max_interval: u64) -> Result<(), ControlError> {
+    if min_interval < 1 {
+        return Err(ControlError::InvalidValueError(
+            "min_interval must be ≥ 1".to_string(),
+        ));
+    }
+    if max_interval < 1 {
+        return Err(ControlError::InvalidValueError(
+            "max_interval must be ≥ 1".to_string(),
+        ));
+    }
+    if max_interval >= min_interval {
+        Ok(())
+    } else {
+        Err(ControlError::InvalidValueError(format!(
+            "Invalid interval configuration: max_interval ({max_interval}) is less than min_interval ({min_interval})"
+        )))
+    }
+}
+
+const fn level_filter(level: LogLevel) -> LevelFilter {
+    match level {
+        LogLevel::Error => LevelFilter::Error,
+        LogLevel::Warning => LevelFilter::Warn,
+        LogLevel::Info => LevelFilter::Info,
+        LogLevel::Debug => LevelFilter::Debug,
+    }
+}
+
+/// Command-line options for the daemon
+pub struct DaemonOptions {
+    /// Level from the command line or RUST_LOG, which beats the configured one
+    pub log_level: Option<LevelFilter>,
+    /// Replace an already running daemon
+    pub replace: bool,
+    /// Session-scoped run, restoring the original settings on exit
+    pub live: bool,
+    /// Fork into the background before starting
+    pub daemonize: bool,
+    /// Only log the sysfs writes each cycle would make
+    pub dry_run: bool,
+    /// Profile to apply instead of selecting one automatically
+    pub force_profile: Option<ForcedProfile>,
+}
+
+/// Run the daemon
+pub fn run_daemon(config: AppConfig, options: &DaemonOptions) -> Result<(), AppError> {
+    // Update the log level filter if needed, without re-initializing the logger.
+    // This is also how the level is changed at runtime over IPC.
+    log::set_max_level(
+        options
+            .log_level
+            .unwrap_or_else(|| level_filter(config.daemon.log_level)),
+    );
+
+    // Validate critical configuration values before proceeding
+    if let Err(err) = validate_poll_intervals(
+        config.daemon.min_poll_interval_sec,
+        config.daemon.max_poll_interval_sec,
+    ) {
+        return Err(AppError::Control(err));
+    }
+    if let Err(err) = validate_adaptive_settings(&config.daemon.adaptive) {
+        return Err(AppError::Control(err));
+    }
+    if let Err(err) = validate_smoothing_settings(&config.daemon.smoothing) {
+        return Err(AppError::Control(err));
+    }
+    engine::apply_order(&config.daemon.apply_order)?;
+    engine::validate_thermal_safety(&config.daemon)?;
+    if let Some(ForcedProfile::Named(name)) = &options.force_profile {
+        engine::named_profile(&config, name)?;
+    }
+
+    // Forking has to happen before any threads are started below. The parent
+    // waits until the PID file is written, then exits with our startup status.
+    let daemonized = if options.daemonize {
+        Some(daemonize::daemonize(
+            config.daemon.log_file.as_deref().map(Path::new),
+        )?)
+    } else {
+        None
+    };
+
+    info!("Starting Watt daemon...");
+    let started = Instant::now();
+
+    // Refuse to run next to another instance, as both would fight over the
+    // same knobs every poll. The lock is held until this function returns.
+    let pid_file = match PidFile::acquire(Path::new(&config.daemon.pid_file), options.replace) {
+        Ok(pid_file) => pid_file,
+        Err(e) => {
+            if let Some(daemonized) = daemonized {
+                daemonized.fail(&e.to_string());
+            }
+            return Err(e.into());
+        }
+    };
+    if let Some(daemonized) = daemonized {
+        daemonized.ready();
+    }
+
+    // Save the settings we are about to change, so they can be put back on
+    // exit. Live runs are session-scoped, so they always restore.
+    let snapshot = if config.daemon.restore_on_exit || options.live {
+        let snapshot = Snapshot::load_or_capture();
+        if let Err(e) = snapshot.persist() {
+            warn!("Failed to save settings snapshot for crash recovery: {e}");
+        }
+        Some(snapshot)
+    } else {
+        None
+    };
+    if options.live {
+        info!("Live session started, press Ctrl+C to stop and restore the original settings");
+    }
+    let controller = control::select(options.dry_run);
+    if options.dry_run {
+        sysfs::record_writes();
+        info!("Dry run, nothing is written. The writes each cycle would make are logged instead");
+    }
+
+    // Wakes the monitor thread between cycles, e.g. on shutdown
+    let waker = Arc::new(Waker::new()?);
+    let shutdown = Arc::new(Shutdown::new(waker.clone()));
+    let signal_shutdown = shutdown.clone();
+
+    // Set up signal handlers for SIGINT and SIGTERM. In a live session a
+    // second one quits right away, e.g. when restoring hangs, and the snapshot
+    // left behind lets the next run restore instead.
+    let live = options.live;
+    ctrlc::set_handler(move || {
+        if live && signal_shutdown.is_requested() {
+            eprintln!("Received another shutdown signal, quitting without restoring settings");
+            process::exit(130);
+        }
+        info!("Received shutdown signal, exiting...");
+        signal_shutdown.request();
+    })
+    .map_err(|e| AppError::Generic(format!("Error setting Ctrl-C handler: {e}")))?;
+
+    // A panic in a single cycle is logged with its backtrace and the daemon
+    // carries on with the next one
+    panics::install_hook();
+
+    // SIGUSR1 logs a status snapshot at the start of the next cycle
+    if let Err(e) = signals::install_status_dump_handler() {
+        warn!("Failed to install SIGUSR1 handler, status dumps are unavailable: {e}");
+    }
+
+    // Let logrotate ask for the log file to be reopened after moving it
+    if config.daemon.log_file.is_some() {
+        if let Err(e) = signals::install_log_reopen_handler() {
+            warn!("Failed to install SIGUSR2 handler, log file cannot be reopened: {e}");
+        }
+    }
+
+    info!(
+        "Daemon initialized with poll interval: {}s",
+        config.daemon.poll_interval_sec
+    );
+
+    // Set up stats file if configured
+    if let Some(stats_path) = &config.daemon.stats_file_path {
+        info!("Stats will be written to: {stats_path}");
+    }
+
+    // Make sure that the poll interval is *never* zero to prevent a busy loop
+    if config.daemon.poll_interval_sec == 0 {
+        warn!("Poll interval is set to zero in config, using 1s minimum to prevent a busy loop");
+    }
+
+    // Removed again when the engine thread exits
+    let state_file = StateFile::new(&config.daemon);
+
+    // Pick up turbo decisions from a daemon that was just restarted, so a
+    // restart during a long compile does not flip turbo for a cycle
+    let turbo_state_path = PathBuf::from(&config.daemon.turbo_state_file);
+    let turbo_states = TurboStates::load(&turbo_state_path);
+
+    // Expose the control interface on the system bus. The daemon works
+    // without it, so a missing bus is only worth a single warning.
+    let state = Arc::new(DaemonState::default());
+    state.set_waker(waker.clone());
+    state.set_profile_names(&config);
+    if let Some(profile) = &options.force_profile {
+        state.set_force_mode(Some(profile.clone()), None);
+    }
+    state.restore_timed_overrides();
+
+    // What the monitor waits on between cycles. In event-driven mode, power
+    // supply and thermal changes cut the wait short.
+    let events = EventLoop::new(config.daemon.event_driven, config::config_paths())?;
+    // D-Bus and the control socket authorize requests alike
+    let authority: Arc<dyn Authority> = Arc::new(Polkit::default());
+    let dbus_service = match DbusService::start(state.clone(), authority.clone()) {
+        Ok(service) => {
+            info!("D-Bus interface available as {}", crate::dbus::BUS_NAME);
+            Some(service)
+        }
+        Err(e) => {
+            warn!("D-Bus interface unavailable, continuing without it: {e}");
+            None
+        }
+    };
+
+    // Firmware tends to reset EPP and charge thresholds on resume, so watch for
+    // it and reapply right away. The kernel's suspend counter is the fallback
+    // when logind is not around.
+    let suspend_counter = match resume::watch_logind(state.clone()) {
+        Ok(()) => None,
+        Err(e) => {
+            debug!("Cannot watch logind for resume, using suspend statistics: {e}");
+            SuspendCounter::new()
+        }
+    };
+
+    // Prometheus exporter, only when an address is configured
+    if let Some(address) = &config.daemon.metrics_address {
+        match metrics::start_server(address, state.clone()) {
+            Ok(()) => info!("Serving metrics at http://{address}/metrics"),
+            Err(e) => warn!("Failed to start metrics exporter on {address}: {e}"),
+        }
+    }
+
+    // Local control socket used by the CLI
+    let ipc_server = match ipc::Server::start(state.clone(), authority) {
+        Ok(server) => Some(server),
+        Err(e) => {
+            warn!("Control socket unavailable, continuing without it: {e}");
+            None
+        }
+    };
+
+    // Service manager notifications, when running under systemd
+    let notifier = Notifier::from_env().map(Mutex::new);
+
+    // Everything that needs root to open is set up by now. The threads below
+    // are started afterwards, so they inherit what the main thread keeps.
+    if let Some(user) = &config.daemon.run_as_user {
+        if let Err(e) = privileges::drop_privileges(&config.daemon, user, options.dry_run) {
+            error!("Not dropping privileges to user {user}, continuing as root: {e}");
+        }
+    }
+
+    // Reports flow from the monitor thread to the engine thread, so a slow
+    // sensor read does not hold up applying settings, and a hung apply does
+    // not stop reports from being collected
+    let (reports, latest_reports) = mpsc::channel();
+    let monitor_heartbeat = Heartbeat::default();
+    let engine_heartbeat = Heartbeat::default();
+
+    let spawned = thread::scope(|scope| -> io::Result<()> {
+        let shutdown = &*shutdown;
+        let engine = EngineThread {
+            state: &state,
+            turbo_states: &turbo_states,
+            turbo_state_path: &turbo_state_path,
+            state_file,
+            history: History::new(&config.daemon),
+            notifications: Notifications::new(&config.daemon),
+            apply_errors: RepeatedErrors::new(&config.daemon),
+            last_apply: None,
+            ready_notified: false,
+            dbus_service: dbus_service.as_ref(),
+            notifier: notifier.as_ref(),
+            shutdown,
+            heartbeat: &engine_heartbeat,
+            controller,
+            dry_run_writes: None,
+            process_boost: ProcessBoost::default(),
+        };
+        thread::Builder::new()
+            .name("engine".to_string())
+            .spawn_scoped(scope, move || {
+                let _stop = shutdown.on_exit();
+                engine.run(&latest_reports);
+            })?;
+
+        let monitor = MonitorThread {
+            config: Arc::new(config),
+            state: &state,
+            events,
+            waker: &waker,
+            suspend_counter,
+            notifier: notifier.as_ref(),
+            shutdown,
+            heartbeat: &monitor_heartbeat,
+        };
+        // If this fails, the sender goes with the closure and the engine
+        // thread stops on its own
+        thread::Builder::new()
+            .name("monitor".to_string())
+            .spawn_scoped(scope, move || {
+                let _stop = shutdown.on_exit();
+                monitor.run(&reports);
+            })
+            .inspect_err(|_| shutdown.request())?;
+
+        supervise(
+            shutdown,
+            notifier.as_ref(),
+            &[
+                ("monitor", &monitor_heartbeat),
+                ("engine", &engine_heartbeat),
+            ],
+        );
+        Ok(())
+    });
+    if let Err(e) = &spawned {
+        error!("Failed to start daemon threads: {e}");
+    }
+
+    if let Some(notifier) = &notifier {
+        lock(notifier).stopping();
+    }
+
+    // Saved once more so the next daemon sees fresh state even if turbo did
+    // not change for a while
+    save_turbo_states(&turbo_states, &turbo_state_path);
+
+    if let Some(snapshot) = &snapshot {
+        // Read before restoring, so the summary shows what the session did
+        let changes = options.live.then(|| snapshot.changes());
+        let restore_failures = snapshot.restore(controller);
+        Snapshot::discard();
+        if let Some(changes) = changes {
+            cli::live::print_summary(
+                started.elapsed(),
+                &state.profile_times(),
+                &changes,
+                restore_failures,
+            );
+        }
+    }
+
+    drop(ipc_server);
+    drop(pid_file);
+
+    spawned?;
+    if let Some(reason) = shutdown.failure() {
+        return Err(AppError::Generic(reason));
+    }
+    info!("Daemon stopped");
+    Ok(())
+}
+
+/// A report handed from the monitor thread to the engine thread
+struct Cycle {
+    report: SystemReport,
+    /// Configuration the report was collected with
+    config: Arc<AppConfig>,
+    /// Set when the system resumed from suspend since the previous report
+    resumed: bool,
+    /// Set when the configuration was reloaded since the previous report
+    reloaded: bool,
+    /// Interval the monitor waited before collecting the report
+    poll_interval_sec: u64,
+    /// When the monitor started collecting the report
+    started: Instant,
+}
+
+/// Collects reports at the poll cadence and owns everything that decides it:
+/// the configuration, adaptive and idle intervals, and event wakeups
+struct MonitorThread<'a> {
+    config: Arc<AppConfig>,
+    state: &'a DaemonState,
+    events: EventLoop,
+    waker: &'a Waker,
+    suspend_counter: Option<SuspendCounter>,
+    notifier: Option<&'a Mutex<Notifier>>,
+    shutdown: &'a Shutdown,
+    heartbeat: &'a Heartbeat,
+}
+
+impl MonitorThread<'_> {
+    fn run(mut self, reports: &mpsc::Sender<Cycle>) {
+        let state = self.state;
+
+        // Early at boot the cpufreq driver may still be loading, so give it a
+        // moment before the first cycle finds no CPU knobs to write
+        if self.config.daemon.cpufreq_wait_sec > 0 && cpu::cpufreq_unavailable_reason().is_some() {
+            self.wait_for_cpufreq(Duration::from_secs(self.config.daemon.cpufreq_wait_sec));
+        }
+
+        let mut config = self.config.clone();
+        let mut current_poll_interval = config.daemon.poll_interval_sec.max(1);
+        let mut system_history = SystemHistory::default();
+        let mut idle_detector = IdleDetector::default();
+        let mut adaptive_interval = AdaptiveInterval::new(current_poll_interval);
+        let mut smoother = ReportSmoother::default();
+        // Interval actually waited last time, after idle stretching
+        let mut poll_interval = current_poll_interval;
+        let mut collect_errors = RepeatedErrors::new(&config.daemon);
+        let mut consecutive_panics = 0;
+
+        while !self.shutdown.is_requested() {
+            let started = Instant::now();
+            let started_wall = SystemTime::now();
+            self.heartbeat.busy();
+
+            let mut reloaded = false;
+            if state.take_reload_request() {
+                if let Some(notifier) = self.notifier {
+                    lock(notifier).reloading();
+                }
+                match config::load_config() {
+                    Ok(new_config) => {
+                        info!("Configuration reloaded");
+                        if state.is_paused() {
+                            info!("Settings management stays paused");
+                        }
+                        state.metrics().record_config_reload();
+                        state.knobs().reset();
+                        idle_detector.reset();
+                        state.set_profile_names(&new_config);
+                        config = Arc::new(new_config);
+                        collect_errors = RepeatedErrors::new(&config.daemon);
+                        reloaded = true;
+                    }
+                    Err(e) => error!("Failed to reload configuration, keeping current one: {e}"),
+                }
+                if let Some(notifier) = self.notifier {
+                    lock(notifier).ready();
+                }
+            }
+
+            let resumed = state.take_resumed()
+                || self
+                    .suspend_counter
+                    .as_mut()
+                    .is_some_and(SuspendCounter::resumed);
+            if resumed {
+                state.metrics().record_resume();
+                // A driver may have come back differently, so try parked knobs
+                state.knobs().reset();
+            }
+
+            let collected = match panics::catch(|| monitor::collect_system_report(&config)) {
+                Ok(collected) => {
+                    consecutive_panics = 0;
+                    Some(collected)
+                }
+                Err(panic) => {
+                    handle_panic(
+                        "monitor",
+                        &panic,
+                        &mut consecutive_panics,
+                        config.daemon.max_consecutive_panics,
+                        state,
+                        self.shutdown,
+                    );
+                    None
+                }
+            };
+            match collected {
+                None => {}
+                Some(Ok(mut report)) => {
+                    debug!("Collected system report, handing it to the engine...");
+                    collect_errors.clear();
+                    smoother.smooth(&mut report, &config.daemon.smoothing);
+
+                    // Store the current state before updating history
+                    let previous_state = system_history.current_state.clone();
+
+                    // Update system history with new data
+                    system_history.update(&report);
+                    idle_detector.record(
+                        average_cpu_usage(&report),
+                        config.daemon.idle_load_threshold,
+                    );
+
+                    // If system state changed, log the new state
+                    if system_history.current_state != previous_state {
+                        info!(
+                            "System state changed to: {:?}",
+                            system_history.current_state
+                        );
+                    }
+
+                    // Update the stats file if configured
+                    if let Some(stats_path) = &config.daemon.stats_file_path {
+                        let counters = config
+                            .daemon
+                            .stats_include_counters
+                            .then(|| state.metrics().counters());
+                        let decision = config
+                            .daemon
+                            .stats_include_decisions
+                            .then(|| state.knobs().last_decision_change())
+                            .flatten();
+                        if let Err(e) = write_stats_file(
+                            stats_path,
+                            &report,
+                            counters.as_ref(),
+                            decision.as_ref(),
+                        ) {
+                            error!("Failed to write stats file: {e}");
+                        }
+                    }
+
+                    current_poll_interval =
+                        next_poll_interval(&report, &config.daemon, &mut adaptive_interval)
+                            .unwrap_or(current_poll_interval);
+
+                    state.set_last_report(report.clone());
+                    let cycle = Cycle {
+                        report,
+                        config: config.clone(),
+                        resumed,
+                        reloaded,
+                        poll_interval_sec: poll_interval,
+                        started,
+                    };
+                    if reports.send(cycle).is_err() {
+                        error!("Engine thread is gone, stopping monitoring");
+                        break;
+                    }
+                }
+                Some(Err(e)) => log_error(
+                    &mut collect_errors,
+                    state.metrics(),
+                    &format!("Error collecting system report: {e}"),
+                    None,
+                ),
+            }
+
+            self.heartbeat.idle();
+            if self.shutdown.is_requested() {
+                break;
+            }
+
+            // Wait for the remaining time in the poll interval, or for an event
+            poll_interval = if config.daemon.adaptive_interval {
+                let stretched = idle_detector.stretch(current_poll_interval, &config.daemon);
+                if stretched != current_poll_interval {
+                    debug!("System idle, stretching poll interval to {stretched}s");
+                }
+                stretched
+            } else {
+                current_poll_interval
+            };
+            // Polls land on multiples of the interval since the epoch, so they
+            // do not drift and line up with other timers on the system. A
+            // cycle that ran early for an event covers a boundary less than
+            // half an interval away.
+            let interval = Duration::from_secs(poll_interval);
+            let mut deadline = events::next_boundary(interval, started_wall + interval / 2);
+            // An override ending puts the profile's settings back right away
+            if let Some(expiry) = state.next_expiry() {
+                if expiry < deadline {
+                    debug!("Waking up early for an override that ends");
+                    deadline = expiry;
+                }
+            }
+            debug!(
+                "Sleeping until {} for the next cycle",
+                jiff::Timestamp::try_from(deadline)
+                    .map_or_else(|_| "?".to_string(), |t| t.to_string())
+            );
+            match wait_for_next_cycle(&mut self.events, self.waker, deadline) {
+                Wakeup::Device { .. } => idle_detector.reset(),
+                Wakeup::ConfigChanged => state.request_reload(),
+                Wakeup::Timer | Wakeup::Requested => {}
+            }
+        }
+    }
+
+    /// Waits up to `timeout` for a cpufreq driver to show up, or until shutdown
+    fn wait_for_cpufreq(&mut self, timeout: Duration) {
+        info!(
+            "No cpufreq driver yet, waiting up to {}s for one",
+            timeout.as_secs()
+        );
+        let deadline = Instant::now() + timeout;
+        while !self.shutdown.is_requested() {
+            if cpu::cpufreq_unavailable_reason().is_none() {
+                info!("cpufreq driver is available");
+                return;
+            }
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return;
+            }
+            let wakeup = wait_for_next_cycle(
+                &mut self.events,
+                self.waker,
+                SystemTime::now() + remaining.min(Duration::from_millis(500)),
+            );
+            if wakeup == Wakeup::ConfigChanged {
+                self.state.request_reload();
+            }
+        }
+    }
+}
+
+/// Returns the poll interval to use after `report`, or `None` to keep the
+/// current one
+fn next_poll_interval(
+    report: &SystemReport,
+    config: &DaemonConfig,
+    adaptive_interval: &mut AdaptiveInterval,
+) -> Option<u64> {
+    // Adapt the polling interval to the workload if enabled
+    if config.adaptive_interval {
+        return average_cpu_usage(report).map(|usage| adaptive_interval.update(usage, config));
+    }
+
+    // Check if we're on battery
+    let on_battery = !engine::is_on_ac_power(report);
+
+    // If adaptive polling is disabled, still apply battery-saving adjustment
+    if config.throttle_on_battery && on_battery {
+        let battery_multiplier = 2; // poll half as often on battery
+
+        // We need to make sure `poll_interval_sec` is *at least* 1
+        // before multiplying.
+        let safe_interval = config.poll_interval_sec.max(1);
+        let interval = (safe_interval * battery_multiplier).min(config.max_poll_interval_sec);
 
-                // Update last_user_activity if CPU usage indicates activity
-                // Consider significant CPU usage or sudden change as user activity
-                if avg_usage > 20.0
-                    || (self.cpu_usage_history.len() > 1
-                        && (avg_usage - self.cpu_usage_history[self.cpu_usage_history.len() - 2])
-                            .abs()
-                            > 15.0)
-                {
-                    self.last_user_activity = Instant::now();
-                    debug!("User activity detected based on CPU usage");
-                }
-            }
+        debug!("On battery power, increased poll interval to {interval}s");
+        Some(interval)
+    } else {
+        // Use the configured poll interval
+        if config.poll_interval_sec == 0 {
+            debug!("Using minimum poll interval of 1s instead of configured 0s");
         }
+        Some(config.poll_interval_sec.max(1))
+    }
+}
 
-        // Update temperature history
-        if let Some(temp) = report.cpu_global.average_temperature_celsius {
-            if self.temperature_history.len() >= 5 {
-                self.temperature_history.pop_front();
-            }
-            self.temperature_history.push_back(temp);
+/// Applies settings for the newest report the monitor thread produced
+struct EngineThread<'a> {
+    state: &'a DaemonState,
+    turbo_states: &'a TurboStates,
+    turbo_state_path: &'a Path,
+    state_file: StateFile,
+    history: History,
+    notifications: Notifications,
+    /// Keeps an apply error that repeats every cycle from flooding the log
+    apply_errors: RepeatedErrors,
+    last_apply: Option<LastApply>,
+    /// Whether systemd was told the daemon is ready
+    ready_notified: bool,
+    dbus_service: Option<&'a DbusService>,
+    notifier: Option<&'a Mutex<Notifier>>,
+    shutdown: &'a Shutdown,
+    heartbeat: &'a Heartbeat,
+    /// Applies the settings, or only records them in a dry run
+    controller: &'static dyn PowerController,
+    /// Writes the previous cycle would have made, in a dry run
+    dry_run_writes: Option<Vec<WriteRecord>>,
+    process_boost: ProcessBoost,
+}
 
-            // Significant temperature increase can indicate user activity
-            if self.temperature_history.len() > 1 {
-                let temp_change =
-                    temp - self.temperature_history[self.temperature_history.len() - 2];
-                if temp_change > 5.0 {
-                    // 5°C rise in temperature
-                    self.last_user_activity = Instant::now();
-                    debug!("User activity detected based on temperature change");
-                }
+impl EngineThread<'_> {
+    /// Runs until the monitor thread hangs up
+    fn run(mut self, reports: &mpsc::Receiver<Cycle>) {
+        let mut consecutive_panics = 0;
+
+        while let Ok(mut cycle) = reports.recv() {
+            // Only the newest report matters, older ones are stale by now
+            let mut resumed = cycle.resumed;
+            let mut reloaded = cycle.reloaded;
+            let mut skipped = 0;
+            for newer in reports.try_iter() {
+                resumed |= newer.resumed;
+                reloaded |= newer.reloaded;
+                cycle = newer;
+                skipped += 1;
             }
+            if skipped > 0 {
+                debug!("Skipped {skipped} stale reports");
+            }
+            if self.shutdown.is_requested() {
+                break;
+            }
+            self.heartbeat.busy();
+
+            match panics::catch(|| self.run_cycle(&cycle, resumed, reloaded)) {
+                Ok(()) => consecutive_panics = 0,
+                Err(panic) => handle_panic(
+                    "engine",
+                    &panic,
+                    &mut consecutive_panics,
+                    cycle.config.daemon.max_consecutive_panics,
+                    self.state,
+                    self.shutdown,
+                ),
+            }
+            self.heartbeat.idle();
         }
+    }
 
-        // Update battery discharge rate
-        if let Some(battery) = report.batteries.first() {
-            // Reset when we are charging or have just connected AC
-            if battery.ac_connected {
-                // Reset discharge tracking but continue updating the rest of
-                // the history so we still detect activity/load changes on AC.
-                self.battery_discharge_rate = None;
-                self.last_battery_percentage = None;
-                self.last_battery_timestamp = None;
-            }
-
-            if let Some(current_percentage) = battery.capacity_percent {
-                let current_percent = f32::from(current_percentage);
-
-                if let (Some(last_percentage), Some(last_timestamp)) =
-                    (self.last_battery_percentage, self.last_battery_timestamp)
-                {
-                    let elapsed_hours = last_timestamp.elapsed().as_secs_f32() / 3600.0;
-                    // Only calculate discharge rate if at least 30 seconds have passed
-                    // and we're not on AC power
-                    if elapsed_hours > 0.0083 && !battery.ac_connected {
-                        // 0.0083 hours = 30 seconds
-                        // Calculate discharge rate in percent per hour
-                        let percent_change = last_percentage - current_percent;
-                        if percent_change > 0.0 {
-                            // Only if battery is discharging
-                            let hourly_rate = percent_change / elapsed_hours;
-                            // Clamp the discharge rate to a reasonable maximum value (100%/hour)
-                            let clamped_rate = hourly_rate.min(100.0);
-                            self.battery_discharge_rate = Some(clamped_rate);
-                        }
-                    }
-                }
+    /// Applies settings for `cycle`, which got `resumed` and `reloaded` from
+    /// any stale cycles it replaced
+    fn run_cycle(&mut self, cycle: &Cycle, resumed: bool, reloaded: bool) {
+        let state = self.state;
+        let turbo_states = self.turbo_states;
 
-                self.last_battery_percentage = Some(current_percent);
-                self.last_battery_timestamp = Some(Instant::now());
-            }
+        if reloaded {
+            self.apply_errors = RepeatedErrors::new(&cycle.config.daemon);
         }
 
-        // Update system state tracking
-        let new_state = determine_system_state(report, self);
-        if new_state != self.current_state {
-            // Record time spent in previous state
-            let time_in_state = self.last_state_change.elapsed();
-            *self
-                .state_durations
-                .entry(self.current_state.clone())
-                .or_insert(Duration::ZERO) += time_in_state;
+        if signals::take_status_dump() {
+            dump::log_status(
+                state,
+                &LoopDetails {
+                    poll_interval_sec: cycle.poll_interval_sec,
+                    turbo_states,
+                    last_apply: self.last_apply.as_ref(),
+                },
+            );
+        }
 
-            // State changes (except to Idle) likely indicate user activity
-            if new_state != SystemState::Idle && new_state != SystemState::LowLoad {
-                self.last_user_activity = Instant::now();
-                debug!("User activity detected based on system state change to {new_state:?}");
+        if let Some(thresholds) = state.take_pending_thresholds() {
+            match self
+                .controller
+                .set_charge_thresholds(thresholds.start, thresholds.stop)
+            {
+                Ok(()) => info!(
+                    "Battery charge thresholds set to {}-{}%",
+                    thresholds.start, thresholds.stop
+                ),
+                Err(e) => error!("Failed to set battery charge thresholds: {e}"),
             }
-
-            // Update state
-            self.current_state = new_state;
-            self.last_state_change = Instant::now();
         }
 
-        // Check for significant load changes
-        if report.system_load.load_avg_1min > 1.0 {
-            self.last_user_activity = Instant::now();
-            debug!("User activity detected based on system load");
+        if resumed {
+            // Turbo state from before the suspend is stale, start over
+            // from the configured initial state
+            turbo_states.reset();
         }
-    }
 
-    /// Calculate CPU usage volatility (how much it's changing)
-    fn get_cpu_volatility(&self) -> f32 {
-        if self.cpu_usage_history.len() < 2 {
-            return 0.0;
+        let mut reset = state.take_pending_reset();
+        let mut restored = None;
+        if let Some(reset) = &mut reset {
+            turbo_states.reset();
+            state.knobs().reset();
+            reset.cleared.push("turbo hysteresis".to_string());
+            reset
+                .cleared
+                .push("parked and drifted knob state".to_string());
+            if reset.hard {
+                restored = Snapshot::load().map(|snapshot| {
+                    let changed = snapshot.changes().len();
+                    let failed = snapshot.restore(self.controller);
+                    (changed.saturating_sub(failed), failed)
+                });
+            }
         }
-
-        let mut sum_of_changes = 0.0;
-        for i in 1..self.cpu_usage_history.len() {
-            sum_of_changes += (self.cpu_usage_history[i] - self.cpu_usage_history[i - 1]).abs();
+        // A dry run records every cycle already
+        let recording = sysfs::is_recording_writes();
+        if reset.is_some() && !recording {
+            sysfs::record_writes();
         }
 
-        sum_of_changes / (self.cpu_usage_history.len() - 1) as f32
-    }
+        // A driver loaded later is picked up on the next cycle
+        state
+            .knobs()
+            .set_cpu_control_unavailable(cpu::cpufreq_unavailable_reason());
+        self.state_file.reconfigure(&cycle.config.daemon);
+        self.history.reconfigure(&cycle.config.daemon);
+        self.notifications.reconfigure(&cycle.config.daemon);
+
+        let report = &cycle.report;
+        let on_ac = engine::is_on_ac_power(report);
+        state.track_power_source(on_ac);
+        let boost = self
+            .process_boost
+            .update(cycle.config.process_boost.as_ref())
+            .cloned();
+        // A profile forced by hand beats the process boost, which beats the
+        // load profile, which beats clamshell mode
+        let load_profile = engine::load_profile(report, &cycle.config)
+            .filter(|_| state.force_mode().is_none() && boost.is_none());
+        let clamshell = engine::clamshell_profile(report, &cycle.config)
+            .filter(|_| state.force_mode().is_none() && boost.is_none() && load_profile.is_none());
+        let force_mode = state
+            .force_mode()
+            .or_else(|| {
+                boost
+                    .is_some()
+                    .then_some(ForcedProfile::Mode(OperationalMode::Performance))
+            })
+            .or_else(|| load_profile.clone())
+            .or_else(|| clamshell.clone());
+        let boost_process = boost
+            .as_ref()
+            .filter(|_| state.force_mode().is_none())
+            .map(|boost| boost.process.clone());
+        state.set_process_boost(boost);
+        let effective_config = state.effective_config(&cycle.config, on_ac);
+
+        // Read once, so a pause ending mid-cycle does not count for half of it
+        let pause = state.pause_state();
+        let apply_result = if pause.is_some() {
+            debug!("Settings management is paused, skipping apply");
+            Ok(())
+        } else {
+            state.metrics().record_apply_cycle();
+            let result = engine::determine_and_apply_settings(
+                report,
+                &effective_config,
+                force_mode.as_ref(),
+                turbo_states,
+                state.knobs(),
+                self.controller,
+            );
+            state.note_overrides(boost_process.as_deref());
+            if let (Some(profile), Some(settings)) = (&load_profile, &cycle.config.load_profile) {
+                state.knobs().note_override(format!(
+                    "Load profile: {} {:.2} above {:.2}{}, {profile} profile",
+                    settings.average.as_str(),
+                    engine::load_average(report, settings).unwrap_or_default(),
+                    settings.above,
+                    if settings.normalize { " per CPU" } else { "" }
+                ));
+            }
+            if let Some(profile) = &clamshell {
+                state.knobs().note_override(format!(
+                    "Clamshell mode: lid closed with {} connected, {profile} profile",
+                    report.lid.external_displays.join(", ")
+                ));
+            }
+            let timestamp = jiff::Timestamp::now();
+            if result.is_ok() {
+                state.record_successful_apply(timestamp);
+            }
+            let summary = state.knobs().last_summary();
+            state.metrics().record_apply_summary(&summary);
+            if summary.changed() > 0 || summary.failed() > 0 {
+                info!(
+                    event = "apply_summary",
+                    profile = summary.profile.as_str(),
+                    changed = summary.changed(),
+                    failed = summary.failed();
+                    "Applied {} profile: {summary}",
+                    summary.profile
+                );
+            } else {
+                debug!("Applied {} profile: {summary}", summary.profile);
+            }
+            self.last_apply = Some(LastApply {
+                timestamp,
+                error: result.as_ref().err().map(ToString::to_string),
+            });
+            if turbo_states.take_changed() {
+                save_turbo_states(turbo_states, self.turbo_state_path);
+            }
+            result
+        };
 
-    /// Calculate temperature volatility
-    fn get_temperature_volatility(&self) -> f32 {
-        if self.temperature_history.len() < 2 {
-            return 0.0;
+        state.set_turbo_hysteresis(self.turbo_states);
+        state.set_frequency_ramp(turbo_states.frequency_ramp());
+        if let Some(temp) = turbo_states.take_thermal_safety_engagement() {
+            state.metrics().record_thermal_safety_engagement();
+            self.notifications.send(
+                notifications::Kind::Thermal,
+                "CPU critically hot",
+                &format!(
+                    "The CPU reached {temp:.1}°C, so it is held at its slowest settings until it cools down"
+                ),
+            );
         }
-
-        let mut sum_of_changes = 0.0;
-        for i in 1..self.temperature_history.len() {
-            sum_of_changes += (self.temperature_history[i] - self.temperature_history[i - 1]).abs();
+        if let Some((enabled, reason)) = turbo_states.take_last_change() {
+            if !enabled && reason == engine::TurboReason::HighTemperature.as_str() {
+                let temp = report
+                    .cpu_global
+                    .average_temperature_celsius
+                    .map_or_else(String::new, |temp| format!(" at {temp:.1}°C"));
+                self.notifications.send(
+                    notifications::Kind::Thermal,
+                    "Turbo disabled, CPU too hot",
+                    &format!(
+                        "The CPU is running hot{temp}, so turbo stays off until it cools down"
+                    ),
+                );
+            }
+            self.history.record(history::Event::Turbo {
+                enabled,
+                reason: reason.to_string(),
+            });
+        }
+        let writes = if recording {
+            sysfs::take_recorded_writes()
+        } else if reset.is_some() {
+            sysfs::stop_recording_writes()
+        } else {
+            Vec::new()
+        };
+        if self.controller.is_dry_run() {
+            self.log_dry_run_writes(&writes);
+        }
+        if let Some(reset) = reset {
+            // The caller may have given up waiting
+            let _ = reset.reply.send(ResetReport {
+                cleared: reset.cleared,
+                restored,
+                profile: engine::selected_profile_name(report, force_mode.as_ref()).to_string(),
+                writes,
+                error: apply_result.as_ref().err().map(ToString::to_string),
+            });
         }
 
-        sum_of_changes / (self.temperature_history.len() - 1) as f32
-    }
+        match apply_result {
+            Ok(()) => {
+                debug!("Successfully applied system settings");
+                self.apply_errors.clear();
+                self.history.flush_errors();
 
-    /// Determine if the system appears to be idle
-    fn is_system_idle(&self) -> bool {
-        if self.cpu_usage_history.is_empty() {
-            return false;
+                if resumed && pause.is_none() {
+                    info!("Re-applied settings after resume");
+                }
+
+                if !self.ready_notified {
+                    if let Some(notifier) = self.notifier {
+                        lock(notifier).ready();
+                    }
+                    self.ready_notified = true;
+                }
+            }
+            Err(e) => {
+                state.metrics().record_apply_error();
+                let message = format!("Error applying system settings: {e}");
+                self.history.error(&message);
+                log_error(
+                    &mut self.apply_errors,
+                    state.metrics(),
+                    &message,
+                    e.context(),
+                );
+            }
         }
 
-        // System considered idle if the average CPU usage of last readings is below 10%
-        let recent_avg =
-            self.cpu_usage_history.iter().sum::<f32>() / self.cpu_usage_history.len() as f32;
-        recent_avg < 10.0 && self.get_cpu_volatility() < 5.0
-    }
+        let profile_name = engine::selected_profile_name(report, force_mode.as_ref());
+        let previous_profile = state.active_profile();
+        if state.update_active_profile(profile_name) {
+            info!("Active profile is now '{profile_name}'");
+            let trigger = if previous_profile.is_some() {
+                engine::selected_profile_reason(report, force_mode.as_ref())
+            } else {
+                "startup"
+            };
+            // The first profile after startup is no news
+            if let Some(previous) = &previous_profile {
+                self.notifications.send(
+                    notifications::Kind::ProfileSwitch,
+                    &format!("Switched to the {profile_name} profile"),
+                    &format!(
+                        "From {previous} to {profile_name}: {}",
+                        history::describe_trigger(trigger)
+                    ),
+                );
+            }
+            self.history.record(history::Event::ProfileSwitch {
+                trigger: trigger.to_string(),
+                from: previous_profile,
+                to: profile_name.to_string(),
+            });
+            if let Some(service) = self.dbus_service {
+                service.emit_profile_changed(profile_name);
+            }
+        }
+        if let Some(notifier) = self.notifier {
+            lock(notifier).status(&format!(
+                "Active profile: {profile_name}{}",
+                if pause.is_some() { " (paused)" } else { "" }
+            ));
+        }
+        self.history.sample(report, profile_name, on_ac);
+        self.state_file.write(&State::new(
+            report,
+            profile_name,
+            pause.as_ref(),
+            state.last_successful_apply(),
+        ));
 
-    /// Calculate optimal polling interval based on system conditions
-    fn calculate_optimal_interval(
-        &self,
-        config: &AppConfig,
-        on_battery: bool,
-    ) -> Result<u64, ControlError> {
-        let params = IntervalParams {
-            base_interval: config.daemon.poll_interval_sec,
-            min_interval: config.daemon.min_poll_interval_sec,
-            max_interval: config.daemon.max_poll_interval_sec,
-            cpu_volatility: self.get_cpu_volatility(),
-            temp_volatility: self.get_temperature_volatility(),
-            battery_discharge_rate: self.battery_discharge_rate,
-            last_user_activity: self.last_user_activity.elapsed(),
-            is_system_idle: self.is_system_idle(),
-            on_battery,
-        };
+        state
+            .metrics()
+            .record_cycle_duration(cycle.started.elapsed());
+        state.record_cycle();
+    }
 
-        compute_new(&params, self)
+    /// Logs the writes this cycle would have made as a table, or only their
+    /// count when they are the same as the previous cycle's
+    fn log_dry_run_writes(&mut self, writes: &[WriteRecord]) {
+        let writes: Vec<WriteRecord> = writes
+            .iter()
+            .filter(|write| write.outcome != WriteOutcome::Unchanged)
+            .cloned()
+            .collect();
+
+        if self.dry_run_writes.as_ref() == Some(&writes) {
+            if writes.is_empty() {
+                debug!("Dry run: nothing to write this cycle");
+            } else {
+                info!(
+                    "Dry run: the same {} writes as the previous cycle",
+                    writes.len()
+                );
+            }
+        } else if writes.is_empty() {
+            info!("Dry run: nothing to write this cycle");
+        } else {
+            info!("Dry run: this cycle would write");
+            for line in cli::write_table(&writes) {
+                info!("{line}");
+            }
+        }
+        self.dry_run_writes = Some(writes);
     }
 }
 
-/// Validates that poll interval configuration is consistent
-/// Returns Ok if configuration is valid, Err with a descriptive message if invalid
-fn validate_poll_intervals(min_interval: u64, max_interval: u64) -> Result<(), ControlError> {
-    if min_interval < 1 {
-        return Err(ControlError::InvalidValueError(
-            "min_interval must be ≥ 1".to_string(),
-        ));
+/// Logs a panic caught in a cycle of the `thread` thread and counts it. After
+/// `limit` panicking cycles in a row, the daemon is stopped with an error so
+/// the service manager can restart it. A `limit` of 0 never stops it.
+fn handle_panic(
+    thread: &str,
+    panic: &str,
+    consecutive: &mut u32,
+    limit: u32,
+    state: &DaemonState,
+    shutdown: &Shutdown,
+) {
+    *consecutive += 1;
+    state.metrics().record_panic();
+    // One record per line, so line-based log collectors keep the backtrace
+    // readable
+    let message = format!("The {thread} thread panicked, skipping this cycle: {panic}");
+    for line in message.lines() {
+        error!("{line}");
     }
-    if max_interval < 1 {
-        return Err(ControlError::InvalidValueError(
-            "max_interval must be ≥ 1".to_string(),
+
+    if limit > 0 && *consecutive >= limit {
+        shutdown.fail(format!(
+            "The {thread} thread panicked in {consecutive} cycles in a row"
         ));
     }
-    if max_interval >= min_interval {
-        Ok(())
-    } else {
-        Err(ControlError::InvalidValueError(format!(
-            "Invalid interval configuration: max_interval ({max_interval}) is less than min_interval ({min_interval})"
-        )))
-    }
 }
 
-/// Run the daemon
-pub fn run_daemon(config: AppConfig, verbose: bool) -> Result<(), AppError> {
-    // Set effective log level based on config and verbose flag
-    let effective_log_level = if verbose {
-        LogLevel::Debug
-    } else {
-        config.daemon.log_level
-    };
-
-    // Get the appropriate level filter
-    let level_filter = match effective_log_level {
-        LogLevel::Error => LevelFilter::Error,
-        LogLevel::Warning => LevelFilter::Warn,
-        LogLevel::Info => LevelFilter::Info,
-        LogLevel::Debug => LevelFilter::Debug,
+/// Logs `message` as an error, unless it is a repeat that `errors` holds back
+fn log_error(
+    errors: &mut RepeatedErrors,
+    metrics: &Metrics,
+    message: &str,
+    context: Option<&ErrorContext>,
+) {
+    let message = match errors.record(message) {
+        Repeat::Log => message.to_string(),
+        Repeat::Summary { suppressed, over } => {
+            let over = if over.as_secs() >= 120 {
+                format!("{} minutes", over.as_secs() / 60)
+            } else {
+                format!("{} seconds", over.as_secs())
+            };
+            format!("{message} (occurred {suppressed} more times in the last {over})")
+        }
+        Repeat::Suppress => {
+            debug!("{message}");
+            metrics.record_suppressed_error();
+            return;
+        }
     };
+    // Also as fields of their own, for the JSON log format
+    match context {
+        Some(context) => error!(
+            knob = context.knob.as_deref(),
+            cpu = context.cpu,
+            path = context.path.as_ref().map(|path| path.display().to_string()),
+            value = context.value.as_deref(),
+            current = context.current.as_deref();
+            "{message}"
+        ),
+        None => error!("{message}"),
+    }
+}
 
-    // Update the log level filter if needed, without re-initializing the logger
-    log::set_max_level(level_filter);
+/// Shutdown request shared by the signal handler and the daemon's threads
+struct Shutdown {
+    requested: Mutex<bool>,
+    /// Why the daemon stopped, if it was because of an error
+    failure: Mutex<Option<String>>,
+    condvar: Condvar,
+    /// Woken so the monitor thread does not sleep out its poll interval
+    waker: Arc<Waker>,
+}
 
-    info!("Starting Watt daemon...");
+impl Shutdown {
+    fn new(waker: Arc<Waker>) -> Self {
+        Self {
+            requested: Mutex::new(false),
+            failure: Mutex::new(None),
+            condvar: Condvar::new(),
+            waker,
+        }
+    }
 
-    // Validate critical configuration values before proceeding
-    if let Err(err) = validate_poll_intervals(
-        config.daemon.min_poll_interval_sec,
-        config.daemon.max_poll_interval_sec,
-    ) {
-        return Err(AppError::Control(err));
+    fn request(&self) {
+        *lock(&self.requested) = true;
+        self.condvar.notify_all();
+        self.waker.wake();
     }
 
-    // Create a flag that will be set to true when a signal is received
-    let running = Arc::new(AtomicBool::new(true));
-    let r = running.clone();
+    /// Requests shutdown because of an error, which the daemon exits with
+    fn fail(&self, reason: String) {
+        error!("{reason}, stopping");
+        *lock(&self.failure) = Some(reason);
+        self.request();
+    }
 
-    // Set up signal handlers
-    ctrlc::set_handler(move || {
-        info!("Received shutdown signal, exiting...");
-        r.store(false, Ordering::SeqCst);
-    })
-    .map_err(|e| AppError::Generic(format!("Error setting Ctrl-C handler: {e}")))?;
+    fn failure(&self) -> Option<String> {
+        lock(&self.failure).clone()
+    }
 
-    info!(
-        "Daemon initialized with poll interval: {}s",
-        config.daemon.poll_interval_sec
-    );
+    fn is_requested(&self) -> bool {
+        *lock(&self.requested)
+    }
 
-    // Set up stats file if configured
-    if let Some(stats_path) = &config.daemon.stats_file_path {
-        info!("Stats will be written to: {stats_path}");
+    /// Waits until shutdown is requested or `timeout` passes, returning
+    /// whether it was requested. Without a timeout, waits for the request.
+    fn wait(&self, timeout: Option<Duration>) -> bool {
+        let requested = lock(&self.requested);
+        let requested = match timeout {
+            Some(timeout) => {
+                self.condvar
+                    .wait_timeout_while(requested, timeout, |requested| !*requested)
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .0
+            }
+            None => self
+                .condvar
+                .wait_while(requested, |requested| !*requested)
+                .unwrap_or_else(PoisonError::into_inner),
+        };
+        *requested
     }
 
-    // Variables for adaptive polling
-    // Make sure that the poll interval is *never* zero to prevent a busy loop
-    let mut current_poll_interval = config.daemon.poll_interval_sec.max(1);
-    if config.daemon.poll_interval_sec == 0 {
-        warn!("Poll interval is set to zero in config, using 1s minimum to prevent a busy loop");
+    /// Requests shutdown when the returned guard is dropped, so a thread that
+    /// stops for any reason, including a panic, takes the daemon down with it
+    fn on_exit(&self) -> impl Drop + '_ {
+        struct Guard<'a>(&'a Shutdown);
+        impl Drop for Guard<'_> {
+            fn drop(&mut self) {
+                self.0.request();
+            }
+        }
+        Guard(self)
     }
-    let mut system_history = SystemHistory::default();
+}
 
-    // Main loop
-    while running.load(Ordering::SeqCst) {
-        let start_time = Instant::now();
+/// How long a thread has been working on its current cycle, so the watchdog
+/// is only fed while no thread is stuck
+#[derive(Default)]
+struct Heartbeat {
+    busy_since: Mutex<Option<Instant>>,
+}
 
-        match monitor::collect_system_report(&config) {
-            Ok(report) => {
-                debug!("Collected system report, applying settings...");
+impl Heartbeat {
+    fn busy(&self) {
+        *lock(&self.busy_since) = Some(Instant::now());
+    }
 
-                // Store the current state before updating history
-                let previous_state = system_history.current_state.clone();
+    fn idle(&self) {
+        *lock(&self.busy_since) = None;
+    }
 
-                // Update system history with new data
-                system_history.update(&report);
+    fn busy_for(&self) -> Duration {
+        lock(&self.busy_since).map_or(Duration::ZERO, |since| since.elapsed())
+    }
+}
 
-                // Update the stats file if configured
-                if let Some(stats_path) = &config.daemon.stats_file_path {
-                    if let Err(e) = write_stats_file(stats_path, &report) {
-                        error!("Failed to write stats file: {e}");
-                    }
+/// Feeds the systemd watchdog until shutdown is requested. A thread stuck on
+/// a single cycle for the whole watchdog timeout stops the pings, which lets
+/// systemd restart the daemon.
+fn supervise(
+    shutdown: &Shutdown,
+    notifier: Option<&Mutex<Notifier>>,
+    threads: &[(&str, &Heartbeat)],
+) {
+    let watchdog_interval = notifier.and_then(|n| lock(n).watchdog_interval());
+    // Pings are due every half timeout, see `Notifier`
+    let timeout = watchdog_interval.map(|interval| interval * 2);
+    let mut stuck = false;
+
+    while !shutdown.wait(watchdog_interval) {
+        let (Some(notifier), Some(timeout)) = (notifier, timeout) else {
+            continue;
+        };
+        let hung = threads
+            .iter()
+            .find(|(_, heartbeat)| heartbeat.busy_for() >= timeout);
+        match hung {
+            Some((name, heartbeat)) => {
+                if !stuck {
+                    error!(
+                        "The {name} thread has been busy for {}s, no longer feeding the watchdog",
+                        heartbeat.busy_for().as_secs()
+                    );
                 }
+                stuck = true;
+            }
+            None => {
+                stuck = false;
+                lock(notifier).ping_watchdog();
+            }
+        }
+    }
+}
 
-                match engine::determine_and_apply_settings(&report, &config, None) {
-                    Ok(()) => {
-                        debug!("Successfully applied system settings");
+/// Locks `mutex`, ignoring poisoning as every value guarded here is valid at
+/// all times
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
 
-                        // If system state changed, log the new state
-                        if system_history.current_state != previous_state {
-                            info!(
-                                "System state changed to: {:?}",
-                                system_history.current_state
-                            );
-                        }
-                    }
-                    Err(e) => {
-                        error!("Error applying system settings: {e}");
-                    }
-                }
+fn save_turbo_states(turbo_states: &TurboStates, path: &Path) {
+    if let Err(e) = turbo_states.save(path) {
+        warn!("Failed to save turbo state to {}: {e}", path.display());
+    }
+}
 
-                // Check if we're on battery
-                let on_battery = !report.batteries.is_empty()
-                    && report.batteries.first().is_some_and(|b| !b.ac_connected);
-
-                // Calculate optimal polling interval if adaptive polling is enabled
-                if config.daemon.adaptive_interval {
-                    match system_history.calculate_optimal_interval(&config, on_battery) {
-                        Ok(optimal_interval) => {
-                            // Store the new interval
-                            system_history.last_computed_interval = Some(optimal_interval);
-
-                            debug!("Recalculated optimal interval: {optimal_interval}s");
-
-                            // Don't change the interval too dramatically at once
-                            match optimal_interval.cmp(&current_poll_interval) {
-                                std::cmp::Ordering::Greater => {
-                                    current_poll_interval =
-                                        (current_poll_interval + optimal_interval) / 2;
-                                }
-                                std::cmp::Ordering::Less => {
-                                    current_poll_interval = current_poll_interval
-                                        - ((current_poll_interval - optimal_interval) / 2).max(1);
-                                }
-                                std::cmp::Ordering::Equal => {
-                                    // No change needed when they're equal
-                                }
-                            }
-                        }
-                        Err(e) => {
-                            // Log the error and stop the daemon when an invalid configuration is detected
-                            error!("Critical configuration error: {e}");
-                            running.store(false, Ordering::SeqCst);
-                            break;
-                        }
-                    }
+/// Wait until the next cycle is due: the wall clock reached `deadline`, a
+/// watched device or configuration file changed, or another thread woke the
+/// loop.
+///
+/// Returns what ended the wait.
+fn wait_for_next_cycle(events: &mut EventLoop, waker: &Waker, deadline: SystemTime) -> Wakeup {
+    let sleep_until_deadline = || {
+        thread::sleep(
+            deadline
+                .duration_since(SystemTime::now())
+                .unwrap_or_default(),
+        );
+        Wakeup::Timer
+    };
 
-                    // Make sure that we respect the (user) configured min and max limits
-                    current_poll_interval = current_poll_interval.clamp(
-                        config.daemon.min_poll_interval_sec,
-                        config.daemon.max_poll_interval_sec,
-                    );
+    if let Err(e) = events.arm_timer(deadline) {
+        error!("Failed to arm poll timer, sleeping instead: {e}");
+        return sleep_until_deadline();
+    }
 
-                    debug!("Adaptive polling: set interval to {current_poll_interval}s");
-                } else {
-                    // If adaptive polling is disabled, still apply battery-saving adjustment
-                    if config.daemon.throttle_on_battery && on_battery {
-                        let battery_multiplier = 2; // poll half as often on battery
-
-                        // We need to make sure `poll_interval_sec` is *at least* 1
-                        // before multiplying.
-                        let safe_interval = config.daemon.poll_interval_sec.max(1);
-                        current_poll_interval = (safe_interval * battery_multiplier)
-                            .min(config.daemon.max_poll_interval_sec);
-
-                        debug!(
-                            "On battery power, increased poll interval to {current_poll_interval}s"
-                        );
-                    } else {
-                        // Use the configured poll interval
-                        current_poll_interval = config.daemon.poll_interval_sec.max(1);
-                        if config.daemon.poll_interval_sec == 0 {
-                            debug!("Using minimum poll interval of 1s instead of configured 0s");
-                        }
-                    }
+    loop {
+        match events.wait(waker, None) {
+            Ok(Some(wakeup)) => {
+                if let Wakeup::Device { subsystem, action } = &wakeup {
+                    debug!("Running cycle early after {subsystem} {action} event");
                 }
+                return wakeup;
             }
+            // Interrupted by a signal, the timer is still armed
+            Ok(None) => {}
             Err(e) => {
-                error!("Error collecting system report: {e}");
+                error!("Failed to wait for events, sleeping instead: {e}");
+                return sleep_until_deadline();
             }
         }
-
-        // Sleep for the remaining time in the poll interval
-        let elapsed = start_time.elapsed();
-        let poll_duration = Duration::from_secs(current_poll_interval);
-        if elapsed < poll_duration {
-            let sleep_time = poll_duration - elapsed;
-            debug!("Sleeping for {}s until next cycle", sleep_time.as_secs());
-            std::thread::sleep(sleep_time);
-        }
     }
-
-    info!("Daemon stopped");
-    Ok(())
 }
 
 /// Write current system stats to a file for --stats to read
-fn write_stats_file(path: &str, report: &SystemReport) -> Result<(), std::io::Error> {
-    let mut file = File::create(path)?;
+///
+/// The file only ever holds the latest report. It is written next to its
+/// final location and renamed into place, so readers never see a partial
+/// file, and its directory is recreated if something cleaned it up.
+fn write_stats_file(
+    path: &str,
+    report: &SystemReport,
+    counters: Option<&Counters>,
+    decision: Option<&DecisionTrace>,
+) -> Result<(), std::io::Error> {
+    let path = Path::new(path);
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    let mut tmp_path = path.as_os_str().to_owned();
+    tmp_path.push(".tmp");
+    let tmp_path = Path::new(&tmp_path);
+
+    let mut file = File::create(tmp_path)?;
+    write_stats(&mut file, report)?;
+    if let Some(counters) = counters {
+        write_counters(&mut file, counters)?;
+    }
+    if let Some(decision) = decision {
+        write_decision(&mut file, decision)?;
+    }
+    std::fs::rename(tmp_path, path)
+}
 
+fn write_stats(file: &mut File, report: &SystemReport) -> Result<(), std::io::Error> {
     writeln!(file, "timestamp={:?}", report.timestamp)?;
 
     // CPU info
@@ -595,24 +2601,101 @@ fn write_stats_file(path: &str, report: &SystemReport) -> Result<(), std::io::Er
     if let Some(temp) = report.cpu_global.average_temperature_celsius {
         writeln!(file, "cpu_temp={temp:.1}")?;
     }
-
-    // Battery info
-    if !report.batteries.is_empty() {
-        let battery = &report.batteries[0];
-        writeln!(file, "ac_power={}", battery.ac_connected)?;
-        if let Some(cap) = battery.capacity_percent {
-            writeln!(file, "battery_percent={cap}")?;
+    if let Some(freq) = report.cpu_global.average_frequency_mhz {
+        writeln!(file, "avg_freq_mhz={freq}")?;
+    }
+    if let Some(freq) = report.cpu_global.highest_frequency_mhz {
+        writeln!(file, "max_freq_mhz={freq}")?;
+    }
+    if let Some(events) = report.throttling.core_events {
+        writeln!(file, "throttle_core_events={events}")?;
+    }
+    if let Some(events) = report.throttling.package_events {
+        writeln!(file, "throttle_package_events={events}")?;
+    }
+    for (domain, watts) in [
+        ("package", report.cpu_power.package_watts),
+        ("cores", report.cpu_power.cores_watts),
+        ("uncore", report.cpu_power.uncore_watts),
+    ] {
+        if let Some(watts) = watts {
+            writeln!(file, "cpu_power_{domain}_w={watts:.2}")?;
         }
     }
 
+    // Power source and battery
+    writeln!(file, "ac_power={}", report.ac_power.online)?;
+    if let Some(cap) = report.batteries.first().and_then(|b| b.capacity_percent) {
+        writeln!(file, "battery_percent={cap}")?;
+    }
+
     // System load
     writeln!(file, "load_1m={:.2}", report.system_load.load_avg_1min)?;
     writeln!(file, "load_5m={:.2}", report.system_load.load_avg_5min)?;
     writeln!(file, "load_15m={:.2}", report.system_load.load_avg_15min)?;
+    if let Some(pressure) = report.system_load.cpu_pressure {
+        writeln!(file, "cpu_pressure_10s={:.2}", pressure.some_avg10)?;
+        writeln!(file, "cpu_pressure_60s={:.2}", pressure.some_avg60)?;
+    }
 
     Ok(())
 }
 
+/// Appends the last decision that changed the profile or turbo, and why
+fn write_decision(file: &mut File, decision: &DecisionTrace) -> Result<(), std::io::Error> {
+    writeln!(file, "decision_changed_at={}", decision.timestamp)?;
+    writeln!(file, "decision_profile={}", decision.profile)?;
+    writeln!(file, "decision_profile_reason={}", decision.profile_reason)?;
+    for turbo in &decision.turbo {
+        writeln!(
+            file,
+            "decision_turbo_{}={}",
+            turbo.group,
+            turbo
+                .enabled
+                .map_or_else(|| "system".to_string(), |enabled| enabled.to_string())
+        )?;
+        writeln!(
+            file,
+            "decision_turbo_{}_reason={}",
+            turbo.group, turbo.reason
+        )?;
+    }
+    if !decision.overrides.is_empty() {
+        writeln!(file, "decision_overrides={}", decision.overrides.join("; "))?;
+    }
+    Ok(())
+}
+
+/// Appends the operational counters to the stats file, for long-term trending
+fn write_counters(file: &mut File, counters: &Counters) -> Result<(), std::io::Error> {
+    writeln!(file, "started_at={}", counters.started_at)?;
+    writeln!(file, "apply_cycles={}", counters.apply_cycles)?;
+    writeln!(file, "apply_errors={}", counters.apply_errors)?;
+    writeln!(file, "errors_suppressed={}", counters.errors_suppressed)?;
+    writeln!(file, "panics={}", counters.panics)?;
+    writeln!(file, "sysfs_writes={}", counters.sysfs_writes)?;
+    writeln!(
+        file,
+        "sysfs_writes_skipped={}",
+        counters.sysfs_writes_skipped
+    )?;
+    for (knob, count) in &counters.sysfs_write_errors {
+        writeln!(file, "sysfs_write_errors.{knob}={count}")?;
+    }
+    writeln!(file, "config_reloads={}", counters.config_reloads)?;
+    writeln!(file, "resumes={}", counters.resumes)?;
+    writeln!(file, "profile_switches={}", counters.profile_switches)?;
+    writeln!(file, "knob_changes={}", counters.knob_changes)?;
+    writeln!(file, "knob_failures={}", counters.knob_failures)?;
+    writeln!(
+        file,
+        "thermal_safety_engagements={}",
+        counters.thermal_safety_engagements
+    )?;
+    Ok(())
+}
+
 /// Simplified system state used for determining when to adjust polling interval
 #[derive(Debug, PartialEq, Eq, Clone, Hash, Default)]
 enum SystemState {
@@ -629,19 +2712,13 @@ enum SystemState {
 /// Determine the current system state for adaptive polling
 fn determine_system_state(report: &SystemReport, history: &SystemHistory) -> SystemState {
     // Check power state first
-    if !report.batteries.is_empty() {
-        if let Some(battery) = report.batteries.first() {
-            if battery.ac_connected {
-                return SystemState::OnAC;
-            }
-            return SystemState::OnBattery;
-        }
-    }
-
-    // No batteries means desktop, so always AC
-    if report.batteries.is_empty() {
+    if engine::is_on_ac_power(report) {
         return SystemState::OnAC;
     }
+    // Off AC without a battery to run on, e.g. behind a UPS, the load decides
+    if !report.batteries.is_empty() {
+        return SystemState::OnBattery;
+    }
 
     // Check temperature
     if let Some(temp) = report.cpu_global.average_temperature_celsius {
@@ -669,3 +2746,179 @@ fn determine_system_state(report: &SystemReport, history: &SystemHistory) -> Sys
     // Default case
     SystemState::Unknown
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The interval after each cycle of CPU usage in `loads`, with `None` for
+    /// a cycle without a reading
+    fn idle_trajectory(
+        detector: &mut IdleDetector,
+        config: &DaemonConfig,
+        loads: &[Option<f32>],
+    ) -> Vec<u64> {
+        loads
+            .iter()
+            .map(|load| {
+                detector.record(*load, config.idle_load_threshold);
+                detector.stretch(config.poll_interval_sec, config)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn idle_stretches_the_interval_up_to_the_maximum() {
+        let config = DaemonConfig::default();
+        let mut detector = IdleDetector::default();
+        let idle = [Some(2.0); 7];
+        assert_eq!(
+            idle_trajectory(&mut detector, &config, &idle),
+            [5, 5, 10, 20, 30, 30, 30]
+        );
+    }
+
+    #[test]
+    fn load_spikes_snap_the_interval_back() {
+        let config = DaemonConfig::default();
+        let mut detector = IdleDetector::default();
+        let loads = [
+            Some(2.0),
+            Some(2.0),
+            Some(2.0),
+            Some(2.0),
+            // No reading keeps the streak going
+            None,
+            Some(9.9),
+            Some(10.0),
+            Some(2.0),
+            Some(2.0),
+            Some(2.0),
+        ];
+        assert_eq!(
+            idle_trajectory(&mut detector, &config, &loads),
+            [5, 5, 10, 20, 20, 30, 5, 5, 5, 10]
+        );
+    }
+
+    #[test]
+    fn power_events_snap_the_interval_back() {
+        let config = DaemonConfig {
+            idle_cycles: 1,
+            ..DaemonConfig::default()
+        };
+        let mut detector = IdleDetector::default();
+        assert_eq!(
+            idle_trajectory(&mut detector, &config, &[Some(0.0); 3]),
+            [10, 20, 30]
+        );
+
+        detector.reset();
+        assert_eq!(detector.stretch(config.poll_interval_sec, &config), 5);
+        assert_eq!(idle_trajectory(&mut detector, &config, &[Some(0.0)]), [10]);
+    }
+
+    #[test]
+    fn idle_never_shortens_the_interval() {
+        let config = DaemonConfig {
+            max_poll_interval_sec: 30,
+            ..DaemonConfig::default()
+        };
+        let mut detector = IdleDetector::default();
+        idle_trajectory(&mut detector, &config, &[Some(0.0); 10]);
+        // An interval above the maximum is left alone, not cut down to it
+        assert_eq!(detector.stretch(60, &config), 60);
+    }
+
+    /// The interval after each sample of CPU usage in `loads`
+    fn adaptive_trajectory(
+        adaptive: &mut AdaptiveInterval,
+        config: &DaemonConfig,
+        loads: impl IntoIterator<Item = f32>,
+    ) -> Vec<u64> {
+        loads
+            .into_iter()
+            .map(|load| adaptive.update(load, config))
+            .collect()
+    }
+
+    #[test]
+    fn stable_load_lengthens_the_interval_to_the_maximum() {
+        let config = DaemonConfig::default();
+        let mut adaptive = AdaptiveInterval::new(config.poll_interval_sec);
+        let trajectory = adaptive_trajectory(&mut adaptive, &config, [20.0; 40]);
+
+        assert_eq!(trajectory[..4], [6, 7, 8, 9]);
+        assert!(trajectory.windows(2).all(|pair| pair[0] <= pair[1]));
+        assert_eq!(trajectory.last(), Some(&config.max_poll_interval_sec));
+    }
+
+    #[test]
+    fn bursty_load_shortens_the_interval_to_the_minimum() {
+        let config = DaemonConfig::default();
+        let mut adaptive = AdaptiveInterval::new(config.max_poll_interval_sec);
+        let bursts = (0..40).map(|i| if i % 2 == 0 { 0.0 } else { 100.0 });
+        let trajectory = adaptive_trajectory(&mut adaptive, &config, bursts);
+
+        assert!(trajectory.windows(2).all(|pair| pair[0] >= pair[1]));
+        assert_eq!(trajectory.last(), Some(&config.min_poll_interval_sec));
+    }
+
+    #[test]
+    fn moderate_variance_holds_the_interval() {
+        let config = DaemonConfig::default();
+        let mut adaptive = AdaptiveInterval::new(config.poll_interval_sec);
+        // Settles at a variance of about 54, between the two thresholds
+        let swings = (0..60).map(|i| if i % 2 == 0 { 35.0 } else { 50.0 });
+        let trajectory = adaptive_trajectory(&mut adaptive, &config, swings);
+
+        // Only the first sample, which has no variance yet, moves it
+        assert_eq!(trajectory[0], 6);
+        assert!(trajectory.iter().all(|interval| *interval == 6));
+    }
+
+    #[test]
+    fn adaptive_interval_recovers_once_bursts_end() {
+        let config = DaemonConfig::default();
+        let mut adaptive = AdaptiveInterval::new(config.poll_interval_sec);
+        let bursts = (0..20).map(|i| if i % 2 == 0 { 0.0 } else { 100.0 });
+        adaptive_trajectory(&mut adaptive, &config, bursts);
+        assert_eq!(adaptive.interval, config.min_poll_interval_sec);
+
+        // The variance decays before the interval grows again
+        let trajectory = adaptive_trajectory(&mut adaptive, &config, [50.0; 60]);
+        assert_eq!(trajectory[0], config.min_poll_interval_sec);
+        assert_eq!(trajectory.last(), Some(&config.max_poll_interval_sec));
+    }
+
+    #[test]
+    fn adaptive_interval_respects_the_bounds() {
+        let config = DaemonConfig {
+            min_poll_interval_sec: 3,
+            max_poll_interval_sec: 8,
+            adaptive: AdaptiveIntervalSettings {
+                step_up_sec: 5,
+                step_down_sec: 5,
+                ..AdaptiveIntervalSettings::default()
+            },
+            ..DaemonConfig::default()
+        };
+        let mut adaptive = AdaptiveInterval::new(5);
+        let loads = (0..100).map(|i| match i / 20 {
+            // Bursty, then stable, in turns
+            phase if phase % 2 == 0 => {
+                if i % 2 == 0 {
+                    0.0
+                } else {
+                    100.0
+                }
+            }
+            _ => 50.0,
+        });
+        let trajectory = adaptive_trajectory(&mut adaptive, &config, loads);
+
+        assert!(trajectory.iter().all(|interval| (3..=8).contains(interval)));
+        assert!(trajectory.contains(&3));
+        assert!(trajectory.contains(&8));
+    }
+}
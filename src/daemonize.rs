@@ -0,0 +1,223 @@
+// Traditional double-fork daemonization for init systems that expect it
+//
+// The original process stays around until the daemon reports back over a pipe,
+// so it can exit with the daemon's startup status and the PID file is in place
+// by the time the init script sees the parent exit.
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::path::Path;
+
+/// Startup status byte sent to the waiting parent
+const STATUS_READY: u8 = 0;
+const STATUS_FAILED: u8 = 1;
+
+/// Handle kept by the daemonized process until startup has finished
+pub struct Daemonized {
+    pipe: Option<File>,
+}
+
+impl Daemonized {
+    /// Tells the original process that startup succeeded, letting it exit
+    pub fn ready(mut self) {
+        if let Some(mut pipe) = self.pipe.take() {
+            let _ = pipe.write_all(&[STATUS_READY]);
+        }
+    }
+
+    /// Tells the original process that startup failed, with the reason
+    pub fn fail(mut self, message: &str) {
+        if let Some(mut pipe) = self.pipe.take() {
+            let _ = pipe.write_all(&[STATUS_FAILED]);
+            let _ = pipe.write_all(message.as_bytes());
+        }
+    }
+}
+
+impl Drop for Daemonized {
+    fn drop(&mut self) {
+        // Startup was abandoned without a word, e.g. by an early return
+        if let Some(mut pipe) = self.pipe.take() {
+            let _ = pipe.write_all(&[STATUS_FAILED]);
+        }
+    }
+}
+
+/// Detaches from the terminal: forks twice, starts a new session, changes to
+/// `/` and points stdio at `log_file`, or `/dev/null` without one.
+///
+/// Only the daemon returns from this function. The original process waits for
+/// [`Daemonized::ready`] or [`Daemonized::fail`] and exits accordingly.
+///
+/// Must be called before any threads are spawned, since only the calling
+/// thread survives a fork.
+pub fn daemonize(log_file: Option<&Path>) -> io::Result<Daemonized> {
+    let (read_end, write_end) = pipe()?;
+
+    // SAFETY: no other threads exist yet, see above
+    match unsafe { libc::fork() } {
+        -1 => return Err(io::Error::last_os_error()),
+        0 => {}
+        _ => {
+            drop(write_end);
+            wait_for_daemon(read_end);
+        }
+    }
+    drop(read_end);
+
+    // Leave the parent's session and process group, losing the terminal
+    // SAFETY: setsid has no memory safety requirements
+    if unsafe { libc::setsid() } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    // Fork again so the daemon is not a session leader and can never
+    // reacquire a controlling terminal
+    // SAFETY: still single-threaded
+    match unsafe { libc::fork() } {
+        -1 => return Err(io::Error::last_os_error()),
+        0 => {}
+        // SAFETY: _exit skips destructors and atexit handlers, which belong
+        // to the daemon now
+        _ => unsafe { libc::_exit(0) },
+    }
+
+    std::env::set_current_dir("/")?;
+    redirect_stdio(log_file)?;
+
+    Ok(Daemonized {
+        pipe: Some(File::from(write_end)),
+    })
+}
+
+/// Runs in the original process: exits once the daemon reports its status
+fn wait_for_daemon(read_end: OwnedFd) -> ! {
+    let code = match startup_status(File::from(read_end)) {
+        Ok(()) => 0,
+        Err(message) => {
+            eprintln!("{message}");
+            1
+        }
+    };
+
+    std::process::exit(code);
+}
+
+/// Reads what the daemon reported over the startup pipe: nothing once it is
+/// ready, or else why it failed
+fn startup_status(mut pipe: impl Read) -> Result<(), String> {
+    let mut status = [STATUS_FAILED];
+    let mut message = String::new();
+
+    match pipe.read_exact(&mut status) {
+        Ok(()) if status[0] == STATUS_READY => Ok(()),
+        Ok(()) => {
+            let _ = pipe.read_to_string(&mut message);
+            if message.is_empty() {
+                Err("Watt daemon failed to start, see its log for details".to_string())
+            } else {
+                Err(format!("Watt daemon failed to start: {message}"))
+            }
+        }
+        // The daemon died before reporting anything
+        Err(_) => Err("Watt daemon exited during startup".to_string()),
+    }
+}
+
+fn pipe() -> io::Result<(OwnedFd, OwnedFd)> {
+    let mut fds: [RawFd; 2] = [-1; 2];
+    // SAFETY: `fds` has room for the two descriptors pipe2 returns
+    if unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // SAFETY: both descriptors were just created and are owned by nobody else
+    Ok(unsafe { (OwnedFd::from_raw_fd(fds[0]), OwnedFd::from_raw_fd(fds[1])) })
+}
+
+/// Points stdin at `/dev/null` and stdout and stderr at `log_file` or
+/// `/dev/null`
+fn redirect_stdio(log_file: Option<&Path>) -> io::Result<()> {
+    let null = File::options().read(true).write(true).open("/dev/null")?;
+    let output = match log_file {
+        Some(path) => File::options().create(true).append(true).open(path)?,
+        None => null.try_clone()?,
+    };
+
+    dup_onto(&null, libc::STDIN_FILENO)?;
+    dup_onto(&output, libc::STDOUT_FILENO)?;
+    dup_onto(&output, libc::STDERR_FILENO)
+}
+
+fn dup_onto(file: &File, target: RawFd) -> io::Result<()> {
+    // SAFETY: both descriptors are valid, and replacing stdio is the point
+    if unsafe { libc::dup2(file.as_raw_fd(), target) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pidfile::{self, PidFile, PidFileError};
+
+    /// Both ends of the startup pipe, as the daemon and the original process
+    /// hold them
+    fn startup_pipe() -> (Daemonized, File) {
+        let (read_end, write_end) = pipe().unwrap();
+        let daemonized = Daemonized {
+            pipe: Some(File::from(write_end)),
+        };
+        (daemonized, File::from(read_end))
+    }
+
+    #[test]
+    fn parent_exits_once_the_pid_file_is_in_place() {
+        let path = std::env::temp_dir().join(format!("watt-test-{}.pid", std::process::id()));
+        let (daemonized, parent) = startup_pipe();
+
+        // As the daemon does it: lock and write the PID file, then report
+        let pid_file = PidFile::acquire(&path, false).unwrap();
+        daemonized.ready();
+
+        assert_eq!(startup_status(parent), Ok(()));
+        let pid = std::process::id().to_string();
+        assert_eq!(pidfile::running_daemon_pid(&path), Some(pid.clone()));
+        assert!(matches!(
+            PidFile::acquire(&path, false),
+            Err(PidFileError::AlreadyRunning(holder)) if holder == pid
+        ));
+
+        drop(pid_file);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn parent_reports_why_startup_failed() {
+        let (daemonized, parent) = startup_pipe();
+        daemonized.fail("Another Watt daemon is already running (PID 1)");
+        assert_eq!(
+            startup_status(parent),
+            Err(
+                "Watt daemon failed to start: Another Watt daemon is already running (PID 1)"
+                    .to_string()
+            )
+        );
+
+        // Dropped without a word, e.g. by an early return
+        let (daemonized, parent) = startup_pipe();
+        drop(daemonized);
+        assert_eq!(
+            startup_status(parent),
+            Err("Watt daemon failed to start, see its log for details".to_string())
+        );
+
+        // Died before reporting anything
+        let (mut daemonized, parent) = startup_pipe();
+        drop(daemonized.pipe.take());
+        assert_eq!(
+            startup_status(parent),
+            Err("Watt daemon exited during startup".to_string())
+        );
+    }
+}
@@ -0,0 +1,348 @@
+// D-Bus interface exposing daemon status and profile control on the system bus
+use crate::auth::{self, Action, Authority, Caller};
+use crate::core::SystemReport;
+use crate::daemon::DaemonState;
+use log::warn;
+use std::sync::Arc;
+use zbus::message::Header;
+use zbus::names::UniqueName;
+use zbus::object_server::SignalEmitter;
+use zbus::{fdo, interface};
+
+/// Well-known bus name owned by the daemon
+pub const BUS_NAME: &str = "org.watt.Daemon";
+/// Object path the daemon interface is served at
+pub const OBJECT_PATH: &str = "/org/watt/Daemon";
+/// Versioned interface name. Bump the trailing number on incompatible changes.
+pub const INTERFACE_NAME: &str = "org.watt.Daemon1";
+/// Version number reported through the `Version` property
+const INTERFACE_VERSION: u32 = 1;
+
+/// The object served at [`OBJECT_PATH`]
+struct DaemonInterface {
+    state: Arc<DaemonState>,
+    authority: Arc<dyn Authority>,
+}
+
+#[interface(name = "org.watt.Daemon1")]
+impl DaemonInterface {
+    /// Returns the most recent system report, serialized as JSON
+    fn get_report(&self) -> fdo::Result<String> {
+        let report = self
+            .state
+            .last_report()
+            .ok_or_else(|| fdo::Error::Failed("No system report collected yet".to_string()))?;
+
+        serde_json::to_string(&report).map_err(|e| fdo::Error::Failed(e.to_string()))
+    }
+
+    /// Forces a profile by name, or returns to automatic selection with "auto"
+    async fn set_profile(
+        &self,
+        name: &str,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &zbus::Connection,
+    ) -> fdo::Result<()> {
+        self.check_authorization(connection, &header, Action::SetProfile)
+            .await?;
+
+        self.state
+            .set_profile_by_name(name, None, None, false)
+            .map_err(fdo::Error::InvalidArgs)
+    }
+
+    /// Forces a profile by name until `duration_sec` seconds have passed or,
+    /// with `until_power_change`, until the power source changes. A duration
+    /// of 0 means none.
+    async fn set_profile_until(
+        &self,
+        name: &str,
+        duration_sec: u64,
+        until_power_change: bool,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &zbus::Connection,
+    ) -> fdo::Result<()> {
+        self.check_authorization(connection, &header, Action::SetProfile)
+            .await?;
+
+        let duration = (duration_sec > 0).then(|| {
+            jiff::SignedDuration::from_secs(i64::try_from(duration_sec).unwrap_or(i64::MAX))
+        });
+        self.state
+            .set_profile_by_name(name, duration, None, until_power_change)
+            .map_err(fdo::Error::InvalidArgs)
+    }
+
+    /// Overrides the turbo setting of the active profile, or clears the
+    /// override with "auto"
+    async fn set_turbo(
+        &self,
+        mode: &str,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &zbus::Connection,
+    ) -> fdo::Result<()> {
+        self.check_authorization(connection, &header, Action::SetTurbo)
+            .await?;
+
+        self.state
+            .set_turbo_by_name(mode, false)
+            .map_err(fdo::Error::InvalidArgs)
+    }
+
+    /// Sets battery charge thresholds, as percentages
+    async fn set_battery_thresholds(
+        &self,
+        start: u8,
+        stop: u8,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &zbus::Connection,
+    ) -> fdo::Result<()> {
+        self.check_authorization(connection, &header, Action::ManageThresholds)
+            .await?;
+
+        self.state
+            .set_battery_thresholds(start, stop)
+            .map_err(fdo::Error::InvalidArgs)
+    }
+
+    /// Asks the daemon to reload its configuration before the next cycle
+    async fn reload(
+        &self,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &zbus::Connection,
+    ) -> fdo::Result<()> {
+        self.check_authorization(connection, &header, Action::Reload)
+            .await?;
+
+        self.state.request_reload();
+        Ok(())
+    }
+
+    /// Name of the profile applied in the last cycle
+    #[zbus(property)]
+    fn active_profile(&self) -> String {
+        self.state.active_profile().unwrap_or_default()
+    }
+
+    /// Version of this interface
+    #[zbus(property)]
+    fn version(&self) -> u32 {
+        INTERFACE_VERSION
+    }
+
+    /// Emitted whenever the daemon switches to a different profile
+    #[zbus(signal)]
+    async fn profile_changed(emitter: &SignalEmitter<'_>, name: &str) -> zbus::Result<()>;
+}
+
+impl DaemonInterface {
+    /// Checks through [`auth::authorize`] whether the sender of a method call
+    /// may perform `action`, as the control socket does
+    async fn check_authorization(
+        &self,
+        connection: &zbus::Connection,
+        header: &Header<'_>,
+        action: Action,
+    ) -> fdo::Result<()> {
+        let sender = header
+            .sender()
+            .ok_or_else(|| fdo::Error::AccessDenied("Method call has no sender".to_string()))?;
+        let caller = caller(connection, sender).await?;
+
+        // Polkit may wait on the user authenticating, which must not hold up
+        // other method calls
+        let authority = self.authority.clone();
+        blocking::unblock(move || auth::authorize(authority.as_ref(), &caller, action))
+            .await
+            .map_err(fdo::Error::AccessDenied)
+    }
+}
+
+/// Credentials of the connection `sender` names, as the bus daemon knows them
+async fn caller(connection: &zbus::Connection, sender: &UniqueName<'_>) -> fdo::Result<Caller> {
+    let dbus = fdo::DBusProxy::new(connection).await?;
+    Ok(Caller {
+        pid: dbus
+            .get_connection_unix_process_id(sender.clone().into())
+            .await?,
+        uid: dbus.get_connection_unix_user(sender.clone().into()).await?,
+    })
+}
+
+/// Handle to the daemon's D-Bus service
+pub struct DbusService {
+    connection: zbus::blocking::Connection,
+}
+
+impl DbusService {
+    /// Connects to the system bus, claims [`BUS_NAME`] and serves the daemon
+    /// interface, checking method calls that change anything with
+    /// `authority`. The returned handle keeps the service alive.
+    pub fn start(state: Arc<DaemonState>, authority: Arc<dyn Authority>) -> zbus::Result<Self> {
+        let connection = zbus::blocking::connection::Builder::system()?
+            .name(BUS_NAME)?
+            .serve_at(OBJECT_PATH, DaemonInterface { state, authority })?
+            .build()?;
+
+        Ok(Self { connection })
+    }
+
+    /// Emits the `ProfileChanged` signal
+    pub fn emit_profile_changed(&self, name: &str) {
+        let result = self
+            .connection
+            .object_server()
+            .interface::<_, DaemonInterface>(OBJECT_PATH)
+            .and_then(|iface| {
+                zbus::block_on(DaemonInterface::profile_changed(
+                    iface.signal_emitter(),
+                    name,
+                ))
+            });
+
+        if let Err(e) = result {
+            warn!("Failed to emit ProfileChanged signal: {e}");
+        }
+    }
+}
+
+/// Fetches the daemon's latest report and active profile over the system bus
+pub fn fetch_status() -> zbus::Result<(SystemReport, String)> {
+    fetch_status_over(&zbus::blocking::Connection::system()?)
+}
+
+/// Fetches the daemon's latest report and active profile over `connection`
+fn fetch_status_over(
+    connection: &zbus::blocking::Connection,
+) -> zbus::Result<(SystemReport, String)> {
+    let proxy = zbus::blocking::Proxy::new(connection, BUS_NAME, OBJECT_PATH, INTERFACE_NAME)?;
+
+    let json: String = proxy.call("GetReport", &())?;
+    let report = serde_json::from_str(&json)
+        .map_err(|e| zbus::Error::Failure(format!("Invalid report from daemon: {e}")))?;
+    let active_profile: String = proxy.get_property("ActiveProfile")?;
+
+    Ok((report, active_profile))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader};
+    use std::process::{Child, Command, Stdio};
+
+    /// A bus daemon of its own, so the tests neither need nor touch the
+    /// system bus
+    struct PrivateBus {
+        daemon: Child,
+        address: String,
+    }
+
+    impl PrivateBus {
+        /// `None` where `dbus-daemon` is not installed
+        fn start() -> Option<Self> {
+            let mut daemon = Command::new("dbus-daemon")
+                .args(["--session", "--nofork", "--print-address=1"])
+                .stdout(Stdio::piped())
+                .stderr(Stdio::null())
+                .spawn()
+                .ok()?;
+            let mut address = String::new();
+            BufReader::new(daemon.stdout.take()?)
+                .read_line(&mut address)
+                .ok()?;
+            Some(Self {
+                daemon,
+                address: address.trim().to_string(),
+            })
+        }
+
+        fn connect(&self) -> zbus::blocking::connection::Builder<'static> {
+            zbus::blocking::connection::Builder::address(self.address.as_str()).unwrap()
+        }
+
+        /// Serves the daemon interface for `state` under [`BUS_NAME`]
+        fn serve(&self, state: Arc<DaemonState>) -> zbus::blocking::Connection {
+            self.connect()
+                .name(BUS_NAME)
+                .unwrap()
+                .serve_at(
+                    OBJECT_PATH,
+                    DaemonInterface {
+                        state,
+                        authority: Arc::new(AllowAll),
+                    },
+                )
+                .unwrap()
+                .build()
+                .unwrap()
+        }
+
+        fn proxy(connection: &zbus::blocking::Connection) -> zbus::blocking::Proxy<'static> {
+            zbus::blocking::Proxy::new(connection, BUS_NAME, OBJECT_PATH, INTERFACE_NAME).unwrap()
+        }
+    }
+
+    impl Drop for PrivateBus {
+        fn drop(&mut self) {
+            let _ = self.daemon.kill();
+            let _ = self.daemon.wait();
+        }
+    }
+
+    struct AllowAll;
+
+    impl Authority for AllowAll {
+        fn is_authorized(&self, _caller: &Caller, _action: Action) -> Result<bool, String> {
+            Ok(true)
+        }
+    }
+
+    #[test]
+    fn status_round_trips_over_the_bus() {
+        let Some(bus) = PrivateBus::start() else {
+            eprintln!("dbus-daemon is not installed, skipping");
+            return;
+        };
+        let state = Arc::new(DaemonState::default());
+        let _service = bus.serve(state.clone());
+        let client = bus.connect().build().unwrap();
+
+        assert!(fetch_status_over(&client).is_err());
+
+        state.set_last_report(SystemReport::for_tests());
+        let (report, active_profile) = fetch_status_over(&client).unwrap();
+        assert_eq!(report.system_info.cpu_model, "Test CPU");
+        assert!(report.ac_power.online);
+        assert_eq!(active_profile, "");
+
+        let version: u32 = PrivateBus::proxy(&client).get_property("Version").unwrap();
+        assert_eq!(version, INTERFACE_VERSION);
+    }
+
+    #[test]
+    fn set_profile_reaches_the_daemon_state() {
+        let Some(bus) = PrivateBus::start() else {
+            eprintln!("dbus-daemon is not installed, skipping");
+            return;
+        };
+        let state = Arc::new(DaemonState::default());
+        let _service = bus.serve(state.clone());
+        let client = bus.connect().build().unwrap();
+        let proxy = PrivateBus::proxy(&client);
+
+        let () = proxy.call("SetProfile", &("powersave",)).unwrap();
+        assert!(state.force_mode().is_some());
+        let () = proxy.call("SetProfile", &("auto",)).unwrap();
+        assert!(state.force_mode().is_none());
+
+        let error = proxy
+            .call::<_, _, ()>("SetProfile", &("no-such-profile",))
+            .unwrap_err();
+        assert!(
+            matches!(&error, zbus::Error::MethodError(name, _, _)
+                if name.as_str() == "org.freedesktop.DBus.Error.InvalidArgs"),
+            "{error}"
+        );
+    }
+}
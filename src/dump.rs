@@ -0,0 +1,206 @@
+// Status snapshot written to the log on SIGUSR1, for debugging without IPC
+use crate::cpu;
+use crate::daemon::{DaemonState, ForceEnd, Pause};
+use crate::engine::{self, TurboStates};
+use crate::util::sysfs;
+use log::info;
+use std::fmt::Write as _;
+
+/// Outcome of the most recent apply, kept by the engine thread
+pub struct LastApply {
+    pub timestamp: jiff::Timestamp,
+    /// The error message if applying failed
+    pub error: Option<String>,
+}
+
+/// Main loop details that are not part of [`DaemonState`]
+pub struct LoopDetails<'a> {
+    pub poll_interval_sec: u64,
+    pub turbo_states: &'a TurboStates,
+    pub last_apply: Option<&'a LastApply>,
+}
+
+/// Logs a snapshot of everything the daemon knows, one line per log record so
+/// line-based log collectors keep it readable
+pub fn log_status(state: &DaemonState, details: &LoopDetails<'_>) {
+    let mut out = String::new();
+    let report = state.last_report();
+    let force_mode = state.force_mode();
+
+    let _ = writeln!(out, "--- Status dump ---");
+    match (&state.active_profile(), &report) {
+        (Some(profile), Some(report)) => {
+            let _ = writeln!(
+                out,
+                "Active profile: {profile} ({})",
+                engine::selected_profile_reason(report, force_mode.as_ref())
+            );
+        }
+        _ => {
+            let _ = writeln!(out, "Active profile: none yet");
+        }
+    }
+    if let Some(boost) = state.process_boost() {
+        let _ = writeln!(
+            out,
+            "Process boost: {} running since {}, turbo kept on: {}",
+            boost.process, boost.since, boost.turbo
+        );
+    }
+    let _ = writeln!(
+        out,
+        "Forced profile: {}{}, turbo override: {:?}, paused: {}",
+        force_mode.map_or_else(|| "none".to_string(), |mode| mode.to_string()),
+        match state.force_end() {
+            Some(ForceEnd::At(until)) => format!(" until {until}"),
+            Some(ForceEnd::PowerChange) => " until the power source changes".to_string(),
+            None => String::new(),
+        },
+        state.turbo_override(),
+        match state.pause_state() {
+            Some(Pause {
+                until: Some(until), ..
+            }) => format!("until {until}"),
+            Some(Pause { until: None, .. }) => "until resumed".to_string(),
+            None => "no".to_string(),
+        }
+    );
+    for entry in state.timed_overrides() {
+        let _ = writeln!(
+            out,
+            "Override: {}{} = {} until {}",
+            entry.knob,
+            entry.describe_scope(),
+            entry.value,
+            entry.until
+        );
+    }
+
+    if let Some(reason) = state.knobs().cpu_control_unavailable() {
+        let _ = writeln!(out, "CPU frequency control: unavailable, {reason}");
+    }
+    for policy in cpu::cpufreq_policies() {
+        let read = |file: &str| {
+            sysfs::read_sysfs_value(policy.join(file)).unwrap_or_else(|_| "N/A".to_string())
+        };
+        let _ = writeln!(
+            out,
+            "{}: governor={} epp={} freq={} min={} max={} (kHz)",
+            policy.file_name().unwrap_or_default().to_string_lossy(),
+            read("scaling_governor"),
+            read("energy_performance_preference"),
+            read("scaling_cur_freq"),
+            read("scaling_min_freq"),
+            read("scaling_max_freq"),
+        );
+    }
+
+    let (charger_turbo, battery_turbo) = details.turbo_states.previous_states();
+    let _ = writeln!(
+        out,
+        "Turbo: {}, hysteresis state on AC: {}, on battery: {}",
+        report
+            .as_ref()
+            .and_then(|r| r.cpu_global.turbo_status)
+            .map_or("unknown", on_off),
+        charger_turbo.map_or("unset", on_off),
+        battery_turbo.map_or("unset", on_off),
+    );
+    for (group, charger_turbo, battery_turbo) in details.turbo_states.group_states() {
+        let _ = writeln!(
+            out,
+            "Turbo [{group}]: hysteresis state on AC: {}, on battery: {}",
+            charger_turbo.map_or("unset", on_off),
+            battery_turbo.map_or("unset", on_off),
+        );
+    }
+
+    if let Some(report) = &report {
+        for battery in &report.batteries {
+            let _ = writeln!(
+                out,
+                "Battery {}: {} {}, {}, thresholds {}-{}",
+                battery.name,
+                battery
+                    .capacity_percent
+                    .map_or_else(|| "N/A".to_string(), |c| format!("{c}%")),
+                battery.charging_state.as_deref().unwrap_or("Unknown"),
+                if battery.ac_connected {
+                    "AC"
+                } else {
+                    "battery"
+                },
+                battery
+                    .charge_start_threshold
+                    .map_or_else(|| "N/A".to_string(), |t| t.to_string()),
+                battery
+                    .charge_stop_threshold
+                    .map_or_else(|| "N/A".to_string(), |t| t.to_string()),
+            );
+        }
+    }
+
+    match details.last_apply {
+        Some(LastApply {
+            timestamp,
+            error: None,
+        }) => {
+            let _ = writeln!(out, "Last apply: succeeded at {timestamp}");
+        }
+        Some(LastApply {
+            timestamp,
+            error: Some(e),
+        }) => {
+            let _ = writeln!(out, "Last apply: failed at {timestamp}: {e}");
+        }
+        None => {
+            let _ = writeln!(out, "Last apply: none yet");
+        }
+    }
+
+    let _ = writeln!(out, "Poll interval: {}s", details.poll_interval_sec);
+    let counters = state.metrics().counters();
+    let _ = writeln!(
+        out,
+        "Since {}: {} cycles, {} applies ({} failed), {} reloads, {} resumes, {} profile switches",
+        counters.started_at,
+        state.metrics().cycles(),
+        counters.apply_cycles,
+        counters.apply_errors,
+        counters.config_reloads,
+        counters.resumes,
+        counters.profile_switches
+    );
+    let _ = writeln!(
+        out,
+        "Sysfs writes: {} written, {} skipped as unchanged, failed: {:?}",
+        counters.sysfs_writes, counters.sysfs_writes_skipped, counters.sysfs_write_errors
+    );
+    let _ = writeln!(out, "Last apply: {}", state.knobs().last_summary());
+
+    for parked in state.knobs().parked() {
+        let _ = writeln!(
+            out,
+            "Parked knob {}: since {}, retrying by {}: {}",
+            parked.knob, parked.since, parked.retry_at, parked.error
+        );
+    }
+    for drifted in state.knobs().drifted() {
+        let _ = writeln!(
+            out,
+            "Drifted knob {}: {}, profile wants {}",
+            drifted.knob, drifted.current, drifted.desired
+        );
+    }
+    for knob in state.knobs().paused() {
+        let _ = writeln!(out, "Paused knob {knob}: changed by hand, not managed");
+    }
+
+    for line in out.lines() {
+        info!("{line}");
+    }
+}
+
+const fn on_off(enabled: bool) -> &'static str {
+    if enabled { "enabled" } else { "disabled" }
+}
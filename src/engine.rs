@@ -1,464 +1,4048 @@
-use crate::battery;
-use crate::config::{AppConfig, ProfileConfig, TurboAutoSettings};
-use crate::core::{OperationalMode, SystemReport, TurboSetting};
-use crate::cpu::{self};
+use crate::config::{
+    AUTO_EPP, AUTO_GOVERNOR, AppConfig, ApplyStep, DaemonConfig, DriftPolicy, EppAutoSettings,
+    GovernorAutoSettings, LoadAverage, LoadMetric, LoadProfileSettings, ProfileConfig,
+    ThermalBackoff, ThermalStep, TurboAutoSettings, TurboStrategyKind, TurboTemperatureSource,
+};
+use crate::control::PowerController;
+use crate::core::{CpuPressure, ForcedProfile, OperationalMode, SystemReport, TurboSetting};
+use crate::smoothing;
+use crate::topology::{self, CoreGroup};
+use crate::turbo_strategy::{self, TurboMetrics, WeightedScore};
 use crate::util::error::{ControlError, EngineError};
-use log::{debug, info, warn};
-use std::sync::OnceLock;
+use crate::util::sysfs::{self, WriteOutcome, WriteRecord};
+use log::{Level, debug, info, log, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
 use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
 
-/// Track turbo boost state for AC and battery power modes
-struct TurboHysteresisStates {
-    /// State for when on AC power
-    charger: TurboHysteresis,
-    /// State for when on battery power
-    battery: TurboHysteresis,
+/// How old a saved turbo state may be before it is ignored at startup. Past
+/// this, the workload it was based on has most likely changed.
+const TURBO_STATE_MAX_AGE: Duration = Duration::from_secs(5 * 60);
+
+/// How long a parked knob is left alone before it is tried again
+const KNOB_RETRY_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+/// Decision traces kept for `watt info --explain` and the status
+const MAX_DECISION_TRACES: usize = 8;
+
+/// Identical failures in a row after which a knob is parked
+const KNOB_FAILURE_LIMIT: u32 = 3;
+
+/// Percent above `min_battery_percent_for_turbo` the battery has to charge
+/// to before turbo may enable again, so it does not flap at the threshold
+const BATTERY_TURBO_HYSTERESIS_PERCENT: u8 = 2;
+
+/// Largest `load_window_samples`, which at the usual poll intervals already
+/// spans well over an hour
+const MAX_LOAD_WINDOW_SAMPLES: usize = 1000;
+
+/// Most auto turbo states kept per core group or for all CPUs, one per
+/// profile and power state. The least recently used goes first.
+const MAX_HYSTERESIS_STATES: usize = 16;
+
+/// Tjmax assumed by the thermal safety net when the sensor reports none
+const DEFAULT_TJMAX_CELSIUS: f32 = 100.0;
+
+/// Degrees below Tjmax at which the thermal safety net engages, unless
+/// `daemon.thermal_safety_critical_celsius` is set
+const THERMAL_SAFETY_MARGIN_CELSIUS: f32 = 5.0;
+
+/// Degrees below the critical temperature the CPU has to cool down to before
+/// the thermal safety net lets go, unless
+/// `daemon.thermal_safety_recovery_celsius` is set
+const THERMAL_SAFETY_RECOVERY_MARGIN_CELSIUS: f32 = 15.0;
+
+/// Governors the thermal safety net picks from, slowest first
+const THERMAL_SAFETY_GOVERNORS: &[&str] = &["powersave", "conservative"];
+
+/// Steps the thermal safety net takes over while it is engaged
+const THERMAL_SAFETY_STEPS: &[ApplyStep] = &[
+    ApplyStep::Governor,
+    ApplyStep::Turbo,
+    ApplyStep::Frequencies,
+];
+
+/// Failure history of a single knob
+struct KnobFailure {
+    /// The most recent error
+    error: String,
+    /// How many times in a row the knob failed with `error`
+    count: u32,
+    /// When the knob was parked, if it is
+    parked_at: Option<jiff::Timestamp>,
 }
 
-impl TurboHysteresisStates {
-    const fn new() -> Self {
-        Self {
-            charger: TurboHysteresis::new(),
-            battery: TurboHysteresis::new(),
-        }
-    }
+/// A knob the engine has stopped writing for now
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ParkedKnob {
+    pub knob: String,
+    /// The error that got the knob parked
+    pub error: String,
+    pub since: jiff::Timestamp,
+    /// When the knob is tried again at the latest
+    pub retry_at: jiff::Timestamp,
+}
 
-    const fn get_for_power_state(&self, is_on_ac: bool) -> &TurboHysteresis {
-        if is_on_ac {
-            &self.charger
-        } else {
-            &self.battery
+/// A knob someone else changed, which the daemon leaves as it is
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DriftedKnob {
+    pub knob: String,
+    /// The value found on the system
+    pub current: String,
+    /// The value the profile asks for
+    pub desired: String,
+}
+
+/// What an apply did to one knob
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "outcome", rename_all = "snake_case")]
+pub enum KnobOutcome {
+    /// Written, or in a dry run, to be written. `from` is the previous value
+    /// when every file held the same one in the same spelling.
+    Changed {
+        from: Option<String>,
+        to: String,
+        files: usize,
+    },
+    /// Already as the profile asks
+    Unchanged {
+        value: String,
+    },
+    /// Not written: unsupported, parked, or left as someone else set it
+    Skipped {
+        reason: String,
+    },
+    Failed {
+        error: String,
+    },
+    /// Written, then restored after a later knob failed
+    RolledBack {
+        files: usize,
+    },
+}
+
+impl fmt::Display for KnobOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Changed { from, to, files } => {
+                match from {
+                    Some(from) => write!(f, "{from}→{to}")?,
+                    None => write!(f, "set to {to}")?,
+                }
+                if *files > 1 {
+                    write!(f, " on {files} files")?;
+                }
+                Ok(())
+            }
+            Self::Unchanged { value } => write!(f, "unchanged ({value})"),
+            Self::Skipped { reason } => write!(f, "skipped ({reason})"),
+            Self::Failed { error } => write!(f, "failed ({error})"),
+            Self::RolledBack { files } => {
+                write!(f, "rolled back")?;
+                if *files > 1 {
+                    write!(f, " on {files} files")?;
+                }
+                Ok(())
+            }
         }
     }
 }
 
-static TURBO_STATES: OnceLock<TurboHysteresisStates> = OnceLock::new();
-
-/// Get or initialize the global turbo states
-fn get_turbo_states() -> &'static TurboHysteresisStates {
-    TURBO_STATES.get_or_init(TurboHysteresisStates::new)
+/// A knob and what an apply did to it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KnobResult {
+    pub knob: String,
+    #[serde(flatten)]
+    pub outcome: KnobOutcome,
 }
 
-/// Manage turbo boost hysteresis state.
-/// Contains the state needed to implement hysteresis
-/// for the dynamic turbo management feature
-struct TurboHysteresis {
-    /// Whether turbo was enabled in the previous cycle
-    previous_state: AtomicBool,
-    /// Whether the hysteresis state has been initialized
-    initialized: AtomicBool,
+/// What one apply did to each knob the profile sets, in the order they were
+/// handled. An apply that stopped early on an error lists the knobs up to it.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ApplySummary {
+    pub profile: String,
+    pub knobs: Vec<KnobResult>,
 }
 
-impl TurboHysteresis {
-    const fn new() -> Self {
-        Self {
-            previous_state: AtomicBool::new(false),
-            initialized: AtomicBool::new(false),
+impl ApplySummary {
+    fn record(&mut self, knob: &str, outcome: KnobOutcome) {
+        match self.knobs.iter_mut().find(|result| result.knob == knob) {
+            Some(result) => result.outcome = outcome,
+            None => self.knobs.push(KnobResult {
+                knob: knob.to_string(),
+                outcome,
+            }),
         }
     }
 
-    /// Get the previous turbo state, if initialized
-    fn get_previous_state(&self) -> Option<bool> {
-        if self.initialized.load(Ordering::Acquire) {
-            Some(self.previous_state.load(Ordering::Acquire))
-        } else {
-            None
-        }
+    /// Knobs written, or in a dry run, to be written
+    pub fn changed(&self) -> usize {
+        self.count(|outcome| matches!(outcome, KnobOutcome::Changed { .. }))
     }
 
-    /// Initialize the state with a specific value if not already initialized
-    /// Only one thread should be able to initialize the state
-    fn initialize_with(&self, initial_state: bool) -> bool {
-        // First, try to atomically change initialized from false to true
-        // Only one thread can win the initialization race
-        match self.initialized.compare_exchange(
-            false,             // expected: not initialized
-            true,              // desired: mark as initialized
-            Ordering::Release, // success: release for memory visibility
-            Ordering::Acquire, // failure: just need to acquire the current value
-        ) {
-            Ok(_) => {
-                // We won the race to initialize
-                // Now it's safe to set the initial state since we know we're the only
-                // thread that has successfully marked this as initialized
-                self.previous_state.store(initial_state, Ordering::Release);
-                initial_state
-            }
-            Err(_) => {
-                // Another thread already initialized it.
-                // Just read the current state value that was set by the winning thread
-                self.previous_state.load(Ordering::Acquire)
-            }
-        }
+    pub fn failed(&self) -> usize {
+        self.count(|outcome| matches!(outcome, KnobOutcome::Failed { .. }))
     }
 
-    /// Update the turbo state for hysteresis
-    fn update_state(&self, new_state: bool) {
-        // First store the new state, then mark as initialized
-        // With this, any thread seeing initialized=true will also see the correct state
-        self.previous_state.store(new_state, Ordering::Release);
+    fn count(&self, filter: impl Fn(&KnobOutcome) -> bool) -> usize {
+        self.knobs
+            .iter()
+            .filter(|result| filter(&result.outcome))
+            .count()
+    }
+}
 
-        // Already initialized, no need for compare_exchange
-        if self.initialized.load(Ordering::Relaxed) {
-            return;
+impl fmt::Display for ApplySummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.knobs.is_empty() {
+            return f.write_str("nothing to apply");
         }
+        for (index, result) in self.knobs.iter().enumerate() {
+            if index > 0 {
+                f.write_str("; ")?;
+            }
+            write!(f, "{}: {}", result.knob, result.outcome)?;
+        }
+        Ok(())
+    }
+}
 
-        // Otherwise, try to set initialized=true (but only if it was false)
-        self.initialized
-            .compare_exchange(
-                false,             // expected: not initialized
-                true,              // desired: mark as initialized
-                Ordering::Release, // success: release for memory visibility
-                Ordering::Relaxed, // failure: we don't care about the current value on failure
+/// What applying a knob to `value` did, judging by its `writes`
+fn outcome_of(writes: &[WriteRecord], value: &str) -> KnobOutcome {
+    if let Some(error) = writes.iter().find_map(|write| match &write.outcome {
+        WriteOutcome::Failed(error) => Some(error),
+        _ => None,
+    }) {
+        return KnobOutcome::Failed {
+            error: error.clone(),
+        };
+    }
+
+    let changed: Vec<&WriteRecord> = writes
+        .iter()
+        .filter(|write| {
+            matches!(
+                write.outcome,
+                WriteOutcome::Written | WriteOutcome::WouldWrite
             )
-            .ok(); // Ignore the result. If it fails, it means another thread already initialized it
+        })
+        .collect();
+    if changed.is_empty() && !writes.is_empty() {
+        return KnobOutcome::Unchanged {
+            value: value.to_string(),
+        };
+    }
+
+    // Only comparable when the files hold the value as it is described,
+    // unlike e.g. frequencies in kHz or turbo as no_turbo
+    let same_spelling = changed.iter().all(|write| write.value == value);
+    let mut old_values: Vec<&str> = changed
+        .iter()
+        .filter_map(|write| write.old_value.as_deref())
+        .collect();
+    old_values.sort_unstable();
+    old_values.dedup();
+    KnobOutcome::Changed {
+        from: match old_values.as_slice() {
+            [from] if same_spelling => Some((*from).to_string()),
+            _ => None,
+        },
+        to: value.to_string(),
+        files: changed.len(),
     }
 }
 
-/// Try applying a CPU feature and handle common error cases. Centralizes the where we
-/// previously did:
-/// 1. Try to apply a feature setting
-/// 2. If not supported, log a warning and continue
-/// 3. If other error, propagate the error
-fn try_apply_feature<F, T>(
-    feature_name: &str,
-    value_description: &str,
-    apply_fn: F,
-) -> Result<(), EngineError>
-where
-    F: FnOnce() -> Result<T, ControlError>,
-{
-    info!("Setting {feature_name} to '{value_description}'");
+/// Why one cycle picked its profile and turbo state: what it looked at, the
+/// rules it checked and the overrides in effect
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DecisionTrace {
+    pub timestamp: jiff::Timestamp,
+    pub inputs: DecisionInputs,
+    /// Profile selection rules in the order they were checked
+    pub rules: Vec<RuleCheck>,
+    /// Overrides in effect, such as a forced profile or governor
+    pub overrides: Vec<String>,
+    pub profile: String,
+    pub profile_reason: String,
+    /// Turbo decision of each group, or of all CPUs
+    pub turbo: Vec<TurboTrace>,
+}
 
-    match apply_fn() {
-        Ok(_) => Ok(()),
-        Err(e) => {
-            if matches!(e, ControlError::NotSupported(_)) {
-                warn!(
-                    "{feature_name} setting is not supported on this system. Skipping {feature_name} configuration."
-                );
-                Ok(())
-            } else {
-                // Propagate all other errors, including InvalidValueError
-                Err(EngineError::ControlError(e))
-            }
-        }
+impl DecisionTrace {
+    /// Whether `other` picked the same profile and turbo states
+    pub fn same_decision(&self, other: &Self) -> bool {
+        self.profile == other.profile
+            && self.turbo.len() == other.turbo.len()
+            && self
+                .turbo
+                .iter()
+                .zip(&other.turbo)
+                .all(|(a, b)| a.group == b.group && a.enabled == b.enabled)
     }
 }
 
-/// Determines the appropriate CPU profile based on power status or forced mode,
-/// and applies the settings (via helpers defined in the `cpu` module)
-pub fn determine_and_apply_settings(
-    report: &SystemReport,
-    config: &AppConfig,
-    force_mode: Option<OperationalMode>,
-) -> Result<(), EngineError> {
-    // First, check if there's a governor override set
-    if let Some(override_governor) = cpu::get_governor_override() {
-        info!(
-            "Governor override is active: '{}'. Setting governor.",
-            override_governor.trim()
-        );
+/// What a decision was based on
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DecisionInputs {
+    pub on_ac_power: bool,
+    /// What the AC detection read, e.g. "ADP1 online"
+    pub power_source: String,
+    pub battery_percent: Option<u8>,
+    /// Average CPU usage, smoothed when the daemon smooths it
+    pub load_percent: Option<f32>,
+    pub temperature_celsius: Option<f32>,
+    /// Throttle events since the previous cycle
+    #[serde(default)]
+    pub throttle_events: Option<u64>,
+}
 
-        // Apply the override governor setting
-        try_apply_feature("override governor", override_governor.trim(), || {
-            cpu::set_governor(override_governor.trim(), None)
-        })?;
+/// One rule checked on the way to a decision
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RuleCheck {
+    pub rule: String,
+    pub matched: bool,
+}
+
+/// How turbo was decided for a group of CPUs
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TurboTrace {
+    /// The core group, or "all"
+    pub group: String,
+    /// `None` when left to the system
+    pub enabled: Option<bool>,
+    /// A [`TurboReason`], or where a fixed setting came from
+    pub reason: String,
+    /// Load the decision used, per the profile's `load_metric`
+    pub load_percent: Option<f32>,
+    pub temperature_celsius: Option<f32>,
+    /// The `turbo_auto_settings.strategy` of an auto turbo decision
+    #[serde(default)]
+    pub strategy: Option<String>,
+}
+
+impl fmt::Display for TurboTrace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = self.enabled.map_or("system default", on_off);
+        write!(f, "{}: {state} ({})", self.group, self.reason)
     }
+}
 
-    // Determine AC/Battery status once, early in the function
-    // For desktops (no batteries), we should always use the AC power profile
-    // For laptops, we check if all batteries report connected to AC
-    let on_ac_power = if report.batteries.is_empty() {
-        // No batteries means desktop/server, always on AC
-        true
-    } else {
-        // Check if all batteries report AC connected
-        report.batteries.iter().all(|b| b.ac_connected)
-    };
+/// What the daemon last asked of the tracked knobs, to tell its own changes
+/// from external ones
+#[derive(Default)]
+struct DriftBook {
+    /// Profile applied in the last cycle
+    profile: Option<&'static str>,
+    /// Value each tracked knob was last asked to take
+    desired: BTreeMap<String, String>,
+    /// Knobs changed by hand and left alone until the next profile switch,
+    /// config reload or reset, by `respect`
+    released: BTreeSet<String>,
+    /// Knobs currently differing from the profile and left alone
+    drifted: BTreeMap<String, DriftedKnob>,
+}
 
-    let selected_profile_config: &ProfileConfig;
+/// A file a knob wrote this cycle, and what it held before
+struct JournalEntry {
+    step: ApplyStep,
+    knob: String,
+    write: WriteRecord,
+}
 
-    if let Some(mode) = force_mode {
-        match mode {
-            OperationalMode::Powersave => {
-                info!("Forced Powersave mode selected. Applying 'battery' profile.");
-                selected_profile_config = &config.battery;
-            }
-            OperationalMode::Performance => {
-                info!("Forced Performance mode selected. Applying 'charger' profile.");
-                selected_profile_config = &config.charger;
-            }
-        }
-    } else {
-        // Use the previously computed on_ac_power value
-        if on_ac_power {
-            info!("On AC power, selecting Charger profile.");
-            selected_profile_config = &config.charger;
-        } else {
-            info!("On Battery power, selecting Battery profile.");
-            selected_profile_config = &config.battery;
+/// Per-knob bookkeeping across cycles
+///
+/// Tracks failing knobs, so one that is not supported or keeps failing the
+/// same way is parked with a single warning instead of logging it every cycle.
+/// A parked knob is tried again after [`KNOB_RETRY_INTERVAL`], or on the next
+/// cycle after [`Self::reset`], in case a module got loaded in the meantime.
+///
+/// Also tracks what each knob was last set to, so a value changed by someone
+/// else is handled according to the configured [`DriftPolicy`].
+#[derive(Default)]
+pub struct KnobStates {
+    knobs: Mutex<BTreeMap<String, KnobFailure>>,
+    drift: Mutex<DriftBook>,
+    /// Why CPU frequency control is unavailable, if it is
+    cpu_control: Mutex<Option<String>>,
+    /// What the current or last cycle did to each knob
+    summary: Mutex<ApplySummary>,
+    /// Files written this cycle, in order, to roll them back if the cycle
+    /// fails
+    journal: Mutex<Vec<JournalEntry>>,
+    /// When each step last wrote a file, for `daemon.min_write_interval_sec`
+    written_at: Mutex<BTreeMap<ApplyStep, Instant>>,
+    /// Decision logged last in each category, so a repeat is only traced
+    logged: Mutex<BTreeMap<String, String>>,
+    /// Log every decision at info level, per `daemon.log_every_cycle`
+    log_every_cycle: AtomicBool,
+    /// Decisions of the last cycles, newest last
+    traces: Mutex<VecDeque<DecisionTrace>>,
+    /// The last decision that differed from the one before it
+    decision_change: Mutex<Option<DecisionTrace>>,
+}
+
+impl KnobStates {
+    /// Forgets all failures and external changes, so the next cycle applies
+    /// the whole profile and tries parked knobs again, e.g. after a config
+    /// reload or resume
+    pub fn reset(&self) {
+        let mut knobs = self.knobs.lock().unwrap_or_else(PoisonError::into_inner);
+        let parked = knobs.values().filter(|f| f.parked_at.is_some()).count();
+        if parked > 0 {
+            debug!("Trying {parked} parked knobs again");
         }
+        knobs.clear();
+
+        *self.drift.lock().unwrap_or_else(PoisonError::into_inner) = DriftBook::default();
+        self.logged
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clear();
     }
 
-    // Apply settings from selected_profile_config
-    if let Some(governor) = &selected_profile_config.governor {
-        info!("Setting governor to '{governor}'");
-        // Let set_governor handle the validation
-        if let Err(e) = cpu::set_governor(governor, None) {
-            // If the governor is not available, log a warning
-            if matches!(e, ControlError::InvalidGovernor(_))
-                || matches!(e, ControlError::NotSupported(_))
-            {
-                warn!(
-                    "Configured governor '{governor}' is not available on this system. Skipping."
-                );
-            } else {
-                return Err(e.into());
-            }
-        }
+    /// Knobs changed by someone else and left alone, by name
+    pub fn drifted(&self) -> Vec<DriftedKnob> {
+        self.drift
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .drifted
+            .values()
+            .cloned()
+            .collect()
     }
 
-    if let Some(turbo_setting) = selected_profile_config.turbo {
-        info!("Setting turbo to '{turbo_setting:?}'");
-        match turbo_setting {
-            TurboSetting::Auto => {
-                if selected_profile_config.enable_auto_turbo {
-                    debug!("Managing turbo in auto mode based on system conditions");
-                    manage_auto_turbo(report, selected_profile_config, on_ac_power)?;
-                } else {
-                    debug!(
-                        "Watt's dynamic turbo management is disabled by configuration. Ensuring system uses its default behavior for automatic turbo control."
-                    );
-                    // Make sure the system is set to its default automatic turbo mode.
-                    // This is important if turbo was previously forced off.
-                    try_apply_feature("Turbo boost", "system default (Auto)", || {
-                        cpu::set_turbo(TurboSetting::Auto)
-                    })?;
-                }
-            }
-            _ => {
-                try_apply_feature("Turbo boost", &format!("{turbo_setting:?}"), || {
-                    cpu::set_turbo(turbo_setting)
-                })?;
-            }
-        }
+    /// Knobs changed by hand and no longer managed until the next profile
+    /// switch, config reload or reset, by name
+    pub fn paused(&self) -> Vec<String> {
+        self.drift
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .released
+            .iter()
+            .cloned()
+            .collect()
+    }
+
+    /// Why the last cycles decided what they did, oldest first
+    pub fn decision_traces(&self) -> Vec<DecisionTrace> {
+        self.traces
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+            .cloned()
+            .collect()
     }
 
-    if let Some(epp) = &selected_profile_config.epp {
-        try_apply_feature("EPP", epp, || cpu::set_epp(epp, None))?;
+    /// The last decision that changed the profile or a turbo state
+    pub fn last_decision_change(&self) -> Option<DecisionTrace> {
+        self.decision_change
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
     }
 
-    if let Some(epb) = &selected_profile_config.epb {
-        try_apply_feature("EPB", epb, || cpu::set_epb(epb, None))?;
+    /// Notes an override in effect for the decision of the current cycle,
+    /// for ones the engine cannot see, like a timed override
+    pub fn note_override(&self, description: String) {
+        self.update_trace(|trace| trace.overrides.push(description));
     }
 
-    if let Some(min_freq) = selected_profile_config.min_freq_mhz {
-        try_apply_feature("min frequency", &format!("{min_freq} MHz"), || {
-            cpu::set_min_frequency(min_freq, None)
-        })?;
+    fn begin_trace(&self, trace: DecisionTrace) {
+        let mut traces = self.traces.lock().unwrap_or_else(PoisonError::into_inner);
+        if traces.len() == MAX_DECISION_TRACES {
+            traces.pop_front();
+        }
+        traces.push_back(trace);
     }
 
-    if let Some(max_freq) = selected_profile_config.max_freq_mhz {
-        try_apply_feature("max frequency", &format!("{max_freq} MHz"), || {
-            cpu::set_max_frequency(max_freq, None)
-        })?;
+    fn update_trace(&self, update: impl FnOnce(&mut DecisionTrace)) {
+        if let Some(trace) = self
+            .traces
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .back_mut()
+        {
+            update(trace);
+        }
     }
 
-    if let Some(profile) = &selected_profile_config.platform_profile {
-        try_apply_feature("platform profile", profile, || {
-            cpu::set_platform_profile(profile)
-        })?;
+    /// Remembers the current cycle's decision if it differs from the last one
+    fn finish_trace(&self) {
+        let traces = self.traces.lock().unwrap_or_else(PoisonError::into_inner);
+        let Some(latest) = traces.back() else {
+            return;
+        };
+        let mut change = self
+            .decision_change
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let previous = traces.len().checked_sub(2).and_then(|i| traces.get(i));
+        if change.is_none() || previous.is_none_or(|previous| !previous.same_decision(latest)) {
+            *change = Some(latest.clone());
+        }
     }
 
-    // Set battery charge thresholds if configured
-    if let Some(thresholds) = &selected_profile_config.battery_charge_thresholds {
-        let start_threshold = thresholds.start;
-        let stop_threshold = thresholds.stop;
+    /// What the last cycle did to each knob
+    pub fn last_summary(&self) -> ApplySummary {
+        self.summary
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
 
-        if start_threshold < stop_threshold && stop_threshold <= 100 {
-            info!("Setting battery charge thresholds: {start_threshold}-{stop_threshold}%");
-            match battery::set_battery_charge_thresholds(start_threshold, stop_threshold) {
-                Ok(()) => debug!("Battery charge thresholds set successfully"),
-                Err(e) => warn!("Failed to set battery charge thresholds: {e}"),
-            }
-        } else {
-            warn!(
-                "Invalid battery threshold values: start={start_threshold}, stop={stop_threshold}"
-            );
+    /// The level to log `decision` in `category` at: info when it differs
+    /// from the decision logged last, or trace for a repeat. With
+    /// `daemon.log_every_cycle`, always info.
+    fn decision_level(&self, category: &str, decision: &str) -> Level {
+        let mut logged = self.logged.lock().unwrap_or_else(PoisonError::into_inner);
+        if logged.get(category).map(String::as_str) == Some(decision)
+            && !self.log_every_cycle.load(Ordering::Relaxed)
+        {
+            return Level::Trace;
         }
+        logged.insert(category.to_string(), decision.to_string());
+        Level::Info
     }
 
-    debug!("Profile settings applied successfully.");
+    fn record_outcome(&self, knob: &str, outcome: KnobOutcome) {
+        self.summary
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .record(knob, outcome);
+    }
 
-    Ok(())
-}
+    /// Starts a cycle applying `profile`. Switching profiles hands every knob
+    /// left alone back to the profile.
+    fn begin_cycle(&self, profile: &'static str) {
+        *self.summary.lock().unwrap_or_else(PoisonError::into_inner) = ApplySummary {
+            profile: profile.to_string(),
+            knobs: Vec::new(),
+        };
+        self.journal
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clear();
+        let mut drift = self.drift.lock().unwrap_or_else(PoisonError::into_inner);
+        if drift.profile != Some(profile) {
+            *drift = DriftBook {
+                profile: Some(profile),
+                ..DriftBook::default()
+            };
+        }
+    }
 
-fn manage_auto_turbo(
-    report: &SystemReport,
-    config: &ProfileConfig,
-    on_ac_power: bool,
-) -> Result<(), EngineError> {
-    // Get the auto turbo settings from the config
-    let turbo_settings = &config.turbo_auto_settings;
+    /// Notes the files `knob` of `step` wrote this cycle, so
+    /// [`Self::roll_back`] can restore them
+    fn journal(&self, step: ApplyStep, knob: &str, writes: &[WriteRecord]) {
+        self.journal
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .extend(
+                writes
+                    .iter()
+                    .filter(|write| {
+                        write.outcome == WriteOutcome::Written && write.old_value.is_some()
+                    })
+                    .map(|write| JournalEntry {
+                        step,
+                        knob: knob.to_string(),
+                        write: write.clone(),
+                    }),
+            );
+    }
 
-    // Validate the complete configuration to ensure it's usable
-    validate_turbo_auto_settings(turbo_settings)?;
+    /// Number of files written this cycle
+    fn journal_len(&self) -> usize {
+        self.journal
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .len()
+    }
 
-    // Get average CPU temperature and CPU load
-    let cpu_temp = report.cpu_global.average_temperature_celsius;
+    /// How long ago `step` last wrote a file, if less than `interval` ago
+    fn written_within(&self, step: ApplyStep, interval: Duration) -> Option<Duration> {
+        self.written_at
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(&step)
+            .map(Instant::elapsed)
+            .filter(|elapsed| *elapsed < interval)
+    }
 
-    // Check if we have CPU usage data available
-    let avg_cpu_usage = if report.cpu_cores.is_empty() {
-        None
-    } else {
-        let sum: f32 = report
-            .cpu_cores
-            .iter()
-            .filter_map(|core| core.usage_percent)
-            .sum();
-        let count = report
-            .cpu_cores
-            .iter()
-            .filter(|core| core.usage_percent.is_some())
-            .count();
+    fn record_write_time(&self, step: ApplyStep) {
+        self.written_at
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(step, Instant::now());
+    }
 
-        if count > 0 {
-            Some(sum / count as f32)
-        } else {
-            None
+    /// Writes back what the files written this cycle held before through
+    /// `controller`, newest first, and marks their knobs as rolled back.
+    /// Returns the knobs rolled back with their steps. A file failing to roll
+    /// back is logged and leaves its knob as it was reported.
+    fn roll_back(&self, controller: &dyn PowerController) -> Vec<(ApplyStep, String)> {
+        let journal =
+            std::mem::take(&mut *self.journal.lock().unwrap_or_else(PoisonError::into_inner));
+        // Knobs with how many of their files were restored, and whether any failed
+        let mut restored: Vec<(ApplyStep, String, usize, bool)> = Vec::new();
+        for JournalEntry { step, knob, write } in journal.iter().rev() {
+            let Some(old_value) = &write.old_value else {
+                continue;
+            };
+            let result = controller.restore_file(&write.path, old_value);
+            if let Err(e) = &result {
+                warn!(
+                    "Failed to roll back {knob} at {} to '{old_value}': {e}",
+                    write.path.display()
+                );
+            }
+            match restored.iter_mut().find(|(_, name, _, _)| name == knob) {
+                Some((_, _, files, failed)) => {
+                    *files += 1;
+                    *failed |= result.is_err();
+                }
+                None => restored.push((*step, knob.clone(), 1, result.is_err())),
+            }
         }
-    };
 
-    // Get the previous state or initialize with the configured initial state
-    let previous_turbo_enabled = {
-        let turbo_states = get_turbo_states();
-        let hysteresis = turbo_states.get_for_power_state(on_ac_power);
-        if let Some(state) = hysteresis.get_previous_state() {
-            state
-        } else {
-            // Initialize with the configured initial state and return it
-            hysteresis.initialize_with(turbo_settings.initial_turbo_state)
+        let mut rolled_back = Vec::new();
+        for (step, knob, files, failed) in restored {
+            // Holds the old value again, which is not an external change
+            self.forget_desired(&knob);
+            if failed {
+                continue;
+            }
+            // The knob that failed keeps saying why
+            let mut summary = self.summary.lock().unwrap_or_else(PoisonError::into_inner);
+            let failed_itself = summary.knobs.iter().any(|result| {
+                result.knob == knob && matches!(result.outcome, KnobOutcome::Failed { .. })
+            });
+            if !failed_itself {
+                summary.record(&knob, KnobOutcome::RolledBack { files });
+            }
+            rolled_back.push((step, knob));
         }
-    };
+        rolled_back
+    }
 
-    // Decision logic for enabling/disabling turbo with hysteresis
-    let enable_turbo = match (cpu_temp, avg_cpu_usage, previous_turbo_enabled) {
-        // If temperature is too high, disable turbo regardless of load
-        (Some(temp), _, _) if temp >= turbo_settings.temp_threshold_high => {
-            info!(
-                "Auto Turbo: Disabled due to high temperature ({:.1}°C >= {:.1}°C)",
-                temp, turbo_settings.temp_threshold_high
-            );
-            false
-        }
+    /// Makes the next cycle write `knob` as if the profile just asked for it
+    fn forget_desired(&self, knob: &str) {
+        self.drift
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .desired
+            .remove(knob);
+    }
 
-        // If load is high enough, enable turbo (unless temp already caused it to disable)
-        (_, Some(usage), _) if usage >= turbo_settings.load_threshold_high => {
-            info!(
-                "Auto Turbo: Enabled due to high CPU load ({:.1}% >= {:.1}%)",
-                usage, turbo_settings.load_threshold_high
-            );
-            true
-        }
+    /// Whether `knob` should be written this cycle, given the `current` value
+    /// found on the system, if known
+    ///
+    /// A knob whose desired value changed since the last cycle is always
+    /// written, since the change is the daemon's own. Otherwise only a knob
+    /// that drifted away from `desired` needs writing, and `policy` decides
+    /// whether it is. In a dry run nothing was written, so a difference is
+    /// not drift.
+    fn should_reassert(
+        &self,
+        policy: DriftPolicy,
+        dry_run: bool,
+        knob: &str,
+        desired: &str,
+        current: Option<&str>,
+    ) -> bool {
+        let mut drift = self.drift.lock().unwrap_or_else(PoisonError::into_inner);
 
-        // If load is low, disable turbo
-        (_, Some(usage), _) if usage <= turbo_settings.load_threshold_low => {
-            info!(
-                "Auto Turbo: Disabled due to low CPU load ({:.1}% <= {:.1}%)",
-                usage, turbo_settings.load_threshold_low
-            );
-            false
+        if drift.desired.get(knob).map(String::as_str) != Some(desired) {
+            drift.desired.insert(knob.to_string(), desired.to_string());
+            drift.released.remove(knob);
+            drift.drifted.remove(knob);
+            return true;
         }
 
-        // In intermediate load range, maintain previous state (hysteresis)
-        (_, Some(usage), prev_state)
-            if usage > turbo_settings.load_threshold_low
-                && usage < turbo_settings.load_threshold_high =>
-        {
-            info!(
-                "Auto Turbo: Maintaining previous state ({}) due to intermediate load ({:.1}%)",
-                if prev_state { "enabled" } else { "disabled" },
-                usage
+        // Without a value to compare, writing is left to the sysfs layer,
+        // which skips values already in place
+        let Some(current) = current else {
+            return true;
+        };
+        if current == desired {
+            drift.drifted.remove(knob);
+            self.record_outcome(
+                knob,
+                KnobOutcome::Unchanged {
+                    value: desired.to_string(),
+                },
             );
-            prev_state
+            return false;
+        }
+        if dry_run {
+            return true;
         }
 
-        // When CPU load data is present but temperature is missing, use the same hysteresis logic
-        (None, Some(usage), prev_state) => {
-            info!(
-                "Auto Turbo: Maintaining previous state ({}) due to missing temperature data (load: {:.1}%)",
-                if prev_state { "enabled" } else { "disabled" },
-                usage
-            );
-            prev_state
+        let drifted = DriftedKnob {
+            knob: knob.to_string(),
+            current: current.to_string(),
+            desired: desired.to_string(),
+        };
+        match policy {
+            DriftPolicy::Correct => {
+                info!(
+                    event = "drift_corrected", knob = knob, old = current, new = desired;
+                    "Correcting external change to {knob}: {current} → {desired}"
+                );
+                true
+            }
+            DriftPolicy::Warn => {
+                if drift.drifted.get(knob).is_none_or(|d| d.current != current) {
+                    warn!(
+                        event = "drift_detected", knob = knob, old = desired, new = current;
+                        "{knob} was changed externally: {desired} → {current}. Leaving it, as drift_policy is \"warn\""
+                    );
+                }
+                drift.drifted.insert(knob.to_string(), drifted);
+                self.record_outcome(knob, external_change(current));
+                false
+            }
+            DriftPolicy::Respect => {
+                if drift.released.insert(knob.to_string()) {
+                    info!(
+                        event = "drift_respected", knob = knob, old = desired, new = current;
+                        "{knob} was changed by hand: {desired} → {current}. No longer managing it until the next profile switch, config reload or reset"
+                    );
+                }
+                drift.drifted.insert(knob.to_string(), drifted);
+                self.record_outcome(knob, external_change(current));
+                false
+            }
         }
+    }
 
-        // When all metrics are missing, maintain the previous state
-        (None, None, prev_state) => {
-            info!(
-                "Auto Turbo: Maintaining previous state ({}) due to missing all CPU metrics",
-                if prev_state { "enabled" } else { "disabled" }
-            );
-            prev_state
+    /// Records whether CPU frequency control is available, with the reason if
+    /// not. While it is unavailable the CPU knobs are skipped, and a change
+    /// either way is logged once.
+    pub fn set_cpu_control_unavailable(&self, reason: Option<String>) {
+        let mut cpu_control = self
+            .cpu_control
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if *cpu_control == reason {
+            return;
         }
 
-        // Any other cases with partial metrics, maintain previous state for stability
-        (_, _, prev_state) => {
-            info!(
-                "Auto Turbo: Maintaining previous state ({}) due to incomplete CPU metrics",
-                if prev_state { "enabled" } else { "disabled" }
-            );
-            prev_state
+        match (&*cpu_control, &reason) {
+            (_, Some(reason)) => warn!(
+                event = "cpu_control_unavailable", reason = reason.as_str();
+                "CPU frequency control is unavailable, only monitoring and managing battery thresholds: {reason}"
+            ),
+            (Some(_), None) => info!(
+                event = "cpu_control_available";
+                "CPU frequency control is available now"
+            ),
+            (None, None) => {}
         }
-    };
+        *cpu_control = reason;
+    }
 
-    // Save the current state for next time
-    {
-        let turbo_states = get_turbo_states();
-        let hysteresis = turbo_states.get_for_power_state(on_ac_power);
-        hysteresis.update_state(enable_turbo);
+    /// Why CPU frequency control is unavailable, or `None` if it is available
+    pub fn cpu_control_unavailable(&self) -> Option<String> {
+        self.cpu_control
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Knobs currently parked, by name
+    pub fn parked(&self) -> Vec<ParkedKnob> {
+        let retry_interval =
+            jiff::SignedDuration::try_from(KNOB_RETRY_INTERVAL).unwrap_or_default();
+        self.knobs
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+            .filter_map(|(knob, failure)| {
+                let since = failure.parked_at?;
+                Some(ParkedKnob {
+                    knob: knob.clone(),
+                    error: failure.error.clone(),
+                    since,
+                    retry_at: since.saturating_add(retry_interval).unwrap_or(since),
+                })
+            })
+            .collect()
+    }
+
+    /// Whether `knob` should be written this cycle
+    fn should_attempt(&self, knob: &str) -> bool {
+        let knobs = self.knobs.lock().unwrap_or_else(PoisonError::into_inner);
+        let Some(parked_at) = knobs.get(knob).and_then(|f| f.parked_at) else {
+            return true;
+        };
+
+        let parked_for = jiff::Timestamp::now().duration_since(parked_at);
+        if parked_for < jiff::SignedDuration::try_from(KNOB_RETRY_INTERVAL).unwrap_or_default() {
+            debug!("Skipping parked {knob}");
+            let error = knobs.get(knob).map(|f| f.error.clone()).unwrap_or_default();
+            drop(knobs);
+            self.record_outcome(
+                knob,
+                KnobOutcome::Skipped {
+                    reason: format!("parked: {error}"),
+                },
+            );
+            return false;
+        }
+        debug!("Trying parked {knob} again");
+        true
+    }
+
+    fn record_success(&self, knob: &str) {
+        let failure = self
+            .knobs
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(knob);
+        if failure.is_some_and(|f| f.parked_at.is_some()) {
+            info!(event = "knob_unparked", knob = knob; "{knob} works again");
+        }
+    }
+
+    /// Records a failed write, returning whether the knob is parked now
+    fn record_failure(&self, knob: &str, error: &str, unsupported: bool) -> bool {
+        let mut knobs = self.knobs.lock().unwrap_or_else(PoisonError::into_inner);
+        let failure = knobs
+            .entry(knob.to_string())
+            .or_insert_with(|| KnobFailure {
+                error: error.to_string(),
+                count: 0,
+                parked_at: None,
+            });
+        if failure.error != error {
+            failure.error = error.to_string();
+            failure.count = 0;
+        }
+        failure.count += 1;
+
+        let was_parked = failure.parked_at.is_some();
+        if !was_parked && !unsupported && failure.count < KNOB_FAILURE_LIMIT {
+            return false;
+        }
+
+        failure.parked_at = Some(jiff::Timestamp::now());
+        if was_parked {
+            debug!("{knob} still fails: {error}");
+        } else {
+            warn!(
+                event = "knob_parked", knob = knob, error = error;
+                "{knob} {}: {error}. Not trying it again for {} minutes",
+                if unsupported { "is not supported" } else { "keeps failing" },
+                KNOB_RETRY_INTERVAL.as_secs() / 60
+            );
+        }
+        true
+    }
+}
+
+/// A knob left as someone else set it, per the drift policy
+fn external_change(current: &str) -> KnobOutcome {
+    KnobOutcome::Skipped {
+        reason: format!("changed externally to {current}"),
+    }
+}
+
+/// Track turbo boost state per profile, for AC and battery power
+///
+/// Owned by the daemon and handed to [`determine_and_apply_settings`], so the
+/// state can be saved and restored across restarts. Also holds the state of
+/// `epp = "auto"`, which is not saved.
+#[derive(Default)]
+pub struct TurboStates {
+    /// Auto turbo state of each profile and power state
+    hysteresis: HysteresisMap,
+    epp_charger: Mutex<AutoEpp>,
+    epp_battery: Mutex<AutoEpp>,
+    governor_charger: Mutex<AutoGovernor>,
+    governor_battery: Mutex<AutoGovernor>,
+    thermal_charger: Mutex<ThermalCap>,
+    thermal_battery: Mutex<ThermalCap>,
+    /// Turbo state of each core group, when turbo is decided per group
+    groups: Mutex<BTreeMap<&'static str, HysteresisMap>>,
+    /// Set whenever either state changes, until taken by [`Self::take_changed`]
+    changed: AtomicBool,
+    /// The last turbo change applied and its reason, until taken by
+    /// [`Self::take_last_change`]
+    last_change: Mutex<Option<(bool, &'static str)>>,
+    /// Whether the thermal safety net holds the CPU at its most conservative
+    /// settings. Survives [`Self::reset`], so it only lets go once the CPU
+    /// cooled down.
+    thermal_safety: AtomicBool,
+    /// Temperature the thermal safety net last engaged at, until taken by
+    /// [`Self::take_thermal_safety_engagement`]
+    thermal_safety_engagement: Mutex<Option<f32>>,
+    /// Max frequency being stepped up per `transition_ramp_sec`
+    ramp: Mutex<Option<RampState>>,
+}
+
+impl TurboStates {
+    /// Forget both states, e.g. after the system resumed and the previous
+    /// turbo state can no longer be trusted
+    pub fn reset(&self) {
+        self.hysteresis.clear();
+        self.groups
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clear();
+        for epp in [&self.epp_charger, &self.epp_battery] {
+            epp.lock().unwrap_or_else(PoisonError::into_inner).reset();
+        }
+        for governor in [&self.governor_charger, &self.governor_battery] {
+            *governor.lock().unwrap_or_else(PoisonError::into_inner) = AutoGovernor::default();
+        }
+        // A cap in place stays known, so it is lifted once the CPU cools down
+        for thermal in [&self.thermal_charger, &self.thermal_battery] {
+            thermal
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .window
+                .clear();
+        }
+        // The next raise starts over from the max frequency in place
+        *self.ramp.lock().unwrap_or_else(PoisonError::into_inner) = None;
+        self.changed.store(true, Ordering::Release);
+    }
+
+    const fn get_governor_for_power_state(&self, is_on_ac: bool) -> &Mutex<AutoGovernor> {
+        if is_on_ac {
+            &self.governor_charger
+        } else {
+            &self.governor_battery
+        }
+    }
+
+    const fn get_thermal_for_power_state(&self, is_on_ac: bool) -> &Mutex<ThermalCap> {
+        if is_on_ac {
+            &self.thermal_charger
+        } else {
+            &self.thermal_battery
+        }
+    }
+
+    const fn get_epp_for_power_state(&self, is_on_ac: bool) -> &Mutex<AutoEpp> {
+        if is_on_ac {
+            &self.epp_charger
+        } else {
+            &self.epp_battery
+        }
+    }
+
+    /// Turbo state remembered for AC and battery power, in that order, of
+    /// the profile used last on each. `None` means no decision has been made
+    /// since startup or the last reset.
+    pub fn previous_states(&self) -> (Option<bool>, Option<bool>) {
+        self.hysteresis.previous_states()
+    }
+
+    /// Turbo state remembered for AC and battery power per core group, for
+    /// the groups turbo has been decided for separately
+    pub fn group_states(&self) -> Vec<(String, Option<bool>, Option<bool>)> {
+        self.groups
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+            .map(|(group, state)| {
+                let (on_ac, on_battery) = state.previous_states();
+                ((*group).to_string(), on_ac, on_battery)
+            })
+            .collect()
+    }
+
+    /// Returns whether either state changed since the last call, i.e. whether
+    /// it needs saving
+    pub fn take_changed(&self) -> bool {
+        self.changed.swap(false, Ordering::AcqRel)
+    }
+
+    /// Returns the last turbo change applied since the last call, as whether
+    /// turbo got enabled and the reason of the decision
+    pub fn take_last_change(&self) -> Option<(bool, &'static str)> {
+        self.last_change
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take()
+    }
+
+    /// The max frequency ramp in progress, if any
+    pub fn frequency_ramp(&self) -> Option<FrequencyRamp> {
+        self.ramp
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .as_ref()
+            .map(|state| state.ramp)
+    }
+
+    /// Returns the temperature the thermal safety net engaged at, if it
+    /// engaged since the last call
+    pub fn take_thermal_safety_engagement(&self) -> Option<f32> {
+        self.thermal_safety_engagement
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take()
+    }
+
+    /// Records turbo as disabled in every hysteresis state, after something
+    /// other than auto turbo turned it off
+    fn mark_turbo_disabled(&self) {
+        let changed = self.hysteresis.mark_disabled();
+        for group in self
+            .groups
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .values()
+        {
+            group.mark_disabled();
+        }
+        if changed {
+            self.changed.store(true, Ordering::Release);
+        }
+    }
+
+    /// Loads the state saved by a previous daemon. A missing, unreadable or
+    /// stale file gives fresh states that start from `initial_turbo_state`.
+    pub fn load(path: &Path) -> Self {
+        let states = Self::default();
+
+        let saved: SavedTurboStates = match fs::read_to_string(path) {
+            Ok(contents) => match serde_json::from_str(&contents) {
+                Ok(saved) => saved,
+                Err(e) => {
+                    warn!("Ignoring invalid turbo state at {}: {e}", path.display());
+                    return states;
+                }
+            },
+            Err(e) => {
+                if e.kind() != io::ErrorKind::NotFound {
+                    warn!("Failed to read turbo state at {}: {e}", path.display());
+                }
+                return states;
+            }
+        };
+
+        let age = jiff::Timestamp::now().duration_since(saved.saved_at);
+        if age > jiff::SignedDuration::try_from(TURBO_STATE_MAX_AGE).unwrap_or_default() {
+            debug!("Ignoring turbo state saved {age:#} ago");
+            return states;
+        }
+
+        // Written by older versions, which only knew the built-in profiles
+        if let Some(saved) = saved.charger {
+            states.hysteresis.restore("charger", true, saved);
+        }
+        if let Some(saved) = saved.battery {
+            states.hysteresis.restore("battery", false, saved);
+        }
+        for saved in saved.profiles {
+            states.hysteresis.restore(
+                intern_profile_name(&saved.profile),
+                saved.on_ac,
+                saved.state,
+            );
+        }
+        info!(
+            "Restored turbo state from {}: {:?}",
+            path.display(),
+            states.previous_states()
+        );
+        states
+    }
+
+    /// Writes the state of every profile to `path`, replacing the file
+    /// atomically
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let saved = SavedTurboStates {
+            saved_at: jiff::Timestamp::now(),
+            charger: None,
+            battery: None,
+            profiles: self.hysteresis.saved(),
+        };
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut tmp_path = path.as_os_str().to_owned();
+        tmp_path.push(".tmp");
+        fs::write(&tmp_path, serde_json::to_vec(&saved)?)?;
+        fs::rename(&tmp_path, path)
+    }
+}
+
+/// The last few readings of one power state, such as CPU usage or
+/// temperature. Missing readings take up their slot without counting, so they
+/// shrink the window rather than skew it.
+struct SampleWindow<K> {
+    /// Profile and settings the samples were taken under. Samples from
+    /// another profile or an older configuration are dropped.
+    key: Option<(&'static str, K)>,
+    samples: VecDeque<Option<f32>>,
+}
+
+impl<K> Default for SampleWindow<K> {
+    fn default() -> Self {
+        Self {
+            key: None,
+            samples: VecDeque::new(),
+        }
+    }
+}
+
+impl<K: Clone + PartialEq> SampleWindow<K> {
+    fn clear(&mut self) {
+        self.key = None;
+        self.samples.clear();
+    }
+
+    /// Drops the samples unless they were taken under `profile` and
+    /// `settings`. Returns whether samples of another profile or
+    /// configuration were dropped.
+    fn rekey(&mut self, profile: &'static str, settings: &K) -> bool {
+        if self
+            .key
+            .as_ref()
+            .is_some_and(|(key_profile, key_settings)| {
+                *key_profile == profile && key_settings == settings
+            })
+        {
+            return false;
+        }
+        let replaced = self.key.replace((profile, settings.clone())).is_some();
+        self.samples.clear();
+        replaced
+    }
+
+    /// Adds `value` and returns the average of the last `size` samples, or
+    /// the `percentile` of them. `None` if no sample in the window is known.
+    fn push(&mut self, value: Option<f32>, size: usize, percentile: Option<f32>) -> Option<f32> {
+        self.samples.push_back(value);
+        while self.samples.len() > size.max(1) {
+            self.samples.pop_front();
+        }
+        window_value(self.samples.iter(), percentile)
+    }
+
+    /// What [`Self::push`] would return for `value` under `profile` and
+    /// `settings`, leaving the window as it is
+    fn peek(
+        &self,
+        profile: &'static str,
+        settings: &K,
+        value: Option<f32>,
+        size: usize,
+        percentile: Option<f32>,
+    ) -> Option<f32> {
+        let keyed = self
+            .key
+            .as_ref()
+            .is_some_and(|(key_profile, key_settings)| {
+                *key_profile == profile && key_settings == settings
+            });
+        let kept = if keyed { size.max(1) - 1 } else { 0 };
+        let older = self
+            .samples
+            .iter()
+            .skip(self.samples.len().saturating_sub(kept));
+        window_value(older.chain([&value]), percentile)
+    }
+}
+
+/// The average of the known `samples`, or the `percentile` of them
+fn window_value<'a>(
+    samples: impl Iterator<Item = &'a Option<f32>>,
+    percentile: Option<f32>,
+) -> Option<f32> {
+    let mut known: Vec<f32> = samples.flatten().copied().collect();
+    if known.is_empty() {
+        return None;
+    }
+    match percentile {
+        Some(percentile) => smoothing::percentile(&mut known, percentile),
+        None => Some(known.iter().sum::<f32>() / known.len() as f32),
+    }
+}
+
+/// State of `epp = "auto"` for one power state
+#[derive(Default)]
+struct AutoEpp {
+    window: SampleWindow<EppAutoSettings>,
+    /// Tier picked last, and when the EPP last moved
+    tier: Option<usize>,
+    changed_at: Option<Instant>,
+}
+
+impl AutoEpp {
+    fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
+
+/// State of `thermal_backoff` for one power state
+#[derive(Default)]
+struct ThermalCap {
+    /// Recent temperatures
+    window: SampleWindow<ThermalBackoff>,
+    /// Step whose cap is in place
+    step: Option<usize>,
+}
+
+/// State of `governor = "auto"` for one power state
+#[derive(Default)]
+struct AutoGovernor {
+    window: SampleWindow<GovernorAutoSettings>,
+    /// Whether the high load governor was picked last, and when the
+    /// governor last switched
+    high_load: Option<bool>,
+    switched_at: Option<Instant>,
+}
+
+/// A raised max frequency being stepped up to, per `transition_ramp_sec`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FrequencyRamp {
+    /// Max frequency in place when the ramp started, in MHz
+    pub from_mhz: u32,
+    /// Max frequency the profile asks for, in MHz
+    pub to_mhz: u32,
+    /// Max frequency written last, in MHz
+    pub current_mhz: u32,
+}
+
+impl FrequencyRamp {
+    /// How far the ramp got, in percent
+    pub fn percent(&self) -> u32 {
+        let span = self.to_mhz.saturating_sub(self.from_mhz).max(1);
+        self.current_mhz.saturating_sub(self.from_mhz) * 100 / span
+    }
+}
+
+/// State of a max frequency ramp
+struct RampState {
+    /// Profile the ramp is for, switching profiles cancels it
+    profile: &'static str,
+    ramp: FrequencyRamp,
+    started_at: Instant,
+    duration: Duration,
+}
+
+/// On-disk form of [`TurboStates`]
+#[derive(Debug, Serialize, Deserialize)]
+struct SavedTurboStates {
+    /// When the file was written, used to detect stale state
+    saved_at: jiff::Timestamp,
+    /// State of the charger profile on AC power, from older versions
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    charger: Option<SavedHysteresis>,
+    /// State of the battery profile on battery power, from older versions
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    battery: Option<SavedHysteresis>,
+    #[serde(default)]
+    profiles: Vec<SavedProfileHysteresis>,
+}
+
+/// Saved turbo state of a profile under a power state
+#[derive(Debug, Serialize, Deserialize)]
+struct SavedProfileHysteresis {
+    profile: String,
+    on_ac: bool,
+    state: SavedHysteresis,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+struct SavedHysteresis {
+    enabled: bool,
+    /// When turbo last switched to `enabled`, if known
+    changed_at: Option<jiff::Timestamp>,
+}
+
+/// Auto turbo states, one per profile and power state, created as they are
+/// first used. Profiles can have very different thresholds, so one does not
+/// carry over its previous state to another.
+#[derive(Default)]
+struct HysteresisMap {
+    entries: Mutex<BTreeMap<(&'static str, bool), HysteresisEntry>>,
+}
+
+struct HysteresisEntry {
+    hysteresis: Arc<TurboHysteresis>,
+    /// Settings the state was built up under, `None` if restored from disk
+    settings: Option<TurboAutoSettings>,
+    used_at: Instant,
+}
+
+impl HysteresisEntry {
+    fn new(settings: Option<TurboAutoSettings>) -> Self {
+        Self {
+            hysteresis: Arc::default(),
+            settings,
+            used_at: Instant::now(),
+        }
+    }
+}
+
+/// The fields that differ between `old` and `new`, as `name old → new`
+fn changed_settings(old: &TurboAutoSettings, new: &TurboAutoSettings) -> Vec<String> {
+    let (Ok(serde_json::Value::Object(old)), Ok(serde_json::Value::Object(new))) =
+        (serde_json::to_value(old), serde_json::to_value(new))
+    else {
+        return Vec::new();
+    };
+    new.iter()
+        .filter_map(|(name, value)| {
+            let before = old.get(name).unwrap_or(&serde_json::Value::Null);
+            (before != value).then(|| format!("{name} {before} → {value}"))
+        })
+        .collect()
+}
+
+impl HysteresisMap {
+    /// The state of `profile` under the power state, starting over when its
+    /// turbo settings changed, e.g. on a config reload
+    fn get(
+        &self,
+        profile: &'static str,
+        on_ac: bool,
+        settings: &TurboAutoSettings,
+    ) -> Arc<TurboHysteresis> {
+        let mut entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+        let key = (profile, on_ac);
+        if let Some(entry) = entries.get_mut(&key) {
+            match &entry.settings {
+                Some(known) if known != settings => {
+                    let changed = changed_settings(known, settings).join(", ");
+                    info!(
+                        event = "turbo_hysteresis_reset", profile = profile, on_ac = on_ac, changed = changed.as_str();
+                        "Auto Turbo: turbo_auto_settings of the {profile} profile changed ({changed}), starting over from initial_turbo_state {}",
+                        on_off(settings.initial_turbo_state)
+                    );
+                    *entry = HysteresisEntry::new(Some(settings.clone()));
+                }
+                Some(_) => {}
+                // Restored from disk, so adopt the settings in place
+                None => entry.settings = Some(settings.clone()),
+            }
+            entry.used_at = Instant::now();
+            return entry.hysteresis.clone();
+        }
+
+        // Profiles come from the configuration, but reloads can rename them
+        while entries.len() >= MAX_HYSTERESIS_STATES {
+            let Some(oldest) = entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.used_at)
+                .map(|(key, _)| *key)
+            else {
+                break;
+            };
+            entries.remove(&oldest);
+        }
+        entries
+            .entry(key)
+            .or_insert_with(|| HysteresisEntry::new(Some(settings.clone())))
+            .hysteresis
+            .clone()
+    }
+
+    fn restore(&self, profile: &'static str, on_ac: bool, saved: SavedHysteresis) {
+        let entry = HysteresisEntry::new(None);
+        entry.hysteresis.restore(saved);
+        self.entries
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert((profile, on_ac), entry);
+    }
+
+    fn clear(&self) {
+        self.entries
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clear();
+    }
+
+    /// The state of the profile used last on AC and on battery power
+    fn previous_states(&self) -> (Option<bool>, Option<bool>) {
+        let entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+        let latest = |on_ac: bool| {
+            entries
+                .iter()
+                .filter(|((_, entry_on_ac), _)| *entry_on_ac == on_ac)
+                .max_by_key(|(_, entry)| entry.used_at)
+                .and_then(|(_, entry)| entry.hysteresis.get_previous_state())
+        };
+        (latest(true), latest(false))
+    }
+
+    /// Records turbo as disabled in every state, returning whether any changed
+    fn mark_disabled(&self) -> bool {
+        let entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+        // Every state is updated, so nothing may stop at the first change
+        let mut changed = false;
+        for entry in entries.values() {
+            changed |= entry.hysteresis.update_state(false);
+        }
+        changed
+    }
+
+    fn saved(&self) -> Vec<SavedProfileHysteresis> {
+        self.entries
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+            .filter_map(|((profile, on_ac), entry)| {
+                entry
+                    .hysteresis
+                    .saved()
+                    .map(|state| SavedProfileHysteresis {
+                        profile: (*profile).to_string(),
+                        on_ac: *on_ac,
+                        state,
+                    })
+            })
+            .collect()
+    }
+}
+
+/// Manage turbo boost hysteresis state.
+/// Contains the state needed to implement hysteresis
+/// for the dynamic turbo management feature
+#[derive(Default)]
+struct TurboHysteresis {
+    /// Whether turbo was enabled in the previous cycle
+    previous_state: AtomicBool,
+    /// Whether the hysteresis state has been initialized
+    initialized: AtomicBool,
+    /// When the state last changed
+    last_change: Mutex<Option<jiff::Timestamp>>,
+    /// Recent CPU usage the load thresholds are compared against
+    load_window: Mutex<SampleWindow<TurboAutoSettings>>,
+    /// Whether turbo was held off by `min_battery_percent_for_turbo` in the
+    /// previous cycle
+    battery_limited: AtomicBool,
+    /// Since when the load has been high, for `duty_cycle`
+    high_load_since: Mutex<Option<Instant>>,
+}
+
+impl TurboHysteresis {
+    /// Get the previous turbo state, if initialized
+    fn get_previous_state(&self) -> Option<bool> {
+        if self.initialized.load(Ordering::Acquire) {
+            Some(self.previous_state.load(Ordering::Acquire))
+        } else {
+            None
+        }
+    }
+
+    /// Initialize the state with a specific value if not already initialized
+    /// Only one thread should be able to initialize the state
+    fn initialize_with(&self, initial_state: bool) -> bool {
+        // First, try to atomically change initialized from false to true
+        // Only one thread can win the initialization race
+        match self.initialized.compare_exchange(
+            false,             // expected: not initialized
+            true,              // desired: mark as initialized
+            Ordering::Release, // success: release for memory visibility
+            Ordering::Acquire, // failure: just need to acquire the current value
+        ) {
+            Ok(_) => {
+                // We won the race to initialize
+                // Now it's safe to set the initial state since we know we're the only
+                // thread that has successfully marked this as initialized
+                self.previous_state.store(initial_state, Ordering::Release);
+                initial_state
+            }
+            Err(_) => {
+                // Another thread already initialized it.
+                // Just read the current state value that was set by the winning thread
+                self.previous_state.load(Ordering::Acquire)
+            }
+        }
+    }
+
+    /// Turns a high load decision into the on or off phase of the profile's
+    /// `duty_cycle` once the load has been high for its `after_sec`. Any other
+    /// decision, such as a high temperature, ends the cycle.
+    fn duty_cycle(
+        &self,
+        settings: &TurboAutoSettings,
+        decision: (bool, TurboReason),
+        now: Instant,
+    ) -> (bool, TurboReason) {
+        let mut since = self
+            .high_load_since
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let Some(cycle) = settings
+            .duty_cycle
+            .filter(|_| decision.1 == TurboReason::HighLoad)
+        else {
+            *since = None;
+            return decision;
+        };
+        let high_for = now.duration_since(*since.get_or_insert(now)).as_secs();
+        let Some(cycling_for) = high_for.checked_sub(cycle.after_sec) else {
+            return decision;
+        };
+        if cycling_for % (cycle.on_sec + cycle.off_sec) < cycle.on_sec {
+            (true, TurboReason::DutyCycleOn)
+        } else {
+            (false, TurboReason::DutyCycleOff)
+        }
+    }
+
+    /// Adds the latest CPU usage to the load window and returns the usage
+    /// over the window
+    fn windowed_usage(
+        &self,
+        profile: &'static str,
+        settings: &TurboAutoSettings,
+        usage: Option<f32>,
+    ) -> Option<f32> {
+        let mut window = self
+            .load_window
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if window.rekey(profile, settings) {
+            debug!("Auto Turbo: Starting a new load window for the {profile} profile");
+        }
+        window.push(
+            usage,
+            settings.load_window_samples,
+            settings.load_window_percentile,
+        )
+    }
+
+    /// Update the turbo state for hysteresis, returning whether it changed
+    fn update_state(&self, new_state: bool) -> bool {
+        let changed = self.get_previous_state() != Some(new_state);
+        if changed {
+            *self
+                .last_change
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner) = Some(jiff::Timestamp::now());
+        }
+
+        // First store the new state, then mark as initialized
+        // With this, any thread seeing initialized=true will also see the correct state
+        self.previous_state.store(new_state, Ordering::Release);
+
+        // Already initialized, no need for compare_exchange
+        if self.initialized.load(Ordering::Relaxed) {
+            return changed;
+        }
+
+        // Otherwise, try to set initialized=true (but only if it was false)
+        self.initialized
+            .compare_exchange(
+                false,             // expected: not initialized
+                true,              // desired: mark as initialized
+                Ordering::Release, // success: release for memory visibility
+                Ordering::Relaxed, // failure: we don't care about the current value on failure
+            )
+            .ok(); // Ignore the result. If it fails, it means another thread already initialized it
+
+        changed
+    }
+
+    fn saved(&self) -> Option<SavedHysteresis> {
+        self.get_previous_state().map(|enabled| SavedHysteresis {
+            enabled,
+            changed_at: *self
+                .last_change
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner),
+        })
+    }
+
+    fn restore(&self, saved: SavedHysteresis) {
+        self.update_state(saved.enabled);
+        *self
+            .last_change
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = saved.changed_at;
+    }
+}
+
+/// Try applying a CPU feature and handle common error cases. Centralizes the where we
+/// previously did:
+/// 1. Skip the feature if it is parked in `knobs`
+/// 2. Try to apply a feature setting
+/// 3. If not supported, park it and continue
+/// 4. If other error, propagate the error, until the same error repeated often
+///    enough to park the feature
+fn try_apply_feature<F, T>(
+    knobs: &KnobStates,
+    step: ApplyStep,
+    feature_name: &str,
+    value_description: &str,
+    apply_fn: F,
+) -> Result<(), EngineError>
+where
+    F: FnOnce() -> Result<T, ControlError>,
+{
+    if !knobs.should_attempt(feature_name) {
+        // Not written, so a differing value is not someone else's doing
+        knobs.forget_desired(feature_name);
+        return Ok(());
+    }
+
+    log!(
+        knobs.decision_level(feature_name, value_description),
+        event = "apply_setting", knob = feature_name, value = value_description;
+        "Setting {feature_name} to '{value_description}'"
+    );
+
+    let (result, writes) = sysfs::capture_writes(apply_fn);
+    // Also when failing, since the knob may be left half-written
+    knobs.journal(step, feature_name, &writes);
+    match result {
+        Ok(_) => {
+            knobs.record_success(feature_name);
+            knobs.record_outcome(feature_name, outcome_of(&writes, value_description));
+            Ok(())
+        }
+        Err(e) => {
+            let e = e.with_knob(feature_name);
+            // The knob may be left half-written, which is not an external change
+            knobs.forget_desired(feature_name);
+
+            // A governor missing from the kernel will not show up by retrying
+            let unsupported = matches!(
+                e.root(),
+                ControlError::NotSupported(_) | ControlError::InvalidGovernor(_)
+            );
+            knobs.record_outcome(
+                feature_name,
+                if unsupported {
+                    KnobOutcome::Skipped {
+                        reason: format!("unsupported: {e}"),
+                    }
+                } else {
+                    KnobOutcome::Failed {
+                        error: e.to_string(),
+                    }
+                },
+            );
+            if knobs.record_failure(feature_name, &e.to_string(), unsupported) {
+                Ok(())
+            } else {
+                // Propagate all other errors, including InvalidValueError
+                Err(EngineError::ControlError(e))
+            }
+        }
+    }
+}
+
+/// How turbo and drift messages spell a turbo state
+const fn on_off(enabled: bool) -> &'static str {
+    if enabled { "enabled" } else { "disabled" }
+}
+
+/// Whether the system runs on AC power, as `ac_sources` or `ac_detection`
+/// decided when the report was collected
+pub fn is_on_ac_power(report: &SystemReport) -> bool {
+    report.ac_power.online
+}
+
+/// Returns the name of the profile `determine_and_apply_settings` would select
+/// for the given report and forced profile: "charger", "battery" or, only
+/// when forced, "balanced" or the name of a config profile
+pub fn selected_profile_name(report: &SystemReport, force: Option<&ForcedProfile>) -> &'static str {
+    match force {
+        Some(ForcedProfile::Mode(OperationalMode::Performance)) => "charger",
+        Some(ForcedProfile::Mode(OperationalMode::Powersave)) => "battery",
+        Some(ForcedProfile::Mode(OperationalMode::Balanced)) => "balanced",
+        Some(ForcedProfile::Named(name)) => intern_profile_name(name),
+        None if is_on_ac_power(report) => "charger",
+        None => "battery",
+    }
+}
+
+/// The `clamshell_profile` to force while the lid is closed with an external
+/// display connected, if one is configured
+pub fn clamshell_profile(report: &SystemReport, config: &AppConfig) -> Option<ForcedProfile> {
+    let name = config.clamshell_profile.as_deref()?;
+    if !report.lid.is_clamshell() {
+        return None;
+    }
+    name.parse().ok()
+}
+
+/// The load average `[load_profile]` compares, divided by the number of
+/// online CPUs with `normalize`. `None` if no CPU was counted.
+pub fn load_average(report: &SystemReport, settings: &LoadProfileSettings) -> Option<f32> {
+    let load = &report.system_load;
+    let average = match settings.average {
+        LoadAverage::Load1 => load.load_avg_1min,
+        LoadAverage::Load5 => load.load_avg_5min,
+        LoadAverage::Load15 => load.load_avg_15min,
+    };
+    if !settings.normalize {
+        return Some(average);
+    }
+    let cpus = report.cpu_cores.len();
+    (cpus > 0).then(|| average / cpus as f32)
+}
+
+/// The `[load_profile]` profile to force while the load average is above its
+/// threshold, if one is configured
+pub fn load_profile(report: &SystemReport, config: &AppConfig) -> Option<ForcedProfile> {
+    let settings = config.load_profile.as_ref()?;
+    if load_average(report, settings)? <= settings.above {
+        return None;
+    }
+    settings.profile.parse().ok()
+}
+
+/// Returns why `selected_profile_name` picks its profile, using the same
+/// reasons as the `profile_selected` log event
+pub fn selected_profile_reason(
+    report: &SystemReport,
+    force: Option<&ForcedProfile>,
+) -> &'static str {
+    match force {
+        Some(_) => "forced",
+        None if is_on_ac_power(report) => "ac_power",
+        None => "battery_power",
+    }
+}
+
+/// Profile names as `&'static str`, like the built-in ones. Each name is
+/// leaked once, and a config only defines a handful.
+fn intern_profile_name(name: &str) -> &'static str {
+    static NAMES: Mutex<BTreeSet<&'static str>> = Mutex::new(BTreeSet::new());
+    let mut names = NAMES.lock().unwrap_or_else(PoisonError::into_inner);
+    if let Some(interned) = names.get(name) {
+        return interned;
+    }
+    let interned: &'static str = Box::leak(name.to_string().into_boxed_str());
+    names.insert(interned);
+    interned
+}
+
+/// The `[profiles.<name>]` profile of `config`
+pub fn named_profile<'a>(
+    config: &'a AppConfig,
+    name: &str,
+) -> Result<&'a ProfileConfig, EngineError> {
+    config.profiles.get(name).ok_or_else(|| {
+        EngineError::ConfigurationError(if config.profiles.is_empty() {
+            format!("No profile named '{name}', the configuration defines none")
+        } else {
+            format!(
+                "No profile named '{name}', the configuration defines {}",
+                config.profile_names().join(", ")
+            )
+        })
+    })
+}
+
+/// Determines the appropriate CPU profile based on power status or forced mode,
+/// and applies the settings through `controller`, usually one picked with
+/// [`crate::control::select`]
+///
+/// With `daemon.rollback_on_failure`, a failed apply first restores the files
+/// it already wrote, so the system is not left with half a profile.
+///
+/// While the thermal safety net is engaged, its settings are applied last,
+/// over whatever the profile, a forced mode or an override asked for.
+pub fn determine_and_apply_settings(
+    report: &SystemReport,
+    config: &AppConfig,
+    force: Option<&ForcedProfile>,
+    turbo_states: &TurboStates,
+    knobs: &KnobStates,
+    controller: &dyn PowerController,
+) -> Result<(), EngineError> {
+    knobs
+        .log_every_cycle
+        .store(config.daemon.log_every_cycle, Ordering::Relaxed);
+    knobs.begin_trace(begin_decision_trace(report, force));
+    let thermal_safety = update_thermal_safety(report, &config.daemon, turbo_states, knobs);
+    if thermal_safety == ThermalSafety::Engaged {
+        knobs.note_override("Thermal safety holds the slowest settings".to_string());
+    }
+    let result = apply_profile(
+        report,
+        config,
+        force,
+        turbo_states,
+        knobs,
+        controller,
+        thermal_safety,
+    );
+    if result.is_err() && config.daemon.rollback_on_failure {
+        let rolled_back = knobs.roll_back(controller);
+        if !rolled_back.is_empty() {
+            let names = rolled_back
+                .iter()
+                .map(|(_, knob)| knob.as_str())
+                .collect::<Vec<_>>()
+                .join(", ");
+            warn!(
+                event = "rollback", knobs = names.as_str();
+                "Rolled back {names} after the apply failed"
+            );
+        }
+        // The turbo hysteresis would otherwise keep the rolled back state
+        if rolled_back
+            .iter()
+            .any(|(step, _)| *step == ApplyStep::Turbo)
+        {
+            turbo_states.reset();
+        }
+    }
+
+    knobs.finish_trace();
+
+    // Last, so nothing applied before can undo it
+    if thermal_safety == ThermalSafety::Engaged {
+        let clamped = apply_thermal_safety(report, knobs, controller);
+        return result.and(clamped);
+    }
+    result
+}
+
+/// The decision trace of a cycle on `report` so far: its inputs, and the
+/// profile selection rules checked up to the one that matched
+fn begin_decision_trace(report: &SystemReport, force: Option<&ForcedProfile>) -> DecisionTrace {
+    let on_ac_power = is_on_ac_power(report);
+    let profile = selected_profile_name(report, force);
+    let mut rules = vec![RuleCheck {
+        rule: "A profile is forced".to_string(),
+        matched: force.is_some(),
+    }];
+    if force.is_none() {
+        rules.push(RuleCheck {
+            rule: "On AC power selects charger".to_string(),
+            matched: on_ac_power,
+        });
+    }
+    if force.is_none() && !on_ac_power {
+        rules.push(RuleCheck {
+            rule: "Otherwise battery".to_string(),
+            matched: true,
+        });
+    }
+
+    DecisionTrace {
+        timestamp: jiff::Timestamp::now(),
+        inputs: DecisionInputs {
+            on_ac_power,
+            power_source: report.ac_power.reading.clone(),
+            battery_percent: battery_percent(report),
+            load_percent: report
+                .system_load
+                .smoothed_cpu_usage
+                .or_else(|| average_cpu_usage(report)),
+            temperature_celsius: report
+                .cpu_global
+                .smoothed_temperature_celsius
+                .or(report.cpu_global.average_temperature_celsius),
+            throttle_events: report.throttling.events(),
+        },
+        rules,
+        overrides: force
+            .map(|_| format!("Profile forced to {profile}"))
+            .into_iter()
+            .collect(),
+        profile: profile.to_string(),
+        profile_reason: selected_profile_reason(report, force).to_string(),
+        turbo: Vec::new(),
+    }
+}
+
+fn apply_profile(
+    report: &SystemReport,
+    config: &AppConfig,
+    force: Option<&ForcedProfile>,
+    turbo_states: &TurboStates,
+    knobs: &KnobStates,
+    controller: &dyn PowerController,
+    thermal_safety: ThermalSafety,
+) -> Result<(), EngineError> {
+    knobs.begin_cycle(selected_profile_name(report, force));
+    let cpu_control_unavailable = knobs.cpu_control_unavailable();
+
+    // First, check if there's a governor override set. The thermal safety net
+    // would only overwrite it again.
+    if let Some(override_governor) = controller
+        .governor_override()
+        .filter(|_| cpu_control_unavailable.is_none() && thermal_safety != ThermalSafety::Engaged)
+    {
+        log!(
+            knobs.decision_level("governor override", override_governor.trim()),
+            "Governor override is active: '{}'. Setting governor.",
+            override_governor.trim()
+        );
+        knobs.note_override(format!("Governor forced to {}", override_governor.trim()));
+
+        // Apply the override governor setting
+        try_apply_feature(
+            knobs,
+            ApplyStep::Governor,
+            "override governor",
+            override_governor.trim(),
+            || controller.set_governor(override_governor.trim(), None),
+        )?;
+    }
+
+    // Determine AC/Battery status once, early in the function
+    let on_ac_power = is_on_ac_power(report);
+
+    // Logged at info level only when the selection changes
+    let level = knobs.decision_level(
+        "profile selection",
+        &format!(
+            "{} ({})",
+            selected_profile_name(report, force),
+            selected_profile_reason(report, force)
+        ),
+    );
+    let selected_profile_config: &ProfileConfig;
+
+    match force {
+        Some(ForcedProfile::Mode(OperationalMode::Powersave)) => {
+            log!(
+                level,
+                event = "profile_selected", profile = "battery", reason = "forced";
+                "Forced Powersave mode selected. Applying 'battery' profile."
+            );
+            selected_profile_config = &config.battery;
+        }
+        Some(ForcedProfile::Mode(OperationalMode::Balanced)) => {
+            log!(
+                level,
+                event = "profile_selected", profile = "balanced", reason = "forced";
+                "Forced Balanced mode selected. Applying 'balanced' profile{}.",
+                if config.balanced.is_some() { "" } else { ", which is the 'battery' profile without a [balanced] section" }
+            );
+            selected_profile_config = config.balanced();
+        }
+        Some(ForcedProfile::Mode(OperationalMode::Performance)) => {
+            log!(
+                level,
+                event = "profile_selected", profile = "charger", reason = "forced";
+                "Forced Performance mode selected. Applying 'charger' profile."
+            );
+            selected_profile_config = &config.charger;
+        }
+        Some(ForcedProfile::Named(name)) => {
+            selected_profile_config = match named_profile(config, name) {
+                Ok(profile) => profile,
+                Err(e) => {
+                    knobs.record_outcome(
+                        "profile",
+                        KnobOutcome::Failed {
+                            error: e.to_string(),
+                        },
+                    );
+                    return Err(e);
+                }
+            };
+            log!(
+                level,
+                event = "profile_selected", profile = name.as_str(), reason = "forced";
+                "Forced profile '{name}' selected."
+            );
+        }
+        // Use the previously computed on_ac_power value
+        None if on_ac_power => {
+            log!(
+                level,
+                event = "profile_selected", profile = "charger", reason = "ac_power";
+                "On AC power, selecting Charger profile."
+            );
+            selected_profile_config = &config.charger;
+        }
+        None => {
+            log!(
+                level,
+                event = "profile_selected", profile = "battery", reason = "battery_power";
+                "On Battery power, selecting Battery profile."
+            );
+            selected_profile_config = &config.battery;
+        }
+    }
+
+    // Knobs that match the profile are left alone, and ones someone else
+    // changed are handled according to the drift policy. Frequencies and
+    // charge thresholds are not tracked, since the kernel and firmware may
+    // round them to a value that never matches the configured one.
+    let policy = config.daemon.effective_drift_policy();
+
+    // Without a cpufreq driver only the settings outside of it are applied
+    if let Some(reason) = &cpu_control_unavailable {
+        debug!("Skipping CPU settings: {reason}");
+        knobs.record_outcome(
+            "CPU settings",
+            KnobOutcome::Skipped {
+                reason: reason.clone(),
+            },
+        );
+    }
+
+    let order = match apply_order(&config.daemon.apply_order) {
+        Ok(order) => order,
+        Err(e) => {
+            knobs.record_outcome(
+                "apply order",
+                KnobOutcome::Failed {
+                    error: e.to_string(),
+                },
+            );
+            return Err(e);
+        }
+    };
+    let context = ApplyContext {
+        report,
+        profile_name: selected_profile_name(report, force),
+        profile: selected_profile_config,
+        on_ac_power,
+        turbo_states,
+        knobs,
+        controller,
+        policy,
+        thermal_safety,
+    };
+    for step in order {
+        if cpu_control_unavailable.is_some() && step.is_cpufreq() {
+            continue;
+        }
+        if thermal_safety == ThermalSafety::Engaged && THERMAL_SAFETY_STEPS.contains(&step) {
+            debug!("Leaving {} to the thermal safety net", step.as_str());
+            continue;
+        }
+
+        let interval = Duration::from_secs(
+            config
+                .daemon
+                .min_write_interval_sec
+                .get(&step)
+                .copied()
+                .unwrap_or_else(|| step.default_min_write_interval_sec()),
+        );
+        if let Some(elapsed) = knobs.written_within(step, interval) {
+            if bypasses_write_interval(&context, step) {
+                debug!("Writing {} early to protect the CPU", step.as_str());
+            } else {
+                debug!(
+                    "Deferring {}: last written {}s ago, at most once every {}s",
+                    step.as_str(),
+                    elapsed.as_secs(),
+                    interval.as_secs()
+                );
+                continue;
+            }
+        }
+
+        let written = knobs.journal_len();
+        let result = apply_step(&context, step);
+        if knobs.journal_len() > written {
+            knobs.record_write_time(step);
+        }
+        result?;
+    }
+
+    debug!("Profile settings applied successfully.");
+
+    Ok(())
+}
+
+/// What [`apply_step`] needs to apply a step of the selected profile
+struct ApplyContext<'a> {
+    report: &'a SystemReport,
+    profile_name: &'static str,
+    profile: &'a ProfileConfig,
+    on_ac_power: bool,
+    turbo_states: &'a TurboStates,
+    knobs: &'a KnobStates,
+    controller: &'a dyn PowerController,
+    policy: DriftPolicy,
+    thermal_safety: ThermalSafety,
+}
+
+/// Steps that must be applied after others, unless `daemon.apply_order` says
+/// otherwise
+const STEP_DEPENDENCIES: &[(ApplyStep, &[ApplyStep])] = &[
+    // Some intel_pstate versions reject EPP writes under the performance
+    // governor, and some firmware resets EPP along with the platform profile
+    (
+        ApplyStep::Epp,
+        &[ApplyStep::Governor, ApplyStep::PlatformProfile],
+    ),
+    (ApplyStep::Epb, &[ApplyStep::PlatformProfile]),
+    // intel_pstate clamps the frequency limits when turbo is switched
+    (
+        ApplyStep::Frequencies,
+        &[
+            ApplyStep::Governor,
+            ApplyStep::Turbo,
+            ApplyStep::PlatformProfile,
+        ],
+    ),
+];
+
+/// Steps `step` must come after
+fn step_dependencies(step: ApplyStep) -> &'static [ApplyStep] {
+    STEP_DEPENDENCIES
+        .iter()
+        .find(|(dependent, _)| *dependent == step)
+        .map_or(&[], |(_, dependencies)| dependencies)
+}
+
+/// The order to apply the steps of a profile in: the `requested` steps as
+/// given, then the remaining ones, each as early as its dependencies allow.
+/// A requested order is trusted even when it breaks a dependency, since it is
+/// there for firmware that needs an unusual one.
+pub fn apply_order(requested: &[ApplyStep]) -> Result<Vec<ApplyStep>, EngineError> {
+    let mut order: Vec<ApplyStep> = Vec::with_capacity(ApplyStep::ALL.len());
+    for &step in requested {
+        if order.contains(&step) {
+            return Err(EngineError::ConfigurationError(format!(
+                "apply_order lists {} more than once",
+                step.as_str()
+            )));
+        }
+        order.push(step);
+    }
+
+    let mut remaining: Vec<ApplyStep> = ApplyStep::ALL
+        .into_iter()
+        .filter(|step| !order.contains(step))
+        .collect();
+    while !remaining.is_empty() {
+        // The dependencies form no cycle, so one of the steps is always ready
+        let ready = remaining
+            .iter()
+            .position(|step| {
+                step_dependencies(*step)
+                    .iter()
+                    .all(|dependency| !remaining.contains(dependency))
+            })
+            .unwrap_or(0);
+        order.push(remaining.remove(ready));
+    }
+    Ok(order)
+}
+
+/// Steps of `order` that come before a step they depend on, as the step and
+/// the dependency
+pub fn apply_order_violations(order: &[ApplyStep]) -> Vec<(ApplyStep, ApplyStep)> {
+    order
+        .iter()
+        .enumerate()
+        .flat_map(|(position, &step)| {
+            step_dependencies(step)
+                .iter()
+                .filter(move |dependency| order[position..].contains(dependency))
+                .map(move |&dependency| (step, dependency))
+        })
+        .collect()
+}
+
+/// Which frequency limit to write first
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrequencyLimit {
+    Min,
+    Max,
+}
+
+/// The order to write the frequency limits in, given the current maximum and
+/// the target minimum in MHz. The kernel rejects a minimum above the current
+/// maximum, so a minimum raised past it needs the maximum written first.
+/// Otherwise the minimum goes first, which also covers lowering the maximum
+/// below the current minimum.
+pub fn frequency_write_order(
+    current_max: Option<u32>,
+    target_min: Option<u32>,
+) -> [FrequencyLimit; 2] {
+    match (target_min, current_max) {
+        (Some(target), Some(current)) if target > current => {
+            [FrequencyLimit::Max, FrequencyLimit::Min]
+        }
+        _ => [FrequencyLimit::Min, FrequencyLimit::Max],
+    }
+}
+
+/// Whether `step` protects an overheating CPU this cycle, and so is written
+/// regardless of `daemon.min_write_interval_sec`. Each is judged on the
+/// temperature its own decision goes by.
+fn bypasses_write_interval(context: &ApplyContext, step: ApplyStep) -> bool {
+    let profile = context.profile;
+    match step {
+        // Auto turbo turns turbo off when the CPU, or a core group, is too hot
+        ApplyStep::Turbo => {
+            let settings = &profile.turbo_auto_settings;
+            let too_hot = |report: &SystemReport| {
+                turbo_temperature(report, settings)
+                    .is_some_and(|temp| temp >= settings.temp_threshold_high)
+            };
+            profile.turbo == Some(TurboSetting::Auto)
+                && (too_hot(context.report)
+                    || topology::core_groups()
+                        .iter()
+                        .any(|group| too_hot(&group_report(context.report, &group.cpus))))
+        }
+        // Thermal backoff caps the frequency by its window of temperatures
+        ApplyStep::Frequencies => profile.thermal_backoff.as_ref().is_some_and(|backoff| {
+            let state = context
+                .turbo_states
+                .get_thermal_for_power_state(context.on_ac_power)
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
+            let temp = state.window.peek(
+                context.profile_name,
+                backoff,
+                context.report.cpu_global.average_temperature_celsius,
+                backoff.temp_window_samples,
+                None,
+            );
+            let previous = state
+                .step
+                .map(|step| step.min(backoff.steps.len().saturating_sub(1)));
+            !backoff.steps.is_empty() && decide_thermal_step(backoff, temp, previous).is_some()
+        }),
+        _ => false,
+    }
+}
+
+/// Applies one step of the selected profile
+fn apply_step(context: &ApplyContext, step: ApplyStep) -> Result<(), EngineError> {
+    let ApplyContext {
+        report,
+        profile_name,
+        profile,
+        on_ac_power,
+        turbo_states,
+        knobs,
+        controller,
+        policy,
+        thermal_safety,
+    } = *context;
+    let global = &report.cpu_global;
+
+    match step {
+        ApplyStep::Governor => {
+            if profile.auto_governor() || profile.governor_auto_settings.is_some() {
+                manage_auto_governor(context)?;
+            } else if let Some(governor) = &profile.governor {
+                if knobs.should_reassert(
+                    policy,
+                    controller.is_dry_run(),
+                    "governor",
+                    governor,
+                    global.current_governor.as_deref(),
+                ) {
+                    // Let set_governor handle the validation. An unavailable governor
+                    // gets parked like any unsupported knob.
+                    try_apply_feature(knobs, ApplyStep::Governor, "governor", governor, || {
+                        controller.set_governor(governor, None)
+                    })?;
+                }
+            }
+        }
+        ApplyStep::Turbo => {
+            if let Some(turbo_setting) = profile.turbo {
+                let setting = format!("{turbo_setting:?}");
+                log!(
+                    knobs.decision_level("turbo setting", &setting),
+                    "Setting turbo to '{setting}'"
+                );
+                if turbo_setting != TurboSetting::Auto || !profile.enable_auto_turbo {
+                    knobs.update_trace(|trace| {
+                        trace.turbo.push(TurboTrace {
+                            group: "all".to_string(),
+                            enabled: (turbo_setting != TurboSetting::Auto)
+                                .then_some(turbo_setting == TurboSetting::Always),
+                            reason: if turbo_setting == TurboSetting::Auto {
+                                "enable_auto_turbo is off, left to the system".to_string()
+                            } else {
+                                format!("profile sets turbo = {setting}").to_lowercase()
+                            },
+                            load_percent: None,
+                            temperature_celsius: None,
+                            strategy: None,
+                        });
+                    });
+                }
+                match turbo_setting {
+                    TurboSetting::Auto => {
+                        if profile.enable_auto_turbo {
+                            debug!("Managing turbo in auto mode based on system conditions");
+                            manage_auto_turbo(context)?;
+                        } else {
+                            debug!(
+                                "Watt's dynamic turbo management is disabled by configuration. Ensuring system uses its default behavior for automatic turbo control."
+                            );
+                            // Make sure the system is set to its default automatic turbo mode.
+                            // This is important if turbo was previously forced off.
+                            try_apply_feature(
+                                knobs,
+                                ApplyStep::Turbo,
+                                "Turbo boost",
+                                "system default (Auto)",
+                                || controller.set_turbo(TurboSetting::Auto),
+                            )?;
+                        }
+                    }
+                    _ => {
+                        let desired = on_off(turbo_setting == TurboSetting::Always);
+                        if knobs.should_reassert(
+                            policy,
+                            controller.is_dry_run(),
+                            "Turbo boost",
+                            desired,
+                            global.turbo_status.map(on_off),
+                        ) {
+                            try_apply_feature(
+                                knobs,
+                                ApplyStep::Turbo,
+                                "Turbo boost",
+                                &format!("{turbo_setting:?}"),
+                                || controller.set_turbo(turbo_setting),
+                            )?;
+                        }
+                    }
+                }
+            }
+        }
+        ApplyStep::Epp => {
+            if profile.auto_epp() {
+                manage_auto_epp(context)?;
+            } else if let Some(epp) = &profile.epp {
+                if knobs.should_reassert(
+                    policy,
+                    controller.is_dry_run(),
+                    "EPP",
+                    epp,
+                    global.epp.as_deref(),
+                ) {
+                    try_apply_feature(knobs, ApplyStep::Epp, "EPP", epp, || {
+                        controller.set_epp(epp, None)
+                    })?;
+                }
+            }
+        }
+        ApplyStep::Epb => {
+            if let Some(epb) = &profile.epb {
+                // The kernel reports EPB as a number, so a named value cannot be
+                // compared with it
+                let current = global.epb.as_deref().filter(|_| epb.parse::<u8>().is_ok());
+                if knobs.should_reassert(policy, controller.is_dry_run(), "EPB", epb, current) {
+                    try_apply_feature(knobs, ApplyStep::Epb, "EPB", epb, || {
+                        controller.set_epb(epb, None)
+                    })?;
+                }
+            }
+        }
+        ApplyStep::Frequencies => {
+            let mut max_freq = thermal_max_frequency(
+                report,
+                profile_name,
+                profile,
+                on_ac_power,
+                turbo_states,
+                knobs,
+                controller,
+            )?;
+            // The thermal safety net left the maximum at the hardware minimum
+            if thermal_safety == ThermalSafety::Released && max_freq.is_none() {
+                max_freq = controller.hardware_max_frequency().ok();
+            }
+            let max_freq =
+                ramped_max_frequency(report, profile_name, profile, turbo_states, max_freq);
+            // The lowest maximum across cores decides what the kernel accepts
+            let current_max = report
+                .cpu_cores
+                .iter()
+                .filter_map(|core| core.max_frequency_mhz)
+                .min();
+            for limit in frequency_write_order(current_max, profile.min_freq_mhz) {
+                match limit {
+                    FrequencyLimit::Min => {
+                        if let Some(min_freq) = profile.min_freq_mhz {
+                            try_apply_feature(
+                                knobs,
+                                ApplyStep::Frequencies,
+                                "min frequency",
+                                &format!("{min_freq} MHz"),
+                                || controller.set_min_frequency(min_freq, None),
+                            )?;
+                        }
+                    }
+                    FrequencyLimit::Max => {
+                        if let Some(max_freq) = max_freq {
+                            try_apply_feature(
+                                knobs,
+                                ApplyStep::Frequencies,
+                                "max frequency",
+                                &format!("{max_freq} MHz"),
+                                || controller.set_max_frequency(max_freq, None),
+                            )?;
+                        }
+                    }
+                }
+            }
+        }
+        ApplyStep::PlatformProfile => {
+            if let Some(platform_profile) = &profile.platform_profile {
+                if knobs.should_reassert(
+                    policy,
+                    controller.is_dry_run(),
+                    "platform profile",
+                    platform_profile,
+                    global.platform_profile.as_deref(),
+                ) {
+                    try_apply_feature(
+                        knobs,
+                        ApplyStep::PlatformProfile,
+                        "platform profile",
+                        platform_profile,
+                        || controller.set_platform_profile(platform_profile),
+                    )?;
+                }
+            }
+        }
+        ApplyStep::ChargeThresholds => {
+            // Set battery charge thresholds if configured
+            if let Some(thresholds) = &profile.battery_charge_thresholds {
+                let start_threshold = thresholds.start;
+                let stop_threshold = thresholds.stop;
+
+                if start_threshold < stop_threshold && stop_threshold <= 100 {
+                    const KNOB: &str = "battery charge thresholds";
+                    if knobs.should_attempt(KNOB) {
+                        log!(
+                            knobs.decision_level(
+                                KNOB,
+                                &format!("{start_threshold}-{stop_threshold}%")
+                            ),
+                            "Setting battery charge thresholds: {start_threshold}-{stop_threshold}%"
+                        );
+                        let (result, writes) = sysfs::capture_writes(|| {
+                            controller.set_charge_thresholds(start_threshold, stop_threshold)
+                        });
+                        knobs.journal(ApplyStep::ChargeThresholds, KNOB, &writes);
+                        match result {
+                            Ok(()) => {
+                                knobs.record_success(KNOB);
+                                knobs.record_outcome(
+                                    KNOB,
+                                    outcome_of(
+                                        &writes,
+                                        &format!("{start_threshold}-{stop_threshold}%"),
+                                    ),
+                                );
+                                debug!("Battery charge thresholds set successfully");
+                            }
+                            Err(e) => {
+                                let e = e.with_knob(KNOB);
+                                let unsupported = matches!(e.root(), ControlError::NotSupported(_));
+                                knobs.record_outcome(
+                                    KNOB,
+                                    if unsupported {
+                                        KnobOutcome::Skipped {
+                                            reason: format!("unsupported: {e}"),
+                                        }
+                                    } else {
+                                        KnobOutcome::Failed {
+                                            error: e.to_string(),
+                                        }
+                                    },
+                                );
+                                if !knobs.record_failure(KNOB, &e.to_string(), unsupported) {
+                                    warn!("Failed to set battery charge thresholds: {e}");
+                                }
+                            }
+                        }
+                    }
+                } else {
+                    warn!(
+                        "Invalid battery threshold values: start={start_threshold}, stop={stop_threshold}"
+                    );
+                    knobs.record_outcome(
+                        "battery charge thresholds",
+                        KnobOutcome::Failed {
+                            error: format!("invalid values {start_threshold}-{stop_threshold}%"),
+                        },
+                    );
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn manage_auto_turbo(context: &ApplyContext) -> Result<(), EngineError> {
+    let ApplyContext {
+        report,
+        profile_name,
+        profile: config,
+        on_ac_power,
+        turbo_states,
+        knobs,
+        controller,
+        ..
+    } = *context;
+    const KNOB: &str = "Turbo boost";
+    // Get the auto turbo settings from the config
+    let turbo_settings = &config.turbo_auto_settings;
+
+    // Validate the complete configuration to ensure it's usable
+    if let Err(e) = validate_turbo_auto_settings(turbo_settings) {
+        knobs.record_outcome(
+            KNOB,
+            KnobOutcome::Failed {
+                error: e.to_string(),
+            },
+        );
+        return Err(e);
+    }
+
+    let groups = topology::core_groups();
+    if groups.len() > 1
+        && groups
+            .iter()
+            .all(|group| controller.per_core_turbo_supported(&group.cpus))
+    {
+        // Each group gets its own decision, e.g. one CCD can be busy while
+        // the other idles
+        let mut states = turbo_states
+            .groups
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let mut result = Ok(());
+        for group in groups {
+            let hysteresis = states.entry(group.id.as_str()).or_default().get(
+                profile_name,
+                on_ac_power,
+                turbo_settings,
+            );
+            let outcome = decide_and_apply_turbo(
+                context,
+                &group_report(report, &group.cpus),
+                turbo_settings,
+                TurboTarget {
+                    group: Some(group),
+                    hysteresis: &hysteresis,
+                },
+            );
+            // A group failing leaves the others to be decided
+            if result.is_ok() {
+                result = outcome;
+            }
+        }
+        return result;
+    }
+
+    let hysteresis = turbo_states
+        .hysteresis
+        .get(profile_name, on_ac_power, turbo_settings);
+    decide_and_apply_turbo(
+        context,
+        report,
+        turbo_settings,
+        TurboTarget {
+            group: None,
+            hysteresis: &hysteresis,
+        },
+    )
+}
+
+/// What an auto turbo decision applies to
+struct TurboTarget<'a> {
+    /// The core group, or `None` for the global turbo control
+    group: Option<&'a CoreGroup>,
+    hysteresis: &'a TurboHysteresis,
+}
+
+/// `report` with only the cores of a group, and their average temperature
+/// if they report one. The smoothed readings are of all cores, so they go.
+fn group_report(report: &SystemReport, cpus: &[u32]) -> SystemReport {
+    let mut view = report.clone();
+    view.cpu_cores.retain(|core| cpus.contains(&core.core_id));
+    view.system_load.smoothed_cpu_usage = None;
+    view.cpu_global.smoothed_temperature_celsius = None;
+    let temps: Vec<f32> = view
+        .cpu_cores
+        .iter()
+        .filter_map(|core| core.temperature_celsius)
+        .collect();
+    if !temps.is_empty() {
+        view.cpu_global.average_temperature_celsius =
+            Some(temps.iter().sum::<f32>() / temps.len() as f32);
+    }
+    view
+}
+
+/// Decides and applies auto turbo for `target`, on `report`, which has only
+/// the cores of its group
+fn decide_and_apply_turbo(
+    context: &ApplyContext,
+    report: &SystemReport,
+    turbo_settings: &TurboAutoSettings,
+    target: TurboTarget,
+) -> Result<(), EngineError> {
+    let ApplyContext {
+        profile_name,
+        on_ac_power,
+        turbo_states,
+        knobs,
+        controller,
+        ..
+    } = *context;
+    let group = target.group.map(|group| group.id.as_str());
+    let (knob, prefix) = match group {
+        Some(group) => (
+            format!("Turbo boost [{group}]"),
+            format!("Auto Turbo [{group}]"),
+        ),
+        None => ("Turbo boost".to_string(), "Auto Turbo".to_string()),
+    };
+    let hysteresis = target.hysteresis;
+
+    let cpu_temp = turbo_temperature(report, turbo_settings);
+    let current_usage = turbo_load(report, turbo_settings);
+    let avg_cpu_usage = hysteresis.windowed_usage(profile_name, turbo_settings, current_usage);
+    if turbo_settings.load_window_samples > 1 {
+        debug!(
+            "{prefix}: CPU load ({}) {} now, {} over the last {} samples",
+            turbo_settings.load_metric.as_str(),
+            current_usage.map_or_else(|| "unknown".to_string(), |usage| format!("{usage:.1}%")),
+            avg_cpu_usage.map_or_else(|| "unknown".to_string(), |usage| format!("{usage:.1}%")),
+            turbo_settings.load_window_samples
+        );
+    }
+
+    // Get the previous state or initialize with the configured initial state
+    let previous_turbo_enabled = {
+        if let Some(state) = hysteresis.get_previous_state() {
+            state
+        } else {
+            // Initialize with the configured initial state and return it
+            hysteresis.initialize_with(turbo_settings.initial_turbo_state)
+        }
+    };
+
+    let battery_percent = battery_percent(report);
+    let low_battery = battery_limits_turbo(
+        turbo_settings,
+        on_ac_power,
+        battery_percent,
+        hysteresis.battery_limited.load(Ordering::Acquire),
+    );
+    hysteresis
+        .battery_limited
+        .store(low_battery, Ordering::Release);
+
+    let cpu_pressure = report.system_load.cpu_pressure;
+    if cpu_pressure.is_none() && uses_pressure(turbo_settings) {
+        debug!("{prefix}: No CPU pressure information, deciding on load alone");
+    }
+
+    let metrics = TurboMetrics {
+        temperature: cpu_temp,
+        load: avg_cpu_usage,
+        cpu_pressure,
+        battery_percent,
+        on_ac_power,
+        low_battery,
+        throttle_events: report.throttling.events(),
+    };
+    let (enable_turbo, reason) = hysteresis.duty_cycle(
+        turbo_settings,
+        decide_auto_turbo(turbo_settings, &metrics, previous_turbo_enabled),
+        Instant::now(),
+    );
+    let level = knobs.decision_level(
+        &format!("turbo decision [{}]", group.unwrap_or("all")),
+        &format!("{} ({})", on_off(enable_turbo), reason.as_str()),
+    );
+    log_turbo_decision(level, turbo_settings, group, &metrics, enable_turbo, reason);
+    let reason = reason.as_str();
+    knobs.update_trace(|trace| {
+        trace.turbo.push(TurboTrace {
+            group: group.unwrap_or("all").to_string(),
+            enabled: Some(enable_turbo),
+            reason: reason.to_string(),
+            load_percent: avg_cpu_usage,
+            temperature_celsius: cpu_temp,
+            strategy: Some(turbo_settings.strategy.as_str().to_string()),
+        });
+    });
+
+    // Save the current state for next time. Group states are not saved.
+    if hysteresis.update_state(enable_turbo) && group.is_none() {
+        turbo_states.changed.store(true, Ordering::Release);
     }
 
     // Only apply the setting if the state has changed
     let changed = previous_turbo_enabled != enable_turbo;
     if changed {
-        let turbo_setting = if enable_turbo {
-            TurboSetting::Always
-        } else {
-            TurboSetting::Never
+        info!(
+            event = "turbo_change",
+            group = group.unwrap_or("all"),
+            old = previous_turbo_enabled,
+            new = enable_turbo;
+            "{prefix}: Applying turbo change from {} to {}",
+            if previous_turbo_enabled {
+                "enabled"
+            } else {
+                "disabled"
+            },
+            if enable_turbo { "enabled" } else { "disabled" }
+        );
+
+        let (result, writes) = sysfs::capture_writes(|| match target.group {
+            Some(group) => controller.set_turbo_for_cpus(enable_turbo, &group.cpus),
+            None => controller.set_turbo(if enable_turbo {
+                TurboSetting::Always
+            } else {
+                TurboSetting::Never
+            }),
+        });
+        knobs.journal(ApplyStep::Turbo, &knob, &writes);
+        match result {
+            Ok(()) => {
+                debug!(
+                    "{prefix}: Successfully set turbo to {}",
+                    if enable_turbo { "enabled" } else { "disabled" }
+                );
+                knobs.record_outcome(
+                    &knob,
+                    KnobOutcome::Changed {
+                        from: Some(on_off(previous_turbo_enabled).to_string()),
+                        to: on_off(enable_turbo).to_string(),
+                        files: writes.len(),
+                    },
+                );
+                *turbo_states
+                    .last_change
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner) = Some((enable_turbo, reason));
+                Ok(())
+            }
+            Err(e) => {
+                let e = e.with_knob(&knob);
+                knobs.record_outcome(
+                    &knob,
+                    KnobOutcome::Failed {
+                        error: e.to_string(),
+                    },
+                );
+                Err(EngineError::ControlError(e))
+            }
+        }
+    } else {
+        debug!(
+            "{prefix}: Maintaining turbo state ({}) - no change needed",
+            if enable_turbo { "enabled" } else { "disabled" }
+        );
+        knobs.record_outcome(
+            &knob,
+            KnobOutcome::Unchanged {
+                value: on_off(enable_turbo).to_string(),
+            },
+        );
+        Ok(())
+    }
+}
+
+fn manage_auto_epp(context: &ApplyContext) -> Result<(), EngineError> {
+    let ApplyContext {
+        report,
+        profile_name,
+        profile: config,
+        on_ac_power,
+        turbo_states,
+        knobs,
+        controller,
+        policy,
+        ..
+    } = *context;
+    const KNOB: &str = "EPP";
+    let settings = &config.epp_auto_settings;
+
+    if let Err(e) = validate_epp_auto_settings(settings) {
+        knobs.record_outcome(
+            KNOB,
+            KnobOutcome::Failed {
+                error: e.to_string(),
+            },
+        );
+        return Err(e);
+    }
+
+    // Without a preference to read there is none to move
+    let Some(current_epp) = report.cpu_global.epp.as_deref() else {
+        return try_apply_feature(knobs, ApplyStep::Epp, KNOB, AUTO_EPP, || {
+            Err::<(), _>(ControlError::NotSupported(
+                "this system has no energy performance preference".to_string(),
+            ))
+        });
+    };
+
+    let mut state = turbo_states
+        .get_epp_for_power_state(on_ac_power)
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner);
+    if state.window.rekey(profile_name, settings) {
+        debug!("Auto EPP: Starting over for the {profile_name} profile");
+        state.tier = None;
+        state.changed_at = None;
+    }
+    let load = state.window.push(
+        cpu_load(report, settings.load_metric, settings.top_n),
+        settings.load_window_samples,
+        settings.load_window_percentile,
+    );
+    let metric = settings.load_metric.as_str();
+
+    let Some((mut tier, mut reason)) = decide_auto_epp(settings, load, state.tier) else {
+        debug!("Auto EPP: No CPU load data, leaving EPP at '{current_epp}'");
+        return Ok(());
+    };
+    if let (Some(previous), Some(changed_at)) = (state.tier, state.changed_at) {
+        let min_interval = Duration::from_secs(settings.min_interval_sec);
+        if tier != previous && changed_at.elapsed() < min_interval {
+            debug!(
+                "Auto EPP: Holding '{}' for now, it changed less than {}s ago",
+                settings.tiers[previous].epp, settings.min_interval_sec
+            );
+            tier = previous;
+            reason = EppReason::RateLimited;
+        }
+    }
+
+    let load = load.unwrap_or_default();
+    let epp = settings.tiers[tier].epp.as_str();
+    if state.tier != Some(tier) {
+        info!(
+            event = "epp_decision",
+            reason = reason.as_str(),
+            load = load,
+            metric = metric,
+            epp = epp;
+            "Auto EPP: Moving to '{epp}' ({}, {metric} load {load:.1}%)",
+            reason.describe()
+        );
+        state.changed_at = Some(Instant::now());
+        state.tier = Some(tier);
+    } else {
+        debug!(
+            "Auto EPP: Keeping '{epp}' ({}, {metric} load {load:.1}%)",
+            reason.describe()
+        );
+    }
+    drop(state);
+
+    if knobs.should_reassert(
+        policy,
+        controller.is_dry_run(),
+        KNOB,
+        epp,
+        Some(current_epp),
+    ) {
+        try_apply_feature(knobs, ApplyStep::Epp, KNOB, epp, || {
+            controller.set_epp(epp, None)
+        })?;
+    }
+    Ok(())
+}
+
+/// The max frequency to write this cycle: the profile's `max_freq_mhz`, or
+/// the `thermal_backoff` cap while one applies if that is lower. Right after
+/// a cap is lifted, the profile's max or else the hardware maximum, to undo
+/// it.
+fn thermal_max_frequency(
+    report: &SystemReport,
+    profile_name: &'static str,
+    config: &ProfileConfig,
+    on_ac_power: bool,
+    turbo_states: &TurboStates,
+    knobs: &KnobStates,
+    controller: &dyn PowerController,
+) -> Result<Option<u32>, EngineError> {
+    let Some(backoff) = &config.thermal_backoff else {
+        return Ok(config.max_freq_mhz);
+    };
+    if let Err(e) = validate_thermal_backoff(backoff) {
+        knobs.record_outcome(
+            "max frequency",
+            KnobOutcome::Failed {
+                error: e.to_string(),
+            },
+        );
+        return Err(e);
+    }
+
+    let mut state = turbo_states
+        .get_thermal_for_power_state(on_ac_power)
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner);
+    state.window.rekey(profile_name, backoff);
+    let temp = state.window.push(
+        report.cpu_global.average_temperature_celsius,
+        backoff.temp_window_samples,
+        None,
+    );
+    let previous = state.step.map(|step| step.min(backoff.steps.len() - 1));
+    let step = decide_thermal_step(backoff, temp, previous);
+    let lifted = state.step.is_some() && step.is_none();
+    let changed = step != state.step;
+    if step.is_none() && !lifted {
+        return Ok(config.max_freq_mhz);
+    }
+
+    let hardware_max = match controller.hardware_max_frequency() {
+        Ok(mhz) => mhz,
+        Err(e) => {
+            debug!("Thermal backoff: Cannot cap without the hardware maximum: {e}");
+            state.step = None;
+            return Ok(config.max_freq_mhz);
+        }
+    };
+    state.step = step;
+    drop(state);
+
+    let temp = temp.unwrap_or_default();
+    let Some(step) = step else {
+        let restored = config.max_freq_mhz.unwrap_or(hardware_max);
+        info!(
+            event = "thermal_backoff",
+            temperature = temp,
+            max_freq_mhz = restored;
+            "Thermal backoff: CPU cooled down to {temp:.1}°C, restoring max frequency to {restored} MHz"
+        );
+        return Ok(Some(restored));
+    };
+
+    let step = &backoff.steps[step];
+    let max_freq = thermal_cap_mhz(step, hardware_max, config.max_freq_mhz);
+    if changed {
+        info!(
+            event = "thermal_backoff",
+            temperature = temp,
+            threshold = step.above_celsius,
+            max_freq_mhz = max_freq;
+            "Thermal backoff: CPU at {temp:.1}°C (above {:.1}°C), capping max frequency at {max_freq} MHz ({}% of {hardware_max} MHz)",
+            step.above_celsius,
+            step.max_percent
+        );
+    }
+    Ok(Some(max_freq))
+}
+
+/// Steps a raised max frequency up to `target`, the one the profile and its
+/// thermal backoff ask for, over the profile's `transition_ramp_sec`. A lower
+/// target applies at once, and one of another profile or a changed target
+/// cancels the ramp in progress.
+fn ramped_max_frequency(
+    report: &SystemReport,
+    profile_name: &'static str,
+    config: &ProfileConfig,
+    turbo_states: &TurboStates,
+    target: Option<u32>,
+) -> Option<u32> {
+    let mut ramp = turbo_states
+        .ramp
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner);
+    let duration = config
+        .transition_ramp_sec
+        .filter(|sec| *sec > 0)
+        .map(Duration::from_secs);
+    let (Some(target), Some(duration)) = (target, duration) else {
+        if let Some(state) = ramp.take() {
+            debug!(
+                "Frequency ramp: Cancelled the ramp to {} MHz",
+                state.ramp.to_mhz
+            );
+        }
+        return target;
+    };
+
+    if let Some(state) =
+        ramp.take_if(|state| state.profile != profile_name || state.ramp.to_mhz != target)
+    {
+        info!(
+            event = "frequency_ramp",
+            from = state.ramp.current_mhz,
+            to = state.ramp.to_mhz;
+            "Frequency ramp: Cancelled the ramp to {} MHz at {} MHz",
+            state.ramp.to_mhz,
+            state.ramp.current_mhz
+        );
+    }
+
+    if ramp.is_none() {
+        // The lowest maximum across cores is where the ramp starts
+        let current = report
+            .cpu_cores
+            .iter()
+            .filter_map(|core| core.max_frequency_mhz)
+            .min();
+        let Some(current) = current.filter(|current| *current < target) else {
+            return Some(target);
         };
+        info!(
+            event = "frequency_ramp",
+            from = current,
+            to = target;
+            "Frequency ramp: Raising max frequency from {current} to {target} MHz over {}s",
+            duration.as_secs()
+        );
+        *ramp = Some(RampState {
+            profile: profile_name,
+            ramp: FrequencyRamp {
+                from_mhz: current,
+                to_mhz: target,
+                current_mhz: current,
+            },
+            started_at: Instant::now(),
+            duration,
+        });
+    }
+    let Some(state) = ramp.as_mut() else {
+        return Some(target);
+    };
+
+    let progress = state.started_at.elapsed().as_secs_f32() / state.duration.as_secs_f32();
+    if progress >= 1.0 {
+        debug!("Frequency ramp: Reached {target} MHz");
+        *ramp = None;
+        return Some(target);
+    }
+    let from = state.ramp.from_mhz;
+    let step = from + ((target - from) as f32 * progress) as u32;
+    // The kernel rejects a maximum below the minimum
+    let step = step.max(config.min_freq_mhz.unwrap_or(0)).min(target);
+    state.ramp.current_mhz = step;
+    Some(step)
+}
+
+/// The max frequency under `step`: its share of the hardware maximum, or the
+/// profile's max if that is lower
+pub fn thermal_cap_mhz(step: &ThermalStep, hardware_max: u32, profile_max: Option<u32>) -> u32 {
+    let cap = hardware_max * u32::from(step.max_percent) / 100;
+    profile_max.map_or(cap, |max| max.min(cap))
+}
+
+/// Picks the `thermal_backoff` step for the smoothed temperature, given the
+/// step in place. A hotter step applies as soon as the temperature passes
+/// it, while a cap is only lowered to a cooler step, or lifted, once the
+/// temperature is `hysteresis` below it. Without a temperature the step in
+/// place stays.
+pub fn decide_thermal_step(
+    backoff: &ThermalBackoff,
+    temp: Option<f32>,
+    previous: Option<usize>,
+) -> Option<usize> {
+    let Some(temp) = temp else {
+        return previous;
+    };
+    let hot = backoff
+        .steps
+        .iter()
+        .rposition(|step| temp > step.above_celsius);
+    match previous {
+        Some(previous) if hot.is_none_or(|hot| hot < previous) => backoff.steps[..=previous]
+            .iter()
+            .rposition(|step| temp > step.above_celsius - backoff.hysteresis),
+        _ => hot,
+    }
+}
+
+pub fn validate_thermal_backoff(backoff: &ThermalBackoff) -> Result<(), EngineError> {
+    let invalid = |problem: &str| {
+        Err(EngineError::ConfigurationError(format!(
+            "Invalid thermal backoff: {problem}"
+        )))
+    };
+    if backoff.steps.is_empty() {
+        return invalid("at least one step is needed");
+    }
+    if backoff
+        .steps
+        .iter()
+        .any(|step| !(1..=100).contains(&step.max_percent) || step.above_celsius <= 0.0)
+    {
+        return invalid(
+            "each step needs above_celsius above 0°C and max_percent between 1 and 100",
+        );
+    }
+    if backoff.steps.windows(2).any(|pair| {
+        pair[0].above_celsius >= pair[1].above_celsius || pair[0].max_percent < pair[1].max_percent
+    }) {
+        return invalid(
+            "steps must be ordered from the coolest to the hottest, with caps that only go down",
+        );
+    }
+    if !(0.0..=50.0).contains(&backoff.hysteresis) {
+        return invalid("hysteresis must be between 0°C and 50°C");
+    }
+    if !(1..=MAX_LOAD_WINDOW_SAMPLES).contains(&backoff.temp_window_samples) {
+        return invalid(&format!(
+            "temp_window_samples must be between 1 and {MAX_LOAD_WINDOW_SAMPLES}"
+        ));
+    }
+    Ok(())
+}
+
+/// What the thermal safety net does in a cycle
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ThermalSafety {
+    /// Not engaged, the profile applies as is
+    Inactive,
+    /// Engaged: its steps are left out of the profile and clamped last
+    Engaged,
+    /// Let go this cycle, so the profile takes its steps back
+    Released,
+}
+
+/// Decides whether the thermal safety net is engaged this cycle and logs when
+/// that changes. Only `daemon.thermal_safety = false` turns it off.
+fn update_thermal_safety(
+    report: &SystemReport,
+    daemon: &DaemonConfig,
+    turbo_states: &TurboStates,
+    knobs: &KnobStates,
+) -> ThermalSafety {
+    let was_engaged = turbo_states.thermal_safety.load(Ordering::Acquire);
+    let temp = hottest_temperature(report);
+    let (critical, recovery) =
+        thermal_safety_thresholds(daemon, report.cpu_global.critical_temperature_celsius);
+    let engaged =
+        daemon.thermal_safety && decide_thermal_safety(temp, critical, recovery, was_engaged);
+    turbo_states
+        .thermal_safety
+        .store(engaged, Ordering::Release);
+
+    let temp = temp.unwrap_or_default();
+    match (was_engaged, engaged) {
+        (false, true) => {
+            warn!(
+                event = "thermal_safety",
+                engaged = true,
+                temperature = temp,
+                threshold = critical;
+                "Thermal safety: CPU at {temp:.1}°C, critical is {critical:.1}°C. Holding turbo off, the slowest governor and the lowest frequency until it cools below {recovery:.1}°C"
+            );
+            *turbo_states
+                .thermal_safety_engagement
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner) = Some(temp);
+            ThermalSafety::Engaged
+        }
+        (true, true) => {
+            debug!("Thermal safety: Still engaged at {temp:.1}°C");
+            ThermalSafety::Engaged
+        }
+        (true, false) => {
+            if daemon.thermal_safety {
+                info!(
+                    event = "thermal_safety",
+                    engaged = false,
+                    temperature = temp,
+                    threshold = recovery;
+                    "Thermal safety: CPU cooled down to {temp:.1}°C, handing the settings back to the profile"
+                );
+            } else {
+                info!(
+                    "Thermal safety: Disabled by configuration, handing the settings back to the profile"
+                );
+            }
+            // The profile writes the clamped knobs again, as if it just asked
+            // for them
+            knobs.forget_desired("governor");
+            knobs.forget_desired("Turbo boost");
+            turbo_states.mark_turbo_disabled();
+            ThermalSafety::Released
+        }
+        (false, false) => ThermalSafety::Inactive,
+    }
+}
+
+/// The hottest core temperature, or the average where the cores report none
+fn hottest_temperature(report: &SystemReport) -> Option<f32> {
+    report
+        .cpu_cores
+        .iter()
+        .filter_map(|core| core.temperature_celsius)
+        .reduce(f32::max)
+        .or(report.cpu_global.average_temperature_celsius)
+}
+
+/// The temperatures the thermal safety net engages at and lets go below, in
+/// °C, given Tjmax if the sensor reports it. The recovery temperature never
+/// exceeds the critical one.
+pub fn thermal_safety_thresholds(daemon: &DaemonConfig, tjmax: Option<f32>) -> (f32, f32) {
+    let critical = daemon
+        .thermal_safety_critical_celsius
+        .unwrap_or_else(|| tjmax.unwrap_or(DEFAULT_TJMAX_CELSIUS) - THERMAL_SAFETY_MARGIN_CELSIUS);
+    let recovery = daemon
+        .thermal_safety_recovery_celsius
+        .unwrap_or(critical - THERMAL_SAFETY_RECOVERY_MARGIN_CELSIUS);
+    (critical, recovery.min(critical))
+}
+
+/// Whether the thermal safety net is engaged at `temp`, given whether it was
+/// in the previous cycle. It engages at `critical` and holds until the
+/// temperature drops below `recovery`. Without a temperature nothing changes.
+pub fn decide_thermal_safety(
+    temp: Option<f32>,
+    critical: f32,
+    recovery: f32,
+    engaged: bool,
+) -> bool {
+    match temp {
+        Some(temp) if engaged => temp >= recovery,
+        Some(temp) => temp >= critical,
+        None => engaged,
+    }
+}
+
+/// Checks the thermal safety temperatures of `[daemon]`
+pub fn validate_thermal_safety(daemon: &DaemonConfig) -> Result<(), EngineError> {
+    let invalid = |problem: &str| {
+        Err(EngineError::ConfigurationError(format!(
+            "Invalid thermal safety: {problem}"
+        )))
+    };
+    if [
+        daemon.thermal_safety_critical_celsius,
+        daemon.thermal_safety_recovery_celsius,
+    ]
+    .into_iter()
+    .flatten()
+    .any(|temp| !(1.0..=150.0).contains(&temp))
+    {
+        return invalid("temperatures must be between 1°C and 150°C");
+    }
+    if let (Some(critical), Some(recovery)) = (
+        daemon.thermal_safety_critical_celsius,
+        daemon.thermal_safety_recovery_celsius,
+    ) {
+        if recovery >= critical {
+            return invalid(&format!(
+                "thermal_safety_recovery_celsius ({recovery}) must be below thermal_safety_critical_celsius ({critical})"
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Holds turbo off, the slowest governor and the frequency range at the
+/// hardware minimum. Every knob is tried, even after one failed.
+fn apply_thermal_safety(
+    report: &SystemReport,
+    knobs: &KnobStates,
+    controller: &dyn PowerController,
+) -> Result<(), EngineError> {
+    if let Some(reason) = knobs.cpu_control_unavailable() {
+        debug!("Thermal safety: Cannot clamp the CPU: {reason}");
+        return Ok(());
+    }
+
+    let mut results = vec![try_apply_feature(
+        knobs,
+        ApplyStep::Turbo,
+        "Turbo boost",
+        "Never",
+        || controller.set_turbo(TurboSetting::Never),
+    )];
+
+    // Without a list of governors, set_governor tells whether it exists
+    let available = &report.cpu_global.available_governors;
+    let governor = THERMAL_SAFETY_GOVERNORS
+        .iter()
+        .copied()
+        .find(|governor| available.iter().any(|available| available == governor))
+        .unwrap_or(THERMAL_SAFETY_GOVERNORS[0]);
+    results.push(try_apply_feature(
+        knobs,
+        ApplyStep::Governor,
+        "governor",
+        governor,
+        || controller.set_governor(governor, None),
+    ));
+
+    match controller.hardware_min_frequency() {
+        Ok(floor) => {
+            // The minimum first, as the kernel rejects a maximum below it
+            let value = format!("{floor} MHz");
+            results.push(try_apply_feature(
+                knobs,
+                ApplyStep::Frequencies,
+                "min frequency",
+                &value,
+                || controller.set_min_frequency(floor, None),
+            ));
+            results.push(try_apply_feature(
+                knobs,
+                ApplyStep::Frequencies,
+                "max frequency",
+                &value,
+                || controller.set_max_frequency(floor, None),
+            ));
+        }
+        Err(e) => {
+            debug!("Thermal safety: Cannot lower the frequency without the hardware minimum: {e}")
+        }
+    }
+
+    results.into_iter().collect()
+}
+
+fn manage_auto_governor(context: &ApplyContext) -> Result<(), EngineError> {
+    let ApplyContext {
+        report,
+        profile_name,
+        profile: config,
+        on_ac_power,
+        turbo_states,
+        knobs,
+        controller,
+        policy,
+        ..
+    } = *context;
+    const KNOB: &str = "governor";
+    let default_settings = GovernorAutoSettings::default();
+    let settings = config
+        .governor_auto_settings
+        .as_ref()
+        .unwrap_or(&default_settings);
+
+    if let Err(e) = validate_auto_governor(config, &report.cpu_global.available_governors) {
+        knobs.record_outcome(
+            KNOB,
+            KnobOutcome::Failed {
+                error: e.to_string(),
+            },
+        );
+        return Err(e);
+    }
+
+    let mut state = turbo_states
+        .get_governor_for_power_state(on_ac_power)
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner);
+    if state.window.rekey(profile_name, settings) {
+        debug!("Auto governor: Starting over for the {profile_name} profile");
+        state.high_load = None;
+        state.switched_at = None;
+    }
+    let load = state.window.push(
+        cpu_load(report, settings.load_metric, settings.top_n),
+        settings.load_window_samples,
+        settings.load_window_percentile,
+    );
+
+    let Some((mut high_load, mut reason)) = decide_auto_governor(settings, load, state.high_load)
+    else {
+        debug!("Auto governor: No CPU load data, leaving the governor alone");
+        return Ok(());
+    };
+    if let (Some(previous), Some(switched_at)) = (state.high_load, state.switched_at) {
+        if high_load != previous
+            && switched_at.elapsed() < Duration::from_secs(settings.min_dwell_sec)
+        {
+            high_load = previous;
+            reason = GovernorReason::Dwell;
+        }
+    }
+
+    let governor = if high_load {
+        settings.high_load_governor.as_str()
+    } else {
+        settings.low_load_governor.as_str()
+    };
+    let load = load.unwrap_or_default();
+    let metric = settings.load_metric.as_str();
+    if state.high_load != Some(high_load) {
+        info!(
+            event = "governor_decision",
+            reason = reason.as_str(),
+            load = load,
+            metric = metric,
+            governor = governor;
+            "Auto governor: Switching to '{governor}' ({}, {metric} load {load:.1}%)",
+            reason.describe()
+        );
+        state.high_load = Some(high_load);
+        state.switched_at = Some(Instant::now());
+    } else {
+        debug!(
+            "Auto governor: Keeping '{governor}' ({}, {metric} load {load:.1}%)",
+            reason.describe()
+        );
+    }
+    drop(state);
+
+    if knobs.should_reassert(
+        policy,
+        controller.is_dry_run(),
+        KNOB,
+        governor,
+        report.cpu_global.current_governor.as_deref(),
+    ) {
+        try_apply_feature(knobs, ApplyStep::Governor, KNOB, governor, || {
+            controller.set_governor(governor, None)
+        })?;
+    }
+    Ok(())
+}
+
+/// Why auto governor picked its governor
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GovernorReason {
+    HighLoad,
+    LowLoad,
+    /// Between the thresholds, so the previous governor stays
+    IntermediateLoad,
+    /// The other governor was picked less than `min_dwell_sec` ago
+    Dwell,
+    /// No CPU load data, so the previous governor stays
+    MissingMetrics,
+}
+
+impl GovernorReason {
+    /// The reason as logged with the `governor_decision` event
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::HighLoad => "high_load",
+            Self::LowLoad => "low_load",
+            Self::IntermediateLoad => "intermediate_load",
+            Self::Dwell => "dwell",
+            Self::MissingMetrics => "missing_metrics",
+        }
+    }
+
+    const fn describe(self) -> &'static str {
+        match self {
+            Self::HighLoad => "high load",
+            Self::LowLoad => "low load",
+            Self::IntermediateLoad => "intermediate load",
+            Self::Dwell => "switched too recently",
+            Self::MissingMetrics => "no CPU load data",
+        }
+    }
+}
+
+/// Decides whether `governor = "auto"` should use the high load governor,
+/// given the windowed load and whether it did before. Between the
+/// thresholds the previous choice stays, or the low load governor if there
+/// is none. `None` without load data and a previous choice.
+pub fn decide_auto_governor(
+    settings: &GovernorAutoSettings,
+    load: Option<f32>,
+    previous: Option<bool>,
+) -> Option<(bool, GovernorReason)> {
+    let Some(load) = load else {
+        return previous.map(|high_load| (high_load, GovernorReason::MissingMetrics));
+    };
+    Some(if load >= settings.load_threshold_high {
+        (true, GovernorReason::HighLoad)
+    } else if load <= settings.load_threshold_low {
+        (false, GovernorReason::LowLoad)
+    } else {
+        (previous.unwrap_or(false), GovernorReason::IntermediateLoad)
+    })
+}
+
+/// Checks that `governor_auto_settings` only comes with `governor = "auto"`,
+/// and the settings `governor = "auto"` uses
+pub fn validate_auto_governor(
+    profile: &ProfileConfig,
+    available: &[String],
+) -> Result<(), EngineError> {
+    match (&profile.governor_auto_settings, profile.auto_governor()) {
+        (Some(_), false) => Err(EngineError::ConfigurationError(format!(
+            "governor_auto_settings only applies to governor = \"{AUTO_GOVERNOR}\", not to a fixed governor"
+        ))),
+        (Some(settings), true) => validate_governor_auto_settings(settings, available),
+        (None, true) => {
+            validate_governor_auto_settings(&GovernorAutoSettings::default(), available)
+        }
+        (None, false) => Ok(()),
+    }
+}
 
-        info!(
-            "Auto Turbo: Applying turbo change from {} to {}",
-            if previous_turbo_enabled {
-                "enabled"
-            } else {
-                "disabled"
-            },
-            if enable_turbo { "enabled" } else { "disabled" }
-        );
+/// Checks `settings`, and with a non-empty `available` list that both
+/// governors are in it
+fn validate_governor_auto_settings(
+    settings: &GovernorAutoSettings,
+    available: &[String],
+) -> Result<(), EngineError> {
+    let invalid = |problem: &str| {
+        Err(EngineError::ConfigurationError(format!(
+            "Invalid governor auto settings: {problem}"
+        )))
+    };
+    for governor in [&settings.low_load_governor, &settings.high_load_governor] {
+        if governor.eq_ignore_ascii_case(AUTO_GOVERNOR) {
+            return invalid("low_load_governor and high_load_governor must be actual governors");
+        }
+        if !available.is_empty() && !available.contains(governor) {
+            return invalid(&format!(
+                "governor '{governor}' is not available, only: {}",
+                available.join(", ")
+            ));
+        }
+    }
+    if settings.load_threshold_high <= settings.load_threshold_low
+        || !(0.0..=100.0).contains(&settings.load_threshold_high)
+        || !(0.0..=100.0).contains(&settings.load_threshold_low)
+    {
+        return invalid("load thresholds must be between 0 % and 100 % with high > low");
+    }
+    if settings.load_metric == LoadMetric::TopNAverage && settings.top_n == 0 {
+        return invalid("top_n must be at least 1");
+    }
+    if !(1..=MAX_LOAD_WINDOW_SAMPLES).contains(&settings.load_window_samples) {
+        return invalid(&format!(
+            "load_window_samples must be between 1 and {MAX_LOAD_WINDOW_SAMPLES}"
+        ));
+    }
+    if settings
+        .load_window_percentile
+        .is_some_and(|percentile| !(0.0..=100.0).contains(&percentile))
+    {
+        return invalid("load_window_percentile must be between 0 and 100");
+    }
+    Ok(())
+}
 
-        match cpu::set_turbo(turbo_setting) {
-            Ok(()) => {
-                debug!(
-                    "Auto Turbo: Successfully set turbo to {}",
-                    if enable_turbo { "enabled" } else { "disabled" }
-                );
-                Ok(())
-            }
-            Err(e) => Err(EngineError::ControlError(e)),
+/// Why auto EPP picked its tier
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EppReason {
+    /// The load reached the tier's `min_load`
+    Load,
+    /// The load fell below the previous tier by less than the hysteresis
+    Hysteresis,
+    /// The previous tier was picked less than `min_interval_sec` ago
+    RateLimited,
+    /// No CPU load data, so the previous tier stays
+    MissingMetrics,
+}
+
+impl EppReason {
+    /// The reason as logged with the `epp_decision` event
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Load => "load",
+            Self::Hysteresis => "hysteresis",
+            Self::RateLimited => "rate_limited",
+            Self::MissingMetrics => "missing_metrics",
+        }
+    }
+
+    const fn describe(self) -> &'static str {
+        match self {
+            Self::Load => "load in range",
+            Self::Hysteresis => "load within the hysteresis of the previous tier",
+            Self::RateLimited => "changed too recently",
+            Self::MissingMetrics => "no CPU load data",
+        }
+    }
+}
+
+/// Picks the tier of `settings.tiers` for `load`, given the tier picked
+/// before. Moving to a higher tier happens as soon as the load reaches it,
+/// moving to a lower one only once the load is `hysteresis` below the
+/// previous tier. `None` without load data and a previous tier.
+pub fn decide_auto_epp(
+    settings: &EppAutoSettings,
+    load: Option<f32>,
+    previous: Option<usize>,
+) -> Option<(usize, EppReason)> {
+    let Some(load) = load else {
+        return previous.map(|tier| (tier, EppReason::MissingMetrics));
+    };
+    let tier = settings
+        .tiers
+        .iter()
+        .position(|tier| load >= tier.min_load)
+        .unwrap_or(settings.tiers.len().saturating_sub(1));
+    match previous {
+        Some(previous)
+            if tier > previous
+                && load >= settings.tiers[previous].min_load - settings.hysteresis =>
+        {
+            Some((previous, EppReason::Hysteresis))
+        }
+        _ => Some((tier, EppReason::Load)),
+    }
+}
+
+pub fn validate_epp_auto_settings(settings: &EppAutoSettings) -> Result<(), EngineError> {
+    let invalid = |problem: &str| {
+        Err(EngineError::ConfigurationError(format!(
+            "Invalid EPP auto settings: {problem}"
+        )))
+    };
+    if settings.tiers.is_empty() {
+        return invalid("at least one tier is needed");
+    }
+    if settings.tiers.iter().any(|tier| {
+        !(0.0..=100.0).contains(&tier.min_load) || tier.epp.eq_ignore_ascii_case(AUTO_EPP)
+    }) {
+        return invalid("each tier needs a min_load between 0 and 100 and an EPP value");
+    }
+    if settings
+        .tiers
+        .windows(2)
+        .any(|pair| pair[0].min_load <= pair[1].min_load)
+    {
+        return invalid("tiers must be ordered from the highest min_load to the lowest");
+    }
+    if !(0.0..=100.0).contains(&settings.hysteresis) {
+        return invalid("hysteresis must be between 0 and 100");
+    }
+    if settings.load_metric == LoadMetric::TopNAverage && settings.top_n == 0 {
+        return invalid("top_n must be at least 1");
+    }
+    if !(1..=MAX_LOAD_WINDOW_SAMPLES).contains(&settings.load_window_samples) {
+        return invalid(&format!(
+            "load_window_samples must be between 1 and {MAX_LOAD_WINDOW_SAMPLES}"
+        ));
+    }
+    if settings
+        .load_window_percentile
+        .is_some_and(|percentile| !(0.0..=100.0).contains(&percentile))
+    {
+        return invalid("load_window_percentile must be between 0 and 100");
+    }
+    Ok(())
+}
+
+/// Why auto turbo decided as it did
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TurboReason {
+    HighTemperature,
+    /// The battery is below `min_battery_percent_for_turbo`
+    LowBattery,
+    /// The CPU throttled at least `throttle_events_limit` times
+    Throttling,
+    HighLoad,
+    /// High load, but too little CPU pressure for it to be CPU-bound
+    LowPressure,
+    LowLoad,
+    IntermediateLoad,
+    /// On phase of `duty_cycle` under sustained high load
+    DutyCycleOn,
+    /// Off phase of `duty_cycle` under sustained high load
+    DutyCycleOff,
+    /// The weighted score reached `enable_above`
+    ScoreHigh,
+    /// The weighted score fell to `disable_below`
+    ScoreLow,
+    ScoreIntermediate,
+    /// Within one of the `schedule` windows
+    ScheduledOn,
+    ScheduledOff,
+    MissingTemperature,
+    MissingMetrics,
+    IncompleteMetrics,
+}
+
+impl TurboReason {
+    /// The reason as logged with the `turbo_decision` event
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::HighTemperature => "high_temperature",
+            Self::LowBattery => "low_battery",
+            Self::Throttling => "throttling",
+            Self::HighLoad => "high_load",
+            Self::LowPressure => "low_pressure",
+            Self::LowLoad => "low_load",
+            Self::IntermediateLoad => "intermediate_load",
+            Self::DutyCycleOn => "duty_cycle_on",
+            Self::DutyCycleOff => "duty_cycle_off",
+            Self::ScoreHigh => "score_high",
+            Self::ScoreLow => "score_low",
+            Self::ScoreIntermediate => "score_intermediate",
+            Self::ScheduledOn => "scheduled_on",
+            Self::ScheduledOff => "scheduled_off",
+            Self::MissingTemperature => "missing_temperature",
+            Self::MissingMetrics => "missing_metrics",
+            Self::IncompleteMetrics => "incomplete_metrics",
         }
+    }
+}
+
+/// Average usage of the CPUs that report it, in percent
+pub fn average_cpu_usage(report: &SystemReport) -> Option<f32> {
+    let usages: Vec<f32> = report
+        .cpu_cores
+        .iter()
+        .filter_map(|core| core.usage_percent)
+        .collect();
+    if usages.is_empty() {
+        None
     } else {
-        debug!(
-            "Auto Turbo: Maintaining turbo state ({}) - no change needed",
-            if enable_turbo { "enabled" } else { "disabled" }
-        );
-        Ok(())
+        Some(usages.iter().sum::<f32>() / usages.len() as f32)
+    }
+}
+
+/// The temperature auto turbo compares against `temp_threshold_high`, per
+/// `temperature_source`. The package temperature is smoothed per
+/// `daemon.smoothing` when the daemon runs, the hottest core is not. Falls
+/// back to the package without per-core readings.
+pub fn turbo_temperature(report: &SystemReport, settings: &TurboAutoSettings) -> Option<f32> {
+    let package = report
+        .cpu_global
+        .smoothed_temperature_celsius
+        .or(report.cpu_global.average_temperature_celsius);
+    match settings.temperature_source {
+        TurboTemperatureSource::Package => package,
+        TurboTemperatureSource::HottestCore => report
+            .cpu_cores
+            .iter()
+            .filter_map(|core| core.temperature_celsius)
+            .reduce(f32::max)
+            .or(package),
+    }
+}
+
+/// Average charge of the batteries that report it, in percent
+pub fn battery_percent(report: &SystemReport) -> Option<u8> {
+    let capacities: Vec<u32> = report
+        .batteries
+        .iter()
+        .filter_map(|battery| battery.capacity_percent)
+        .map(u32::from)
+        .collect();
+    (!capacities.is_empty())
+        .then(|| capacities.iter().sum::<u32>() / capacities.len() as u32)
+        .and_then(|percent| u8::try_from(percent).ok())
+}
+
+/// Whether `min_battery_percent_for_turbo` holds turbo off. Never on AC
+/// power. Once it does, the battery has to charge a little past the
+/// threshold before turbo may enable again.
+pub fn battery_limits_turbo(
+    settings: &TurboAutoSettings,
+    on_ac_power: bool,
+    battery_percent: Option<u8>,
+    limited_before: bool,
+) -> bool {
+    let (Some(min_percent), Some(percent), false) = (
+        settings.min_battery_percent_for_turbo,
+        battery_percent,
+        on_ac_power,
+    ) else {
+        return false;
+    };
+    if limited_before {
+        percent < min_percent.saturating_add(BATTERY_TURBO_HYSTERESIS_PERCENT)
+    } else {
+        percent < min_percent
+    }
+}
+
+/// CPU load auto turbo compares against its thresholds, per the profile's
+/// `load_metric`. The average is the smoothed one when the daemon smooths.
+pub fn turbo_load(report: &SystemReport, settings: &TurboAutoSettings) -> Option<f32> {
+    match settings.load_metric {
+        LoadMetric::Average => report
+            .system_load
+            .smoothed_cpu_usage
+            .or_else(|| average_cpu_usage(report)),
+        metric => cpu_load(report, metric, settings.top_n),
+    }
+}
+
+/// CPU usage reduced to a single load per `metric`, in percent
+pub fn cpu_load(report: &SystemReport, metric: LoadMetric, top_n: usize) -> Option<f32> {
+    let top_n = match metric {
+        LoadMetric::Average => return average_cpu_usage(report),
+        LoadMetric::MaxCore => 1,
+        LoadMetric::TopNAverage => top_n,
+    };
+    let mut usages: Vec<f32> = report
+        .cpu_cores
+        .iter()
+        .filter_map(|core| core.usage_percent)
+        .collect();
+    if usages.is_empty() {
+        return None;
+    }
+    // Busiest first
+    usages.sort_by(|a, b| b.total_cmp(a));
+    usages.truncate(top_n.max(1));
+    Some(usages.iter().sum::<f32>() / usages.len() as f32)
+}
+
+/// Whether `settings` sets a CPU pressure threshold
+const fn uses_pressure(settings: &TurboAutoSettings) -> bool {
+    settings.pressure_threshold_avg10.is_some() || settings.pressure_threshold_avg60.is_some()
+}
+
+/// Whether the CPU pressure is high enough for high load to enable turbo.
+/// Without pressure thresholds or PSI, it always is.
+pub fn pressure_allows_turbo(
+    settings: &TurboAutoSettings,
+    cpu_pressure: Option<CpuPressure>,
+) -> bool {
+    let Some(pressure) = cpu_pressure else {
+        return true;
+    };
+    settings
+        .pressure_threshold_avg10
+        .is_none_or(|threshold| pressure.some_avg10 >= threshold)
+        && settings
+            .pressure_threshold_avg60
+            .is_none_or(|threshold| pressure.some_avg60 >= threshold)
+}
+
+/// Decides whether auto turbo should be enabled, given `metrics` and
+/// whether it was enabled before. Temperature comes first, then throttling,
+/// then the battery, then the [`turbo_strategy::TurboStrategy`] `settings.strategy` selects. Touches nothing,
+/// so `watt simulate` can ask it about conditions that are not there.
+pub fn decide_auto_turbo(
+    settings: &TurboAutoSettings,
+    metrics: &TurboMetrics,
+    previous: bool,
+) -> (bool, TurboReason) {
+    match metrics.temperature {
+        // If temperature is too high, disable turbo regardless of load
+        Some(temp) if temp >= settings.temp_threshold_high => (false, TurboReason::HighTemperature),
+        // Likewise when the CPU throttles anyway
+        _ if settings
+            .throttle_events_limit
+            .zip(metrics.throttle_events)
+            .is_some_and(|(limit, events)| events >= limit) =>
+        {
+            (false, TurboReason::Throttling)
+        }
+        // Likewise with the battery running low
+        _ if metrics.low_battery => (false, TurboReason::LowBattery),
+        _ => turbo_strategy::strategy(settings).decide(metrics, previous),
+    }
+}
+
+fn log_turbo_decision(
+    level: Level,
+    settings: &TurboAutoSettings,
+    group: Option<&str>,
+    metrics: &TurboMetrics,
+    enabled: bool,
+    reason: TurboReason,
+) {
+    let TurboMetrics {
+        temperature: cpu_temp,
+        load: avg_cpu_usage,
+        cpu_pressure,
+        battery_percent,
+        ..
+    } = *metrics;
+    let prefix = group.map_or_else(
+        || "Auto Turbo".to_string(),
+        |group| format!("Auto Turbo [{group}]"),
+    );
+    let group = group.unwrap_or("all");
+    let state = on_off(enabled);
+    let temp = cpu_temp.unwrap_or_default();
+    let usage = avg_cpu_usage.unwrap_or_default();
+    let metric = settings.load_metric.as_str();
+    // Only worth mentioning when it is part of the decision
+    let pressure = cpu_pressure
+        .filter(|_| uses_pressure(settings))
+        .map(|pressure| {
+            format!(
+                "CPU pressure {:.1}% avg10, {:.1}% avg60",
+                pressure.some_avg10, pressure.some_avg60
+            )
+        });
+    match reason {
+        TurboReason::HighTemperature => log!(
+            level,
+            event = "turbo_decision",
+            group = group,
+            reason = reason.as_str(),
+            temperature = temp,
+            threshold = settings.temp_threshold_high,
+            enabled = enabled;
+            "{prefix}: Disabled due to high temperature ({:.1}°C >= {:.1}°C)",
+            temp, settings.temp_threshold_high
+        ),
+        TurboReason::Throttling => {
+            let events = metrics.throttle_events.unwrap_or_default();
+            let limit = settings.throttle_events_limit.unwrap_or_default();
+            log!(
+                level,
+                event = "turbo_decision",
+                group = group,
+                reason = reason.as_str(),
+                throttle_events = events,
+                threshold = limit,
+                enabled = enabled;
+                "{prefix}: Disabled as the CPU throttled {events} times since the last cycle (limit {limit})"
+            );
+        }
+        TurboReason::LowBattery => {
+            let battery = battery_percent.unwrap_or_default();
+            let threshold = settings.min_battery_percent_for_turbo.unwrap_or_default();
+            // Say so when the load alone would have enabled turbo
+            let overridden =
+                if avg_cpu_usage.is_some_and(|usage| usage >= settings.load_threshold_high) {
+                    format!(", despite high CPU load ({metric} {usage:.1}%)")
+                } else {
+                    String::new()
+                };
+            log!(
+                level,
+                event = "turbo_decision",
+                group = group,
+                reason = reason.as_str(),
+                battery = battery,
+                threshold = threshold,
+                load = usage,
+                metric = metric,
+                enabled = enabled;
+                "{prefix}: Disabled due to low battery ({battery}% < {threshold}%){overridden}"
+            );
+        }
+        TurboReason::HighLoad => log!(
+            level,
+            event = "turbo_decision",
+            group = group,
+            reason = reason.as_str(),
+            load = usage,
+            metric = metric,
+            threshold = settings.load_threshold_high,
+            enabled = enabled;
+            "{prefix}: Enabled due to high CPU load ({metric} {:.1}% >= {:.1}%{})",
+            usage, settings.load_threshold_high,
+            pressure.map(|pressure| format!(", {pressure}")).unwrap_or_default()
+        ),
+        TurboReason::LowPressure => log!(
+            level,
+            event = "turbo_decision",
+            group = group,
+            reason = reason.as_str(),
+            load = usage,
+            metric = metric,
+            pressure_avg10 = cpu_pressure.map_or(0.0, |pressure| pressure.some_avg10),
+            pressure_avg60 = cpu_pressure.map_or(0.0, |pressure| pressure.some_avg60),
+            enabled = enabled;
+            "{prefix}: Maintaining previous state ({state}) despite high CPU load ({metric} {usage:.1}%), as {} is below the pressure thresholds",
+            pressure.unwrap_or_default()
+        ),
+        TurboReason::LowLoad => log!(
+            level,
+            event = "turbo_decision",
+            group = group,
+            reason = reason.as_str(),
+            load = usage,
+            metric = metric,
+            threshold = settings.load_threshold_low,
+            enabled = enabled;
+            "{prefix}: Disabled due to low CPU load ({metric} {:.1}% <= {:.1}%)",
+            usage, settings.load_threshold_low
+        ),
+        TurboReason::IntermediateLoad => log!(
+            level,
+            event = "turbo_decision",
+            group = group,
+            reason = reason.as_str(),
+            load = usage,
+            metric = metric,
+            enabled = enabled;
+            "{prefix}: Maintaining previous state ({state}) due to intermediate load ({metric} {usage:.1}%)"
+        ),
+        TurboReason::DutyCycleOn | TurboReason::DutyCycleOff => log!(
+            level,
+            event = "turbo_decision",
+            group = group,
+            reason = reason.as_str(),
+            load = usage,
+            metric = metric,
+            threshold = settings.load_threshold_high,
+            enabled = enabled;
+            "{prefix}: {} for the duty cycle under sustained high CPU load ({metric} {:.1}% >= {:.1}%)",
+            if enabled { "Enabled" } else { "Disabled" },
+            usage, settings.load_threshold_high
+        ),
+        TurboReason::ScoreHigh | TurboReason::ScoreLow | TurboReason::ScoreIntermediate => {
+            let scoring = WeightedScore {
+                settings: &settings.weighted_score,
+                temp_threshold_high: settings.temp_threshold_high,
+            };
+            let score = scoring.score(metrics).unwrap_or_default();
+            let verdict = match reason {
+                TurboReason::ScoreHigh => format!(
+                    "Enabled due to high turbo score ({score:.2} >= {:.2})",
+                    settings.weighted_score.enable_above
+                ),
+                TurboReason::ScoreLow => format!(
+                    "Disabled due to low turbo score ({score:.2} <= {:.2})",
+                    settings.weighted_score.disable_below
+                ),
+                _ => format!("Maintaining previous state ({state}) at turbo score {score:.2}"),
+            };
+            log!(
+                level,
+                event = "turbo_decision",
+                group = group,
+                reason = reason.as_str(),
+                score = score,
+                load = usage,
+                temperature = temp,
+                enabled = enabled;
+                "{prefix}: {verdict} (load: {metric} {usage:.1}%, temperature {temp:.1}°C)"
+            );
+        }
+        TurboReason::ScheduledOn | TurboReason::ScheduledOff => log!(
+            level,
+            event = "turbo_decision",
+            group = group,
+            reason = reason.as_str(),
+            enabled = enabled;
+            "{prefix}: {} by the schedule",
+            if enabled { "Enabled" } else { "Disabled" }
+        ),
+        TurboReason::MissingTemperature => log!(
+            level,
+            event = "turbo_decision",
+            group = group,
+            reason = reason.as_str(),
+            load = usage,
+            metric = metric,
+            enabled = enabled;
+            "{prefix}: Maintaining previous state ({state}) due to missing temperature data (load: {metric} {usage:.1}%)"
+        ),
+        TurboReason::MissingMetrics => log!(
+            level,
+            event = "turbo_decision",
+            group = group,
+            reason = reason.as_str(),
+            enabled = enabled;
+            "{prefix}: Maintaining previous state ({state}) due to missing all CPU metrics"
+        ),
+        TurboReason::IncompleteMetrics => log!(
+            level,
+            event = "turbo_decision",
+            group = group,
+            reason = reason.as_str(),
+            enabled = enabled;
+            "{prefix}: Maintaining previous state ({state}) due to incomplete CPU metrics"
+        ),
     }
 }
 
-fn validate_turbo_auto_settings(settings: &TurboAutoSettings) -> Result<(), EngineError> {
+pub fn validate_turbo_auto_settings(settings: &TurboAutoSettings) -> Result<(), EngineError> {
     if settings.load_threshold_high <= settings.load_threshold_low
         || settings.load_threshold_high > 100.0
         || settings.load_threshold_high < 0.0
@@ -482,5 +4066,635 @@ fn validate_turbo_auto_settings(settings: &TurboAutoSettings) -> Result<(), Engi
         ));
     }
 
+    if settings.load_metric == LoadMetric::TopNAverage && settings.top_n == 0 {
+        return Err(EngineError::ConfigurationError(
+            "Invalid turbo auto settings: top_n must be at least 1".to_string(),
+        ));
+    }
+
+    if [
+        settings.pressure_threshold_avg10,
+        settings.pressure_threshold_avg60,
+    ]
+    .into_iter()
+    .flatten()
+    .any(|threshold| !(0.0..=100.0).contains(&threshold))
+    {
+        return Err(EngineError::ConfigurationError(
+            "Invalid turbo auto settings: pressure thresholds must be between 0 % and 100 %"
+                .to_string(),
+        ));
+    }
+
+    if settings
+        .min_battery_percent_for_turbo
+        .is_some_and(|percent| percent > 100)
+    {
+        return Err(EngineError::ConfigurationError(
+            "Invalid turbo auto settings: min_battery_percent_for_turbo must be between 0 and 100"
+                .to_string(),
+        ));
+    }
+
+    if settings
+        .duty_cycle
+        .is_some_and(|cycle| cycle.on_sec == 0 || cycle.off_sec == 0)
+    {
+        return Err(EngineError::ConfigurationError(
+            "Invalid turbo auto settings: duty_cycle on_sec and off_sec must be at least 1"
+                .to_string(),
+        ));
+    }
+
+    if !(1..=MAX_LOAD_WINDOW_SAMPLES).contains(&settings.load_window_samples) {
+        return Err(EngineError::ConfigurationError(format!(
+            "Invalid turbo auto settings: load_window_samples must be between 1 and {MAX_LOAD_WINDOW_SAMPLES}"
+        )));
+    }
+    if settings
+        .load_window_percentile
+        .is_some_and(|percentile| !(0.0..=100.0).contains(&percentile))
+    {
+        return Err(EngineError::ConfigurationError(
+            "Invalid turbo auto settings: load_window_percentile must be between 0 and 100"
+                .to_string(),
+        ));
+    }
+
+    let score = &settings.weighted_score;
+    if settings.strategy == TurboStrategyKind::WeightedScore {
+        let weights = [
+            score.load_weight,
+            score.temperature_weight,
+            score.battery_weight,
+        ];
+        if weights.iter().any(|weight| *weight < 0.0) || weights.iter().sum::<f32>() <= 0.0 {
+            return Err(EngineError::ConfigurationError(
+                "Invalid turbo auto settings: weighted_score weights must not be negative, and not all 0"
+                    .to_string(),
+            ));
+        }
+        if !(0.0..=1.0).contains(&score.disable_below)
+            || !(0.0..=1.0).contains(&score.enable_above)
+            || score.enable_above <= score.disable_below
+        {
+            return Err(EngineError::ConfigurationError(
+                "Invalid turbo auto settings: weighted_score thresholds must be between 0 and 1 with enable_above > disable_below"
+                    .to_string(),
+            ));
+        }
+    }
+
+    if settings.strategy == TurboStrategyKind::Schedule
+        && !settings
+            .schedule
+            .iter()
+            .any(|window| window.from != window.to)
+    {
+        return Err(EngineError::ConfigurationError(
+            "Invalid turbo auto settings: the schedule strategy needs at least one schedule window"
+                .to_string(),
+        ));
+    }
+
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::TurboDutyCycle;
+    use crate::control::mock::MockController;
+    use crate::core::{BatteryInfo, CpuCoreInfo};
+
+    /// A charger profile that sets every knob the mock controller has
+    fn config() -> AppConfig {
+        AppConfig {
+            charger: ProfileConfig {
+                governor: Some("performance".to_string()),
+                turbo: Some(TurboSetting::Never),
+                epp: Some("performance".to_string()),
+                epb: Some("0".to_string()),
+                min_freq_mhz: Some(800),
+                max_freq_mhz: Some(3000),
+                platform_profile: Some("performance".to_string()),
+                ..ProfileConfig::default()
+            },
+            ..AppConfig::default()
+        }
+    }
+
+    fn apply(config: &AppConfig, controller: &MockController) -> Result<(), EngineError> {
+        determine_and_apply_settings(
+            &SystemReport::for_tests(),
+            config,
+            None,
+            &TurboStates::default(),
+            &KnobStates::default(),
+            controller,
+        )
+    }
+
+    #[test]
+    fn profile_follows_the_detected_power_source() {
+        let mut report = SystemReport::for_tests();
+        assert_eq!(selected_profile_name(&report, None), "charger");
+        assert_eq!(selected_profile_reason(&report, None), "ac_power");
+
+        report.ac_power.online = false;
+        assert!(!is_on_ac_power(&report));
+        assert_eq!(selected_profile_name(&report, None), "battery");
+        assert_eq!(selected_profile_reason(&report, None), "battery_power");
+    }
+
+    #[test]
+    fn batteries_do_not_override_the_detection() {
+        let mut report = SystemReport::for_tests();
+        report.batteries.push(BatteryInfo {
+            name: "BAT0".to_string(),
+            ac_connected: false,
+            charging_state: Some("Discharging".to_string()),
+            capacity_percent: Some(50),
+            power_rate_watts: None,
+            charge_start_threshold: None,
+            charge_stop_threshold: None,
+        });
+        assert!(is_on_ac_power(&report));
+        assert_eq!(selected_profile_name(&report, None), "charger");
+    }
+
+    /// A config whose battery and `[balanced]` profiles set different knobs
+    fn balanced_config(balanced: bool) -> AppConfig {
+        AppConfig {
+            battery: ProfileConfig {
+                governor: Some("conservative".to_string()),
+                epp: Some("power".to_string()),
+                ..ProfileConfig::default()
+            },
+            balanced: balanced.then(|| ProfileConfig {
+                governor: Some("schedutil".to_string()),
+                epp: Some("balance_performance".to_string()),
+                ..ProfileConfig::default()
+            }),
+            ..AppConfig::default()
+        }
+    }
+
+    fn apply_balanced(config: &AppConfig, controller: &MockController) {
+        let report = SystemReport::for_tests();
+        let force = ForcedProfile::Mode(OperationalMode::Balanced);
+        assert_eq!(selected_profile_name(&report, Some(&force)), "balanced");
+        determine_and_apply_settings(
+            &report,
+            config,
+            Some(&force),
+            &TurboStates::default(),
+            &KnobStates::default(),
+            controller,
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn balanced_falls_back_to_the_battery_profile() {
+        let controller = MockController::new();
+        apply_balanced(&balanced_config(false), &controller);
+        assert_eq!(
+            controller.calls(),
+            ["set_governor conservative", "set_epp power"]
+        );
+    }
+
+    #[test]
+    fn balanced_applies_its_own_section() {
+        let controller = MockController::new();
+        apply_balanced(&balanced_config(true), &controller);
+        assert_eq!(
+            controller.calls(),
+            ["set_governor schedutil", "set_epp balance_performance"]
+        );
+    }
+
+    #[test]
+    fn applies_in_order() {
+        let controller = MockController::new();
+        apply(&config(), &controller).unwrap();
+        assert_eq!(
+            controller.calls(),
+            [
+                "set_governor performance",
+                "set_turbo 0",
+                "set_platform_profile performance",
+                "set_epp performance",
+                "set_epb 0",
+                "set_min_frequency 800",
+                "set_max_frequency 3000",
+            ]
+        );
+    }
+
+    #[test]
+    fn requested_order_comes_first() {
+        let mut config = config();
+        config.daemon.apply_order = vec![ApplyStep::Frequencies, ApplyStep::Epp];
+        let controller = MockController::new();
+        apply(&config, &controller).unwrap();
+        assert_eq!(
+            controller.calls(),
+            [
+                "set_min_frequency 800",
+                "set_max_frequency 3000",
+                "set_epp performance",
+                "set_governor performance",
+                "set_turbo 0",
+                "set_platform_profile performance",
+                "set_epb 0",
+            ]
+        );
+    }
+
+    #[test]
+    fn default_order_follows_the_dependencies() {
+        let order = apply_order(&[]).unwrap();
+        assert_eq!(
+            order,
+            [
+                ApplyStep::Governor,
+                ApplyStep::Turbo,
+                ApplyStep::PlatformProfile,
+                ApplyStep::Epp,
+                ApplyStep::Epb,
+                ApplyStep::Frequencies,
+                ApplyStep::ChargeThresholds,
+            ]
+        );
+        assert!(apply_order_violations(&order).is_empty());
+    }
+
+    #[test]
+    fn requested_order_is_trusted_and_reported() {
+        let order = apply_order(&[ApplyStep::Epp, ApplyStep::Turbo]).unwrap();
+        assert_eq!(
+            order,
+            [
+                ApplyStep::Epp,
+                ApplyStep::Turbo,
+                ApplyStep::Governor,
+                ApplyStep::PlatformProfile,
+                ApplyStep::Epb,
+                ApplyStep::Frequencies,
+                ApplyStep::ChargeThresholds,
+            ]
+        );
+        assert_eq!(
+            apply_order_violations(&order),
+            [
+                (ApplyStep::Epp, ApplyStep::Governor),
+                (ApplyStep::Epp, ApplyStep::PlatformProfile),
+            ]
+        );
+    }
+
+    #[test]
+    fn repeated_steps_are_rejected() {
+        assert!(matches!(
+            apply_order(&[ApplyStep::Epp, ApplyStep::Governor, ApplyStep::Epp]),
+            Err(EngineError::ConfigurationError(_))
+        ));
+    }
+
+    #[test]
+    fn frequency_limits_write_in_an_order_the_kernel_accepts() {
+        use FrequencyLimit::{Max, Min};
+        // Raising the minimum past the current maximum
+        assert_eq!(frequency_write_order(Some(2000), Some(3000)), [Max, Min]);
+        // Lowering the maximum below the current minimum, or within range
+        assert_eq!(frequency_write_order(Some(4000), Some(800)), [Min, Max]);
+        assert_eq!(frequency_write_order(Some(3000), Some(3000)), [Min, Max]);
+        // Nothing to compare against
+        assert_eq!(frequency_write_order(None, Some(3000)), [Min, Max]);
+        assert_eq!(frequency_write_order(Some(2000), None), [Min, Max]);
+    }
+
+    /// A charger profile with auto turbo under `settings`, whose turbo was
+    /// written moments ago and may only be written every 10 minutes
+    fn limited_auto_turbo(settings: TurboAutoSettings) -> (AppConfig, KnobStates) {
+        let mut config = AppConfig {
+            charger: ProfileConfig {
+                governor: None,
+                turbo: Some(TurboSetting::Auto),
+                turbo_auto_settings: settings,
+                ..ProfileConfig::default()
+            },
+            ..AppConfig::default()
+        };
+        config
+            .daemon
+            .min_write_interval_sec
+            .insert(ApplyStep::Turbo, 600);
+        let knobs = KnobStates::default();
+        knobs.record_write_time(ApplyStep::Turbo);
+        (config, knobs)
+    }
+
+    fn apply_with(
+        report: &SystemReport,
+        config: &AppConfig,
+        turbo_states: &TurboStates,
+        knobs: &KnobStates,
+        controller: &MockController,
+    ) {
+        determine_and_apply_settings(report, config, None, turbo_states, knobs, controller)
+            .unwrap();
+    }
+
+    #[test]
+    fn hot_core_cuts_turbo_despite_the_write_interval() {
+        let (config, knobs) = limited_auto_turbo(TurboAutoSettings {
+            temperature_source: TurboTemperatureSource::HottestCore,
+            initial_turbo_state: true,
+            ..TurboAutoSettings::default()
+        });
+        let mut report = SystemReport::for_tests();
+        report.cpu_global.average_temperature_celsius = Some(60.0);
+        report.cpu_cores.push(CpuCoreInfo {
+            core_id: 0,
+            current_frequency_mhz: None,
+            min_frequency_mhz: None,
+            max_frequency_mhz: None,
+            usage_percent: None,
+            temperature_celsius: Some(80.0),
+            governor: None,
+        });
+
+        let controller = MockController::new();
+        apply_with(
+            &report,
+            &config,
+            &TurboStates::default(),
+            &knobs,
+            &controller,
+        );
+        assert_eq!(controller.calls(), ["set_turbo 0"]);
+    }
+
+    #[test]
+    fn smoothed_heat_cuts_turbo_despite_the_write_interval() {
+        let (config, knobs) = limited_auto_turbo(TurboAutoSettings {
+            initial_turbo_state: true,
+            ..TurboAutoSettings::default()
+        });
+        let mut report = SystemReport::for_tests();
+        report.cpu_global.average_temperature_celsius = Some(70.0);
+        report.cpu_global.smoothed_temperature_celsius = Some(80.0);
+
+        let controller = MockController::new();
+        apply_with(
+            &report,
+            &config,
+            &TurboStates::default(),
+            &knobs,
+            &controller,
+        );
+        assert_eq!(controller.calls(), ["set_turbo 0"]);
+    }
+
+    #[test]
+    fn thermal_cap_goes_by_its_window_despite_the_write_interval() {
+        let mut config = AppConfig {
+            charger: ProfileConfig {
+                governor: None,
+                turbo: None,
+                thermal_backoff: Some(ThermalBackoff {
+                    steps: vec![ThermalStep {
+                        above_celsius: 60.0,
+                        max_percent: 50,
+                    }],
+                    hysteresis: 3.0,
+                    temp_window_samples: 3,
+                }),
+                ..ProfileConfig::default()
+            },
+            ..AppConfig::default()
+        };
+        config
+            .daemon
+            .min_write_interval_sec
+            .insert(ApplyStep::Frequencies, 600);
+        let turbo_states = TurboStates::default();
+        let knobs = KnobStates::default();
+        let controller = MockController::new();
+        let mut report = SystemReport::for_tests();
+
+        report.cpu_global.average_temperature_celsius = Some(70.0);
+        apply_with(&report, &config, &turbo_states, &knobs, &controller);
+        assert_eq!(controller.calls(), ["set_max_frequency 2000"]);
+
+        // Below the step, but the window of 70 and 55 is still above it
+        report.cpu_global.average_temperature_celsius = Some(55.0);
+        apply_with(&report, &config, &turbo_states, &knobs, &controller);
+        assert_eq!(controller.calls().len(), 2);
+    }
+
+    #[test]
+    fn failed_apply_rolls_back_newest_first() {
+        let mut config = config();
+        config.daemon.rollback_on_failure = true;
+        let controller = MockController::new().failing("set_epb");
+        assert!(apply(&config, &controller).is_err());
+        assert_eq!(
+            controller.calls(),
+            [
+                "set_governor performance",
+                "set_turbo 0",
+                "set_platform_profile performance",
+                "set_epp performance",
+                "set_epb 0",
+                "restore_file epp balance_power",
+                "restore_file platform_profile balanced",
+                "restore_file turbo 1",
+                "restore_file governor powersave",
+            ]
+        );
+        assert_eq!(controller.value("governor"), "powersave");
+        assert_eq!(controller.value("max_frequency"), "4000");
+    }
+
+    #[test]
+    fn rollback_names_the_steps_it_undid() {
+        let controller = MockController::new();
+        let knobs = KnobStates::default();
+        let (_, writes) = sysfs::capture_writes(|| controller.set_governor("performance", None));
+        knobs.journal(ApplyStep::Governor, "governor", &writes);
+        let (_, writes) = sysfs::capture_writes(|| controller.set_turbo_for_cpus(false, &[0, 1]));
+        knobs.journal(ApplyStep::Turbo, "Turbo boost [efficiency]", &writes);
+
+        assert_eq!(
+            knobs.roll_back(&controller),
+            [
+                (ApplyStep::Turbo, "Turbo boost [efficiency]".to_string()),
+                (ApplyStep::Governor, "governor".to_string()),
+            ]
+        );
+        assert_eq!(knobs.journal_len(), 0);
+    }
+
+    #[test]
+    fn failed_apply_stays_without_rollback_on_failure() {
+        let controller = MockController::new().failing("set_epb");
+        assert!(apply(&config(), &controller).is_err());
+        assert_eq!(controller.calls().last().unwrap(), "set_epb 0");
+        assert_eq!(controller.value("governor"), "performance");
+    }
+
+    /// One auto turbo cycle of `profile` at `load`, as
+    /// `decide_and_apply_turbo` runs it, returning whether turbo is on after
+    fn turbo_cycle(
+        map: &HysteresisMap,
+        profile: &'static str,
+        on_ac: bool,
+        settings: &TurboAutoSettings,
+        load: f32,
+    ) -> bool {
+        let hysteresis = map.get(profile, on_ac, settings);
+        let previous = hysteresis
+            .get_previous_state()
+            .unwrap_or_else(|| hysteresis.initialize_with(settings.initial_turbo_state));
+        let metrics = TurboMetrics {
+            temperature: Some(50.0),
+            load: Some(load),
+            on_ac_power: on_ac,
+            ..TurboMetrics::default()
+        };
+        let (enabled, _) = decide_auto_turbo(settings, &metrics, previous);
+        hysteresis.update_state(enabled);
+        enabled
+    }
+
+    #[test]
+    fn hysteresis_switches_at_the_thresholds() {
+        let map = HysteresisMap::default();
+        let settings = TurboAutoSettings::default();
+
+        assert!(!turbo_cycle(&map, "charger", true, &settings, 50.0));
+        assert!(turbo_cycle(&map, "charger", true, &settings, 70.0));
+        assert!(turbo_cycle(&map, "charger", true, &settings, 50.0));
+        assert!(turbo_cycle(&map, "charger", true, &settings, 30.1));
+        assert!(!turbo_cycle(&map, "charger", true, &settings, 30.0));
+        assert!(!turbo_cycle(&map, "charger", true, &settings, 69.9));
+    }
+
+    #[test]
+    fn duty_cycle_waits_out_the_dwell_time() {
+        let settings = TurboAutoSettings {
+            duty_cycle: Some(TurboDutyCycle {
+                after_sec: 60,
+                on_sec: 20,
+                off_sec: 10,
+            }),
+            ..TurboAutoSettings::default()
+        };
+        let hysteresis = TurboHysteresis::default();
+        let start = Instant::now();
+        let high = (true, TurboReason::HighLoad);
+        let at = |secs, decision| {
+            hysteresis.duty_cycle(&settings, decision, start + Duration::from_secs(secs))
+        };
+
+        assert_eq!(at(0, high), high);
+        assert_eq!(at(59, high), high);
+        assert_eq!(at(60, high), (true, TurboReason::DutyCycleOn));
+        assert_eq!(at(79, high), (true, TurboReason::DutyCycleOn));
+        assert_eq!(at(80, high), (false, TurboReason::DutyCycleOff));
+        assert_eq!(at(89, high), (false, TurboReason::DutyCycleOff));
+        assert_eq!(at(90, high), (true, TurboReason::DutyCycleOn));
+
+        // Any other decision ends the cycle, and the wait starts over
+        let low = (false, TurboReason::LowLoad);
+        assert_eq!(at(95, low), low);
+        assert_eq!(at(100, high), high);
+        assert_eq!(at(159, high), high);
+        assert_eq!(at(160, high), (true, TurboReason::DutyCycleOn));
+    }
+
+    #[test]
+    fn profiles_keep_their_own_hysteresis() {
+        let map = HysteresisMap::default();
+        let charger = TurboAutoSettings::default();
+        let quiet = TurboAutoSettings {
+            load_threshold_high: 90.0,
+            load_threshold_low: 60.0,
+            ..TurboAutoSettings::default()
+        };
+
+        assert!(turbo_cycle(&map, "charger", true, &charger, 80.0));
+        // Another profile starts from initial_turbo_state, not from turbo on
+        assert!(!turbo_cycle(&map, "quiet", true, &quiet, 80.0));
+        // As does the same profile on battery power
+        assert!(!turbo_cycle(&map, "charger", false, &charger, 50.0));
+        // And neither touched the charger profile on AC
+        assert!(turbo_cycle(&map, "charger", true, &charger, 50.0));
+        assert!(!turbo_cycle(&map, "quiet", true, &quiet, 80.0));
+    }
+
+    #[test]
+    fn reload_with_new_thresholds_starts_over() {
+        let map = HysteresisMap::default();
+        let settings = TurboAutoSettings::default();
+        assert!(turbo_cycle(&map, "charger", true, &settings, 80.0));
+        // The same settings keep the state, so turbo stays on between the
+        // thresholds
+        assert!(turbo_cycle(&map, "charger", true, &settings, 50.0));
+
+        let reloaded = TurboAutoSettings {
+            load_threshold_high: 80.0,
+            ..TurboAutoSettings::default()
+        };
+        assert_eq!(
+            changed_settings(&settings, &reloaded),
+            ["load_threshold_high 70.0 → 80.0"]
+        );
+        // Back to initial_turbo_state, which is off
+        assert!(!turbo_cycle(&map, "charger", true, &reloaded, 50.0));
+        assert!(!turbo_cycle(&map, "charger", true, &reloaded, 75.0));
+        assert!(turbo_cycle(&map, "charger", true, &reloaded, 80.1));
+    }
+
+    #[test]
+    fn restored_state_adopts_the_settings() {
+        let map = HysteresisMap::default();
+        let settings = TurboAutoSettings::default();
+        turbo_cycle(&map, "charger", true, &settings, 80.0);
+        let saved = map.saved();
+        assert_eq!(saved.len(), 1);
+
+        let restored = HysteresisMap::default();
+        for entry in saved {
+            restored.restore("charger", entry.on_ac, entry.state);
+        }
+        // The saved state carries over into whatever settings are loaded
+        let reloaded = TurboAutoSettings {
+            load_threshold_low: 40.0,
+            ..TurboAutoSettings::default()
+        };
+        assert!(turbo_cycle(&restored, "charger", true, &reloaded, 50.0));
+        assert!(!turbo_cycle(&restored, "charger", true, &reloaded, 40.0));
+    }
+
+    #[test]
+    fn mark_disabled_updates_every_state() {
+        let map = HysteresisMap::default();
+        let settings = TurboAutoSettings::default();
+        assert!(turbo_cycle(&map, "charger", true, &settings, 80.0));
+        assert!(turbo_cycle(&map, "quiet", true, &settings, 80.0));
+
+        assert!(map.mark_disabled());
+        for profile in ["charger", "quiet"] {
+            assert_eq!(
+                map.get(profile, true, &settings).get_previous_state(),
+                Some(false)
+            );
+        }
+        assert!(!map.mark_disabled());
+    }
+}
@@ -0,0 +1,737 @@
+// Event sources the daemon's monitor thread waits on between cycles
+//
+// The loop sleeps in poll(2) over a timerfd for the periodic cycle, an eventfd
+// used to wake it from other threads, an inotify descriptor watching the
+// configuration, and, in event-driven mode, the kernel's uevent netlink socket
+// so power supply, thermal and display changes are handled as soon as they
+// happen, and the lid switch's input device.
+use crate::lid;
+use log::{debug, info, warn};
+use std::collections::HashMap;
+use std::ffi::{CString, OsString};
+use std::fs::{self, File};
+use std::io;
+use std::mem;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::MetadataExt;
+use std::path::{self, Path, PathBuf};
+use std::ptr;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Kernel uevent multicast group
+const UEVENT_GROUP_KERNEL: u32 = 1;
+
+/// Subsystems whose uevents trigger an immediate cycle. drm reports displays
+/// being plugged in and out.
+const WATCHED_SUBSYSTEMS: &[&str] = &["power_supply", "thermal", "drm"];
+
+/// Directory events that can change a configuration file or what its path
+/// resolves to
+const CONFIG_DIR_EVENTS: u32 = libc::IN_CLOSE_WRITE
+    | libc::IN_CREATE
+    | libc::IN_DELETE
+    | libc::IN_MOVED_FROM
+    | libc::IN_MOVED_TO
+    | libc::IN_ATTRIB
+    | libc::IN_DELETE_SELF
+    | libc::IN_MOVE_SELF
+    | libc::IN_ONLYDIR;
+
+/// How long the configuration has to stay quiet before a change counts, so a
+/// reload does not catch an editor halfway through saving
+const CONFIG_SETTLE_TIME: Duration = Duration::from_millis(200);
+
+/// Longest a burst of configuration events is waited out
+const CONFIG_SETTLE_LIMIT: Duration = Duration::from_secs(2);
+
+/// Symlinks followed at most when resolving a configuration path, as in the
+/// kernel
+const MAX_SYMLINKS: usize = 40;
+
+/// Why the monitor thread was woken up
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Wakeup {
+    /// The poll interval elapsed
+    Timer,
+    /// A power supply, thermal or display device changed, or the lid moved
+    Device { subsystem: String, action: String },
+    /// Another thread asked for a new cycle, e.g. after a control request
+    Requested,
+    /// A configuration file was changed or replaced
+    ConfigChanged,
+}
+
+/// Wakes the monitor thread from another thread. Backed by an eventfd, so it is
+/// safe to use from signal-handling threads.
+pub struct Waker {
+    fd: OwnedFd,
+}
+
+impl Waker {
+    pub fn new() -> io::Result<Self> {
+        // SAFETY: eventfd has no memory safety requirements
+        let fd = unsafe { libc::eventfd(0, libc::EFD_CLOEXEC | libc::EFD_NONBLOCK) };
+        Ok(Self { fd: owned_fd(fd)? })
+    }
+
+    /// Makes the next (or current) wait return [`Wakeup::Requested`]
+    pub fn wake(&self) {
+        let value: u64 = 1;
+        // SAFETY: writes 8 bytes from a valid u64
+        let ret = unsafe {
+            libc::write(
+                self.fd.as_raw_fd(),
+                (&raw const value).cast(),
+                mem::size_of::<u64>(),
+            )
+        };
+        if ret < 0 {
+            debug!(
+                "Failed to wake monitor thread: {}",
+                io::Error::last_os_error()
+            );
+        }
+    }
+}
+
+/// One-shot CLOCK_REALTIME timer firing at absolute times, so cycles land on
+/// the same wall-clock boundaries however long each one took
+struct Timer {
+    fd: OwnedFd,
+}
+
+impl Timer {
+    fn new() -> io::Result<Self> {
+        // SAFETY: timerfd_create has no memory safety requirements
+        let fd = unsafe {
+            libc::timerfd_create(libc::CLOCK_REALTIME, libc::TFD_CLOEXEC | libc::TFD_NONBLOCK)
+        };
+        Ok(Self { fd: owned_fd(fd)? })
+    }
+
+    /// Arms the timer to fire once at `deadline`. If the system clock is set
+    /// in the meantime, the timer fires early so it can be armed again.
+    fn arm(&self, deadline: SystemTime) -> io::Result<()> {
+        // A zero value would disarm the timer instead of firing immediately
+        let since_epoch = deadline
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .max(Duration::from_nanos(1));
+        let spec = libc::itimerspec {
+            it_interval: libc::timespec {
+                tv_sec: 0,
+                tv_nsec: 0,
+            },
+            it_value: libc::timespec {
+                tv_sec: since_epoch.as_secs() as libc::time_t,
+                tv_nsec: libc::c_long::from(since_epoch.subsec_nanos()),
+            },
+        };
+        let flags = libc::TFD_TIMER_ABSTIME | libc::TFD_TIMER_CANCEL_ON_SET;
+        // SAFETY: `spec` is a valid itimerspec and the old value is not requested
+        let ret = unsafe {
+            libc::timerfd_settime(self.fd.as_raw_fd(), flags, &spec, std::ptr::null_mut())
+        };
+        if ret < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+}
+
+/// Socket receiving kernel uevents
+struct UeventSocket {
+    fd: OwnedFd,
+}
+
+impl UeventSocket {
+    fn new() -> io::Result<Self> {
+        // SAFETY: socket has no memory safety requirements
+        let fd = unsafe {
+            libc::socket(
+                libc::AF_NETLINK,
+                libc::SOCK_RAW | libc::SOCK_CLOEXEC | libc::SOCK_NONBLOCK,
+                libc::NETLINK_KOBJECT_UEVENT,
+            )
+        };
+        let fd = owned_fd(fd)?;
+
+        // SAFETY: sockaddr_nl is plain data, all-zeroes is a valid value
+        let mut addr: libc::sockaddr_nl = unsafe { mem::zeroed() };
+        addr.nl_family = libc::AF_NETLINK as libc::sa_family_t;
+        addr.nl_groups = UEVENT_GROUP_KERNEL;
+
+        // SAFETY: `addr` is a valid sockaddr_nl of the given size
+        let ret = unsafe {
+            libc::bind(
+                fd.as_raw_fd(),
+                (&raw const addr).cast(),
+                mem::size_of::<libc::sockaddr_nl>() as libc::socklen_t,
+            )
+        };
+        if ret < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(Self { fd })
+    }
+
+    /// Reads all pending uevents, returning the first one from a watched subsystem
+    fn receive(&self) -> Option<Wakeup> {
+        let mut buf = [0u8; 8192];
+        let mut first = None;
+
+        loop {
+            // SAFETY: `buf` is valid for writes of its full length
+            let len =
+                unsafe { libc::recv(self.fd.as_raw_fd(), buf.as_mut_ptr().cast(), buf.len(), 0) };
+            if len <= 0 {
+                break;
+            }
+
+            if let Some(event) = parse_uevent(&buf[..len as usize]) {
+                debug!("Received uevent: {event:?}");
+                first.get_or_insert(event);
+            }
+        }
+
+        first
+    }
+}
+
+/// Parses a kernel uevent of the form `action@devpath\0KEY=VALUE\0...`,
+/// returning it if it belongs to a watched subsystem
+fn parse_uevent(message: &[u8]) -> Option<Wakeup> {
+    let mut fields = message
+        .split(|&b| b == 0)
+        .filter_map(|f| std::str::from_utf8(f).ok());
+    // Skip the `action@devpath` header
+    fields.next()?;
+
+    let mut action = None;
+    let mut subsystem = None;
+    for field in fields {
+        if let Some(value) = field.strip_prefix("ACTION=") {
+            action = Some(value);
+        } else if let Some(value) = field.strip_prefix("SUBSYSTEM=") {
+            subsystem = Some(value);
+        }
+    }
+
+    let subsystem = subsystem.filter(|s| WATCHED_SUBSYSTEMS.contains(s))?;
+    Some(Wakeup::Device {
+        subsystem: subsystem.to_string(),
+        action: action.unwrap_or("change").to_string(),
+    })
+}
+
+/// The input device of the lid switch
+struct LidSwitch {
+    device: File,
+}
+
+impl LidSwitch {
+    /// Reads all pending input events, returning the last lid switch change
+    fn receive(&self) -> Option<Wakeup> {
+        // SAFETY: input_event is plain data, all-zeroes is a valid value
+        let mut event: libc::input_event = unsafe { mem::zeroed() };
+        let mut last = None;
+
+        loop {
+            // SAFETY: reads at most one input_event into a valid input_event
+            let len = unsafe {
+                libc::read(
+                    self.device.as_raw_fd(),
+                    (&raw mut event).cast(),
+                    mem::size_of::<libc::input_event>(),
+                )
+            };
+            if len != mem::size_of::<libc::input_event>() as isize {
+                break;
+            }
+
+            if event.type_ == lid::EV_SW && event.code == lid::SW_LID {
+                last = Some(Wakeup::Device {
+                    subsystem: "lid".to_string(),
+                    action: if event.value == 0 { "opened" } else { "closed" }.to_string(),
+                });
+            }
+        }
+
+        if let Some(event) = &last {
+            debug!("Received lid switch event: {event:?}");
+        }
+        last
+    }
+}
+
+/// What a configuration path resolves to, to tell real changes from noise
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct FileVersion {
+    target: PathBuf,
+    dev: u64,
+    ino: u64,
+    size: u64,
+    mtime: (i64, i64),
+}
+
+impl FileVersion {
+    /// `None` if nothing is at `path`
+    fn of(path: &Path) -> Option<Self> {
+        let target = fs::canonicalize(path).ok()?;
+        let metadata = fs::metadata(&target).ok()?;
+        Some(Self {
+            target,
+            dev: metadata.dev(),
+            ino: metadata.ino(),
+            size: metadata.len(),
+            mtime: (metadata.mtime(), metadata.mtime_nsec()),
+        })
+    }
+}
+
+/// Watches the configuration files through inotify
+///
+/// Editors tend to save by renaming a new file over the old one, and on NixOS
+/// the file is reached through symlinks that system activation repoints. So
+/// rather than the files, the directories holding them and every symlink on
+/// the way are watched, events are matched by name, and the paths are
+/// resolved again after each change.
+struct ConfigWatch {
+    fd: OwnedFd,
+    paths: Vec<PathBuf>,
+    /// Entry names of interest in each watched directory, by watch descriptor
+    watches: HashMap<libc::c_int, Vec<OsString>>,
+    /// What each path resolved to after the last change
+    versions: Vec<Option<FileVersion>>,
+}
+
+impl ConfigWatch {
+    fn new(paths: Vec<PathBuf>) -> io::Result<Self> {
+        // SAFETY: inotify_init1 has no memory safety requirements
+        let fd = unsafe { libc::inotify_init1(libc::IN_CLOEXEC | libc::IN_NONBLOCK) };
+        let mut watch = Self {
+            fd: owned_fd(fd)?,
+            versions: paths.iter().map(|path| FileVersion::of(path)).collect(),
+            paths,
+            watches: HashMap::new(),
+        };
+        watch.rewatch();
+        Ok(watch)
+    }
+
+    /// Replaces the watches with ones for what the paths resolve to now
+    fn rewatch(&mut self) {
+        for wd in self.watches.keys() {
+            // SAFETY: inotify_rm_watch has no memory safety requirements
+            unsafe { libc::inotify_rm_watch(self.fd.as_raw_fd(), *wd) };
+        }
+        self.watches.clear();
+
+        for path in &self.paths {
+            for (dir, name) in watch_points(path) {
+                let Ok(c_dir) = CString::new(dir.as_os_str().as_bytes()) else {
+                    continue;
+                };
+                // SAFETY: `c_dir` is a valid NUL-terminated path
+                let wd = unsafe {
+                    libc::inotify_add_watch(self.fd.as_raw_fd(), c_dir.as_ptr(), CONFIG_DIR_EVENTS)
+                };
+                if wd < 0 {
+                    debug!(
+                        "Cannot watch {} for configuration changes: {}",
+                        dir.display(),
+                        io::Error::last_os_error()
+                    );
+                    continue;
+                }
+                self.watches.entry(wd).or_default().push(name);
+            }
+        }
+    }
+
+    /// Consumes pending events, returning whether a configuration file now has
+    /// different contents. A file that disappeared does not count, so the
+    /// current configuration stays until it is back.
+    fn changed(&mut self) -> bool {
+        if !self.read_events() {
+            return false;
+        }
+        let settle_until = Instant::now() + CONFIG_SETTLE_LIMIT;
+        while Instant::now() < settle_until && is_readable(&self.fd, CONFIG_SETTLE_TIME) {
+            self.read_events();
+        }
+
+        self.rewatch();
+        let mut changed = false;
+        for (path, version) in self.paths.iter().zip(&mut self.versions) {
+            let current = FileVersion::of(path);
+            if current == *version {
+                continue;
+            }
+            if current.is_some() {
+                info!("Configuration file {} changed", path.display());
+                changed = true;
+            } else {
+                info!(
+                    "Configuration file {} was removed, keeping the current configuration",
+                    path.display()
+                );
+            }
+            *version = current;
+        }
+        changed
+    }
+
+    /// Reads all pending events, returning whether any concerns a watched name
+    fn read_events(&self) -> bool {
+        let mut relevant = false;
+        read_inotify_events(&self.fd, |event, name| {
+            relevant |= self.is_relevant(event, name);
+        });
+        relevant
+    }
+
+    fn is_relevant(&self, event: &libc::inotify_event, name: &[u8]) -> bool {
+        // Events were lost, so anything may have changed
+        if event.mask & libc::IN_Q_OVERFLOW != 0 {
+            return true;
+        }
+        let Some(names) = self.watches.get(&event.wd) else {
+            return false;
+        };
+        // The watched directory itself went away or moved
+        if event.mask & (libc::IN_DELETE_SELF | libc::IN_MOVE_SELF) != 0 {
+            return true;
+        }
+        names.iter().any(|watched| watched.as_bytes() == name)
+    }
+}
+
+/// Hands every pending event of the inotify descriptor `fd` to `f`, with the
+/// name it carries, if any
+fn read_inotify_events(fd: &OwnedFd, mut f: impl FnMut(&libc::inotify_event, &[u8])) {
+    const HEADER: usize = mem::size_of::<libc::inotify_event>();
+    let mut buf = [0u8; 4096];
+
+    loop {
+        // SAFETY: `buf` is valid for writes of its full length
+        let len = unsafe { libc::read(fd.as_raw_fd(), buf.as_mut_ptr().cast(), buf.len()) };
+        if len <= 0 {
+            break;
+        }
+        let len = len as usize;
+
+        let mut offset = 0;
+        while offset + HEADER <= len {
+            // SAFETY: the kernel only returns whole events, and the
+            // unaligned read copes with the byte buffer
+            let event: libc::inotify_event =
+                unsafe { ptr::read_unaligned(buf[offset..].as_ptr().cast()) };
+            let name_end = (offset + HEADER + event.len as usize).min(len);
+            // The name is padded with NULs
+            let name = buf[offset + HEADER..name_end]
+                .split(|&b| b == 0)
+                .next()
+                .unwrap_or_default();
+            f(&event, name);
+            offset = name_end;
+        }
+    }
+}
+
+/// Watches a single file through inotify on its directory, so a file replaced
+/// by renaming another over it is noticed as well
+pub struct FileWatch {
+    fd: OwnedFd,
+    name: OsString,
+}
+
+impl FileWatch {
+    /// Fails if the directory holding `path` does not exist
+    pub fn new(path: &Path) -> io::Result<Self> {
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        let name = path.file_name().unwrap_or_default().to_os_string();
+        // SAFETY: inotify_init1 has no memory safety requirements
+        let fd = owned_fd(unsafe { libc::inotify_init1(libc::IN_CLOEXEC | libc::IN_NONBLOCK) })?;
+        let c_dir = CString::new(dir.as_os_str().as_bytes())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        // SAFETY: `c_dir` is a valid NUL-terminated path
+        let wd = unsafe {
+            libc::inotify_add_watch(
+                fd.as_raw_fd(),
+                c_dir.as_ptr(),
+                libc::IN_CLOSE_WRITE
+                    | libc::IN_CREATE
+                    | libc::IN_DELETE
+                    | libc::IN_MOVED_FROM
+                    | libc::IN_MOVED_TO
+                    | libc::IN_ONLYDIR,
+            )
+        };
+        if wd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Self { fd, name })
+    }
+
+    /// Waits until the file is written, replaced or removed, or `timeout`
+    /// passed
+    pub fn wait(&self, timeout: Duration) {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let left = deadline.saturating_duration_since(Instant::now());
+            if left.is_zero() || !is_readable(&self.fd, left) {
+                return;
+            }
+            let mut changed = false;
+            read_inotify_events(&self.fd, |event, name| {
+                changed |= event.mask & libc::IN_Q_OVERFLOW != 0 || name == self.name.as_bytes();
+            });
+            if changed {
+                return;
+            }
+        }
+    }
+}
+
+/// Directory entries whose change can change what `path` resolves to: every
+/// symlink on the way to the file, and the file itself. A missing directory is
+/// replaced by its nearest existing ancestor, so its creation is noticed.
+fn watch_points(path: &Path) -> Vec<(PathBuf, OsString)> {
+    let mut points = Vec::new();
+    let mut path = path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+
+    'resolve: for _ in 0..MAX_SYMLINKS {
+        let mut prefix = PathBuf::new();
+        for component in path.components() {
+            prefix.push(component);
+            let (Some(parent), Some(name)) = (prefix.parent(), prefix.file_name()) else {
+                continue;
+            };
+            let Ok(target) = fs::read_link(&prefix) else {
+                continue;
+            };
+            points.push((parent.to_path_buf(), name.to_os_string()));
+
+            // Continue with the rest of the path below the link's target
+            let mut resolved = parent.join(target);
+            if let Ok(rest) = path.strip_prefix(&prefix) {
+                if !rest.as_os_str().is_empty() {
+                    resolved.push(rest);
+                }
+            }
+            path = resolved;
+            continue 'resolve;
+        }
+        break;
+    }
+
+    let mut dir = path.parent().map(Path::to_path_buf);
+    let mut name = path.file_name().map(|name| name.to_os_string());
+    while let (Some(current_dir), Some(current_name)) = (&dir, &name) {
+        if current_dir.is_dir() {
+            points.push((current_dir.clone(), current_name.clone()));
+            break;
+        }
+        name = current_dir.file_name().map(|name| name.to_os_string());
+        dir = current_dir.parent().map(Path::to_path_buf);
+    }
+    points
+}
+
+/// Whether `fd` becomes readable within `timeout`
+fn is_readable(fd: &OwnedFd, timeout: Duration) -> bool {
+    let mut fds = [pollfd(fd.as_raw_fd())];
+    let timeout_ms = libc::c_int::try_from(timeout.as_millis()).unwrap_or(libc::c_int::MAX);
+    // SAFETY: `fds` is a valid array of one pollfd
+    unsafe { libc::poll(fds.as_mut_ptr(), 1, timeout_ms) > 0 }
+}
+
+/// Everything the monitor thread waits on between cycles
+pub struct EventLoop {
+    timer: Timer,
+    config: Option<ConfigWatch>,
+    uevents: Option<UeventSocket>,
+    lid: Option<LidSwitch>,
+}
+
+impl EventLoop {
+    /// Sets up the timer, a watch on `config_paths` and, when `event_driven`
+    /// is set, the uevent socket and the lid switch. Without inotify or a
+    /// netlink socket, the daemon only loses live reloading or degrades to
+    /// plain polling.
+    pub fn new(event_driven: bool, config_paths: Vec<PathBuf>) -> io::Result<Self> {
+        let config = match ConfigWatch::new(config_paths) {
+            Ok(watch) => Some(watch),
+            Err(e) => {
+                warn!("Cannot watch the configuration, reload it manually after changes: {e}");
+                None
+            }
+        };
+
+        let uevents = if event_driven {
+            match UeventSocket::new() {
+                Ok(socket) => {
+                    debug!("Listening for power supply and thermal uevents");
+                    Some(socket)
+                }
+                Err(e) => {
+                    warn!("Uevent socket unavailable, falling back to polling: {e}");
+                    None
+                }
+            }
+        } else {
+            None
+        };
+
+        // Without one, a lid change is seen on the next poll
+        let lid = event_driven
+            .then(lid::open_lid_switch)
+            .flatten()
+            .map(|device| {
+                debug!("Listening for lid switch events");
+                LidSwitch { device }
+            });
+
+        Ok(Self {
+            timer: Timer::new()?,
+            config,
+            uevents,
+            lid,
+        })
+    }
+
+    /// Schedules the next timer wakeup at `deadline`
+    pub fn arm_timer(&self, deadline: SystemTime) -> io::Result<()> {
+        self.timer.arm(deadline)
+    }
+
+    /// Blocks until the timer fires, a watched device or configuration file
+    /// changes or `waker` is woken. Returns `None` once `timeout` passes without any of these, which
+    /// lets the caller run periodic housekeeping such as watchdog pings.
+    pub fn wait(&mut self, waker: &Waker, timeout: Option<Duration>) -> io::Result<Option<Wakeup>> {
+        let mut fds = vec![
+            pollfd(self.timer.fd.as_raw_fd()),
+            pollfd(waker.fd.as_raw_fd()),
+        ];
+        let config_index = self.config.as_ref().map(|config| {
+            fds.push(pollfd(config.fd.as_raw_fd()));
+            fds.len() - 1
+        });
+        let uevents_index = self.uevents.as_ref().map(|uevents| {
+            fds.push(pollfd(uevents.fd.as_raw_fd()));
+            fds.len() - 1
+        });
+        let lid_index = self.lid.as_ref().map(|lid| {
+            fds.push(pollfd(lid.device.as_raw_fd()));
+            fds.len() - 1
+        });
+
+        let timeout_ms = timeout.map_or(-1, |t| {
+            libc::c_int::try_from(t.as_millis()).unwrap_or(libc::c_int::MAX)
+        });
+
+        // SAFETY: `fds` is a valid array of pollfd of the given length
+        let ret = unsafe { libc::poll(fds.as_mut_ptr(), fds.len() as libc::nfds_t, timeout_ms) };
+        if ret < 0 {
+            let err = io::Error::last_os_error();
+            // Signals interrupt the wait; the caller checks its shutdown flag
+            if err.kind() == io::ErrorKind::Interrupted {
+                return Ok(None);
+            }
+            return Err(err);
+        }
+
+        if fds[1].revents != 0 {
+            let _ = drain(waker.fd.as_raw_fd());
+            return Ok(Some(Wakeup::Requested));
+        }
+
+        if let (Some(config), Some(index)) = (&mut self.config, config_index) {
+            if fds[index].revents != 0 && config.changed() {
+                return Ok(Some(Wakeup::ConfigChanged));
+            }
+        }
+
+        if let (Some(uevents), Some(index)) = (&self.uevents, uevents_index) {
+            if fds[index].revents != 0 {
+                if let Some(event) = uevents.receive() {
+                    return Ok(Some(event));
+                }
+            }
+        }
+
+        if let (Some(lid), Some(index)) = (&self.lid, lid_index) {
+            if fds[index].revents != 0 {
+                if let Some(event) = lid.receive() {
+                    return Ok(Some(event));
+                }
+            }
+        }
+
+        if fds[0].revents != 0 {
+            if let Err(e) = drain(self.timer.fd.as_raw_fd()) {
+                if e.raw_os_error() == Some(libc::ECANCELED) {
+                    debug!("System clock was set, realigning the poll timer");
+                }
+            }
+            return Ok(Some(Wakeup::Timer));
+        }
+
+        Ok(None)
+    }
+}
+
+const fn pollfd(fd: RawFd) -> libc::pollfd {
+    libc::pollfd {
+        fd,
+        events: libc::POLLIN,
+        revents: 0,
+    }
+}
+
+/// Consumes the counter of a readable eventfd or timerfd
+fn drain(fd: RawFd) -> io::Result<()> {
+    let mut value: u64 = 0;
+    // SAFETY: reads at most 8 bytes into a valid u64
+    let ret = unsafe { libc::read(fd, (&raw mut value).cast(), mem::size_of::<u64>()) };
+    if ret < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// The first multiple of `interval` since the epoch at or after `not_before`,
+/// so polls line up with clean wall-clock boundaries
+///
+/// `not_before` is clamped to between now and half an interval from now, so a
+/// clock set backwards does not postpone the next poll indefinitely.
+pub fn next_boundary(interval: Duration, not_before: SystemTime) -> SystemTime {
+    let now = SystemTime::now();
+    let not_before = not_before.clamp(now, now + interval / 2);
+
+    let interval_ns = interval.as_nanos().max(1);
+    let since_epoch = not_before
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+    let boundary = since_epoch.div_ceil(interval_ns) * interval_ns;
+
+    let secs = u64::try_from(boundary / 1_000_000_000).unwrap_or(u64::MAX);
+    let nanos = (boundary % 1_000_000_000) as u32;
+    UNIX_EPOCH + Duration::new(secs, nanos)
+}
+
+/// Wraps a descriptor returned by a libc call, turning -1 into the OS error
+fn owned_fd(fd: RawFd) -> io::Result<OwnedFd> {
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // SAFETY: `fd` was just returned by the kernel and is owned by nobody else
+    Ok(unsafe { OwnedFd::from_raw_fd(fd) })
+}
@@ -0,0 +1,516 @@
+// Append-only record of what the daemon decided and when, for `watt history`
+use crate::config::DaemonConfig;
+use crate::core::SystemReport;
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// How often a sample of the system is recorded between events
+pub const SAMPLE_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// One line of the history file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Entry {
+    pub at: jiff::Timestamp,
+    #[serde(flatten)]
+    pub event: Event,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    /// Periodic snapshot of the system, recorded every [`SAMPLE_INTERVAL`]
+    Sample {
+        profile: String,
+        on_ac: bool,
+        battery_percent: Option<u8>,
+        /// Average current frequency over all cores
+        #[serde(default)]
+        avg_freq_mhz: Option<u32>,
+        load_1m: f32,
+        cpu_temp: Option<f32>,
+        turbo: Option<bool>,
+        /// Power drawn from the batteries, when on battery and they report it
+        #[serde(default)]
+        battery_draw_watts: Option<f32>,
+        /// CPU package power, from RAPL
+        #[serde(default)]
+        cpu_package_watts: Option<f32>,
+    },
+    /// The active profile changed. `from` is missing for the first profile
+    /// after startup.
+    ProfileSwitch {
+        from: Option<String>,
+        to: String,
+        /// "startup", "forced", "ac_power" or "battery_power"
+        trigger: String,
+    },
+    /// Auto turbo enabled or disabled turbo
+    Turbo { enabled: bool, reason: String },
+    /// Applying settings failed `count` times in a row, from `at` until
+    /// `until`
+    Errors {
+        count: u32,
+        until: jiff::Timestamp,
+        /// The first error of the burst
+        message: String,
+    },
+}
+
+/// Words for the `trigger` of a profile switch
+pub fn describe_trigger(trigger: &str) -> &str {
+    match trigger {
+        "startup" => "daemon started",
+        "forced" => "manual override",
+        "ac_power" => "AC connected",
+        "battery_power" => "on battery",
+        other => other,
+    }
+}
+
+/// Applies that failed in a row, not recorded until they stop
+struct ErrorBurst {
+    started: jiff::Timestamp,
+    count: u32,
+    message: String,
+}
+
+/// Where the history is written and how much of it is kept
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct HistoryFile {
+    path: PathBuf,
+    /// Size after which the file is rotated to `<file>.<time>`
+    max_size: u64,
+    /// Age after which rotated files are deleted, `None` to keep them all
+    retention: Option<Duration>,
+}
+
+/// The history file, written by the daemon. Write errors are logged and
+/// otherwise ignored, as the history is only informational.
+pub struct History {
+    /// `None` when disabled
+    file: Option<HistoryFile>,
+    last_sample: Option<Instant>,
+    errors: Option<ErrorBurst>,
+}
+
+impl History {
+    pub fn new(config: &DaemonConfig) -> Self {
+        Self {
+            file: configured_file(config),
+            last_sample: None,
+            errors: None,
+        }
+    }
+
+    /// Follows a reloaded configuration
+    pub fn reconfigure(&mut self, config: &DaemonConfig) {
+        let file = configured_file(config);
+        if file.as_ref().map(|file| &file.path) != self.file.as_ref().map(|file| &file.path) {
+            self.flush_errors();
+            self.last_sample = None;
+        }
+        self.file = file;
+    }
+
+    /// Records a sample of `report` if the last one is old enough
+    pub fn sample(&mut self, report: &SystemReport, profile: &str, on_ac: bool) {
+        if self
+            .last_sample
+            .is_some_and(|last| last.elapsed() < SAMPLE_INTERVAL)
+        {
+            return;
+        }
+        self.last_sample = Some(Instant::now());
+
+        let capacities: Vec<u32> = report
+            .batteries
+            .iter()
+            .filter_map(|battery| battery.capacity_percent)
+            .map(u32::from)
+            .collect();
+        let battery_percent = (!capacities.is_empty())
+            .then(|| capacities.iter().sum::<u32>() / capacities.len() as u32)
+            .and_then(|percent| u8::try_from(percent).ok());
+        // Drivers disagree on the sign of power_now, so only the size counts
+        let draws: Vec<f32> = report
+            .batteries
+            .iter()
+            .filter_map(|battery| battery.power_rate_watts)
+            .map(f32::abs)
+            .collect();
+        let battery_draw_watts = (!on_ac && !draws.is_empty()).then(|| draws.iter().sum());
+
+        self.record(Event::Sample {
+            profile: profile.to_string(),
+            on_ac,
+            battery_percent,
+            avg_freq_mhz: report.cpu_global.average_frequency_mhz,
+            load_1m: report.system_load.load_avg_1min,
+            cpu_temp: report.cpu_global.average_temperature_celsius,
+            turbo: report.cpu_global.turbo_status,
+            battery_draw_watts,
+            cpu_package_watts: report.cpu_power.package_watts,
+        });
+    }
+
+    /// Counts a failed apply towards the current error burst
+    pub fn error(&mut self, message: &str) {
+        match &mut self.errors {
+            Some(burst) => burst.count += 1,
+            None => {
+                self.errors = Some(ErrorBurst {
+                    started: jiff::Timestamp::now(),
+                    count: 1,
+                    message: message.to_string(),
+                });
+            }
+        }
+    }
+
+    /// Records the current error burst, if any, as applying works again
+    pub fn flush_errors(&mut self) {
+        let Some(burst) = self.errors.take() else {
+            return;
+        };
+        self.append(&Entry {
+            at: burst.started,
+            event: Event::Errors {
+                count: burst.count,
+                until: jiff::Timestamp::now(),
+                message: burst.message,
+            },
+        });
+    }
+
+    pub fn record(&self, event: Event) {
+        self.append(&Entry {
+            at: jiff::Timestamp::now(),
+            event,
+        });
+    }
+
+    fn append(&self, entry: &Entry) {
+        let Some(file) = &self.file else {
+            return;
+        };
+        if let Err(e) = append_line(file, entry) {
+            warn!("Failed to write history file {}: {e}", file.path.display());
+        }
+    }
+}
+
+impl Drop for History {
+    fn drop(&mut self) {
+        self.flush_errors();
+    }
+}
+
+fn configured_file(config: &DaemonConfig) -> Option<HistoryFile> {
+    config.history_enabled.then(|| HistoryFile {
+        path: PathBuf::from(&config.history_file),
+        max_size: config.history_max_size_mb.saturating_mul(1024 * 1024),
+        retention: (config.history_retention_days > 0).then(|| {
+            Duration::from_secs(config.history_retention_days.saturating_mul(24 * 60 * 60))
+        }),
+    })
+}
+
+fn append_line(file: &HistoryFile, entry: &Entry) -> io::Result<()> {
+    let path = &file.path;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    if fs::metadata(path)
+        .is_ok_and(|metadata| metadata.len() > 0 && metadata.len() >= file.max_size)
+    {
+        rotate(path)?;
+        if let Some(retention) = file.retention {
+            prune(path, retention);
+        }
+    }
+
+    let mut handle = OpenOptions::new()
+        .append(true)
+        .create(true)
+        .mode(0o644)
+        .open(path)?;
+    // The umask may have taken away the read bits
+    handle.set_permissions(fs::Permissions::from_mode(0o644))?;
+    let mut line = serde_json::to_vec(entry)?;
+    line.push(b'\n');
+    handle.write_all(&line)
+}
+
+/// Moves `<file>` to `<file>.<time>`, e.g. `history.jsonl.20250102T030405Z`.
+/// The rename is atomic, so readers see each entry in exactly one file.
+fn rotate(path: &Path) -> io::Result<()> {
+    let stamp = jiff::Timestamp::now()
+        .strftime("%Y%m%dT%H%M%SZ")
+        .to_string();
+    let mut rotated = suffixed_path(path, &stamp);
+    // Only a tiny size limit rotates twice within a second
+    let mut n = 1;
+    while rotated.exists() {
+        rotated = suffixed_path(path, &format!("{stamp}-{n}"));
+        n += 1;
+    }
+    match fs::rename(path, rotated) {
+        // Removed by someone else in the meantime
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        result => result,
+    }
+}
+
+/// Deletes the rotated files last written more than `retention` ago
+fn prune(path: &Path, retention: Duration) {
+    let rotated = match rotated_files(path) {
+        Ok(rotated) => rotated,
+        Err(e) => {
+            warn!("Failed to list rotated history files: {e}");
+            return;
+        }
+    };
+    for file in rotated {
+        let expired = fs::metadata(&file)
+            .and_then(|metadata| metadata.modified())
+            .is_ok_and(|modified| modified.elapsed().is_ok_and(|age| age > retention));
+        if !expired {
+            continue;
+        }
+        match fs::remove_file(&file) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => {
+                warn!("Failed to delete old history file {}: {e}", file.display());
+            }
+            _ => {}
+        }
+    }
+}
+
+fn suffixed_path(path: &Path, suffix: &str) -> PathBuf {
+    let mut rotated = path.as_os_str().to_owned();
+    rotated.push(format!(".{suffix}"));
+    PathBuf::from(rotated)
+}
+
+/// Whether `suffix` is the time of a rotation, as [`rotate`] writes it
+fn is_rotation_time(suffix: &str) -> bool {
+    let bytes = suffix.as_bytes();
+    bytes.len() >= 16
+        && bytes[..8].iter().all(u8::is_ascii_digit)
+        && bytes[8] == b'T'
+        && bytes[9..15].iter().all(u8::is_ascii_digit)
+        && bytes[15] == b'Z'
+}
+
+/// The rotated files of the history file at `path`, oldest first. The
+/// numbered `<file>.N` of older versions, where a higher `N` is older, come
+/// before the ones named after the time of their rotation.
+fn rotated_files(path: &Path) -> io::Result<Vec<PathBuf>> {
+    let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+        return Ok(Vec::new());
+    };
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+
+    let mut numbered = Vec::new();
+    let mut timed = Vec::new();
+    for entry in entries {
+        let file_name = entry?.file_name();
+        let Some(suffix) = file_name
+            .to_str()
+            .and_then(|file_name| file_name.strip_prefix(name))
+            .and_then(|rest| rest.strip_prefix('.'))
+        else {
+            continue;
+        };
+        if let Ok(n) = suffix.parse::<u32>() {
+            numbered.push((n, suffix.to_string()));
+        } else if is_rotation_time(suffix) {
+            timed.push(suffix.to_string());
+        }
+    }
+    numbered.sort_by_key(|(n, _)| std::cmp::Reverse(*n));
+    timed.sort();
+
+    Ok(numbered
+        .into_iter()
+        .map(|(_, suffix)| suffix)
+        .chain(timed)
+        .map(|suffix| suffixed_path(path, &suffix))
+        .collect())
+}
+
+/// Reads the entries at or after `since` from the history file at `path` and
+/// its rotated files, oldest first
+pub fn read(path: &Path, since: jiff::Timestamp) -> io::Result<Vec<Entry>> {
+    let mut entries = Vec::new();
+    for_each_entry(path, |entry| {
+        // Error bursts that end in the range count as in it
+        let end = match &entry.event {
+            Event::Errors { until, .. } => *until,
+            _ => entry.at,
+        };
+        if end >= since {
+            entries.push(entry);
+        }
+    })?;
+    Ok(entries)
+}
+
+/// Streams every entry of the history file at `path` and its rotated files to
+/// `f`, oldest first, without holding them all in memory. Lines that do not
+/// parse, e.g. one cut off by a crash, are skipped and their number returned.
+pub fn for_each_entry(path: &Path, mut f: impl FnMut(Entry)) -> io::Result<usize> {
+    let mut skipped = 0;
+    let mut found = false;
+    let files = rotated_files(path)?.into_iter().chain([path.to_path_buf()]);
+
+    for file in files {
+        let file = match File::open(&file) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e),
+        };
+        found = true;
+        for line in BufReader::new(file).lines() {
+            match serde_json::from_str::<Entry>(&line?) {
+                Ok(entry) => f(entry),
+                Err(_) => skipped += 1,
+            }
+        }
+    }
+
+    if !found {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("{} does not exist", path.display()),
+        ));
+    }
+    Ok(skipped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::SystemTime;
+
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new() -> Self {
+            static NEXT: AtomicUsize = AtomicUsize::new(0);
+            let dir = std::env::temp_dir().join(format!(
+                "watt-history-{}-{}",
+                std::process::id(),
+                NEXT.fetch_add(1, Ordering::Relaxed)
+            ));
+            fs::create_dir_all(&dir).unwrap();
+            Self(dir)
+        }
+
+        fn file(&self, max_size: u64, retention: Option<Duration>) -> HistoryFile {
+            HistoryFile {
+                path: self.0.join("history.jsonl"),
+                max_size,
+                retention,
+            }
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn turbo(reason: &str) -> Entry {
+        Entry {
+            at: jiff::Timestamp::UNIX_EPOCH,
+            event: Event::Turbo {
+                enabled: true,
+                reason: reason.to_string(),
+            },
+        }
+    }
+
+    fn write_legacy(file: &HistoryFile, n: u32, reason: &str) -> PathBuf {
+        let path = suffixed_path(&file.path, &n.to_string());
+        let mut line = serde_json::to_vec(&turbo(reason)).unwrap();
+        line.push(b'\n');
+        fs::write(&path, line).unwrap();
+        path
+    }
+
+    fn reasons(path: &Path) -> Vec<String> {
+        let mut reasons = Vec::new();
+        for_each_entry(path, |entry| {
+            if let Event::Turbo { reason, .. } = entry.event {
+                reasons.push(reason);
+            }
+        })
+        .unwrap();
+        reasons
+    }
+
+    #[test]
+    fn rotated_files_are_read_oldest_first() {
+        let dir = TempDir::new();
+        let file = dir.file(1, None);
+        write_legacy(&file, 2, "oldest");
+        write_legacy(&file, 1, "older");
+        fs::write(dir.0.join("history.jsonl.bak"), "not history\n").unwrap();
+
+        // Each line fills the file, so every append after the first rotates
+        for reason in ["first", "second", "third"] {
+            append_line(&file, &turbo(reason)).unwrap();
+        }
+
+        assert_eq!(
+            reasons(&file.path),
+            ["oldest", "older", "first", "second", "third"]
+        );
+        // The two rotations, next to the old files and the current one
+        assert_eq!(fs::read_dir(&dir.0).unwrap().count(), 6);
+    }
+
+    #[test]
+    fn rotation_deletes_expired_files() {
+        let dir = TempDir::new();
+        let file = dir.file(1, Some(Duration::from_secs(24 * 60 * 60)));
+        let expired = write_legacy(&file, 2, "expired");
+        File::options()
+            .write(true)
+            .open(&expired)
+            .unwrap()
+            .set_modified(SystemTime::now() - Duration::from_secs(2 * 24 * 60 * 60))
+            .unwrap();
+        write_legacy(&file, 1, "recent");
+
+        append_line(&file, &turbo("first")).unwrap();
+        append_line(&file, &turbo("second")).unwrap();
+
+        assert!(!expired.exists());
+        assert_eq!(reasons(&file.path), ["recent", "first", "second"]);
+    }
+
+    #[test]
+    fn rotation_times_are_recognized() {
+        assert!(is_rotation_time("20250102T030405Z"));
+        assert!(is_rotation_time("20250102T030405Z-1"));
+        assert!(!is_rotation_time("bak"));
+        assert!(!is_rotation_time("2025-01-02"));
+    }
+}
@@ -0,0 +1,866 @@
+// Unix domain socket IPC between the CLI and the daemon
+//
+// Every message is a JSON document prefixed with its length as a big-endian u32.
+// A connection carries a single request followed by a single response.
+use crate::auth::{self, Action, Authority, Caller};
+use crate::daemon::{DaemonState, DaemonStatus, Pong, ResetReport};
+use crate::overrides::{self, OverrideKnob, PowerScope};
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{self, Read, Write};
+use std::os::fd::AsRawFd;
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Path of the daemon's control socket
+pub const SOCKET_PATH: &str = "/run/watt/watt.sock";
+
+/// Upper bound for a request. Anyone may connect, so the daemon reads no
+/// more than any real request takes.
+const MAX_REQUEST_SIZE: u32 = 64 * 1024;
+
+/// Upper bound for a response, which only the daemon sends. A status with
+/// a report of many CPUs runs into hundreds of KiB.
+const MAX_RESPONSE_SIZE: u32 = 16 * 1024 * 1024;
+
+/// Connections served at once, each on a thread of its own. Further ones
+/// are turned away until one ends.
+const MAX_CONNECTIONS: usize = 8;
+
+/// How long either side waits on a stalled peer
+const IO_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long a client has to send its whole request. Anyone may connect, so
+/// one trickling in a byte at a time must not hold a connection slot.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long the CLI waits for a response, which may include the user
+/// answering an authentication prompt
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Requests understood by the daemon
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum Request {
+    Status,
+    Ping,
+    SetProfile {
+        name: String,
+        /// Seconds after which automatic selection resumes on its own
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        duration_sec: Option<u64>,
+        /// When automatic selection resumes on its own
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        until: Option<jiff::Timestamp>,
+        /// Resume automatic selection once the power source changes
+        #[serde(default)]
+        until_power_change: bool,
+    },
+    SetTurbo {
+        mode: String,
+        /// End the override once the power source changes
+        #[serde(default)]
+        until_power_change: bool,
+    },
+    Reload,
+    Pause {
+        /// Seconds after which management resumes on its own
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        duration_sec: Option<u64>,
+    },
+    Resume,
+    SetLogLevel {
+        level: String,
+    },
+    SetBatteryThresholds {
+        start: u8,
+        stop: u8,
+    },
+    SetOverride {
+        knob: OverrideKnob,
+        value: String,
+        /// Seconds after which the profile's setting applies again
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        duration_sec: Option<u64>,
+        /// When the profile's setting applies again, instead of a duration
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        until: Option<jiff::Timestamp>,
+        /// Only applied while on this power source, always when missing
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        scope: Option<PowerScope>,
+    },
+    ClearOverrides {
+        /// Every override when missing
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        knob: Option<OverrideKnob>,
+        /// Overrides for either power source or none when missing
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        scope: Option<PowerScope>,
+    },
+    Reset {
+        /// Restore the settings snapshot before applying the profile
+        #[serde(default)]
+        hard: bool,
+    },
+}
+
+impl Request {
+    /// The polkit action a request needs, or `None` for read-only requests
+    const fn action(&self) -> Option<Action> {
+        match self {
+            Self::Status | Self::Ping => None,
+            Self::SetProfile { .. }
+            | Self::SetOverride { .. }
+            | Self::ClearOverrides { .. }
+            | Self::Reset { .. }
+            | Self::Pause { .. }
+            | Self::Resume => Some(Action::SetProfile),
+            Self::SetTurbo { .. } => Some(Action::SetTurbo),
+            Self::SetBatteryThresholds { .. } => Some(Action::ManageThresholds),
+            Self::Reload | Self::SetLogLevel { .. } => Some(Action::Reload),
+        }
+    }
+}
+
+/// Category of an error returned by the daemon
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ErrorKind {
+    /// The request could not be decoded
+    InvalidRequest,
+    /// The request was well-formed but carried an unacceptable value
+    InvalidArgument,
+    /// The caller is not allowed to make the request
+    NotAuthorized,
+    /// The daemon failed to carry out the request
+    Internal,
+    /// The daemon serves too many connections already
+    Busy,
+}
+
+/// Response to a [`Request`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "result", rename_all = "kebab-case")]
+pub enum Response {
+    Ok {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        data: Option<serde_json::Value>,
+    },
+    Error {
+        kind: ErrorKind,
+        message: String,
+    },
+}
+
+impl Response {
+    const fn ok() -> Self {
+        Self::Ok { data: None }
+    }
+
+    fn error(kind: ErrorKind, message: impl Into<String>) -> Self {
+        Self::Error {
+            kind,
+            message: message.into(),
+        }
+    }
+}
+
+/// Writes a length-prefixed JSON message of at most `max_size` bytes
+pub fn write_message<T: Serialize>(
+    writer: &mut impl Write,
+    message: &T,
+    max_size: u32,
+) -> io::Result<()> {
+    let payload = serde_json::to_vec(message)?;
+    let len = u32::try_from(payload.len())
+        .ok()
+        .filter(|len| *len <= max_size)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Message too large"))?;
+
+    writer.write_all(&len.to_be_bytes())?;
+    writer.write_all(&payload)?;
+    writer.flush()
+}
+
+/// Reads a length-prefixed JSON message of at most `max_size` bytes
+///
+/// The payload buffer grows as data arrives, so a length prefix alone does
+/// not make the reader allocate.
+pub fn read_message<T: for<'de> Deserialize<'de>>(
+    reader: &mut impl Read,
+    max_size: u32,
+) -> io::Result<T> {
+    let mut len_bytes = [0u8; 4];
+    reader.read_exact(&mut len_bytes)?;
+
+    let len = u32::from_be_bytes(len_bytes);
+    if len > max_size {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Message of {len} bytes exceeds the maximum size of {max_size}"),
+        ));
+    }
+
+    let mut payload = Vec::new();
+    reader.take(u64::from(len)).read_to_end(&mut payload)?;
+    if payload.len() != len as usize {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            format!("Message ended after {} of {len} bytes", payload.len()),
+        ));
+    }
+    serde_json::from_slice(&payload).map_err(io::Error::from)
+}
+
+/// Executes a request against the daemon state, after checking that the caller
+/// is authorized to make it
+fn handle_request(
+    state: &DaemonState,
+    authority: &dyn Authority,
+    caller: &Caller,
+    request: Request,
+) -> Response {
+    debug!(
+        "Handling IPC request from PID {} (UID {}): {request:?}",
+        caller.pid, caller.uid
+    );
+
+    if let Some(action) = request.action() {
+        if let Err(e) = auth::authorize(authority, caller, action) {
+            return Response::error(ErrorKind::NotAuthorized, e);
+        }
+    }
+
+    match request {
+        Request::Status => match serde_json::to_value(state.status()) {
+            Ok(data) => Response::Ok { data: Some(data) },
+            Err(e) => Response::error(ErrorKind::Internal, e.to_string()),
+        },
+        Request::Ping => match serde_json::to_value(state.pong()) {
+            Ok(data) => Response::Ok { data: Some(data) },
+            Err(e) => Response::error(ErrorKind::Internal, e.to_string()),
+        },
+        Request::SetProfile {
+            name,
+            duration_sec,
+            until,
+            until_power_change,
+        } => {
+            let duration = duration_sec.map(|secs| {
+                jiff::SignedDuration::from_secs(i64::try_from(secs).unwrap_or(i64::MAX))
+            });
+            match state.set_profile_by_name(&name, duration, until, until_power_change) {
+                Ok(()) => Response::ok(),
+                Err(e) => Response::error(ErrorKind::InvalidArgument, e),
+            }
+        }
+        Request::SetTurbo {
+            mode,
+            until_power_change,
+        } => match state.set_turbo_by_name(&mode, until_power_change) {
+            Ok(()) => Response::ok(),
+            Err(e) => Response::error(ErrorKind::InvalidArgument, e),
+        },
+        Request::Reload => {
+            state.request_reload();
+            Response::ok()
+        }
+        Request::Pause { duration_sec } => {
+            let duration = duration_sec.map(|secs| {
+                jiff::SignedDuration::from_secs(i64::try_from(secs).unwrap_or(i64::MAX))
+            });
+            match state.pause(duration) {
+                Ok(()) => Response::ok(),
+                Err(e) => Response::error(ErrorKind::InvalidArgument, e),
+            }
+        }
+        Request::Resume => {
+            state.resume();
+            Response::ok()
+        }
+        Request::SetLogLevel { level } => match state.set_log_level_by_name(&level) {
+            Ok(()) => Response::ok(),
+            Err(e) => Response::error(ErrorKind::InvalidArgument, e),
+        },
+        Request::SetBatteryThresholds { start, stop } => {
+            match state.set_battery_thresholds(start, stop) {
+                Ok(()) => Response::ok(),
+                Err(e) => Response::error(ErrorKind::InvalidArgument, e),
+            }
+        }
+        Request::SetOverride {
+            knob,
+            value,
+            duration_sec,
+            until,
+            scope,
+        } => {
+            let until = match (duration_sec, until) {
+                (None, Some(until)) => Ok(until),
+                (Some(secs), None) => overrides::expiry_after(jiff::SignedDuration::from_secs(
+                    i64::try_from(secs).unwrap_or(i64::MAX),
+                )),
+                _ => Err("An override needs either a duration or an expiry".to_string()),
+            };
+            match until.and_then(|until| state.set_timed_override(knob, &value, until, scope)) {
+                Ok(()) => Response::ok(),
+                Err(e) => Response::error(ErrorKind::InvalidArgument, e),
+            }
+        }
+        Request::ClearOverrides { knob, scope } => Response::Ok {
+            data: Some(state.clear_timed_overrides(knob, scope).into()),
+        },
+        Request::Reset { hard } => match state.reset(hard) {
+            Ok(report) => match serde_json::to_value(report) {
+                Ok(data) => Response::Ok { data: Some(data) },
+                Err(e) => Response::error(ErrorKind::Internal, e.to_string()),
+            },
+            Err(e) => Response::error(ErrorKind::Internal, e),
+        },
+    }
+}
+
+/// Credentials of the process on the other end of `stream`
+fn peer_credentials(stream: &UnixStream) -> io::Result<Caller> {
+    // SAFETY: ucred is plain data, all-zeroes is a valid value
+    let mut cred: libc::ucred = unsafe { std::mem::zeroed() };
+    let mut len = std::mem::size_of::<libc::ucred>() as libc::socklen_t;
+
+    // SAFETY: `cred` and `len` describe a buffer of the size SO_PEERCRED fills
+    let ret = unsafe {
+        libc::getsockopt(
+            stream.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_PEERCRED,
+            (&raw mut cred).cast(),
+            &raw mut len,
+        )
+    };
+    if ret < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(Caller {
+        pid: cred.pid as u32,
+        uid: cred.uid,
+    })
+}
+
+/// Serves a single connection: one request, one response
+fn handle_connection(
+    state: &DaemonState,
+    authority: &dyn Authority,
+    mut stream: UnixStream,
+) -> io::Result<()> {
+    stream.set_write_timeout(Some(IO_TIMEOUT))?;
+    let caller = peer_credentials(&stream)?;
+
+    let mut reader = DeadlineReader::new(&stream, Instant::now() + REQUEST_TIMEOUT);
+    let response = match read_message::<Request>(&mut reader, MAX_REQUEST_SIZE) {
+        Ok(request) => handle_request(state, authority, &caller, request),
+        Err(e) if e.kind() == io::ErrorKind::InvalidData => {
+            Response::error(ErrorKind::InvalidRequest, e.to_string())
+        }
+        Err(e) => return Err(e),
+    };
+
+    write_message(&mut stream, &response, MAX_RESPONSE_SIZE)
+}
+
+/// Reads from a socket until a deadline, rather than waiting up to a timeout
+/// on each read
+struct DeadlineReader<'a> {
+    stream: &'a UnixStream,
+    deadline: Instant,
+}
+
+impl<'a> DeadlineReader<'a> {
+    const fn new(stream: &'a UnixStream, deadline: Instant) -> Self {
+        Self { stream, deadline }
+    }
+}
+
+impl Read for DeadlineReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let left = self.deadline.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            return Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "Request not received in time",
+            ));
+        }
+        self.stream.set_read_timeout(Some(left))?;
+        let mut stream = self.stream;
+        stream.read(buf)
+    }
+}
+
+/// A connection being served, counted against [`MAX_CONNECTIONS`] until
+/// dropped
+struct ConnectionSlot(Arc<AtomicUsize>);
+
+impl ConnectionSlot {
+    /// Takes a slot, or `None` if all are taken
+    fn acquire(active: &Arc<AtomicUsize>) -> Option<Self> {
+        active
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |count| {
+                (count < MAX_CONNECTIONS).then_some(count + 1)
+            })
+            .ok()?;
+        Some(Self(active.clone()))
+    }
+}
+
+impl Drop for ConnectionSlot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+/// Turns a connection away without waiting on the peer
+fn reject_busy(mut stream: UnixStream) {
+    let response = Response::error(
+        ErrorKind::Busy,
+        "The daemon serves too many connections, try again",
+    );
+    if let Err(e) = stream
+        .set_nonblocking(true)
+        .and_then(|()| write_message(&mut stream, &response, MAX_RESPONSE_SIZE))
+    {
+        debug!("Failed to turn away IPC connection: {e}");
+    }
+}
+
+/// Control socket server. Connections are handled on dedicated threads so
+/// they never block the daemon's control loop, and a caller waiting on an
+/// authentication prompt does not hold up anyone else. At most
+/// [`MAX_CONNECTIONS`] are served at once, so the socket being open to every
+/// user does not let one tie up the daemon.
+pub struct Server;
+
+impl Server {
+    /// Binds [`SOCKET_PATH`] and starts serving requests in the background,
+    /// checking requests that change anything with `authority`
+    pub fn start(state: Arc<DaemonState>, authority: Arc<dyn Authority>) -> io::Result<Self> {
+        let path = Path::new(SOCKET_PATH);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        // A leftover socket from an unclean shutdown would make bind fail
+        if path.exists() {
+            fs::remove_file(path)?;
+        }
+
+        // Anyone may connect and read the status, everything else is
+        // authorized per request
+        let listener = UnixListener::bind(path)?;
+        fs::set_permissions(path, fs::Permissions::from_mode(0o666))?;
+
+        let active = Arc::new(AtomicUsize::new(0));
+        thread::Builder::new()
+            .name("ipc".to_string())
+            .spawn(move || {
+                for stream in listener.incoming() {
+                    let stream = match stream {
+                        Ok(stream) => stream,
+                        Err(e) => {
+                            warn!("Failed to accept IPC connection: {e}");
+                            continue;
+                        }
+                    };
+
+                    let Some(slot) = ConnectionSlot::acquire(&active) else {
+                        debug!("Turning away IPC connection, {MAX_CONNECTIONS} are being served");
+                        reject_busy(stream);
+                        continue;
+                    };
+                    let state = state.clone();
+                    let authority = authority.clone();
+                    let spawned =
+                        thread::Builder::new()
+                            .name("ipc-conn".to_string())
+                            .spawn(move || {
+                                let _slot = slot;
+                                if let Err(e) =
+                                    handle_connection(&state, authority.as_ref(), stream)
+                                {
+                                    debug!("IPC connection failed: {e}");
+                                }
+                            });
+                    if let Err(e) = spawned {
+                        warn!("Failed to start IPC connection thread: {e}");
+                    }
+                }
+            })?;
+
+        debug!("Control socket listening at {SOCKET_PATH}");
+        Ok(Self)
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        if let Err(e) = fs::remove_file(SOCKET_PATH) {
+            debug!("Failed to remove control socket: {e}");
+        }
+    }
+}
+
+/// Whether a daemon control socket is present
+pub fn socket_available() -> bool {
+    Path::new(SOCKET_PATH).exists()
+}
+
+/// Sends a request to the running daemon and waits for its response
+pub fn send_request(request: &Request) -> io::Result<Response> {
+    let mut stream = UnixStream::connect(SOCKET_PATH)?;
+    // Only requests that need authorization may wait on a prompt
+    let timeout = if request.action().is_some() {
+        RESPONSE_TIMEOUT
+    } else {
+        IO_TIMEOUT
+    };
+    stream.set_read_timeout(Some(timeout))?;
+    stream.set_write_timeout(Some(IO_TIMEOUT))?;
+
+    write_message(&mut NoSigPipe(&stream), request, MAX_REQUEST_SIZE)?;
+    read_message(&mut stream, MAX_RESPONSE_SIZE)
+}
+
+/// Writes to a socket without raising SIGPIPE when the daemon hung up, which
+/// would end the command without a word, as it no longer ignores SIGPIPE
+struct NoSigPipe<'a>(&'a UnixStream);
+
+impl Write for NoSigPipe<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // SAFETY: `buf` is valid for its length, and the descriptor for the
+        // lifetime of the stream
+        let sent = unsafe {
+            libc::send(
+                self.0.as_raw_fd(),
+                buf.as_ptr().cast(),
+                buf.len(),
+                libc::MSG_NOSIGNAL,
+            )
+        };
+        usize::try_from(sent).map_err(|_| io::Error::last_os_error())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Sends a request that carries no response data, turning an error response
+/// into an error
+pub fn send_command(request: &Request) -> io::Result<()> {
+    match send_request(request)? {
+        Response::Ok { .. } => Ok(()),
+        Response::Error { kind, message } => Err(response_error(kind, message)),
+    }
+}
+
+/// Ends the daemon's overrides of `knob` on the power source of `scope`, with
+/// a missing one matching any, returning how many ended
+pub fn clear_overrides(knob: Option<OverrideKnob>, scope: Option<PowerScope>) -> io::Result<usize> {
+    fetch(&Request::ClearOverrides { knob, scope })
+}
+
+/// Has the running daemon return to the configured state and apply the
+/// selected profile in full, waiting until it did
+pub fn reset(hard: bool) -> io::Result<ResetReport> {
+    fetch(&Request::Reset { hard })
+}
+
+/// Queries the running daemon's status
+pub fn fetch_status() -> io::Result<DaemonStatus> {
+    fetch(&Request::Status)
+}
+
+/// Pings the running daemon
+pub fn ping() -> io::Result<Pong> {
+    fetch(&Request::Ping)
+}
+
+/// Sends a request and decodes the data of its response
+fn fetch<T: for<'de> Deserialize<'de>>(request: &Request) -> io::Result<T> {
+    match send_request(request)? {
+        Response::Ok { data: Some(data) } => serde_json::from_value(data).map_err(io::Error::from),
+        Response::Ok { data: None } => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Daemon returned no data",
+        )),
+        Response::Error { kind, message } => Err(response_error(kind, message)),
+    }
+}
+
+fn response_error(kind: ErrorKind, message: String) -> io::Error {
+    match kind {
+        ErrorKind::NotAuthorized => io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!(
+                "{message}. Run the command as root, or have an administrator allow it through \
+                 polkit, e.g. by adding you to the wheel or watt group"
+            ),
+        ),
+        ErrorKind::InvalidArgument => io::Error::new(io::ErrorKind::InvalidInput, message),
+        _ => io::Error::other(format!("Daemon returned an error ({kind:?}): {message}")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    /// A message as it goes over the socket, with a length prefix of `len`
+    fn framed(len: u32, payload: &[u8]) -> Cursor<Vec<u8>> {
+        let mut bytes = len.to_be_bytes().to_vec();
+        bytes.extend_from_slice(payload);
+        Cursor::new(bytes)
+    }
+
+    struct AllowAll;
+
+    impl Authority for AllowAll {
+        fn is_authorized(&self, _caller: &Caller, _action: Action) -> Result<bool, String> {
+            Ok(true)
+        }
+    }
+
+    /// `message` after a trip through the framing, compared as JSON since
+    /// the messages have no equality of their own
+    fn round_trip<T: Serialize + for<'de> Deserialize<'de>>(message: &T) -> serde_json::Value {
+        let mut bytes = Vec::new();
+        write_message(&mut bytes, message, MAX_RESPONSE_SIZE).unwrap();
+        let decoded: T = read_message(&mut Cursor::new(bytes), MAX_RESPONSE_SIZE).unwrap();
+        serde_json::to_value(decoded).unwrap()
+    }
+
+    #[test]
+    fn requests_round_trip() {
+        let requests = [
+            Request::Status,
+            Request::Ping,
+            Request::SetProfile {
+                name: "battery".to_string(),
+                duration_sec: Some(600),
+                until: None,
+                until_power_change: false,
+            },
+            Request::SetProfile {
+                name: "charger".to_string(),
+                duration_sec: None,
+                until: Some("2026-01-02T03:04:05Z".parse().unwrap()),
+                until_power_change: true,
+            },
+            Request::SetTurbo {
+                mode: "never".to_string(),
+                until_power_change: true,
+            },
+            Request::Reload,
+            Request::Pause {
+                duration_sec: Some(60),
+            },
+            Request::Resume,
+            Request::SetLogLevel {
+                level: "debug".to_string(),
+            },
+            Request::SetBatteryThresholds {
+                start: 40,
+                stop: 80,
+            },
+            Request::SetOverride {
+                knob: OverrideKnob::MaxFreq,
+                value: "2400".to_string(),
+                duration_sec: Some(3600),
+                until: None,
+                scope: Some(PowerScope::Battery),
+            },
+            Request::ClearOverrides {
+                knob: None,
+                scope: Some(PowerScope::Ac),
+            },
+            Request::Reset { hard: true },
+        ];
+        for request in &requests {
+            assert_eq!(round_trip(request), serde_json::to_value(request).unwrap());
+        }
+    }
+
+    #[test]
+    fn responses_round_trip() {
+        let responses = [
+            Response::ok(),
+            Response::Ok {
+                data: Some(serde_json::json!({ "active_profile": "charger" })),
+            },
+            Response::error(ErrorKind::NotAuthorized, "Not authorized"),
+            Response::error(ErrorKind::Busy, "Too many connections"),
+        ];
+        for response in &responses {
+            assert_eq!(
+                round_trip(response),
+                serde_json::to_value(response).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn wire_format() {
+        let request = Request::SetProfile {
+            name: "battery".to_string(),
+            duration_sec: None,
+            until: None,
+            until_power_change: false,
+        };
+        assert_eq!(
+            serde_json::to_string(&request).unwrap(),
+            r#"{"type":"set-profile","name":"battery","until_power_change":false}"#
+        );
+        assert_eq!(
+            serde_json::to_string(&Response::error(ErrorKind::InvalidArgument, "no")).unwrap(),
+            r#"{"result":"error","kind":"invalid-argument","message":"no"}"#
+        );
+        assert_eq!(
+            serde_json::to_string(&Response::ok()).unwrap(),
+            r#"{"result":"ok"}"#
+        );
+        // Fields left out by older clients take their defaults
+        assert!(matches!(
+            serde_json::from_str(r#"{"type":"set-turbo","mode":"auto"}"#).unwrap(),
+            Request::SetTurbo {
+                until_power_change: false,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn trickled_requests_run_out_of_time() {
+        let (mut client, server) = UnixStream::pair().unwrap();
+        // One byte at a time, each well within a per-read timeout
+        let trickle = thread::spawn(move || {
+            for byte in 1_000u32.to_be_bytes().iter().chain(&[b'{'; 1000]) {
+                if client.write_all(&[*byte]).is_err() {
+                    break;
+                }
+                thread::sleep(Duration::from_millis(50));
+            }
+        });
+
+        let start = Instant::now();
+        let mut reader = DeadlineReader::new(&server, start + Duration::from_millis(300));
+        let error = read_message::<Request>(&mut reader, MAX_REQUEST_SIZE).unwrap_err();
+        assert!(matches!(
+            error.kind(),
+            io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock
+        ));
+        assert!(start.elapsed() < Duration::from_secs(1));
+        drop(server);
+        trickle.join().unwrap();
+    }
+
+    #[test]
+    fn connections_carry_a_request_and_its_response() {
+        let state = DaemonState::default();
+        let exchange = |payload: &[u8]| {
+            let (mut client, server) = UnixStream::pair().unwrap();
+            let len = u32::try_from(payload.len()).unwrap();
+            client.write_all(&len.to_be_bytes()).unwrap();
+            client.write_all(payload).unwrap();
+            handle_connection(&state, &AllowAll, server).unwrap();
+            read_message::<Response>(&mut client, MAX_RESPONSE_SIZE).unwrap()
+        };
+
+        let response = exchange(br#"{"type":"set-profile","name":"battery"}"#);
+        assert!(matches!(response, Response::Ok { data: None }));
+        assert!(state.force_mode().is_some());
+
+        let response = exchange(br#"{"type":"set-profile","name":"no-such-profile"}"#);
+        assert!(matches!(
+            response,
+            Response::Error {
+                kind: ErrorKind::InvalidArgument,
+                ..
+            }
+        ));
+
+        let response = exchange(br#"{"type":"self-destruct"}"#);
+        assert!(matches!(
+            response,
+            Response::Error {
+                kind: ErrorKind::InvalidRequest,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn oversized_requests_are_refused_before_reading() {
+        let mut message = framed(MAX_REQUEST_SIZE + 1, b"{}");
+        let error = read_message::<Request>(&mut message, MAX_REQUEST_SIZE).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        // Nothing past the length prefix was read
+        assert_eq!(message.position(), 4);
+    }
+
+    #[test]
+    fn truncated_messages_are_an_error() {
+        let payload = br#"{"type":"status"}"#;
+        let len = u32::try_from(payload.len()).unwrap();
+        let mut message = framed(len + 100, payload);
+        let error = read_message::<Request>(&mut message, MAX_REQUEST_SIZE).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+
+        let mut message = framed(len, payload);
+        assert!(matches!(
+            read_message::<Request>(&mut message, MAX_REQUEST_SIZE),
+            Ok(Request::Status)
+        ));
+    }
+
+    #[test]
+    fn only_requests_that_change_something_need_authorization() {
+        assert_eq!(Request::Status.action(), None);
+        assert_eq!(Request::Ping.action(), None);
+        assert_eq!(
+            Request::SetTurbo {
+                mode: "off".to_string(),
+                until_power_change: false,
+            }
+            .action(),
+            Some(Action::SetTurbo)
+        );
+        assert_eq!(
+            Request::SetBatteryThresholds {
+                start: 40,
+                stop: 80,
+            }
+            .action(),
+            Some(Action::ManageThresholds)
+        );
+        assert_eq!(Request::Reload.action(), Some(Action::Reload));
+        assert_eq!(Request::Resume.action(), Some(Action::SetProfile));
+    }
+
+    #[test]
+    fn connection_slots_are_bounded() {
+        let active = Arc::new(AtomicUsize::new(0));
+        let slots: Vec<ConnectionSlot> = (0..MAX_CONNECTIONS)
+            .map(|_| ConnectionSlot::acquire(&active).unwrap())
+            .collect();
+        assert!(ConnectionSlot::acquire(&active).is_none());
+
+        drop(slots);
+        assert_eq!(active.load(Ordering::Acquire), 0);
+        assert!(ConnectionSlot::acquire(&active).is_some());
+    }
+}
@@ -0,0 +1,117 @@
+//! Lid switch and connected displays, to tell when a laptop runs closed on a
+//! dock (clamshell mode)
+//!
+//! The lid is read from the ACPI button driver where it has one, or else from
+//! the input device reporting the lid switch, which the event loop also
+//! watches to run a cycle as soon as the lid moves.
+
+use crate::core::LidInfo;
+use std::fs::{self, File, OpenOptions};
+use std::os::fd::AsRawFd;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+
+const ACPI_LID_PATH: &str = "/proc/acpi/button/lid";
+const INPUT_PATH: &str = "/sys/class/input";
+const DRM_PATH: &str = "/sys/class/drm";
+
+/// Switch events, from `linux/input-event-codes.h`
+pub const EV_SW: u16 = 0x05;
+pub const SW_LID: u16 = 0x00;
+
+/// Connector types of built-in panels, which are not external displays
+const INTERNAL_CONNECTORS: &[&str] = &["eDP", "LVDS", "DSI"];
+
+/// The lid state and the connected external displays
+pub fn read() -> LidInfo {
+    LidInfo {
+        lid_closed: acpi_lid_closed().or_else(evdev_lid_closed),
+        external_displays: external_displays(),
+    }
+}
+
+/// From `/proc/acpi/button/lid/*/state`, which reads `state:      closed`
+fn acpi_lid_closed() -> Option<bool> {
+    let entry = fs::read_dir(ACPI_LID_PATH).ok()?.flatten().next()?;
+    let state = fs::read_to_string(entry.path().join("state")).ok()?;
+    match state.split_whitespace().last()? {
+        "closed" => Some(true),
+        "open" => Some(false),
+        _ => None,
+    }
+}
+
+/// From the switch state of the lid's input device
+fn evdev_lid_closed() -> Option<bool> {
+    let device = open_lid_switch()?;
+    let mut switches = [0u8; 8];
+    // SAFETY: EVIOCGSW writes at most the size encoded in the request, which
+    // is that of `switches`
+    let ret = unsafe {
+        libc::ioctl(
+            device.as_raw_fd(),
+            libc::_IOR::<[u8; 8]>(u32::from(b'E'), 0x1b),
+            switches.as_mut_ptr(),
+        )
+    };
+    (ret >= 0).then_some(switches[0] & (1 << SW_LID) != 0)
+}
+
+/// Opens the input device that reports the lid switch, non-blocking, if
+/// there is one
+pub fn open_lid_switch() -> Option<File> {
+    let path = lid_switch_device()?;
+    OpenOptions::new()
+        .read(true)
+        .custom_flags(libc::O_NONBLOCK | libc::O_CLOEXEC)
+        .open(path)
+        .ok()
+}
+
+/// The `/dev/input/event*` node whose switch capabilities include the lid
+fn lid_switch_device() -> Option<PathBuf> {
+    fs::read_dir(INPUT_PATH)
+        .ok()?
+        .flatten()
+        .filter(|entry| entry.file_name().to_string_lossy().starts_with("event"))
+        .find(|entry| has_lid_switch(&entry.path()))
+        .map(|entry| Path::new("/dev/input").join(entry.file_name()))
+}
+
+/// Whether the lowest bit of the switch capability mask is set. The mask is
+/// printed as hex words, most significant first.
+fn has_lid_switch(event_path: &Path) -> bool {
+    fs::read_to_string(event_path.join("device/capabilities/sw"))
+        .ok()
+        .and_then(|mask| {
+            let lowest = mask.split_whitespace().last()?;
+            u64::from_str_radix(lowest, 16).ok()
+        })
+        .is_some_and(|mask| mask & (1 << SW_LID) != 0)
+}
+
+/// Connectors like `card1-HDMI-A-1` with a display connected, leaving out
+/// built-in panels
+fn external_displays() -> Vec<String> {
+    let Ok(entries) = fs::read_dir(DRM_PATH) else {
+        return Vec::new();
+    };
+    let mut displays: Vec<String> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            // Connectors are named card<N>-<type>-<index>
+            let (_, connector) = name.split_once('-')?;
+            if INTERNAL_CONNECTORS
+                .iter()
+                .any(|internal| connector.starts_with(internal))
+            {
+                return None;
+            }
+            let status = fs::read_to_string(entry.path().join("status")).ok()?;
+            (status.trim() == "connected").then_some(name)
+        })
+        .collect();
+    displays.sort();
+    displays
+}
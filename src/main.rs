@@ -1,37 +1,173 @@
+mod auth;
 mod battery;
+mod capabilities;
 mod cli;
 mod config;
+mod control;
 mod core;
 mod cpu;
 mod daemon;
+mod daemonize;
+mod dbus;
+mod dump;
 mod engine;
+mod events;
+mod history;
+mod ipc;
+mod lid;
+mod metrics;
 mod monitor;
+mod notifications;
+mod overrides;
+mod pidfile;
+mod privileges;
+mod process_boost;
+mod rapl;
+mod resume;
+mod sensors;
+mod signals;
+mod smoothing;
+mod snapshot;
+mod statefile;
+mod systemd;
+mod topology;
+mod turbo_strategy;
 mod util;
 
-use crate::config::AppConfig;
-use crate::core::{GovernorOverrideMode, TurboSetting};
-use crate::util::error::{AppError, ControlError};
-use clap::{Parser, value_parser};
-use env_logger::Builder;
-use log::{debug, error, info};
+use crate::config::{AppConfig, LogFormat};
+use crate::core::{ForcedProfile, GovernorOverrideMode, TurboSetting};
+use crate::util::error::AppError;
+use crate::util::logging::LogFile;
+use clap::{ArgAction, CommandFactory, Parser, value_parser};
+use env_logger::{Builder, Target, WriteStyle};
+use log::{LevelFilter, debug, error, info};
 use std::error::Error;
-use std::sync::Once;
+use std::path::Path;
 
 #[derive(Parser, Debug)]
 #[clap(author, version, about, long_about = None)]
 struct Cli {
     #[clap(subcommand)]
     command: Option<Commands>,
+
+    /// Print the output of `info`, `status`, `capabilities`, `cpu`, `battery`,
+    /// `history` and `doctor` as JSON
+    #[clap(long, global = true)]
+    json: bool,
+
+    /// Print what `--json` does as `key=value` lines, or tab-separated columns
+    /// for lists, without padding, units in values or color
+    #[clap(long, global = true, conflicts_with = "json")]
+    plain: bool,
+
+    /// Show the sysfs writes `daemon`, `apply` and `set` would make without
+    /// changing anything
+    #[clap(long, global = true)]
+    dry_run: bool,
+
+    /// How to print an error: log lines, or a JSON object on stderr for
+    /// scripts. The exit code tells the kind of error either way.
+    #[clap(long, global = true, value_enum, default_value_t)]
+    error_format: cli::ErrorFormat,
+
+    /// Log more: once for debug messages, twice for trace. Overrides RUST_LOG
+    /// and daemon.log_level.
+    #[clap(short, long, global = true, action = ArgAction::Count)]
+    verbose: u8,
+
+    /// Only log errors. Overrides RUST_LOG and daemon.log_level.
+    #[clap(short, long, global = true, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// When to color output and log lines. NO_COLOR turns off the default
+    /// `auto`, as does output that is not a terminal.
+    #[clap(long, global = true, value_enum, default_value_t)]
+    color: cli::style::ColorChoice,
 }
 
 #[derive(Parser, Debug)]
 enum Commands {
     /// Display current system information
-    Info,
+    Info {
+        /// Explain why the running daemon picked its profile and turbo state
+        #[clap(long)]
+        explain: bool,
+    },
+    /// Summarize the system state, the active profile and conflicting services
+    Status {
+        /// Query the running daemon over D-Bus instead of reading sysfs directly
+        #[clap(long)]
+        via_dbus: bool,
+    },
     /// Run as a daemon in the background
+    #[clap(after_help = "Signals:\n  \
+        SIGINT, SIGTERM  Stop the daemon\n  \
+        SIGUSR1          Log a status snapshot at the start of the next cycle\n  \
+        SIGUSR2          Reopen daemon.log_file, e.g. after logrotate moved it")]
     Daemon {
+        /// Stop an already running daemon and take its place
+        #[clap(long)]
+        replace: bool,
+        /// Run for the current session only, restoring the original settings on exit
+        #[clap(long)]
+        live: bool,
+        /// Fork into the background once started, for init systems that expect it
+        #[clap(long)]
+        daemonize: bool,
+        /// Stay in the foreground, which is the default
+        #[clap(long, conflicts_with = "daemonize")]
+        foreground: bool,
+        /// Apply this profile instead of selecting one automatically, until
+        /// changed with `watt profile`
+        #[clap(long)]
+        force_profile: Option<ForcedProfile>,
+    },
+    /// Apply the profile for the current system state once and exit
+    #[clap(
+        after_help = "Exits with 0 when every setting was applied, 7 when only some were, and 1 when none could be. A running daemon applies its own profile again on its next cycle, unless paused with --pause-daemon."
+    )]
+    Apply {
+        /// Apply this profile regardless of power source: charger, balanced or battery
+        #[clap(long, visible_alias = "profile")]
+        force_profile: Option<ForcedProfile>,
+        /// Pause a running daemon for this long first, e.g. 10m, so it does
+        /// not apply its own profile again meanwhile
+        #[clap(long)]
+        pause_daemon: Option<jiff::SignedDuration>,
+    },
+    /// Show what the engine would decide under made-up conditions: the
+    /// profile, each setting's value and the auto turbo decision
+    #[clap(
+        after_help = "Reads the configuration only, nothing from or to sysfs. Conditions left out count as unreadable, as they would on a system that does not report them.\n\nExample: watt simulate --load 85 --temp 78 --on-battery --battery-pct 45"
+    )]
+    Simulate {
+        /// Average CPU usage, in percent
+        #[clap(long)]
+        load: Option<f32>,
+        /// Average CPU temperature, in °C
+        #[clap(long)]
+        temp: Option<f32>,
+        /// Run on battery rather than AC power
+        #[clap(long)]
+        on_battery: bool,
+        /// Battery charge, in percent
+        #[clap(long, value_parser = value_parser!(u8).range(0..=100))]
+        battery_pct: Option<u8>,
+        /// Whether auto turbo was on before, for its hysteresis: on or off.
+        /// By default the configured initial_turbo_state.
+        #[clap(long, value_parser = clap::builder::BoolishValueParser::new())]
+        prev_turbo: Option<bool>,
+        /// Simulate this profile regardless of power source: charger, balanced or battery
         #[clap(long)]
-        verbose: bool,
+        force_profile: Option<ForcedProfile>,
+    },
+    /// Write a single setting once, e.g. `watt set governor performance`
+    #[clap(
+        after_help = "Needs root. A running daemon may write its profile's value back on its next cycle, depending on daemon.drift_policy."
+    )]
+    Set {
+        #[clap(subcommand)]
+        knob: cli::set::Knob,
     },
     /// Set CPU governor
     SetGovernor {
@@ -50,8 +186,253 @@ enum Commands {
         #[clap(value_enum)]
         setting: TurboSetting,
     },
+    /// Write a configuration for this machine, asking what to tune it for
+    #[clap(
+        after_help = "Every question has a flag, and without a terminal on stdin nothing is asked, so it can be scripted, e.g. `watt init --preset laptop-battery --charge-thresholds 40-80 --force`. An existing file is only replaced after confirming, or with --force."
+    )]
+    Init {
+        /// Answer what to tune for: laptop-battery, laptop-balanced,
+        /// laptop-performance or desktop
+        #[clap(long, value_enum)]
+        preset: Option<cli::init::Preset>,
+        /// Stop charging early to extend the battery's lifespan: START-STOP,
+        /// e.g. 40-80, or off
+        #[clap(long, value_parser = cli::init::parse_charge_thresholds)]
+        charge_thresholds: Option<cli::init::ChargeThresholds>,
+        /// Whether to manage the platform profile: on or off
+        #[clap(long, value_parser = clap::builder::BoolishValueParser::new())]
+        platform_profile: Option<bool>,
+        /// Where to write it, by default $WATT_CONFIG or
+        /// /etc/xdg/watt/config.toml
+        #[clap(long, short)]
+        out: Option<std::path::PathBuf>,
+        /// Replace an existing file without asking
+        #[clap(long)]
+        force: bool,
+        /// Print the configuration instead of writing it
+        #[clap(long, conflicts_with_all = ["out", "force"])]
+        print: bool,
+    },
     /// Display comprehensive debug information
     Debug,
+    /// Collect sysfs state, the effective config and recent logs into an
+    /// archive to attach to bug reports
+    #[clap(
+        after_help = "Serial numbers and other identifying attributes are left out. Run it as root to include files only root can read."
+    )]
+    DebugDump {
+        /// Where to write the bundle, by default watt-debug-<time>.tar.gz in
+        /// the current directory
+        #[clap(long, short)]
+        out: Option<std::path::PathBuf>,
+        /// Write a directory tree instead of a tar.gz archive
+        #[clap(long)]
+        dir: bool,
+    },
+    /// Check the setup for common problems, with a hint for each one found
+    #[clap(
+        after_help = "Exits with 0 when every check passes, 1 when the worst result is a warning and 2 when a check fails."
+    )]
+    Doctor,
+    /// List what this machine supports: governors, EPP, turbo, platform
+    /// profiles, charge thresholds and more
+    Capabilities,
+    /// Print a JSON line for a waybar or i3status-rust custom module
+    #[clap(
+        after_help = "Template placeholders: {profile}, {governor}, {avg_freq}, {temp}, {battery}, {power} and {icon}. Values that are not available are left out."
+    )]
+    Statusline {
+        #[clap(long, value_enum, default_value_t = cli::statusline::StatuslineFormat::Waybar)]
+        format: cli::statusline::StatuslineFormat,
+        /// What the module shows
+        #[clap(long, default_value = cli::statusline::DEFAULT_TEMPLATE)]
+        template: String,
+        /// Keep running and print a new line whenever the state changes
+        #[clap(long)]
+        watch: bool,
+    },
+    /// Show health, energy, power draw, charge thresholds and more for every
+    /// battery, and the AC sources
+    Battery,
+    /// Show frequencies, governor, EPP, EPB, temperature, usage and boost for
+    /// every logical CPU
+    #[clap(
+        after_help = "CPUs with the same settings as the one before them are collapsed into one line, unless --all is passed. Current frequency, temperature and usage are not compared."
+    )]
+    Cpu {
+        /// Show every CPU on its own line
+        #[clap(long)]
+        all: bool,
+        /// Redraw the table in place every this many seconds
+        #[clap(long, value_name = "SECS", value_parser = value_parser!(u64).range(1..))]
+        watch: Option<u64>,
+    },
+    /// Show recent profile switches, turbo changes and error bursts recorded
+    /// by the daemon
+    #[clap(
+        after_help = "Reads daemon.history_file and its rotated files. --json includes the periodic samples as well."
+    )]
+    History {
+        /// How far back to go, e.g. 2h or 30m
+        #[clap(long, default_value = "24h")]
+        since: jiff::SignedDuration,
+    },
+    /// Aggregate what the daemon recorded: time per profile, turbo, temperature,
+    /// frequency, energy and events
+    #[clap(
+        after_help = "E.g. `watt stats summary --since 7d`. Reads daemon.history_file and its rotated files, where the daemon records a sample every five minutes. Time between entries more than ten minutes apart, when the daemon was not running, is not counted. Battery energy is estimated from the power draw the batteries report."
+    )]
+    Stats {
+        #[clap(value_enum)]
+        action: cli::stats::StatsAction,
+        /// How far back to go, e.g. 7d or 12h
+        #[clap(long, default_value = "24h", value_parser = cli::stats::parse_since)]
+        since: jiff::SignedDuration,
+    },
+    /// Export the samples the daemon recorded as CSV or JSON lines
+    #[clap(
+        after_help = "Reads daemon.history_file and its rotated files, where the daemon records a sample every five minutes."
+    )]
+    Export {
+        #[clap(long, value_enum, default_value_t = cli::export::ExportFormat::Csv)]
+        format: cli::export::ExportFormat,
+        /// Earliest sample to export, e.g. 2024-05-01 or 2024-05-01T12:00
+        #[clap(long)]
+        from: Option<String>,
+        /// Latest sample to export. A bare date includes that whole day.
+        #[clap(long)]
+        to: Option<String>,
+        /// Comma-separated fields to export, all by default
+        #[clap(long, value_delimiter = ',', value_parser = clap::builder::PossibleValuesParser::new(cli::export::FIELDS))]
+        fields: Vec<String>,
+        /// File to write to instead of stdout
+        #[clap(long, short)]
+        out: Option<std::path::PathBuf>,
+    },
+    /// Show a live dashboard of frequencies, usage, batteries and decisions
+    #[clap(
+        after_help = "With the daemon reachable, p cycles the forced profile, t the turbo override and space pauses or resumes management. q, Escape or Ctrl+C quit."
+    )]
+    Watch {
+        /// Seconds between refreshes
+        #[clap(long, default_value_t = 1, value_parser = value_parser!(u64).range(1..))]
+        interval_sec: u64,
+    },
+    /// Print frequency, load, temperature, turbo, battery charge and power
+    /// draw every interval, without the daemon
+    #[clap(
+        after_help = "E.g. `watt monitor --interval 1 --count 30 --output run.csv`. Without --count it runs until Ctrl+C. Either way it ends with the lowest, average and highest value of each column. Load is the average CPU usage over all cores."
+    )]
+    Monitor {
+        /// Seconds between samples
+        #[clap(long, default_value_t = 1, value_parser = value_parser!(u64).range(1..))]
+        interval: u64,
+        /// Stop after this many samples
+        #[clap(long, value_parser = value_parser!(u64).range(1..))]
+        count: Option<u64>,
+        /// Also write the samples to this CSV file
+        #[clap(long, short)]
+        output: Option<std::path::PathBuf>,
+    },
+    /// Print a shell completion script
+    #[clap(
+        after_help = "Governors, EPP values and platform profiles are completed from what the system running this supports."
+    )]
+    Completions {
+        #[clap(value_enum, required_unless_present = "out_dir")]
+        shell: Option<cli::completions::Shell>,
+        /// Write the scripts for every shell, or the one named, to this directory
+        #[clap(long)]
+        out_dir: Option<std::path::PathBuf>,
+    },
+    /// Check that the running daemon is alive and cycling
+    #[clap(
+        after_help = "Exits with 0 when the daemon answered and finished a cycle recently, and 1 otherwise."
+    )]
+    Ping {
+        /// Seconds since the last cycle after which the daemon counts as stuck
+        #[clap(long, default_value_t = 60)]
+        max_staleness: u64,
+    },
+    /// Force a profile in the running daemon, or return to automatic selection
+    #[clap(
+        after_help = "Without --for, --until or --until-power-change the profile stays until changed again. Durations look like 45m, 1h 30m or 90s, times like 2026-01-01T18:00:00Z or 2026-01-01T18:00:00+01:00."
+    )]
+    Profile {
+        /// One of: charger, battery, performance, balanced, powersave, auto, or
+        /// the name of a `[profiles.<name>]` section
+        name: String,
+        /// Return to automatic selection after this long
+        #[clap(long = "for", conflicts_with_all = ["until", "until_power_change"])]
+        duration: Option<jiff::SignedDuration>,
+        /// Return to automatic selection at this time, which setting the clock
+        /// back does not postpone
+        #[clap(long, conflicts_with = "until_power_change")]
+        until: Option<jiff::Timestamp>,
+        /// Return to automatic selection once the system switches between AC
+        /// and battery power
+        #[clap(long)]
+        until_power_change: bool,
+    },
+    /// Switch turbo on or off right away, or hand it back to the profile
+    #[clap(
+        after_help = "With a daemon running, on and off stay until `watt turbo auto`, a daemon restart or, with --until-power-change, the next switch between AC and battery power."
+    )]
+    Turbo {
+        #[clap(value_enum)]
+        action: cli::turbo::TurboAction,
+        /// Hand turbo back to the profile once the system switches between AC
+        /// and battery power
+        #[clap(long)]
+        until_power_change: bool,
+    },
+    /// Override the governor, EPP, turbo or maximum frequency for a while
+    #[clap(
+        after_help = "E.g. `watt override governor performance --for 30m`, `watt override governor performance --for 8h --on ac`, `watt override clear governor` or `watt override list`. Overrides apply on top of whichever profile is selected, outlive configuration reloads and daemon restarts, and are gone after a reboot. One limited to a power source with --on beats one that is not while on that power source."
+    )]
+    Override {
+        #[clap(value_enum)]
+        action: cli::overrides::OverrideAction,
+        /// The setting to use, or for clear, which override to end
+        value: Option<String>,
+        /// How long the override lasts, e.g. 30m or 1h 30m
+        #[clap(long = "for", conflicts_with = "until")]
+        duration: Option<jiff::SignedDuration>,
+        /// When the override ends, e.g. 2026-01-01T18:00:00Z, which setting
+        /// the clock back does not postpone
+        #[clap(long)]
+        until: Option<jiff::Timestamp>,
+        /// Only apply the override while on this power source, or for clear,
+        /// only end the overrides limited to it
+        #[clap(long = "on", value_enum)]
+        scope: Option<overrides::PowerScope>,
+    },
+    /// Clear every override and apply the selected profile in full again
+    #[clap(
+        after_help = "Clears the forced profile, turbo and timed overrides, the force-governor override and a pause, and forgets the turbo hysteresis and knobs left alone after failing or drifting. Goes through the daemon when one runs, otherwise needs root."
+    )]
+    Reset {
+        /// Restore the settings snapshot taken when the daemon started first,
+        /// if there is one, undoing changes to settings the profile leaves alone
+        #[clap(long)]
+        hard: bool,
+    },
+    /// Stop the running daemon from changing settings, e.g. for benchmarking
+    #[clap(
+        after_help = "The daemon keeps monitoring and writing stats while paused. Durations look like 30m, 1h 30m or 90s."
+    )]
+    Pause {
+        /// Resume on its own after this long, instead of waiting for `watt resume`
+        #[clap(long = "for")]
+        duration: Option<jiff::SignedDuration>,
+    },
+    /// Let the running daemon manage settings again after `watt pause`
+    Resume,
+    /// Change the running daemon's log level until it exits
+    LogLevel {
+        /// One of: error, warning, info, debug
+        level: String,
+    },
     /// Set Energy Performance Preference (EPP)
     SetEpp {
         epp: String,
@@ -89,16 +470,143 @@ enum Commands {
     },
 }
 
-fn main() -> Result<(), AppError> {
-    // Initialize logger once for the entire application
-    init_logger();
+impl Commands {
+    /// What the command does that needs root, for the message when it is
+    /// run without. `set` checks on its own, naming the setting.
+    const fn privileged_operation(&self) -> Option<&'static str> {
+        match self {
+            Self::Daemon { .. } => Some("Running the daemon"),
+            Self::Apply { .. } => Some("Applying the profile"),
+            Self::ForceGovernor { .. } => Some("Forcing the governor"),
+            Self::SetGovernor { .. } => Some("Setting the governor"),
+            Self::SetTurbo { .. } => Some("Setting turbo"),
+            Self::SetEpp { .. } => Some("Setting the EPP"),
+            Self::SetEpb { .. } => Some("Setting the EPB"),
+            Self::SetMinFreq { .. } => Some("Setting the minimum frequency"),
+            Self::SetMaxFreq { .. } => Some("Setting the maximum frequency"),
+            Self::SetPlatformProfile { .. } => Some("Setting the platform profile"),
+            _ => None,
+        }
+    }
+
+    /// Whether the command writes to sysfs, and so can be run with `--dry-run`
+    const fn writes_sysfs(&self) -> bool {
+        matches!(
+            self,
+            Self::Daemon { .. } | Self::Apply { .. } | Self::Set { .. }
+        ) || self.is_legacy_setter()
+    }
+
+    /// Whether the command is one of the single setters that predate `set`,
+    /// which print nothing of their own
+    const fn is_legacy_setter(&self) -> bool {
+        matches!(
+            self,
+            Self::SetGovernor { .. }
+                | Self::SetTurbo { .. }
+                | Self::SetEpp { .. }
+                | Self::SetEpb { .. }
+                | Self::SetMinFreq { .. }
+                | Self::SetMaxFreq { .. }
+                | Self::SetPlatformProfile { .. }
+        )
+    }
+}
 
+fn main() {
     let cli = Cli::parse();
+    let is_daemon = matches!(cli.command, Some(Commands::Daemon { .. }));
+
+    // A pipeline like `watt info --plain | head -1` closes stdout early, and
+    // a one-shot command then ends quietly on SIGPIPE as other tools do,
+    // instead of panicking in println!. The daemon keeps ignoring it, so a
+    // client hanging up cannot take it down.
+    if !is_daemon {
+        restore_sigpipe();
+    }
+
+    // Settled before anything else, so it applies to loading the
+    // configuration as well
+    let cli_level = match (cli.quiet, cli.verbose) {
+        (true, _) => Some(LevelFilter::Error),
+        (false, 0) => None,
+        (false, 1) => Some(LevelFilter::Debug),
+        (false, _) => Some(LevelFilter::Trace),
+    };
+    let env_log = std::env::var("RUST_LOG").ok().filter(|env| !env.is_empty());
+    // The daemon only logs, and log files must not end up with escapes
+    cli::style::init(if is_daemon || cli.plain {
+        cli::style::ColorChoice::Never
+    } else {
+        cli.color
+    });
+    let write_style = match cli.color {
+        cli::style::ColorChoice::Auto => WriteStyle::Auto,
+        cli::style::ColorChoice::Always => WriteStyle::Always,
+        cli::style::ColorChoice::Never => WriteStyle::Never,
+    };
+    // One-shot commands only log what went wrong unless asked for more; the
+    // daemon switches to daemon.log_level once it starts
+    let default_level = if is_daemon {
+        LevelFilter::Info
+    } else {
+        LevelFilter::Warn
+    };
+
+    // Log to stderr until the configuration says otherwise
+    init_logger(
+        LogFormat::default(),
+        None,
+        cli_level,
+        env_log.as_deref(),
+        default_level,
+        write_style,
+    );
 
     // Load configuration first, as it might be needed by the monitor module
     // E.g., for ignored power supplies
-    let config = match config::load_config() {
+    let config_result = config::load_config();
+
+    // Only the daemon logs to a file, other commands keep printing to the terminal
+    let log_file = match (is_daemon, &config_result) {
+        (true, Ok(config)) => config.daemon.log_file.as_ref().map(|path| {
+            LogFile::new(
+                Path::new(path),
+                config
+                    .daemon
+                    .log_file_max_size_mb
+                    .saturating_mul(1024 * 1024),
+                config.daemon.log_file_keep,
+            )
+        }),
+        _ => None,
+    };
+
+    // Switch to the configured format and, for the daemon, log file
+    init_logger(
+        config_result
+            .as_ref()
+            .map_or(LogFormat::default(), |config| config.daemon.log_format),
+        log_file,
+        cli_level,
+        env_log.as_deref(),
+        default_level,
+        write_style,
+    );
+    // A level from the command line or RUST_LOG beats daemon.log_level
+    let log_level = (cli_level.is_some() || env_log.is_some()).then(log::max_level);
+
+    let config = match config_result {
         Ok(cfg) => cfg,
+        // Both would act on settings nobody asked for
+        Err(e)
+            if matches!(
+                cli.command,
+                Some(Commands::Daemon { .. } | Commands::Apply { .. })
+            ) =>
+        {
+            std::process::exit(report_error(&AppError::Config(e), cli.error_format));
+        }
         Err(e) => {
             error!("Error loading configuration: {e}. Using default values.");
             // Proceed with default config if loading fails
@@ -106,9 +614,63 @@ fn main() -> Result<(), AppError> {
         }
     };
 
+    let controller = control::select(cli.dry_run);
+    let print_writes = cli.dry_run && cli.command.as_ref().is_some_and(Commands::is_legacy_setter);
+    if print_writes {
+        util::sysfs::record_writes();
+    }
+
+    // Checked before anything is touched, so a command run without root fails
+    // with a message naming what needs it rather than on the first write.
+    // Read-only commands never need it, and a dry run writes nothing.
+    let privileges_check = match cli
+        .command
+        .as_ref()
+        .and_then(Commands::privileged_operation)
+    {
+        Some(operation) if !cli.dry_run => privileges::require_sysfs_access(operation),
+        _ => Ok(()),
+    };
+
+    // --plain is --json in another format, see cli::plain
+    cli::set_plain(cli.plain);
+    let json = cli.json || cli.plain;
+
     let command_result: Result<(), AppError> = match cli.command {
+        _ if json
+            && !matches!(
+                cli.command,
+                Some(
+                    Commands::Info { .. }
+                        | Commands::Status { .. }
+                        | Commands::Capabilities
+                        | Commands::Cpu { .. }
+                        | Commands::Battery
+                        | Commands::History { .. }
+                        | Commands::Doctor
+                        | Commands::Override { .. }
+                        | Commands::Simulate { .. }
+                        | Commands::Stats { .. }
+                )
+            ) =>
+        {
+            Err(AppError::Usage(
+                "--json and --plain are only supported by info, status, capabilities, cpu, battery, history, stats, doctor, simulate and override list"
+                    .to_string(),
+            ))
+        }
+        _ if cli.dry_run && !cli.command.as_ref().is_some_and(Commands::writes_sysfs) => {
+            Err(AppError::Usage(
+                "--dry-run is only supported by daemon, apply and the set commands".to_string(),
+            ))
+        }
+        _ if privileges_check.is_err() => privileges_check,
+        Some(Commands::Info { explain: true }) => cli::explain::run_explain(json),
+        Some(Commands::Info { .. }) if json => monitor::collect_system_report(&config)
+            .map_err(AppError::Monitor)
+            .and_then(|report| cli::print_json(&report)),
         // TODO: This will be moved to a different module in the future.
-        Some(Commands::Info) => match monitor::collect_system_report(&config) {
+        Some(Commands::Info { .. }) => match monitor::collect_system_report(&config) {
             Ok(report) => {
                 // Format section headers with proper centering
                 let format_section = |title: &str| {
@@ -156,6 +718,14 @@ fn main() -> Result<(), AppError> {
                         None => "Unknown",
                     }
                 );
+                if let Some(turbo_override) = ipc::socket_available()
+                    .then(ipc::fetch_status)
+                    .and_then(Result::ok)
+                    .as_ref()
+                    .and_then(cli::turbo::describe_override)
+                {
+                    println!("Turbo Override:      {turbo_override}");
+                }
 
                 println!(
                     "EPP:                 {}",
@@ -180,6 +750,66 @@ fn main() -> Result<(), AppError> {
                         |t| format!("{t:.1}°C")
                     )
                 );
+                let throttling = &report.throttling;
+                println!(
+                    "Throttling:          {}",
+                    match (throttling.events(), throttling.total_events) {
+                        (Some(events), _) if events > 0 => cli::style::error(format!(
+                            "{events} events since the last report, on CPUs {}",
+                            capabilities::compact_ranges(&throttling.throttled_cpus)
+                        )),
+                        (_, Some(total)) if total > 0 => {
+                            cli::style::warn(format!("{total} events since boot"))
+                        }
+                        (_, Some(_)) => cli::style::ok("none since boot"),
+                        (_, None) => "N/A (no throttle counters)".to_string(),
+                    }
+                );
+                println!(
+                    "Temperature Sensor:  {}",
+                    report
+                        .cpu_global
+                        .temperature_sensor
+                        .as_deref()
+                        .unwrap_or("average of the cores")
+                );
+
+                println!(
+                    "CPU Frequency:       {}",
+                    match (
+                        report.cpu_global.average_frequency_mhz,
+                        report.cpu_global.highest_frequency_mhz,
+                    ) {
+                        (Some(average), Some(highest)) => {
+                            format!("{average} MHz average, {highest} MHz highest")
+                        }
+                        _ => "N/A".to_string(),
+                    }
+                );
+
+                let power = &report.cpu_power;
+                println!(
+                    "CPU Power:           {}",
+                    power.package_watts.map_or_else(
+                        || "N/A (no RAPL counters, or not root)".to_string(),
+                        |package| {
+                            let domains: Vec<String> = [
+                                ("cores", power.cores_watts),
+                                ("uncore", power.uncore_watts),
+                            ]
+                            .into_iter()
+                            .filter_map(|(domain, watts)| {
+                                Some(format!("{domain} {:.1} W", watts?))
+                            })
+                            .collect();
+                            if domains.is_empty() {
+                                format!("{package:.1} W package")
+                            } else {
+                                format!("{package:.1} W package ({})", domains.join(", "))
+                            }
+                        }
+                    )
+                );
 
                 format_section("CPU Core Info");
 
@@ -259,6 +889,48 @@ fn main() -> Result<(), AppError> {
                     );
                 }
 
+                if !report.cpu_policies.is_empty() {
+                    format_section("CPU Policies");
+                    let mhz = |freq: Option<u32>| {
+                        freq.map_or_else(|| "N/A".to_string(), |f| format!("{f} MHz"))
+                    };
+                    for policy in &report.cpu_policies {
+                        println!(
+                            "Policy {:<3} CPUs {}: {} at {}, limits {}–{} of {}–{}",
+                            policy.id,
+                            capabilities::compact_ranges(&policy.cpus),
+                            policy.governor.as_deref().unwrap_or("N/A"),
+                            mhz(policy.current_frequency_mhz),
+                            mhz(policy.min_frequency_mhz),
+                            mhz(policy.max_frequency_mhz),
+                            mhz(policy.hardware_min_frequency_mhz),
+                            mhz(policy.hardware_max_frequency_mhz),
+                        );
+                    }
+                }
+
+                format_section("Power Source");
+                println!(
+                    "AC Power:           {}",
+                    if report.ac_power.online { "Connected" } else { "Disconnected" }
+                );
+                println!("AC Detection:       {}", report.ac_power.source);
+                println!("Reading:            {}", report.ac_power.reading);
+                println!(
+                    "Lid:                {}",
+                    match report.lid.lid_closed {
+                        Some(true) => "Closed",
+                        Some(false) => "Open",
+                        None => "N/A",
+                    }
+                );
+                if !report.lid.external_displays.is_empty() {
+                    println!(
+                        "External Displays:  {}",
+                        report.lid.external_displays.join(", ")
+                    );
+                }
+
                 // Only display battery info for systems that have real batteries
                 // Skip this section entirely on desktop systems
                 if !report.batteries.is_empty() {
@@ -337,32 +1009,41 @@ fn main() -> Result<(), AppError> {
                     "Load Average (15m): {:.2}",
                     report.system_load.load_avg_15min
                 );
+                if let Some(pressure) = report.system_load.cpu_pressure {
+                    println!(
+                        "CPU Pressure:       {:.1}% (10s), {:.1}% (60s)",
+                        pressure.some_avg10, pressure.some_avg60
+                    );
+                }
                 Ok(())
             }
             Err(e) => Err(AppError::Monitor(e)),
         },
+        Some(Commands::Set { knob }) => cli::set::run_set(&config, &knob, controller),
         Some(Commands::SetGovernor { governor, core_id }) => {
-            cpu::set_governor(&governor, core_id).map_err(AppError::Control)
+            controller.set_governor(&governor, core_id).map_err(AppError::Control)
         }
         Some(Commands::ForceGovernor { mode }) => {
             cpu::force_governor(mode).map_err(AppError::Control)
         }
-        Some(Commands::SetTurbo { setting }) => cpu::set_turbo(setting).map_err(AppError::Control),
+        Some(Commands::SetTurbo { setting }) => {
+            controller.set_turbo(setting).map_err(AppError::Control)
+        }
         Some(Commands::SetEpp { epp, core_id }) => {
-            cpu::set_epp(&epp, core_id).map_err(AppError::Control)
+            controller.set_epp(&epp, core_id).map_err(AppError::Control)
         }
         Some(Commands::SetEpb { epb, core_id }) => {
-            cpu::set_epb(&epb, core_id).map_err(AppError::Control)
+            controller.set_epb(&epb, core_id).map_err(AppError::Control)
         }
         Some(Commands::SetMinFreq { freq_mhz, core_id }) => {
             // Basic validation for reasonable CPU frequency values
-            validate_freq(freq_mhz, "Minimum")?;
-            cpu::set_min_frequency(freq_mhz, core_id).map_err(AppError::Control)
+            validate_freq(freq_mhz, "Minimum")
+                .and_then(|()| controller.set_min_frequency(freq_mhz, core_id).map_err(AppError::Control))
         }
         Some(Commands::SetMaxFreq { freq_mhz, core_id }) => {
             // Basic validation for reasonable CPU frequency values
-            validate_freq(freq_mhz, "Maximum")?;
-            cpu::set_max_frequency(freq_mhz, core_id).map_err(AppError::Control)
+            validate_freq(freq_mhz, "Maximum")
+                .and_then(|()| controller.set_max_frequency(freq_mhz, core_id).map_err(AppError::Control))
         }
         Some(Commands::SetPlatformProfile { profile }) => {
             // Get available platform profiles and validate early if possible
@@ -370,14 +1051,14 @@ fn main() -> Result<(), AppError> {
                 Ok(available_profiles) => {
                     if available_profiles.contains(&profile) {
                         info!("Setting platform profile to '{profile}'");
-                        cpu::set_platform_profile(&profile).map_err(AppError::Control)
+                        controller.set_platform_profile(&profile).map_err(AppError::Control)
                     } else {
                         error!(
                             "Invalid platform profile: '{}'. Available profiles: {}",
                             profile,
                             available_profiles.join(", ")
                         );
-                        Err(AppError::Generic(format!(
+                        Err(AppError::Usage(format!(
                             "Invalid platform profile: '{}'. Available profiles: {}",
                             profile,
                             available_profiles.join(", ")
@@ -386,7 +1067,7 @@ fn main() -> Result<(), AppError> {
                 }
                 Err(_e) => {
                     // If we can't get profiles (e.g., feature not supported), pass through to the function
-                    cpu::set_platform_profile(&profile).map_err(AppError::Control)
+                    controller.set_platform_profile(&profile).map_err(AppError::Control)
                 }
             }
         }
@@ -399,78 +1080,344 @@ fn main() -> Result<(), AppError> {
                 error!(
                     "Start threshold ({start_threshold}) must be less than stop threshold ({stop_threshold})"
                 );
-                Err(AppError::Generic(format!(
+                Err(AppError::Usage(format!(
                     "Start threshold ({start_threshold}) must be less than stop threshold ({stop_threshold})"
                 )))
             } else {
                 info!(
                     "Setting battery thresholds: start at {start_threshold}%, stop at {stop_threshold}%"
                 );
-                battery::set_battery_charge_thresholds(start_threshold, stop_threshold)
-                    .map_err(AppError::Control)
+                // SAFETY: geteuid has no preconditions
+                if unsafe { libc::geteuid() } != 0 && ipc::socket_available() {
+                    // Without root, only the daemon can write the thresholds
+                    ipc::send_command(&ipc::Request::SetBatteryThresholds {
+                        start: start_threshold,
+                        stop: stop_threshold,
+                    })
+                    .map_err(|e| AppError::daemon("Failed to set thresholds", &e))
+                } else {
+                    battery::set_battery_charge_thresholds(start_threshold, stop_threshold)
+                        .map_err(AppError::Control)
+                }
             }
         }
-        Some(Commands::Daemon { verbose }) => daemon::run_daemon(config, verbose),
+        Some(Commands::Daemon {
+            replace,
+            live,
+            daemonize,
+            foreground: _,
+            force_profile,
+        }) => daemon::run_daemon(
+            config,
+            &daemon::DaemonOptions {
+                log_level,
+                replace,
+                live,
+                daemonize,
+                dry_run: cli.dry_run,
+                force_profile,
+            },
+        ),
+        Some(Commands::Apply {
+            force_profile,
+            pause_daemon,
+        }) => cli::apply::run_apply(&config, force_profile, pause_daemon, controller)
+            .and_then(|outcome| match outcome {
+                cli::apply::ApplyOutcome::Success => Ok(()),
+                cli::apply::ApplyOutcome::Partial => Err(AppError::PartialApply(
+                    "Some settings could not be applied".to_string(),
+                )),
+            }),
+        Some(Commands::Simulate {
+            load,
+            temp,
+            on_battery,
+            battery_pct,
+            prev_turbo,
+            force_profile,
+        }) => cli::simulate::run_simulate(
+            &config,
+            cli::simulate::Conditions {
+                load,
+                temp,
+                on_battery,
+                battery_pct,
+                prev_turbo,
+                force_mode: force_profile,
+            },
+            json,
+        ),
+        Some(Commands::Init {
+            preset,
+            charge_thresholds,
+            platform_profile,
+            out,
+            force,
+            print,
+        }) => cli::init::run_init(cli::init::InitOptions {
+            preset,
+            charge_thresholds,
+            platform_profile,
+            out,
+            force,
+            print,
+        }),
         Some(Commands::Debug) => cli::debug::run_debug(&config),
+        Some(Commands::DebugDump { out, dir }) => {
+            cli::bundle::run_debug_dump(&config, out.as_deref(), dir)
+        }
+        Some(Commands::Capabilities) => cli::capabilities::run_capabilities(json),
+        Some(Commands::Statusline {
+            format,
+            template,
+            watch,
+        }) => cli::statusline::run_statusline(&config, format, &template, watch),
+        Some(Commands::Doctor) => cli::doctor::run_doctor(&config, json)
+            .map(|worst| std::process::exit(worst.exit_code())),
+        Some(Commands::Battery) => cli::battery::run_battery(&config, json),
+        Some(Commands::Cpu { all, watch }) => {
+            cli::cpu::run_cpu(&config, all, json, watch.map(std::time::Duration::from_secs))
+        }
+        Some(Commands::Stats { action, since }) => {
+            cli::stats::run_stats(&config, action, since, json)
+        }
+        Some(Commands::Export {
+            format,
+            from,
+            to,
+            fields,
+            out,
+        }) => cli::export::run_export(
+            &config,
+            format,
+            from.as_deref(),
+            to.as_deref(),
+            &fields,
+            out.as_deref(),
+        ),
+        Some(Commands::History { since }) => cli::history::run_history(&config, since, json),
+        Some(Commands::Watch { interval_sec }) => {
+            cli::watch::run_watch(&config, std::time::Duration::from_secs(interval_sec))
+        }
+        Some(Commands::Monitor {
+            interval,
+            count,
+            output,
+        }) => cli::monitor::run_monitor(
+            &config,
+            std::time::Duration::from_secs(interval),
+            count,
+            output.as_deref(),
+        ),
+        Some(Commands::Completions { shell, out_dir }) => {
+            cli::completions::run_completions(Cli::command(), shell, out_dir.as_deref())
+        }
+        Some(Commands::Status { via_dbus }) => cli::status::run_status(&config, via_dbus, json),
+        Some(Commands::Ping { max_staleness }) => cli::ping::run_ping(max_staleness),
+        Some(Commands::Profile {
+            name,
+            duration,
+            until,
+            until_power_change,
+        }) => set_daemon_profile(&name, duration, until, until_power_change),
+        Some(Commands::Turbo {
+            action,
+            until_power_change,
+        }) => cli::turbo::run_turbo(&config, action, until_power_change),
+        Some(Commands::Override {
+            action,
+            value,
+            duration,
+            until,
+            scope,
+        }) => cli::overrides::run_override(&config, action, value, duration, until, scope, json),
+        Some(Commands::Reset { hard }) => {
+            cli::reset::run_reset(&config, hard).and_then(|outcome| match outcome {
+                cli::apply::ApplyOutcome::Success => Ok(()),
+                cli::apply::ApplyOutcome::Partial => Err(AppError::PartialApply(
+                    "Some settings could not be applied".to_string(),
+                )),
+            })
+        }
+        Some(Commands::Pause { duration }) => pause_daemon(duration),
+        Some(Commands::Resume) => ipc::send_command(&ipc::Request::Resume)
+            .map(|()| println!("Daemon settings management resumed"))
+            .map_err(|e| AppError::daemon("Failed to resume daemon", &e)),
+        Some(Commands::LogLevel { level }) => ipc::send_command(&ipc::Request::SetLogLevel {
+            level: level.clone(),
+        })
+        .map(|()| println!("Daemon log level set to {level}"))
+        .map_err(|e| AppError::daemon("Failed to set daemon log level", &e)),
         None => {
-            info!("Welcome to Watt! Use --help for commands.");
+            println!("Welcome to Watt! Use --help for commands.");
             debug!("Current effective configuration: {config:?}");
             Ok(())
         }
     };
 
-    if let Err(e) = command_result {
-        error!("Error executing command: {e}");
-        if let Some(source) = e.source() {
-            error!("Caused by: {source}");
+    if print_writes {
+        for line in cli::write_table(&util::sysfs::take_recorded_writes()) {
+            println!("{line}");
         }
+    }
 
-        // Check for permission denied errors
-        if let AppError::Control(control_error) = &e {
-            if matches!(control_error, ControlError::PermissionDenied(_)) {
-                error!(
-                    "Hint: This operation may require administrator privileges (e.g., run with sudo)."
-                );
-            }
-        }
+    if let Err(e) = command_result {
+        std::process::exit(report_error(&e, cli.error_format));
+    }
+}
 
-        std::process::exit(1);
+/// Reports `error` on stderr in `format` and returns the exit code for it.
+/// Every command's error goes through here, so the codes stay consistent.
+/// Restores the default action of SIGPIPE, which the Rust runtime ignores
+fn restore_sigpipe() {
+    // SAFETY: no other thread is running yet, and SIG_DFL is a valid handler
+    unsafe {
+        libc::signal(libc::SIGPIPE, libc::SIG_DFL);
     }
+}
 
-    Ok(())
+fn report_error(error: &AppError, format: cli::ErrorFormat) -> i32 {
+    let kind = error.kind();
+    match format {
+        cli::ErrorFormat::Text => {
+            error!("Error executing command: {error}");
+            if let Some(source) = error.source() {
+                error!("Caused by: {source}");
+            }
+            if let Some(hint) = kind.hint() {
+                error!("Hint: {hint}");
+            }
+        }
+        cli::ErrorFormat::Json => match serde_json::to_string(&cli::ErrorReport::new(error)) {
+            Ok(json) => eprintln!("{json}"),
+            Err(_) => eprintln!("{error}"),
+        },
+    }
+    kind.exit_code()
 }
 
 /// Initialize the logger for the entire application
-static LOGGER_INIT: Once = Once::new();
-fn init_logger() {
-    LOGGER_INIT.call_once(|| {
-        // Set default log level based on environment or default to Info
-        let env_log = std::env::var("RUST_LOG").unwrap_or_else(|_| "info".to_string());
-
-        Builder::new()
-            .parse_filters(&env_log)
-            .format_timestamp(None)
-            .format_module_path(false)
-            .init();
-
-        debug!("Logger initialized with RUST_LOG={env_log}");
+/// Sends log records to stderr or `log_file` in `format` from now on
+///
+/// The level comes from `cli_level` if given, else from `env_log` in
+/// RUST_LOG syntax, else it is `default_level`.
+fn init_logger(
+    format: LogFormat,
+    log_file: Option<LogFile>,
+    cli_level: Option<LevelFilter>,
+    env_log: Option<&str>,
+    default_level: LevelFilter,
+    write_style: WriteStyle,
+) {
+    // Let everything through the builder's own filter and leave the overall
+    // level to `log::max_level`, which the daemon can change at runtime.
+    // Directives from RUST_LOG still apply on top, unless -v or -q override
+    // them.
+    let mut builder = Builder::new();
+    builder
+        .filter_level(LevelFilter::Trace)
+        .format_timestamp(None)
+        .format_module_path(false)
+        .write_style(write_style);
+    if let (None, Some(filters)) = (cli_level, env_log) {
+        builder.parse_filters(filters);
+    }
+    if format == LogFormat::Json {
+        builder.format(util::logging::format_json);
+    }
+    if let Some(log_file) = log_file {
+        // Unlike the journal, a plain file does not timestamp lines for us
+        builder
+            .format_timestamp_secs()
+            .target(Target::Pipe(Box::new(log_file)));
+    }
+    let logger = builder.build();
+
+    log::set_max_level(match (cli_level, env_log) {
+        (Some(level), _) => level,
+        (None, Some(_)) => logger.filter(),
+        (None, None) => default_level,
     });
+    util::logging::install(logger);
+
+    debug!(
+        "Logger initialized with RUST_LOG={env_log:?}, level {}",
+        log::max_level()
+    );
 }
 
 /// Validate CPU frequency input values
 fn validate_freq(freq_mhz: u32, label: &str) -> Result<(), AppError> {
     if freq_mhz == 0 {
         error!("{label} frequency cannot be zero");
-        Err(AppError::Generic(format!(
-            "{label} frequency cannot be zero"
-        )))
+        Err(AppError::Usage(format!("{label} frequency cannot be zero")))
     } else if freq_mhz > 10000 {
         // Extremely high value unlikely to be valid
         error!("{label} frequency ({freq_mhz} MHz) is unreasonably high");
-        Err(AppError::Generic(format!(
+        Err(AppError::Usage(format!(
             "{label} frequency ({freq_mhz} MHz) is unreasonably high"
         )))
     } else {
         Ok(())
     }
 }
+
+/// Pauses the running daemon, for `duration` if given
+fn set_daemon_profile(
+    name: &str,
+    duration: Option<jiff::SignedDuration>,
+    until: Option<jiff::Timestamp>,
+    until_power_change: bool,
+) -> Result<(), AppError> {
+    let duration_sec = match duration {
+        None => None,
+        Some(duration) => match u64::try_from(duration.as_secs()) {
+            Ok(secs) if secs > 0 => Some(secs),
+            _ => {
+                return Err(AppError::Usage(format!(
+                    "Profile duration must be at least a second, got {duration:#}"
+                )));
+            }
+        },
+    };
+
+    ipc::send_command(&ipc::Request::SetProfile {
+        name: name.to_string(),
+        duration_sec,
+        until,
+        until_power_change,
+    })
+    .map(|()| match (duration, until) {
+        _ if name.eq_ignore_ascii_case("auto") => {
+            println!("Daemon returned to automatic profile selection");
+        }
+        (Some(duration), _) => println!("Daemon profile set to {name} for {duration:#}"),
+        (None, Some(until)) => println!("Daemon profile set to {name} until {until}"),
+        (None, None) if until_power_change => {
+            println!("Daemon profile set to {name} until the power source changes");
+        }
+        (None, None) => println!("Daemon profile set to {name} until changed again"),
+    })
+    .map_err(|e| AppError::daemon("Failed to set daemon profile", &e))
+}
+
+fn pause_daemon(duration: Option<jiff::SignedDuration>) -> Result<(), AppError> {
+    let duration_sec = match duration {
+        None => None,
+        Some(duration) => match u64::try_from(duration.as_secs()) {
+            Ok(secs) if secs > 0 => Some(secs),
+            _ => {
+                return Err(AppError::Usage(format!(
+                    "Pause duration must be at least a second, got {duration:#}"
+                )));
+            }
+        },
+    };
+
+    ipc::send_command(&ipc::Request::Pause { duration_sec })
+        .map(|()| match duration {
+            Some(duration) => println!("Daemon settings management paused for {duration:#}"),
+            None => println!("Daemon settings management paused until `watt resume`"),
+        })
+        .map_err(|e| AppError::daemon("Failed to pause daemon", &e))
+}
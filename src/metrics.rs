@@ -0,0 +1,526 @@
+// Prometheus metrics exporter
+//
+// Serves `/metrics` in the Prometheus text exposition format from its own
+// thread. Only the latest report stored in the daemon state is read, so
+// scrapes never touch sysfs or block the control loop.
+use crate::daemon::DaemonState;
+use crate::engine::ApplySummary;
+use crate::util::sysfs;
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::thread;
+use std::time::Duration;
+
+/// Upper bounds of the poll duration histogram buckets, in seconds
+const CYCLE_DURATION_BUCKETS: &[f64] = &[0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0];
+
+/// How long a scraper may take to send its request
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Cumulative histogram of cycle durations
+#[derive(Debug, Default)]
+struct Histogram {
+    /// Observations per bucket, non-cumulative, plus one for `+Inf`
+    buckets: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+
+/// Counters and histograms updated by the control loop
+#[derive(Debug)]
+pub struct Metrics {
+    started_at: jiff::Timestamp,
+    apply_cycles: AtomicU64,
+    apply_errors: AtomicU64,
+    errors_suppressed: AtomicU64,
+    panics: AtomicU64,
+    config_reloads: AtomicU64,
+    resumes: AtomicU64,
+    profile_switches: AtomicU64,
+    knob_changes: AtomicU64,
+    knob_failures: AtomicU64,
+    thermal_safety_engagements: AtomicU64,
+    cycle_durations: Mutex<Histogram>,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self {
+            started_at: jiff::Timestamp::now(),
+            apply_cycles: AtomicU64::default(),
+            apply_errors: AtomicU64::default(),
+            errors_suppressed: AtomicU64::default(),
+            panics: AtomicU64::default(),
+            config_reloads: AtomicU64::default(),
+            resumes: AtomicU64::default(),
+            profile_switches: AtomicU64::default(),
+            knob_changes: AtomicU64::default(),
+            knob_failures: AtomicU64::default(),
+            thermal_safety_engagements: AtomicU64::default(),
+            cycle_durations: Mutex::default(),
+        }
+    }
+}
+
+/// Operational counters, all monotonically increasing since `started_at`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Counters {
+    pub started_at: jiff::Timestamp,
+    /// Cycles in which settings were applied, i.e. not paused
+    pub apply_cycles: u64,
+    /// Apply cycles that failed
+    pub apply_errors: u64,
+    /// Errors left out of the log as repeats, see `daemon.error_repeat_limit`
+    #[serde(default)]
+    pub errors_suppressed: u64,
+    /// Cycles skipped because they panicked
+    #[serde(default)]
+    pub panics: u64,
+    pub sysfs_writes: u64,
+    /// Writes skipped because the value was already in place
+    pub sysfs_writes_skipped: u64,
+    /// Failed writes by attribute name
+    pub sysfs_write_errors: BTreeMap<String, u64>,
+    pub config_reloads: u64,
+    pub resumes: u64,
+    pub profile_switches: u64,
+    /// Knobs written in apply cycles, counting each knob once per cycle
+    #[serde(default)]
+    pub knob_changes: u64,
+    /// Knobs that failed in apply cycles, counting each knob once per cycle
+    #[serde(default)]
+    pub knob_failures: u64,
+    /// Times the thermal safety net engaged
+    #[serde(default)]
+    pub thermal_safety_engagements: u64,
+}
+
+impl Metrics {
+    pub fn record_apply_cycle(&self) {
+        self.apply_cycles.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_apply_error(&self) {
+        self.apply_errors.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_suppressed_error(&self) {
+        self.errors_suppressed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_panic(&self) {
+        self.panics.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_config_reload(&self) {
+        self.config_reloads.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_resume(&self) {
+        self.resumes.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_profile_switch(&self) {
+        self.profile_switches.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_thermal_safety_engagement(&self) {
+        self.thermal_safety_engagements
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Counts the knobs an apply cycle changed and those that failed
+    pub fn record_apply_summary(&self, summary: &ApplySummary) {
+        self.knob_changes
+            .fetch_add(summary.changed() as u64, Ordering::Relaxed);
+        self.knob_failures
+            .fetch_add(summary.failed() as u64, Ordering::Relaxed);
+    }
+
+    /// Current values of all counters
+    pub fn counters(&self) -> Counters {
+        Counters {
+            started_at: self.started_at,
+            apply_cycles: self.apply_cycles.load(Ordering::Relaxed),
+            apply_errors: self.apply_errors.load(Ordering::Relaxed),
+            errors_suppressed: self.errors_suppressed.load(Ordering::Relaxed),
+            panics: self.panics.load(Ordering::Relaxed),
+            sysfs_writes: sysfs::write_count(),
+            sysfs_writes_skipped: sysfs::skipped_write_count(),
+            sysfs_write_errors: sysfs::write_errors(),
+            config_reloads: self.config_reloads.load(Ordering::Relaxed),
+            resumes: self.resumes.load(Ordering::Relaxed),
+            profile_switches: self.profile_switches.load(Ordering::Relaxed),
+            knob_changes: self.knob_changes.load(Ordering::Relaxed),
+            knob_failures: self.knob_failures.load(Ordering::Relaxed),
+            thermal_safety_engagements: self.thermal_safety_engagements.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Number of collect-and-apply cycles run so far
+    pub fn cycles(&self) -> u64 {
+        self.cycle_durations
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .count
+    }
+
+    /// Records how long a full collect-and-apply cycle took
+    pub fn record_cycle_duration(&self, duration: Duration) {
+        let seconds = duration.as_secs_f64();
+        let mut histogram = self
+            .cycle_durations
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+
+        if histogram.buckets.is_empty() {
+            histogram.buckets = vec![0; CYCLE_DURATION_BUCKETS.len() + 1];
+        }
+        let bucket = CYCLE_DURATION_BUCKETS
+            .iter()
+            .position(|bound| seconds <= *bound)
+            .unwrap_or(CYCLE_DURATION_BUCKETS.len());
+        histogram.buckets[bucket] += 1;
+        histogram.sum += seconds;
+        histogram.count += 1;
+    }
+}
+
+/// Renders all metrics in the Prometheus text format
+fn render(state: &DaemonState) -> String {
+    let mut out = String::new();
+    let metrics = state.metrics();
+
+    if let Some(report) = state.last_report() {
+        header(
+            &mut out,
+            "watt_cpu_frequency_mhz",
+            "gauge",
+            "Current CPU frequency",
+        );
+        for core in &report.cpu_cores {
+            if let Some(freq) = core.current_frequency_mhz {
+                let _ = writeln!(
+                    out,
+                    "watt_cpu_frequency_mhz{{core=\"{}\"}} {freq}",
+                    core.core_id
+                );
+            }
+        }
+
+        header(&mut out, "watt_cpu_usage_percent", "gauge", "CPU usage");
+        for core in &report.cpu_cores {
+            if let Some(usage) = core.usage_percent {
+                let _ = writeln!(
+                    out,
+                    "watt_cpu_usage_percent{{core=\"{}\"}} {usage}",
+                    core.core_id
+                );
+            }
+        }
+
+        if let Some(temp) = report.cpu_global.average_temperature_celsius {
+            header(
+                &mut out,
+                "watt_cpu_temperature_celsius",
+                "gauge",
+                "CPU package temperature",
+            );
+            let _ = writeln!(out, "watt_cpu_temperature_celsius {temp}");
+        }
+
+        let throttling = &report.throttling;
+        if throttling.events().is_some() {
+            header(
+                &mut out,
+                "watt_throttle_events",
+                "gauge",
+                "Thermal throttle events during the last polling interval",
+            );
+            for (scope, events) in [
+                ("core", throttling.core_events),
+                ("package", throttling.package_events),
+            ] {
+                if let Some(events) = events {
+                    let _ = writeln!(out, "watt_throttle_events{{scope=\"{scope}\"}} {events}");
+                }
+            }
+        }
+
+        let power = &report.cpu_power;
+        if power.package_watts.is_some() {
+            header(
+                &mut out,
+                "watt_cpu_power_watts",
+                "gauge",
+                "CPU power draw per RAPL domain during the last polling interval",
+            );
+            for (domain, watts) in [
+                ("package", power.package_watts),
+                ("cores", power.cores_watts),
+                ("uncore", power.uncore_watts),
+            ] {
+                if let Some(watts) = watts {
+                    let _ = writeln!(out, "watt_cpu_power_watts{{domain=\"{domain}\"}} {watts}");
+                }
+            }
+        }
+
+        if let Some(turbo) = report.cpu_global.turbo_status {
+            header(
+                &mut out,
+                "watt_turbo_enabled",
+                "gauge",
+                "Whether turbo boost is enabled",
+            );
+            let _ = writeln!(out, "watt_turbo_enabled {}", u8::from(turbo));
+        }
+
+        header(
+            &mut out,
+            "watt_battery_capacity_percent",
+            "gauge",
+            "Battery charge level",
+        );
+        for battery in &report.batteries {
+            if let Some(capacity) = battery.capacity_percent {
+                let _ = writeln!(
+                    out,
+                    "watt_battery_capacity_percent{{battery=\"{}\"}} {capacity}",
+                    escape(&battery.name)
+                );
+            }
+        }
+
+        header(
+            &mut out,
+            "watt_battery_power_watts",
+            "gauge",
+            "Battery power draw, negative when discharging",
+        );
+        for battery in &report.batteries {
+            if let Some(power) = battery.power_rate_watts {
+                let _ = writeln!(
+                    out,
+                    "watt_battery_power_watts{{battery=\"{}\"}} {power}",
+                    escape(&battery.name)
+                );
+            }
+        }
+    }
+
+    if let Some(profile) = state.active_profile() {
+        header(
+            &mut out,
+            "watt_active_profile",
+            "gauge",
+            "Profile applied in the last cycle",
+        );
+        let _ = writeln!(
+            out,
+            "watt_active_profile{{profile=\"{}\"}} 1",
+            escape(&profile)
+        );
+    }
+
+    let counters = metrics.counters();
+    header(
+        &mut out,
+        "watt_start_time_seconds",
+        "gauge",
+        "When the daemon started, as a Unix timestamp",
+    );
+    let _ = writeln!(
+        out,
+        "watt_start_time_seconds {}",
+        counters.started_at.as_second()
+    );
+
+    for (name, help, value) in [
+        (
+            "watt_apply_cycles_total",
+            "Cycles in which settings were applied",
+            counters.apply_cycles,
+        ),
+        (
+            "watt_apply_errors_total",
+            "Cycles in which applying settings failed",
+            counters.apply_errors,
+        ),
+        (
+            "watt_errors_suppressed_total",
+            "Errors left out of the log because they kept repeating",
+            counters.errors_suppressed,
+        ),
+        (
+            "watt_panics_total",
+            "Cycles skipped because they panicked",
+            counters.panics,
+        ),
+        (
+            "watt_sysfs_writes_total",
+            "Values written to sysfs",
+            counters.sysfs_writes,
+        ),
+        (
+            "watt_sysfs_writes_skipped_total",
+            "Sysfs writes skipped because the value was already in place",
+            counters.sysfs_writes_skipped,
+        ),
+        (
+            "watt_config_reloads_total",
+            "Successful configuration reloads",
+            counters.config_reloads,
+        ),
+        (
+            "watt_resumes_total",
+            "Resumes from suspend",
+            counters.resumes,
+        ),
+        (
+            "watt_profile_switches_total",
+            "Changes of the active profile",
+            counters.profile_switches,
+        ),
+        (
+            "watt_knob_changes_total",
+            "Knobs written in apply cycles",
+            counters.knob_changes,
+        ),
+        (
+            "watt_knob_failures_total",
+            "Knobs that failed to apply in apply cycles",
+            counters.knob_failures,
+        ),
+        (
+            "watt_thermal_safety_engagements_total",
+            "Times the thermal safety net clamped an overheating CPU",
+            counters.thermal_safety_engagements,
+        ),
+    ] {
+        header(&mut out, name, "counter", help);
+        let _ = writeln!(out, "{name} {value}");
+    }
+
+    header(
+        &mut out,
+        "watt_sysfs_write_errors_total",
+        "counter",
+        "Failed sysfs writes by attribute",
+    );
+    for (knob, count) in &counters.sysfs_write_errors {
+        let _ = writeln!(
+            out,
+            "watt_sysfs_write_errors_total{{knob=\"{}\"}} {count}",
+            escape(knob)
+        );
+    }
+
+    header(
+        &mut out,
+        "watt_cycle_duration_seconds",
+        "histogram",
+        "Duration of a collect-and-apply cycle",
+    );
+    let histogram = metrics
+        .cycle_durations
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner);
+    let mut cumulative = 0;
+    for (i, bound) in CYCLE_DURATION_BUCKETS.iter().enumerate() {
+        cumulative += histogram.buckets.get(i).copied().unwrap_or(0);
+        let _ = writeln!(
+            out,
+            "watt_cycle_duration_seconds_bucket{{le=\"{bound}\"}} {cumulative}"
+        );
+    }
+    let _ = writeln!(
+        out,
+        "watt_cycle_duration_seconds_bucket{{le=\"+Inf\"}} {}",
+        histogram.count
+    );
+    let _ = writeln!(out, "watt_cycle_duration_seconds_sum {}", histogram.sum);
+    let _ = writeln!(out, "watt_cycle_duration_seconds_count {}", histogram.count);
+
+    out
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {name} {help}");
+    let _ = writeln!(out, "# TYPE {name} {kind}");
+}
+
+/// Escapes a label value
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Answers a single HTTP request
+fn handle_connection(state: &DaemonState, stream: TcpStream) -> io::Result<()> {
+    stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+    stream.set_write_timeout(Some(REQUEST_TIMEOUT))?;
+
+    let mut reader = BufReader::new(&stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+
+    // Drain the headers so the client sees a clean close
+    let mut line = String::new();
+    while reader.read_line(&mut line)? > 2 {
+        line.clear();
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let (status, content_type, body) = match (parts.next(), parts.next()) {
+        (Some("GET"), Some("/metrics")) => (
+            "200 OK",
+            "text/plain; version=0.0.4; charset=utf-8",
+            render(state),
+        ),
+        (Some("GET"), _) => ("404 Not Found", "text/plain", "Not found\n".to_string()),
+        _ => (
+            "405 Method Not Allowed",
+            "text/plain",
+            "Method not allowed\n".to_string(),
+        ),
+    };
+
+    let mut stream = &stream;
+    write!(
+        stream,
+        "HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )?;
+    stream.flush()
+}
+
+/// Starts serving metrics on `address` in the background
+pub fn start_server(address: &str, state: Arc<DaemonState>) -> io::Result<()> {
+    let listener = TcpListener::bind(address)?;
+
+    thread::Builder::new()
+        .name("metrics".to_string())
+        .spawn(move || {
+            for stream in listener.incoming() {
+                match stream {
+                    Ok(stream) => {
+                        if let Err(e) = handle_connection(&state, stream) {
+                            debug!("Metrics request failed: {e}");
+                        }
+                    }
+                    Err(e) => warn!("Failed to accept metrics connection: {e}"),
+                }
+            }
+        })?;
+
+    Ok(())
+}
@@ -1,13 +1,20 @@
-use crate::config::AppConfig;
-use crate::core::{BatteryInfo, CpuCoreInfo, CpuGlobalInfo, SystemInfo, SystemLoad, SystemReport};
-use crate::cpu::get_logical_core_count;
+use crate::config::{AcDetection, AppConfig};
+use crate::core::{
+    AcPowerReading, BatteryInfo, CpuCoreInfo, CpuGlobalInfo, CpuPolicyInfo, CpuPressure,
+    SystemInfo, SystemLoad, SystemReport, ThrottleInfo,
+};
+use crate::cpu::{self, get_logical_core_count};
+use crate::lid;
+use crate::rapl;
+use crate::sensors;
 use crate::util::error::SysMonitorError;
 use log::debug;
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, HashMap},
     fs,
     path::{Path, PathBuf},
     str::FromStr,
+    sync::{Mutex, OnceLock, PoisonError},
     thread,
     time::Duration,
     time::SystemTime,
@@ -61,24 +68,109 @@ pub struct CpuTimes {
 }
 
 impl CpuTimes {
-    const fn total_time(&self) -> u64 {
-        self.user
-            + self.nice
-            + self.system
-            + self.idle
-            + self.iowait
-            + self.irq
-            + self.softirq
-            + self.steal
+    const fn fields(&self) -> [u64; 8] {
+        [
+            self.user,
+            self.nice,
+            self.system,
+            self.idle,
+            self.iowait,
+            self.irq,
+            self.softirq,
+            self.steal,
+        ]
+    }
+
+    /// Time spent idle. Waiting for I/O and time taken by the hypervisor
+    /// count as idle unless `count_iowait_as_busy` is set.
+    const fn idle_time(&self, count_iowait_as_busy: bool) -> u64 {
+        if count_iowait_as_busy {
+            self.idle
+        } else {
+            self.idle + self.iowait + self.steal
+        }
     }
 
-    const fn idle_time(&self) -> u64 {
-        self.idle + self.iowait
+    /// Share of the time since `previous` the CPU was busy, in percent.
+    /// `None` when no time passed, or when a counter went backwards, as it
+    /// does when a CPU goes offline and comes back or a counter wraps.
+    pub fn usage_since(&self, previous: &Self, count_iowait_as_busy: bool) -> Option<f32> {
+        if self
+            .fields()
+            .iter()
+            .zip(previous.fields())
+            .any(|(now, before)| *now < before)
+        {
+            return None;
+        }
+        let total_diff: u64 = self
+            .fields()
+            .iter()
+            .zip(previous.fields())
+            .map(|(now, before)| now - before)
+            .sum();
+        let idle_diff =
+            self.idle_time(count_iowait_as_busy) - previous.idle_time(count_iowait_as_busy);
+        (total_diff > 0).then(|| {
+            let usage = 100.0 * (1.0 - idle_diff as f32 / total_diff as f32);
+            usage.clamp(0.0, 100.0)
+        })
     }
 }
 
-fn read_all_cpu_times() -> Result<HashMap<u32, CpuTimes>> {
+/// The counters usage was last measured at, the baseline of the next report
+static PREVIOUS_CPU_TIMES: Mutex<Option<HashMap<u32, CpuTimes>>> = Mutex::new(None);
+
+/// How long the very first measurement waits for a baseline
+const FIRST_SAMPLE_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Usage of each CPU in `/proc/stat` since the previous call, so the daemon
+/// measures over its whole polling interval. The first call measures over
+/// [`FIRST_SAMPLE_INTERVAL`] instead. CPUs that have no baseline yet, or
+/// whose counters went backwards, report `None` and start over from this
+/// sample.
+pub fn get_cpu_usage(count_iowait_as_busy: bool) -> Result<HashMap<u32, Option<f32>>> {
+    let mut previous = PREVIOUS_CPU_TIMES
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner);
+    let baseline = match previous.take() {
+        Some(baseline) => baseline,
+        None => {
+            let baseline = read_all_cpu_times()?;
+            thread::sleep(FIRST_SAMPLE_INTERVAL);
+            baseline
+        }
+    };
+    let current = read_all_cpu_times()?;
+    let usage = usage_between(&baseline, &current, count_iowait_as_busy);
+    *previous = Some(current);
+    Ok(usage)
+}
+
+/// Usage of each CPU in `current` since `baseline`
+fn usage_between(
+    baseline: &HashMap<u32, CpuTimes>,
+    current: &HashMap<u32, CpuTimes>,
+    count_iowait_as_busy: bool,
+) -> HashMap<u32, Option<f32>> {
+    current
+        .iter()
+        .map(|(core_id, now)| {
+            let usage = baseline
+                .get(core_id)
+                .and_then(|before| now.usage_since(before, count_iowait_as_busy));
+            (*core_id, usage)
+        })
+        .collect()
+}
+
+pub fn read_all_cpu_times() -> Result<HashMap<u32, CpuTimes>> {
     let content = fs::read_to_string("/proc/stat").map_err(SysMonitorError::Io)?;
+    parse_cpu_times(&content)
+}
+
+/// The counters of each CPU in the contents of `/proc/stat`
+fn parse_cpu_times(content: &str) -> Result<HashMap<u32, CpuTimes>> {
     let mut cpu_times_map = HashMap::new();
 
     for line in content.lines() {
@@ -153,10 +245,12 @@ fn read_all_cpu_times() -> Result<HashMap<u32, CpuTimes>> {
     Ok(cpu_times_map)
 }
 
+/// The state of one core, with its temperature from `core_temps` when a
+/// per-core sensor covers it, see [`sensors::core_temperatures`]
 pub fn get_cpu_core_info(
     core_id: u32,
-    prev_times: &CpuTimes,
-    current_times: &CpuTimes,
+    usage_percent: Option<f32>,
+    core_temps: &BTreeMap<u32, f32>,
 ) -> Result<CpuCoreInfo> {
     let cpufreq_path = PathBuf::from(format!("/sys/devices/system/cpu/cpu{core_id}/cpufreq/"));
 
@@ -169,14 +263,19 @@ pub fn get_cpu_core_info(
     let max_frequency_mhz = read_sysfs_value::<u32>(cpufreq_path.join("scaling_max_freq"))
         .map(|khz| khz / 1000)
         .ok();
+    let governor = read_sysfs_file_trimmed(cpufreq_path.join("scaling_governor")).ok();
 
     // Temperature detection.
     // Should be generic enough to be able to support for multiple hardware sensors
     // with the possibility of extending later down the road.
-    let mut temperature_celsius: Option<f32> = None;
+    let mut temperature_celsius: Option<f32> = core_temps.get(&core_id).copied();
 
-    // Search for temperature in hwmon devices
-    if let Ok(hwmon_dir) = fs::read_dir("/sys/class/hwmon") {
+    // Search for temperature in hwmon devices, unless a per-core sensor had it
+    if let Some(hwmon_dir) = temperature_celsius
+        .is_none()
+        .then(|| fs::read_dir("/sys/class/hwmon").ok())
+        .flatten()
+    {
         for hw_entry in hwmon_dir.flatten() {
             let hw_path = hw_entry.path();
 
@@ -260,25 +359,6 @@ pub fn get_cpu_core_info(
         }
     }
 
-    let usage_percent: Option<f32> = {
-        let prev_idle = prev_times.idle_time();
-        let current_idle = current_times.idle_time();
-
-        let prev_total = prev_times.total_time();
-        let current_total = current_times.total_time();
-
-        let total_diff = current_total.saturating_sub(prev_total);
-        let idle_diff = current_idle.saturating_sub(prev_idle);
-
-        // Avoid division by zero if no time has passed or counters haven't changed
-        if total_diff == 0 {
-            None
-        } else {
-            let usage = 100.0 * (1.0 - (idle_diff as f32 / total_diff as f32));
-            Some(usage.clamp(0.0, 100.0)) // clamp between 0 and 100
-        }
-    };
-
     Ok(CpuCoreInfo {
         core_id,
         current_frequency_mhz,
@@ -286,6 +366,7 @@ pub fn get_cpu_core_info(
         max_frequency_mhz,
         usage_percent,
         temperature_celsius,
+        governor,
     })
 }
 
@@ -359,22 +440,18 @@ fn get_fallback_temperature(hw_path: &Path) -> Option<f32> {
     None
 }
 
-pub fn get_all_cpu_core_info() -> Result<Vec<CpuCoreInfo>> {
-    let initial_cpu_times = read_all_cpu_times()?;
-    thread::sleep(Duration::from_millis(250)); // interval for CPU usage calculation
-    let final_cpu_times = read_all_cpu_times()?;
+pub fn get_all_cpu_core_info(count_iowait_as_busy: bool) -> Result<Vec<CpuCoreInfo>> {
+    let cpu_usage = get_cpu_usage(count_iowait_as_busy)?;
 
     let num_cores = get_logical_core_count()
         .map_err(|_| SysMonitorError::ReadError("Could not get the number of cores".to_string()))?;
 
     let mut core_infos = Vec::with_capacity(num_cores as usize);
+    let core_temps = sensors::core_temperatures();
 
     for core_id in 0..num_cores {
-        if let (Some(prev), Some(curr)) = (
-            initial_cpu_times.get(&core_id),
-            final_cpu_times.get(&core_id),
-        ) {
-            match get_cpu_core_info(core_id, prev, curr) {
+        if let Some(usage) = cpu_usage.get(&core_id) {
+            match get_cpu_core_info(core_id, *usage, &core_temps) {
                 Ok(info) => core_infos.push(info),
                 Err(e) => {
                     // Log or handle error for a single core, maybe push a partial info or skip
@@ -389,7 +466,10 @@ pub fn get_all_cpu_core_info() -> Result<Vec<CpuCoreInfo>> {
     Ok(core_infos)
 }
 
-pub fn get_cpu_global_info(cpu_cores: &[CpuCoreInfo]) -> CpuGlobalInfo {
+pub fn get_cpu_global_info(
+    cpu_cores: &[CpuCoreInfo],
+    temperature_sensor: Option<&str>,
+) -> CpuGlobalInfo {
     // Find a valid CPU to read global settings from
     // Try cpu0 first, then fall back to any available CPU with cpufreq
     let mut cpufreq_base_path_buf = PathBuf::from("/sys/devices/system/cpu/cpu0/cpufreq/");
@@ -453,28 +533,39 @@ pub fn get_cpu_global_info(cpu_cores: &[CpuCoreInfo]) -> CpuGlobalInfo {
 
     let platform_profile = read_sysfs_file_trimmed("/sys/firmware/acpi/platform_profile").ok();
 
-    // Calculate average CPU temperature from the core temperatures
-    let average_temperature_celsius = if cpu_cores.is_empty() {
+    // Prefer the package sensor, else the average of the core temperatures
+    let sensor = sensors::cpu_sensor(temperature_sensor)
+        .and_then(|sensor| Some((sensor.read()?, sensor.name)));
+    let core_average = if cpu_cores.is_empty() {
         None
     } else {
-        // Filter cores with temperature readings, then calculate average
-        let cores_with_temp: Vec<&CpuCoreInfo> = cpu_cores
+        // Take cores with temperature readings, then calculate average
+        let temperatures: Vec<f32> = cpu_cores
             .iter()
-            .filter(|core| core.temperature_celsius.is_some())
+            .filter_map(|core| core.temperature_celsius)
             .collect();
 
-        if cores_with_temp.is_empty() {
+        if temperatures.is_empty() {
             None
         } else {
             // Sum up all temperatures and divide by count
-            let sum: f32 = cores_with_temp
-                .iter()
-                .map(|core| core.temperature_celsius.unwrap())
-                .sum();
-            Some(sum / cores_with_temp.len() as f32)
+            let sum: f32 = temperatures.iter().sum();
+            Some(sum / temperatures.len() as f32)
         }
     };
 
+    let (average_temperature_celsius, temperature_sensor) = match sensor {
+        Some((temp, name)) => (Some(temp), Some(name)),
+        None => (core_average, None),
+    };
+
+    let frequencies: Vec<u32> = cpu_cores
+        .iter()
+        .filter_map(|core| core.current_frequency_mhz)
+        .collect();
+    let average_frequency_mhz = (!frequencies.is_empty())
+        .then(|| frequencies.iter().sum::<u32>() / frequencies.len() as u32);
+
     // Return the constructed CpuGlobalInfo
     CpuGlobalInfo {
         current_governor,
@@ -484,60 +575,106 @@ pub fn get_cpu_global_info(cpu_cores: &[CpuCoreInfo]) -> CpuGlobalInfo {
         epb: energy_perf_bias,
         platform_profile,
         average_temperature_celsius,
+        temperature_sensor,
+        smoothed_temperature_celsius: None,
+        critical_temperature_celsius: get_critical_temperature(),
+        average_frequency_mhz,
+        highest_frequency_mhz: frequencies.iter().copied().max(),
+    }
+}
+
+/// The cpufreq policies with the CPUs they cover, listed once since they only
+/// change with CPU hotplug
+fn policy_paths() -> &'static [(u32, PathBuf, Vec<u32>)] {
+    static POLICIES: OnceLock<Vec<(u32, PathBuf, Vec<u32>)>> = OnceLock::new();
+    POLICIES.get_or_init(|| {
+        cpu::cpufreq_policies()
+            .into_iter()
+            .filter_map(|path| {
+                let id = path
+                    .file_name()?
+                    .to_str()?
+                    .strip_prefix("policy")?
+                    .parse()
+                    .ok()?;
+                let cpus = read_sysfs_file_trimmed(path.join("related_cpus"))
+                    .map(|cpus| {
+                        cpus.split_whitespace()
+                            .filter_map(|cpu| cpu.parse().ok())
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                Some((id, path, cpus))
+            })
+            .collect()
+    })
+}
+
+/// The current state of every cpufreq policy. Files that cannot be read are
+/// left as `None`.
+pub fn get_cpu_policy_info() -> Vec<CpuPolicyInfo> {
+    let mhz = |path: &Path, file: &str| {
+        read_sysfs_value::<u32>(path.join(file))
+            .map(|khz| khz / 1000)
+            .ok()
+    };
+    policy_paths()
+        .iter()
+        .map(|(id, path, cpus)| CpuPolicyInfo {
+            id: *id,
+            cpus: cpus.clone(),
+            governor: read_sysfs_file_trimmed(path.join("scaling_governor")).ok(),
+            current_frequency_mhz: mhz(path, "scaling_cur_freq"),
+            min_frequency_mhz: mhz(path, "scaling_min_freq"),
+            max_frequency_mhz: mhz(path, "scaling_max_freq"),
+            hardware_min_frequency_mhz: mhz(path, "cpuinfo_min_freq"),
+            hardware_max_frequency_mhz: mhz(path, "cpuinfo_max_freq"),
+        })
+        .collect()
+}
+
+/// Reads Tjmax from the critical threshold of the CPU's hwmon sensor. coretemp
+/// reports it for each core, k10temp only on some families.
+fn get_critical_temperature() -> Option<f32> {
+    let hwmon_dir = fs::read_dir("/sys/class/hwmon").ok()?;
+    for hw_entry in hwmon_dir.flatten() {
+        let hw_path = hw_entry.path();
+        let Ok(name) = read_sysfs_file_trimmed(hw_path.join("name")) else {
+            continue;
+        };
+        if !matches!(name.as_str(), "coretemp" | "k10temp" | "zenpower") {
+            continue;
+        }
+
+        // The lowest threshold is the one the CPU reaches first
+        let critical = (1..=32)
+            .filter_map(|i| read_sysfs_value::<i32>(hw_path.join(format!("temp{i}_crit"))).ok())
+            .filter(|temp_mc| *temp_mc > 0)
+            .min();
+        if let Some(temp_mc) = critical {
+            return Some(temp_mc as f32 / 1000.0);
+        }
     }
+    None
 }
 
 pub fn get_battery_info(config: &AppConfig) -> Result<Vec<BatteryInfo>> {
+    get_power_info(config).map(|(batteries, _)| batteries)
+}
+
+/// The batteries, with whether the system is on AC power as decided by
+/// `ac_sources` or `ac_detection`
+pub fn get_power_info(config: &AppConfig) -> Result<(Vec<BatteryInfo>, AcPowerReading)> {
     let mut batteries = Vec::new();
     let power_supply_path = Path::new("/sys/class/power_supply");
 
     if !power_supply_path.exists() {
-        return Ok(batteries); // no power supply directory
+        // no power supply directory
+        return Ok((batteries, detect_ac_power(config, power_supply_path, &[])));
     }
 
     let ignored_supplies = config.ignored_power_supplies.clone().unwrap_or_default();
 
-    // Determine overall AC connection status
-    let mut overall_ac_connected = false;
-    for entry in fs::read_dir(power_supply_path)? {
-        let entry = entry?;
-        let ps_path = entry.path();
-        let name = entry.file_name().into_string().unwrap_or_default();
-
-        // Check for AC adapter type (common names: AC, ACAD, ADP)
-        if let Ok(ps_type) = read_sysfs_file_trimmed(ps_path.join("type")) {
-            if ps_type == "Mains"
-                || ps_type == "USB_PD_DRP"
-                || ps_type == "USB_PD"
-                || ps_type == "USB_DCP"
-                || ps_type == "USB_CDP"
-                || ps_type == "USB_ACA"
-            {
-                // USB types can also provide power
-                if let Ok(online) = read_sysfs_value::<u8>(ps_path.join("online")) {
-                    if online == 1 {
-                        overall_ac_connected = true;
-                        break;
-                    }
-                }
-            }
-        } else if name.starts_with("AC") || name.contains("ACAD") || name.contains("ADP") {
-            // Fallback for type file missing
-            if let Ok(online) = read_sysfs_value::<u8>(ps_path.join("online")) {
-                if online == 1 {
-                    overall_ac_connected = true;
-                    break;
-                }
-            }
-        }
-    }
-
-    // No AC adapter detected but we're on a desktop system
-    // Default to AC power for desktops
-    if !overall_ac_connected {
-        overall_ac_connected = is_likely_desktop_system();
-    }
-
     for entry in fs::read_dir(power_supply_path)? {
         let entry = entry?;
         let ps_path = entry.path();
@@ -585,7 +722,7 @@ pub fn get_battery_info(config: &AppConfig) -> Result<Vec<BatteryInfo>> {
 
                 batteries.push(BatteryInfo {
                     name: name.clone(),
-                    ac_connected: overall_ac_connected,
+                    ac_connected: false,
                     charging_state: status_str,
                     capacity_percent,
                     power_rate_watts,
@@ -596,12 +733,135 @@ pub fn get_battery_info(config: &AppConfig) -> Result<Vec<BatteryInfo>> {
         }
     }
 
+    let ac_power = detect_ac_power(config, power_supply_path, &batteries);
+    for battery in &mut batteries {
+        battery.ac_connected = ac_power.online;
+    }
+
     // If we found no batteries but have power supplies, we're likely on a desktop
-    if batteries.is_empty() && overall_ac_connected {
+    if batteries.is_empty() && ac_power.online {
         debug!("No laptop batteries found, likely a desktop system");
     }
 
-    Ok(batteries)
+    Ok((batteries, ac_power))
+}
+
+/// Whether the system is on AC power, from the supplies pinned by
+/// `ac_sources` or else per `ac_detection`
+fn detect_ac_power(
+    config: &AppConfig,
+    power_supply_path: &Path,
+    batteries: &[BatteryInfo],
+) -> AcPowerReading {
+    let online =
+        |name: &str| read_sysfs_value::<u8>(power_supply_path.join(name).join("online")).ok();
+
+    if let Some(sources) = config.ac_sources.as_ref().filter(|s| !s.is_empty()) {
+        let readings: Vec<(&str, Option<u8>)> = sources
+            .iter()
+            .map(|name| (name.as_str(), online(name)))
+            .collect();
+        return AcPowerReading {
+            source: format!("ac_sources = {}", sources.join(", ")),
+            reading: readings
+                .iter()
+                .map(|(name, online)| match online {
+                    Some(1) => format!("{name} online"),
+                    Some(_) => format!("{name} offline"),
+                    None => format!("{name} missing"),
+                })
+                .collect::<Vec<_>>()
+                .join(", "),
+            online: readings.iter().any(|(_, online)| *online == Some(1)),
+        };
+    }
+
+    let source = config.ac_detection.to_string();
+    let battery_states = || {
+        batteries
+            .iter()
+            .map(|b| {
+                format!(
+                    "{} {}",
+                    b.name,
+                    b.charging_state.as_deref().unwrap_or("Unknown")
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    match config.ac_detection {
+        AcDetection::Auto | AcDetection::AnyMainsOnline => {
+            let mains_only = config.ac_detection == AcDetection::AnyMainsOnline;
+            let supply = online_ac_supply(power_supply_path, mains_only);
+            match supply {
+                Some(name) => AcPowerReading {
+                    source,
+                    reading: format!("{name} online"),
+                    online: true,
+                },
+                // No AC adapter detected but we're on a desktop system
+                // Default to AC power for desktops
+                None if !mains_only && is_likely_desktop_system() => AcPowerReading {
+                    source,
+                    reading: "no supply online, desktop system".to_string(),
+                    online: true,
+                },
+                None => AcPowerReading {
+                    source,
+                    reading: "no supply online".to_string(),
+                    online: false,
+                },
+            }
+        }
+        AcDetection::AllBatteriesReportingAc => AcPowerReading {
+            source,
+            reading: battery_states(),
+            online: !batteries.is_empty()
+                && batteries.iter().all(|b| {
+                    matches!(
+                        b.charging_state.as_deref(),
+                        Some("Charging" | "Full" | "Not charging")
+                    )
+                }),
+        },
+        AcDetection::BatteryStatusBased => AcPowerReading {
+            source,
+            reading: battery_states(),
+            online: !batteries
+                .iter()
+                .any(|b| b.charging_state.as_deref() == Some("Discharging")),
+        },
+    }
+}
+
+/// The first online supply that can power the system, only of type Mains with
+/// `mains_only`
+fn online_ac_supply(power_supply_path: &Path, mains_only: bool) -> Option<String> {
+    for entry in fs::read_dir(power_supply_path).ok()?.flatten() {
+        let ps_path = entry.path();
+        let name = entry.file_name().into_string().unwrap_or_default();
+
+        // Check for AC adapter type (common names: AC, ACAD, ADP)
+        let is_ac = if let Ok(ps_type) = read_sysfs_file_trimmed(ps_path.join("type")) {
+            if mains_only {
+                ps_type == "Mains"
+            } else {
+                // USB types can also provide power
+                matches!(
+                    ps_type.as_str(),
+                    "Mains" | "USB_PD_DRP" | "USB_PD" | "USB_DCP" | "USB_CDP" | "USB_ACA"
+                )
+            }
+        } else {
+            // Fallback for type file missing
+            !mains_only && (name.starts_with("AC") || name.contains("ACAD") || name.contains("ADP"))
+        };
+        if is_ac && read_sysfs_value::<u8>(ps_path.join("online")).is_ok_and(|online| online == 1) {
+            return Some(name);
+        }
+    }
+    None
 }
 
 /// Check if a battery is likely a peripheral (mouse, keyboard, etc) not a laptop battery
@@ -705,21 +965,125 @@ pub fn get_system_load() -> Result<SystemLoad> {
         load_avg_1min,
         load_avg_5min,
         load_avg_15min,
+        cpu_pressure: get_cpu_pressure(),
+        smoothed_cpu_usage: None,
+    })
+}
+
+/// Reads the "some" averages of `/proc/pressure/cpu`, which is missing on
+/// kernels built without PSI and unreadable with PSI disabled at boot
+fn get_cpu_pressure() -> Option<CpuPressure> {
+    let content = read_sysfs_file_trimmed("/proc/pressure/cpu").ok()?;
+    let some = content
+        .lines()
+        .find_map(|line| line.strip_prefix("some "))?;
+    let field = |name: &str| {
+        some.split_whitespace()
+            .find_map(|pair| pair.strip_prefix(name)?.strip_prefix('='))?
+            .parse()
+            .ok()
+    };
+    Some(CpuPressure {
+        some_avg10: field("avg10")?,
+        some_avg60: field("avg60")?,
     })
 }
 
+/// Cumulative `thermal_throttle` counters, per CPU and per package
+#[derive(Debug, Default)]
+struct ThrottleCounts {
+    cores: HashMap<u32, u64>,
+    packages: HashMap<u32, u64>,
+}
+
+/// The counters of the previous report, throttle events are counted from
+static PREVIOUS_THROTTLE_COUNTS: Mutex<Option<ThrottleCounts>> = Mutex::new(None);
+
+/// Reads the throttle counters of every CPU. Each CPU repeats the count of
+/// its package, which is taken once.
+fn read_throttle_counts() -> ThrottleCounts {
+    let mut counts = ThrottleCounts::default();
+    let Ok(entries) = fs::read_dir("/sys/devices/system/cpu") else {
+        return counts;
+    };
+    for entry in entries.flatten() {
+        let Some(cpu) = entry
+            .file_name()
+            .to_str()
+            .and_then(|name| name.strip_prefix("cpu")?.parse::<u32>().ok())
+        else {
+            continue;
+        };
+        let path = entry.path();
+        let throttle = path.join("thermal_throttle");
+        if let Ok(count) = read_sysfs_value::<u64>(throttle.join("core_throttle_count")) {
+            counts.cores.insert(cpu, count);
+        }
+        if let (Ok(count), Ok(package)) = (
+            read_sysfs_value::<u64>(throttle.join("package_throttle_count")),
+            read_sysfs_value::<u32>(path.join("topology/physical_package_id")),
+        ) {
+            counts.packages.entry(package).or_insert(count);
+        }
+    }
+    counts
+}
+
+/// Throttle events since the previous call. Counters that went backwards, as
+/// when a CPU comes back online, count nothing for this interval.
+pub fn get_throttle_info() -> ThrottleInfo {
+    let current = read_throttle_counts();
+    let total_events = (!current.cores.is_empty() || !current.packages.is_empty())
+        .then(|| current.cores.values().sum::<u64>() + current.packages.values().sum::<u64>());
+    let mut previous = PREVIOUS_THROTTLE_COUNTS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner);
+    let info = previous.as_ref().map_or_else(
+        || ThrottleInfo {
+            total_events,
+            ..ThrottleInfo::default()
+        },
+        |previous| {
+            let events = |now: &HashMap<u32, u64>, before: &HashMap<u32, u64>| {
+                now.iter()
+                    .filter_map(|(id, count)| Some((*id, count.saturating_sub(*before.get(id)?))))
+                    .collect::<BTreeMap<u32, u64>>()
+            };
+            let cores = events(&current.cores, &previous.cores);
+            let packages = events(&current.packages, &previous.packages);
+            ThrottleInfo {
+                core_events: (!current.cores.is_empty()).then(|| cores.values().sum()),
+                package_events: (!current.packages.is_empty()).then(|| packages.values().sum()),
+                throttled_cpus: cores
+                    .iter()
+                    .filter(|(_, events)| **events > 0)
+                    .map(|(cpu, _)| *cpu)
+                    .collect(),
+                total_events,
+            }
+        },
+    );
+    *previous = Some(current);
+    info
+}
+
 pub fn collect_system_report(config: &AppConfig) -> Result<SystemReport> {
     let system_info = get_system_info();
-    let cpu_cores = get_all_cpu_core_info()?;
-    let cpu_global = get_cpu_global_info(&cpu_cores);
-    let batteries = get_battery_info(config)?;
+    let cpu_cores = get_all_cpu_core_info(config.count_iowait_as_busy)?;
+    let cpu_global = get_cpu_global_info(&cpu_cores, config.temperature_sensor.as_deref());
+    let (batteries, ac_power) = get_power_info(config)?;
     let system_load = get_system_load()?;
 
     Ok(SystemReport {
         system_info,
         cpu_cores,
         cpu_global,
+        cpu_policies: get_cpu_policy_info(),
         batteries,
+        ac_power,
+        lid: lid::read(),
+        throttling: get_throttle_info(),
+        cpu_power: rapl::cpu_power(),
         system_load,
         timestamp: SystemTime::now(),
     })
@@ -785,3 +1149,207 @@ pub fn get_linux_distribution() -> Result<String> {
         lsb_release_path.display()
     )))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// A power supply directory holding `supplies` as (name, type, online)
+    struct Supplies(PathBuf);
+
+    impl Supplies {
+        fn new(supplies: &[(&str, &str, u8)]) -> Self {
+            static NEXT: AtomicUsize = AtomicUsize::new(0);
+            let dir = std::env::temp_dir().join(format!(
+                "watt-supplies-{}-{}",
+                std::process::id(),
+                NEXT.fetch_add(1, Ordering::Relaxed)
+            ));
+            for (name, kind, online) in supplies {
+                let supply = dir.join(name);
+                fs::create_dir_all(&supply).unwrap();
+                fs::write(supply.join("type"), kind).unwrap();
+                fs::write(supply.join("online"), online.to_string()).unwrap();
+            }
+            fs::create_dir_all(&dir).unwrap();
+            Self(dir)
+        }
+    }
+
+    impl Drop for Supplies {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn battery(charging_state: &str) -> BatteryInfo {
+        BatteryInfo {
+            name: "BAT0".to_string(),
+            ac_connected: false,
+            charging_state: Some(charging_state.to_string()),
+            capacity_percent: Some(50),
+            power_rate_watts: None,
+            charge_start_threshold: None,
+            charge_stop_threshold: None,
+        }
+    }
+
+    fn online(detection: AcDetection, supplies: &Supplies, batteries: &[BatteryInfo]) -> bool {
+        let config = AppConfig {
+            ac_detection: detection,
+            ..AppConfig::default()
+        };
+        detect_ac_power(&config, &supplies.0, batteries).online
+    }
+
+    #[test]
+    fn auto_counts_usb_power() {
+        let usb = Supplies::new(&[("ucsi-source-psy-1", "USB_PD", 1)]);
+        assert!(online(AcDetection::Auto, &usb, &[battery("Charging")]));
+        let adapter = Supplies::new(&[("ADP1", "Mains", 1)]);
+        assert!(online(AcDetection::Auto, &adapter, &[battery("Charging")]));
+    }
+
+    #[test]
+    fn any_mains_online_needs_a_mains_supply() {
+        let usb = Supplies::new(&[("ucsi-source-psy-1", "USB_PD", 1)]);
+        assert!(!online(AcDetection::AnyMainsOnline, &usb, &[]));
+        let unplugged = Supplies::new(&[("ADP1", "Mains", 0)]);
+        assert!(!online(AcDetection::AnyMainsOnline, &unplugged, &[]));
+        let adapter = Supplies::new(&[("ADP1", "Mains", 1)]);
+        assert!(online(AcDetection::AnyMainsOnline, &adapter, &[]));
+    }
+
+    #[test]
+    fn all_batteries_reporting_ac_needs_every_battery() {
+        let none = Supplies::new(&[]);
+        let detection = AcDetection::AllBatteriesReportingAc;
+        assert!(online(
+            detection,
+            &none,
+            &[battery("Charging"), battery("Full")]
+        ));
+        assert!(online(detection, &none, &[battery("Not charging")]));
+        assert!(!online(
+            detection,
+            &none,
+            &[battery("Charging"), battery("Discharging")]
+        ));
+        assert!(!online(detection, &none, &[battery("Unknown")]));
+        // Nothing reports AC without a battery
+        assert!(!online(detection, &none, &[]));
+    }
+
+    #[test]
+    fn battery_status_based_looks_for_discharging() {
+        let none = Supplies::new(&[]);
+        let detection = AcDetection::BatteryStatusBased;
+        assert!(online(detection, &none, &[battery("Unknown")]));
+        assert!(!online(
+            detection,
+            &none,
+            &[battery("Full"), battery("Discharging")]
+        ));
+        // Nothing discharges without a battery
+        assert!(online(detection, &none, &[]));
+    }
+
+    #[test]
+    fn ac_sources_override_the_detection() {
+        let supplies = Supplies::new(&[("ADP1", "Mains", 0), ("ucsi-source-psy-1", "USB_PD", 1)]);
+        let config = |sources: &[&str]| AppConfig {
+            ac_sources: Some(sources.iter().map(ToString::to_string).collect()),
+            ac_detection: AcDetection::BatteryStatusBased,
+            ..AppConfig::default()
+        };
+        let reading = detect_ac_power(&config(&["ADP1"]), &supplies.0, &[battery("Full")]);
+        assert!(!reading.online);
+        assert_eq!(reading.reading, "ADP1 offline");
+
+        let reading = detect_ac_power(
+            &config(&["ADP1", "ucsi-source-psy-1", "AC"]),
+            &supplies.0,
+            &[battery("Discharging")],
+        );
+        assert!(reading.online);
+        assert_eq!(
+            reading.reading,
+            "ADP1 offline, ucsi-source-psy-1 online, AC missing"
+        );
+    }
+
+    /// Usage to the whole percent, as the ratio of f32s is not exact
+    fn rounded(usage: Option<f32>) -> Option<f32> {
+        usage.map(f32::round)
+    }
+
+    const STAT_BEFORE: &str = "\
+cpu  300 0 150 1500 50 0 0 0 0 0
+cpu0 100 0 50 800 50 0 0 0 0 0
+cpu1 200 0 100 700 0 0 0 0 0 0
+intr 12345 0 0
+ctxt 67890
+";
+
+    // cpu1 went offline and came back, cpu2 came online
+    const STAT_AFTER: &str = "\
+cpu  210 0 110 900 150 0 0 0 0 0
+cpu0 150 0 100 850 150 0 0 0 0 0
+cpu1 10 0 5 20 0 0 0 0 0 0
+cpu2 50 0 5 25 0 0 0 0 0 0
+intr 23456 0 0
+";
+
+    #[test]
+    fn parses_each_cpu_of_proc_stat() {
+        let times = parse_cpu_times(STAT_BEFORE).unwrap();
+        let mut cpus: Vec<u32> = times.keys().copied().collect();
+        cpus.sort_unstable();
+        assert_eq!(cpus, [0, 1]);
+        assert_eq!(times[&0].fields(), [100, 0, 50, 800, 50, 0, 0, 0]);
+
+        assert!(matches!(
+            parse_cpu_times("cpu0 1 2 3\n"),
+            Err(SysMonitorError::ProcStatParseError(_))
+        ));
+    }
+
+    #[test]
+    fn usage_is_measured_between_samples() {
+        let before = parse_cpu_times(STAT_BEFORE).unwrap();
+        let after = parse_cpu_times(STAT_AFTER).unwrap();
+
+        let usage = usage_between(&before, &after, false);
+        // 250 ticks passed, 50 idle and 100 waiting for I/O
+        assert_eq!(rounded(usage[&0]), Some(40.0));
+        // Counters that went backwards start over
+        assert_eq!(usage[&1], None);
+        // As does a CPU without a first sample
+        assert_eq!(usage[&2], None);
+
+        let usage = usage_between(&before, &after, true);
+        assert_eq!(rounded(usage[&0]), Some(80.0));
+    }
+
+    #[test]
+    fn no_time_passed_is_no_usage() {
+        let times = parse_cpu_times(STAT_BEFORE).unwrap();
+        assert_eq!(times[&0].usage_since(&times[&0], false), None);
+        assert_eq!(usage_between(&times, &times, true)[&1], None);
+    }
+
+    #[test]
+    fn steal_counts_as_idle_unless_iowait_is_busy() {
+        let before = parse_cpu_times("cpu0 0 0 0 0 0 0 0 0 0 0\n").unwrap();
+        let after = parse_cpu_times("cpu0 30 0 10 40 0 0 0 20 0 0\n").unwrap();
+        assert_eq!(
+            rounded(after[&0].usage_since(&before[&0], false)),
+            Some(40.0)
+        );
+        assert_eq!(
+            rounded(after[&0].usage_since(&before[&0], true)),
+            Some(60.0)
+        );
+    }
+}
@@ -0,0 +1,261 @@
+// Desktop notifications for the user at the active graphical session, sent
+// over their session bus as the freedesktop notification spec describes
+//
+// Everything here is best effort: without logind, a graphical session or a
+// notification server, notifications are dropped with a debug message.
+use crate::config::{DaemonConfig, NotificationSettings};
+use crate::privileges;
+use log::debug;
+use std::collections::HashMap;
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+use zbus::zvariant::{OwnedObjectPath, Value};
+
+const LOGIND: &str = "org.freedesktop.login1";
+
+/// Session types with a display to show notifications on
+const GRAPHICAL_SESSIONS: &[&str] = &["x11", "wayland", "mir"];
+
+/// What a notification is about. Each kind is rate limited on its own, and a
+/// new notification replaces the previous one of its kind on screen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Kind {
+    ProfileSwitch,
+    /// Turbo was disabled because the CPU got too hot
+    Thermal,
+}
+
+impl Kind {
+    const fn icon(self) -> &'static str {
+        match self {
+            Self::ProfileSwitch => "dialog-information",
+            Self::Thermal => "dialog-warning",
+        }
+    }
+}
+
+struct Message {
+    kind: Kind,
+    summary: String,
+    body: String,
+    /// Least time since the last notification of this kind
+    min_interval: Duration,
+}
+
+/// Sends desktop notifications with `daemon.notifications`, from a thread of
+/// its own so a slow session bus never holds up a cycle
+pub struct Notifications {
+    /// `None` when disabled
+    settings: Option<NotificationSettings>,
+    /// Started with the first notification
+    sender: Option<mpsc::Sender<Message>>,
+}
+
+impl Notifications {
+    pub fn new(config: &DaemonConfig) -> Self {
+        Self {
+            settings: config.notifications.then(|| config.notify.clone()),
+            sender: None,
+        }
+    }
+
+    /// Follows a reloaded configuration
+    pub fn reconfigure(&mut self, config: &DaemonConfig) {
+        self.settings = config.notifications.then(|| config.notify.clone());
+    }
+
+    /// Sends a notification, unless its kind is turned off. Within
+    /// `min_interval_sec` of the last one of its kind, it is held back and
+    /// only sent if no newer one replaced it by then, so a profile flapping
+    /// back and forth shows up once, with where it ended up.
+    pub fn send(&mut self, kind: Kind, summary: &str, body: &str) {
+        let Some(settings) = &self.settings else {
+            return;
+        };
+        let wanted = match kind {
+            Kind::ProfileSwitch => settings.profile_switch,
+            Kind::Thermal => settings.thermal,
+        };
+        if !wanted {
+            return;
+        }
+
+        let message = Message {
+            kind,
+            summary: summary.to_string(),
+            body: body.to_string(),
+            min_interval: Duration::from_secs(settings.min_interval_sec),
+        };
+        let sender = match &self.sender {
+            Some(sender) => sender,
+            None => match spawn_sender() {
+                Ok(sender) => self.sender.insert(sender),
+                Err(e) => {
+                    debug!("Failed to start the notification thread: {e}");
+                    return;
+                }
+            },
+        };
+        if sender.send(message).is_err() {
+            // Started again with the next notification
+            self.sender = None;
+        }
+    }
+}
+
+fn spawn_sender() -> std::io::Result<mpsc::Sender<Message>> {
+    let (sender, messages) = mpsc::channel::<Message>();
+    thread::Builder::new()
+        .name("notifications".to_string())
+        .spawn(move || deliver(&messages))?;
+    Ok(sender)
+}
+
+/// Sends what arrives on `messages`, holding back each kind until its
+/// minimum interval has passed, until the daemon hangs up
+fn deliver(messages: &mpsc::Receiver<Message>) {
+    let mut session = SessionBus::default();
+    let mut last_sent: HashMap<Kind, Instant> = HashMap::new();
+    let mut pending: HashMap<Kind, Message> = HashMap::new();
+    // When a message may go out, `None` if right away
+    let due = |last_sent: &HashMap<Kind, Instant>, message: &Message| {
+        last_sent
+            .get(&message.kind)
+            .map(|last| *last + message.min_interval)
+    };
+
+    loop {
+        // `None` sorts first, so anything due right away wins
+        let next_due = pending
+            .values()
+            .map(|message| due(&last_sent, message))
+            .min();
+        let received = match next_due {
+            Some(next_due) => {
+                let wait = next_due.map_or(Duration::ZERO, |next_due| {
+                    next_due.saturating_duration_since(Instant::now())
+                });
+                match messages.recv_timeout(wait) {
+                    Ok(message) => Some(message),
+                    Err(mpsc::RecvTimeoutError::Timeout) => None,
+                    Err(mpsc::RecvTimeoutError::Disconnected) => return,
+                }
+            }
+            None => match messages.recv() {
+                Ok(message) => Some(message),
+                Err(mpsc::RecvError) => return,
+            },
+        };
+        if let Some(message) = received {
+            if let Some(replaced) = pending.insert(message.kind, message) {
+                debug!(
+                    "Notification '{}' was replaced before it was sent",
+                    replaced.summary
+                );
+            }
+        }
+
+        let now = Instant::now();
+        let ready: Vec<Kind> = pending
+            .values()
+            .filter(|message| due(&last_sent, message).is_none_or(|due| due <= now))
+            .map(|message| message.kind)
+            .collect();
+        for kind in ready {
+            let Some(message) = pending.remove(&kind) else {
+                continue;
+            };
+            last_sent.insert(kind, now);
+            if let Err(e) = session.notify(&message) {
+                debug!("Dropped notification '{}': {e}", message.summary);
+            }
+        }
+    }
+}
+
+/// The connection to the session bus of the user notified last
+#[derive(Default)]
+struct SessionBus {
+    system: Option<zbus::blocking::Connection>,
+    session: Option<(u32, zbus::blocking::Connection)>,
+    /// Notification on screen for each kind, to be replaced by the next one
+    shown: HashMap<Kind, u32>,
+}
+
+impl SessionBus {
+    fn notify(&mut self, message: &Message) -> zbus::Result<()> {
+        let uid = self.active_user()?;
+        if self.session.as_ref().is_none_or(|(user, _)| *user != uid) {
+            self.shown.clear();
+            let address = format!("unix:path=/run/user/{uid}/bus");
+            let connection = privileges::as_user(uid, || {
+                zbus::blocking::connection::Builder::address(address.as_str())?.build()
+            })
+            .map_err(|e| zbus::Error::Failure(format!("Cannot connect as uid {uid}: {e}")))??;
+            self.session = Some((uid, connection));
+        }
+        let Some((_, connection)) = &self.session else {
+            unreachable!("connected above");
+        };
+
+        let hints: HashMap<&str, Value<'_>> = HashMap::new();
+        let reply = connection.call_method(
+            Some("org.freedesktop.Notifications"),
+            "/org/freedesktop/Notifications",
+            Some("org.freedesktop.Notifications"),
+            "Notify",
+            &(
+                "Watt",
+                self.shown.get(&message.kind).copied().unwrap_or(0),
+                message.kind.icon(),
+                message.summary.as_str(),
+                message.body.as_str(),
+                Vec::<&str>::new(),
+                hints,
+                -1_i32,
+            ),
+        );
+        match reply {
+            Ok(reply) => {
+                let id: u32 = reply.body().deserialize()?;
+                self.shown.insert(message.kind, id);
+                Ok(())
+            }
+            Err(e) => {
+                // The session may have ended, so connect again next time
+                self.session = None;
+                Err(e)
+            }
+        }
+    }
+
+    /// The user of the active graphical session, as logind knows it
+    fn active_user(&mut self) -> zbus::Result<u32> {
+        let system = match &self.system {
+            Some(system) => system,
+            None => self.system.insert(zbus::blocking::Connection::system()?),
+        };
+        let manager = zbus::blocking::Proxy::new(
+            system,
+            LOGIND,
+            "/org/freedesktop/login1",
+            "org.freedesktop.login1.Manager",
+        )?;
+        let sessions: Vec<(String, u32, String, String, OwnedObjectPath)> =
+            manager.call("ListSessions", &())?;
+
+        for (_, uid, _, _, path) in sessions {
+            let session =
+                zbus::blocking::Proxy::new(system, LOGIND, path, "org.freedesktop.login1.Session")?;
+            let active: bool = session.get_property("Active")?;
+            let kind: String = session.get_property("Type")?;
+            if active && GRAPHICAL_SESSIONS.contains(&kind.as_str()) {
+                return Ok(uid);
+            }
+        }
+        Err(zbus::Error::Failure(
+            "no graphical session is active".to_string(),
+        ))
+    }
+}
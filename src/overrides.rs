@@ -0,0 +1,276 @@
+// Time-limited overrides of single profile settings, see `watt override`
+use crate::config::{AppConfig, ProfileConfig};
+use crate::core::TurboSetting;
+use clap::ValueEnum;
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Where active overrides are kept. /run is a tmpfs, so they are gone after a
+/// reboot, but outlive configuration reloads and daemon restarts.
+pub const OVERRIDES_PATH: &str = "/run/watt/overrides.json";
+
+/// A profile setting that can be overridden for a while
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum OverrideKnob {
+    Governor,
+    Epp,
+    Turbo,
+    MaxFreq,
+}
+
+impl fmt::Display for OverrideKnob {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Governor => "governor",
+            Self::Epp => "epp",
+            Self::Turbo => "turbo",
+            Self::MaxFreq => "max-freq",
+        })
+    }
+}
+
+/// The power source an override is limited to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum PowerScope {
+    Ac,
+    Battery,
+}
+
+impl PowerScope {
+    pub const fn matches(self, on_ac: bool) -> bool {
+        match self {
+            Self::Ac => on_ac,
+            Self::Battery => !on_ac,
+        }
+    }
+}
+
+impl fmt::Display for PowerScope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Ac => "ac",
+            Self::Battery => "battery",
+        })
+    }
+}
+
+/// A setting applied on top of the selected profile until `until`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TimedOverride {
+    pub knob: OverrideKnob,
+    /// Governor or EPP name, "on" or "off" for turbo, MHz for max-freq
+    pub value: String,
+    pub until: jiff::Timestamp,
+    /// Only applied while on this power source, always when missing
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope: Option<PowerScope>,
+    /// The boot clock at `until`, in milliseconds, so setting the wall clock
+    /// back does not bring an override back. The file is gone after a reboot,
+    /// so the boot clock it was taken from is still running.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub until_boot_ms: Option<u64>,
+}
+
+/// When an override set to last `duration` from now ends
+pub fn expiry_after(duration: jiff::SignedDuration) -> Result<jiff::Timestamp, String> {
+    if !duration.is_positive() {
+        return Err("Override duration must be positive".to_string());
+    }
+    jiff::Timestamp::now()
+        .checked_add(duration)
+        .and_then(|until| until.round(jiff::Unit::Second))
+        .map_err(|e| format!("Invalid override duration: {e}"))
+}
+
+impl TimedOverride {
+    /// Checks `value` for `knob` and sets the override to end at `until`
+    pub fn new(
+        knob: OverrideKnob,
+        value: &str,
+        until: jiff::Timestamp,
+        scope: Option<PowerScope>,
+    ) -> Result<Self, String> {
+        let left = until.duration_since(jiff::Timestamp::now());
+        if !left.is_positive() {
+            return Err(format!("Override expiry {until} has passed already"));
+        }
+        let value = value.trim();
+        let value = match knob {
+            OverrideKnob::Governor | OverrideKnob::Epp if value.is_empty() => {
+                return Err(format!("The {knob} override needs a value"));
+            }
+            OverrideKnob::Governor | OverrideKnob::Epp => value.to_string(),
+            OverrideKnob::Turbo => match value.to_ascii_lowercase().as_str() {
+                "on" | "always" => "on".to_string(),
+                "off" | "never" => "off".to_string(),
+                _ => return Err(format!("Unknown turbo value '{value}'. Expected on or off")),
+            },
+            OverrideKnob::MaxFreq => match value.parse::<u32>() {
+                Ok(mhz) if mhz > 0 => mhz.to_string(),
+                _ => {
+                    return Err(format!(
+                        "Invalid max-freq value '{value}'. Expected a frequency in MHz"
+                    ));
+                }
+            },
+        };
+        let until_boot_ms = boot_clock_ms()
+            .map(|now| now.saturating_add(u64::try_from(left.as_millis()).unwrap_or(u64::MAX)));
+
+        Ok(Self {
+            knob,
+            value,
+            until,
+            scope,
+            until_boot_ms,
+        })
+    }
+
+    /// Whether it applies while on AC power or not, as `on_ac` says
+    pub fn applies(&self, on_ac: bool) -> bool {
+        !self.is_expired() && self.scope.is_none_or(|scope| scope.matches(on_ac))
+    }
+
+    /// Whether `watt override clear` for `knob` and `scope` ends it. Missing
+    /// ones match any.
+    pub fn cleared_by(&self, knob: Option<OverrideKnob>, scope: Option<PowerScope>) -> bool {
+        knob.is_none_or(|knob| knob == self.knob)
+            && scope.is_none_or(|scope| self.scope == Some(scope))
+    }
+
+    /// " on ac" or " on battery" for scoped overrides, nothing otherwise
+    pub fn describe_scope(&self) -> String {
+        self.scope
+            .map(|scope| format!(" on {scope}"))
+            .unwrap_or_default()
+    }
+
+    /// Whether its time is up by the wall clock, or by the boot clock in case
+    /// the wall clock was set back since
+    pub fn is_expired(&self) -> bool {
+        self.until <= jiff::Timestamp::now()
+            || self
+                .until_boot_ms
+                .zip(boot_clock_ms())
+                .is_some_and(|(until, now)| until <= now)
+    }
+
+    /// Time left until it expires, rounded down to whole seconds
+    pub fn remaining(&self) -> jiff::SignedDuration {
+        let mut remaining = self.until.duration_since(jiff::Timestamp::now());
+        if let Some((until, now)) = self.until_boot_ms.zip(boot_clock_ms()) {
+            let by_boot_clock =
+                jiff::SignedDuration::from_millis(i64::try_from(until).unwrap_or(i64::MAX))
+                    - jiff::SignedDuration::from_millis(i64::try_from(now).unwrap_or(i64::MAX));
+            remaining = remaining.min(by_boot_clock);
+        }
+        jiff::SignedDuration::from_secs(remaining.as_secs().max(0))
+    }
+
+    pub fn turbo_setting(&self) -> Option<TurboSetting> {
+        match (self.knob, self.value.as_str()) {
+            (OverrideKnob::Turbo, "on") => Some(TurboSetting::Always),
+            (OverrideKnob::Turbo, "off") => Some(TurboSetting::Never),
+            _ => None,
+        }
+    }
+
+    fn apply_to(&self, profile: &mut ProfileConfig) {
+        match self.knob {
+            OverrideKnob::Governor => {
+                profile.governor = Some(self.value.clone());
+                // A fixed governor replaces the profile's auto mode
+                profile.governor_auto_settings = None;
+            }
+            OverrideKnob::Epp => profile.epp = Some(self.value.clone()),
+            OverrideKnob::Turbo => profile.turbo = self.turbo_setting(),
+            OverrideKnob::MaxFreq => profile.max_freq_mhz = self.value.parse().ok(),
+        }
+    }
+}
+
+/// CLOCK_BOOTTIME in milliseconds, which only moves forward and keeps counting
+/// during suspend
+fn boot_clock_ms() -> Option<u64> {
+    let mut ts = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    // SAFETY: `ts` is a valid, writable timespec
+    if unsafe { libc::clock_gettime(libc::CLOCK_BOOTTIME, &mut ts) } != 0 {
+        return None;
+    }
+    Some(u64::try_from(ts.tv_sec).ok()? * 1000 + u64::try_from(ts.tv_nsec).ok()? / 1_000_000)
+}
+
+/// Applies the overrides that have not expired and match the power source to
+/// every profile of `config`. One limited to the power source beats one that
+/// is not, as it comes later.
+pub fn apply(overrides: &[TimedOverride], config: &mut AppConfig, on_ac: bool) {
+    for entry in overrides.iter().filter(|entry| entry.applies(on_ac)) {
+        entry.apply_to(&mut config.charger);
+        entry.apply_to(&mut config.battery);
+        if let Some(balanced) = &mut config.balanced {
+            entry.apply_to(balanced);
+        }
+        for profile in config.profiles.values_mut() {
+            entry.apply_to(profile);
+        }
+    }
+}
+
+/// Adds `entry`, replacing any override of the same knob and power source
+pub fn insert(overrides: &mut Vec<TimedOverride>, entry: TimedOverride) {
+    overrides.retain(|existing| (existing.knob, existing.scope) != (entry.knob, entry.scope));
+    overrides.push(entry);
+    overrides.sort_by_key(|entry| (entry.knob, entry.scope));
+}
+
+/// Reads the overrides that have not expired from [`OVERRIDES_PATH`]. A
+/// missing or unreadable file means there are none.
+pub fn load() -> Vec<TimedOverride> {
+    let contents = match fs::read_to_string(OVERRIDES_PATH) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Vec::new(),
+        Err(e) => {
+            warn!("Failed to read {OVERRIDES_PATH}: {e}");
+            return Vec::new();
+        }
+    };
+    match serde_json::from_str::<Vec<TimedOverride>>(&contents) {
+        Ok(mut overrides) => {
+            overrides.retain(|entry| !entry.is_expired());
+            overrides
+        }
+        Err(e) => {
+            warn!("Ignoring malformed {OVERRIDES_PATH}: {e}");
+            Vec::new()
+        }
+    }
+}
+
+/// Writes `overrides` to [`OVERRIDES_PATH`], removing the file when there are
+/// none left
+pub fn save(overrides: &[TimedOverride]) -> io::Result<()> {
+    let path = Path::new(OVERRIDES_PATH);
+    if overrides.is_empty() {
+        return match fs::remove_file(path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        };
+    }
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    // Written aside and renamed, so readers never see half a file
+    let tmp_path = path.with_extension("json.tmp");
+    fs::write(&tmp_path, serde_json::to_vec_pretty(overrides)?)?;
+    fs::rename(&tmp_path, path)
+}
@@ -0,0 +1,249 @@
+// PID file handling and single-instance enforcement for the daemon
+use log::{debug, info, warn};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::os::unix::fs::MetadataExt;
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How long `--replace` waits for the previous instance to exit
+const REPLACE_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, thiserror::Error)]
+pub enum PidFileError {
+    #[error("I/O error on PID file {0}: {1}")]
+    Io(PathBuf, io::Error),
+
+    #[error("Another Watt daemon is already running (PID {0})")]
+    AlreadyRunning(String),
+
+    #[error("Previous Watt daemon (PID {0}) did not exit within {1:?}")]
+    ReplaceTimeout(String, Duration),
+}
+
+/// An exclusively locked PID file, held for the lifetime of the daemon.
+///
+/// The lock is an flock(2) on the open file, so the kernel releases it when a
+/// crashed instance exits and stale files never block startup.
+pub struct PidFile {
+    /// Kept open so the lock is held until drop
+    _file: File,
+    path: PathBuf,
+}
+
+impl PidFile {
+    /// Locks `path` and writes our PID into it. With `replace`, a running
+    /// instance holding the lock is sent SIGTERM and we wait for it to exit.
+    pub fn acquire(path: &Path, replace: bool) -> Result<Self, PidFileError> {
+        let io_err = |e| PidFileError::Io(path.to_path_buf(), e);
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(io_err)?;
+        }
+
+        // An instance that exits removes the file while we may be waiting on
+        // its lock, so the file we lock has to still be the one at `path`
+        let mut file = loop {
+            let mut file = OpenOptions::new()
+                .read(true)
+                .write(true)
+                .create(true)
+                .truncate(false)
+                .open(path)
+                .map_err(io_err)?;
+
+            if !try_lock(&file).map_err(io_err)? {
+                let holder = read_pid(&mut file).unwrap_or_else(|| "unknown".to_string());
+                if !replace {
+                    return Err(PidFileError::AlreadyRunning(holder));
+                }
+
+                terminate(&holder);
+                wait_for_lock(&file, &holder).map_err(|e| match e {
+                    WaitError::Io(e) => io_err(e),
+                    WaitError::Timeout => {
+                        PidFileError::ReplaceTimeout(holder.clone(), REPLACE_TIMEOUT)
+                    }
+                })?;
+                info!("Replaced previous daemon instance (PID {holder})");
+            }
+
+            if is_linked(&file, path).map_err(io_err)? {
+                break file;
+            }
+            debug!(
+                "PID file {} was removed while we locked it, retrying",
+                path.display()
+            );
+        };
+
+        file.set_len(0).map_err(io_err)?;
+        file.seek(SeekFrom::Start(0)).map_err(io_err)?;
+        writeln!(file, "{}", std::process::id()).map_err(io_err)?;
+        file.flush().map_err(io_err)?;
+
+        debug!("Acquired PID file lock at {}", path.display());
+        Ok(Self {
+            _file: file,
+            path: path.to_path_buf(),
+        })
+    }
+}
+
+impl Drop for PidFile {
+    fn drop(&mut self) {
+        // Remove the file while still holding the lock. An instance waiting
+        // on the lock sees the file is gone once it gets it, and starts over.
+        if let Err(e) = fs::remove_file(&self.path) {
+            debug!("Failed to remove PID file {}: {e}", self.path.display());
+        }
+    }
+}
+
+/// Returns the PID recorded in the file at `path` if a live daemon holds its lock
+pub fn running_daemon_pid(path: &Path) -> Option<String> {
+    let mut file = File::open(path).ok()?;
+    match try_lock(&file) {
+        // We got the lock, so nobody else is holding it
+        Ok(true) => None,
+        Ok(false) => read_pid(&mut file),
+        Err(_) => None,
+    }
+}
+
+/// Whether `file` is still the file at `path`, rather than one removed or
+/// replaced since it was opened
+fn is_linked(file: &File, path: &Path) -> io::Result<bool> {
+    let opened = file.metadata()?;
+    match fs::metadata(path) {
+        Ok(current) => Ok(opened.dev() == current.dev() && opened.ino() == current.ino()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+/// Attempts to take an exclusive, non-blocking flock
+fn try_lock(file: &File) -> io::Result<bool> {
+    // SAFETY: the descriptor is valid for the lifetime of `file`
+    let ret = unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) };
+    if ret == 0 {
+        return Ok(true);
+    }
+
+    let err = io::Error::last_os_error();
+    if err.raw_os_error() == Some(libc::EWOULDBLOCK) {
+        Ok(false)
+    } else {
+        Err(err)
+    }
+}
+
+fn read_pid(file: &mut File) -> Option<String> {
+    let mut contents = String::new();
+    file.seek(SeekFrom::Start(0)).ok()?;
+    file.read_to_string(&mut contents).ok()?;
+    let pid = contents.trim();
+    (!pid.is_empty()).then(|| pid.to_string())
+}
+
+/// Sends SIGTERM to the process with the given PID
+fn terminate(pid: &str) {
+    let Ok(pid) = pid.parse::<libc::pid_t>() else {
+        warn!("PID file holds an invalid PID '{pid}', waiting for the lock anyway");
+        return;
+    };
+
+    info!("Sending SIGTERM to running daemon (PID {pid})");
+    // SAFETY: kill(2) has no memory safety requirements
+    if unsafe { libc::kill(pid, libc::SIGTERM) } != 0 {
+        warn!("Failed to signal PID {pid}: {}", io::Error::last_os_error());
+    }
+}
+
+enum WaitError {
+    Io(io::Error),
+    Timeout,
+}
+
+fn wait_for_lock(file: &File, holder: &str) -> Result<(), WaitError> {
+    let start = Instant::now();
+    while start.elapsed() < REPLACE_TIMEOUT {
+        if try_lock(file).map_err(WaitError::Io)? {
+            return Ok(());
+        }
+        debug!("Waiting for daemon (PID {holder}) to release the lock");
+        thread::sleep(Duration::from_millis(100));
+    }
+    Err(WaitError::Timeout)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// A PID file path in a directory of its own, removed on drop
+    struct TempPath(PathBuf);
+
+    impl TempPath {
+        fn new() -> Self {
+            static NEXT: AtomicUsize = AtomicUsize::new(0);
+            let dir = std::env::temp_dir().join(format!(
+                "watt-pidfile-{}-{}",
+                std::process::id(),
+                NEXT.fetch_add(1, Ordering::Relaxed)
+            ));
+            Self(dir.join("watt.pid"))
+        }
+    }
+
+    impl Drop for TempPath {
+        fn drop(&mut self) {
+            if let Some(dir) = self.0.parent() {
+                let _ = fs::remove_dir_all(dir);
+            }
+        }
+    }
+
+    #[test]
+    fn second_instance_is_refused() {
+        let path = TempPath::new();
+        let _first = PidFile::acquire(&path.0, false).unwrap();
+        assert!(matches!(
+            PidFile::acquire(&path.0, false),
+            Err(PidFileError::AlreadyRunning(_))
+        ));
+    }
+
+    #[test]
+    fn waiting_instance_relocks_a_removed_file() {
+        let path = TempPath::new();
+        let first = PidFile::acquire(&path.0, false).unwrap();
+        // Not a PID, so the replacing instance does not signal this process
+        // and only waits for the lock
+        fs::write(&path.0, "none\n").unwrap();
+
+        let waiting = {
+            let path = path.0.clone();
+            thread::spawn(move || PidFile::acquire(&path, true))
+        };
+        thread::sleep(Duration::from_millis(300));
+        drop(first);
+        let second = waiting.join().unwrap().unwrap();
+
+        // The second instance holds the file at the path, so it is seen as
+        // running and a third one is refused
+        assert_eq!(
+            running_daemon_pid(&path.0),
+            Some(std::process::id().to_string())
+        );
+        assert!(matches!(
+            PidFile::acquire(&path.0, false),
+            Err(PidFileError::AlreadyRunning(_))
+        ));
+        drop(second);
+        assert!(!path.0.exists());
+    }
+}
@@ -0,0 +1,480 @@
+// Checking for and dropping root. Commands that write sysfs check for it up
+// front, and the daemon drops it once set up, for `daemon.run_as_user`.
+//
+// Everything that needs root to open is in place before this runs. Afterwards
+// the main thread keeps CAP_DAC_OVERRIDE for writing sysfs, passing it on to
+// the engine and monitor threads started from it, and every other thread keeps
+// nothing. With `daemon.chown_sysfs`, the managed files are handed
+// to the user's group first and no capability is kept at all.
+use crate::config::DaemonConfig;
+use crate::ipc::SOCKET_PATH;
+use crate::snapshot::{self, SNAPSHOT_PATH};
+use crate::util::error::AppError;
+use log::{debug, info, warn};
+use std::ffi::CString;
+use std::fs;
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::thread;
+
+/// Bypasses file permission checks, which is what writing root-owned sysfs
+/// files takes
+const CAP_DAC_OVERRIDE: u32 = 1;
+
+/// Capability ABI version with 64-bit capability sets
+const LINUX_CAPABILITY_VERSION_3: u32 = 0x2008_0522;
+
+#[repr(C)]
+struct CapUserHeader {
+    version: u32,
+    pid: libc::c_int,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+struct CapUserData {
+    effective: u32,
+    permitted: u32,
+    inheritable: u32,
+}
+
+/// The account the daemon switches to
+#[derive(Clone)]
+struct Account {
+    name: String,
+    uid: libc::uid_t,
+    gid: libc::gid_t,
+    groups: Vec<libc::gid_t>,
+}
+
+/// Which threads a credential change applies to
+#[derive(Clone, Copy)]
+enum Scope {
+    /// Every thread, through the libc wrappers
+    Process,
+    /// Only the calling thread, through the raw system calls
+    Thread,
+}
+
+/// Original owner and mode of a file handed to the daemon's user
+struct Ownership {
+    path: PathBuf,
+    uid: u32,
+    gid: u32,
+    mode: u32,
+}
+
+/// Whether this process may write the root-owned sysfs files: as root, or
+/// with `CAP_DAC_OVERRIDE`, e.g. granted by the service manager
+pub fn can_write_sysfs() -> bool {
+    // SAFETY: geteuid has no preconditions
+    if unsafe { libc::geteuid() } == 0 {
+        return true;
+    }
+    let effective = fs::read_to_string("/proc/self/status")
+        .ok()
+        .and_then(|status| {
+            let caps = status
+                .lines()
+                .find_map(|line| line.strip_prefix("CapEff:"))?;
+            u64::from_str_radix(caps.trim(), 16).ok()
+        })
+        .unwrap_or(0);
+    effective & (1 << CAP_DAC_OVERRIDE) != 0
+}
+
+/// Fails before anything is written unless [`can_write_sysfs`], naming
+/// `operation` in the message, e.g. "Applying the profile"
+pub fn require_sysfs_access(operation: &str) -> Result<(), AppError> {
+    if can_write_sysfs() {
+        return Ok(());
+    }
+    Err(AppError::Permission(format!(
+        "{operation} writes to sysfs, which needs root. Run it with sudo"
+    )))
+}
+
+/// Runs `f` with the calling thread's effective user and group switched to
+/// `uid` and its primary group, e.g. to connect to that user's session bus,
+/// which only lets its owner in. Other threads keep their credentials.
+///
+/// Needs root, unless the thread already runs as `uid`.
+pub fn as_user<T>(uid: libc::uid_t, f: impl FnOnce() -> T) -> io::Result<T> {
+    // SAFETY: geteuid and getegid have no preconditions
+    let (euid, egid) = unsafe { (libc::geteuid(), libc::getegid()) };
+    if euid == uid {
+        return Ok(f());
+    }
+    if euid != 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!("switching to uid {uid} needs root"),
+        ));
+    }
+    let gid = primary_group(uid)?;
+
+    let check = |ret: libc::c_long| {
+        if ret < 0 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(())
+        }
+    };
+    let keep: libc::c_long = -1;
+    // SAFETY: the calls only take plain integers. The raw system calls,
+    // unlike the libc wrappers, leave the other threads alone, and the real
+    // and saved ids stay root so the effective ones can be switched back.
+    unsafe {
+        check(libc::syscall(libc::SYS_setresgid, keep, gid, keep))?;
+        if let Err(e) = check(libc::syscall(libc::SYS_setresuid, keep, uid, keep)) {
+            let _ = check(libc::syscall(libc::SYS_setresgid, keep, egid, keep));
+            return Err(e);
+        }
+    }
+    let result = f();
+    // SAFETY: as above
+    unsafe {
+        if let Err(e) = check(libc::syscall(libc::SYS_setresuid, keep, euid, keep))
+            .and_then(|()| check(libc::syscall(libc::SYS_setresgid, keep, egid, keep)))
+        {
+            warn!("Failed to switch this thread back to uid {euid}: {e}");
+        }
+    }
+    Ok(result)
+}
+
+fn primary_group(uid: libc::uid_t) -> io::Result<libc::gid_t> {
+    // SAFETY: passwd is plain data, all-zeroes is a valid value
+    let mut passwd: libc::passwd = unsafe { std::mem::zeroed() };
+    let mut result: *mut libc::passwd = std::ptr::null_mut();
+    let mut buf = vec![0 as libc::c_char; 16 * 1024];
+
+    // SAFETY: every pointer refers to a live buffer of the stated size, and
+    // `passwd` is only read after getpwuid_r reported success
+    let ret = unsafe {
+        libc::getpwuid_r(
+            uid,
+            &raw mut passwd,
+            buf.as_mut_ptr(),
+            buf.len(),
+            &raw mut result,
+        )
+    };
+    if ret != 0 {
+        return Err(io::Error::from_raw_os_error(ret));
+    }
+    if result.is_null() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("no user has uid {uid}"),
+        ));
+    }
+    Ok(passwd.pw_gid)
+}
+
+/// Switches to `user`, keeping only what writing the managed sysfs files
+/// needs. Nothing is changed if a probe shows that writes would fail.
+pub fn drop_privileges(config: &DaemonConfig, user: &str, dry_run: bool) -> Result<(), String> {
+    let account = lookup_user(user)?;
+    let files: Vec<PathBuf> = snapshot::managed_paths()
+        .into_iter()
+        .filter(|path| path.exists())
+        .collect();
+    // A dry run changes nothing in sysfs, ownership included
+    let chown_sysfs = config.chown_sysfs && !dry_run;
+    let keep = if chown_sysfs {
+        0
+    } else {
+        1 << CAP_DAC_OVERRIDE
+    };
+
+    let mut handed = Vec::new();
+    let result = (|| {
+        if chown_sysfs {
+            hand_over(&account, &files, &mut handed)?;
+        }
+        probe(&account, keep, files, runtime_dirs(config))?;
+        switch_credentials(&account, keep, Scope::Process)
+            .map_err(|e| format!("Failed to switch to user {user}: {e}"))
+    })();
+
+    if let Err(e) = result {
+        // Staying root, so nobody else gets to write what was handed over
+        take_back(&handed);
+        return Err(e);
+    }
+
+    info!("Dropped privileges to user {user}: {}", credential_state());
+    Ok(())
+}
+
+fn lookup_user(name: &str) -> Result<Account, String> {
+    let c_name = CString::new(name).map_err(|_| format!("Invalid user name '{name}'"))?;
+
+    // SAFETY: passwd is plain data, all-zeroes is a valid value
+    let mut passwd: libc::passwd = unsafe { std::mem::zeroed() };
+    let mut result: *mut libc::passwd = std::ptr::null_mut();
+    let mut buf = vec![0 as libc::c_char; 16 * 1024];
+
+    // SAFETY: every pointer refers to a live buffer of the stated size, and
+    // `passwd` is only read after getpwnam_r reported success
+    let ret = unsafe {
+        libc::getpwnam_r(
+            c_name.as_ptr(),
+            &raw mut passwd,
+            buf.as_mut_ptr(),
+            buf.len(),
+            &raw mut result,
+        )
+    };
+    if ret != 0 {
+        return Err(format!(
+            "Failed to look up user {name}: {}",
+            io::Error::from_raw_os_error(ret)
+        ));
+    }
+    if result.is_null() {
+        return Err(format!("User {name} does not exist"));
+    }
+
+    // Supplementary groups, growing the buffer until they fit
+    let mut groups: Vec<libc::gid_t> = vec![0; 32];
+    loop {
+        let mut count = libc::c_int::try_from(groups.len()).unwrap_or(libc::c_int::MAX);
+        // SAFETY: `groups` has room for `count` entries, and getgrouplist
+        // updates `count` to the number it needs
+        let ret = unsafe {
+            libc::getgrouplist(
+                c_name.as_ptr(),
+                passwd.pw_gid,
+                groups.as_mut_ptr(),
+                &raw mut count,
+            )
+        };
+        let count = usize::try_from(count).unwrap_or(0);
+        if ret >= 0 {
+            groups.truncate(count);
+            break;
+        }
+        groups.resize(count.max(groups.len() * 2), 0);
+    }
+
+    Ok(Account {
+        name: name.to_string(),
+        uid: passwd.pw_uid,
+        gid: passwd.pw_gid,
+        groups,
+    })
+}
+
+/// Gives the user's group write access to the managed files, and the user
+/// the runtime directory. What was changed is added to `handed`.
+fn hand_over(
+    account: &Account,
+    files: &[PathBuf],
+    handed: &mut Vec<Ownership>,
+) -> Result<(), String> {
+    let mut hand = |path: &Path, uid: Option<u32>, gid: u32, mode_bits: u32| {
+        let metadata = fs::metadata(path)?;
+        handed.push(Ownership {
+            path: path.to_path_buf(),
+            uid: metadata.uid(),
+            gid: metadata.gid(),
+            mode: metadata.mode(),
+        });
+        std::os::unix::fs::chown(path, uid, Some(gid))?;
+        fs::set_permissions(
+            path,
+            fs::Permissions::from_mode(metadata.mode() | mode_bits),
+        )
+    };
+
+    for file in files {
+        hand(file, None, account.gid, 0o020)
+            .map_err(|e| format!("Failed to hand {} to {}: {e}", file.display(), account.name))?;
+    }
+    debug!(
+        "Handed {} sysfs files to group {}",
+        files.len(),
+        account.gid
+    );
+
+    // Only the directory Watt owns is handed over. Others configured for the
+    // PID, stats or log file have to be writable already.
+    if let Some(dir) = Path::new(SOCKET_PATH).parent() {
+        hand(dir, Some(account.uid), account.gid, 0)
+            .map_err(|e| format!("Failed to hand {} to {}: {e}", dir.display(), account.name))?;
+    }
+    Ok(())
+}
+
+/// Puts back the owners and modes changed by [`hand_over`]
+fn take_back(handed: &[Ownership]) {
+    for original in handed {
+        let result =
+            std::os::unix::fs::chown(&original.path, Some(original.uid), Some(original.gid))
+                .and_then(|()| {
+                    fs::set_permissions(&original.path, fs::Permissions::from_mode(original.mode))
+                });
+        if let Err(e) = result {
+            warn!(
+                "Failed to restore owner of {}: {e}",
+                original.path.display()
+            );
+        }
+    }
+}
+
+/// Directories the daemon creates, replaces or removes files in while running
+fn runtime_dirs(config: &DaemonConfig) -> Vec<PathBuf> {
+    let mut files = vec![
+        PathBuf::from(&config.pid_file),
+        PathBuf::from(&config.turbo_state_file),
+        PathBuf::from(&config.state_file_path),
+        PathBuf::from(&config.history_file),
+        PathBuf::from(SNAPSHOT_PATH),
+        PathBuf::from(SOCKET_PATH),
+    ];
+    files.extend(config.stats_file_path.iter().map(PathBuf::from));
+    files.extend(config.log_file.iter().map(PathBuf::from));
+
+    let mut dirs: Vec<PathBuf> = files
+        .iter()
+        .filter_map(|file| file.parent())
+        // Missing directories get created, which takes the nearest existing one
+        .filter_map(|dir| dir.ancestors().find(|dir| dir.exists()))
+        .map(Path::to_path_buf)
+        .collect();
+    dirs.sort();
+    dirs.dedup();
+    dirs
+}
+
+/// Checks from a throwaway thread running as `account` that every file can be
+/// opened for writing and every directory written to. Nothing is written.
+fn probe(
+    account: &Account,
+    keep: u32,
+    files: Vec<PathBuf>,
+    dirs: Vec<PathBuf>,
+) -> Result<(), String> {
+    let account = account.clone();
+    thread::Builder::new()
+        .name("privilege-probe".to_string())
+        .spawn(move || -> Result<(), String> {
+            // The changed credentials die with this thread
+            switch_credentials(&account, keep, Scope::Thread)
+                .map_err(|e| format!("Failed to switch to user {}: {e}", account.name))?;
+
+            for file in &files {
+                fs::OpenOptions::new()
+                    .write(true)
+                    .open(file)
+                    .map_err(|e| format!("{} would not be writable: {e}", file.display()))?;
+            }
+            for dir in &dirs {
+                if !is_writable_dir(dir) {
+                    return Err(format!(
+                        "{} would not be writable: {}",
+                        dir.display(),
+                        io::Error::last_os_error()
+                    ));
+                }
+            }
+            Ok(())
+        })
+        .map_err(|e| format!("Failed to start privilege probe: {e}"))?
+        .join()
+        .map_err(|_| "Privilege probe panicked".to_string())?
+}
+
+fn is_writable_dir(dir: &Path) -> bool {
+    let Ok(c_dir) = CString::new(dir.as_os_str().as_bytes()) else {
+        return false;
+    };
+    // SAFETY: `c_dir` is a valid NUL-terminated path
+    unsafe {
+        libc::faccessat(
+            libc::AT_FDCWD,
+            c_dir.as_ptr(),
+            libc::W_OK | libc::X_OK,
+            libc::AT_EACCESS,
+        ) == 0
+    }
+}
+
+/// Switches user, group and supplementary groups, keeping the capabilities in
+/// `keep` on the calling thread only
+fn switch_credentials(account: &Account, keep: u32, scope: Scope) -> io::Result<()> {
+    let check = |ret: libc::c_long| {
+        if ret < 0 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(())
+        }
+    };
+    let (uid, gid) = (account.uid, account.gid);
+
+    // SAFETY: the group list is valid for its length, and the remaining calls
+    // only take plain integers
+    unsafe {
+        // Keep the permitted set across the uid change so `keep` can be
+        // restored from it below
+        check(libc::prctl(libc::PR_SET_KEEPCAPS, libc::c_ulong::from(keep != 0)).into())?;
+
+        match scope {
+            Scope::Process => {
+                check(libc::setgroups(account.groups.len(), account.groups.as_ptr()).into())?;
+                check(libc::setresgid(gid, gid, gid).into())?;
+                check(libc::setresuid(uid, uid, uid).into())?;
+            }
+            Scope::Thread => {
+                check(libc::syscall(
+                    libc::SYS_setgroups,
+                    account.groups.len(),
+                    account.groups.as_ptr(),
+                ))?;
+                check(libc::syscall(libc::SYS_setresgid, gid, gid, gid))?;
+                check(libc::syscall(libc::SYS_setresuid, uid, uid, uid))?;
+            }
+        }
+    }
+
+    if keep != 0 {
+        let header = CapUserHeader {
+            version: LINUX_CAPABILITY_VERSION_3,
+            pid: 0,
+        };
+        let data = [
+            CapUserData {
+                effective: keep,
+                permitted: keep,
+                inheritable: 0,
+            },
+            CapUserData::default(),
+        ];
+        // SAFETY: the header and both data entries match the version 3 ABI
+        check(unsafe { libc::syscall(libc::SYS_capset, &raw const header, data.as_ptr()) })?;
+    }
+    Ok(())
+}
+
+/// The process's credentials as the kernel reports them
+fn credential_state() -> String {
+    let Ok(status) = fs::read_to_string("/proc/self/status") else {
+        return "credentials unavailable".to_string();
+    };
+
+    status
+        .lines()
+        .filter(|line| {
+            ["Uid:", "Gid:", "Groups:", "CapPrm:", "CapEff:"]
+                .iter()
+                .any(|key| line.starts_with(key))
+        })
+        .map(|line| line.split_whitespace().collect::<Vec<_>>().join(" "))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
@@ -0,0 +1,143 @@
+// Forcing the performance profile while games or compilers run, see
+// `[process_boost]`
+//
+// Processes are found by scanning /proc every cycle, so a boost ends on the
+// first cycle after the last matching process exited.
+use crate::config::ProcessBoostSettings;
+use log::{debug, info};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// A boost in progress, as `watt status` shows it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BoostStatus {
+    /// The matching process that started it
+    pub process: String,
+    pub since: jiff::Timestamp,
+    /// Whether turbo is kept on meanwhile
+    pub turbo: bool,
+}
+
+/// Whether the performance profile is forced for a running process, carried
+/// from cycle to cycle
+#[derive(Debug, Default)]
+pub struct ProcessBoost {
+    active: Option<BoostStatus>,
+    /// When the last boost ended, for the cooldown
+    ended_at: Option<Instant>,
+}
+
+impl ProcessBoost {
+    /// Scans for matching processes and starts or ends the boost. Returns the
+    /// boost in progress, if any.
+    pub fn update(&mut self, settings: Option<&ProcessBoostSettings>) -> Option<&BoostStatus> {
+        let matched = settings
+            .filter(|settings| !settings.processes.is_empty())
+            .and_then(|settings| find_process(&settings.processes));
+
+        match (&self.active, matched, settings) {
+            (Some(active), None, _) => {
+                info!(
+                    "Process boost for {} ended, back to automatic selection",
+                    active.process
+                );
+                self.active = None;
+                self.ended_at = Some(Instant::now());
+            }
+            (None, Some(process), Some(settings)) => {
+                let cooldown = Duration::from_secs(settings.cooldown_sec);
+                if self
+                    .ended_at
+                    .is_some_and(|ended_at| ended_at.elapsed() < cooldown)
+                {
+                    debug!("{process} is running, but the process boost is cooling down");
+                } else {
+                    info!("{process} is running, forcing the performance profile");
+                    self.active = Some(BoostStatus {
+                        process,
+                        since: jiff::Timestamp::now(),
+                        turbo: settings.turbo,
+                    });
+                }
+            }
+            _ => {}
+        }
+        self.active.as_ref()
+    }
+}
+
+/// The name of the first running process matching one of `patterns`
+fn find_process(patterns: &[String]) -> Option<String> {
+    let entries = match fs::read_dir("/proc") {
+        Ok(entries) => entries,
+        Err(e) => {
+            debug!("Cannot list processes for the process boost: {e}");
+            return None;
+        }
+    };
+    entries.flatten().find_map(|entry| {
+        let name = entry.file_name();
+        let name = name.to_str()?;
+        if !name.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        process_names(&entry.path())
+            .into_iter()
+            .find(|name| patterns.iter().any(|pattern| glob_match(pattern, name)))
+    })
+}
+
+/// The command name, cut to 15 characters by the kernel, and the file name of
+/// the executable the process was started as. Wine reports Windows paths for
+/// the latter, hence the backslashes.
+fn process_names(pid_path: &Path) -> Vec<String> {
+    let mut names = Vec::with_capacity(2);
+    if let Ok(comm) = fs::read_to_string(pid_path.join("comm")) {
+        names.push(comm.trim_end().to_string());
+    }
+    if let Ok(cmdline) = fs::read(pid_path.join("cmdline")) {
+        let argv0 = cmdline.split(|&b| b == 0).next().unwrap_or_default();
+        let argv0 = String::from_utf8_lossy(argv0);
+        if let Some(file_name) = argv0.rsplit(['/', '\\']).next() {
+            if !file_name.is_empty() && !names.iter().any(|name| name == file_name) {
+                names.push(file_name.to_string());
+            }
+        }
+    }
+    names
+}
+
+/// Matches `name` against `pattern`, where `*` stands for any run of
+/// characters and `?` for a single one, ignoring case
+fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+    let name: Vec<char> = name.to_lowercase().chars().collect();
+
+    // Where the last `*` was and the part of the name it covers so far, to
+    // backtrack to when the rest does not match
+    let (mut p, mut n) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                Some((star_p, star_n)) => {
+                    p = star_p + 1;
+                    n = star_n + 1;
+                    star = Some((star_p, star_n + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
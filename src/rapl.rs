@@ -0,0 +1,158 @@
+//! CPU power draw from the RAPL energy counters in powercap
+//!
+//! Intel CPUs, and AMD ones since Zen, count the energy of the package and
+//! its domains in `/sys/class/powercap/intel-rapl:*`. The power is the energy
+//! drawn between two reads over the time between them, so the first read
+//! samples over a short interval. The counters are only readable by root.
+
+use crate::core::CpuPowerInfo;
+use log::debug;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock, PoisonError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const POWERCAP_PATH: &str = "/sys/class/powercap";
+
+/// How long the first read samples over, as there is no previous one
+const FIRST_SAMPLE_INTERVAL: Duration = Duration::from_millis(250);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Domain {
+    Package,
+    Cores,
+    Uncore,
+}
+
+/// One powercap zone with an energy counter
+#[derive(Debug)]
+struct Zone {
+    domain: Domain,
+    energy: PathBuf,
+    /// Where the counter wraps to 0, in µJ
+    max_energy: u64,
+}
+
+/// The energy counters of the previous read, power is measured from
+struct Reading {
+    at: Instant,
+    energies: Vec<Option<u64>>,
+}
+
+static PREVIOUS_READING: Mutex<Option<Reading>> = Mutex::new(None);
+
+/// The zones found once, as they do not change until reboot
+fn zones() -> &'static [Zone] {
+    static ZONES: OnceLock<Vec<Zone>> = OnceLock::new();
+    ZONES.get_or_init(|| {
+        let zones = find_zones(Path::new(POWERCAP_PATH));
+        if zones.is_empty() {
+            debug!("No readable RAPL energy counters in {POWERCAP_PATH}, CPU power is unavailable");
+        }
+        zones
+    })
+}
+
+/// The package zones and their subzones. `intel-rapl-mmio` repeats the
+/// package of `intel-rapl` and is left out, as is `psys`, which covers more
+/// than the CPU.
+fn find_zones(powercap: &Path) -> Vec<Zone> {
+    let Ok(entries) = fs::read_dir(powercap) else {
+        return Vec::new();
+    };
+    let mut zones: Vec<Zone> = entries
+        .flatten()
+        .filter(|entry| {
+            entry
+                .file_name()
+                .to_str()
+                .is_some_and(|name| name.starts_with("intel-rapl:"))
+        })
+        .filter_map(|entry| {
+            let path = entry.path();
+            let name = fs::read_to_string(path.join("name")).ok()?;
+            let domain = match name.trim() {
+                name if name.starts_with("package-") => Domain::Package,
+                "core" => Domain::Cores,
+                "uncore" => Domain::Uncore,
+                _ => return None,
+            };
+            let energy = path.join("energy_uj");
+            read_counter(&energy)?;
+            Some(Zone {
+                domain,
+                max_energy: read_counter(&path.join("max_energy_range_uj")).unwrap_or(0),
+                energy,
+            })
+        })
+        .collect();
+    zones.sort_by(|a, b| a.energy.cmp(&b.energy));
+    zones
+}
+
+fn read_counter(path: &Path) -> Option<u64> {
+    fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+/// Energy drawn from `before` to `now`, in µJ, across one wrap of the counter
+fn energy_since(before: u64, now: u64, max_energy: u64) -> Option<u64> {
+    if now >= before {
+        Some(now - before)
+    } else {
+        (max_energy >= before).then(|| max_energy - before + now)
+    }
+}
+
+/// Power per domain since the previous call, summed over the packages. All
+/// fields are `None` without RAPL counters.
+pub fn cpu_power() -> CpuPowerInfo {
+    let zones = zones();
+    if zones.is_empty() {
+        return CpuPowerInfo::default();
+    }
+    let read = || Reading {
+        at: Instant::now(),
+        energies: zones
+            .iter()
+            .map(|zone| read_counter(&zone.energy))
+            .collect(),
+    };
+
+    let mut previous = PREVIOUS_READING
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner);
+    let baseline = match previous.take() {
+        Some(baseline) => baseline,
+        None => {
+            let baseline = read();
+            thread::sleep(FIRST_SAMPLE_INTERVAL);
+            baseline
+        }
+    };
+    let current = read();
+    let seconds = current.at.duration_since(baseline.at).as_secs_f64();
+
+    let watts = |domain: Domain| -> Option<f32> {
+        if seconds <= 0.0 {
+            return None;
+        }
+        zones
+            .iter()
+            .zip(baseline.energies.iter().zip(&current.energies))
+            .filter(|(zone, _)| zone.domain == domain)
+            .map(|(zone, (before, now))| energy_since((*before)?, (*now)?, zone.max_energy))
+            .try_fold(None, |total: Option<u64>, energy| {
+                Some(Some(total.unwrap_or(0) + energy?))
+            })
+            .flatten()
+            .map(|energy| (energy as f64 / 1_000_000.0 / seconds) as f32)
+    };
+    let info = CpuPowerInfo {
+        package_watts: watts(Domain::Package),
+        cores_watts: watts(Domain::Cores),
+        uncore_watts: watts(Domain::Uncore),
+    };
+    *previous = Some(current);
+    info
+}
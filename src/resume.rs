@@ -0,0 +1,72 @@
+// Detection of resume from suspend, so settings reset by firmware get reapplied
+use crate::daemon::DaemonState;
+use log::{debug, warn};
+use std::fs;
+use std::sync::Arc;
+use std::thread;
+
+/// Number of successful suspends since boot, used when logind is unavailable
+const SUSPEND_SUCCESS_PATH: &str = "/sys/power/suspend_stats/success";
+
+/// Subscribes to logind's `PrepareForSleep` signal on a background thread,
+/// marking a resume in `state` whenever the system wakes up
+pub fn watch_logind(state: Arc<DaemonState>) -> zbus::Result<()> {
+    let connection = zbus::blocking::Connection::system()?;
+    let proxy = zbus::blocking::Proxy::new(
+        &connection,
+        "org.freedesktop.login1",
+        "/org/freedesktop/login1",
+        "org.freedesktop.login1.Manager",
+    )?;
+    let signals = proxy.receive_signal("PrepareForSleep")?;
+
+    thread::Builder::new()
+        .name("resume".to_string())
+        .spawn(move || {
+            // Keep the connection alive for as long as we listen
+            let _connection = connection;
+            for message in signals {
+                match message.body().deserialize::<bool>() {
+                    // `false` means the system has just woken up
+                    Ok(false) => state.notify_resumed(),
+                    Ok(true) => debug!("System is about to suspend"),
+                    Err(e) => warn!("Invalid PrepareForSleep signal: {e}"),
+                }
+            }
+            debug!("Stopped listening for PrepareForSleep signals");
+        })
+        .map_err(|e| zbus::Error::Failure(format!("Failed to spawn resume watcher: {e}")))?;
+
+    Ok(())
+}
+
+/// Detects resumes by watching the kernel's suspend counter between cycles
+pub struct SuspendCounter {
+    last: u64,
+}
+
+impl SuspendCounter {
+    /// Returns `None` if the kernel does not expose suspend statistics
+    pub fn new() -> Option<Self> {
+        read_success_count().map(|last| Self { last })
+    }
+
+    /// Whether the system suspended and resumed since the last call
+    pub fn resumed(&mut self) -> bool {
+        let Some(current) = read_success_count() else {
+            return false;
+        };
+
+        let resumed = current != self.last;
+        self.last = current;
+        resumed
+    }
+}
+
+fn read_success_count() -> Option<u64> {
+    fs::read_to_string(SUSPEND_SUCCESS_PATH)
+        .ok()?
+        .trim()
+        .parse()
+        .ok()
+}
@@ -0,0 +1,379 @@
+//! Which sensor the CPU temperature is read from
+//!
+//! Picked once, as hwmon devices keep their numbers until reboot, and again
+//! when `temperature_sensor` changes. Known CPU drivers come first, with their
+//! package or control temperature, then the CPU thermal zones, so a chipset
+//! or drive sensor never stands in for the CPU.
+//!
+//! Drivers that measure single cores or CCDs are read for the cores as well,
+//! mapped to logical CPUs through the topology.
+
+use crate::topology;
+use log::{debug, info, warn};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Mutex, OnceLock, PoisonError};
+
+const HWMON_PATH: &str = "/sys/class/hwmon";
+const CPU_PATH: &str = "/sys/devices/system/cpu";
+const THERMAL_PATH: &str = "/sys/class/thermal";
+
+/// CPU temperature drivers in order of preference, with the labels of their
+/// package or control temperature, best first
+const CPU_DRIVERS: &[(&str, &[&str])] = &[
+    ("coretemp", &["Package id 0"]),
+    ("k10temp", &["Tctl", "Tdie"]),
+    ("zenpower", &["Tctl", "Tdie"]),
+    ("thinkpad", &["CPU"]),
+];
+
+/// Drivers reporting one temperature per CCD, labeled `Tccd1` and up
+const CCD_DRIVERS: &[&str] = &["k10temp", "zenpower"];
+
+/// Thermal zone types that measure the CPU package
+const CPU_THERMAL_ZONES: &[&str] = &["x86_pkg_temp", "cpu-thermal", "cpu_thermal"];
+
+/// A sensor as `temperature_sensor` names it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SensorSpec {
+    /// `hwmon:<driver>` or `hwmon:<driver>/<label>`
+    Hwmon {
+        driver: String,
+        label: Option<String>,
+    },
+    /// A `temp*_input` file, or a thermal zone directory
+    Path(PathBuf),
+}
+
+impl FromStr for SensorSpec {
+    type Err = String;
+
+    fn from_str(spec: &str) -> Result<Self, Self::Err> {
+        if let Some(sensor) = spec.strip_prefix("hwmon:") {
+            let (driver, label) = match sensor.split_once('/') {
+                Some((driver, label)) => (driver, Some(label.to_string())),
+                None => (sensor, None),
+            };
+            if driver.is_empty() || label.as_deref() == Some("") {
+                return Err(format!(
+                    "'{spec}' should read hwmon:<driver> or hwmon:<driver>/<label>"
+                ));
+            }
+            Ok(Self::Hwmon {
+                driver: driver.to_string(),
+                label,
+            })
+        } else if spec.starts_with('/') {
+            Ok(Self::Path(PathBuf::from(spec)))
+        } else {
+            Err(format!(
+                "'{spec}' is neither hwmon:<driver>/<label> nor an absolute sysfs path"
+            ))
+        }
+    }
+}
+
+/// The sensor the CPU temperature is read from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TemperatureSensor {
+    /// e.g. `hwmon:k10temp/Tctl` or `thermal:x86_pkg_temp`
+    pub name: String,
+    /// The file holding the temperature in millidegrees
+    pub input: PathBuf,
+}
+
+impl TemperatureSensor {
+    /// The current temperature, in °C
+    pub fn read(&self) -> Option<f32> {
+        read_millidegrees(&self.input)
+    }
+}
+
+/// The last selection, with the `temperature_sensor` it was made for
+static SELECTED: Mutex<Option<(Option<String>, Option<TemperatureSensor>)>> = Mutex::new(None);
+
+/// The CPU temperature sensor, per `wanted` if set and found, or else the
+/// best known one. Logs the choice whenever it is made.
+pub fn cpu_sensor(wanted: Option<&str>) -> Option<TemperatureSensor> {
+    let mut selected = SELECTED.lock().unwrap_or_else(PoisonError::into_inner);
+    if let Some((_, sensor)) = selected
+        .as_ref()
+        .filter(|(previous, _)| previous.as_deref() == wanted)
+    {
+        return sensor.clone();
+    }
+    let sensor = select(wanted);
+    match &sensor {
+        Some(sensor) => info!(
+            event = "temperature_sensor",
+            sensor = sensor.name.as_str(),
+            path = sensor.input.display().to_string().as_str();
+            "Using {} ({}) for the CPU temperature",
+            sensor.name,
+            sensor.input.display()
+        ),
+        None => info!(
+            event = "temperature_sensor";
+            "No CPU temperature sensor found, falling back to the per-core readings"
+        ),
+    }
+    *selected = Some((wanted.map(String::from), sensor.clone()));
+    sensor
+}
+
+fn select(wanted: Option<&str>) -> Option<TemperatureSensor> {
+    if let Some(wanted) = wanted {
+        match wanted.parse::<SensorSpec>().map(|spec| find(&spec)) {
+            Ok(Some(sensor)) => return Some(sensor),
+            Ok(None) => {
+                warn!("temperature_sensor '{wanted}' was not found, picking one automatically");
+            }
+            Err(e) => warn!("temperature_sensor: {e}, picking one automatically"),
+        }
+    }
+    CPU_DRIVERS
+        .iter()
+        .find_map(|(driver, labels)| hwmon_sensor(driver, labels, true))
+        .or_else(thermal_zone_sensor)
+}
+
+/// The sensor `spec` names, if it is there and readable
+fn find(spec: &SensorSpec) -> Option<TemperatureSensor> {
+    match spec {
+        SensorSpec::Hwmon { driver, label } => {
+            if let Some(label) = label {
+                return hwmon_sensor(driver, &[label.as_str()], false);
+            }
+            // The labels preferred for a known driver, or else its first input
+            let labels = CPU_DRIVERS
+                .iter()
+                .find(|(known, _)| known == driver)
+                .map_or(&[][..], |(_, labels)| *labels);
+            hwmon_sensor(driver, labels, true)
+        }
+        SensorSpec::Path(path) => {
+            let input = if path.is_dir() {
+                path.join("temp")
+            } else {
+                path.clone()
+            };
+            read_millidegrees(&input)?;
+            Some(TemperatureSensor {
+                name: path.display().to_string(),
+                input,
+            })
+        }
+    }
+}
+
+/// The first of `labels` a `driver` hwmon device reports, or else its first
+/// temperature input if `any_input` is set
+fn hwmon_sensor(driver: &str, labels: &[&str], any_input: bool) -> Option<TemperatureSensor> {
+    // The first package on multi-socket machines
+    let devices = hwmon_devices(driver);
+    let device = devices.first()?;
+
+    let inputs = temperature_inputs(device);
+    let labeled = labels.iter().find_map(|wanted| {
+        inputs.iter().find(|(label, _)| {
+            label
+                .as_deref()
+                .is_some_and(|label| label.eq_ignore_ascii_case(wanted))
+        })
+    });
+    let (label, input) = labeled.or_else(|| inputs.first().filter(|_| any_input))?;
+    read_millidegrees(input)?;
+    Some(TemperatureSensor {
+        name: match label {
+            Some(label) => format!("hwmon:{driver}/{label}"),
+            None => format!("hwmon:{driver}"),
+        },
+        input: input.clone(),
+    })
+}
+
+/// The `temp*_input` files of a hwmon device with their labels, in index
+/// order
+fn temperature_inputs(device: &Path) -> Vec<(Option<String>, PathBuf)> {
+    let Ok(entries) = fs::read_dir(device) else {
+        return Vec::new();
+    };
+    let mut inputs: Vec<(u32, Option<String>, PathBuf)> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            let index = name
+                .strip_prefix("temp")?
+                .strip_suffix("_input")?
+                .parse()
+                .ok()?;
+            let label = fs::read_to_string(device.join(format!("temp{index}_label")))
+                .ok()
+                .map(|label| label.trim().to_string());
+            Some((index, label, entry.path()))
+        })
+        .collect();
+    inputs.sort_by_key(|(index, ..)| *index);
+    inputs
+        .into_iter()
+        .map(|(_, label, input)| (label, input))
+        .collect()
+}
+
+/// The first thermal zone measuring the CPU package
+fn thermal_zone_sensor() -> Option<TemperatureSensor> {
+    let mut zones: Vec<(String, PathBuf)> = fs::read_dir(THERMAL_PATH)
+        .ok()?
+        .flatten()
+        .filter(|entry| {
+            entry
+                .file_name()
+                .to_string_lossy()
+                .starts_with("thermal_zone")
+        })
+        .filter_map(|entry| {
+            let zone_type = fs::read_to_string(entry.path().join("type")).ok()?;
+            Some((zone_type.trim().to_string(), entry.path()))
+        })
+        .collect();
+    zones.sort_by(|a, b| a.1.cmp(&b.1));
+    CPU_THERMAL_ZONES.iter().find_map(|wanted| {
+        let (zone_type, path) = zones.iter().find(|(zone_type, _)| zone_type == wanted)?;
+        let input = path.join("temp");
+        read_millidegrees(&input)?;
+        Some(TemperatureSensor {
+            name: format!("thermal:{zone_type}"),
+            input,
+        })
+    })
+}
+
+/// The temperature of each logical CPU a per-core or per-CCD sensor covers.
+/// Empty on machines without such sensors.
+pub fn core_temperatures() -> BTreeMap<u32, f32> {
+    static INPUTS: OnceLock<BTreeMap<u32, PathBuf>> = OnceLock::new();
+    let inputs = INPUTS.get_or_init(|| {
+        let inputs = coretemp_inputs();
+        let inputs = if inputs.is_empty() {
+            ccd_inputs()
+        } else {
+            inputs
+        };
+        debug!("Per-core temperatures available for {} CPUs", inputs.len());
+        inputs
+    });
+    inputs
+        .iter()
+        .filter_map(|(cpu, input)| Some((*cpu, read_millidegrees(input)?)))
+        .collect()
+}
+
+/// hwmon devices of `driver`, in hwmon order
+fn hwmon_devices(driver: &str) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(HWMON_PATH) else {
+        return Vec::new();
+    };
+    let mut devices: Vec<PathBuf> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            fs::read_to_string(path.join("name")).is_ok_and(|name| name.trim() == driver)
+        })
+        .collect();
+    devices.sort();
+    devices
+}
+
+/// coretemp's `Core N` inputs, one device per package, where N is the
+/// physical core that the CPU's `topology/core_id` names
+fn coretemp_inputs() -> BTreeMap<u32, PathBuf> {
+    let mut by_core: BTreeMap<(u32, u32), PathBuf> = BTreeMap::new();
+    for device in hwmon_devices("coretemp") {
+        let inputs = temperature_inputs(&device);
+        let package = inputs
+            .iter()
+            .find_map(|(label, _)| label.as_deref()?.strip_prefix("Package id ")?.parse().ok())
+            .unwrap_or(0);
+        for (label, input) in inputs {
+            if let Some(core) = label
+                .as_deref()
+                .and_then(|label| label.strip_prefix("Core ")?.parse().ok())
+            {
+                by_core.insert((package, core), input);
+            }
+        }
+    }
+    if by_core.is_empty() {
+        return BTreeMap::new();
+    }
+
+    let read_id = |cpu: u32, file: &str| {
+        fs::read_to_string(Path::new(CPU_PATH).join(format!("cpu{cpu}/topology/{file}")))
+            .ok()?
+            .trim()
+            .parse::<u32>()
+            .ok()
+    };
+    topology::core_groups()
+        .iter()
+        .flat_map(|group| group.cpus.iter().copied())
+        .filter_map(|cpu| {
+            let key = (
+                read_id(cpu, "physical_package_id")?,
+                read_id(cpu, "core_id")?,
+            );
+            Some((cpu, by_core.get(&key)?.clone()))
+        })
+        .collect()
+}
+
+/// `Tccd` inputs of AMD drivers, mapped to the CPUs of the L3 caches of each
+/// CCD. Left out unless every CCD has the same number of L3 caches.
+fn ccd_inputs() -> BTreeMap<u32, PathBuf> {
+    let Some(driver) = CCD_DRIVERS
+        .iter()
+        .find(|driver| !hwmon_devices(driver).is_empty())
+    else {
+        return BTreeMap::new();
+    };
+    let ccds: Vec<PathBuf> = hwmon_devices(driver)
+        .iter()
+        .flat_map(|device| {
+            let mut ccds: Vec<(u32, PathBuf)> = temperature_inputs(device)
+                .into_iter()
+                .filter_map(|(label, input)| {
+                    Some((label?.strip_prefix("Tccd")?.parse().ok()?, input))
+                })
+                .collect();
+            ccds.sort_by_key(|(ccd, _)| *ccd);
+            ccds.into_iter().map(|(_, input)| input)
+        })
+        .collect();
+
+    let groups = topology::core_groups();
+    let l3_groups = groups.iter().all(|group| group.id.starts_with("l3-"));
+    if ccds.is_empty() || !l3_groups || groups.len() % ccds.len() != 0 {
+        return BTreeMap::new();
+    }
+    // Zen 2 has two L3 caches per CCD, numbered in CPU order like the CCDs
+    let per_ccd = groups.len() / ccds.len();
+    groups
+        .iter()
+        .enumerate()
+        .flat_map(|(i, group)| {
+            let input = &ccds[i / per_ccd];
+            group.cpus.iter().map(move |cpu| (*cpu, input.clone()))
+        })
+        .collect()
+}
+
+fn read_millidegrees(path: &Path) -> Option<f32> {
+    fs::read_to_string(path)
+        .ok()?
+        .trim()
+        .parse::<i32>()
+        .ok()
+        .map(|millidegrees| millidegrees as f32 / 1000.0)
+}
@@ -0,0 +1,59 @@
+// Signals handled by the daemon besides SIGINT and SIGTERM
+//
+// Handlers only set a flag, which normal code picks up later, so nothing that
+// allocates, locks or logs ever runs in signal context.
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Set by SIGUSR1, asking for a status snapshot in the log
+static DUMP_STATUS: AtomicBool = AtomicBool::new(false);
+
+/// Set by SIGUSR2, asking the log file to be reopened
+static REOPEN_LOG: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn handle_signal(signal: libc::c_int) {
+    match signal {
+        libc::SIGUSR1 => DUMP_STATUS.store(true, Ordering::SeqCst),
+        libc::SIGUSR2 => REOPEN_LOG.store(true, Ordering::SeqCst),
+        _ => {}
+    }
+}
+
+/// Installs the handler for `signal`, which must be one of the signals
+/// handled above
+fn install(signal: libc::c_int) -> io::Result<()> {
+    // SAFETY: sigaction is plain data, all-zeroes is a valid value
+    let mut action: libc::sigaction = unsafe { std::mem::zeroed() };
+    action.sa_sigaction = handle_signal as extern "C" fn(libc::c_int) as libc::sighandler_t;
+    action.sa_flags = libc::SA_RESTART;
+
+    // SAFETY: `action` is fully initialised and the handler is async-signal-safe
+    let ret = unsafe {
+        libc::sigemptyset(&raw mut action.sa_mask);
+        libc::sigaction(signal, &action, std::ptr::null_mut())
+    };
+    if ret < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Makes SIGUSR1 request a status dump
+pub fn install_status_dump_handler() -> io::Result<()> {
+    install(libc::SIGUSR1)
+}
+
+/// Whether SIGUSR1 was received since the last call
+pub fn take_status_dump() -> bool {
+    DUMP_STATUS.swap(false, Ordering::SeqCst)
+}
+
+/// Makes SIGUSR2 reopen the log file, as expected by logrotate
+pub fn install_log_reopen_handler() -> io::Result<()> {
+    install(libc::SIGUSR2)
+}
+
+/// Whether SIGUSR2 was received since the last call
+pub fn take_log_reopen() -> bool {
+    REOPEN_LOG.swap(false, Ordering::SeqCst)
+}
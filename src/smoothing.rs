@@ -0,0 +1,151 @@
+// Smoothing of noisy readings, shared by the features that want a calmer
+// signal than a single report gives
+//
+// The monitor smooths CPU usage and temperature once per report, see
+// `[daemon.smoothing]`, and leaves the raw readings next to the smoothed ones.
+use crate::config::SmoothingSettings;
+use crate::core::SystemReport;
+use crate::engine;
+
+/// Exponentially weighted moving average and variance of a series
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Ewma {
+    /// Smoothed value, once the first sample arrived
+    mean: Option<f32>,
+    /// Smoothed variance around `mean`
+    variance: f32,
+}
+
+impl Ewma {
+    /// Feeds `sample` with `alpha`, the weight of the newest sample in (0, 1],
+    /// and returns the new mean. 1 follows the samples as they are.
+    pub fn update(&mut self, sample: f32, alpha: f32) -> f32 {
+        let mean = match self.mean {
+            None => sample,
+            Some(mean) => {
+                // Incremental form, so the variance needs no past samples
+                let diff = sample - mean;
+                let increment = alpha * diff;
+                self.variance = (1.0 - alpha) * (self.variance + diff * increment);
+                mean + increment
+            }
+        };
+        self.mean = Some(mean);
+        mean
+    }
+
+    pub const fn variance(&self) -> f32 {
+        self.variance
+    }
+}
+
+/// The `percentile` of `samples` by nearest rank, so the result is always a
+/// sample that was seen. `None` without samples.
+pub fn percentile(samples: &mut [f32], percentile: f32) -> Option<f32> {
+    if samples.is_empty() {
+        return None;
+    }
+    samples.sort_by(f32::total_cmp);
+    let rank = (percentile / 100.0 * samples.len() as f32).ceil() as usize;
+    Some(samples[rank.clamp(1, samples.len()) - 1])
+}
+
+/// Smoothed CPU usage and temperature, carried from report to report
+#[derive(Debug, Default)]
+pub struct ReportSmoother {
+    load: Ewma,
+    temperature: Ewma,
+}
+
+impl ReportSmoother {
+    /// Feeds the readings of `report` and fills in its smoothed values. A
+    /// missing reading leaves the smoothed value missing too, without
+    /// touching the average.
+    pub fn smooth(&mut self, report: &mut SystemReport, settings: &SmoothingSettings) {
+        report.system_load.smoothed_cpu_usage =
+            engine::average_cpu_usage(report).map(|usage| self.load.update(usage, settings.load));
+        report.cpu_global.smoothed_temperature_celsius = report
+            .cpu_global
+            .average_temperature_celsius
+            .map(|temp| self.temperature.update(temp, settings.temperature));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn first_sample_is_taken_as_is() {
+        let mut ewma = Ewma::default();
+        assert_eq!(ewma.update(42.0, 0.2), 42.0);
+        assert_eq!(ewma.variance(), 0.0);
+    }
+
+    #[test]
+    fn alpha_one_follows_the_samples() {
+        let mut ewma = Ewma::default();
+        for sample in [10.0, 80.0, 5.0, 60.0] {
+            assert_eq!(ewma.update(sample, 1.0), sample);
+            assert_eq!(ewma.variance(), 0.0);
+        }
+    }
+
+    #[test]
+    fn mean_and_variance_follow_the_weights() {
+        let mut ewma = Ewma::default();
+        ewma.update(0.0, 0.5);
+        assert_eq!(ewma.update(10.0, 0.5), 5.0);
+        assert_eq!(ewma.variance(), 25.0);
+        // A sample on the mean leaves it, and the variance decays
+        assert_eq!(ewma.update(5.0, 0.5), 5.0);
+        assert_eq!(ewma.variance(), 12.5);
+    }
+
+    #[test]
+    fn step_converges_and_variance_settles() {
+        let mut ewma = Ewma::default();
+        ewma.update(0.0, 0.3);
+        let mut previous = 0.0;
+        for _ in 0..50 {
+            let mean = ewma.update(100.0, 0.3);
+            assert!(mean >= previous && mean <= 100.0);
+            previous = mean;
+        }
+        assert!((100.0 - previous).abs() < 0.01);
+        assert!(ewma.variance() < 0.01);
+    }
+
+    #[test]
+    fn percentile_picks_the_nearest_rank() {
+        let mut samples = [5.0, 1.0, 3.0, 2.0, 4.0];
+        assert_eq!(percentile(&mut samples, 0.0), Some(1.0));
+        assert_eq!(percentile(&mut samples, 50.0), Some(3.0));
+        assert_eq!(percentile(&mut samples, 90.0), Some(5.0));
+        assert_eq!(percentile(&mut samples, 100.0), Some(5.0));
+        assert_eq!(percentile(&mut [7.5], 50.0), Some(7.5));
+        assert_eq!(percentile(&mut [], 50.0), None);
+    }
+
+    #[test]
+    fn missing_readings_stay_missing() {
+        let settings = SmoothingSettings {
+            load: 0.5,
+            temperature: 0.5,
+        };
+        let mut smoother = ReportSmoother::default();
+        let mut report = SystemReport::for_tests();
+        report.cpu_global.average_temperature_celsius = Some(40.0);
+        smoother.smooth(&mut report, &settings);
+        assert_eq!(report.system_load.smoothed_cpu_usage, None);
+        assert_eq!(report.cpu_global.smoothed_temperature_celsius, Some(40.0));
+
+        report.cpu_global.average_temperature_celsius = None;
+        smoother.smooth(&mut report, &settings);
+        assert_eq!(report.cpu_global.smoothed_temperature_celsius, None);
+
+        report.cpu_global.average_temperature_celsius = Some(60.0);
+        smoother.smooth(&mut report, &settings);
+        assert_eq!(report.cpu_global.smoothed_temperature_celsius, Some(50.0));
+    }
+}
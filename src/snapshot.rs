@@ -0,0 +1,235 @@
+// Snapshot of the system settings Watt manages, so they can be restored on exit
+use crate::battery;
+use crate::control::PowerController;
+use crate::util::sysfs;
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Copy of the snapshot kept on disk, so the original values survive a crash
+pub const SNAPSHOT_PATH: &str = "/run/watt/snapshot.json";
+
+/// Per-policy cpufreq files, in the order they are restored. The governor goes
+/// first since switching governors may reset EPP, and the maximum frequency
+/// goes before the minimum so the range stays valid on the way down.
+const CPUFREQ_FILES: &[&str] = &[
+    "scaling_governor",
+    "energy_performance_preference",
+    "energy_performance_bias",
+    "scaling_max_freq",
+    "scaling_min_freq",
+    "boost",
+];
+
+/// Global turbo and platform controls
+const GLOBAL_FILES: &[&str] = &[
+    "/sys/devices/system/cpu/intel_pstate/no_turbo",
+    "/sys/devices/system/cpu/amd_pstate/cpufreq/boost",
+    "/sys/devices/system/cpu/cpufreq/amd_pstate_enable_boost",
+    "/sys/devices/system/cpu/cpufreq/boost",
+    "/sys/firmware/acpi/platform_profile",
+];
+
+/// A single saved sysfs value
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SavedValue {
+    path: PathBuf,
+    value: String,
+}
+
+/// A managed setting that no longer has its saved value
+#[derive(Debug, Clone)]
+pub struct Change {
+    pub path: PathBuf,
+    pub original: String,
+    pub current: String,
+}
+
+/// Values of every knob Watt may write, as found before the daemon touched them
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Snapshot {
+    values: Vec<SavedValue>,
+}
+
+impl Snapshot {
+    /// Reads the current value of every managed knob present on this system
+    pub fn capture() -> Self {
+        let mut snapshot = Self::default();
+
+        for path in managed_paths() {
+            snapshot.save(path);
+        }
+
+        debug!(
+            "Captured {} settings to restore on exit",
+            snapshot.values.len()
+        );
+        snapshot
+    }
+
+    /// Loads the snapshot left behind by a daemon that did not exit cleanly,
+    /// or captures a fresh one. The settings currently in place are the crashed
+    /// daemon's, so the old snapshot is the one holding the original values.
+    pub fn load_or_capture() -> Self {
+        if let Some(snapshot) = Self::load() {
+            info!("Recovered settings snapshot left by a previous daemon");
+            return snapshot;
+        }
+        Self::capture()
+    }
+
+    /// The snapshot kept at [`SNAPSHOT_PATH`], if there is a valid one
+    pub fn load() -> Option<Self> {
+        match fs::read_to_string(SNAPSHOT_PATH) {
+            Ok(contents) => match serde_json::from_str(&contents) {
+                Ok(snapshot) => return Some(snapshot),
+                Err(e) => warn!("Ignoring invalid settings snapshot at {SNAPSHOT_PATH}: {e}"),
+            },
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => warn!("Failed to read settings snapshot at {SNAPSHOT_PATH}: {e}"),
+        }
+        None
+    }
+
+    fn save(&mut self, path: PathBuf) {
+        if let Ok(value) = sysfs::read_sysfs_value(&path) {
+            self.values.push(SavedValue { path, value });
+        }
+    }
+
+    /// Writes the snapshot to [`SNAPSHOT_PATH`]
+    pub fn persist(&self) -> io::Result<()> {
+        if let Some(parent) = Path::new(SNAPSHOT_PATH).parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(SNAPSHOT_PATH, serde_json::to_vec(self)?)
+    }
+
+    /// Removes the on-disk copy once it is no longer needed
+    pub fn discard() {
+        if let Err(e) = fs::remove_file(SNAPSHOT_PATH) {
+            if e.kind() != io::ErrorKind::NotFound {
+                warn!("Failed to remove settings snapshot: {e}");
+            }
+        }
+    }
+
+    /// Settings whose current value differs from the saved one
+    pub fn changes(&self) -> Vec<Change> {
+        self.values
+            .iter()
+            .filter_map(|saved| {
+                let current = sysfs::read_sysfs_value(&saved.path).ok()?;
+                (current != saved.value).then(|| Change {
+                    path: saved.path.clone(),
+                    original: saved.value.clone(),
+                    current,
+                })
+            })
+            .collect()
+    }
+
+    /// Writes every saved value back through `controller`. A failure is logged
+    /// and does not stop the remaining values from being restored. Returns how
+    /// many failed.
+    pub fn restore(&self, controller: &dyn PowerController) -> usize {
+        info!("Restoring original system settings...");
+
+        let mut failed = Vec::new();
+        for saved in &self.values {
+            if !write_if_changed(saved, controller) {
+                failed.push(saved);
+            }
+        }
+
+        // Some values can only be written once their neighbours are restored,
+        // e.g. a minimum frequency above the current maximum, so retry once
+        let mut failures = 0;
+        for saved in failed {
+            if let Err(e) = controller.restore_file(&saved.path, &saved.value) {
+                warn!(
+                    "Failed to restore {} to '{}': {e}",
+                    saved.path.display(),
+                    saved.value
+                );
+                failures += 1;
+            }
+        }
+
+        if failures == 0 {
+            info!("Restored {} settings", self.values.len());
+        } else {
+            warn!(
+                "Restored {} of {} settings",
+                self.values.len() - failures,
+                self.values.len()
+            );
+        }
+        failures
+    }
+}
+
+/// Restores a single value, skipping the write if it is already in place.
+/// Returns whether the value is now in place.
+fn write_if_changed(saved: &SavedValue, controller: &dyn PowerController) -> bool {
+    if sysfs::read_sysfs_value(&saved.path).is_ok_and(|current| current == saved.value) {
+        return true;
+    }
+
+    match controller.restore_file(&saved.path, &saved.value) {
+        Ok(()) => true,
+        Err(e) => {
+            debug!("Deferring restore of {}: {e}", saved.path.display());
+            false
+        }
+    }
+}
+
+/// Every knob Watt may write, in the order they are restored. Files missing on
+/// this system are included, callers skip them.
+pub fn managed_paths() -> Vec<PathBuf> {
+    let mut paths = Vec::new();
+
+    for policy in cpufreq_dirs() {
+        for file in CPUFREQ_FILES {
+            paths.push(policy.join(file));
+        }
+    }
+
+    paths.extend(GLOBAL_FILES.iter().map(PathBuf::from));
+
+    match battery::find_supported_batteries(Path::new("/sys/class/power_supply")) {
+        Ok(batteries) => {
+            for battery in batteries {
+                // Stop before start, mirroring how thresholds are applied
+                paths.push(battery.path.join(battery.pattern.stop_path));
+                paths.push(battery.path.join(battery.pattern.start_path));
+            }
+        }
+        Err(e) => debug!("Not managing charge thresholds: {e}"),
+    }
+
+    paths
+}
+
+/// cpufreq directories of all CPUs, in CPU order
+fn cpufreq_dirs() -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir("/sys/devices/system/cpu") else {
+        return Vec::new();
+    };
+
+    let mut cpus: Vec<(u32, PathBuf)> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name();
+            let id = name.to_str()?.strip_prefix("cpu")?.parse::<u32>().ok()?;
+            let cpufreq = entry.path().join("cpufreq");
+            cpufreq.exists().then_some((id, cpufreq))
+        })
+        .collect();
+
+    cpus.sort_by_key(|(id, _)| *id);
+    cpus.into_iter().map(|(_, path)| path).collect()
+}
@@ -0,0 +1,194 @@
+// Machine-readable state for status bars and scripts, rewritten every cycle
+use crate::config::DaemonConfig;
+use crate::core::SystemReport;
+use crate::cpu;
+use crate::daemon::Pause;
+use crate::engine;
+use crate::util::sysfs;
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+
+/// Everything written to the state file
+#[derive(Debug, Serialize)]
+pub struct State<'a> {
+    pub active_profile: &'a str,
+    /// "ac" or "battery"
+    pub power_source: &'static str,
+    pub paused: bool,
+    /// When a timed pause ends
+    pub paused_until: Option<jiff::Timestamp>,
+    /// Seconds left of a timed pause, as of `updated_at`
+    pub pause_remaining_sec: Option<u64>,
+    /// Missing if the system does not report it
+    pub turbo: Option<bool>,
+    /// Average CPU temperature, missing without a sensor
+    pub temperature_celsius: Option<f32>,
+    pub policies: Vec<PolicyState>,
+    pub batteries: Vec<BatteryState>,
+    /// When settings were last applied without errors
+    pub last_successful_apply: Option<jiff::Timestamp>,
+    pub updated_at: jiff::Timestamp,
+}
+
+/// Governor and frequencies of one cpufreq policy
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PolicyState {
+    pub policy: String,
+    pub governor: Option<String>,
+    /// Energy performance preference, where the driver has one
+    pub epp: Option<String>,
+    pub cur_freq_mhz: Option<u32>,
+    pub min_freq_mhz: Option<u32>,
+    pub max_freq_mhz: Option<u32>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BatteryState {
+    pub name: String,
+    pub capacity_percent: Option<u8>,
+    pub charging_state: Option<String>,
+}
+
+impl<'a> State<'a> {
+    /// Collects the state after a cycle that selected `active_profile`, with
+    /// management paused if `pause` is set
+    pub fn new(
+        report: &SystemReport,
+        active_profile: &'a str,
+        pause: Option<&Pause>,
+        last_successful_apply: Option<jiff::Timestamp>,
+    ) -> Self {
+        let on_ac_power = engine::is_on_ac_power(report);
+        let updated_at = jiff::Timestamp::now();
+        let paused_until = pause.and_then(|pause| pause.until);
+        Self {
+            active_profile,
+            power_source: if on_ac_power { "ac" } else { "battery" },
+            paused: pause.is_some(),
+            paused_until,
+            pause_remaining_sec: paused_until.map(|until| {
+                u64::try_from(until.duration_since(updated_at).as_secs()).unwrap_or(0)
+            }),
+            turbo: report.cpu_global.turbo_status,
+            temperature_celsius: report.cpu_global.average_temperature_celsius,
+            policies: cpu::cpufreq_policies()
+                .iter()
+                .map(|path| policy_state(path))
+                .collect(),
+            batteries: report
+                .batteries
+                .iter()
+                .map(|battery| BatteryState {
+                    name: battery.name.clone(),
+                    capacity_percent: battery.capacity_percent,
+                    charging_state: battery.charging_state.clone(),
+                })
+                .collect(),
+            last_successful_apply,
+            updated_at,
+        }
+    }
+}
+
+/// Reads the state of the cpufreq policy at `path`
+pub fn policy_state(path: &Path) -> PolicyState {
+    let read = |file: &str| sysfs::read_sysfs_value(path.join(file)).ok();
+    let read_mhz = |file: &str| read(file)?.parse::<u32>().ok().map(|khz| khz / 1000);
+
+    PolicyState {
+        policy: path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned(),
+        governor: read("scaling_governor"),
+        epp: read("energy_performance_preference"),
+        cur_freq_mhz: read_mhz("scaling_cur_freq"),
+        min_freq_mhz: read_mhz("scaling_min_freq"),
+        max_freq_mhz: read_mhz("scaling_max_freq"),
+    }
+}
+
+/// The state file, removed again when dropped so a stopped daemon does not
+/// leave state behind that looks live
+pub struct StateFile {
+    /// `None` when disabled
+    path: Option<PathBuf>,
+}
+
+impl StateFile {
+    pub fn new(config: &DaemonConfig) -> Self {
+        Self {
+            path: configured_path(config),
+        }
+    }
+
+    /// Follows a reloaded configuration, removing the old file if the path
+    /// changed or the file got disabled
+    pub fn reconfigure(&mut self, config: &DaemonConfig) {
+        let path = configured_path(config);
+        if path != self.path {
+            self.remove();
+            self.path = path;
+        }
+    }
+
+    /// Replaces the file with `state`. Readers never see a partial file.
+    pub fn write(&self, state: &State<'_>) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        if let Err(e) = write_atomically(path, state) {
+            warn!("Failed to write state file {}: {e}", path.display());
+        }
+    }
+
+    fn remove(&self) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        match fs::remove_file(path) {
+            Ok(()) => debug!("Removed state file {}", path.display()),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => warn!("Failed to remove state file {}: {e}", path.display()),
+        }
+    }
+}
+
+impl Drop for StateFile {
+    fn drop(&mut self) {
+        self.remove();
+    }
+}
+
+fn configured_path(config: &DaemonConfig) -> Option<PathBuf> {
+    config
+        .state_file_enabled
+        .then(|| PathBuf::from(&config.state_file_path))
+}
+
+fn write_atomically(path: &Path, state: &State<'_>) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let mut tmp_path = path.as_os_str().to_owned();
+    tmp_path.push(".tmp");
+    let tmp_path = Path::new(&tmp_path);
+
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o644)
+        .open(tmp_path)?;
+    // The umask may have taken away the read bits
+    file.set_permissions(fs::Permissions::from_mode(0o644))?;
+    serde_json::to_writer_pretty(&mut file, state)?;
+    file.write_all(b"\n")?;
+    fs::rename(tmp_path, path)
+}
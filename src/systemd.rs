@@ -0,0 +1,258 @@
+// systemd service notification (sd_notify) and watchdog support
+//
+// This speaks the sd_notify datagram protocol directly so we don't need to
+// link against libsystemd.
+use log::{debug, warn};
+use std::env;
+use std::io;
+use std::os::linux::net::SocketAddrExt;
+use std::os::unix::net::{SocketAddr, UnixDatagram};
+use std::time::{Duration, Instant};
+
+/// Connection to the service manager's notification socket
+pub struct Notifier {
+    socket: UnixDatagram,
+    address: SocketAddr,
+    /// Interval at which watchdog pings are sent, if the watchdog is enabled
+    watchdog_interval: Option<Duration>,
+    /// When the last watchdog ping was sent
+    last_watchdog_ping: Instant,
+}
+
+impl Notifier {
+    /// Creates a notifier from `NOTIFY_SOCKET`, returning `None` when not
+    /// running under a service manager that expects notifications
+    pub fn from_env() -> Option<Self> {
+        let socket_path = env::var("NOTIFY_SOCKET").ok()?;
+        let watchdog_interval = watchdog_interval(
+            env::var("WATCHDOG_USEC").ok().as_deref(),
+            env::var("WATCHDOG_PID").ok().as_deref(),
+        );
+
+        match Self::new(&socket_path, watchdog_interval) {
+            Ok(notifier) => Some(notifier),
+            Err(e) => {
+                warn!("Invalid NOTIFY_SOCKET '{socket_path}': {e}");
+                None
+            }
+        }
+    }
+
+    /// Creates a notifier sending to `socket_path`, an abstract socket if it
+    /// starts with `@`, and pinging the watchdog every `watchdog_interval`
+    fn new(socket_path: &str, watchdog_interval: Option<Duration>) -> io::Result<Self> {
+        let address = if let Some(name) = socket_path.strip_prefix('@') {
+            SocketAddr::from_abstract_name(name.as_bytes())?
+        } else {
+            SocketAddr::from_pathname(socket_path)?
+        };
+
+        if let Some(interval) = watchdog_interval {
+            debug!("systemd watchdog enabled, pinging every {interval:?}");
+        }
+
+        Ok(Self {
+            socket: UnixDatagram::unbound()?,
+            address,
+            watchdog_interval,
+            last_watchdog_ping: Instant::now(),
+        })
+    }
+
+    /// Sends a raw notification message such as `READY=1`
+    fn send(&self, message: &str) -> io::Result<()> {
+        self.socket
+            .send_to_addr(message.as_bytes(), &self.address)
+            .map(|_| ())
+    }
+
+    /// Sends a notification, logging failures instead of propagating them
+    fn notify(&self, message: &str) {
+        if let Err(e) = self.send(message) {
+            warn!("Failed to notify service manager ({message}): {e}");
+        }
+    }
+
+    /// Tells the service manager startup is complete
+    pub fn ready(&self) {
+        self.notify("READY=1");
+    }
+
+    /// Tells the service manager the configuration is being reloaded
+    pub fn reloading(&self) {
+        self.notify(&format!("RELOADING=1\nMONOTONIC_USEC={}", monotonic_usec()));
+    }
+
+    /// Tells the service manager the daemon is shutting down
+    pub fn stopping(&self) {
+        self.notify("STOPPING=1");
+    }
+
+    /// Updates the free-form status string shown by `systemctl status`
+    pub fn status(&self, status: &str) {
+        self.notify(&format!("STATUS={status}"));
+    }
+
+    /// The interval at which the watchdog must be pinged, if enabled
+    pub const fn watchdog_interval(&self) -> Option<Duration> {
+        self.watchdog_interval
+    }
+
+    /// Pings the watchdog if it is enabled and a ping is due
+    pub fn ping_watchdog(&mut self) {
+        let Some(interval) = self.watchdog_interval else {
+            return;
+        };
+
+        if self.last_watchdog_ping.elapsed() >= interval {
+            self.notify("WATCHDOG=1");
+            self.last_watchdog_ping = Instant::now();
+        }
+    }
+}
+
+/// Half the watchdog timeout in `WATCHDOG_USEC`, as recommended by
+/// sd_watchdog_enabled(3), or `None` without a watchdog for this process
+fn watchdog_interval(usec: Option<&str>, pid: Option<&str>) -> Option<Duration> {
+    // If WATCHDOG_PID is set, the watchdog is meant for that process only
+    if let Some(pid) = pid {
+        if pid.parse::<u32>().ok() != Some(std::process::id()) {
+            return None;
+        }
+    }
+
+    let usec = usec?.parse::<u64>().ok()?;
+    if usec == 0 {
+        return None;
+    }
+
+    Some(Duration::from_micros(usec / 2))
+}
+
+/// Current CLOCK_MONOTONIC time in microseconds, as expected by `MONOTONIC_USEC`
+fn monotonic_usec() -> u64 {
+    let mut ts = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    // SAFETY: `ts` is a valid, writable timespec
+    unsafe {
+        libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut ts);
+    }
+
+    (ts.tv_sec as u64) * 1_000_000 + (ts.tv_nsec as u64) / 1_000
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The service manager's end of the notification socket
+    struct FakeManager {
+        socket: UnixDatagram,
+        path: std::path::PathBuf,
+    }
+
+    impl FakeManager {
+        fn bind(name: &str) -> Self {
+            let path = env::temp_dir().join(format!("watt-notify-{}-{name}", std::process::id()));
+            let _ = std::fs::remove_file(&path);
+            let socket = UnixDatagram::bind(&path).unwrap();
+            socket.set_nonblocking(true).unwrap();
+            Self { socket, path }
+        }
+
+        fn notifier(&self, watchdog_interval: Option<Duration>) -> Notifier {
+            Notifier::new(self.path.to_str().unwrap(), watchdog_interval).unwrap()
+        }
+
+        /// The next message, or `None` if nothing was sent
+        fn received(&self) -> Option<String> {
+            let mut buf = [0u8; 1024];
+            match self.socket.recv(&mut buf) {
+                Ok(len) => Some(String::from_utf8_lossy(&buf[..len]).into_owned()),
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => None,
+                Err(e) => panic!("{e}"),
+            }
+        }
+    }
+
+    impl Drop for FakeManager {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+
+    #[test]
+    fn sends_state_changes() {
+        let manager = FakeManager::bind("states");
+        let notifier = manager.notifier(None);
+
+        notifier.ready();
+        assert_eq!(manager.received().as_deref(), Some("READY=1"));
+
+        notifier.reloading();
+        let reloading = manager.received().unwrap();
+        let usec = reloading
+            .strip_prefix("RELOADING=1\nMONOTONIC_USEC=")
+            .unwrap();
+        assert!(usec.parse::<u64>().unwrap() > 0);
+
+        notifier.status("Profile: charger");
+        assert_eq!(
+            manager.received().as_deref(),
+            Some("STATUS=Profile: charger")
+        );
+
+        notifier.stopping();
+        assert_eq!(manager.received().as_deref(), Some("STOPPING=1"));
+        assert_eq!(manager.received(), None);
+    }
+
+    #[test]
+    fn abstract_sockets() {
+        let name = format!("watt-notify-test-{}", std::process::id());
+        let address = SocketAddr::from_abstract_name(name.as_bytes()).unwrap();
+        let socket = UnixDatagram::bind_addr(&address).unwrap();
+
+        Notifier::new(&format!("@{name}"), None).unwrap().ready();
+        let mut buf = [0u8; 64];
+        let len = socket.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..len], b"READY=1");
+    }
+
+    #[test]
+    fn watchdog_pings_once_due() {
+        let manager = FakeManager::bind("watchdog");
+
+        manager.notifier(None).ping_watchdog();
+        assert_eq!(manager.received(), None);
+
+        manager
+            .notifier(Some(Duration::from_secs(3600)))
+            .ping_watchdog();
+        assert_eq!(manager.received(), None);
+
+        let mut notifier = manager.notifier(Some(Duration::ZERO));
+        notifier.ping_watchdog();
+        assert_eq!(manager.received().as_deref(), Some("WATCHDOG=1"));
+    }
+
+    #[test]
+    fn watchdog_interval_is_half_the_timeout() {
+        assert_eq!(
+            watchdog_interval(Some("30000000"), None),
+            Some(Duration::from_secs(15))
+        );
+        let pid = std::process::id().to_string();
+        assert_eq!(
+            watchdog_interval(Some("4000000"), Some(&pid)),
+            Some(Duration::from_secs(2))
+        );
+        // Meant for another process
+        assert_eq!(watchdog_interval(Some("4000000"), Some("1")), None);
+        assert_eq!(watchdog_interval(Some("0"), None), None);
+        assert_eq!(watchdog_interval(Some("soon"), None), None);
+        assert_eq!(watchdog_interval(None, None), None);
+    }
+}
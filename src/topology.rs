@@ -0,0 +1,88 @@
+//! CPU topology: which CPUs share a last level cache or a package
+//!
+//! Read once and cached, since it only changes with CPU hotplug, which the
+//! engine does not follow anyway.
+
+use crate::util::sysfs::read_sysfs_value;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::sync::OnceLock;
+
+const CPU_PATH: &str = "/sys/devices/system/cpu";
+
+/// CPUs that share an L3 cache, such as a CCD of a Ryzen, or else a package
+#[derive(Debug, Clone)]
+pub struct CoreGroup {
+    /// e.g. `l3-0` or `package0`, numbered in CPU order
+    pub id: String,
+    pub cpus: Vec<u32>,
+}
+
+/// The core groups of this machine, in CPU order. Empty if the topology
+/// cannot be read.
+pub fn core_groups() -> &'static [CoreGroup] {
+    static GROUPS: OnceLock<Vec<CoreGroup>> = OnceLock::new();
+    GROUPS.get_or_init(probe)
+}
+
+fn probe() -> Vec<CoreGroup> {
+    let Ok(entries) = fs::read_dir(CPU_PATH) else {
+        return Vec::new();
+    };
+    let mut cpus: Vec<u32> = entries
+        .flatten()
+        .filter_map(|entry| {
+            entry
+                .file_name()
+                .to_str()?
+                .strip_prefix("cpu")?
+                .parse()
+                .ok()
+        })
+        .collect();
+    cpus.sort_unstable();
+
+    // Keyed by what the CPUs share, with the CPUs in order of appearance
+    let mut by_l3: BTreeMap<String, Vec<u32>> = BTreeMap::new();
+    let mut by_package: BTreeMap<String, Vec<u32>> = BTreeMap::new();
+    for &cpu in &cpus {
+        let path = Path::new(CPU_PATH).join(format!("cpu{cpu}"));
+        if let Some(shared) = l3_shared_cpus(&path) {
+            by_l3.entry(shared).or_default().push(cpu);
+        }
+        if let Ok(package) = read_sysfs_value(path.join("topology/physical_package_id")) {
+            by_package.entry(package).or_default().push(cpu);
+        }
+    }
+
+    let (prefix, groups) =
+        if !by_l3.is_empty() && by_l3.values().map(Vec::len).sum::<usize>() == cpus.len() {
+            ("l3-", by_l3)
+        } else if by_package.values().map(Vec::len).sum::<usize>() == cpus.len() {
+            ("package", by_package)
+        } else {
+            return Vec::new();
+        };
+
+    let mut groups: Vec<Vec<u32>> = groups.into_values().collect();
+    groups.sort_by_key(|cpus| cpus.first().copied());
+    groups
+        .into_iter()
+        .enumerate()
+        .map(|(i, cpus)| CoreGroup {
+            id: format!("{prefix}{i}"),
+            cpus,
+        })
+        .collect()
+}
+
+/// The `shared_cpu_list` of the CPU's L3 cache, if it has one
+fn l3_shared_cpus(cpu_path: &Path) -> Option<String> {
+    fs::read_dir(cpu_path.join("cache"))
+        .ok()?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|index| read_sysfs_value(index.join("level")).is_ok_and(|level| level == "3"))
+        .find_map(|index| read_sysfs_value(index.join("shared_cpu_list")).ok())
+}
@@ -0,0 +1,394 @@
+//! The models auto turbo decides with, once the temperature and the battery
+//! allow turbo at all, picked with `turbo_auto_settings.strategy`
+//!
+//! Each [`TurboStrategy`] sees the same [`TurboMetrics`] and the previous
+//! state, and so can hold it where it is undecided.
+
+use crate::config::{
+    TurboAutoSettings, TurboScheduleWindow, TurboStrategyKind, WeightedScoreSettings,
+};
+use crate::core::CpuPressure;
+use crate::engine::{TurboReason, pressure_allows_turbo};
+
+/// What an auto turbo decision is made on
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TurboMetrics {
+    /// Average CPU temperature, in °C
+    pub temperature: Option<f32>,
+    /// CPU load per `load_metric`, in percent
+    pub load: Option<f32>,
+    pub cpu_pressure: Option<CpuPressure>,
+    pub battery_percent: Option<u8>,
+    pub on_ac_power: bool,
+    /// Whether the battery is too low for turbo, per
+    /// `min_battery_percent_for_turbo`
+    pub low_battery: bool,
+    /// Throttle events since the previous report
+    pub throttle_events: Option<u64>,
+}
+
+pub trait TurboStrategy {
+    /// Whether turbo should be on, and why. `previous` is the current state.
+    fn decide(&self, metrics: &TurboMetrics, previous: bool) -> (bool, TurboReason);
+}
+
+/// The strategy `settings` select
+pub fn strategy(settings: &TurboAutoSettings) -> Box<dyn TurboStrategy + '_> {
+    match settings.strategy {
+        TurboStrategyKind::Threshold => Box::new(Threshold { settings }),
+        TurboStrategyKind::WeightedScore => Box::new(WeightedScore {
+            settings: &settings.weighted_score,
+            temp_threshold_high: settings.temp_threshold_high,
+        }),
+        TurboStrategyKind::Schedule => Box::new(Schedule {
+            windows: &settings.schedule,
+            now: jiff::Zoned::now().time(),
+        }),
+    }
+}
+
+/// Enables turbo above `load_threshold_high` and disables it below
+/// `load_threshold_low`, where CPU pressure can only hold back enabling it
+pub struct Threshold<'a> {
+    pub settings: &'a TurboAutoSettings,
+}
+
+impl TurboStrategy for Threshold<'_> {
+    fn decide(&self, metrics: &TurboMetrics, previous: bool) -> (bool, TurboReason) {
+        let settings = self.settings;
+        match (metrics.temperature, metrics.load) {
+            // High load that is not CPU-bound does not enable turbo
+            (_, Some(usage))
+                if usage >= settings.load_threshold_high
+                    && !pressure_allows_turbo(settings, metrics.cpu_pressure) =>
+            {
+                (previous, TurboReason::LowPressure)
+            }
+            (_, Some(usage)) if usage >= settings.load_threshold_high => {
+                (true, TurboReason::HighLoad)
+            }
+            (_, Some(usage)) if usage <= settings.load_threshold_low => {
+                (false, TurboReason::LowLoad)
+            }
+            // In intermediate load range, maintain previous state (hysteresis)
+            (_, Some(usage))
+                if usage > settings.load_threshold_low && usage < settings.load_threshold_high =>
+            {
+                (previous, TurboReason::IntermediateLoad)
+            }
+            // When CPU load data is present but temperature is missing, use the same hysteresis logic
+            (None, Some(_)) => (previous, TurboReason::MissingTemperature),
+            // When all metrics are missing, maintain the previous state
+            (None, None) => (previous, TurboReason::MissingMetrics),
+            // Any other cases with partial metrics, maintain previous state for stability
+            _ => (previous, TurboReason::IncompleteMetrics),
+        }
+    }
+}
+
+/// Scores the load, the temperature headroom and the battery level, and
+/// enables turbo at `enable_above`, disables it at `disable_below`
+pub struct WeightedScore<'a> {
+    pub settings: &'a WeightedScoreSettings,
+    pub temp_threshold_high: f32,
+}
+
+impl WeightedScore<'_> {
+    /// The weighted average of the inputs that are known, from 0 to 1, or
+    /// `None` without any
+    pub fn score(&self, metrics: &TurboMetrics) -> Option<f32> {
+        let settings = self.settings;
+        let battery = if metrics.on_ac_power {
+            Some(1.0)
+        } else {
+            metrics
+                .battery_percent
+                .map(|percent| f32::from(percent) / 100.0)
+        };
+        let terms = [
+            (settings.load_weight, metrics.load.map(|load| load / 100.0)),
+            (
+                settings.temperature_weight,
+                metrics
+                    .temperature
+                    .map(|temp| 1.0 - temp / self.temp_threshold_high),
+            ),
+            (settings.battery_weight, battery),
+        ];
+        // Missing inputs leave the others to share their weight
+        let (total, weights) = terms
+            .iter()
+            .filter_map(|&(weight, value)| Some((weight, value?.clamp(0.0, 1.0))))
+            .filter(|&(weight, _)| weight > 0.0)
+            .fold((0.0, 0.0), |(total, weights), (weight, value)| {
+                (total + weight * value, weights + weight)
+            });
+        (weights > 0.0).then(|| total / weights)
+    }
+}
+
+impl TurboStrategy for WeightedScore<'_> {
+    fn decide(&self, metrics: &TurboMetrics, previous: bool) -> (bool, TurboReason) {
+        match self.score(metrics) {
+            None => (previous, TurboReason::MissingMetrics),
+            Some(score) if score >= self.settings.enable_above => (true, TurboReason::ScoreHigh),
+            Some(score) if score <= self.settings.disable_below => (false, TurboReason::ScoreLow),
+            Some(_) => (previous, TurboReason::ScoreIntermediate),
+        }
+    }
+}
+
+/// Enables turbo during the configured times of day, whatever the load
+pub struct Schedule<'a> {
+    pub windows: &'a [TurboScheduleWindow],
+    /// The local time of day
+    pub now: jiff::civil::Time,
+}
+
+impl TurboStrategy for Schedule<'_> {
+    fn decide(&self, _metrics: &TurboMetrics, _previous: bool) -> (bool, TurboReason) {
+        if self
+            .windows
+            .iter()
+            .any(|window| in_window(window, self.now))
+        {
+            (true, TurboReason::ScheduledOn)
+        } else {
+            (false, TurboReason::ScheduledOff)
+        }
+    }
+}
+
+/// Whether `now` falls in `window`, from `from` up to but not including `to`
+fn in_window(window: &TurboScheduleWindow, now: jiff::civil::Time) -> bool {
+    if window.from <= window.to {
+        window.from <= now && now < window.to
+    } else {
+        now >= window.from || now < window.to
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at_load(load: f32) -> TurboMetrics {
+        TurboMetrics {
+            temperature: Some(50.0),
+            load: Some(load),
+            ..TurboMetrics::default()
+        }
+    }
+
+    #[test]
+    fn threshold_switches_at_the_thresholds() {
+        let settings = TurboAutoSettings::default();
+        let strategy = Threshold {
+            settings: &settings,
+        };
+
+        assert_eq!(
+            strategy.decide(&at_load(70.0), false),
+            (true, TurboReason::HighLoad)
+        );
+        assert_eq!(
+            strategy.decide(&at_load(30.0), true),
+            (false, TurboReason::LowLoad)
+        );
+        for previous in [false, true] {
+            assert_eq!(
+                strategy.decide(&at_load(69.9), previous),
+                (previous, TurboReason::IntermediateLoad)
+            );
+            assert_eq!(
+                strategy.decide(&at_load(30.1), previous),
+                (previous, TurboReason::IntermediateLoad)
+            );
+        }
+    }
+
+    #[test]
+    fn threshold_holds_the_state_without_a_load() {
+        let settings = TurboAutoSettings::default();
+        let strategy = Threshold {
+            settings: &settings,
+        };
+        let no_load = TurboMetrics {
+            temperature: Some(50.0),
+            ..TurboMetrics::default()
+        };
+
+        assert_eq!(
+            strategy.decide(&no_load, true),
+            (true, TurboReason::IncompleteMetrics)
+        );
+        assert_eq!(
+            strategy.decide(&TurboMetrics::default(), false),
+            (false, TurboReason::MissingMetrics)
+        );
+    }
+
+    #[test]
+    fn threshold_needs_cpu_pressure_to_enable() {
+        let settings = TurboAutoSettings {
+            pressure_threshold_avg10: Some(20.0),
+            ..TurboAutoSettings::default()
+        };
+        let strategy = Threshold {
+            settings: &settings,
+        };
+        let with_pressure = |some_avg10| TurboMetrics {
+            cpu_pressure: Some(CpuPressure {
+                some_avg10,
+                some_avg60: 0.0,
+            }),
+            ..at_load(90.0)
+        };
+
+        assert_eq!(
+            strategy.decide(&with_pressure(19.9), false),
+            (false, TurboReason::LowPressure)
+        );
+        assert_eq!(
+            strategy.decide(&with_pressure(20.0), false),
+            (true, TurboReason::HighLoad)
+        );
+    }
+
+    #[test]
+    fn weighted_score_switches_at_its_bounds() {
+        let settings = WeightedScoreSettings {
+            load_weight: 1.0,
+            temperature_weight: 0.0,
+            battery_weight: 0.0,
+            ..WeightedScoreSettings::default()
+        };
+        let strategy = WeightedScore {
+            settings: &settings,
+            temp_threshold_high: 80.0,
+        };
+
+        assert_eq!(strategy.score(&at_load(60.0)), Some(0.6));
+        assert_eq!(
+            strategy.decide(&at_load(60.0), false),
+            (true, TurboReason::ScoreHigh)
+        );
+        assert_eq!(
+            strategy.decide(&at_load(40.0), true),
+            (false, TurboReason::ScoreLow)
+        );
+        for previous in [false, true] {
+            assert_eq!(
+                strategy.decide(&at_load(50.0), previous),
+                (previous, TurboReason::ScoreIntermediate)
+            );
+        }
+    }
+
+    #[test]
+    fn weighted_score_combines_the_inputs() {
+        let settings = WeightedScoreSettings {
+            load_weight: 0.5,
+            temperature_weight: 0.5,
+            battery_weight: 0.0,
+            ..WeightedScoreSettings::default()
+        };
+        let strategy = WeightedScore {
+            settings: &settings,
+            temp_threshold_high: 80.0,
+        };
+
+        // Full load at the temperature threshold, with no headroom left
+        let hot = TurboMetrics {
+            temperature: Some(80.0),
+            ..at_load(100.0)
+        };
+        assert_eq!(strategy.score(&hot), Some(0.5));
+        // Beyond it the headroom does not go negative
+        let hotter = TurboMetrics {
+            temperature: Some(100.0),
+            ..at_load(100.0)
+        };
+        assert_eq!(strategy.score(&hotter), Some(0.5));
+        // A missing input leaves its weight to the others
+        let unknown_temperature = TurboMetrics {
+            temperature: None,
+            ..at_load(100.0)
+        };
+        assert_eq!(strategy.score(&unknown_temperature), Some(1.0));
+    }
+
+    #[test]
+    fn weighted_score_counts_ac_power_as_a_full_battery() {
+        let settings = WeightedScoreSettings {
+            load_weight: 0.0,
+            temperature_weight: 0.0,
+            battery_weight: 1.0,
+            ..WeightedScoreSettings::default()
+        };
+        let strategy = WeightedScore {
+            settings: &settings,
+            temp_threshold_high: 80.0,
+        };
+        let on_battery = |percent| TurboMetrics {
+            battery_percent: Some(percent),
+            ..TurboMetrics::default()
+        };
+
+        assert_eq!(strategy.score(&on_battery(25)), Some(0.25));
+        assert_eq!(
+            strategy.score(&TurboMetrics {
+                on_ac_power: true,
+                ..on_battery(25)
+            }),
+            Some(1.0)
+        );
+        assert_eq!(
+            strategy.decide(&TurboMetrics::default(), true),
+            (true, TurboReason::MissingMetrics)
+        );
+    }
+
+    #[test]
+    fn schedule_follows_the_windows() {
+        let windows = [
+            TurboScheduleWindow {
+                from: jiff::civil::time(9, 0, 0, 0),
+                to: jiff::civil::time(17, 0, 0, 0),
+            },
+            // Past midnight
+            TurboScheduleWindow {
+                from: jiff::civil::time(22, 0, 0, 0),
+                to: jiff::civil::time(2, 0, 0, 0),
+            },
+        ];
+        let decide = |hour, minute| {
+            Schedule {
+                windows: &windows,
+                now: jiff::civil::time(hour, minute, 0, 0),
+            }
+            .decide(&TurboMetrics::default(), false)
+        };
+
+        assert_eq!(decide(8, 59), (false, TurboReason::ScheduledOff));
+        assert_eq!(decide(9, 0), (true, TurboReason::ScheduledOn));
+        assert_eq!(decide(16, 59), (true, TurboReason::ScheduledOn));
+        assert_eq!(decide(17, 0), (false, TurboReason::ScheduledOff));
+        assert_eq!(decide(22, 0), (true, TurboReason::ScheduledOn));
+        assert_eq!(decide(0, 0), (true, TurboReason::ScheduledOn));
+        assert_eq!(decide(1, 59), (true, TurboReason::ScheduledOn));
+        assert_eq!(decide(2, 0), (false, TurboReason::ScheduledOff));
+    }
+
+    #[test]
+    fn schedule_without_windows_keeps_turbo_off() {
+        let strategy = Schedule {
+            windows: &[],
+            now: jiff::civil::time(12, 0, 0, 0),
+        };
+        assert_eq!(
+            strategy.decide(&at_load(100.0), true),
+            (false, TurboReason::ScheduledOff)
+        );
+    }
+}
@@ -1,4 +1,5 @@
-use std::io;
+use std::path::PathBuf;
+use std::{fmt, io};
 
 #[derive(Debug, thiserror::Error)]
 pub enum ControlError {
@@ -31,6 +32,60 @@ pub enum ControlError {
 
     #[error("Path missing: {0}")]
     PathMissing(String),
+
+    /// Another error, with where it happened
+    #[error("{source} ({context})")]
+    Context {
+        context: Box<ErrorContext>,
+        source: Box<ControlError>,
+    },
+}
+
+/// Where a control error happened, as far as it is known
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ErrorContext {
+    /// The knob being set, e.g. "governor" or "EPP"
+    pub knob: Option<String>,
+    pub cpu: Option<u32>,
+    pub path: Option<PathBuf>,
+    /// The value that was being written
+    pub value: Option<String>,
+    /// What the file held, if it could be read
+    pub current: Option<String>,
+}
+
+impl ErrorContext {
+    /// Takes what `self` does not know yet from `other`
+    fn fill_from(&mut self, other: Self) {
+        self.knob = self.knob.take().or(other.knob);
+        self.cpu = self.cpu.or(other.cpu);
+        self.path = self.path.take().or(other.path);
+        self.value = self.value.take().or(other.value);
+        self.current = self.current.take().or(other.current);
+    }
+}
+
+/// All on one line, e.g. `knob="EPP" cpu=3 path=/sys/... value="performance"`
+impl fmt::Display for ErrorContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut fields = Vec::new();
+        if let Some(knob) = &self.knob {
+            fields.push(format!("knob={knob:?}"));
+        }
+        if let Some(cpu) = self.cpu {
+            fields.push(format!("cpu={cpu}"));
+        }
+        if let Some(path) = &self.path {
+            fields.push(format!("path={}", path.display()));
+        }
+        if let Some(value) = &self.value {
+            fields.push(format!("value={value:?}"));
+        }
+        if let Some(current) = &self.current {
+            fields.push(format!("current={current:?}"));
+        }
+        f.write_str(&fields.join(" "))
+    }
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -72,9 +127,248 @@ pub enum AppError {
     #[error("{0}")]
     Config(#[from] crate::config::ConfigError),
 
+    #[error("{0}")]
+    PidFile(#[from] crate::pidfile::PidFileError),
+
     #[error("{0}")]
     Generic(String),
 
     #[error("I/O error: {0}")]
     Io(#[from] io::Error),
+
+    /// The command was given arguments it cannot work with
+    #[error("{0}")]
+    Usage(String),
+
+    /// The command needs root, or an authorization it was not given
+    #[error("{0}")]
+    Permission(String),
+
+    #[error("{0}")]
+    DaemonUnreachable(String),
+
+    /// Some settings were applied and others failed
+    #[error("{0}")]
+    PartialApply(String),
+}
+
+/// What went wrong, as far as scripts are concerned. Each kind has its own
+/// exit code, which is part of the command line interface and must not change.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ErrorKind {
+    /// Anything not covered by a more specific kind
+    Failure,
+    Usage,
+    Config,
+    Permission,
+    /// The hardware or kernel lacks the knob or feature
+    Unsupported,
+    DaemonUnreachable,
+    PartialApply,
+}
+
+impl ErrorKind {
+    pub const fn exit_code(self) -> i32 {
+        match self {
+            Self::Failure => 1,
+            Self::Usage => 2,
+            Self::Config => 3,
+            Self::Permission => 4,
+            Self::Unsupported => 5,
+            Self::DaemonUnreachable => 6,
+            Self::PartialApply => 7,
+        }
+    }
+
+    /// What to try next, where there is something general to say
+    pub const fn hint(self) -> Option<&'static str> {
+        match self {
+            Self::Failure => None,
+            Self::Usage => Some("Run the command with --help to see what it accepts"),
+            Self::Config => Some("Fix the configuration file, which is named in the lines above"),
+            Self::Permission => Some("Run the command as root, e.g. with sudo"),
+            Self::Unsupported => Some("Run `watt capabilities` to see what this machine supports"),
+            Self::DaemonUnreachable => {
+                Some("Start the daemon, e.g. with `systemctl start watt` or `sudo watt daemon`")
+            }
+            Self::PartialApply => Some("The settings that failed are listed above"),
+        }
+    }
+}
+
+impl ControlError {
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Self::Io(e) => io_error_kind(e),
+            Self::PermissionDenied(_) => ErrorKind::Permission,
+            Self::NotSupported(_) | Self::PathMissing(_) => ErrorKind::Unsupported,
+            Self::InvalidValueError(_) | Self::InvalidProfile(_) | Self::InvalidGovernor(_) => {
+                ErrorKind::Usage
+            }
+            Self::WriteError(_) | Self::ReadError(_) | Self::ParseError(_) => ErrorKind::Failure,
+            Self::Context { source, .. } => source.kind(),
+        }
+    }
+
+    /// Adds to where the error happened. What is known already stays, as
+    /// it comes from closer to the failure.
+    #[must_use]
+    pub fn with_context(self, context: ErrorContext) -> Self {
+        match self {
+            Self::Context {
+                context: mut known,
+                source,
+            } => {
+                known.fill_from(context);
+                Self::Context {
+                    context: known,
+                    source,
+                }
+            }
+            error => Self::Context {
+                context: Box::new(context),
+                source: Box::new(error),
+            },
+        }
+    }
+
+    /// Names the knob being set
+    #[must_use]
+    pub fn with_knob(self, knob: &str) -> Self {
+        self.with_context(ErrorContext {
+            knob: Some(knob.to_string()),
+            ..ErrorContext::default()
+        })
+    }
+
+    /// Names the CPU being set
+    #[must_use]
+    pub fn with_cpu(self, cpu: u32) -> Self {
+        self.with_context(ErrorContext {
+            cpu: Some(cpu),
+            ..ErrorContext::default()
+        })
+    }
+
+    /// Where the error happened, if that is known
+    pub fn context(&self) -> Option<&ErrorContext> {
+        match self {
+            Self::Context { context, .. } => Some(context),
+            _ => None,
+        }
+    }
+
+    /// The error without its context, for matching on what went wrong
+    pub fn root(&self) -> &Self {
+        match self {
+            Self::Context { source, .. } => source.root(),
+            error => error,
+        }
+    }
+}
+
+impl EngineError {
+    /// Where the control error happened, if that is known
+    pub fn context(&self) -> Option<&ErrorContext> {
+        match self {
+            Self::ControlError(e) => e.context(),
+            Self::ConfigurationError(_) => None,
+        }
+    }
+}
+
+impl AppError {
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Self::Control(e) | Self::Engine(EngineError::ControlError(e)) => e.kind(),
+            Self::Monitor(SysMonitorError::Io(e)) | Self::Io(e) => io_error_kind(e),
+            Self::Config(_) | Self::Engine(EngineError::ConfigurationError(_)) => ErrorKind::Config,
+            Self::Usage(_) => ErrorKind::Usage,
+            Self::Permission(_) => ErrorKind::Permission,
+            Self::DaemonUnreachable(_) => ErrorKind::DaemonUnreachable,
+            Self::PartialApply(_) => ErrorKind::PartialApply,
+            Self::Monitor(_) | Self::PidFile(_) | Self::Generic(_) => ErrorKind::Failure,
+        }
+    }
+
+    /// Wraps an error from talking to the daemon over its control socket,
+    /// telling a daemon that is not there from one that refused the request
+    pub fn daemon(context: &str, error: &io::Error) -> Self {
+        let message = format!("{context}: {error}");
+        match error.kind() {
+            io::ErrorKind::NotFound
+            | io::ErrorKind::ConnectionRefused
+            | io::ErrorKind::TimedOut
+            | io::ErrorKind::WouldBlock
+            | io::ErrorKind::UnexpectedEof
+            | io::ErrorKind::BrokenPipe => Self::DaemonUnreachable(message),
+            io::ErrorKind::PermissionDenied => Self::Permission(message),
+            io::ErrorKind::InvalidInput => Self::Usage(message),
+            _ => Self::Generic(message),
+        }
+    }
+}
+
+fn io_error_kind(error: &io::Error) -> ErrorKind {
+    match error.kind() {
+        io::ErrorKind::PermissionDenied => ErrorKind::Permission,
+        _ => ErrorKind::Failure,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::ErrorReport;
+    use crate::config::ConfigError;
+
+    fn exit_code(error: impl Into<AppError>) -> i32 {
+        error.into().kind().exit_code()
+    }
+
+    #[test]
+    fn control_errors_exit_by_kind() {
+        assert_eq!(exit_code(ControlError::PermissionDenied("no".into())), 4);
+        assert_eq!(exit_code(ControlError::NotSupported("no".into())), 5);
+        assert_eq!(exit_code(ControlError::PathMissing("no".into())), 5);
+        assert_eq!(exit_code(ControlError::WriteError("no".into())), 1);
+        // Context and the engine leave the kind alone
+        let error = ControlError::PermissionDenied("no".into()).with_knob("governor");
+        assert_eq!(exit_code(EngineError::ControlError(error)), 4);
+    }
+
+    #[test]
+    fn unreachable_daemon_exits_6() {
+        for kind in [io::ErrorKind::ConnectionRefused, io::ErrorKind::NotFound] {
+            let error = AppError::daemon("Cannot reach the daemon", &io::Error::from(kind));
+            assert_eq!(exit_code(error), 6);
+        }
+        let denied = io::Error::from(io::ErrorKind::PermissionDenied);
+        assert_eq!(exit_code(AppError::daemon("Denied", &denied)), 4);
+    }
+
+    #[test]
+    fn config_and_partial_apply_exit_codes() {
+        assert_eq!(exit_code(ConfigError::Validation("bad".into())), 3);
+        assert_eq!(exit_code(EngineError::ConfigurationError("bad".into())), 3);
+        assert_eq!(exit_code(AppError::PartialApply("half".into())), 7);
+        assert_eq!(exit_code(AppError::Usage("what".into())), 2);
+        assert_eq!(exit_code(AppError::Generic("oops".into())), 1);
+    }
+
+    #[test]
+    fn json_error_format() {
+        let error = AppError::from(ControlError::NotSupported("no EPP".into()));
+        assert_eq!(
+            serde_json::to_string(&ErrorReport::new(&error)).unwrap(),
+            r#"{"code":5,"kind":"unsupported","message":"Control action not supported: no EPP","hint":"Run `watt capabilities` to see what this machine supports"}"#
+        );
+
+        let error = AppError::Generic("oops".into());
+        assert_eq!(
+            serde_json::to_string(&ErrorReport::new(&error)).unwrap(),
+            r#"{"code":1,"kind":"failure","message":"oops","hint":null}"#
+        );
+    }
 }
@@ -0,0 +1,313 @@
+use crate::config::DaemonConfig;
+use crate::signals;
+use env_logger::fmt::Formatter;
+use log::kv::{self, VisitSource};
+use log::{Log, Metadata, Record};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::sync::{OnceLock, PoisonError, RwLock, RwLockReadGuard};
+use std::time::{Duration, Instant};
+
+/// Collects a record's key-value pairs into a JSON object
+struct JsonFields<'a>(&'a mut Map<String, Value>);
+
+impl<'kvs> VisitSource<'kvs> for JsonFields<'_> {
+    fn visit_pair(&mut self, key: kv::Key<'kvs>, value: kv::Value<'kvs>) -> Result<(), kv::Error> {
+        let value = serde_json::to_value(value).unwrap_or_else(|e| Value::String(e.to_string()));
+        self.0.insert(key.as_str().to_string(), value);
+        Ok(())
+    }
+}
+
+/// Writes a record as a single-line JSON object
+///
+/// The standard keys are `timestamp`, `level`, `module` and `message`. Any
+/// structured fields attached to the record, e.g.
+/// `info!(event = "turbo_decision", load = usage; "...")`, are added as
+/// top-level keys alongside them.
+pub fn format_json(buf: &mut Formatter, record: &Record<'_>) -> io::Result<()> {
+    let mut object = Map::new();
+    object.insert(
+        "timestamp".to_string(),
+        Value::String(jiff::Timestamp::now().to_string()),
+    );
+    object.insert(
+        "level".to_string(),
+        Value::String(record.level().to_string()),
+    );
+    object.insert(
+        "module".to_string(),
+        Value::String(record.target().to_string()),
+    );
+    object.insert(
+        "message".to_string(),
+        Value::String(record.args().to_string()),
+    );
+
+    // A failing visitor only means some fields are missing from the line
+    let _ = record.key_values().visit(&mut JsonFields(&mut object));
+
+    writeln!(buf, "{}", Value::Object(object))
+}
+
+/// Log file with size-based rotation, used as the logger's output
+///
+/// The logger hands over one complete record per write and the file is not
+/// buffered, so every line reaches the disk as soon as it is logged. Once the
+/// file would grow past `max_size`, it is renamed to `<path>.1`, older files
+/// are shifted up and anything beyond `keep` rotated files is dropped.
+///
+/// Logging must never take the daemon down, so when the file cannot be opened
+/// or written, e.g. because the disk is full, lines go to stderr instead and a
+/// single warning is printed until the file works again.
+pub struct LogFile {
+    path: PathBuf,
+    max_size: u64,
+    keep: u32,
+    file: Option<File>,
+    size: u64,
+    /// Whether the fallback to stderr has been announced
+    warned: bool,
+}
+
+impl LogFile {
+    pub fn new(path: &Path, max_size: u64, keep: u32) -> Self {
+        let mut log_file = Self {
+            path: path.to_path_buf(),
+            max_size,
+            keep,
+            file: None,
+            size: 0,
+            warned: false,
+        };
+        if let Err(e) = log_file.open() {
+            log_file.warn_fallback(&e);
+        }
+        log_file
+    }
+
+    fn open(&mut self) -> io::Result<()> {
+        self.file = None;
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        self.size = file.metadata()?.len();
+        self.file = Some(file);
+        Ok(())
+    }
+
+    /// Path of the `index`th rotated file
+    fn rotated_path(&self, index: u32) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(format!(".{index}"));
+        PathBuf::from(path)
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file = None;
+
+        let result = if self.keep == 0 {
+            fs::remove_file(&self.path)
+        } else {
+            // The oldest file is overwritten by the rename below
+            for index in (1..self.keep).rev() {
+                let from = self.rotated_path(index);
+                if from.exists() {
+                    fs::rename(&from, self.rotated_path(index + 1))?;
+                }
+            }
+            fs::rename(&self.path, self.rotated_path(1))
+        };
+
+        // Someone else may have moved the file away already
+        match result {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => self.open(),
+        }
+    }
+
+    fn write_to_file(&mut self, buf: &[u8]) -> io::Result<()> {
+        if signals::take_log_reopen() {
+            // logrotate has moved the file away, start a new one at the path
+            self.open()?;
+        }
+
+        if self.size > 0 && self.size + buf.len() as u64 > self.max_size {
+            self.rotate()?;
+        }
+
+        if self.file.is_none() {
+            self.open()?;
+        }
+        let file = self.file.as_mut().ok_or(io::ErrorKind::NotFound)?;
+        file.write_all(buf)?;
+        self.size += buf.len() as u64;
+        Ok(())
+    }
+
+    fn warn_fallback(&mut self, error: &io::Error) {
+        if !self.warned {
+            // The logger cannot log about itself, so say it directly
+            eprintln!(
+                "Failed to write log file {}, logging to stderr instead: {error}",
+                self.path.display()
+            );
+            self.warned = true;
+        }
+    }
+}
+
+impl Write for LogFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self.write_to_file(buf) {
+            Ok(()) => self.warned = false,
+            Err(e) => {
+                self.warn_fallback(&e);
+                let _ = io::stderr().write_all(buf);
+            }
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Hands records to an `env_logger` logger that can be replaced
+///
+/// `log` only takes one logger for the whole run, yet the format and output
+/// come from the configuration, which has to be read with logging already
+/// working. Logging starts on stderr and moves over with [`install`] once the
+/// configuration is loaded.
+struct Relay(RwLock<env_logger::Logger>);
+
+impl Log for Relay {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        self.logger().enabled(metadata)
+    }
+
+    fn log(&self, record: &Record<'_>) {
+        self.logger().log(record);
+    }
+
+    fn flush(&self) {
+        self.logger().flush();
+    }
+}
+
+impl Relay {
+    fn logger(&self) -> RwLockReadGuard<'_, env_logger::Logger> {
+        self.0.read().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+static RELAY: OnceLock<Relay> = OnceLock::new();
+
+/// Makes `logger` the one every record goes to, replacing any installed before
+///
+/// The overall level is left alone, it is up to the caller.
+pub fn install(logger: env_logger::Logger) {
+    let mut logger = Some(logger);
+    let relay = RELAY.get_or_init(|| Relay(RwLock::new(logger.take().expect("not taken yet"))));
+    match logger {
+        Some(logger) => *relay.0.write().unwrap_or_else(PoisonError::into_inner) = logger,
+        // Only fails if some other logger was set, which nothing in Watt does
+        None => {
+            let _ = log::set_logger(relay);
+        }
+    }
+}
+
+/// What to do with an error reported to [`RepeatedErrors`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Repeat {
+    /// Log it as usual
+    Log,
+    /// Log it together with how often it was suppressed since it was last
+    /// logged
+    Summary { suppressed: u64, over: Duration },
+    /// Leave it out of the log
+    Suppress,
+}
+
+/// How often one error message has been seen
+struct ErrorSeen {
+    count: u64,
+    /// Occurrences not logged since `last_logged`
+    suppressed: u64,
+    last_logged: Instant,
+    /// Time from `last_logged` to the next summary, doubled after each one
+    summary_interval: Duration,
+}
+
+/// Keeps an error that repeats every cycle from flooding the log
+///
+/// The first `repeat_limit` occurrences of a message are logged. After that it
+/// is only logged as a summary, first after `summary_interval` and then at
+/// doubling intervals up to `max_summary_interval`. A message not seen before
+/// is always logged right away.
+pub struct RepeatedErrors {
+    repeat_limit: u32,
+    summary_interval: Duration,
+    max_summary_interval: Duration,
+    seen: HashMap<String, ErrorSeen>,
+}
+
+impl RepeatedErrors {
+    pub fn new(config: &DaemonConfig) -> Self {
+        Self {
+            repeat_limit: config.error_repeat_limit,
+            summary_interval: Duration::from_secs(config.error_summary_interval_sec),
+            max_summary_interval: Duration::from_secs(config.error_summary_max_interval_sec),
+            seen: HashMap::new(),
+        }
+    }
+
+    /// Records an occurrence of `message` and decides whether to log it
+    pub fn record(&mut self, message: &str) -> Repeat {
+        let now = Instant::now();
+        let Some(seen) = self.seen.get_mut(message) else {
+            self.seen.insert(
+                message.to_string(),
+                ErrorSeen {
+                    count: 1,
+                    suppressed: 0,
+                    last_logged: now,
+                    summary_interval: self.summary_interval,
+                },
+            );
+            return Repeat::Log;
+        };
+
+        seen.count += 1;
+        if seen.count <= u64::from(self.repeat_limit) {
+            seen.last_logged = now;
+            return Repeat::Log;
+        }
+
+        let over = now.duration_since(seen.last_logged);
+        if over < seen.summary_interval {
+            seen.suppressed += 1;
+            return Repeat::Suppress;
+        }
+
+        let suppressed = mem::take(&mut seen.suppressed);
+        seen.last_logged = now;
+        seen.summary_interval = (seen.summary_interval * 2).min(self.max_summary_interval);
+        Repeat::Summary { suppressed, over }
+    }
+
+    /// Forgets every error, e.g. once things work again
+    pub fn clear(&mut self) {
+        self.seen.clear();
+    }
+}
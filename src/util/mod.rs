@@ -1,2 +1,4 @@
 pub mod error;
+pub mod logging;
+pub mod panics;
 pub mod sysfs;
@@ -0,0 +1,57 @@
+// Catching panics in daemon cycles, so one bad cycle does not take the daemon
+// down and leave settings half-applied
+use std::any::Any;
+use std::backtrace::Backtrace;
+use std::cell::{Cell, RefCell};
+use std::panic::{self, AssertUnwindSafe};
+
+thread_local! {
+    /// Set while running code under [`catch`]
+    static CATCHING: Cell<bool> = const { Cell::new(false) };
+    /// Location and backtrace of the panic [`catch`] is about to return
+    static CAUGHT: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// Installs a panic hook that keeps the location and a backtrace of panics
+/// under [`catch`] for it to return, instead of printing them. Any other panic
+/// goes to the previous hook as usual.
+pub fn install_hook() {
+    let previous = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        if !CATCHING.with(Cell::get) {
+            previous(info);
+            return;
+        }
+        let location = info
+            .location()
+            .map_or_else(|| "unknown location".to_string(), ToString::to_string);
+        let details = format!("at {location}\n{}", Backtrace::force_capture());
+        CAUGHT.with(|caught| *caught.borrow_mut() = Some(details));
+    }));
+}
+
+/// Runs `f`, turning a panic into an error with its message, location and
+/// backtrace
+///
+/// Shared state is only ever behind locks that shrug off poisoning, so
+/// carrying on after a panic is fine.
+pub fn catch<R>(f: impl FnOnce() -> R) -> Result<R, String> {
+    CATCHING.with(|catching| catching.set(true));
+    let result = panic::catch_unwind(AssertUnwindSafe(f));
+    CATCHING.with(|catching| catching.set(false));
+
+    result.map_err(|payload| {
+        let details = CAUGHT
+            .with(|caught| caught.borrow_mut().take())
+            .unwrap_or_default();
+        format!("{} {details}", payload_message(&*payload))
+    })
+}
+
+fn payload_message(payload: &(dyn Any + Send)) -> &str {
+    payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("unknown panic")
+}
@@ -1,5 +1,166 @@
-use crate::util::error::ControlError;
-use std::{fs, io, path::Path};
+use crate::util::error::{ControlError, ErrorContext};
+use log::debug;
+use serde::{Deserialize, Serialize};
+use std::cell::{Cell, RefCell};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, PoisonError};
+use std::{fs, io};
+
+/// Number of successful sysfs writes since startup
+static WRITE_COUNT: AtomicU64 = AtomicU64::new(0);
+
+/// Number of writes skipped because the value was already in place
+static SKIPPED_WRITE_COUNT: AtomicU64 = AtomicU64::new(0);
+
+/// Failed writes since startup, by attribute name
+static WRITE_ERRORS: Mutex<BTreeMap<String, u64>> = Mutex::new(BTreeMap::new());
+
+/// Outcomes of writes since recording started, if it has
+static RECORDED_WRITES: Mutex<Option<Vec<WriteRecord>>> = Mutex::new(None);
+
+thread_local! {
+    /// Writes made on this thread inside [`capture_writes`]
+    static CAPTURED_WRITES: RefCell<Option<Vec<WriteRecord>>> = const { RefCell::new(None) };
+
+    /// Whether writes on this thread are inside [`pretend`]
+    static PRETENDING: Cell<bool> = const { Cell::new(false) };
+}
+
+/// What happened to a single write
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WriteOutcome {
+    Written,
+    /// The file already held the value
+    Unchanged,
+    /// Dry-run mode, the value would have been written
+    WouldWrite,
+    Failed(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WriteRecord {
+    pub path: PathBuf,
+    /// What the file held before, if it could be read
+    pub old_value: Option<String>,
+    pub value: String,
+    pub outcome: WriteOutcome,
+}
+
+/// Runs `f` with the writes it makes on this thread only logged and recorded
+/// as [`WriteOutcome::WouldWrite`], for dry runs. Reads still see the real
+/// files, so decisions are made on the real system state.
+pub fn pretend<T>(f: impl FnOnce() -> T) -> T {
+    let pretending = PRETENDING.replace(true);
+    let result = f();
+    PRETENDING.set(pretending);
+    result
+}
+
+/// Starts keeping a record of every write, for summaries of one-off runs
+pub fn record_writes() {
+    *RECORDED_WRITES
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner) = Some(Vec::new());
+}
+
+/// Returns the writes recorded since [`record_writes`] or the last call
+pub fn take_recorded_writes() -> Vec<WriteRecord> {
+    RECORDED_WRITES
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .as_mut()
+        .map(std::mem::take)
+        .unwrap_or_default()
+}
+
+/// Returns the writes recorded since [`record_writes`] or the last call to
+/// [`take_recorded_writes`], and stops recording
+pub fn stop_recording_writes() -> Vec<WriteRecord> {
+    RECORDED_WRITES
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .take()
+        .unwrap_or_default()
+}
+
+/// Whether writes are being recorded
+pub fn is_recording_writes() -> bool {
+    RECORDED_WRITES
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .is_some()
+}
+
+/// Runs `f` and returns the writes it made on this thread, whether or not
+/// writes are being recorded, e.g. to tell what applying one knob changed.
+/// Captures do not nest.
+pub fn capture_writes<T>(f: impl FnOnce() -> T) -> (T, Vec<WriteRecord>) {
+    CAPTURED_WRITES.with_borrow_mut(|captured| *captured = Some(Vec::new()));
+    let result = f();
+    let writes = CAPTURED_WRITES.with_borrow_mut(Option::take);
+    (result, writes.unwrap_or_default())
+}
+
+fn record(path: &Path, old_value: Option<&str>, value: &str, outcome: WriteOutcome) {
+    let capturing = CAPTURED_WRITES.with_borrow(Option::is_some);
+    let mut recorded = RECORDED_WRITES
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner);
+    if recorded.is_none() && !capturing {
+        return;
+    }
+
+    let record = WriteRecord {
+        path: path.to_path_buf(),
+        old_value: old_value.map(ToString::to_string),
+        value: value.trim().to_string(),
+        outcome,
+    };
+    if capturing {
+        CAPTURED_WRITES.with_borrow_mut(|captured| {
+            if let Some(captured) = captured {
+                captured.push(record.clone());
+            }
+        });
+    }
+    if let Some(records) = recorded.as_mut() {
+        records.push(record);
+    }
+}
+
+/// Number of successful sysfs writes since startup
+pub fn write_count() -> u64 {
+    WRITE_COUNT.load(Ordering::Relaxed)
+}
+
+/// Number of writes skipped as unchanged since startup
+pub fn skipped_write_count() -> u64 {
+    SKIPPED_WRITE_COUNT.load(Ordering::Relaxed)
+}
+
+/// Failed writes since startup, keyed by attribute name such as
+/// `scaling_governor`
+pub fn write_errors() -> BTreeMap<String, u64> {
+    WRITE_ERRORS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone()
+}
+
+fn record_write_error(path: &Path) {
+    let knob = path.file_name().map_or_else(
+        || path.display().to_string(),
+        |name| name.to_string_lossy().into_owned(),
+    );
+    *WRITE_ERRORS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .entry(knob)
+        .or_default() += 1;
+}
 
 /// Write a value to a sysfs file with consistent error handling
 ///
@@ -14,19 +175,54 @@ use std::{fs, io, path::Path};
 /// - `ControlError::PermissionDenied` if permission is denied
 /// - `ControlError::PathMissing` if the path doesn't exist
 /// - `ControlError::WriteError` for other I/O errors
+///
+/// each wrapped with the path, the value and what the file held.
+///
+/// Nothing is written if the file already holds `value`.
 pub fn write_sysfs_value(path: impl AsRef<Path>, value: &str) -> Result<(), ControlError> {
     let p = path.as_ref();
 
+    // Writing a value that is already in place achieves nothing, and some
+    // attributes are slow to write or make the driver log every write
+    let current = read_sysfs_value(p).ok();
+    if current.as_deref() == Some(value.trim()) {
+        SKIPPED_WRITE_COUNT.fetch_add(1, Ordering::Relaxed);
+        record(p, current.as_deref(), value, WriteOutcome::Unchanged);
+        return Ok(());
+    }
+
+    if PRETENDING.get() {
+        debug!("Dry run: would write '{}' to {}", value.trim(), p.display());
+        record(p, current.as_deref(), value, WriteOutcome::WouldWrite);
+        return Ok(());
+    }
+
     fs::write(p, value).map_err(|e| {
-        let error_msg = format!("Path: {:?}, Value: '{}', Error: {}", p.display(), value, e);
-        match e.kind() {
-            io::ErrorKind::PermissionDenied => ControlError::PermissionDenied(error_msg),
-            io::ErrorKind::NotFound => {
-                ControlError::PathMissing(format!("Path '{}' does not exist", p.display()))
+        record_write_error(p);
+        record(
+            p,
+            current.as_deref(),
+            value,
+            WriteOutcome::Failed(e.to_string()),
+        );
+        let error = match e.kind() {
+            io::ErrorKind::PermissionDenied => {
+                ControlError::PermissionDenied("cannot write the file".to_string())
             }
-            _ => ControlError::WriteError(error_msg),
-        }
-    })
+            io::ErrorKind::NotFound => ControlError::PathMissing("does not exist".to_string()),
+            _ => ControlError::WriteError(e.to_string()),
+        };
+        error.with_context(ErrorContext {
+            path: Some(p.to_path_buf()),
+            value: Some(value.trim().to_string()),
+            current: current.clone(),
+            ..ErrorContext::default()
+        })
+    })?;
+
+    WRITE_COUNT.fetch_add(1, Ordering::Relaxed);
+    record(p, current.as_deref(), value, WriteOutcome::Written);
+    Ok(())
 }
 
 /// Read a value from a sysfs file with consistent error handling
@@ -45,18 +241,23 @@ pub fn write_sysfs_value(path: impl AsRef<Path>, value: &str) -> Result<(), Cont
 /// - `ControlError::PermissionDenied` if permission is denied
 /// - `ControlError::PathMissing` if the path doesn't exist
 /// - `ControlError::ReadError` for other I/O errors
+///
+/// each wrapped with the path.
 pub fn read_sysfs_value(path: impl AsRef<Path>) -> Result<String, ControlError> {
     let p = path.as_ref();
     fs::read_to_string(p)
         .map_err(|e| {
-            let error_msg = format!("Path: {:?}, Error: {}", p.display(), e);
-            match e.kind() {
-                io::ErrorKind::PermissionDenied => ControlError::PermissionDenied(error_msg),
-                io::ErrorKind::NotFound => {
-                    ControlError::PathMissing(format!("Path '{}' does not exist", p.display()))
+            let error = match e.kind() {
+                io::ErrorKind::PermissionDenied => {
+                    ControlError::PermissionDenied("cannot read the file".to_string())
                 }
-                _ => ControlError::ReadError(error_msg),
-            }
+                io::ErrorKind::NotFound => ControlError::PathMissing("does not exist".to_string()),
+                _ => ControlError::ReadError(e.to_string()),
+            };
+            error.with_context(ErrorContext {
+                path: Some(p.to_path_buf()),
+                ..ErrorContext::default()
+            })
         })
         .map(|s| s.trim().to_string())
 }
@@ -0,0 +1,36 @@
+// Output piped into a reader that stops early, as `| head -1` does
+use std::os::unix::process::ExitStatusExt;
+use std::process::{Command, Stdio};
+
+/// Runs watt with `args` with its stdout already closed on the reading end,
+/// and checks that it ended quietly: done, or killed by SIGPIPE like any
+/// other tool in a pipeline, but not panicking
+fn run_into_closed_pipe(args: &[&str]) {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_watt"))
+        .args(args)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    drop(child.stdout.take());
+    let output = child.wait_with_output().unwrap();
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!stderr.contains("panicked"), "{args:?}: {stderr}");
+    assert!(
+        output.status.success() || output.status.signal() == Some(libc::SIGPIPE),
+        "{args:?} ended with {}: {stderr}",
+        output.status
+    );
+}
+
+#[test]
+fn info_ends_quietly_when_the_reader_stops() {
+    run_into_closed_pipe(&["info", "--plain"]);
+    run_into_closed_pipe(&["info", "--json"]);
+}
+
+#[test]
+fn completions_end_quietly_when_the_reader_stops() {
+    run_into_closed_pipe(&["completions", "zsh"]);
+}